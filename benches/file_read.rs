@@ -0,0 +1,86 @@
+//! Throughput of the default file read path against `--io-uring`.
+//!
+//! ```bash
+//! cargo bench --features io-uring --bench file_read
+//! ```
+//!
+//! Reads a 32 MiB file with 1, 8 and 32 concurrent readers on a single-threaded runtime
+//! (like one Actix worker) and prints MiB/s for each path.
+
+use futures::StreamExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use webserve::UringReader;
+
+const FILE_SIZE: usize = 32 * 1024 * 1024;
+const ROUNDS: usize = 5;
+
+async fn read_default(path: &Path) -> usize {
+    let mut file = tokio::fs::File::open(path).await.unwrap();
+    let mut total = 0;
+    loop {
+        let mut buf = bytes::BytesMut::with_capacity(64 * 1024);
+        match file.read_buf(&mut buf).await.unwrap() {
+            0 => return total,
+            n => total += n,
+        }
+    }
+}
+
+async fn read_uring(reader: &UringReader, path: &Path) -> usize {
+    let mut stream = std::pin::pin!(reader.stream(path));
+    let mut total = 0;
+    while let Some(chunk) = stream.next().await {
+        total += chunk.unwrap().len();
+    }
+    total
+}
+
+fn report(name: &str, readers: usize, elapsed: Duration) {
+    let mib = (FILE_SIZE * readers * ROUNDS) as f64 / (1024.0 * 1024.0);
+    println!(
+        "{:<8} readers={:<3} {:>8.1} MiB/s",
+        name,
+        readers,
+        mib / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("artifact.bin");
+    let body: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, body).unwrap();
+
+    let reader = match UringReader::start() {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("io_uring unavailable: {}", e);
+            return;
+        }
+    };
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        for readers in [1, 8, 32] {
+            let start = Instant::now();
+            for _ in 0..ROUNDS {
+                let reads = (0..readers).map(|_| read_default(&path));
+                let sizes = futures::future::join_all(reads).await;
+                assert!(sizes.iter().all(|&n| n == FILE_SIZE));
+            }
+            report("default", readers, start.elapsed());
+
+            let start = Instant::now();
+            for _ in 0..ROUNDS {
+                let reads = (0..readers).map(|_| read_uring(&reader, &path));
+                let sizes = futures::future::join_all(reads).await;
+                assert!(sizes.iter().all(|&n| n == FILE_SIZE));
+            }
+            report("io_uring", readers, start.elapsed());
+        }
+    });
+}
@@ -0,0 +1,137 @@
+//! The `webserve` command line: [`ServeOptions`] flags plus the subcommands run instead of
+//! the server.
+//!
+//! Parsing happens here; [`resolve_layers`] in `webserve-core` then fills every flag not
+//! given from the config file and the `WEBSERVE_*` environment.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use structopt::StructOpt;
+use webserve_core::{resolve_layers, ServeOptions};
+
+use crate::tools::{CheckLinksOptions, CleanOptions, DiffOptions};
+
+/// Everything given on the command line.
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(
+    name = "webserve",
+    about = "A simple static file server with live reload."
+)]
+pub struct Cli {
+    #[structopt(flatten)]
+    pub options: ServeOptions,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Tools run instead of the server.
+#[derive(StructOpt, Debug, Clone)]
+pub enum Command {
+    /// Report broken internal links of a directory or running site (exit 1 if any, 2 on error)
+    #[structopt(name = "check-links")]
+    CheckLinks(CheckLinksOptions),
+    /// Compare two build outputs: added, removed and changed files (exit 1 if any differ)
+    #[structopt(name = "diff")]
+    Diff(DiffOptions),
+    /// Delete generated caches under --cache-dir, or trim them with --max-size
+    #[structopt(name = "clean")]
+    Clean(CleanOptions),
+    /// Stop a server started with --daemon, found through its --pid-file
+    #[structopt(name = "stop")]
+    Stop(StopOptions),
+    /// Install, remove or run webserve as a Windows service
+    #[structopt(name = "service")]
+    Service(ServiceAction),
+}
+
+/// Options of the `stop` subcommand.
+#[derive(StructOpt, Debug, Clone)]
+pub struct StopOptions {
+    /// PID file of the server to stop (default: --pid-file, else webserve.pid)
+    #[structopt(long = "pid-file", parse(from_os_str))]
+    pub pid_file: Option<PathBuf>,
+
+    /// Seconds to wait for the server to exit; open requests get up to 30 to finish
+    #[structopt(long = "timeout", default_value = "35")]
+    pub timeout: u64,
+}
+
+/// Actions of the `service` subcommand (Windows only).
+#[derive(StructOpt, Debug, Clone, PartialEq, Eq)]
+pub enum ServiceAction {
+    /// Register a service running with this --config (from an elevated prompt)
+    #[structopt(name = "install")]
+    Install {
+        /// Service name, as used by `sc` and `net start`
+        #[structopt(long = "name", default_value = "webserve")]
+        name: String,
+
+        /// Name shown in the Services console (default: the service name)
+        #[structopt(long = "display-name")]
+        display_name: Option<String>,
+
+        /// Start only on demand instead of at boot
+        #[structopt(long = "manual")]
+        manual: bool,
+    },
+    /// Stop and remove an installed service
+    #[structopt(name = "uninstall")]
+    Uninstall {
+        /// Service name given at install
+        #[structopt(long = "name", default_value = "webserve")]
+        name: String,
+    },
+    /// Entry point started by the service manager; not meant to be run by hand
+    #[structopt(name = "run")]
+    Run {
+        /// Service name given at install
+        #[structopt(long = "name", default_value = "webserve")]
+        name: String,
+    },
+}
+
+/// Parses CLI args and layers the config file and process environment underneath them.
+pub fn resolve_cli<I>(args: I) -> Result<Cli, String>
+where
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+{
+    resolve_cli_with_env(args, std::env::vars())
+}
+
+/// [`resolve_cli`] with an explicit environment (for tests and embedding).
+pub fn resolve_cli_with_env<I, E>(args: I, env: E) -> Result<Cli, String>
+where
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+    E: IntoIterator<Item = (String, String)>,
+{
+    let matches = Cli::clap().get_matches_from(args);
+    let mut cli = Cli::from_clap(&matches);
+    resolve_layers(
+        &mut cli.options,
+        |name| matches.occurrences_of(name) > 0,
+        env,
+    )?;
+    Ok(cli)
+}
+
+/// [`resolve_cli`] without the subcommand.
+pub fn resolve_options<I>(args: I) -> Result<ServeOptions, String>
+where
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+{
+    resolve_cli(args).map(|cli| cli.options)
+}
+
+/// [`resolve_cli_with_env`] without the subcommand.
+pub fn resolve_options_with_env<I, E>(args: I, env: E) -> Result<ServeOptions, String>
+where
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+    E: IntoIterator<Item = (String, String)>,
+{
+    resolve_cli_with_env(args, env).map(|cli| cli.options)
+}
@@ -0,0 +1,220 @@
+//! `--daemon` and `webserve stop`: a background server without a service unit.
+//!
+//! With `--daemon` the process forks before the runtime starts. The child leaves the
+//! terminal's session, writes its PID to `--pid-file` and sends stdout and stderr to
+//! `--daemon-log` (discarded without one); the parent waits until the child is listening,
+//! prints its PID and exits, or exits 1 if the child dies first. `--log-file` stays the
+//! access log alone, so startup messages and warnings never land between its lines unless
+//! both options name the same file. The working directory is kept, so relative paths behave
+//! as in the foreground. `webserve stop` sends `SIGTERM` to the PID in the file and waits for
+//! the server to exit; the server removes the file itself.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use webserve_core::ServeOptions;
+
+use crate::cli::StopOptions;
+
+/// `--pid-file` when none is given, relative to the working directory.
+pub const DEFAULT_PID_FILE: &str = "webserve.pid";
+
+/// The PID file `options` (or `stop`, which takes precedence) points at.
+pub fn pid_file_path(options: &ServeOptions, stop: Option<&StopOptions>) -> PathBuf {
+    stop.and_then(|s| s.pid_file.clone())
+        .or_else(|| options.pid_file.clone())
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_PID_FILE))
+}
+
+/// Reads the PID stored in `path`.
+pub fn read_pid(path: &Path) -> Result<i32, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    contents
+        .trim()
+        .parse()
+        .ok()
+        .filter(|pid| *pid > 0)
+        .ok_or_else(|| format!("{}: not a PID: {:?}", path.display(), contents.trim()))
+}
+
+/// Whether a process with `pid` exists (possibly owned by another user).
+#[cfg(unix)]
+pub fn is_running(pid: i32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists and may be signalled.
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+pub fn is_running(_pid: i32) -> bool {
+    false
+}
+
+/// The daemonized server's side of `--daemon`: reports readiness to the waiting parent and
+/// removes the PID file when dropped.
+pub struct Daemon {
+    pid_file: PathBuf,
+    #[cfg(unix)]
+    ready: Option<std::os::unix::net::UnixStream>,
+}
+
+impl Daemon {
+    /// Tells the parent the server is listening, so it can exit.
+    pub fn ready(&mut self) {
+        #[cfg(unix)]
+        if let Some(mut parent) = self.ready.take() {
+            let _ = std::io::Write::write_all(&mut parent, b"1");
+        }
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        // Only our own PID: a newer instance may have taken the file over.
+        if read_pid(&self.pid_file).ok() == Some(std::process::id() as i32) {
+            let _ = fs::remove_file(&self.pid_file);
+        }
+    }
+}
+
+/// `--daemon`: forks into the background. Returns in the child only; the parent exits once
+/// the child reports [`Daemon::ready`] or dies.
+#[cfg(unix)]
+pub fn daemonize(options: &ServeOptions) -> Result<Daemon, String> {
+    use std::fs::{File, OpenOptions};
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+
+    let pid_file = pid_file_path(options, None);
+    if let Ok(pid) = read_pid(&pid_file) {
+        if is_running(pid) {
+            return Err(format!(
+                "already running (PID {} in {}); stop it with `webserve stop`",
+                pid,
+                pid_file.display()
+            ));
+        }
+    }
+    let log = match options.daemon_log {
+        Some(ref path) => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("--daemon-log {}: {}", path.display(), e))?,
+        None => OpenOptions::new()
+            .write(true)
+            .open("/dev/null")
+            .map_err(|e| format!("/dev/null: {}", e))?,
+    };
+    let null = File::open("/dev/null").map_err(|e| format!("/dev/null: {}", e))?;
+    let (mut waiting, ready) =
+        UnixStream::pair().map_err(|e| format!("--daemon: cannot create pipe: {}", e))?;
+
+    // SAFETY: no other threads exist yet; the runtime is only started after this returns.
+    match unsafe { libc::fork() } {
+        -1 => Err(format!(
+            "--daemon: fork failed: {}",
+            std::io::Error::last_os_error()
+        )),
+        0 => {
+            drop(waiting);
+            // SAFETY: plain syscalls on descriptors this process owns.
+            unsafe {
+                libc::setsid();
+                libc::dup2(null.as_raw_fd(), 0);
+                libc::dup2(log.as_raw_fd(), 1);
+                libc::dup2(log.as_raw_fd(), 2);
+            }
+            let daemon = Daemon {
+                pid_file,
+                ready: Some(ready),
+            };
+            fs::write(&daemon.pid_file, format!("{}\n", std::process::id()))
+                .map_err(|e| format!("--pid-file {}: {}", daemon.pid_file.display(), e))?;
+            Ok(daemon)
+        }
+        pid => {
+            drop(ready);
+            let mut status = Vec::new();
+            let _ = waiting.read_to_end(&mut status);
+            let output = match options.daemon_log {
+                Some(ref path) => format!("output in {}", path.display()),
+                None => "output discarded (use --daemon-log to keep it)".to_string(),
+            };
+            if status.is_empty() {
+                eprintln!("webserve failed to start in the background; {}", output);
+                std::process::exit(1);
+            }
+            println!(
+                "webserve running in the background (PID {}); {}",
+                pid, output
+            );
+            let stop = match options.pid_file {
+                Some(ref path) => format!("webserve stop --pid-file {}", path.display()),
+                None => "webserve stop".to_string(),
+            };
+            println!("Stop it with `{}`", stop);
+            std::process::exit(0);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_options: &ServeOptions) -> Result<Daemon, String> {
+    Err("--daemon is only supported on Unix".to_string())
+}
+
+/// `webserve stop`: terminates the server in the PID file; returns the exit code.
+pub fn run_stop(options: &ServeOptions, stop: &StopOptions) -> i32 {
+    let pid_file = pid_file_path(options, Some(stop));
+    match stop_pid_file(&pid_file, stop.timeout) {
+        Ok(pid) => {
+            println!("Stopped webserve (PID {})", pid);
+            0
+        }
+        Err(e) => {
+            eprintln!("stop: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(unix)]
+fn stop_pid_file(pid_file: &Path, timeout: u64) -> Result<i32, String> {
+    use std::time::{Duration, Instant};
+
+    let pid = read_pid(pid_file)?;
+    if !is_running(pid) {
+        let _ = fs::remove_file(pid_file);
+        return Err(format!(
+            "PID {} from {} is not running; removed the stale file",
+            pid,
+            pid_file.display()
+        ));
+    }
+    // SAFETY: sends a signal; no memory is shared with the target.
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        return Err(format!(
+            "cannot signal PID {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        ));
+    }
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    while is_running(pid) {
+        if Instant::now() >= deadline {
+            return Err(format!("PID {} still running after {}s", pid, timeout));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    if read_pid(pid_file).ok() == Some(pid) {
+        let _ = fs::remove_file(pid_file);
+    }
+    Ok(pid)
+}
+
+#[cfg(not(unix))]
+fn stop_pid_file(_pid_file: &Path, _timeout: u64) -> Result<i32, String> {
+    Err("only supported on Unix".to_string())
+}
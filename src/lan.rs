@@ -0,0 +1,63 @@
+//! LAN URLs and the `--qr` code printed at startup.
+//!
+//! When bound to a wildcard address (`0.0.0.0` or `::`) the server is reachable from other
+//! devices on the network, so startup lists a URL per local interface address. `--qr`
+//! renders the first of them as a terminal QR code for opening on a phone.
+
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use std::net::IpAddr;
+use webserve_core::browser_url;
+
+/// True for hosts that accept connections on every interface.
+pub fn is_wildcard_host(host: &str) -> bool {
+    matches!(host, "0.0.0.0" | "::" | "[::]" | "")
+}
+
+/// Addresses other devices can use: IPv4 for `0.0.0.0`, IPv4 and IPv6 for `::`.
+///
+/// Loopback and link-local addresses are left out; link-local IPv6 needs a zone id that
+/// browsers don't accept in URLs.
+pub fn lan_addresses(host: &str, interfaces: &[IpAddr]) -> Vec<IpAddr> {
+    if !is_wildcard_host(host) {
+        return Vec::new();
+    }
+    let ipv6 = host.contains(':');
+    let mut addrs: Vec<IpAddr> = interfaces
+        .iter()
+        .copied()
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+        .filter(|ip| match ip {
+            IpAddr::V4(v4) => !v4.is_link_local(),
+            IpAddr::V6(v6) => ipv6 && (v6.segments()[0] & 0xffc0) != 0xfe80,
+        })
+        .collect();
+    addrs.sort_by_key(|ip| (ip.is_ipv6(), *ip));
+    addrs.dedup();
+    addrs
+}
+
+/// Addresses of the machine's network interfaces; empty when they can't be listed.
+pub fn interface_addresses() -> Vec<IpAddr> {
+    if_addrs::get_if_addrs()
+        .map(|list| list.iter().map(|i| i.ip()).collect())
+        .unwrap_or_default()
+}
+
+/// `http://<addr>:<port>/` for each LAN address of `host`.
+pub fn network_urls(host: &str, port: u16, interfaces: &[IpAddr]) -> Vec<String> {
+    lan_addresses(host, interfaces)
+        .iter()
+        .map(|ip| browser_url(&ip.to_string(), port, "/"))
+        .collect()
+}
+
+/// `text` as a QR code drawn with half-block characters (two modules per line).
+pub fn qr_code(text: &str) -> Result<String, String> {
+    let code = QrCode::new(text.as_bytes()).map_err(|e| format!("cannot encode QR code: {}", e))?;
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
@@ -7,19 +7,134 @@
 //! ## Features
 //! - Serves static files from a directory
 //! - Single Page Application (SPA) mode (fallback to `index.html`)
-//! - Directory listing if no `index.html` is found
-//! - Optional file watcher for live reloads via polling
-//! - Customizable host and port
+//! - Nearest-ancestor `index.html` fallback for sectioned sites (`--section-index`)
+//! - "Did you mean" suggestions for mistyped or mis-cased paths on 404s (`--suggest`)
+//! - Single-file mode serving one file at `/` (`--file`)
+//! - `HEAD` and `OPTIONS` (with `Allow`) on every served path
+//! - Directory listing if no `index.html` is found, translated and with localized sizes and
+//!   dates (`--listing-lang`, or `auto` from `Accept-Language`)
+//! - Markdown files and directory READMEs rendered as HTML (`--render-markdown`, `?raw=1`)
+//! - Syntax-highlighted source view with line numbers (`--highlight`, `?view=1`)
+//! - File uploads via `PUT` or a form on directory listings, with `DELETE` and `MKCOL` for
+//!   removing entries and creating folders (`--upload`, `--max-upload-size`)
+//! - WebDAV for mounting the served directory read/write (`--webdav`)
+//! - Optional file watcher for live reloads via polling, falling back to a polling watcher
+//!   when OS watch limits (inotify) are exhausted; editor temp files are ignored and atomic
+//!   saves coalesce into a single reload; `--exec` runs a build command first and reloads
+//!   only when it succeeds; `--watch-path` adds source directories outside the served root
+//! - A per-response CSP nonce on the injected scripts when a `Content-Security-Policy`
+//!   header or meta tag would block them
+//! - HTTPS with HTTP/2 negotiated via ALPN (`--tls-cert`, `--tls-key`; `--http1-only`), plus
+//!   an optional plain HTTP listener that redirects to it (`--redirect-http`); negotiated
+//!   TLS versions, ciphers and ALPN protocols are logged and counted on the dashboard
+//! - Unix domain socket listener instead of TCP (`--uds`)
+//! - systemd socket activation and readiness notification (`--systemd`)
+//! - Background mode with a PID file and `webserve stop` (`--daemon`, Unix)
+//! - Windows service install, removal and SCM start/stop (`webserve service`)
+//! - Concurrent requests for the same file share one disk read
+//! - Experimental io_uring file reads on Linux (`io-uring` feature, `--io-uring`)
+//! - Customizable host and port; several `--host` addresses, IPv4 or IPv6, bound at once
+//! - Worker, connection and timeout tuning for load tests (`--workers`, `--max-connections`,
+//!   `--keep-alive`, `--client-timeout`)
+//! - Redirect and rewrite rules (`--redirect`, `--rewrite`, `--rules`)
+//! - Hardening mode for internet-facing use: conflicting `Content-Length` /
+//!   `Transfer-Encoding`, oversized headers and absolute-form targets get `400` (`--harden`)
+//! - Slow-client protection: request head deadlines on every connection and a minimum
+//!   request body rate (`--slowloris-guard`, `--header-timeout`, `--min-rate`)
+//! - Request body size and read-time limits, and a method allowlist that grows with the
+//!   enabled modes (`--max-request-size`, `--read-timeout`, `--allow-method`)
+//! - Client IP allowlist / denylist by CIDR block, answered with 403 (`--allow`, `--deny`)
+//! - YAML fixtures with canned responses for front-end test suites (`--fixtures`)
+//! - Selectable ETag strategy: file metadata, SHA-256 content hash or none (`--etag`)
+//! - Content-Type overrides and additions by extension (`--mime glb=model/gltf-binary`)
+//! - Explicit charset on text responses, per extension if needed (`--charset`, `--charset-ext`)
+//! - Cache-busting development mode (`--no-cache`)
+//! - gzip / Brotli compression that eases off as CPU usage rises (`--compress`), with an
+//!   optional size-capped on-disk cache of compressed files (`--compress-cache`)
+//! - Generated caches kept under one capped `.webserve-cache` root (`--cache-dir`,
+//!   `--cache-size`), emptied with `webserve clean`
+//! - Slow-network simulation for file responses (`--throttle <kbps>`, `--latency <ms>`)
+//! - Per-request delay/status fault injection via `X-Webserve-Fault` (`--fault-injection`)
+//! - GraphQL mock endpoint answering from per-operation JSON files (`--graphql-mocks`)
+//! - json-server style REST API from one JSON file (`--mock`), optionally written back
+//!   (`--mock-persist`)
+//! - Several sites in one process, under a path prefix or for a host name, each with its own
+//!   watcher and reload channel (`--mount`, `--vhost`)
+//! - path-prefix forwarding to another server (`--proxy`), limited to `--proxy-allow`
+//!   targets and kept off private addresses with `--proxy-deny-private`
+//! - Diagnostic logging via `tracing` (`--log-level`, `-v` / `-vv`, `RUST_LOG`)
+//! - Status dashboard at `/__webserve/` (options, reload clients, recent requests and changes),
+//!   like the other `/__webserve/` pages for localhost only unless `--admin-remote`
+//! - Broken-links report of `404`s and their referers (`/__webserve/broken-links`)
+//! - `webserve check-links [dir|url]` reporting broken internal links with a CI exit status
+//! - `webserve diff <before> <after>` listing added, removed and changed files of two build
+//!   outputs, optionally as a served HTML report (`--serve`)
+//! - Custom logo, title and footer on listings, the dashboard and error pages (`--brand-*`)
+//! - Pluggable key-value store for persisted state such as download counts (`--store`:
+//!   memory, or sled / SQLite behind the `sled` / `sqlite` features)
+//! - LAN URLs listed when bound to `0.0.0.0` / `::`, with an optional terminal QR code (`--qr`)
+//! - mDNS / Bonjour announcement as `<name>.local` (`--mdns`)
+//! - `/__webserve/changes?since=<ms>` with files added, modified and removed under `--watch`
+//! - `/__webserve/manifest` listing served files with sizes and SHA-256 hashes
+//! - `/__webserve/search?q=` finding files by path or content, behind the listing's search box
+//! - `/__webserve/events` Server-Sent Events stream of requests, file changes, reloads and
+//!   errors, behind a bearer token (`--events-token`)
+//! - `/__webserve/open-in-editor?file=&line=` opening files in `$EDITOR` / VS Code for
+//!   localhost requests, linked from an injected error overlay (`--open-in-editor`)
+//! - `--max-depth` / `--max-entries` limits for tree walks (watcher) and listings
+//! - Access logging in common, combined or JSON format (`--log`, `--log-file`), with noisy
+//!   paths excluded or sampled (`--log-exclude`, `--log-sample`)
+//! - Optional `webserve.toml` config file, overridden by CLI flags; `--export-config` writes the
+//!   merged options out as one
+//! - Embeddable as a library (`Webserve::builder()`), with `on_request`, `on_response` and
+//!   `resolve_path` hooks; the `webserve-core` crate carries it without the CLI's TLS,
+//!   socket and discovery dependencies, with subsystems behind Cargo features
+//! - Sandboxed WebAssembly request handlers (`wasm-plugins` feature, `--plugin`), reloaded
+//!   with `--watch`
 //!
 //! ## Example
 //! ```bash
 //! webserve --dir ./public --port 3000 --watch --spa
 //! ```
+//!
+//! ## Crates
+//! This crate is the CLI: argument parsing and the subcommands, the listeners (TLS, Unix
+//! sockets, systemd), daemon and Windows service modes, LAN / mDNS discovery and the log
+//! subscriber. Everything else
+//! lives in `webserve-core` and is re-exported here, so `webserve::Webserve` and
+//! `webserve_core::Webserve` are the same type.
 
-pub mod path;
-pub mod serve;
-pub mod types;
+pub mod cli;
+pub mod daemon;
+pub mod lan;
+pub mod logging;
+pub mod mdns;
+pub mod service;
+pub mod systemd;
+pub mod tls;
+pub mod tools;
+pub mod uds;
 
-pub use path::{join_serve_path, normalize_url_path, validate_static_root};
-pub use serve::{directory_listing, reload_poll, serve_file};
-pub use types::{AppState, DirEntry, ServeOptions, StaticDirError};
+pub use cli::{
+    resolve_cli, resolve_cli_with_env, resolve_options, resolve_options_with_env, Cli, Command,
+    ServiceAction, StopOptions,
+};
+pub use daemon::{
+    daemonize, is_running, pid_file_path, read_pid, run_stop, Daemon, DEFAULT_PID_FILE,
+};
+pub use lan::{interface_addresses, is_wildcard_host, lan_addresses, network_urls, qr_code};
+pub use logging::{init_logging, log_filter};
+pub use mdns::{mdns_addresses, mdns_label, mdns_url, MdnsAnnouncement, MDNS_SERVICE_TYPE};
+#[cfg(windows)]
+pub use service::service_started;
+pub use service::{run_service_command, service_config, service_launch_arguments, Serve};
+pub use systemd::{activated_listeners, notify, notify_socket, ActivatedListener};
+pub use tls::{
+    http1_server, https_location, redirect_https, server_config, tls_info, track_tls, HttpsPort,
+    OnConnect, Tls,
+};
+pub use tools::{
+    run_check_links, run_clean, run_diff, CheckLinksOptions, CleanOptions, DiffOptions,
+};
+pub use uds::{prepare_socket_path, uds_addr};
+pub use webserve_core::*;
@@ -0,0 +1,32 @@
+//! Diagnostic logging through `tracing`.
+//!
+//! Verbosity comes from `--log-level <level>`, else `-v` (debug) / `-vv` (trace), else
+//! `RUST_LOG` when set, else `info`. The level applies to webserve itself; other crates
+//! only log warnings and errors unless `RUST_LOG` says otherwise.
+
+use std::io::IsTerminal;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
+use webserve_core::{log_level, ServeOptions};
+
+/// Filter for the subscriber; `rust_log` is only consulted when no flag set a level.
+pub fn log_filter(options: &ServeOptions, rust_log: Option<&str>) -> EnvFilter {
+    let directives = match (log_level(options), rust_log) {
+        (Some(level), _) => format!("warn,webserve={}", level),
+        (None, Some(spec)) if !spec.trim().is_empty() => spec.to_string(),
+        (None, _) => "warn,webserve=info".to_string(),
+    };
+    EnvFilter::try_new(&directives).unwrap_or_else(|_| EnvFilter::new("warn,webserve=info"))
+}
+
+/// Installs the global subscriber; a second call (e.g. from tests) is a no-op.
+pub fn init_logging(options: &ServeOptions) {
+    let rust_log = std::env::var("RUST_LOG").ok();
+    let filter = log_filter(options, rust_log.as_deref());
+    let detailed = log_level(options).is_some_and(|l| l >= LevelFilter::DEBUG);
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(detailed)
+        .with_ansi(std::io::stdout().is_terminal())
+        .try_init();
+}
@@ -2,163 +2,506 @@
 
 use actix_web::{web, App, HttpServer};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
-use std::collections::HashMap;
 use std::io;
-use std::path::Path;
-use std::sync::atomic::AtomicBool;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::RwLock;
 use std::thread;
 use std::time::Duration;
-use structopt::StructOpt;
-use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
 use webserve::{
-    reload_poll, serve_file, validate_static_root, AppState, ServeOptions, StaticDirError,
+    activated_listeners, bind_host, browser_url, daemonize, export_config, host_port, http1_server,
+    init_logging, interface_addresses, listen_error, mdns_addresses, mdns_label, network_urls,
+    notify, prepare_socket_path, qr_code, redirect_https, resolve_cli, resolve_options,
+    run_check_links, run_clean, run_diff, run_service_command, run_stop, static_root,
+    track_connection, track_tls, uds_addr, unknown_env_vars, validate_static_root,
+    ActivatedListener, AppState, Cli, Command, Daemon, HttpsPort, MdnsAnnouncement, OnConnect,
+    ServeOptions, ServerTuning, Settings, SlowlorisGuard, StaticDirError, Tls, Webserve,
+    ADMIN_PREFIX,
 };
 
-fn log_info(msg: &str) {
-    println!("[INFO] {}", msg);
-}
+/// Ports tried (the requested one included) before giving up, unless `--strict-port`.
+const PORT_FALLBACK_ATTEMPTS: u16 = 20;
 
-/// Exit when `--dir` (or the resolved root) is invalid.
-fn fail_static_dir(path: &Path, err: StaticDirError) -> ! {
+/// Error reported when `--dir` (or the resolved root) is invalid.
+fn static_dir_error(path: &Path, err: StaticDirError) -> String {
     match err {
-        StaticDirError::NotFound => eprintln!("{} not found", path.display()),
-        StaticDirError::NotADirectory => eprintln!("{} is not a directory", path.display()),
+        StaticDirError::NotFound => format!("{} not found", path.display()),
+        StaticDirError::NotADirectory => format!("{} is not a directory", path.display()),
     }
-    std::process::exit(1);
 }
 
-fn listen_error(addr: &str, e: &io::Error) -> String {
-    match e.kind() {
-        io::ErrorKind::AddrInUse => format!("{} already in use", addr),
-        io::ErrorKind::PermissionDenied => format!("permission denied binding to {}", addr),
-        io::ErrorKind::AddrNotAvailable => format!("address not available: {}", addr),
-        io::ErrorKind::InvalidInput => format!("invalid listen address {}", addr),
-        _ => format!("cannot listen on {}: {}", addr, e),
+/// Reports `state` to systemd under `--systemd`; failures only warn.
+fn sd_notify(state: &str) {
+    match notify(state) {
+        Ok(true) => debug!("systemd: {}", state),
+        Ok(false) => debug!("systemd: NOTIFY_SOCKET unset, {} not sent", state),
+        Err(e) => warn!("--systemd: cannot send {}: {}", state, e),
     }
 }
 
-#[actix_web::main]
-async fn main() {
-    if let Err(msg) = run().await {
+/// With `--config` + `--watch`: re-resolve options when the config file changes and swap
+/// in the per-request settings of every site. Bind address, roots and watch mode still
+/// need a restart.
+fn watch_config(
+    config_path: &Path,
+    initial: ServeOptions,
+    sites: Vec<AppState>,
+) -> Result<RecommendedWatcher, String> {
+    let config_path = config_path.to_path_buf();
+    let config_name = config_path.file_name().map(|n| n.to_os_string());
+    let watch_dir = match config_path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let args: Vec<_> = std::env::args_os().collect();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        if !event.kind.is_modify() && !event.kind.is_create() {
+            return;
+        }
+        if !event
+            .paths
+            .iter()
+            .any(|p| p.file_name().map(|n| n.to_os_string()) == config_name)
+        {
+            return;
+        }
+        debug!(kind = ?event.kind, "config file event");
+        let reloaded = resolve_options(args.clone())
+            .and_then(|options| Settings::from_options(&options).map(|s| (options, s)));
+        match reloaded {
+            Ok((options, new_settings)) => {
+                for site in &sites {
+                    site.reload_settings(new_settings.clone());
+                }
+                info!("Config reloaded: {}", config_path.display());
+                if options.port != initial.port
+                    || options.hosts != initial.hosts
+                    || options.uds != initial.uds
+                    || options.systemd != initial.systemd
+                    || options.tls_cert != initial.tls_cert
+                    || options.tls_key != initial.tls_key
+                    || options.http1_only != initial.http1_only
+                    || options.directory != initial.directory
+                    || options.mounts != initial.mounts
+                    || options.vhosts != initial.vhosts
+                    || options.watch != initial.watch
+                    || options.exec != initial.exec
+                    || options.watch_paths != initial.watch_paths
+                {
+                    warn!("port, host, uds, systemd, TLS, dir, mount, vhost, watch and exec changes take effect after a restart");
+                }
+            }
+            Err(e) => warn!("config reload failed, keeping previous settings: {}", e),
+        }
+    })
+    .map_err(|e| format!("file watch unavailable: {}", e))?;
+    watcher
+        .watch(&watch_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("cannot watch {}: {}", watch_dir.display(), e))?;
+    Ok(watcher)
+}
+
+fn main() {
+    if let Err(msg) = start() {
         eprintln!("{}", msg);
         std::process::exit(1);
     }
 }
 
-async fn run() -> Result<(), String> {
-    let options = ServeOptions::from_args();
-    let static_dir = Arc::new(if let Some(ref p) = options.directory {
-        p.clone()
-    } else {
-        std::env::current_dir().map_err(|e| format!("working directory unavailable: {}", e))?
-    });
-
-    if let Err(e) = validate_static_root(&static_dir) {
-        fail_static_dir(&static_dir, e);
+/// Resolves options and, with `--daemon`, forks before the runtime starts any threads.
+fn start() -> Result<(), String> {
+    let Cli { options, command } = resolve_cli(std::env::args_os())?;
+    // The service manager's dispatcher owns this thread; the server runs on its own.
+    if let Some(Command::Service(ref action)) = command {
+        std::process::exit(run_service_command(&options, action, serve));
     }
+    let daemon = match options.daemon && command.is_none() {
+        true if options.export_config.is_none() => Some(daemonize(&options)?),
+        _ => None,
+    };
+    actix_web::rt::System::new().block_on(run(options, command, daemon))
+}
 
-    let (tx, _rx) = broadcast::channel::<()>(16);
-    let reload_pending = Arc::new(AtomicBool::new(false));
-    let html_cache = options.watch.then(|| Arc::new(RwLock::new(HashMap::new())));
+/// Runs the server in the foreground, for `service run`.
+fn serve(options: ServeOptions) -> Result<(), String> {
+    actix_web::rt::System::new().block_on(run(options, None, None))
+}
 
-    log_info("Starting webserve");
-    log_info(&format!("Directory: {}", static_dir.display()));
-    log_info(&format!("Host: {}", options.host));
-    log_info(&format!("Port: {}", options.port));
-    if options.spa {
-        log_info("SPA mode: enabled");
+async fn run(
+    options: ServeOptions,
+    command: Option<Command>,
+    mut daemon: Option<Daemon>,
+) -> Result<(), String> {
+    init_logging(&options);
+    for name in unknown_env_vars(std::env::vars()) {
+        warn!("{}: unknown variable, ignored", name);
     }
-    if options.watch {
-        log_info("Watch: enabled");
+    match command {
+        Some(Command::CheckLinks(ref check)) => {
+            std::process::exit(run_check_links(&options, check).await)
+        }
+        Some(Command::Diff(ref diff)) => std::process::exit(run_diff(&options, diff).await),
+        Some(Command::Clean(ref clean)) => std::process::exit(run_clean(&options, clean)),
+        Some(Command::Stop(ref stop)) => std::process::exit(run_stop(&options, stop)),
+        Some(Command::Service(_)) | None => {}
     }
-    if options.open {
-        log_info("Open browser: enabled");
+    if let Some(ref path) = options.export_config {
+        export_config(&options, path)?;
+        println!("Wrote {}", path.display());
+        return Ok(());
     }
-    if options.no_redirect_dir_slash {
-        log_info("Directory slash redirect: disabled");
+    let static_dir = static_root(&options)?;
+    if options.file.is_none() {
+        validate_static_root(&static_dir).map_err(|e| static_dir_error(&static_dir, e))?;
     }
+    let mdns_name = options.mdns.as_deref().map(mdns_label).transpose()?;
 
+    let webserve = Webserve::from_options(options.clone())?;
+    let state = webserve.state().clone();
+    let stats = state.stats.clone();
+    // A token given by flag, config or environment is not echoed to the log
+    let shown_token = match options.events_token {
+        Some(_) => "<events-token>".to_string(),
+        None => state.events.token().to_string(),
+    };
     if options.watch {
-        let watch_path = static_dir.clone();
-        let tx_watcher = tx.clone();
-        let reload_flag = reload_pending.clone();
-        let cache_to_clear = html_cache.clone().expect("watch implies html_cache");
-        let mut watcher: RecommendedWatcher =
-            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
-                if res.is_ok() {
-                    reload_flag.store(true, std::sync::atomic::Ordering::SeqCst);
-                    let _ = tx_watcher.send(());
-                    if let Ok(mut guard) = cache_to_clear.write() {
-                        guard.clear();
-                    }
+        if let Some(ref config_path) = options.config {
+            let sites = std::iter::once(&webserve)
+                .chain(webserve.sites())
+                .map(|site| site.state().clone())
+                .collect();
+            let config_watcher = watch_config(config_path, options.clone(), sites)?;
+            thread::spawn(move || {
+                let _keep_alive = config_watcher;
+                loop {
+                    thread::sleep(Duration::from_secs(60));
                 }
-            })
-            .map_err(|e| format!("file watch unavailable: {}", e))?;
-        watcher
-            .watch(&watch_path, RecursiveMode::Recursive)
-            .map_err(|e| format!("cannot watch {}: {}", watch_path.display(), e))?;
-        thread::spawn(move || {
-            let _keep_alive = watcher;
-            loop {
-                thread::sleep(Duration::from_secs(60));
-            }
-        });
-        log_info(&format!("Watching directory: {}", watch_path.display()));
+            });
+            info!("Watching config: {}", config_path.display());
+        }
+    }
+
+    let mut activated = Vec::new();
+    if options.systemd {
+        activated = activated_listeners().map_err(|e| format!("--systemd: {}", e))?;
+        if activated.is_empty() {
+            info!("--systemd: no LISTEN_FDS passed; binding as usual");
+        }
     }
+    let socket_activated = !activated.is_empty();
+    let tls = Tls::from_options(&options)?;
+    let tuning = ServerTuning::from_options(&options)?;
+    let slowloris = SlowlorisGuard::from_options(&options)?;
+    if let Some(guard) = slowloris {
+        info!("Slowloris guard: {}", guard.describe());
+    }
+    if !tuning.is_default() {
+        info!("Server tuning: {}", tuning.describe());
+    }
+    let tuning = ServerTuning {
+        client_timeout: tuning
+            .client_timeout
+            .or(slowloris.map(|g| g.header_timeout)),
+        ..tuning
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let secure = |url: String| match tls {
+        Some(_) => url.replacen("http://", "https://", 1),
+        None => url,
+    };
 
     let mut port = options.port;
-    let (server, bound_addr, actual_port) = loop {
-        let addr = format!("{}:{}", options.host, port);
-        let app_state = web::Data::new(AppState {
-            static_dir: static_dir.clone(),
-            watch: options.watch,
-            spa: options.spa,
-            addr: addr.clone(),
-            tx: tx.clone(),
-            redirect_dir_slash: !options.no_redirect_dir_slash,
-            reload_pending: reload_pending.clone(),
-            html_cache: html_cache.clone(),
+    let (server, bound_addrs, actual_port, http1, main_listens) = loop {
+        let addrs: Vec<String> = match options.uds {
+            _ if socket_activated => activated.iter().map(ActivatedListener::describe).collect(),
+            Some(ref path) => vec![uds_addr(path)],
+            None => options.hosts.iter().map(|h| host_port(h, port)).collect(),
+        };
+        let site = webserve.clone().with_addr(addrs.join(", "));
+        let app = move || App::new().service(site.clone().into_actix_service());
+        let mut server = HttpServer::new(app.clone());
+        if let Some(n) = tuning.workers {
+            server = server.workers(n);
+        }
+        if let Some(n) = tuning.max_connections {
+            server = server.max_connections(n);
+        }
+        if let Some(d) = tuning.keep_alive {
+            server = server.keep_alive(d);
+        }
+        if let Some(d) = tuning.client_timeout {
+            server = server.client_request_timeout(d);
+        }
+        let guarded = slowloris.is_some();
+        let conn_stats = stats.clone();
+        let on_connect: OnConnect = Arc::new(move |io, extensions| {
+            if guarded {
+                track_connection(io, extensions);
+            }
+            track_tls(io, extensions, &conn_stats);
         });
-        match HttpServer::new(move || {
-            App::new()
-                .app_data(app_state.clone())
-                .route("/reload", web::get().to(reload_poll))
-                .route("/{_:.*}", web::get().to(serve_file))
-        })
-        .bind(&addr)
-        {
-            Ok(s) => break (s, addr, port),
-            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+        if guarded || tls.is_some() {
+            let on_connect = on_connect.clone();
+            server = server.on_connect(move |io, extensions| on_connect(io, extensions));
+        }
+        // With --http1-only, TLS listeners go to a separate HTTP/1-only server.
+        let mut http1_listeners = Vec::new();
+        let mut listen_tcp = |s: HttpServer<_, _, _, _>, l: std::net::TcpListener| match tls {
+            None => s.listen(l),
+            Some(ref tls) if !tls.http1_only => s.listen_rustls_0_23(l, tls.config.clone()),
+            Some(_) => {
+                http1_listeners.push(l);
+                Ok(s)
+            }
+        };
+        // Whether any listener is left for the main server
+        let main_listens = match tls {
+            Some(ref tls) if tls.http1_only => {
+                options.uds.is_some()
+                    || activated
+                        .iter()
+                        .any(|l| !matches!(l, ActivatedListener::Tcp(_)))
+            }
+            _ => true,
+        };
+        let bound = match options.uds {
+            _ if socket_activated => std::mem::take(&mut activated)
+                .into_iter()
+                .zip(&addrs)
+                .try_fold(server, |s, (listener, addr)| {
+                    match listener {
+                        ActivatedListener::Tcp(l) => listen_tcp(s, l),
+                        #[cfg(unix)]
+                        ActivatedListener::Unix(l) => s.listen_uds(l),
+                    }
+                    .map_err(|e| (addr, e))
+                }),
+            Some(ref path) => {
+                #[cfg(unix)]
+                let bound = prepare_socket_path(path).and_then(|()| server.bind_uds(path));
+                #[cfg(not(unix))]
+                let bound = prepare_socket_path(path).map(|()| server);
+                bound.map_err(|e| (&addrs[0], e))
+            }
+            None => options
+                .hosts
+                .iter()
+                .zip(&addrs)
+                .try_fold(server, |s, (host, addr)| {
+                    match tls {
+                        None => s.bind((bind_host(host), port)),
+                        Some(_) => std::net::TcpListener::bind((bind_host(host), port))
+                            .and_then(|l| listen_tcp(s, l)),
+                    }
+                    .map_err(|e| (addr, e))
+                }),
+        };
+        match bound {
+            Ok(s) => {
+                let http1 = match tls {
+                    Some(ref tls) if !http1_listeners.is_empty() => Some(
+                        http1_server(http1_listeners, tls.config.clone(), app, tuning, on_connect)
+                            .map_err(|e| format!("--http1-only: {}", e))?,
+                    ),
+                    _ => None,
+                };
+                break (s, addrs, port, http1, main_listens);
+            }
+            Err((addr, e))
+                if e.kind() == io::ErrorKind::AddrInUse
+                    && options.uds.is_none()
+                    && !socket_activated =>
+            {
+                if options.strict_port {
+                    return Err(listen_error(addr, &e));
+                }
                 let next = port.wrapping_add(1);
-                if next == 0 {
-                    return Err("no available port".into());
+                let tried = port.wrapping_sub(options.port) + 1;
+                if next == 0 || tried >= PORT_FALLBACK_ATTEMPTS {
+                    return Err(format!(
+                        "ports {}-{} are all in use (use --port to pick another range)",
+                        options.port, port
+                    ));
                 }
-                log_info(&format!("Port {} in use, trying {}...", port, next));
+                info!("Port {} in use, trying {}...", port, next);
                 port = next;
             }
-            Err(e) => return Err(listen_error(&addr, &e)),
+            Err((addr, e)) => return Err(listen_error(addr, &e)),
         }
     };
 
-    log_info(&format!("Serving on http://{}", bound_addr));
-
-    if options.open {
-        let open_host = if options.host == "0.0.0.0" {
-            "127.0.0.1"
-        } else {
-            options.host.as_str()
-        };
-        let url = format!("http://{}:{}/", open_host, actual_port);
-        log_info(&format!("Opening browser: {}", url));
-        let _ = open::that(&url);
+    if let Some(ref tls) = tls {
+        match tls.http1_only {
+            true => info!("TLS: HTTP/1.1 only"),
+            false => info!("TLS: HTTP/2 and HTTP/1.1 via ALPN"),
+        }
     }
 
-    server
-        .run()
-        .await
-        .map_err(|e| format!("server error: {}", e))?;
+    let tcp_only = [
+        ("--qr", options.qr),
+        ("--mdns", mdns_name.is_some()),
+        ("--open", options.open.is_some()),
+        ("--redirect-http", options.redirect_http.is_some()),
+    ];
+    let announcement = match options.uds {
+        _ if socket_activated => {
+            for addr in &bound_addrs {
+                match addr.starts_with("unix:") {
+                    true => info!("Serving on {} (socket-activated)", addr),
+                    false => info!("Serving on {}://{} (socket-activated)", scheme, addr),
+                }
+            }
+            for (flag, _) in tcp_only.iter().filter(|(_, on)| *on) {
+                warn!(
+                    "{} needs --host/--port; ignored with socket activation",
+                    flag
+                );
+            }
+            None
+        }
+        Some(ref path) => {
+            info!("Serving on unix:{}", path.display());
+            info!(
+                "Status dashboard: curl --unix-socket {} http://localhost{}/",
+                path.display(),
+                ADMIN_PREFIX
+            );
+            info!(
+                "Event stream: curl -N --unix-socket {} -H 'Authorization: Bearer {}' http://localhost{}/events",
+                path.display(),
+                shown_token,
+                ADMIN_PREFIX
+            );
+            for (flag, _) in tcp_only.iter().filter(|(_, on)| *on) {
+                warn!("{} needs a TCP listener; ignored with --uds", flag);
+            }
+            None
+        }
+        None => {
+            for addr in &bound_addrs {
+                info!("Serving on {}://{}", scheme, addr);
+            }
+            let interfaces = interface_addresses();
+            let mut lan_urls: Vec<String> = options
+                .hosts
+                .iter()
+                .flat_map(|h| network_urls(h, actual_port, &interfaces))
+                .map(secure)
+                .collect();
+            lan_urls.dedup();
+            for url in &lan_urls {
+                info!("Network: {}", url);
+            }
+            info!(
+                "Status dashboard: {}://{}{}/",
+                scheme, bound_addrs[0], ADMIN_PREFIX
+            );
+            info!(
+                "Event stream: {}://{}{}/events?token={}",
+                scheme, bound_addrs[0], ADMIN_PREFIX, shown_token
+            );
+            let host = &options.hosts[0];
+
+            if let Some(http_port) = options.redirect_http {
+                let redirect = HttpServer::new(move || {
+                    App::new()
+                        .app_data(web::Data::new(HttpsPort(actual_port)))
+                        .default_service(web::to(redirect_https))
+                })
+                .workers(1);
+                let redirect = options
+                    .hosts
+                    .iter()
+                    .try_fold(redirect, |s, host| {
+                        s.bind((bind_host(host), http_port))
+                            .map_err(|e| listen_error(&host_port(host, http_port), &e))
+                    })?
+                    .run();
+                actix_web::rt::spawn(redirect);
+                info!("Redirecting http://{} to HTTPS", host_port(host, http_port));
+            }
+
+            if options.qr {
+                let url = match lan_urls.first() {
+                    Some(url) => url.clone(),
+                    None => {
+                        let loopback = host == "localhost"
+                            || bind_host(host)
+                                .parse::<std::net::IpAddr>()
+                                .is_ok_and(|ip| ip.is_loopback());
+                        if loopback {
+                            warn!("--qr: bound to loopback; use --host 0.0.0.0 to reach it from other devices");
+                        }
+                        secure(browser_url(host, actual_port, "/"))
+                    }
+                };
+                match qr_code(&url) {
+                    Ok(code) => println!("\n{}\n{}\n", code, url),
+                    Err(e) => warn!("{}", e),
+                }
+            }
+
+            let announcement = match mdns_name {
+                Some(ref label) => match mdns_addresses(&options.hosts, &interfaces) {
+                    Some(addrs) => match MdnsAnnouncement::register(label, actual_port, &addrs) {
+                        Ok(a) => {
+                            info!("mDNS: {}", secure(a.url().to_string()));
+                            Some(a)
+                        }
+                        Err(e) => {
+                            warn!("{}", e);
+                            None
+                        }
+                    },
+                    None => {
+                        warn!("--mdns: bound to loopback; use --host 0.0.0.0 to reach it from other devices");
+                        None
+                    }
+                },
+                None => None,
+            };
+
+            if let Some(path) = options.open_path() {
+                let url = secure(browser_url(host, actual_port, path));
+                info!("Opening browser: {}", url);
+                if let Err(e) = open::that(&url) {
+                    warn!("could not open browser: {}", e);
+                }
+            }
+            announcement
+        }
+    };
+
+    let (server, http1) = match http1 {
+        Some(http1) if !main_listens => (http1, None),
+        http1 => (server.run(), http1),
+    };
+    if options.systemd {
+        sd_notify("READY=1");
+    }
+    if let Some(ref mut daemon) = daemon {
+        daemon.ready();
+    }
+    #[cfg(windows)]
+    webserve::service_started(server.handle());
+    let result = match http1 {
+        Some(http1) => {
+            let handle = http1.handle();
+            actix_web::rt::spawn(http1);
+            let result = server.await;
+            handle.stop(true).await;
+            result
+        }
+        None => server.await,
+    };
+    if options.systemd {
+        sd_notify("STOPPING=1");
+    }
+    if let Some(announcement) = announcement {
+        announcement.shutdown();
+    }
+    result.map_err(|e| format!("server error: {}", e))?;
 
     Ok(())
 }
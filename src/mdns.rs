@@ -0,0 +1,137 @@
+//! `--mdns <name>`: advertise the server as `<name>.local` over mDNS / Bonjour.
+//!
+//! Registers an `_http._tcp` service whose host record points at the LAN addresses the
+//! server is reachable on, so other devices can open `http://<name>.local:<port>/` without
+//! knowing the IP. The record is withdrawn again when the server shuts down.
+
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use std::net::IpAddr;
+use std::time::Duration;
+use webserve_core::bind_host;
+
+use crate::lan::{is_wildcard_host, lan_addresses};
+
+/// DNS-SD service type the server is announced under.
+pub const MDNS_SERVICE_TYPE: &str = "_http._tcp.local.";
+
+/// How long shutdown waits for the goodbye packets to go out.
+const UNREGISTER_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Checks an `--mdns` name and returns it as a single lowercase label.
+///
+/// A trailing `.local` is accepted and dropped; the rest must be 1-63 letters, digits or
+/// hyphens, not starting or ending with a hyphen.
+pub fn mdns_label(name: &str) -> Result<String, String> {
+    let label = name.trim().trim_end_matches('.');
+    let label = label
+        .strip_suffix(".local")
+        .unwrap_or(label)
+        .to_ascii_lowercase();
+    let valid = !label.is_empty()
+        && label.len() <= 63
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if valid {
+        Ok(label)
+    } else {
+        Err(format!(
+            "invalid --mdns name {}: use letters, digits and hyphens (e.g. my-site)",
+            name
+        ))
+    }
+}
+
+/// URL other devices open for an announced `label`.
+pub fn mdns_url(label: &str, port: u16) -> String {
+    format!("http://{}.local:{}/", label, port)
+}
+
+/// Addresses `name.local` should resolve to for a server bound to `hosts`.
+///
+/// Wildcard binds use the LAN addresses and a specific address is announced as is; empty
+/// lets the daemon track interfaces itself (host names, or no LAN address found). `None`
+/// when every host is loopback, which other devices can't reach.
+pub fn mdns_addresses<S: AsRef<str>>(hosts: &[S], interfaces: &[IpAddr]) -> Option<Vec<IpAddr>> {
+    let per_host: Vec<Vec<IpAddr>> = hosts
+        .iter()
+        .filter_map(|host| {
+            let host = host.as_ref();
+            if is_wildcard_host(host) {
+                return Some(lan_addresses(host, interfaces));
+            }
+            match bind_host(host).parse::<IpAddr>() {
+                Ok(ip) if ip.is_loopback() => None,
+                Ok(ip) => Some(vec![ip]),
+                Err(_) if host == "localhost" => None,
+                Err(_) => Some(Vec::new()),
+            }
+        })
+        .collect();
+    if per_host.is_empty() {
+        return None;
+    }
+    if per_host.iter().any(Vec::is_empty) {
+        return Some(Vec::new());
+    }
+    let mut addrs: Vec<IpAddr> = per_host.into_iter().flatten().collect();
+    addrs.sort_by_key(|ip| (ip.is_ipv6(), *ip));
+    addrs.dedup();
+    Some(addrs)
+}
+
+/// A registered mDNS service; call [`MdnsAnnouncement::shutdown`] to withdraw it.
+pub struct MdnsAnnouncement {
+    daemon: ServiceDaemon,
+    fullname: String,
+    url: String,
+}
+
+impl MdnsAnnouncement {
+    /// Announces `label.local` on `port`, resolving to `addrs`.
+    ///
+    /// With no addresses (e.g. a wildcard bind before interfaces are up) the daemon tracks
+    /// the machine's interface addresses itself.
+    pub fn register(label: &str, port: u16, addrs: &[IpAddr]) -> Result<Self, String> {
+        let daemon = ServiceDaemon::new().map_err(|e| format!("mDNS unavailable: {}", e))?;
+        let host_name = format!("{}.local.", label);
+        let info = ServiceInfo::new(
+            MDNS_SERVICE_TYPE,
+            label,
+            &host_name,
+            addrs,
+            port,
+            &[("path", "/")][..],
+        )
+        .map_err(|e| format!("cannot announce {}: {}", host_name, e))?;
+        let info = if addrs.is_empty() {
+            info.enable_addr_auto()
+        } else {
+            info
+        };
+        let fullname = info.get_fullname().to_string();
+        daemon
+            .register(info)
+            .map_err(|e| format!("cannot announce {}: {}", host_name, e))?;
+        Ok(MdnsAnnouncement {
+            daemon,
+            fullname,
+            url: mdns_url(label, port),
+        })
+    }
+
+    /// `http://<name>.local:<port>/`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Sends the goodbye records and stops the responder.
+    pub fn shutdown(self) {
+        if let Ok(status) = self.daemon.unregister(&self.fullname) {
+            let _ = status.recv_timeout(UNREGISTER_TIMEOUT);
+        }
+        if let Ok(status) = self.daemon.shutdown() {
+            let _ = status.recv_timeout(UNREGISTER_TIMEOUT);
+        }
+    }
+}
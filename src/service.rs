@@ -0,0 +1,291 @@
+//! `webserve service`: run as a Windows service under the service control manager.
+//!
+//! `service install` registers this executable with `service run` and the absolute path of
+//! the `--config` file (or `webserve.toml` in the working directory) as its launch
+//! arguments, which Windows keeps in the service's registry entry. Every other option comes
+//! from that file: a service starts in `C:\Windows\System32` with no flags of its own, and
+//! `--export-config` turns a working command line into one. `service run` is what the
+//! manager launches; it reports the service running once the listeners are bound, and
+//! `sc stop`, `net stop` or the Services console shut the server down gracefully. A service
+//! has no console, so set `log-file` in the config to keep an access log.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use webserve_core::ServeOptions;
+
+use crate::cli::ServiceAction;
+
+/// Runs the server in the foreground until it stops.
+pub type Serve = fn(ServeOptions) -> Result<(), String>;
+
+/// Config file a service installed from `options` runs with, as an absolute path.
+pub fn service_config(options: &ServeOptions) -> Result<PathBuf, String> {
+    let Some(ref config) = options.config else {
+        return Err(
+            "service install needs --config <file>; write one with --export-config".to_string(),
+        );
+    };
+    if !config.is_file() {
+        return Err(format!("{} not found", config.display()));
+    }
+    std::path::absolute(config).map_err(|e| format!("{}: {}", config.display(), e))
+}
+
+/// Arguments the service manager starts the executable with.
+pub fn service_launch_arguments(config: &Path, name: &str) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["--config".into(), config.into()];
+    args.extend(["service", "run", "--name", name].map(OsString::from));
+    args
+}
+
+/// `webserve service ...`: returns the exit code; `run` returns once the service stops.
+pub fn run_service_command(options: &ServeOptions, action: &ServiceAction, serve: Serve) -> i32 {
+    match service_command(options, action, serve) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("service: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(windows)]
+fn service_command(
+    options: &ServeOptions,
+    action: &ServiceAction,
+    serve: Serve,
+) -> Result<(), String> {
+    match action {
+        ServiceAction::Install {
+            name,
+            display_name,
+            manual,
+        } => {
+            let config = service_config(options)?;
+            windows::install(
+                name,
+                display_name.as_deref().unwrap_or(name),
+                *manual,
+                &config,
+            )?;
+            println!("Installed service {} with {}", name, config.display());
+            match manual {
+                true => println!("Start it with `sc start {}`", name),
+                false => println!("It starts at boot; start it now with `sc start {}`", name),
+            }
+            Ok(())
+        }
+        ServiceAction::Uninstall { name } => {
+            windows::uninstall(name)?;
+            println!("Removed service {}", name);
+            Ok(())
+        }
+        ServiceAction::Run { name } => windows::run(name, options, serve),
+    }
+}
+
+#[cfg(not(windows))]
+fn service_command(
+    _options: &ServeOptions,
+    _action: &ServiceAction,
+    _serve: Serve,
+) -> Result<(), String> {
+    Err("Windows services are only supported on Windows (see --daemon or --systemd)".to_string())
+}
+
+/// Under `service run`: tells the service manager the server is listening and hands it
+/// the server to stop. Does nothing outside a service.
+#[cfg(windows)]
+pub fn service_started(server: actix_web::dev::ServerHandle) {
+    windows::started(server)
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::OsString;
+    use std::path::Path;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::{Duration, Instant};
+
+    use actix_web::dev::ServerHandle;
+    use webserve_core::ServeOptions;
+    use windows_service::service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    };
+    use windows_service::service_control_handler::{
+        self, ServiceControlHandlerResult, ServiceStatusHandle,
+    };
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    use windows_service::{define_windows_service, service_dispatcher};
+
+    use super::{service_launch_arguments, Serve};
+
+    /// Service name, options and server entry for `service_main`, set before dispatching.
+    static ENTRY: OnceLock<(String, ServeOptions, Serve)> = OnceLock::new();
+    /// Status handle of the running service.
+    static STATUS: OnceLock<ServiceStatusHandle> = OnceLock::new();
+    /// The server once bound, and whether a stop arrived before that.
+    static SERVER: Mutex<(Option<ServerHandle>, bool)> = Mutex::new((None, false));
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    /// `Display` of `windows_service::Error` hides the OS error; show it.
+    fn describe(e: windows_service::Error) -> String {
+        match e {
+            windows_service::Error::Winapi(e) if e.raw_os_error() == Some(5) => {
+                format!("{} (run from an elevated prompt)", e)
+            }
+            windows_service::Error::Winapi(e) => e.to_string(),
+            e => e.to_string(),
+        }
+    }
+
+    fn status(state: ServiceState, exit_code: ServiceExitCode) -> ServiceStatus {
+        let pending = matches!(
+            state,
+            ServiceState::StartPending | ServiceState::StopPending
+        );
+        ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: match state {
+                ServiceState::Running => {
+                    ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+                }
+                _ => ServiceControlAccept::empty(),
+            },
+            exit_code,
+            checkpoint: 0,
+            // actix waits up to 30s for open requests on shutdown
+            wait_hint: if pending {
+                Duration::from_secs(35)
+            } else {
+                Duration::default()
+            },
+            process_id: None,
+        }
+    }
+
+    pub fn install(
+        name: &str,
+        display_name: &str,
+        manual: bool,
+        config: &Path,
+    ) -> Result<(), String> {
+        let access = ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE;
+        let manager = ServiceManager::local_computer(None::<&str>, access).map_err(describe)?;
+        let executable_path =
+            std::env::current_exe().map_err(|e| format!("cannot locate webserve.exe: {}", e))?;
+        let info = ServiceInfo {
+            name: OsString::from(name),
+            display_name: OsString::from(display_name),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: match manual {
+                true => ServiceStartType::OnDemand,
+                false => ServiceStartType::AutoStart,
+            },
+            error_control: ServiceErrorControl::Normal,
+            executable_path,
+            launch_arguments: service_launch_arguments(config, name),
+            dependencies: vec![],
+            account_name: None,
+            account_password: None,
+        };
+        let service = manager
+            .create_service(&info, ServiceAccess::CHANGE_CONFIG)
+            .map_err(describe)?;
+        service
+            .set_description(format!("webserve with {}", config.display()))
+            .map_err(describe)
+    }
+
+    pub fn uninstall(name: &str) -> Result<(), String> {
+        let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+            .map_err(describe)?;
+        let access = ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE;
+        let service = manager.open_service(name, access).map_err(describe)?;
+        // Marked for deletion now; removed once stopped and every handle is closed.
+        service.delete().map_err(describe)?;
+        if service.query_status().map_err(describe)?.current_state != ServiceState::Stopped {
+            service.stop().map_err(describe)?;
+            let deadline = Instant::now() + Duration::from_secs(35);
+            while Instant::now() < deadline
+                && service.query_status().map_err(describe)?.current_state != ServiceState::Stopped
+            {
+                std::thread::sleep(Duration::from_millis(250));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn run(name: &str, options: &ServeOptions, serve: Serve) -> Result<(), String> {
+        let mut options = options.clone();
+        options.daemon = false;
+        let _ = ENTRY.set((name.to_string(), options, serve));
+        service_dispatcher::start(name, ffi_service_main).map_err(|e| {
+            format!(
+                "not started by the service manager ({}); use `sc start {}`",
+                describe(e),
+                name
+            )
+        })
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let Some((name, options, serve)) = ENTRY.get() else {
+            return;
+        };
+        let handler = |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                stop();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+        let Ok(handle) = service_control_handler::register(name, handler) else {
+            return;
+        };
+        let _ = STATUS.set(handle);
+        let _ = handle.set_service_status(status(
+            ServiceState::StartPending,
+            ServiceExitCode::NO_ERROR,
+        ));
+        let exit_code = match serve(options.clone()) {
+            Ok(()) => ServiceExitCode::NO_ERROR,
+            Err(e) => {
+                eprintln!("{}", e);
+                ServiceExitCode::ServiceSpecific(1)
+            }
+        };
+        let _ = handle.set_service_status(status(ServiceState::Stopped, exit_code));
+    }
+
+    fn stop() {
+        if let Some(handle) = STATUS.get() {
+            let _ = handle
+                .set_service_status(status(ServiceState::StopPending, ServiceExitCode::NO_ERROR));
+        }
+        let mut server = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+        match server.0 {
+            // Sends the stop command; completion is reported by `service_main`.
+            Some(ref handle) => drop(handle.stop(true)),
+            None => server.1 = true,
+        }
+    }
+
+    pub fn started(server: ServerHandle) {
+        let Some(handle) = STATUS.get() else {
+            return;
+        };
+        let mut slot = SERVER.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.1 {
+            drop(server.stop(true));
+        } else {
+            let _ =
+                handle.set_service_status(status(ServiceState::Running, ServiceExitCode::NO_ERROR));
+        }
+        slot.0 = Some(server);
+    }
+}
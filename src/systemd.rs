@@ -0,0 +1,114 @@
+//! `--systemd`: socket activation and readiness notification.
+//!
+//! Under a `.socket` unit systemd binds the listening sockets itself and hands them over
+//! through `LISTEN_FDS`; with `--systemd` the server accepts on those instead of binding
+//! `--host` / `--port` (or `--uds`). Once listening it sends `READY=1` to `NOTIFY_SOCKET`,
+//! so a `Type=notify` service is only marked started when requests can be served, and
+//! `STOPPING=1` on shutdown. Without `LISTEN_FDS` it binds as usual and still notifies.
+
+use listenfd::ListenFd;
+use std::ffi::OsStr;
+use std::io;
+use std::net::TcpListener;
+use webserve_core::host_port;
+
+use crate::uds::uds_addr;
+
+/// A listening socket inherited from the service manager.
+pub enum ActivatedListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixListener),
+}
+
+impl ActivatedListener {
+    /// `host:port` or `unix:<path>`, as shown in logs and on the dashboard.
+    pub fn describe(&self) -> String {
+        match self {
+            ActivatedListener::Tcp(l) => match l.local_addr() {
+                Ok(addr) => host_port(&addr.ip().to_string(), addr.port()),
+                Err(_) => "tcp:?".to_string(),
+            },
+            #[cfg(unix)]
+            ActivatedListener::Unix(l) => {
+                match l
+                    .local_addr()
+                    .ok()
+                    .and_then(|a| a.as_pathname().map(uds_addr))
+                {
+                    Some(addr) => addr,
+                    None => "unix:?".to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Listening sockets passed via `LISTEN_FDS` (empty when not socket-activated).
+///
+/// Only TCP and Unix stream listeners are accepted; anything else is an error so a
+/// misconfigured `.socket` unit fails loudly instead of being ignored.
+pub fn activated_listeners() -> io::Result<Vec<ActivatedListener>> {
+    let mut fds = ListenFd::from_env();
+    let mut listeners = Vec::new();
+    for i in 0..fds.len() {
+        if let Ok(Some(l)) = fds.take_tcp_listener(i) {
+            listeners.push(ActivatedListener::Tcp(l));
+            continue;
+        }
+        #[cfg(unix)]
+        if let Ok(Some(l)) = fds.take_unix_listener(i) {
+            listeners.push(ActivatedListener::Unix(l));
+            continue;
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "socket activation: fd {} is not a TCP or Unix stream listener",
+                3 + i
+            ),
+        ));
+    }
+    Ok(listeners)
+}
+
+/// Sends `state` (e.g. `READY=1`) to the service manager.
+///
+/// Returns `Ok(false)` when `NOTIFY_SOCKET` is unset, i.e. not running under systemd.
+pub fn notify(state: &str) -> io::Result<bool> {
+    match std::env::var_os("NOTIFY_SOCKET") {
+        Some(socket) => notify_socket(&socket, state),
+        None => Ok(false),
+    }
+}
+
+/// Sends `state` to the datagram socket at `socket`; a leading `@` names an abstract
+/// socket (Linux). Returns `Ok(false)` where notification is unsupported.
+pub fn notify_socket(socket: &OsStr, state: &str) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::UnixDatagram;
+
+        let sender = UnixDatagram::unbound()?;
+        match socket.as_bytes().strip_prefix(b"@") {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                sender.send_to_addr(state.as_bytes(), &addr)?;
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => return Ok(false),
+            None => {
+                sender.send_to(state.as_bytes(), socket)?;
+            }
+        }
+        Ok(true)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (socket, state);
+        Ok(false)
+    }
+}
@@ -0,0 +1,228 @@
+//! HTTPS with `--tls-cert` / `--tls-key`.
+//!
+//! TLS listeners offer `h2` and `http/1.1` via ALPN, so browsers multiplex the many small
+//! requests of module-heavy frontends over one connection. `--http1-only` restores plain
+//! HTTP/1.1 for debugging: actix-web always advertises `h2` on its own TLS listeners, so in
+//! that mode the TLS listeners are served by a separate HTTP/1-only server
+//! ([`http1_server`]) offering ALPN `http/1.1` alone. It runs the same app and keeps each
+//! client's address for `--allow` / `--deny`, the access log and the localhost checks.
+//!
+//! Every TLS connection's negotiated version, cipher suite and ALPN protocol is logged at
+//! debug level (`-v`) and counted under `tls` in `/__webserve/stats`, which helps with
+//! "works in Chrome, fails in curl" handshakes.
+//!
+//! `--redirect-http <port>` adds a plain HTTP listener that answers every request with a
+//! `301` to the same path on the HTTPS origin, so `http://` bookmarks don't just hang.
+
+use actix_http::{HttpService, Request, Response};
+use actix_server::Server;
+use actix_service::{map_config, IntoServiceFactory, Service, ServiceFactory, ServiceFactoryExt};
+use actix_web::body::MessageBody;
+use actix_web::dev::{AppConfig, Extensions};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ProtocolVersion, ServerConfig, ServerConnection};
+use std::any::Any;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::debug;
+use webserve_core::{ServeOptions, ServerTuning, Stats, TlsInfo};
+
+/// ALPN protocol id of HTTP/1.1.
+const ALPN_HTTP1: &[u8] = b"http/1.1";
+
+/// TLS settings resolved from the options.
+#[derive(Clone)]
+pub struct Tls {
+    pub config: ServerConfig,
+    /// `--http1-only`: negotiate HTTP/1.1 only.
+    pub http1_only: bool,
+}
+
+impl Tls {
+    /// `None` without `--tls-cert`; errors when the pair is incomplete or unreadable.
+    pub fn from_options(options: &ServeOptions) -> Result<Option<Tls>, String> {
+        let (cert, key) = match (&options.tls_cert, &options.tls_key) {
+            (None, None) if options.http1_only => {
+                return Err("--http1-only needs --tls-cert and --tls-key".to_string())
+            }
+            (None, None) if options.redirect_http.is_some() => {
+                return Err("--redirect-http needs --tls-cert and --tls-key".to_string())
+            }
+            (None, None) => return Ok(None),
+            (Some(cert), Some(key)) => (cert, key),
+            _ => return Err("--tls-cert and --tls-key must be given together".to_string()),
+        };
+        if options.redirect_http == Some(options.port) {
+            return Err("--redirect-http must differ from --port".to_string());
+        }
+        if options.uds.is_some() {
+            return Err("--tls-cert cannot be combined with --uds".to_string());
+        }
+        Ok(Some(Tls {
+            config: server_config(cert, key)?,
+            http1_only: options.http1_only,
+        }))
+    }
+}
+
+/// Server config for a PEM certificate chain and private key.
+pub fn server_config(cert: &Path, key: &Path) -> Result<ServerConfig, String> {
+    let open = |path: &Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))
+    };
+    let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut open(cert)?)
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("invalid certificate {}: {}", cert.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", cert.display()));
+    }
+    let private_key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut open(key)?)
+        .map_err(|e| format!("invalid private key {}: {}", key.display(), e))?
+        .ok_or_else(|| format!("no private key found in {}", key.display()))?;
+    ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|b| b.with_no_client_auth().with_single_cert(certs, private_key))
+        .map_err(|e| format!("TLS setup failed: {}", e))
+}
+
+/// What `conn` negotiated; `None` until the handshake has finished.
+pub fn tls_info(conn: &ServerConnection) -> Option<TlsInfo> {
+    let version = match conn.protocol_version()? {
+        ProtocolVersion::TLSv1_3 => "TLS 1.3".to_string(),
+        ProtocolVersion::TLSv1_2 => "TLS 1.2".to_string(),
+        other => format!("{:?}", other),
+    };
+    let cipher = conn.negotiated_cipher_suite()?.suite();
+    Some(TlsInfo {
+        version,
+        cipher: cipher
+            .as_str()
+            .map_or_else(|| format!("{:?}", cipher), str::to_string),
+        alpn: conn
+            .alpn_protocol()
+            .map(|p| String::from_utf8_lossy(p).into_owned()),
+    })
+}
+
+/// Logs and counts a finished handshake.
+fn record_handshake(info: &TlsInfo, peer: Option<SocketAddr>, stats: &Stats) {
+    debug!(
+        peer = ?peer,
+        version = %info.version,
+        cipher = %info.cipher,
+        alpn = info.alpn.as_deref().unwrap_or("-"),
+        "TLS connection"
+    );
+    stats.record_tls(info);
+}
+
+/// `HttpServer::on_connect` part for TLS listeners: records the handshake of `io` and keeps
+/// its [`TlsInfo`] in the connection data. Plain connections are ignored.
+pub fn track_tls(io: &dyn Any, extensions: &mut Extensions, stats: &Stats) {
+    type Stream = actix_tls::accept::rustls_0_23::TlsStream<tokio::net::TcpStream>;
+    let Some(stream) = io.downcast_ref::<Stream>() else {
+        return;
+    };
+    let (tcp, conn) = stream.get_ref();
+    if let Some(info) = tls_info(conn) {
+        record_handshake(&info, tcp.peer_addr().ok(), stats);
+        extensions.insert(info);
+    }
+}
+
+/// Port of the HTTPS listener that `--redirect-http` points at.
+#[derive(Clone, Copy)]
+pub struct HttpsPort(pub u16);
+
+/// `https://` URL for `host` (a `Host` header; any port is replaced) and `path`.
+pub fn https_location(host: &str, port: u16, path: &str) -> String {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((ip, _)) => format!("[{}]", ip),
+            None => host.to_string(),
+        },
+        None => host.split(':').next().unwrap_or(host).to_string(),
+    };
+    match port {
+        443 => format!("https://{}{}", name, path),
+        _ => format!("https://{}:{}{}", name, port, path),
+    }
+}
+
+/// Handler of the `--redirect-http` listener: `301` to the HTTPS origin, path and query kept.
+pub async fn redirect_https(req: HttpRequest, port: web::Data<HttpsPort>) -> HttpResponse {
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let location = https_location(req.connection_info().host(), port.0, path);
+    HttpResponse::MovedPermanently()
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// Per-connection hook, as given to `HttpServer::on_connect`.
+pub type OnConnect = Arc<dyn Fn(&dyn Any, &mut Extensions) + Send + Sync>;
+
+/// Serves `listeners` over TLS with ALPN `http/1.1` only, for `--http1-only`. `factory`
+/// builds the app as for `HttpServer::new`; `on_connect` sees every TLS stream like
+/// `HttpServer::on_connect`. Run it next to the main server and stop it with it.
+pub fn http1_server<F, I, S, B>(
+    listeners: Vec<TcpListener>,
+    config: ServerConfig,
+    factory: F,
+    tuning: ServerTuning,
+    on_connect: OnConnect,
+) -> io::Result<Server>
+where
+    F: Fn() -> I + Send + Clone + 'static,
+    I: IntoServiceFactory<S, Request>,
+    S: ServiceFactory<Request, Config = AppConfig> + 'static,
+    S::Error: Into<actix_web::Error> + 'static,
+    S::InitError: std::fmt::Debug,
+    S::Response: Into<Response<B>> + 'static,
+    <S::Service as Service<Request>>::Future: 'static,
+    S::Service: 'static,
+    B: MessageBody + 'static,
+{
+    let mut config = config;
+    config.alpn_protocols = vec![ALPN_HTTP1.to_vec()];
+    let mut builder = Server::build();
+    if let Some(n) = tuning.workers {
+        builder = builder.workers(n);
+    }
+    if let Some(n) = tuning.max_connections {
+        builder = builder.max_concurrent_connections(n);
+    }
+    for listener in listeners {
+        let addr = listener.local_addr()?;
+        let (config, factory, on_connect) = (config.clone(), factory.clone(), on_connect.clone());
+        builder = builder.listen(format!("webserve-http1-{}", addr), listener, move || {
+            let mut service = HttpService::build().local_addr(addr);
+            if let Some(d) = tuning.keep_alive {
+                service = service.keep_alive(d);
+            }
+            if let Some(d) = tuning.client_timeout {
+                service = service.client_request_timeout(d);
+            }
+            let on_connect = on_connect.clone();
+            let app = factory()
+                .into_factory()
+                .map_err(|err| err.into().error_response());
+            // Only `Host`-less requests fall back to the config's host, which HTTP/1.1 forbids.
+            service
+                .on_connect_ext(move |io: &_, ext: _| on_connect(io as &dyn Any, ext))
+                .h1(map_config(app, |_| AppConfig::default()))
+                .rustls_0_23(config.clone())
+        })?;
+    }
+    Ok(builder.run())
+}
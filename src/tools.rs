@@ -0,0 +1,180 @@
+//! `check-links`, `diff` and `clean`: the subcommands that run instead of the server and
+//! report on stdout. The work itself is done by `webserve-core`; these print it and pick
+//! the exit code.
+
+use actix_web::{web, App, HttpResponse, HttpServer};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use webserve_core::{
+    browser_url, cache_root, check_dir, check_url, diff_dirs, diff_report_html, host_port,
+    text_line, trim, usage, ServeOptions, WalkLimits, EXIT_ERROR,
+};
+
+/// Options of the `check-links` subcommand.
+#[derive(StructOpt, Debug, Clone)]
+pub struct CheckLinksOptions {
+    /// Directory or `http(s)://` URL to check (default: the served directory)
+    pub target: Option<String>,
+
+    /// Stop parsing pages after this many
+    #[structopt(long = "max-pages", default_value = "1000")]
+    pub max_pages: usize,
+}
+
+/// Options of the `diff` subcommand.
+#[derive(StructOpt, Debug, Clone)]
+pub struct DiffOptions {
+    /// Current build output
+    #[structopt(parse(from_os_str))]
+    pub before: PathBuf,
+
+    /// Build output about to replace it
+    #[structopt(parse(from_os_str))]
+    pub after: PathBuf,
+
+    /// Also serve the report as an HTML page on --host/--port
+    #[structopt(long = "serve")]
+    pub serve: bool,
+}
+
+/// Options of the `clean` subcommand.
+#[derive(StructOpt, Debug, Clone)]
+pub struct CleanOptions {
+    /// Only delete the least recently written files until the cache fits in this many MiB
+    #[structopt(long = "max-size")]
+    pub max_size: Option<u64>,
+
+    /// Report what would be deleted without deleting it
+    #[structopt(long = "dry-run")]
+    pub dry_run: bool,
+}
+
+/// Runs `webserve check-links`, prints broken links and a summary, and returns the exit code.
+pub async fn run_check_links(options: &ServeOptions, check: &CheckLinksOptions) -> i32 {
+    let target = match (&check.target, &options.directory) {
+        (Some(target), _) => target.clone(),
+        (None, Some(dir)) => dir.display().to_string(),
+        (None, None) => ".".to_string(),
+    };
+    let report = if target.starts_with("http://") || target.starts_with("https://") {
+        check_url(&target, check.max_pages).await
+    } else {
+        let root = PathBuf::from(&target);
+        if root.is_dir() {
+            Ok(check_dir(&root, WalkLimits::from_options(options), check.max_pages).await)
+        } else {
+            Err(format!("{}: not a directory", target))
+        }
+    };
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("check-links: {}", e);
+            return EXIT_ERROR;
+        }
+    };
+    for dead in &report.broken {
+        println!(
+            "{}: {} -> {} ({})",
+            dead.page, dead.href, dead.target, dead.problem
+        );
+    }
+    println!(
+        "Checked {} page(s) and {} link(s): {} broken{}",
+        report.pages,
+        report.links,
+        report.broken.len(),
+        if report.truncated {
+            " (stopped at --max-pages)"
+        } else {
+            ""
+        }
+    );
+    report.exit_code()
+}
+
+/// Runs `webserve diff`, prints the differences and a summary, and returns the exit code;
+/// with `--serve` the HTML report is served until the process is interrupted.
+pub async fn run_diff(options: &ServeOptions, diff: &DiffOptions) -> i32 {
+    for dir in [&diff.before, &diff.after] {
+        if !dir.is_dir() {
+            eprintln!("diff: {}: not a directory", dir.display());
+            return EXIT_ERROR;
+        }
+    }
+    let report = diff_dirs(&diff.before, &diff.after, WalkLimits::from_options(options));
+    for file in &report.files {
+        println!("{}", text_line(file));
+    }
+    println!("{}", report.summary());
+    if !diff.serve {
+        return report.exit_code();
+    }
+
+    let html = diff_report_html(
+        &diff.before.display().to_string(),
+        &diff.after.display().to_string(),
+        &report,
+    );
+    let mut server = HttpServer::new(move || {
+        let html = html.clone();
+        App::new().default_service(web::to(move || {
+            let html = html.clone();
+            async move {
+                HttpResponse::Ok()
+                    .content_type("text/html; charset=utf-8")
+                    .insert_header(("Cache-Control", "no-store"))
+                    .body(html)
+            }
+        }))
+    })
+    .workers(1);
+    for host in &options.hosts {
+        server = match server.bind(host_port(host, options.port)) {
+            Ok(server) => server,
+            Err(e) => {
+                eprintln!("diff: cannot bind {}: {}", host_port(host, options.port), e);
+                return EXIT_ERROR;
+            }
+        };
+    }
+    for host in &options.hosts {
+        println!(
+            "Serving diff report on {}",
+            browser_url(host, options.port, "/")
+        );
+    }
+    if let Err(e) = server.run().await {
+        eprintln!("diff: {}", e);
+        return EXIT_ERROR;
+    }
+    report.exit_code()
+}
+
+/// `webserve clean`: empties or trims the cache root; returns the exit code.
+pub fn run_clean(options: &ServeOptions, clean: &CleanOptions) -> i32 {
+    let root = cache_root(options);
+    let before = usage(&root);
+    let max = clean.max_size.map(|mib| mib.saturating_mul(1024 * 1024));
+    match trim(&root, max, clean.dry_run) {
+        Ok(removed) => {
+            let verb = if clean.dry_run {
+                "Would delete"
+            } else {
+                "Deleted"
+            };
+            println!(
+                "{}: {} {} of {}",
+                root.display(),
+                verb,
+                removed.describe(),
+                before.describe()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("clean: {}: {}", root.display(), e);
+            EXIT_ERROR
+        }
+    }
+}
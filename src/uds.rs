@@ -0,0 +1,37 @@
+//! `--uds <path>`: listen on a Unix domain socket instead of TCP.
+//!
+//! Useful behind nginx or caddy, or in sandboxes without network access. The injected
+//! reload script and generated pages use relative URLs, so they work unchanged through a
+//! proxy in front of the socket.
+
+use std::io;
+use std::path::Path;
+
+/// Address shown in logs and on the dashboard for a socket at `path`.
+pub fn uds_addr(path: &Path) -> String {
+    format!("unix:{}", path.display())
+}
+
+/// Readies `path` for binding: a stale socket left by a crashed run is removed, while a
+/// socket something still listens on, or any other kind of file, is an error.
+#[cfg(unix)]
+pub fn prepare_socket_path(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(io::ErrorKind::AddrInUse.into());
+    }
+    std::fs::remove_file(path)
+}
+
+/// Unix sockets are not available on this platform.
+#[cfg(not(unix))]
+pub fn prepare_socket_path(_path: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
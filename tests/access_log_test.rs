@@ -0,0 +1,211 @@
+//! Access log formats and middleware.
+
+use actix_web::middleware::from_fn;
+use actix_web::{web, App as ActixApp};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    access_log, parse_sample, serve_file, AccessLogger, AccessRecord, AppState, LogFilter,
+    LogFormat, PathPattern, ServeOptions,
+};
+
+fn record() -> AccessRecord {
+    AccessRecord {
+        remote: "10.0.0.1".into(),
+        time: UNIX_EPOCH + Duration::from_secs(971_186_136),
+        method: "GET".into(),
+        target: "/a.txt?x=1".into(),
+        version: "HTTP/1.1".into(),
+        status: 200,
+        bytes: Some(42),
+        referer: None,
+        user_agent: Some("curl/8".into()),
+        latency: Duration::from_micros(1500),
+    }
+}
+
+#[test]
+fn formats_common_combined_and_json() {
+    let r = record();
+    assert_eq!(
+        r.format(LogFormat::Common),
+        r#"10.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /a.txt?x=1 HTTP/1.1" 200 42 1.500ms"#
+    );
+    assert_eq!(
+        r.format(LogFormat::Combined),
+        r#"10.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /a.txt?x=1 HTTP/1.1" 200 42 "-" "curl/8" 1.500ms"#
+    );
+    let json: serde_json::Value = serde_json::from_str(&r.format(LogFormat::Json)).unwrap();
+    assert_eq!(json["time"], "2000-10-10T13:55:36Z");
+    assert_eq!(json["status"], 200);
+    assert_eq!(json["bytes"], 42);
+    assert_eq!(json["referer"], serde_json::Value::Null);
+    assert_eq!(json["latency_ms"], 1.5);
+}
+
+#[test]
+fn log_format_parses_names() {
+    assert_eq!("combined".parse::<LogFormat>(), Ok(LogFormat::Combined));
+    assert_eq!("JSON".parse::<LogFormat>(), Ok(LogFormat::Json));
+    assert!("apache".parse::<LogFormat>().is_err());
+}
+
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[actix_web::test]
+async fn middleware_writes_one_line_per_request() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    std::fs::write(temp.path().join("a.txt"), b"hello").unwrap();
+    let buf = Shared::default();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        access_log: Some(Arc::new(AccessLogger::new(
+            LogFormat::Common,
+            Box::new(buf.clone()),
+        ))),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state)
+            .wrap(from_fn(access_log))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    for uri in ["/a.txt", "/missing?q=1"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains(r#""GET /a.txt HTTP/1.1" 200 5 "#));
+    assert!(lines[1].contains(r#""GET /missing?q=1 HTTP/1.1" 404 "#));
+}
+
+#[test]
+fn log_filters_parse_and_describe() {
+    let options = ServeOptions::from_iter(
+        [
+            "webserve",
+            "--log-exclude",
+            "/__webserve/*",
+            "--log-exclude",
+            "/favicon.ico",
+            "--log-sample",
+            "/api/*=0.25",
+        ]
+        .iter(),
+    );
+    let filter = LogFilter::from_options(&options).unwrap();
+    assert_eq!(filter.describe(), "2 excluded, 1 sampled");
+    assert!(
+        LogFilter::from_options(&ServeOptions::from_iter(["webserve"].iter()))
+            .unwrap()
+            .is_empty()
+    );
+
+    for bad in ["/api/*", "/api/*=0", "/api/*=1.5", "/api/*=often", "=0.5"] {
+        assert!(parse_sample(bad).is_err(), "{}", bad);
+    }
+    assert_eq!(
+        parse_sample("/q?a=b=0.5").unwrap(),
+        (PathPattern::new("/q?a=b"), 0.5)
+    );
+}
+
+#[test]
+fn path_patterns_use_star_wildcards() {
+    let pattern = PathPattern::new("/__webserve/*");
+    assert!(pattern.matches("/__webserve/stats"));
+    assert!(pattern.matches("/__webserve/"));
+    assert!(!pattern.matches("/__webserve"));
+    assert!(PathPattern::new("*.map").matches("/js/app.js.map"));
+    assert!(PathPattern::new("/api/*/items").matches("/api/v1/items"));
+    assert!(!PathPattern::new("/api/*/items").matches("/api/v1/items/2"));
+    assert!(PathPattern::new("/favicon.ico").matches("/favicon.ico"));
+    assert!(!PathPattern::new("/favicon.ico").matches("/favicon.ico.bak"));
+    assert!(PathPattern::new("*").matches("/anything"));
+}
+
+#[test]
+fn sampling_keeps_the_rate_and_every_error() {
+    let filter = LogFilter::default()
+        .sample(PathPattern::new("/api/*"), 0.1)
+        .sample(PathPattern::new("*"), 1.0);
+    let kept = (0..100).filter(|_| filter.keeps("/api/items", 200)).count();
+    assert_eq!(kept, 10);
+    assert!((0..5).all(|_| filter.keeps("/api/items", 500)));
+    assert!((0..5).all(|_| filter.keeps("/index.html", 200)));
+}
+
+#[actix_web::test]
+async fn middleware_skips_excluded_and_sampled_requests() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    std::fs::write(temp.path().join("a.txt"), b"hello").unwrap();
+    std::fs::write(temp.path().join("favicon.ico"), b"icon").unwrap();
+    let buf = Shared::default();
+    let filter = LogFilter::from_options(&ServeOptions::from_iter(
+        [
+            "webserve",
+            "--log-exclude",
+            "/favicon.ico",
+            "--log-sample",
+            "/*.txt=0.5",
+        ]
+        .iter(),
+    ))
+    .unwrap();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        access_log: Some(Arc::new(
+            AccessLogger::new(LogFormat::Common, Box::new(buf.clone())).with_filter(filter),
+        )),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state)
+            .wrap(from_fn(access_log))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    for uri in [
+        "/favicon.ico",
+        "/a.txt",
+        "/a.txt",
+        "/a.txt",
+        "/a.txt",
+        "/gone.txt",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", out);
+    assert!(lines[..2].iter().all(|l| l.contains("GET /a.txt ")));
+    assert!(lines[2].contains(r#""GET /gone.txt HTTP/1.1" 404 "#));
+}
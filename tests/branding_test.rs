@@ -0,0 +1,127 @@
+//! `--brand-title` / `--brand-logo` / `--brand-footer` on generated pages.
+
+use actix_web::http::header;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::sync::Arc;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    dashboard, directory_listing, serve_file, AppState, Branding, ListingView, ServeOptions,
+    Settings, ADMIN_PREFIX,
+};
+
+fn acme() -> Branding {
+    Branding {
+        title: Some("Acme <Previews>".to_string()),
+        logo: Some("/assets/logo.svg".to_string()),
+        footer: Some("© Acme Corp".to_string()),
+    }
+}
+
+#[test]
+fn branding_options_reach_the_settings() {
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    assert!(Branding::from_options(&options).is_empty());
+    let options = ServeOptions::from_iter(
+        [
+            "webserve",
+            "--brand-title",
+            "Acme",
+            "--brand-logo",
+            "/logo.png",
+            "--brand-footer",
+            "",
+        ]
+        .iter(),
+    );
+    let branding = Settings::from_options(&options).unwrap().branding;
+    assert_eq!(branding.title.as_deref(), Some("Acme"));
+    assert_eq!(branding.logo.as_deref(), Some("/logo.png"));
+    assert_eq!(branding.footer, None, "empty text is no footer");
+}
+
+#[tokio::test]
+async fn listing_shows_logo_title_and_footer() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("a.txt"), "a").unwrap();
+    let branding = acme();
+    let view = ListingView {
+        branding: &branding,
+        ..Default::default()
+    };
+    let listing = view.render(temp.path(), "/").await;
+    assert!(listing.contains("<title>Index of / · Acme &lt;Previews&gt;</title>"));
+    assert!(listing.contains(r#"<img class="brand-logo" src="/assets/logo.svg""#));
+    assert!(listing.contains(r#"<span class="brand-title">Acme &lt;Previews&gt;</span>"#));
+    assert!(listing.contains(r#"<footer class="brand-footer">© Acme Corp</footer>"#));
+
+    let plain = directory_listing(temp.path(), "/").await;
+    assert!(plain.contains("<title>Index of /</title>"));
+    assert!(!plain.contains(r#"class="brand""#));
+}
+
+#[actix_web::test]
+async fn error_pages_and_dashboard_are_branded() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    let data_with = |branding: Branding| {
+        web::Data::new(AppState {
+            static_dir: Arc::new(temp.path().to_path_buf()),
+            settings: Arc::new(
+                Settings {
+                    branding,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            ..Default::default()
+        })
+    };
+    let missing = |accept: &str| {
+        test::TestRequest::get()
+            .uri("/missing.txt")
+            .insert_header((header::ACCEPT, accept))
+            .to_request()
+    };
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data_with(acme()))
+            .route(&format!("{}/", ADMIN_PREFIX), web::get().to(dashboard))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, missing("text/html,*/*;q=0.8")).await;
+    assert_eq!(res.status(), 404);
+    assert_eq!(
+        res.headers().get(header::CACHE_CONTROL).unwrap(),
+        "no-store"
+    );
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("<h1>404 Not Found</h1>"));
+    assert!(body.contains("brand-footer"));
+
+    // Scripts and tools asking for other types keep the empty body.
+    let res = test::call_service(&app, missing("application/json")).await;
+    assert_eq!(res.status(), 404);
+    assert!(test::read_body(res).await.is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/", ADMIN_PREFIX))
+        .to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(body.contains("<title>webserve status · Acme &lt;Previews&gt;</title>"));
+    assert!(body.contains(r#"<footer class="brand-footer">"#));
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data_with(Branding::default()))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, missing("text/html")).await;
+    assert_eq!(res.status(), 404);
+    assert!(test::read_body(res).await.is_empty());
+}
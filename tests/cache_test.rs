@@ -0,0 +1,105 @@
+//! `.webserve-cache` root: cache placement, `--cache-size` and `webserve clean`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    cache_root, compress_cache_dir, enforce_cache_size, resolve_options_with_env, trim, usage,
+    FileConfig, ServeOptions, CACHE_DIR,
+};
+
+/// Writes `len` bytes to `root/name`, last modified `age` seconds ago.
+fn entry(root: &Path, name: &str, len: usize, age: u64) {
+    let path = root.join(name);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, vec![0u8; len]).unwrap();
+    let file = fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(SystemTime::now() - Duration::from_secs(age))
+        .unwrap();
+}
+
+#[test]
+fn caches_live_under_one_root() {
+    let options = ServeOptions::from_iter(["webserve"]);
+    assert_eq!(cache_root(&options), PathBuf::from(CACHE_DIR));
+    assert_eq!(options.cache_size, 1024);
+    assert_eq!(compress_cache_dir(&options), None);
+
+    let options =
+        ServeOptions::from_iter(["webserve", "--compress-cache", "--cache-dir", "/tmp/w"]);
+    assert_eq!(
+        compress_cache_dir(&options),
+        Some(PathBuf::from("/tmp/w/compress"))
+    );
+    let options = ServeOptions::from_iter(["webserve", "--compress-cache", "own"]);
+    assert_eq!(compress_cache_dir(&options), Some(PathBuf::from("own")));
+
+    let config = FileConfig::parse("cache-dir = \"tmp\"\ncache-size = 64").unwrap();
+    assert_eq!(config.cache_dir, Some(PathBuf::from("tmp")));
+    assert_eq!(config.cache_size, Some(64));
+    let env = vec![("WEBSERVE_CACHE_SIZE".to_string(), "8".to_string())];
+    assert_eq!(
+        resolve_options_with_env(["webserve"], env)
+            .unwrap()
+            .cache_size,
+        8
+    );
+}
+
+#[test]
+fn trims_oldest_files_first_and_empties() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    entry(root, "compress/old.gz", 400, 300);
+    entry(root, "compress/mid.gz", 300, 200);
+    entry(root, "thumbs/new.png", 200, 100);
+    assert_eq!(usage(root).bytes, 900);
+
+    let planned = trim(root, Some(500), true).unwrap();
+    assert_eq!((planned.files, planned.bytes), (1, 400));
+    assert_eq!(usage(root).files, 3);
+
+    let removed = trim(root, Some(500), false).unwrap();
+    assert_eq!(removed, planned);
+    assert!(!root.join("compress/old.gz").exists());
+    assert!(root.join("compress/mid.gz").exists());
+
+    let removed = trim(root, None, false).unwrap();
+    assert_eq!((removed.files, removed.bytes), (2, 500));
+    assert!(root.exists());
+    assert_eq!(fs::read_dir(root).unwrap().count(), 0);
+    assert_eq!(usage(&root.join("missing")).files, 0);
+}
+
+#[test]
+fn startup_enforces_cache_size() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path().join("cache");
+    entry(&root, "a", 1024 * 1024, 20);
+    entry(&root, "b", 1024 * 1024, 10);
+    let mut options = ServeOptions::from_iter(["webserve"]);
+    options.cache_dir = Some(root.clone());
+    options.cache_size = 1;
+    enforce_cache_size(&options).unwrap();
+    assert!(!root.join("a").exists());
+    assert!(root.join("b").exists());
+}
+
+#[test]
+fn clean_subcommand_empties_the_root() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path().join("cache");
+    entry(&root, "compress/x.br", 10, 0);
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .arg("--cache-dir")
+        .arg(&root)
+        .arg("clean")
+        .output()
+        .expect("run webserve binary");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Deleted 1 file(s)"), "stdout: {}", stdout);
+    assert_eq!(usage(&root).files, 0);
+}
@@ -0,0 +1,98 @@
+//! `--charset` / `--charset-ext`: the charset announced for text files.
+
+use actix_web::App;
+use std::fs;
+use std::path::Path;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{Charsets, ServeOptions, Webserve};
+
+#[test]
+fn parses_and_applies_charsets() {
+    assert_eq!(
+        Charsets::parse_name(" Shift_JIS ").unwrap().as_deref(),
+        Some("shift_jis")
+    );
+    assert_eq!(Charsets::parse_name("off").unwrap(), None);
+    assert!(Charsets::parse_name("utf 8").is_err());
+    assert!(Charsets::parse_ext("txt").is_err());
+    assert!(Charsets::parse_ext("=latin1").is_err());
+
+    let charsets = Charsets::default();
+    assert_eq!(charsets.for_path(Path::new("index.html")), Some("utf-8"));
+
+    let options = ServeOptions::from_iter([
+        "webserve",
+        "--charset",
+        "windows-1252",
+        "--charset-ext",
+        ".TXT=shift_jis",
+        "--charset-ext",
+        "css=off",
+    ]);
+    let charsets = Charsets::from_options(&options).unwrap();
+    assert_eq!(charsets.for_path(Path::new("a.html")), Some("windows-1252"));
+    assert_eq!(charsets.for_path(Path::new("notes.Txt")), Some("shift_jis"));
+    assert_eq!(charsets.for_path(Path::new("site.css")), None);
+    assert_eq!(charsets.describe(), "windows-1252, css=off, txt=shift_jis");
+}
+
+#[actix_web::test]
+async fn text_files_carry_the_configured_charset() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), "<p>hi</p>").unwrap();
+    fs::write(temp.path().join("app.js"), "export {}").unwrap();
+    fs::write(temp.path().join("legacy.txt"), b"\x82\xa0").unwrap();
+    fs::write(temp.path().join("notes.log"), "plain").unwrap();
+    fs::write(temp.path().join("logo.png"), b"\x89PNG").unwrap();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .configure(|o| {
+            o.charset_ext = vec!["txt=shift_jis".to_string()];
+            o.mime = vec!["log=text/plain; charset=iso-8859-1".to_string()];
+        })
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let content_type = |res: &actix_web::dev::ServiceResponse| {
+        res.headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/index.html")).await;
+    assert_eq!(content_type(&res), "text/html; charset=utf-8");
+    let res = test::call_service(&app, get("/app.js")).await;
+    assert_eq!(content_type(&res), "text/javascript; charset=utf-8");
+    let res = test::call_service(&app, get("/legacy.txt")).await;
+    assert_eq!(content_type(&res), "text/plain; charset=shift_jis");
+    // An explicit `--mime` charset is kept
+    let res = test::call_service(&app, get("/notes.log")).await;
+    assert_eq!(content_type(&res), "text/plain; charset=iso-8859-1");
+    let res = test::call_service(&app, get("/logo.png")).await;
+    assert_eq!(content_type(&res), "image/png");
+}
+
+#[actix_web::test]
+async fn charset_off_sends_no_parameter() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("site.css"), "p {}").unwrap();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .configure(|o| o.charset = "off".to_string())
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+    let req = test::TestRequest::get().uri("/site.css").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.headers().get("content-type").unwrap(), "text/css");
+}
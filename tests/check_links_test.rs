@@ -0,0 +1,184 @@
+//! `webserve check-links`: broken internal links in a directory or a running site.
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    check_dir, extract_links, resolve_link, Cli, Command as Subcommand, WalkLimits, EXIT_BROKEN,
+    EXIT_ERROR, EXIT_OK,
+};
+
+fn site() -> TempDir {
+    common::site(&[
+        ("docs/img/logo.png", "png"),
+        ("files/", ""),
+        (
+            "index.html",
+            r##"<a href="docs/">Docs</a> <a href="/files/">Files</a> <img src="/missing.png">
+<a href="https://example.com/">out</a> <a href="mailto:me@example.com">mail</a> <a href="#top">top</a>"##,
+        ),
+        (
+            "docs/index.html",
+            r##"<img src='img/logo.png'> <a href="../index.html?x=1#top">home</a> <a href=gone.html>gone</a>"##,
+        ),
+    ])
+}
+
+#[test]
+fn extracts_and_resolves_links() {
+    let html = r#"<a class=x href="/a?b=1&amp;c=2">a</a><img  SRC='b.png'><script src=c.js></script><div data-src="skip">"#;
+    assert_eq!(extract_links(html), vec!["/a?b=1&c=2", "b.png", "c.js"]);
+
+    assert_eq!(
+        resolve_link("/docs/page.html", "img/a.png", None).as_deref(),
+        Some("/docs/img/a.png")
+    );
+    assert_eq!(
+        resolve_link("/docs/", "../index.html#x", None).as_deref(),
+        Some("/index.html")
+    );
+    assert_eq!(
+        resolve_link("/docs/page.html", "sub/", None).as_deref(),
+        Some("/docs/sub/")
+    );
+    assert_eq!(
+        resolve_link(
+            "/",
+            "http://localhost:8080/a",
+            Some("http://localhost:8080")
+        )
+        .as_deref(),
+        Some("/a")
+    );
+    assert_eq!(
+        resolve_link("/", "http://localhost:8080", Some("http://localhost:8080")).as_deref(),
+        Some("/")
+    );
+    for skipped in [
+        "https://example.com/",
+        "//cdn.example.com/x.js",
+        "mailto:me@example.com",
+        "javascript:void(0)",
+        "data:image/png;base64,AAAA",
+        "#section",
+        "",
+    ] {
+        assert_eq!(resolve_link("/", skipped, None), None, "{}", skipped);
+    }
+}
+
+#[actix_web::test]
+async fn reports_broken_links_in_a_directory() {
+    let temp = site();
+    let report = check_dir(temp.path(), WalkLimits::default(), 100).await;
+    assert_eq!(report.pages, 2, "/docs/ and /docs/index.html are one page");
+    assert_eq!(report.links, 6);
+    let broken: Vec<(&str, &str)> = report
+        .broken
+        .iter()
+        .map(|d| (d.page.as_str(), d.target.as_str()))
+        .collect();
+    assert_eq!(
+        broken,
+        vec![
+            ("/docs/index.html", "/docs/gone.html"),
+            ("/index.html", "/missing.png"),
+        ]
+    );
+    assert_eq!(report.exit_code(), EXIT_BROKEN);
+
+    let report = check_dir(temp.path(), WalkLimits::default(), 1).await;
+    assert_eq!(report.pages, 1);
+    assert!(report.truncated);
+}
+
+#[test]
+fn parses_the_subcommand() {
+    let cli = Cli::from_iter(["webserve"].iter());
+    assert!(cli.command.is_none());
+    let cli = Cli::from_iter(["webserve", "-d", "site", "check-links", "--max-pages", "5"].iter());
+    assert_eq!(cli.options.directory.unwrap().to_str(), Some("site"));
+    let Some(Subcommand::CheckLinks(check)) = cli.command else {
+        panic!("check-links parsed");
+    };
+    assert_eq!(check.target, None);
+    assert_eq!(check.max_pages, 5);
+}
+
+fn check_links(args: &[&str]) -> (i32, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .arg("check-links")
+        .args(args)
+        .output()
+        .expect("run webserve binary");
+    (
+        out.status.code().unwrap(),
+        String::from_utf8_lossy(&out.stdout).into_owned(),
+    )
+}
+
+#[test]
+fn exit_status_reflects_the_result() {
+    let temp = site();
+    let dir = temp.path().to_str().unwrap();
+    let (code, stdout) = check_links(&[dir]);
+    assert_eq!(code, EXIT_BROKEN);
+    assert!(stdout.contains("/index.html: /missing.png -> /missing.png (not found)"));
+    assert!(stdout.contains("Checked 2 page(s) and 6 link(s): 2 broken"));
+
+    fs::write(temp.path().join("missing.png"), b"png").unwrap();
+    fs::write(temp.path().join("docs/gone.html"), b"back").unwrap();
+    assert_eq!(check_links(&[dir]).0, EXIT_OK);
+
+    let missing = temp.path().join("nope");
+    assert_eq!(check_links(&[missing.to_str().unwrap()]).0, EXIT_ERROR);
+}
+
+#[cfg(feature = "crawl")]
+#[test]
+fn crawls_a_running_site() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let temp = site();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--strict-port", "-p", &port.to_string(), "-d"])
+        .arg(temp.path())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run webserve binary");
+    let stdout = child.stdout.take().unwrap();
+    BufReader::new(stdout)
+        .lines()
+        .map_while(Result::ok)
+        .find(|line| line.contains("Serving on"))
+        .expect("server reports its URL");
+
+    // `/docs` redirects to `/docs/`, so its relative links resolve from there
+    fs::write(
+        temp.path().join("start.html"),
+        r#"<a href="/docs">docs</a>"#,
+    )
+    .unwrap();
+    let (code, stdout) = check_links(&[&format!("http://127.0.0.1:{}/start.html", port)]);
+    let unreachable = check_links(&["http://127.0.0.1:9/"]).0;
+    child.kill().ok();
+    child.wait().ok();
+
+    assert_eq!(code, EXIT_BROKEN, "{}", stdout);
+    assert!(
+        stdout.contains("/docs/: gone.html -> /docs/gone.html (404 Not Found)"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("/index.html: /missing.png -> /missing.png (404 Not Found)"));
+    assert_eq!(unreachable, EXIT_ERROR);
+}
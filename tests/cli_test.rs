@@ -7,40 +7,40 @@ use webserve::ServeOptions;
 
 #[test]
 fn test_cli_options_defaults() {
-    let args = vec!["webserve"];
+    let args = ["webserve"];
     let options = ServeOptions::from_iter(args.iter());
     assert_eq!(options.port, 8080);
-    assert_eq!(options.host, "127.0.0.1");
+    assert_eq!(options.hosts, vec!["127.0.0.1"]);
     assert!(!options.spa);
     assert!(!options.watch);
-    assert!(!options.open);
+    assert!(options.open.is_none());
     assert!(!options.no_redirect_dir_slash);
 }
 
 #[test]
 fn test_cli_options_custom_port() {
-    let args = vec!["webserve", "--port", "3000"];
+    let args = ["webserve", "--port", "3000"];
     let options = ServeOptions::from_iter(args.iter());
     assert_eq!(options.port, 3000);
 }
 
 #[test]
 fn test_cli_options_custom_host() {
-    let args = vec!["webserve", "--host", "0.0.0.0"];
+    let args = ["webserve", "--host", "0.0.0.0"];
     let options = ServeOptions::from_iter(args.iter());
-    assert_eq!(options.host, "0.0.0.0");
+    assert_eq!(options.hosts, vec!["0.0.0.0"]);
 }
 
 #[test]
 fn test_cli_options_spa_flag() {
-    let args = vec!["webserve", "--spa"];
+    let args = ["webserve", "--spa"];
     let options = ServeOptions::from_iter(args.iter());
     assert!(options.spa);
 }
 
 #[test]
 fn test_cli_options_watch_flag() {
-    let args = vec!["webserve", "--watch"];
+    let args = ["webserve", "--watch"];
     let options = ServeOptions::from_iter(args.iter());
     assert!(options.watch);
 }
@@ -49,16 +49,16 @@ fn test_cli_options_watch_flag() {
 fn test_cli_options_directory() {
     let temp_dir = TempDir::new().unwrap();
     let dir_str = temp_dir.path().to_str().unwrap();
-    let args = vec!["webserve", "--dir", dir_str];
+    let args = ["webserve", "--dir", dir_str];
     let options = ServeOptions::from_iter(args.iter());
     assert_eq!(options.directory.unwrap(), temp_dir.path());
 }
 
 #[test]
 fn test_cli_options_open_and_no_redirect_dir_slash() {
-    let args = vec!["webserve", "--open", "--no-redirect-dir-slash"];
+    let args = ["webserve", "--open", "--no-redirect-dir-slash"];
     let options = ServeOptions::from_iter(args.iter());
-    assert!(options.open);
+    assert_eq!(options.open_path(), Some("/"));
     assert!(options.no_redirect_dir_slash);
 }
 
@@ -66,12 +66,12 @@ fn test_cli_options_open_and_no_redirect_dir_slash() {
 fn test_cli_short_flags() {
     let temp_dir = TempDir::new().unwrap();
     let dir_str = temp_dir.path().to_str().unwrap();
-    let args = vec![
+    let args = [
         "webserve", "-p", "4000", "-h", "0.0.0.0", "-d", dir_str, "-w",
     ];
     let options = ServeOptions::from_iter(args.iter());
     assert_eq!(options.port, 4000);
-    assert_eq!(options.host, "0.0.0.0");
+    assert_eq!(options.hosts, vec!["0.0.0.0"]);
     assert_eq!(options.directory.as_ref().unwrap(), temp_dir.path());
     assert!(options.watch);
 }
@@ -80,7 +80,7 @@ fn test_cli_short_flags() {
 fn test_cli_combined_options() {
     let temp_dir = TempDir::new().unwrap();
     let dir_str = temp_dir.path().to_str().unwrap();
-    let args = vec![
+    let args = [
         "webserve", "--dir", dir_str, "--port", "5000", "--spa", "--watch", "--open",
     ];
     let options = ServeOptions::from_iter(args.iter());
@@ -88,7 +88,7 @@ fn test_cli_combined_options() {
     assert_eq!(options.directory.as_ref().unwrap(), temp_dir.path());
     assert!(options.spa);
     assert!(options.watch);
-    assert!(options.open);
+    assert_eq!(options.open_path(), Some("/"));
 }
 
 /// --help exits 0 and prints usage (integration: run binary).
@@ -110,3 +110,169 @@ fn test_cli_help_exits_zero_and_prints_usage() {
         stdout
     );
 }
+
+#[test]
+fn test_cli_no_slash_redirect_alias() {
+    let args = ["webserve", "--no-slash-redirect"];
+    let options = ServeOptions::from_iter(args.iter());
+    assert!(options.no_redirect_dir_slash);
+}
+
+#[test]
+fn test_cli_log_level_and_verbosity() {
+    use tracing::level_filters::LevelFilter;
+    use tracing_subscriber::EnvFilter;
+    use webserve::{log_filter, log_level};
+
+    let same = |a: EnvFilter, b: &str| assert_eq!(a.to_string(), EnvFilter::new(b).to_string());
+
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    assert_eq!(log_level(&options), None);
+    same(log_filter(&options, None), "warn,webserve=info");
+    same(
+        log_filter(&options, Some("webserve=trace")),
+        "webserve=trace",
+    );
+
+    let options = ServeOptions::from_iter(["webserve", "-v"].iter());
+    assert_eq!(log_level(&options), Some(LevelFilter::DEBUG));
+    let options = ServeOptions::from_iter(["webserve", "-vv"].iter());
+    assert_eq!(log_level(&options), Some(LevelFilter::TRACE));
+
+    let options = ServeOptions::from_iter(["webserve", "-vv", "--log-level", "warn"].iter());
+    assert_eq!(log_level(&options), Some(LevelFilter::WARN));
+    same(log_filter(&options, Some("trace")), "warn,webserve=warn");
+}
+
+#[test]
+fn test_cli_strict_port_fails_when_port_busy() {
+    let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = busy.local_addr().unwrap().port().to_string();
+    let temp_dir = TempDir::new().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--strict-port", "-p", &port, "-d"])
+        .arg(temp_dir.path())
+        .output()
+        .expect("run webserve binary");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("already in use"), "stderr: {}", stderr);
+}
+
+#[test]
+fn test_cli_busy_port_falls_back_to_next() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = busy.local_addr().unwrap().port();
+    let temp_dir = TempDir::new().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["-p", &port.to_string(), "-d"])
+        .arg(temp_dir.path())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run webserve binary");
+    let stdout = child.stdout.take().unwrap();
+    let serving = BufReader::new(stdout)
+        .lines()
+        .map_while(Result::ok)
+        .find(|line| line.contains("Serving on"));
+    let _ = child.kill();
+    let _ = child.wait();
+    let serving = serving.expect("server reports its URL");
+    assert!(
+        !serving.ends_with(&format!(":{}", port)),
+        "should not bind the busy port: {}",
+        serving
+    );
+}
+
+#[test]
+fn test_cli_open_with_optional_path() {
+    use webserve::browser_url;
+
+    let options = ServeOptions::from_iter(["webserve", "--open", "/docs/", "-p", "3000"].iter());
+    assert_eq!(options.open_path(), Some("/docs/"));
+    assert_eq!(options.port, 3000);
+    let options = ServeOptions::from_iter(["webserve", "--open", "--spa"].iter());
+    assert_eq!(options.open_path(), Some("/"));
+    assert!(options.spa);
+
+    assert_eq!(browser_url("0.0.0.0", 8081, "/"), "http://127.0.0.1:8081/");
+    assert_eq!(
+        browser_url("localhost", 80, "docs/?q=1#a"),
+        "http://localhost:80/docs/?q=1#a"
+    );
+    assert_eq!(browser_url("::", 8080, "/"), "http://[::1]:8080/");
+    assert_eq!(browser_url("fe80::1", 8080, "/"), "http://[fe80::1]:8080/");
+}
+
+#[test]
+fn test_cli_repeated_hosts_and_ipv6_literals() {
+    use webserve::{bind_host, host_port, resolve_options_with_env, FileConfig, HostSetting};
+
+    let options = ServeOptions::from_iter(["webserve", "--host", "::1", "-h", "127.0.0.1"].iter());
+    assert_eq!(options.hosts, vec!["::1", "127.0.0.1"]);
+
+    let config = FileConfig::parse("host = [\"::1\", \"127.0.0.1\"]").unwrap();
+    assert_eq!(
+        config.host,
+        Some(HostSetting::Many(vec!["::1".into(), "127.0.0.1".into()]))
+    );
+    let env = vec![("WEBSERVE_HOST".to_string(), "::, 0.0.0.0".to_string())];
+    let options = resolve_options_with_env(["webserve"], env).unwrap();
+    assert_eq!(options.hosts, vec!["::", "0.0.0.0"]);
+
+    let options =
+        resolve_options_with_env(["webserve", "-h", "::1", "-h", "[::1]"], Vec::new()).unwrap();
+    assert_eq!(options.hosts, vec!["::1"]);
+
+    assert_eq!(bind_host("[::1]"), "::1");
+    assert_eq!(host_port("::1", 8080), "[::1]:8080");
+    assert_eq!(host_port("[::1]", 8080), "[::1]:8080");
+    assert_eq!(host_port("127.0.0.1", 8080), "127.0.0.1:8080");
+}
+
+#[test]
+fn test_cli_binds_every_host() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    if std::net::TcpListener::bind("[::1]:0").is_err() {
+        return; // no IPv6 loopback in this environment
+    }
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+        .to_string();
+    let temp_dir = TempDir::new().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args([
+            "--strict-port",
+            "-p",
+            &port,
+            "-h",
+            "::1",
+            "-h",
+            "127.0.0.1",
+            "-d",
+        ])
+        .arg(temp_dir.path())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run webserve binary");
+    let stdout = child.stdout.take().unwrap();
+    let serving: Vec<String> = BufReader::new(stdout)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| line.contains("Serving on"))
+        .take(2)
+        .collect();
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(serving[0].ends_with(&format!("http://[::1]:{}", port)));
+    assert!(serving[1].ends_with(&format!("http://127.0.0.1:{}", port)));
+}
@@ -0,0 +1,20 @@
+//! Fixtures shared by the integration tests.
+
+use std::fs;
+use tempfile::TempDir;
+
+/// A temporary site holding `files` as `(path, contents)`, parent directories created; a
+/// path ending in `/` is an empty directory.
+pub fn site(files: &[(&str, &str)]) -> TempDir {
+    let temp = TempDir::new().unwrap();
+    for (path, contents) in files {
+        let full = temp.path().join(path);
+        if path.ends_with('/') {
+            fs::create_dir_all(full).unwrap();
+            continue;
+        }
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, contents).unwrap();
+    }
+    temp
+}
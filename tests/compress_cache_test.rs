@@ -0,0 +1,128 @@
+//! `--compress-cache` on-disk store of compressed files.
+
+use actix_web::http::header;
+use actix_web::middleware::from_fn;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    compress, resolve_options_with_env, serve_file, AdaptiveCompression, AppState, CacheSetting,
+    CompressCache, Encoding, FileConfig, ServeOptions, Settings,
+};
+
+#[test]
+fn compress_cache_takes_a_switch_or_a_directory() {
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    assert_eq!(options.compress_cache, None);
+    assert_eq!(options.compress_cache_size, 256);
+    let options = ServeOptions::from_iter(["webserve", "--compress-cache", "cache"].iter());
+    assert_eq!(options.compress_cache, Some(Some(PathBuf::from("cache"))));
+    let options = ServeOptions::from_iter(["webserve", "--compress-cache"].iter());
+    assert_eq!(options.compress_cache, Some(None));
+
+    assert_eq!(
+        FileConfig::parse("compress-cache = true")
+            .unwrap()
+            .compress_cache,
+        Some(CacheSetting::Flag(true))
+    );
+    let env = vec![("WEBSERVE_COMPRESS_CACHE".to_string(), "on".to_string())];
+    let options = resolve_options_with_env(["webserve"], env).unwrap();
+    assert_eq!(options.compress_cache, Some(None));
+}
+
+#[test]
+fn evicts_least_recently_used_and_survives_reopen() {
+    let temp = TempDir::new().unwrap();
+    let cache = CompressCache::open(temp.path(), 250).unwrap();
+    let a = CompressCache::key("/a.js", "v1", Encoding::Gzip);
+    let b = CompressCache::key("/b.js", "v1", Encoding::Gzip);
+    let c = CompressCache::key("/c.js", "v1", Encoding::Gzip);
+    assert_ne!(a, CompressCache::key("/a.js", "v2", Encoding::Gzip));
+    assert_ne!(a, CompressCache::key("/a.js", "v1", Encoding::Brotli));
+
+    cache.put(&a, &[1; 100]).unwrap();
+    cache.put(&b, &[2; 100]).unwrap();
+    assert_eq!(cache.get(&a), Some(vec![1; 100]));
+    cache.put(&c, &[3; 100]).unwrap();
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.size(), 200);
+    assert_eq!(cache.get(&b), None, "least recently used entry is evicted");
+    assert!(!temp.path().join(&b).exists());
+
+    // Larger than the whole budget: not stored at all.
+    cache.put(&b, &[2; 300]).unwrap();
+    assert_eq!(cache.get(&b), None);
+
+    let reopened = CompressCache::open(temp.path(), 250).unwrap();
+    assert_eq!(reopened.len(), 2);
+    assert_eq!(reopened.get(&c), Some(vec![3; 100]));
+    let shrunk = CompressCache::open(temp.path(), 150).unwrap();
+    assert_eq!(shrunk.len(), 1);
+}
+
+#[actix_web::test]
+async fn serves_cached_bodies_even_under_load() {
+    use actix_web::test;
+
+    let root = TempDir::new().unwrap();
+    let store = TempDir::new().unwrap();
+    let css = "main { display: grid; }\n".repeat(200);
+    fs::write(root.path().join("site.css"), &css).unwrap();
+    let cache = Arc::new(CompressCache::open(store.path(), 1 << 20).unwrap());
+
+    let app_at = |load: u8| {
+        web::Data::new(AppState {
+            static_dir: Arc::new(root.path().to_path_buf()),
+            settings: Arc::new(
+                Settings {
+                    compress: true,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            compression: Arc::new(AdaptiveCompression::fixed(load)),
+            compress_cache: Some(cache.clone()),
+            ..Default::default()
+        })
+    };
+    let get = || {
+        test::TestRequest::get()
+            .uri("/site.css")
+            .insert_header((header::ACCEPT_ENCODING, "gzip"))
+            .to_request()
+    };
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_at(10))
+            .wrap(from_fn(compress))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, get()).await;
+    assert_eq!(res.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+    let first = test::read_body(res).await;
+    assert_eq!(cache.len(), 1);
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_at(95))
+            .wrap(from_fn(compress))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, get()).await;
+    assert_eq!(res.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+    assert!(res
+        .headers()
+        .get(header::ETAG)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("W/"));
+    assert_eq!(test::read_body(res).await, first);
+}
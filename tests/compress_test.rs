@@ -0,0 +1,132 @@
+//! `--compress` response compression and its CPU-load back-off.
+
+use actix_web::http::header;
+use actix_web::middleware::from_fn;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::io::Read;
+use std::sync::Arc;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    compress, is_compressible, serve_file, AdaptiveCompression, AppState, CompressLevel, Encoding,
+    ServeOptions, Settings,
+};
+
+#[test]
+fn compress_cpu_limit_must_be_positive() {
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    assert!(!options.compress);
+    assert_eq!(options.compress_cpu_limit, 80);
+    let options = ServeOptions::from_iter(["webserve", "--compress-cpu-limit", "0"].iter());
+    assert!(Settings::from_options(&options).is_err());
+}
+
+#[test]
+fn negotiates_encoding_and_level() {
+    assert_eq!(
+        Encoding::negotiate("gzip, deflate, br"),
+        Some(Encoding::Brotli)
+    );
+    assert_eq!(Encoding::negotiate("gzip, br;q=0"), Some(Encoding::Gzip));
+    assert_eq!(Encoding::negotiate("*"), Some(Encoding::Brotli));
+    assert_eq!(Encoding::negotiate("identity"), None);
+
+    assert_eq!(CompressLevel::for_load(None, 80), CompressLevel::Normal);
+    assert_eq!(CompressLevel::for_load(Some(20), 80), CompressLevel::Normal);
+    assert_eq!(CompressLevel::for_load(Some(40), 80), CompressLevel::Fast);
+    assert_eq!(CompressLevel::for_load(Some(80), 80), CompressLevel::Off);
+
+    assert!(is_compressible("text/html; charset=utf-8"));
+    assert!(is_compressible("application/manifest+json"));
+    assert!(!is_compressible("image/png"));
+}
+
+#[actix_web::test]
+async fn compresses_text_and_backs_off_under_load() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    let css = "body { color: rebeccapurple; }\n".repeat(200);
+    fs::write(temp.path().join("site.css"), &css).unwrap();
+    fs::write(temp.path().join("tiny.css"), "a{}").unwrap();
+    fs::write(temp.path().join("photo.png"), vec![7u8; 4096]).unwrap();
+
+    let app_at = |load: u8| {
+        web::Data::new(AppState {
+            static_dir: Arc::new(temp.path().to_path_buf()),
+            settings: Arc::new(
+                Settings {
+                    compress: true,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            compression: Arc::new(AdaptiveCompression::fixed(load)),
+            ..Default::default()
+        })
+    };
+    let get = |uri: &str, accept: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .insert_header((header::ACCEPT_ENCODING, accept.to_string()))
+            .to_request()
+    };
+
+    for load in [10, 50] {
+        let app = test::init_service(
+            ActixApp::new()
+                .app_data(app_at(load))
+                .wrap(from_fn(compress))
+                .route("/{_:.*}", web::get().to(serve_file)),
+        )
+        .await;
+
+        let res = test::call_service(&app, get("/site.css", "gzip")).await;
+        assert_eq!(res.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+        let etag = res.headers().get(header::ETAG).unwrap().to_str().unwrap();
+        assert!(etag.starts_with("W/\""), "{}", etag);
+        let body = test::read_body(res).await;
+        assert!(body.len() < css.len());
+        let mut plain = String::new();
+        flate2::read::GzDecoder::new(body.as_ref())
+            .read_to_string(&mut plain)
+            .unwrap();
+        assert_eq!(plain, css);
+
+        let res = test::call_service(&app, get("/site.css", "gzip, br")).await;
+        assert_eq!(res.headers().get(header::CONTENT_ENCODING).unwrap(), "br");
+        let body = test::read_body(res).await;
+        let mut plain = String::new();
+        brotli::Decompressor::new(body.as_ref(), 4096)
+            .read_to_string(&mut plain)
+            .unwrap();
+        assert_eq!(plain, css);
+
+        for (uri, accept) in [
+            ("/tiny.css", "gzip"),
+            ("/photo.png", "gzip"),
+            ("/site.css", ""),
+        ] {
+            let res = test::call_service(&app, get(uri, accept)).await;
+            assert!(
+                !res.headers().contains_key(header::CONTENT_ENCODING),
+                "{}",
+                uri
+            );
+        }
+    }
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_at(95))
+            .wrap(from_fn(compress))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, get("/site.css", "gzip, br")).await;
+    assert!(!res.headers().contains_key(header::CONTENT_ENCODING));
+    assert_eq!(res.headers().get(header::VARY).unwrap(), "Accept-Encoding");
+    assert_eq!(test::read_body(res).await, css.as_bytes());
+}
@@ -0,0 +1,631 @@
+//! Config file parsing and CLI-over-file precedence.
+
+use std::fs;
+use tempfile::TempDir;
+use webserve::{parse_header, resolve_options, FileConfig};
+
+#[test]
+fn file_config_parses_known_keys_and_rejects_unknown() {
+    let config = FileConfig::parse(
+        r#"
+port = 3000
+spa = true
+redirect-dir-slash = false
+rewrites = ["^/v1/(.*)=/api/$1"]
+replace-regex = ["*.html=https?://example\\.com/=>/"]
+
+[headers]
+X-Frame-Options = "DENY"
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.port, Some(3000));
+    assert_eq!(config.spa, Some(true));
+    assert_eq!(config.redirect_dir_slash, Some(false));
+    assert_eq!(config.rewrites.len(), 1);
+    assert_eq!(config.replace_regex, ["*.html=https?://example\\.com/=>/"]);
+    assert_eq!(config.headers["X-Frame-Options"], "DENY");
+
+    assert!(FileConfig::parse("prot = 3000").is_err());
+    assert!(FileConfig::parse("port = \"abc\"").is_err());
+}
+
+#[test]
+fn cli_flags_override_config_file() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("public")).unwrap();
+    let config_path = temp.path().join("webserve.toml");
+    fs::write(
+        &config_path,
+        "port = 3000\nhost = \"0.0.0.0\"\ndir = \"public\"\nwatch = true\n",
+    )
+    .unwrap();
+
+    let options = resolve_options([
+        "webserve",
+        "--config",
+        config_path.to_str().unwrap(),
+        "--port",
+        "9000",
+    ])
+    .unwrap();
+    assert_eq!(options.port, 9000);
+    assert_eq!(options.hosts, vec!["0.0.0.0"]);
+    assert_eq!(options.directory.unwrap(), temp.path().join("public"));
+    assert!(options.watch);
+    assert!(!options.spa);
+}
+
+#[test]
+fn missing_or_invalid_config_file_is_an_error() {
+    let temp = TempDir::new().unwrap();
+    let missing = temp.path().join("nope.toml");
+    let err = resolve_options(["webserve", "--config", missing.to_str().unwrap()]).unwrap_err();
+    assert!(err.contains("cannot read config"), "{}", err);
+
+    let bad = temp.path().join("bad.toml");
+    fs::write(&bad, "unknown-key = 1\n").unwrap();
+    let err = resolve_options(["webserve", "--config", bad.to_str().unwrap()]).unwrap_err();
+    assert!(err.contains("invalid config"), "{}", err);
+}
+
+#[test]
+fn parse_header_spec() {
+    let (name, value) = parse_header("X-Frame-Options: DENY").unwrap();
+    assert_eq!(name.as_str(), "x-frame-options");
+    assert_eq!(value.to_str().unwrap(), "DENY");
+    assert!(parse_header("no-colon").is_err());
+    assert!(parse_header("Bad Name: x").is_err());
+}
+
+fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn env_vars_sit_between_config_file_and_cli() {
+    use webserve::resolve_options_with_env;
+
+    let temp = TempDir::new().unwrap();
+    let config_path = temp.path().join("webserve.toml");
+    fs::write(
+        &config_path,
+        "port = 3000\nhost = \"0.0.0.0\"\nspa = false\n",
+    )
+    .unwrap();
+    let vars = env(&[
+        ("WEBSERVE_CONFIG", config_path.to_str().unwrap()),
+        ("WEBSERVE_PORT", "4000"),
+        ("WEBSERVE_SPA", "yes"),
+        ("WEBSERVE_HOOK_VERSION_CHECK", "1"),
+        ("HOME", "/tmp"),
+    ]);
+
+    let options = resolve_options_with_env(["webserve"], vars.clone()).unwrap();
+    assert_eq!(options.port, 4000);
+    assert_eq!(options.hosts, vec!["0.0.0.0"]);
+    assert!(options.spa);
+    assert_eq!(options.config.as_deref(), Some(config_path.as_path()));
+
+    let options = resolve_options_with_env(["webserve", "-p", "5000"], vars).unwrap();
+    assert_eq!(options.port, 5000);
+}
+
+#[test]
+fn unknown_env_vars_are_reported_not_applied() {
+    use webserve::unknown_env_vars;
+
+    let vars = env(&[
+        ("WEBSERVE_PROT", "4000"),
+        ("WEBSERVE_PORT", "3000"),
+        ("WEBSERVE_CONFIG", "webserve.toml"),
+        ("HOME", "/tmp"),
+    ]);
+    assert_eq!(unknown_env_vars(vars.clone()), ["WEBSERVE_PROT"]);
+    let config = FileConfig::from_env(vars).unwrap();
+    assert_eq!(config.port, Some(3000));
+}
+
+#[test]
+fn env_vars_reject_bad_values() {
+    let err = FileConfig::from_env(env(&[("WEBSERVE_PORT", "http")])).unwrap_err();
+    assert!(err.contains("WEBSERVE_PORT"), "{}", err);
+    let err = FileConfig::from_env(env(&[("WEBSERVE_WATCH", "maybe")])).unwrap_err();
+    assert!(err.contains("WEBSERVE_WATCH"), "{}", err);
+    let err = FileConfig::from_env(env(&[("WEBSERVE_THROTTLE", "4294967296")])).unwrap_err();
+    assert_eq!(err, "WEBSERVE_THROTTLE: 4294967296 is out of range");
+    let err = FileConfig::from_env(env(&[("WEBSERVE_HEADER", "X-Frame-Options")])).unwrap_err();
+    assert!(err.contains("WEBSERVE_HEADER"), "{}", err);
+}
+
+#[test]
+fn env_rules_and_headers_take_one_entry_per_line() {
+    let config = FileConfig::from_env(env(&[
+        ("WEBSERVE_REDIRECT", "/old=/new:301\n/a,b=/c"),
+        ("WEBSERVE_REWRITE", "^/v(\\d{1,2})/(.*)=/api/$2"),
+        ("WEBSERVE_REPLACE", "*.html=a, b=>c\n\n*.css=x=>y"),
+        ("WEBSERVE_REPLACE_REGEX", "*.js=https?://[a-z]+/=>/"),
+        (
+            "WEBSERVE_HEADER",
+            "Cache-Control: no-cache, no-store\nX-Frame-Options: DENY",
+        ),
+        ("WEBSERVE_MAX_REQUEST_SIZE", "1048576"),
+    ]))
+    .unwrap();
+    assert_eq!(config.redirects, ["/old=/new:301", "/a,b=/c"]);
+    assert_eq!(config.rewrites, ["^/v(\\d{1,2})/(.*)=/api/$2"]);
+    assert_eq!(config.replace, ["*.html=a, b=>c", "*.css=x=>y"]);
+    assert_eq!(config.replace_regex, ["*.js=https?://[a-z]+/=>/"]);
+    assert_eq!(config.headers["Cache-Control"], "no-cache, no-store");
+    assert_eq!(config.headers["X-Frame-Options"], "DENY");
+    assert_eq!(config.max_request_size, Some(1048576));
+}
+
+#[actix_web::test]
+async fn live_settings_swap_applies_to_next_request() {
+    use actix_web::http::header::{HeaderName, HeaderValue};
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, web, App as ActixApp};
+    use std::sync::Arc;
+    use webserve::{extra_headers, serve_file, AppState, LiveSettings, Settings};
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), b"<p>app</p>").unwrap();
+    let settings = Arc::new(LiveSettings::default());
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        settings: settings.clone(),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .wrap(from_fn(extra_headers))
+            .app_data(app_state)
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/client/route").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+    settings.store(Settings {
+        spa: true,
+        headers: vec![(
+            HeaderName::from_static("x-frame-options"),
+            HeaderValue::from_static("DENY"),
+        )],
+        ..Default::default()
+    });
+    let req = test::TestRequest::get().uri("/client/route").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("x-frame-options").unwrap(), "DENY");
+}
+
+#[actix_web::test]
+async fn reloaded_settings_drop_cached_injected_pages() {
+    use actix_web::{test, web, App as ActixApp};
+    use std::sync::Arc;
+    use webserve::{serve_file, AppState, Settings};
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), b"<p>app</p>").unwrap();
+    let state = AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        watch: true,
+        html_cache: Some(Default::default()),
+        ..Default::default()
+    };
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(web::Data::new(state.clone()))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let page = || async {
+        let req = test::TestRequest::get().uri("/index.html").to_request();
+        String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap()
+    };
+
+    assert!(!page().await.contains("/__webserve/open-in-editor"));
+    state.reload_settings(Settings {
+        open_in_editor: true,
+        ..Default::default()
+    });
+    assert!(page().await.contains("/__webserve/open-in-editor"));
+}
+
+#[test]
+fn open_accepts_a_switch_or_a_path() {
+    use webserve::{resolve_options_with_env, OpenSetting};
+
+    let config = FileConfig::parse("open = \"/docs/\"").unwrap();
+    assert_eq!(config.open, Some(OpenSetting::Path("/docs/".into())));
+    let config = FileConfig::parse("open = true").unwrap();
+    assert_eq!(config.open, Some(OpenSetting::Flag(true)));
+
+    let temp = TempDir::new().unwrap();
+    let config_path = temp.path().join("webserve.toml");
+    fs::write(&config_path, "open = \"/guide/\"\n").unwrap();
+    let config_arg = config_path.to_str().unwrap();
+    let options =
+        resolve_options_with_env(["webserve", "--config", config_arg], Vec::new()).unwrap();
+    assert_eq!(options.open_path(), Some("/guide/"));
+
+    let options = resolve_options_with_env(
+        ["webserve", "--config", config_arg],
+        env(&[("WEBSERVE_OPEN", "off")]),
+    )
+    .unwrap();
+    assert_eq!(options.open_path(), None);
+
+    let options =
+        resolve_options_with_env(["webserve", "--config", config_arg, "--open"], Vec::new())
+            .unwrap();
+    assert_eq!(options.open_path(), Some("/"));
+}
+
+#[test]
+fn export_config_round_trips_the_merged_options() {
+    use webserve::{export_config, resolve_options_with_env};
+
+    let temp = TempDir::new().unwrap();
+    let site = temp.path().join("dist");
+    let out = temp.path().join("webserve.toml");
+    let options = resolve_options_with_env(
+        [
+            "webserve",
+            "-p",
+            "3000",
+            "-h",
+            "0.0.0.0",
+            "-d",
+            site.to_str().unwrap(),
+            "--no-redirect-dir-slash",
+            "--latency",
+            "1.5s",
+            "--header",
+            "X-Frame-Options: DENY",
+            "--open",
+            "--export-config",
+            out.to_str().unwrap(),
+        ],
+        env(&[("WEBSERVE_ETAG", "sha256"), ("WEBSERVE_SPA", "1")]),
+    )
+    .unwrap();
+    export_config(&options, &out).unwrap();
+
+    let written = fs::read_to_string(&out).unwrap();
+    assert!(written.contains("port = 3000\n"), "{}", written);
+    assert!(written.contains("host = \"0.0.0.0\"\n"));
+    assert!(written.contains(&format!("dir = \"{}\"\n", site.display())));
+    assert!(written.contains("redirect-dir-slash = false\n"));
+    assert!(written.contains("latency = \"1500ms\"\n"));
+    assert!(written.contains("etag = \"sha256\"\n"));
+    assert!(written.contains("[headers]\nX-Frame-Options = \"DENY\"\n"));
+    assert!(
+        !written.contains("export"),
+        "the flag itself is not exported"
+    );
+    assert!(!written.contains("watch"), "defaults are left out");
+
+    let reloaded =
+        resolve_options_with_env(["webserve", "--config", out.to_str().unwrap()], Vec::new())
+            .unwrap();
+    assert_eq!(reloaded.port, 3000);
+    assert_eq!(reloaded.hosts, vec!["0.0.0.0"]);
+    assert_eq!(reloaded.directory.as_deref(), Some(site.as_path()));
+    assert!(reloaded.spa && reloaded.no_redirect_dir_slash);
+    assert_eq!(reloaded.latency, options.latency);
+    assert_eq!(reloaded.etag, options.etag);
+    assert_eq!(reloaded.headers, options.headers);
+    assert_eq!(reloaded.open_path(), Some("/"));
+}
+
+#[test]
+fn export_of_defaults_is_empty() {
+    use structopt::StructOpt;
+    use webserve::ServeOptions;
+
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    let config = FileConfig::from_options(&options, std::path::Path::new(""));
+    assert_eq!(config, FileConfig::default());
+    assert_eq!(config.to_toml().unwrap(), "");
+}
+
+#[test]
+fn default_options_match_a_bare_command_line() {
+    use structopt::StructOpt;
+    use webserve::ServeOptions;
+
+    assert_eq!(
+        format!("{:?}", ServeOptions::default()),
+        format!("{:?}", ServeOptions::from_iter(["webserve"].iter()))
+    );
+}
+
+/// One option group set on each layer: command-line flags, config file keys and
+/// `WEBSERVE_*` variables.
+type Layers = (
+    &'static [&'static str],
+    &'static str,
+    &'static [(&'static str, &'static str)],
+);
+
+/// Option groups that mean the same on every layer. Paths are absolute so the config
+/// file's directory does not change them; list and table values are in the order the file
+/// and environment sort them.
+const LAYERS: &[Layers] = &[
+    (
+        &["--open-in-editor"],
+        "open-in-editor = true",
+        &[("WEBSERVE_OPEN_IN_EDITOR", "on")],
+    ),
+    (
+        &["--admin-remote"],
+        "admin-remote = true",
+        &[("WEBSERVE_ADMIN_REMOTE", "1")],
+    ),
+    (
+        &["--mdns", "my-site"],
+        "mdns = \"my-site\"",
+        &[("WEBSERVE_MDNS", "my-site")],
+    ),
+    (
+        &["--listing-lang", "pt-BR"],
+        "listing-lang = \"pt-BR\"",
+        &[("WEBSERVE_LISTING_LANG", "pt-BR")],
+    ),
+    (
+        &["--watch", "--exec", "npm run build"],
+        "watch = true\nexec = \"npm run build\"",
+        &[("WEBSERVE_WATCH", "1"), ("WEBSERVE_EXEC", "npm run build")],
+    ),
+    (
+        &["--watch", "--watch-path", "/src", "--watch-path", "/styles"],
+        "watch = true\nwatch-paths = [\"/src\", \"/styles\"]",
+        &[("WEBSERVE_WATCH", "1"), ("WEBSERVE_WATCH_PATH", "/src, /styles")],
+    ),
+    (
+        &["--compress", "--compress-cpu-limit", "60"],
+        "compress = true\ncompress-cpu-limit = 60",
+        &[
+            ("WEBSERVE_COMPRESS", "on"),
+            ("WEBSERVE_COMPRESS_CPU_LIMIT", "60"),
+        ],
+    ),
+    (
+        &["--compress-cache", "/tmp/c", "--compress-cache-size", "64"],
+        "compress-cache = \"/tmp/c\"\ncompress-cache-size = 64",
+        &[
+            ("WEBSERVE_COMPRESS_CACHE", "/tmp/c"),
+            ("WEBSERVE_COMPRESS_CACHE_SIZE", "64"),
+        ],
+    ),
+    (
+        &["--mime", "glb=model/gltf-binary", "--mime", "wasm=application/wasm"],
+        "[mime]\nglb = \"model/gltf-binary\"\nwasm = \"application/wasm\"",
+        &[(
+            "WEBSERVE_MIME",
+            "glb=model/gltf-binary, wasm=application/wasm",
+        )],
+    ),
+    (
+        &["--charset", "latin1", "--charset-ext", "csv=windows-1252", "--charset-ext", "txt=shift_jis"],
+        "charset = \"latin1\"\n[charset-ext]\ntxt = \"shift_jis\"\ncsv = \"windows-1252\"",
+        &[
+            ("WEBSERVE_CHARSET", "latin1"),
+            ("WEBSERVE_CHARSET_EXT", "txt=shift_jis, csv=windows-1252"),
+        ],
+    ),
+    (
+        &["--mock", "/srv/db.json", "--mock-prefix", "/api", "--mock-persist"],
+        "mock = \"/srv/db.json\"\nmock-prefix = \"/api\"\nmock-persist = true",
+        &[
+            ("WEBSERVE_MOCK", "/srv/db.json"),
+            ("WEBSERVE_MOCK_PREFIX", "/api"),
+            ("WEBSERVE_MOCK_PERSIST", "1"),
+        ],
+    ),
+    (
+        &["--uds", "/run/w.sock"],
+        "uds = \"/run/w.sock\"",
+        &[("WEBSERVE_UDS", "/run/w.sock")],
+    ),
+    (
+        &["--systemd"],
+        "systemd = true",
+        &[("WEBSERVE_SYSTEMD", "yes")],
+    ),
+    (
+        &["--io-uring"],
+        "io-uring = true",
+        &[("WEBSERVE_IO_URING", "on")],
+    ),
+    (
+        &["--tls-cert", "/etc/c.pem", "--tls-key", "/etc/k.pem", "--http1-only", "--redirect-http", "8000"],
+        "tls-cert = \"/etc/c.pem\"\ntls-key = \"/etc/k.pem\"\nhttp1-only = true\nredirect-http = 8000",
+        &[
+            ("WEBSERVE_TLS_CERT", "/etc/c.pem"),
+            ("WEBSERVE_TLS_KEY", "/etc/k.pem"),
+            ("WEBSERVE_HTTP1_ONLY", "1"),
+            ("WEBSERVE_REDIRECT_HTTP", "8000"),
+        ],
+    ),
+    (
+        &["--daemon", "--pid-file", "/run/w.pid", "--daemon-log", "/run/w.out"],
+        "daemon = true\npid-file = \"/run/w.pid\"\ndaemon-log = \"/run/w.out\"",
+        &[
+            ("WEBSERVE_DAEMON", "yes"),
+            ("WEBSERVE_PID_FILE", "/run/w.pid"),
+            ("WEBSERVE_DAEMON_LOG", "/run/w.out"),
+        ],
+    ),
+    (
+        &["--workers", "2", "--max-connections", "500", "--keep-alive", "30s", "--client-timeout", "250ms"],
+        "workers = 2\nmax-connections = 500\nkeep-alive = \"30s\"\nclient-timeout = \"250ms\"",
+        &[
+            ("WEBSERVE_WORKERS", "2"),
+            ("WEBSERVE_MAX_CONNECTIONS", "500"),
+            ("WEBSERVE_KEEP_ALIVE", "30s"),
+            ("WEBSERVE_CLIENT_TIMEOUT", "250ms"),
+        ],
+    ),
+    (
+        &["--slowloris-guard", "--header-timeout", "750ms", "--min-rate", "64"],
+        "slowloris-guard = true\nheader-timeout = \"750ms\"\nmin-rate = 64",
+        &[
+            ("WEBSERVE_SLOWLORIS_GUARD", "1"),
+            ("WEBSERVE_HEADER_TIMEOUT", "750ms"),
+            ("WEBSERVE_MIN_RATE", "64"),
+        ],
+    ),
+    (
+        &["--max-request-size", "8", "--read-timeout", "90s", "--allow-method", "put", "--allow-method", "delete"],
+        "max-request-size = 8\nread-timeout = \"90s\"\nallow-methods = [\"put\", \"delete\"]",
+        &[
+            ("WEBSERVE_MAX_REQUEST_SIZE", "8"),
+            ("WEBSERVE_READ_TIMEOUT", "90s"),
+            ("WEBSERVE_ALLOW_METHODS", "put, delete"),
+        ],
+    ),
+    (
+        &["--harden", "--max-header-bytes", "4096"],
+        "harden = true\nmax-header-bytes = 4096",
+        &[
+            ("WEBSERVE_HARDEN", "on"),
+            ("WEBSERVE_MAX_HEADER_BYTES", "4096"),
+        ],
+    ),
+    (
+        &["--allow", "10.1.0.0/16", "--allow", "10.2.0.0/16", "--deny", "0.0.0.0/0"],
+        "allow = [\"10.1.0.0/16\", \"10.2.0.0/16\"]\ndeny = [\"0.0.0.0/0\"]",
+        &[
+            ("WEBSERVE_ALLOW", "10.1.0.0/16, 10.2.0.0/16"),
+            ("WEBSERVE_DENY", "0.0.0.0/0"),
+        ],
+    ),
+    (
+        &["--throttle", "750", "--latency", "300ms"],
+        "throttle = 750\nlatency = \"300ms\"",
+        &[("WEBSERVE_THROTTLE", "750"), ("WEBSERVE_LATENCY", "300ms")],
+    ),
+    (
+        &["--upload", "--max-upload-size", "7"],
+        "upload = true\nmax-upload-size = 7",
+        &[("WEBSERVE_UPLOAD", "1"), ("WEBSERVE_MAX_UPLOAD_SIZE", "7")],
+    ),
+    (
+        &["--webdav"],
+        "webdav = true",
+        &[("WEBSERVE_WEBDAV", "on")],
+    ),
+    (
+        &["--render-markdown"],
+        "render-markdown = true",
+        &[("WEBSERVE_RENDER_MARKDOWN", "yes")],
+    ),
+    (
+        &["--highlight"],
+        "highlight = true",
+        &[("WEBSERVE_HIGHLIGHT", "on")],
+    ),
+    (
+        &["--log-exclude", "/favicon.ico", "--log-exclude", "/__webserve/*", "--log-sample", "/api/*=0.5"],
+        "log-exclude = [\"/favicon.ico\", \"/__webserve/*\"]\nlog-sample = [\"/api/*=0.5\"]",
+        &[
+            ("WEBSERVE_LOG_EXCLUDE", "/favicon.ico, /__webserve/*"),
+            ("WEBSERVE_LOG_SAMPLE", "/api/*=0.5"),
+        ],
+    ),
+    (
+        &["--brand-title", "Acme", "--brand-logo", "https://cdn.test/l.svg", "--brand-footer", "Support"],
+        "brand-title = \"Acme\"\nbrand-logo = \"https://cdn.test/l.svg\"\nbrand-footer = \"Support\"",
+        &[
+            ("WEBSERVE_BRAND_TITLE", "Acme"),
+            ("WEBSERVE_BRAND_LOGO", "https://cdn.test/l.svg"),
+            ("WEBSERVE_BRAND_FOOTER", "Support"),
+        ],
+    ),
+    (
+        &["--store", "sled:/var/lib/ws"],
+        "store = \"sled:/var/lib/ws\"",
+        &[("WEBSERVE_STORE", "sled:/var/lib/ws")],
+    ),
+    (
+        &["--events-token", "abc"],
+        "events-token = \"abc\"",
+        &[("WEBSERVE_EVENTS_TOKEN", "abc")],
+    ),
+    (
+        &["--plugin", "/p/a.wasm", "--plugin", "/p/b.wasm"],
+        "plugins = [\"/p/a.wasm\", \"/p/b.wasm\"]",
+        &[("WEBSERVE_PLUGIN", "/p/a.wasm, /p/b.wasm")],
+    ),
+    (
+        &["--file", "/srv/report.html"],
+        "file = \"/srv/report.html\"",
+        &[("WEBSERVE_FILE", "/srv/report.html")],
+    ),
+    (
+        &["--mount", "/docs=/srv/docs", "--vhost", "blog.localhost=/srv/blog"],
+        "[mounts]\n\"/docs\" = \"/srv/docs\"\n[vhosts]\n\"blog.localhost\" = \"/srv/blog\"",
+        &[
+            ("WEBSERVE_MOUNT", "/docs=/srv/docs"),
+            ("WEBSERVE_VHOST", "blog.localhost=/srv/blog"),
+        ],
+    ),
+    (
+        &[
+            "--proxy",
+            "/api=http://localhost:3000",
+            "--proxy",
+            "/ws=http://localhost:4000",
+            "--proxy-allow",
+            "localhost:3000",
+            "--proxy-allow",
+            "localhost:4000",
+            "--proxy-deny-private",
+        ],
+        "proxy-allow = [\"localhost:3000\", \"localhost:4000\"]\nproxy-deny-private = true\n[proxies]\n\"/ws\" = \"http://localhost:4000\"\n\"/api\" = \"http://localhost:3000\"",
+        &[
+            (
+                "WEBSERVE_PROXY",
+                "/ws=http://localhost:4000, /api=http://localhost:3000",
+            ),
+            ("WEBSERVE_PROXY_ALLOW", "localhost:3000, localhost:4000"),
+            ("WEBSERVE_PROXY_DENY_PRIVATE", "yes"),
+        ],
+    ),
+];
+
+#[test]
+fn options_parse_the_same_from_every_layer() {
+    use webserve::resolve_options_with_env;
+
+    let temp = TempDir::new().unwrap();
+    let config_path = temp.path().join("webserve.toml");
+    let config_arg = config_path.to_str().unwrap();
+    let defaults = format!(
+        "{:?}",
+        resolve_options_with_env(["webserve"], env(&[])).unwrap()
+    );
+    for (flags, file, vars) in LAYERS {
+        let args = ["webserve"].iter().chain(flags.iter());
+        let cli = resolve_options_with_env(args, env(&[])).unwrap();
+        fs::write(&config_path, file).unwrap();
+        let mut from_file =
+            resolve_options_with_env(["webserve", "--config", config_arg], env(&[])).unwrap();
+        from_file.config = None;
+        let from_env = resolve_options_with_env(["webserve"], env(vars)).unwrap();
+
+        let cli = format!("{:?}", cli);
+        assert_ne!(cli, defaults, "{:?} changes nothing", flags);
+        assert_eq!(format!("{:?}", from_file), cli, "{}", file);
+        assert_eq!(format!("{:?}", from_env), cli, "{:?}", vars);
+    }
+}
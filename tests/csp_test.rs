@@ -0,0 +1,120 @@
+//! Nonces for the injected reload script under a Content-Security-Policy.
+
+mod common;
+
+use actix_web::App;
+use tempfile::TempDir;
+use webserve::{add_nonce, Webserve, SCRIPT_MARKER};
+
+#[test]
+fn adds_the_nonce_where_scripts_are_restricted() {
+    assert_eq!(
+        add_nonce("default-src 'self'; img-src *", "abc").as_deref(),
+        Some("default-src 'self' 'nonce-abc'; img-src *")
+    );
+    // The most specific script directive governs
+    assert_eq!(
+        add_nonce("default-src 'none'; Script-Src 'self' ", "abc").as_deref(),
+        Some("default-src 'none'; Script-Src 'self' 'nonce-abc' ")
+    );
+    assert_eq!(
+        add_nonce("script-src 'self'; script-src-elem 'sha256-x'", "abc").as_deref(),
+        Some("script-src 'self'; script-src-elem 'sha256-x' 'nonce-abc'")
+    );
+    // Each of several policies
+    assert_eq!(
+        add_nonce("script-src 'self', img-src *", "abc").as_deref(),
+        Some("script-src 'self' 'nonce-abc', img-src *")
+    );
+    // Scripts unrestricted, or inline scripts already allowed
+    assert_eq!(add_nonce("img-src 'self'", "abc"), None);
+    assert_eq!(add_nonce("script-src 'self' 'unsafe-inline'", "abc"), None);
+    assert!(add_nonce("script-src 'unsafe-inline' 'nonce-x'", "abc").is_some());
+}
+
+fn site() -> TempDir {
+    common::site(&[
+        ("index.html", "<p>hi</p><script>alert(1)</script>"),
+        (
+            "meta.html",
+            r#"<head><meta http-equiv="Content-Security-Policy" content="script-src 'self'"></head><p>meta</p>"#,
+        ),
+    ])
+}
+
+fn nonce_of(html: &str) -> String {
+    let start = html.find("<script data-webserve nonce=\"").unwrap() + 29;
+    html[start..start + 32].to_string()
+}
+
+#[actix_web::test]
+async fn injected_script_gets_a_fresh_nonce_per_response() {
+    use actix_web::test;
+
+    let temp = site();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .watch(true)
+        .configure(|o| o.headers = vec!["Content-Security-Policy: script-src 'self'".to_string()])
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+    let mut nonces = Vec::new();
+    for _ in 0..2 {
+        let req = test::TestRequest::get().uri("/index.html").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+        assert!(!res.headers().contains_key("etag"));
+        assert!(!res.headers().contains_key("last-modified"));
+        let policy = res
+            .headers()
+            .get("content-security-policy")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+        let nonce = nonce_of(&html);
+        assert_eq!(policy, format!("script-src 'self' 'nonce-{}'", nonce));
+        // The page's own inline script stays under the policy
+        assert!(
+            html.contains("<p>hi</p><script>alert(1)</script>"),
+            "{}",
+            html
+        );
+        assert!(!html.contains(SCRIPT_MARKER));
+        nonces.push(nonce);
+    }
+    assert_ne!(nonces[0], nonces[1]);
+
+    // A policy in a meta tag gets the same nonce as the script
+    let req = test::TestRequest::get().uri("/meta.html").to_request();
+    let html = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    let nonce = nonce_of(&html);
+    assert!(
+        html.contains(&format!(r#"content="script-src 'self' 'nonce-{}'""#, nonce)),
+        "{}",
+        html
+    );
+}
+
+#[actix_web::test]
+async fn pages_without_a_policy_are_left_alone() {
+    use actix_web::test;
+
+    let temp = site();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .watch(true)
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+    let req = test::TestRequest::get().uri("/index.html").to_request();
+    let res = test::call_service(&app, req).await;
+    assert!(res.headers().contains_key("etag"));
+    let html = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(html.contains(SCRIPT_MARKER));
+    assert!(!html.contains("nonce="));
+}
@@ -0,0 +1,110 @@
+//! `--daemon`, `--pid-file` and `webserve stop`.
+#![cfg(unix)]
+
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::Command;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{is_running, pid_file_path, read_pid, Cli, ServeOptions, DEFAULT_PID_FILE};
+
+fn webserve(dir: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("run webserve binary")
+}
+
+#[test]
+fn pid_file_comes_from_the_options_or_stop() {
+    let options = ServeOptions::from_iter(["webserve"]);
+    assert!(!options.daemon);
+    assert_eq!(pid_file_path(&options, None), Path::new(DEFAULT_PID_FILE));
+
+    let options = ServeOptions::from_iter(["webserve", "--daemon", "--pid-file", "run/a.pid"]);
+    assert_eq!(pid_file_path(&options, None), Path::new("run/a.pid"));
+    let cli = Cli::from_iter(["webserve", "stop", "--pid-file", "b.pid"]);
+    match cli.command {
+        Some(webserve::Command::Stop(ref stop)) => {
+            assert_eq!(pid_file_path(&cli.options, Some(stop)), Path::new("b.pid"));
+            assert_eq!(stop.timeout, 35);
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn runs_in_the_background_until_stopped() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    fs::create_dir(root.join("site")).unwrap();
+    fs::write(root.join("site").join("index.html"), "<p>daemon</p>").unwrap();
+    let args = [
+        "--daemon",
+        "--uds",
+        "w.sock",
+        "-d",
+        "site",
+        "--pid-file",
+        "w.pid",
+        "--log-file",
+        "access.log",
+        "--daemon-log",
+        "w.out",
+    ];
+
+    // Returns once the server listens
+    let started = webserve(root, &args);
+    assert!(started.status.success(), "{:?}", started);
+    let stdout = String::from_utf8_lossy(&started.stdout);
+    assert!(
+        stdout.contains("webserve stop --pid-file w.pid"),
+        "{}",
+        stdout
+    );
+    let pid = read_pid(&root.join("w.pid")).unwrap();
+    assert!(is_running(pid));
+    let mut stream = UnixStream::connect(root.join("w.sock")).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.contains("<p>daemon</p>"), "{}", response);
+
+    let again = webserve(root, &args);
+    assert!(!again.status.success());
+    assert!(String::from_utf8_lossy(&again.stderr).contains("already running"));
+
+    let stopped = webserve(root, &["stop", "--pid-file", "w.pid"]);
+    assert!(stopped.status.success(), "{:?}", stopped);
+    assert!(!is_running(pid));
+    assert!(!root.join("w.pid").exists());
+    // Server output and the access log go to their own files
+    let output = fs::read_to_string(root.join("w.out")).unwrap();
+    assert!(output.contains("Serving on unix:w.sock"), "{}", output);
+    let access = fs::read_to_string(root.join("access.log")).unwrap();
+    assert!(access.contains("\"GET / HTTP/1.1\" 200"), "{}", access);
+    assert!(!access.contains("Serving on"), "{}", access);
+
+    let missing = webserve(root, &["stop", "--pid-file", "w.pid"]);
+    assert_eq!(missing.status.code(), Some(1));
+}
+
+#[test]
+fn reports_a_server_that_fails_to_start() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path();
+    let out = webserve(
+        root,
+        &["--daemon", "-d", "missing", "--daemon-log", "w.log"],
+    );
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("failed to start"));
+    assert!(!root.join(DEFAULT_PID_FILE).exists());
+    let log = fs::read_to_string(root.join("w.log")).unwrap();
+    assert!(log.contains("missing not found"), "{}", log);
+}
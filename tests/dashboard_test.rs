@@ -0,0 +1,246 @@
+//! `/__webserve/` dashboard and the stats collector behind it.
+
+use actix_web::middleware::from_fn;
+use actix_web::{test, web, App as ActixApp};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{
+    broken_links, collect_stats, dashboard, reload_poll, serve_file, stats_json, AppState, Stats,
+    Webserve, ADMIN_PREFIX, BROKEN_LINK_LIMIT, REFERER_LIMIT,
+};
+
+#[actix_web::test]
+async fn admin_pages_answer_localhost_unless_admin_remote() {
+    let temp = TempDir::new().unwrap();
+    std::fs::write(temp.path().join("a.txt"), b"a").unwrap();
+    let app = |remote: bool| {
+        let server = Webserve::builder()
+            .dir(temp.path())
+            .configure(|o| o.admin_remote = remote)
+            .build()
+            .unwrap();
+        test::init_service(ActixApp::new().service(server.into_actix_service()))
+    };
+    let get = |uri: &str, peer: Option<&str>| {
+        let req = test::TestRequest::get().uri(uri);
+        match peer {
+            Some(peer) => req.peer_addr(peer.parse().unwrap()),
+            None => req,
+        }
+    };
+
+    let local = app(false).await;
+    for uri in [
+        "/__webserve",
+        "/__webserve/",
+        "/__webserve/stats",
+        "/__webserve/manifest",
+    ] {
+        let req = get(uri, Some("127.0.0.1:5000")).to_request();
+        assert_eq!(
+            test::call_service(&local, req).await.status(),
+            200,
+            "{}",
+            uri
+        );
+        let req = get(uri, Some("[::ffff:127.0.0.1]:5000")).to_request();
+        assert_eq!(
+            test::call_service(&local, req).await.status(),
+            200,
+            "{}",
+            uri
+        );
+        let req = get(uri, Some("192.0.2.7:5000")).to_request();
+        assert_eq!(
+            test::call_service(&local, req).await.status(),
+            403,
+            "{}",
+            uri
+        );
+        // No peer address (--uds) and relayed requests fail closed
+        let req = get(uri, None).to_request();
+        assert_eq!(
+            test::call_service(&local, req).await.status(),
+            403,
+            "{}",
+            uri
+        );
+        let req = get(uri, Some("127.0.0.1:5000"))
+            .insert_header(("X-Forwarded-For", "192.0.2.7"))
+            .to_request();
+        assert_eq!(
+            test::call_service(&local, req).await.status(),
+            403,
+            "{}",
+            uri
+        );
+    }
+    let req = test::TestRequest::put()
+        .uri("/__webserve/scenario")
+        .peer_addr("192.0.2.7:5000".parse().unwrap())
+        .set_payload("error-state")
+        .to_request();
+    assert_eq!(test::call_service(&local, req).await.status(), 403);
+    // A rebound host name cannot write even from loopback
+    let put = |host: &str| {
+        test::TestRequest::put()
+            .uri("/__webserve/scenario")
+            .peer_addr("127.0.0.1:5000".parse().unwrap())
+            .insert_header(("Host", host))
+            .set_payload("error-state")
+            .to_request()
+    };
+    let res = test::call_service(&local, put("rebound.example:8080")).await;
+    assert_eq!(res.status(), 403);
+    for host in ["localhost:8080", "127.0.0.1:8080", "[::1]:8080"] {
+        let res = test::call_service(&local, put(host)).await;
+        assert_ne!(res.status(), 403, "{}", host);
+    }
+    // Files and look-alike paths are not affected
+    let req = get("/a.txt", Some("192.0.2.7:5000")).to_request();
+    assert_eq!(test::call_service(&local, req).await.status(), 200);
+    let req = get("/__webservex", Some("192.0.2.7:5000")).to_request();
+    assert_eq!(test::call_service(&local, req).await.status(), 404);
+
+    let remote = app(true).await;
+    let req = get("/__webserve/stats", Some("192.0.2.7:5000")).to_request();
+    assert_eq!(test::call_service(&remote, req).await.status(), 200);
+}
+
+#[actix_web::test]
+async fn dashboard_shows_options_requests_clients_and_changes() {
+    let temp = TempDir::new().unwrap();
+    std::fs::write(temp.path().join("a.txt"), b"a").unwrap();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        ..Default::default()
+    });
+    data.stats
+        .record_change("Modify(Any)", vec![PathBuf::from("site/<b>.html")]);
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data.clone())
+            .wrap(from_fn(collect_stats))
+            .route(&format!("{}/", ADMIN_PREFIX), web::get().to(dashboard))
+            .route(
+                &format!("{}/stats", ADMIN_PREFIX),
+                web::get().to(stats_json),
+            )
+            .route("/reload", web::get().to(reload_poll))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    for uri in ["/a.txt", "/missing", "/reload"] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("User-Agent", "test-browser"))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/", ADMIN_PREFIX))
+        .to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(body.contains("webserve status"));
+    // Only the directory's name is shown, not where it sits on disk
+    let name = temp.path().file_name().unwrap().to_string_lossy();
+    assert!(body.contains(&format!("<td>{}</td>", name)), "{}", body);
+    assert!(!body.contains(&temp.path().display().to_string()));
+    assert!(body.contains("<td>/a.txt</td><td>200</td>"));
+    assert!(body.contains("<td>/missing</td><td>404</td>"));
+    assert!(body.contains("test-browser"));
+    assert!(body.contains("site/&lt;b&gt;.html"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/stats", ADMIN_PREFIX))
+        .to_request();
+    let json: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    // /reload polls and dashboard views are not counted.
+    assert_eq!(json["total_requests"], 2);
+    assert_eq!(json["recent_requests"][0]["path"], "/missing");
+    assert_eq!(json["reload_clients"].as_array().unwrap().len(), 1);
+    assert_eq!(json["recent_changes"][0]["kind"], "Modify(Any)");
+}
+
+#[actix_web::test]
+async fn missing_paths_are_reported_with_referers() {
+    let temp = TempDir::new().unwrap();
+    std::fs::write(temp.path().join("index.html"), b"<img src=/logo.png>").unwrap();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data.clone())
+            .wrap(from_fn(collect_stats))
+            .route(&format!("{}/", ADMIN_PREFIX), web::get().to(dashboard))
+            .route(
+                &format!("{}/broken-links", ADMIN_PREFIX),
+                web::get().to(broken_links),
+            )
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let requests = [
+        ("/logo.png", Some("http://localhost:8080/docs/page.html")),
+        ("/logo.png", Some("http://localhost:8080/docs/page.html")),
+        ("/logo.png", Some("https://elsewhere.example/post")),
+        ("/old-page", None),
+        ("/index.html", Some("http://localhost:8080/")),
+    ];
+    for (uri, referer) in requests {
+        let mut req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Host", "localhost:8080"));
+        if let Some(referer) = referer {
+            req = req.insert_header(("Referer", referer));
+        }
+        test::call_service(&app, req.to_request()).await;
+    }
+    let req = test::TestRequest::post().uri("/form-target").to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/broken-links", ADMIN_PREFIX))
+        .to_request();
+    let json: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let links = json.as_array().unwrap();
+    assert_eq!(links.len(), 2, "{}", json);
+    assert_eq!(links[0]["path"], "/logo.png");
+    assert_eq!(links[0]["hits"], 3);
+    assert_eq!(links[0]["referers"]["/docs/page.html"], 2);
+    assert_eq!(links[0]["referers"]["https://elsewhere.example/post"], 1);
+    assert_eq!(links[1]["path"], "/old-page");
+    assert_eq!(links[1]["referers"]["-"], 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/", ADMIN_PREFIX))
+        .to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert!(body.contains("Broken links (2)"));
+    assert!(body.contains("<tr><td>/logo.png</td><td>3</td>"));
+}
+
+#[actix_web::test]
+async fn broken_link_report_is_bounded() {
+    let stats = Stats::default();
+    for i in 0..BROKEN_LINK_LIMIT + 5 {
+        stats.record_not_found(&format!("/missing-{}", i), None);
+    }
+    assert_eq!(stats.broken_links().len(), BROKEN_LINK_LIMIT);
+
+    for i in 0..REFERER_LIMIT + 3 {
+        stats.record_not_found("/hot", Some(&format!("/page-{}", i)));
+    }
+    stats.record_not_found("/hot", Some("/page-0"));
+    let hot = &stats.broken_links()[0];
+    assert_eq!(hot.path, "/hot");
+    assert_eq!(hot.hits, REFERER_LIMIT as u64 + 4);
+    assert_eq!(hot.referers.len(), REFERER_LIMIT);
+    assert_eq!(hot.referers["/page-0"], 2);
+}
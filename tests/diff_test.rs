@@ -0,0 +1,146 @@
+//! `webserve diff`: added, removed and changed files between two build outputs.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    diff_dirs, diff_report_html, Cli, Command as Subcommand, DiffKind, WalkLimits, EXIT_DIFFERENT,
+    EXIT_ERROR, EXIT_IDENTICAL,
+};
+
+/// `before` and `after` builds: `index.html` grows, `old.css` goes, `js/app.js` arrives.
+fn builds() -> (TempDir, TempDir) {
+    let before = TempDir::new().unwrap();
+    let after = TempDir::new().unwrap();
+    for dir in [&before, &after] {
+        fs::create_dir(dir.path().join("img")).unwrap();
+        fs::write(dir.path().join("img/logo.svg"), "<svg/>").unwrap();
+    }
+    fs::write(before.path().join("index.html"), "<h1>v1</h1>").unwrap();
+    fs::write(before.path().join("old.css"), "body{}").unwrap();
+    fs::write(after.path().join("index.html"), "<h1>v2, longer</h1>").unwrap();
+    fs::create_dir(after.path().join("js")).unwrap();
+    fs::write(after.path().join("js/app.js"), "run()").unwrap();
+    (before, after)
+}
+
+#[test]
+fn compares_trees_by_hash() {
+    let (before, after) = builds();
+    let diff = diff_dirs(before.path(), after.path(), WalkLimits::default());
+    let files: Vec<(&str, DiffKind, i64)> = diff
+        .files
+        .iter()
+        .map(|f| (f.path.as_str(), f.kind, f.size_delta()))
+        .collect();
+    assert_eq!(
+        files,
+        vec![
+            ("/index.html", DiffKind::Changed, 8),
+            ("/js/app.js", DiffKind::Added, 5),
+            ("/old.css", DiffKind::Removed, -6),
+        ]
+    );
+    assert_eq!(diff.unchanged, 1);
+    assert_eq!(diff.size_delta(), 7);
+    assert_eq!(diff.exit_code(), EXIT_DIFFERENT);
+    let changed = &diff.files[0];
+    assert_eq!(changed.before.as_ref().unwrap().sha256.len(), 64);
+    assert_ne!(
+        changed.before.as_ref().unwrap().sha256,
+        changed.after.as_ref().unwrap().sha256
+    );
+    assert_eq!(
+        diff.summary(),
+        "1 added, 1 removed, 1 changed, 1 unchanged; size +7 B"
+    );
+
+    let same = diff_dirs(before.path(), before.path(), WalkLimits::default());
+    assert!(same.files.is_empty());
+    assert_eq!(same.exit_code(), EXIT_IDENTICAL);
+
+    let html = diff_report_html("live", "<dist>", &diff);
+    assert!(html.contains("<code>&lt;dist&gt;</code>"));
+    assert!(html.contains(r#"<tr class="added"><td>added</td><td class="path">/js/app.js</td>"#));
+    assert!(html.contains(r#"<td class="size">-6 B</td></tr>"#));
+}
+
+#[test]
+fn parses_the_subcommand() {
+    let cli = Cli::from_iter(["webserve", "diff", "a", "b", "--serve"].iter());
+    let Some(Subcommand::Diff(diff)) = cli.command else {
+        panic!("diff parsed");
+    };
+    assert_eq!(diff.before.to_str(), Some("a"));
+    assert_eq!(diff.after.to_str(), Some("b"));
+    assert!(diff.serve);
+}
+
+fn diff(args: &[&str]) -> (i32, String) {
+    let out = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .arg("diff")
+        .args(args)
+        .output()
+        .expect("run webserve binary");
+    (
+        out.status.code().unwrap(),
+        String::from_utf8_lossy(&out.stdout).into_owned(),
+    )
+}
+
+#[test]
+fn exit_status_follows_diff() {
+    let (before, after) = builds();
+    let (a, b) = (
+        before.path().to_str().unwrap(),
+        after.path().to_str().unwrap(),
+    );
+    let (code, stdout) = diff(&[a, b]);
+    assert_eq!(code, EXIT_DIFFERENT);
+    assert!(stdout.contains("+ /js/app.js  5 B, sha256 "), "{}", stdout);
+    assert!(stdout.contains("- /old.css  6 B, sha256 "), "{}", stdout);
+    assert!(stdout.contains("~ /index.html  11 B -> 19 B (+8 B), sha256 "));
+    assert_eq!(diff(&[a, a]).0, EXIT_IDENTICAL);
+    assert_eq!(diff(&[a, "/no/such/build"]).0, EXIT_ERROR);
+}
+
+#[test]
+fn serves_the_html_report() {
+    let (before, after) = builds();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["-p", &port.to_string(), "diff"])
+        .arg(before.path())
+        .arg(after.path())
+        .arg("--serve")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run webserve binary");
+    let stdout = child.stdout.take().unwrap();
+    BufReader::new(stdout)
+        .lines()
+        .map_while(Result::ok)
+        .find(|line| line.contains("Serving diff report on"))
+        .expect("report is served");
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    child.kill().ok();
+    child.wait().ok();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(
+        response.contains(r#"<tr class="removed"><td>removed</td><td class="path">/old.css</td>"#)
+    );
+}
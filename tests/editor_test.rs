@@ -0,0 +1,203 @@
+//! `--open-in-editor`: editor command lines, file resolution, the localhost guard and the
+//! error overlay.
+
+use actix_web::{web, App};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{
+    editor_command, open_in_editor, resolve_editor_file, serve_file, AppState, LiveSettings,
+    Settings,
+};
+
+fn args(editor: &str, line: Option<u32>, column: Option<u32>) -> Vec<String> {
+    let command = editor_command(editor, Path::new("src/app.js"), line, column).unwrap();
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect()
+}
+
+fn data(root: &TempDir, open_in_editor: bool) -> web::Data<AppState> {
+    web::Data::new(AppState {
+        static_dir: Arc::new(root.path().to_path_buf()),
+        watch: true,
+        settings: Arc::new(LiveSettings::new(Settings {
+            open_in_editor,
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+#[test]
+fn each_editor_gets_its_line_syntax() {
+    assert_eq!(
+        args("code", Some(12), Some(3)),
+        ["code", "--goto", "src/app.js:12:3"]
+    );
+    assert_eq!(
+        args("/usr/bin/codium -r", Some(12), None),
+        ["/usr/bin/codium", "-r", "--goto", "src/app.js:12"]
+    );
+    assert_eq!(args("subl", Some(4), Some(1)), ["subl", "src/app.js:4:1"]);
+    assert_eq!(
+        args("idea", Some(7), Some(2)),
+        ["idea", "--line", "7", "src/app.js"]
+    );
+    assert_eq!(args("nvim", Some(9), Some(5)), ["nvim", "+9", "src/app.js"]);
+    assert_eq!(
+        args("emacs -nw", None, None),
+        ["emacs", "-nw", "src/app.js"]
+    );
+    assert!(editor_command("  ", Path::new("a"), None, None).is_none());
+}
+
+#[test]
+fn files_resolve_under_the_root_or_working_directory() {
+    let root = TempDir::new().unwrap();
+    let cwd = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    fs::create_dir(root.path().join("js")).unwrap();
+    fs::write(root.path().join("js/app.js"), "run()").unwrap();
+    fs::write(cwd.path().join("main.ts"), "run()").unwrap();
+    fs::write(outside.path().join("secret.txt"), "x").unwrap();
+
+    let resolve = |file: &str| resolve_editor_file(root.path(), cwd.path(), file);
+    assert_eq!(
+        resolve("/js/app.js").unwrap(),
+        root.path().join("js/app.js").canonicalize().unwrap()
+    );
+    assert_eq!(
+        resolve("main.ts").unwrap(),
+        cwd.path().join("main.ts").canonicalize().unwrap()
+    );
+    let secret = outside.path().join("secret.txt");
+    assert_eq!(resolve(secret.to_str().unwrap()), Err(403));
+    assert_eq!(resolve("/js/missing.js"), Err(404));
+    assert_eq!(resolve("js"), Err(404));
+}
+
+#[actix_web::test]
+async fn endpoint_is_opt_in_and_local_only() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    let route = "/__webserve/open-in-editor";
+    let off = test::init_service(
+        App::new()
+            .app_data(data(&temp, false))
+            .route(route, web::get().to(open_in_editor)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/__webserve/open-in-editor?file=/index.html")
+        .to_request();
+    assert_eq!(test::call_service(&off, req).await.status(), 404);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(data(&temp, true))
+            .route(route, web::get().to(open_in_editor)),
+    )
+    .await;
+    let refused = [
+        test::TestRequest::get()
+            .uri("/__webserve/open-in-editor?file=/index.html")
+            .peer_addr("192.168.1.20:50000".parse().unwrap()),
+        test::TestRequest::get()
+            .uri("/__webserve/open-in-editor?file=/index.html")
+            .peer_addr("127.0.0.1:50000".parse().unwrap())
+            .insert_header(("Sec-Fetch-Site", "cross-site")),
+        test::TestRequest::get()
+            .uri("/__webserve/open-in-editor?file=/index.html")
+            .peer_addr("127.0.0.1:50000".parse().unwrap())
+            .insert_header(("Host", "localhost:8080"))
+            .insert_header(("Origin", "https://evil.example")),
+        // DNS rebinding: the page's own host name reaches a loopback server
+        test::TestRequest::get()
+            .uri("/__webserve/open-in-editor?file=/index.html")
+            .peer_addr("127.0.0.1:50000".parse().unwrap())
+            .insert_header(("Host", "rebound.example:8080"))
+            .insert_header(("Origin", "http://rebound.example:8080"))
+            .insert_header(("Sec-Fetch-Site", "same-origin")),
+        // Neither header says where the request comes from
+        test::TestRequest::get()
+            .uri("/__webserve/open-in-editor?file=/index.html")
+            .peer_addr("127.0.0.1:50000".parse().unwrap()),
+        // No peer address (a Unix socket)
+        test::TestRequest::get()
+            .uri("/__webserve/open-in-editor?file=/index.html")
+            .insert_header(("Sec-Fetch-Site", "same-origin")),
+    ];
+    for req in refused {
+        assert_eq!(
+            test::call_service(&app, req.to_request()).await.status(),
+            403
+        );
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/__webserve/open-in-editor")
+        .peer_addr("[::1]:50000".parse().unwrap())
+        .insert_header(("Host", "[::1]:8080"))
+        .insert_header(("Sec-Fetch-Site", "same-origin"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+    let req = test::TestRequest::get()
+        .uri("/__webserve/open-in-editor?file=/no-such-file.js&line=3")
+        .peer_addr("127.0.0.1:50000".parse().unwrap())
+        .insert_header(("Host", "localhost:8080"))
+        .insert_header(("Origin", "http://localhost:8080"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+}
+
+#[actix_web::test]
+async fn overlay_rides_along_with_the_reload_script() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), "<h1>Home</h1>").unwrap();
+    for (enabled, expected) in [(false, false), (true, true)] {
+        let app = test::init_service(
+            App::new()
+                .app_data(data(&temp, enabled))
+                .route("/{_:.*}", web::get().to(serve_file)),
+        )
+        .await;
+        let req = test::TestRequest::get().uri("/index.html").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("/reload"));
+        assert_eq!(body.contains("webserve-error-overlay"), expected);
+        assert_eq!(body.contains("/__webserve/open-in-editor"), expected);
+    }
+}
+
+#[test]
+fn host_is_local_names_this_machine_only() {
+    use webserve::host_is_local;
+
+    let bound = "192.168.1.5:8080, [fe80::1]:8080";
+    for host in [
+        "localhost:8080",
+        "LOCALHOST.",
+        "app.localhost:3000",
+        "127.0.0.1:8080",
+        "[::1]:8080",
+        "192.168.1.5:8080",
+        "[fe80::1]:8080",
+    ] {
+        assert!(host_is_local(host, bound), "{}", host);
+    }
+    for host in [
+        "",
+        "rebound.example:8080",
+        "localhost.example",
+        "192.168.1.6:8080",
+    ] {
+        assert!(!host_is_local(host, bound), "{}", host);
+    }
+}
@@ -0,0 +1,155 @@
+//! `/__webserve/events`: token check and the request, change and reload events.
+
+use actix_web::body::MessageBody;
+use actix_web::middleware::from_fn;
+use actix_web::{web, App, HttpResponse};
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::sync::broadcast::Receiver;
+use webserve::{collect_stats, event_stream, generate_token, serve_file, AppState, EventBus};
+
+fn data(root: &TempDir) -> web::Data<AppState> {
+    web::Data::new(AppState {
+        static_dir: Arc::new(root.path().to_path_buf()),
+        events: Arc::new(EventBus::new("s3cret")),
+        ..Default::default()
+    })
+}
+
+/// Event names and JSON payloads received so far.
+fn drain(rx: &mut Receiver<bytes::Bytes>) -> Vec<(String, serde_json::Value)> {
+    let mut events = Vec::new();
+    while let Ok(frame) = rx.try_recv() {
+        let frame = String::from_utf8(frame.to_vec()).unwrap();
+        let field = |name: &str| {
+            frame
+                .lines()
+                .find_map(|l| l.strip_prefix(name))
+                .unwrap()
+                .to_string()
+        };
+        let data = serde_json::from_str(&field("data: ")).unwrap();
+        events.push((field("event: "), data));
+    }
+    events
+}
+
+#[test]
+fn generated_tokens_are_random() {
+    let token = generate_token();
+    assert_eq!(token.len(), 32);
+    assert_ne!(token, generate_token());
+    assert_eq!(EventBus::default().token().len(), 32);
+}
+
+#[actix_web::test]
+async fn stream_needs_the_token() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(data(&temp))
+            .route("/__webserve/events", web::get().to(event_stream)),
+    )
+    .await;
+
+    for req in [
+        test::TestRequest::get().uri("/__webserve/events"),
+        test::TestRequest::get().uri("/__webserve/events?token=s3cre"),
+        test::TestRequest::get()
+            .uri("/__webserve/events")
+            .insert_header(("Authorization", "Bearer nope")),
+    ] {
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), 401);
+        assert!(res.headers().contains_key("www-authenticate"));
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/__webserve/events")
+        .insert_header(("Authorization", "Bearer s3cret"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "text/event-stream"
+    );
+    let body = res.into_body();
+    let mut body = std::pin::pin!(body);
+    let first = futures::future::poll_fn(|cx| body.as_mut().poll_next(cx))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&first[..], b"retry: 3000\n: connected\n\n");
+
+    let req = test::TestRequest::get()
+        .uri("/__webserve/events?token=s3cret")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+}
+
+#[actix_web::test]
+async fn requests_and_errors_are_published() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), "<h1>Home</h1>").unwrap();
+    let data = data(&temp);
+    let mut rx = data.events.subscribe();
+    let app = test::init_service(
+        App::new()
+            .app_data(data.clone())
+            .wrap(from_fn(collect_stats))
+            .route("/boom", web::get().to(HttpResponse::InternalServerError))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+    test::call_service(&app, test::TestRequest::get().uri("/boom").to_request()).await;
+    test::call_service(&app, test::TestRequest::get().uri("/reload").to_request()).await;
+
+    let events = drain(&mut rx);
+    let names: Vec<&str> = events.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["request", "error", "request"]);
+    let (_, request) = &events[0];
+    assert_eq!(request["type"], "request");
+    assert_eq!(request["method"], "GET");
+    assert_eq!(request["path"], "/");
+    assert_eq!(request["status"], 200);
+    assert!(request["duration_ms"].is_number());
+    assert!(request["time"].as_u64().unwrap() > 0);
+    assert_eq!(events[1].1["message"], "GET /boom answered 500");
+    assert_eq!(events[2].1["status"], 500);
+}
+
+#[test]
+fn changes_and_reloads_are_published() {
+    let temp = TempDir::new().unwrap();
+    let file = temp.path().join("app.js");
+    fs::write(&file, "run()").unwrap();
+    let data = data(&temp);
+    // Nobody listening: nothing is queued
+    data.publish_changes(&[notify::Event::new(notify::EventKind::Any).add_path(file.clone())]);
+
+    let mut rx = data.events.subscribe();
+    data.publish_changes(&[notify::Event::new(notify::EventKind::Create(
+        notify::event::CreateKind::File,
+    ))
+    .add_path(file)]);
+    let events = drain(&mut rx);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].0, "change");
+    assert_eq!(events[0].1["path"], "/app.js");
+    assert_eq!(events[0].1["kind"], "added");
+    assert_eq!(events[1].0, "reload");
+    assert_eq!(events[1].1["seq"], 2);
+    assert_eq!(events[1].1["paths"], serde_json::json!(["/app.js"]));
+    assert_eq!(
+        events[1].1["changes"],
+        serde_json::json!([{ "path": "/app.js", "kind": "added" }])
+    );
+}
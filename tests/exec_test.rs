@@ -0,0 +1,192 @@
+//! `--exec`: build command run between a watcher batch and the reload.
+
+use actix_web::{web, App};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fs, thread};
+use tempfile::TempDir;
+use webserve::{reload_poll, serve_file, strip_ansi, AppState, BuildCommand, BuildError};
+
+fn create(path: &str) -> notify::Event {
+    notify::Event::new(notify::EventKind::Create(notify::event::CreateKind::File))
+        .add_path(PathBuf::from(path))
+}
+
+#[test]
+fn failed_builds_do_not_reload() {
+    let ok = BuildCommand::new("exit 0");
+    assert_eq!(ok.command(), "exit 0");
+    let batch = ok.build(vec![create("site/app.scss")]).unwrap();
+    assert_eq!(batch.len(), 1);
+    assert!(BuildCommand::new("exit 3")
+        .build(vec![create("site/app.scss")])
+        .is_err());
+    assert!(BuildCommand::new("no-such-build-tool-7f3a").run().is_err());
+    // Idle: events go to the watcher as usual
+    assert!(!ok.absorb(&Ok(create("site/app.scss"))));
+}
+
+#[cfg(unix)]
+#[test]
+fn events_during_the_build_are_its_output() {
+    let build = Arc::new(BuildCommand::new("sleep 0.5"));
+    let watcher = build.clone();
+    let output = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        (
+            watcher.absorb(&Ok(create("site/app.css"))),
+            watcher.absorb(&Ok(create("site/.app.css.swp"))),
+        )
+    });
+    let batch = build.build(vec![create("site/app.scss")]).unwrap();
+    assert_eq!(output.join().unwrap(), (true, true));
+    let paths: Vec<_> = batch.iter().flat_map(|e| e.paths.clone()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("site/app.scss"),
+            PathBuf::from("site/app.css")
+        ]
+    );
+    assert!(!build.absorb(&Ok(create("site/app.scss"))));
+}
+
+fn get(port: u16, path: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[cfg(unix)]
+#[test]
+fn builds_once_per_change() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), "<p>v1</p>").unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--watch", "--strict-port", "-p", &port.to_string()])
+        .args(["--exec", "echo built >> build.log"])
+        .arg("-d")
+        .arg(temp.path())
+        .current_dir(temp.path())
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("run webserve binary");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(Instant::now() < deadline, "server never came up");
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    fs::write(temp.path().join("index.html"), "<p>v2</p>").unwrap();
+    let log = temp.path().join("build.log");
+    while !log.exists() {
+        assert!(Instant::now() < deadline, "build never ran");
+        thread::sleep(Duration::from_millis(50));
+    }
+    // The build's own write to build.log must not start another build
+    thread::sleep(Duration::from_millis(1500));
+    let changes = get(port, "/__webserve/changes?since=0");
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert_eq!(fs::read_to_string(&log).unwrap(), "built\n");
+    assert!(changes.contains("\"/index.html\""), "{}", changes);
+    assert!(changes.contains("\"/build.log\""), "{}", changes);
+}
+
+#[cfg(unix)]
+#[test]
+fn failures_keep_the_compiler_output() {
+    let err =
+        BuildCommand::new("echo compiling; echo '\x1b[31merror\x1b[0m: bad token' >&2; exit 3")
+            .build(vec![create("site/app.ts")])
+            .unwrap_err();
+    assert_eq!(err.status, "exit status: 3");
+    assert!(err.output.contains("compiling\n"), "{:?}", err.output);
+    assert!(
+        err.output.contains("error: bad token\n"),
+        "{:?}",
+        err.output
+    );
+    assert_eq!(
+        err.message(),
+        "exec: `echo compiling; echo '\x1b[31merror\x1b[0m: bad token' >&2; exit 3` failed (exit status: 3)"
+    );
+    let missing = BuildCommand::new("no-such-build-tool-7f3a")
+        .run()
+        .unwrap_err();
+    assert!(missing.status.contains("127") || missing.status.starts_with("cannot run"));
+
+    assert_eq!(
+        strip_ansi("\x1b[1;31merror\x1b[0m[E0308]\x1b]8;;file:///a.rs\x1b\\a.rs\x1b]8;;\x1b\\"),
+        "error[E0308]a.rs"
+    );
+}
+
+#[actix_web::test]
+async fn failed_builds_reach_the_page() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), "<h1>Home</h1>").unwrap();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        watch: true,
+        ..Default::default()
+    });
+    let app = test::init_service(
+        App::new()
+            .app_data(data.clone())
+            .route("/reload", web::get().to(reload_poll))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let page = |app| async move {
+        let req = test::TestRequest::get().uri("/index.html").to_request();
+        String::from_utf8(test::call_and_read_body(app, req).await.to_vec()).unwrap()
+    };
+    assert!(page(&app).await.contains("var failed = null;"));
+
+    let mut rx = data.tx.subscribe();
+    data.publish_build_error(BuildError {
+        command: "tsc".to_string(),
+        status: "exit status: 2".to_string(),
+        output: "app.ts(3,1): error TS1005: '</script>' expected.".to_string(),
+    });
+    assert_eq!(rx.try_recv().unwrap().error.unwrap().command, "tsc");
+    let req = test::TestRequest::get()
+        .uri("/reload?since=0")
+        .insert_header(("Accept", "application/json"))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["current"], 1);
+    assert_eq!(body["missed"][0]["error"]["status"], "exit status: 2");
+    assert_eq!(body["missed"][0]["changes"], serde_json::json!([]));
+
+    // Pages loaded while the build is broken show it too, without closing their script early
+    let html = page(&app).await;
+    assert!(html.contains(r#"var failed = {"command":"tsc","status":"exit status: 2""#));
+    assert!(html.contains(r"'\u003c/script>' expected."));
+    assert!(!html.contains("'</script>'"));
+    assert_eq!(data.reload_log.build_error().unwrap().command, "tsc");
+
+    data.publish_changes(&[create(temp.path().join("index.html").to_str().unwrap())]);
+    assert_eq!(data.reload_log.build_error(), None);
+    assert!(page(&app).await.contains("var failed = null;"));
+}
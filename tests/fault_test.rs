@@ -0,0 +1,103 @@
+//! `--fault-injection`: header parsing and per-request delay/status.
+
+use actix_web::http::StatusCode;
+use actix_web::middleware::from_fn;
+use actix_web::{web, App as ActixApp};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use webserve::{inject_fault, parse_duration, serve_file, AppState, Fault, Settings};
+
+#[test]
+fn parses_fault_directives() {
+    assert_eq!(
+        Fault::parse("delay=2s, status=503").unwrap(),
+        Fault {
+            delay: Some(Duration::from_secs(2)),
+            status: Some(StatusCode::SERVICE_UNAVAILABLE),
+        }
+    );
+    assert_eq!(
+        Fault::parse("delay=250ms").unwrap().delay,
+        Some(Duration::from_millis(250))
+    );
+    assert!(Fault::parse("status=99").is_err());
+    assert!(Fault::parse("explode=1").is_err());
+    assert!(Fault::parse("delay").is_err());
+    assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+    assert_eq!(parse_duration("40").unwrap(), Duration::from_millis(40));
+    assert!(parse_duration("-1s").is_err());
+}
+
+fn state(enabled: bool, root: &std::path::Path) -> web::Data<AppState> {
+    web::Data::new(AppState {
+        static_dir: Arc::new(root.to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                fault_injection: enabled,
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    })
+}
+
+#[actix_web::test]
+async fn header_injects_status_and_delay_when_enabled() {
+    use actix_web::test;
+
+    let root = TempDir::new().unwrap();
+    std::fs::write(root.path().join("a.txt"), b"ok").unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(state(true, root.path()))
+            .wrap(from_fn(inject_fault))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/a.txt")
+        .insert_header(("X-Webserve-Fault", "status=503"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let started = Instant::now();
+    let req = test::TestRequest::get()
+        .uri("/a.txt")
+        .insert_header(("X-Webserve-Fault", "delay=50ms"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(test::read_body(resp).await, "ok");
+
+    let req = test::TestRequest::get()
+        .uri("/a.txt")
+        .insert_header(("X-Webserve-Fault", "nonsense"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn header_ignored_when_disabled() {
+    use actix_web::test;
+
+    let root = TempDir::new().unwrap();
+    std::fs::write(root.path().join("a.txt"), b"ok").unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(state(false, root.path()))
+            .wrap(from_fn(inject_fault))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri("/a.txt")
+        .insert_header(("X-Webserve-Fault", "status=503"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
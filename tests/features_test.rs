@@ -0,0 +1,38 @@
+//! Subsystems behind Cargo features: their options still parse, and fail at startup when
+//! the build left them out.
+
+use structopt::StructOpt;
+use webserve::{ServeOptions, Settings};
+
+#[test]
+fn subsystem_flags_need_their_feature() {
+    let flags = [
+        ("--render-markdown", "markdown", cfg!(feature = "markdown")),
+        ("--highlight", "highlight", cfg!(feature = "highlight")),
+        ("--webdav", "webdav", cfg!(feature = "webdav")),
+        (
+            "--proxy=/api=http://localhost:3000",
+            "proxy",
+            cfg!(feature = "proxy"),
+        ),
+    ];
+    for (flag, feature, built) in flags {
+        let options = ServeOptions::from_iter(["webserve", flag]);
+        match Settings::from_options(&options) {
+            Ok(_) => assert!(built, "{} accepted without `{}`", flag, feature),
+            Err(e) => {
+                assert!(!built, "{}: {}", flag, e);
+                assert!(e.contains(&format!("--features {}", feature)), "{}", e);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "crawl"))]
+#[actix_web::test]
+async fn url_checks_need_the_crawl_feature() {
+    let err = webserve::check_url("http://127.0.0.1:9/", 10)
+        .await
+        .unwrap_err();
+    assert!(err.contains("--features crawl"), "{}", err);
+}
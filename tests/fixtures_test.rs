@@ -0,0 +1,215 @@
+//! YAML fixture loading and the fixtures middleware.
+
+use actix_web::http::Method;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+use webserve::Fixtures;
+
+#[test]
+fn fixtures_parse_and_match() {
+    let fixtures = Fixtures::parse(
+        r#"
+routes:
+  - path: /api/users
+    method: get
+    json: [{ id: 1 }]
+  - path: /api/*
+    status: 503
+    headers: { Retry-After: "5" }
+    body: down
+"#,
+        Path::new("."),
+    )
+    .unwrap();
+    assert_eq!(fixtures.len(), 2);
+
+    let users = fixtures.find(&Method::GET, "/api/users", None).unwrap();
+    assert_eq!(users.body, r#"[{"id":1}]"#);
+    assert!(users
+        .headers
+        .iter()
+        .any(|(n, v)| n == "content-type" && v == "application/json"));
+    assert!(fixtures.find(&Method::HEAD, "/api/users", None).is_some());
+
+    let fallback = fixtures.find(&Method::POST, "/api/users", None).unwrap();
+    assert_eq!(fallback.status.as_u16(), 503);
+    assert!(fixtures.find(&Method::GET, "/index.html", None).is_none());
+}
+
+#[test]
+fn fixtures_reject_invalid_routes() {
+    let base = Path::new(".");
+    assert!(Fixtures::parse("routes:\n  - path: /x\n    status: 99\n", base).is_err());
+    assert!(Fixtures::parse("routes:\n  - path: /x\n    body: a\n    json: 1\n", base).is_err());
+    assert!(Fixtures::parse("routes:\n  - path: /x\n    colour: red\n", base).is_err());
+}
+
+#[test]
+fn load_dir_reads_yaml_files_in_name_order_with_body_files() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("users.json"), b"[]").unwrap();
+    fs::write(
+        temp.path().join("b.yml"),
+        "routes:\n  - path: /users\n    body: second\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join("a.yaml"),
+        "routes:\n  - path: /users\n    body_file: users.json\n",
+    )
+    .unwrap();
+    fs::write(temp.path().join("notes.txt"), b"ignored").unwrap();
+
+    let fixtures = Fixtures::load_dir(temp.path()).unwrap();
+    assert_eq!(fixtures.len(), 2);
+    assert_eq!(
+        fixtures.find(&Method::GET, "/users", None).unwrap().body,
+        "[]"
+    );
+}
+
+#[actix_web::test]
+async fn fixtures_take_precedence_over_files() {
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, web, App as ActixApp};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use webserve::{serve_file, serve_fixtures, AppState, Settings};
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("data.json"), b"from disk").unwrap();
+    fs::write(temp.path().join("other.txt"), b"plain file").unwrap();
+    let fixtures = Fixtures::parse(
+        "routes:\n  - path: /data.json\n    json: { fixture: true }\n    delay_ms: 50\n",
+        temp.path(),
+    )
+    .unwrap();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                fixtures: Some(Arc::new(fixtures)),
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .wrap(from_fn(serve_fixtures))
+            .app_data(app_state)
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let started = Instant::now();
+    let req = test::TestRequest::get().uri("/data.json").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert!(resp.status().is_success());
+    assert_eq!(test::read_body(resp).await, r#"{"fixture":true}"#);
+
+    let req = test::TestRequest::get().uri("/other.txt").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(test::read_body(resp).await, "plain file");
+}
+
+#[test]
+fn scenario_routes_override_base_routes_only_when_active() {
+    let temp = TempDir::new().unwrap();
+    fs::write(
+        temp.path().join("base.yaml"),
+        "routes:\n  - path: /api/users\n    json: []\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join("errors.yaml"),
+        "scenario: error-state\nroutes:\n  - path: /api/users\n    status: 500\n",
+    )
+    .unwrap();
+    let fixtures = Fixtures::load_dir(temp.path()).unwrap();
+    assert_eq!(fixtures.scenarios(), vec!["error-state".to_string()]);
+
+    let base = fixtures.find(&Method::GET, "/api/users", None).unwrap();
+    assert_eq!(base.status.as_u16(), 200);
+    let errored = fixtures
+        .find(&Method::GET, "/api/users", Some("error-state"))
+        .unwrap();
+    assert_eq!(errored.status.as_u16(), 500);
+    let unknown = fixtures
+        .find(&Method::GET, "/api/users", Some("slow-backend"))
+        .unwrap();
+    assert_eq!(unknown.status.as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn scenario_switches_via_header_and_admin_api() {
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, web, App as ActixApp};
+    use std::sync::Arc;
+    use webserve::{
+        scenario_status, serve_file, serve_fixtures, set_scenario, AppState, Settings,
+        SCENARIO_HEADER,
+    };
+
+    let fixtures = Fixtures::parse(
+        "routes:\n  - path: /api\n    body: ok\n  - path: /api\n    scenario: down\n    status: 503\n",
+        Path::new("."),
+    )
+    .unwrap();
+    let app_state = web::Data::new(AppState {
+        settings: Arc::new(
+            Settings {
+                fixtures: Some(Arc::new(fixtures)),
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .wrap(from_fn(serve_fixtures))
+            .app_data(app_state)
+            .route("/__webserve/scenario", web::get().to(scenario_status))
+            .route("/__webserve/scenario", web::put().to(set_scenario))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/api").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+
+    let req = test::TestRequest::get()
+        .uri("/api")
+        .insert_header((SCENARIO_HEADER, "down"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 503);
+
+    let req = test::TestRequest::put()
+        .uri("/__webserve/scenario")
+        .set_payload("nope")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 404);
+
+    let req = test::TestRequest::put()
+        .uri("/__webserve/scenario")
+        .set_payload("down")
+        .to_request();
+    let status: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(status["active"], "down");
+    assert_eq!(status["available"][0], "down");
+
+    let req = test::TestRequest::get().uri("/api").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 503);
+
+    let req = test::TestRequest::put()
+        .uri("/__webserve/scenario")
+        .set_payload("default")
+        .to_request();
+    test::call_service(&app, req).await;
+    let req = test::TestRequest::get().uri("/api").to_request();
+    assert_eq!(test::call_service(&app, req).await.status().as_u16(), 200);
+}
@@ -0,0 +1,116 @@
+//! `--graphql-mocks`: operation lookup, introspection and fall-through.
+
+use actix_web::middleware::from_fn;
+use actix_web::{web, App as ActixApp};
+use serde_json::{json, Value};
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{graphql_mock, operation_name, serve_file, AppState, Settings};
+
+#[test]
+fn operation_name_reads_named_operations() {
+    assert_eq!(
+        operation_name("query GetUser($id: ID!) { user(id: $id) { name } }").as_deref(),
+        Some("GetUser")
+    );
+    assert_eq!(
+        operation_name("  mutation AddTodo { add }").as_deref(),
+        Some("AddTodo")
+    );
+    assert_eq!(operation_name("{ users { id } }"), None);
+    assert_eq!(operation_name("query { users { id } }"), None);
+    assert_eq!(operation_name("queryX { a }"), None);
+}
+
+fn state(mocks: Option<&std::path::Path>, root: &std::path::Path) -> web::Data<AppState> {
+    web::Data::new(AppState {
+        static_dir: Arc::new(root.to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                graphql_mocks: mocks.map(|m| m.to_path_buf()),
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    })
+}
+
+#[actix_web::test]
+async fn serves_mocks_by_operation_name() {
+    use actix_web::test;
+
+    let mocks = TempDir::new().unwrap();
+    fs::write(
+        mocks.path().join("GetUser.json"),
+        r#"{ "user": { "name": "Ada" } }"#,
+    )
+    .unwrap();
+    fs::write(
+        mocks.path().join("Broken.json"),
+        r#"{ "errors": [{ "message": "boom" }] }"#,
+    )
+    .unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(state(Some(mocks.path()), mocks.path()))
+            .wrap(from_fn(graphql_mock))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/graphql")
+        .set_json(json!({ "query": "query GetUser { user { name } }" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body, json!({ "data": { "user": { "name": "Ada" } } }));
+
+    let req = test::TestRequest::post()
+        .uri("/graphql")
+        .set_json(json!({ "query": "{ x }", "operationName": "Broken" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["errors"][0]["message"], "boom");
+
+    let req = test::TestRequest::get()
+        .uri("/graphql?query=query%20Missing%20%7B%20a%20%7D")
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["errors"][0]["message"],
+        "no mock for operation Missing"
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/graphql")
+        .set_json(json!({ "query": "query IntrospectionQuery { __schema { types { name } } }" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["data"]["__schema"]["queryType"]["name"], "Query");
+
+    let req = test::TestRequest::put().uri("/graphql").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.status(),
+        actix_web::http::StatusCode::METHOD_NOT_ALLOWED
+    );
+}
+
+#[actix_web::test]
+async fn disabled_without_mocks_dir() {
+    use actix_web::test;
+
+    let root = TempDir::new().unwrap();
+    fs::write(root.path().join("graphql"), b"static file").unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(state(None, root.path()))
+            .wrap(from_fn(graphql_mock))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/graphql").to_request();
+    assert_eq!(test::call_and_read_body(&app, req).await, "static file");
+}
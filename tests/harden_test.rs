@@ -0,0 +1,139 @@
+//! `--harden` request checks.
+
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::{Uri, Version};
+use actix_web::middleware::from_fn;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{audit_request, harden, serve_file, AppState, Settings, DEFAULT_MAX_HEADER_BYTES};
+
+fn headers(pairs: &[(HeaderName, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.append(name.clone(), HeaderValue::from_str(value).unwrap());
+    }
+    map
+}
+
+fn audit(uri: &str, pairs: &[(HeaderName, &str)]) -> Vec<String> {
+    let uri: Uri = uri.parse().unwrap();
+    audit_request(
+        Version::HTTP_11,
+        &uri,
+        &headers(pairs),
+        DEFAULT_MAX_HEADER_BYTES,
+    )
+}
+
+#[test]
+fn ordinary_requests_pass() {
+    assert!(audit("/index.html", &[(header::HOST, "example.com")]).is_empty());
+    assert!(audit(
+        "/upload",
+        &[
+            (header::CONTENT_LENGTH, "12"),
+            (header::CONTENT_LENGTH, "12")
+        ]
+    )
+    .is_empty());
+    assert!(audit("/upload", &[(header::TRANSFER_ENCODING, "Chunked")]).is_empty());
+    // HTTP/2 requests always carry scheme and authority.
+    let uri: Uri = "https://example.com/app.js".parse().unwrap();
+    assert!(audit_request(Version::HTTP_2, &uri, &HeaderMap::new(), 1024).is_empty());
+}
+
+#[test]
+fn smuggling_shapes_are_reported() {
+    let problems = audit(
+        "/",
+        &[
+            (header::CONTENT_LENGTH, "4"),
+            (header::TRANSFER_ENCODING, "chunked"),
+        ],
+    );
+    assert_eq!(problems, vec!["both Content-Length and Transfer-Encoding"]);
+
+    let problems = audit("/", &[(header::CONTENT_LENGTH, "4, 5")]);
+    assert_eq!(problems, vec!["conflicting Content-Length values"]);
+    let problems = audit("/", &[(header::CONTENT_LENGTH, "+4")]);
+    assert_eq!(problems, vec!["Content-Length is not a number"]);
+
+    for te in ["chunked, identity", "xchunked", "gzip"] {
+        let problems = audit("/", &[(header::TRANSFER_ENCODING, te)]);
+        assert_eq!(
+            problems,
+            vec!["Transfer-Encoding other than chunked"],
+            "{}",
+            te
+        );
+    }
+
+    let problems = audit("http://internal.test/admin", &[]);
+    assert_eq!(problems, vec!["absolute-form request target"]);
+
+    let big = "x".repeat(DEFAULT_MAX_HEADER_BYTES);
+    let problems = audit("/", &[(header::COOKIE, &big)]);
+    assert_eq!(problems.len(), 1);
+    assert!(
+        problems[0].starts_with("header section of"),
+        "{:?}",
+        problems
+    );
+}
+
+#[actix_web::test]
+async fn rejected_requests_get_400_and_close() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), "<p>hi</p>").unwrap();
+    let data_with = |harden: bool| {
+        web::Data::new(AppState {
+            static_dir: Arc::new(temp.path().to_path_buf()),
+            settings: Arc::new(
+                Settings {
+                    harden,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            ..Default::default()
+        })
+    };
+    let smuggled = || {
+        test::TestRequest::get()
+            .uri("/")
+            .insert_header((header::CONTENT_LENGTH, "0"))
+            .insert_header((header::TRANSFER_ENCODING, "chunked"))
+            .to_request()
+    };
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data_with(true))
+            .wrap(from_fn(harden))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, smuggled()).await;
+    assert_eq!(res.status(), 400);
+    assert_eq!(
+        res.response().head().connection_type(),
+        actix_web::http::ConnectionType::Close
+    );
+    assert_eq!(test::read_body(res).await, "400 Bad Request");
+    let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert_eq!(res.status(), 200);
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data_with(false))
+            .wrap(from_fn(harden))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, smuggled()).await;
+    assert_eq!(res.status(), 200);
+}
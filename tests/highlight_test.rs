@@ -0,0 +1,122 @@
+//! `--highlight`: syntax-highlighted source view for `?view=1`.
+
+#![cfg(feature = "highlight")]
+
+use actix_web::http::header;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{
+    is_highlightable, render_source, serve_file, wants_view, AppState, Branding, ListingView,
+    Locale, Settings,
+};
+
+#[test]
+fn renders_numbered_highlighted_source() {
+    assert!(is_highlightable(Path::new("src/main.rs")));
+    assert!(is_highlightable(Path::new("app.JS")));
+    assert!(is_highlightable(Path::new("tool.py")));
+    assert!(is_highlightable(Path::new("Makefile")));
+    assert!(!is_highlightable(Path::new("photo.png")));
+    assert!(!is_highlightable(Path::new("LICENSE")));
+    assert!(wants_view("view=1"));
+    assert!(wants_view("a=b&view"));
+    assert!(!wants_view("view=0"));
+    assert!(!wants_view("preview=1"));
+
+    let source = "fn main() {\n    let x = \"<b>\";\n}\n";
+    let page = render_source(source, "main.rs", "/src/main.rs", &Branding::default());
+    assert!(page.contains("<title>main.rs</title>"));
+    assert!(page.contains(r##"<a id="L3" href="#L3">3</a>"##));
+    assert!(!page.contains(r#"id="L4""#));
+    assert!(page.contains("Rust &middot; 3 lines"));
+    assert!(page.contains(r#"<a href="/src/main.rs">Raw</a>"#));
+    assert!(page.contains(r#"<span class="hl-storage hl-type hl-function hl-rust">fn</span>"#));
+    assert!(page.contains("&lt;b&gt;"), "source is escaped");
+    assert!(page.contains("prefers-color-scheme: dark"));
+}
+
+#[actix_web::test]
+async fn serves_the_viewer_for_view_queries() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("app.js"), "const a = 1;\n").unwrap();
+    fs::write(temp.path().join("logo.png"), [0x89, b'P', b'N', b'G']).unwrap();
+    let data_with = |highlight: bool| {
+        web::Data::new(AppState {
+            static_dir: Arc::new(temp.path().to_path_buf()),
+            settings: Arc::new(
+                Settings {
+                    highlight,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            ..Default::default()
+        })
+    };
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data_with(true))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/app.js?view=1")).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("JavaScript &middot; 1 line"), "{}", body);
+
+    assert_eq!(
+        test::call_and_read_body(&app, get("/app.js")).await,
+        "const a = 1;\n"
+    );
+    let res = test::call_service(&app, get("/logo.png?view=1")).await;
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "image/png"
+    );
+
+    let listing = test::call_and_read_body(&app, get("/")).await;
+    let listing = String::from_utf8(listing.to_vec()).unwrap();
+    assert!(listing.contains(r#"id="source-toggle""#));
+    assert!(listing.contains(r#"href="/app.js" data-source>"#));
+    assert!(!listing.contains(r#"href="/logo.png" data-source"#));
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data_with(false))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    assert_eq!(
+        test::call_and_read_body(&app, get("/app.js?view=1")).await,
+        "const a = 1;\n"
+    );
+    let listing = test::call_and_read_body(&app, get("/")).await;
+    assert!(!String::from_utf8(listing.to_vec())
+        .unwrap()
+        .contains("source-toggle"));
+}
+
+#[actix_web::test]
+async fn listing_toggle_is_localized() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("lib.rs"), "").unwrap();
+    let view = ListingView {
+        locale: Locale::De,
+        source_toggle: true,
+        ..Default::default()
+    };
+    let listing = view.render(temp.path(), "/").await;
+    assert!(listing.contains(r#"aria-label="Codedateien als hervorgehobenen Quelltext öffnen""#));
+    assert!(listing.contains("webserve-source-view"));
+}
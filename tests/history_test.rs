@@ -0,0 +1,222 @@
+//! Change history behind `/__webserve/changes`.
+
+use actix_web::{web, App as ActixApp};
+use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{
+    changes, classify, AppState, ChangeHistory, ChangeKind, ReloadChange, ReloadLog, ADMIN_PREFIX,
+    HISTORY_LIMIT, RELOAD_HISTORY_LIMIT,
+};
+
+fn event(kind: EventKind, paths: &[&Path]) -> Event {
+    paths
+        .iter()
+        .fold(Event::new(kind), |e, p| e.add_path(p.to_path_buf()))
+}
+
+fn create(path: &Path) -> Event {
+    event(EventKind::Create(CreateKind::File), &[path])
+}
+
+fn modify(path: &Path) -> Event {
+    event(
+        EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+        &[path],
+    )
+}
+
+fn remove(path: &Path) -> Event {
+    event(EventKind::Remove(RemoveKind::File), &[path])
+}
+
+#[test]
+fn events_are_classified_per_path() {
+    let root = PathBuf::from("/site");
+    let rename = event(
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+        &[&root.join("old.html"), &root.join("new.html")],
+    );
+    assert_eq!(
+        classify(&rename),
+        vec![
+            (root.join("old.html").as_path(), ChangeKind::Removed),
+            (root.join("new.html").as_path(), ChangeKind::Added),
+        ]
+    );
+    assert!(classify(&create(&root.join(".index.html.swp"))).is_empty());
+}
+
+#[test]
+fn since_reports_net_changes() {
+    let root = PathBuf::from("/site");
+    let history = ChangeHistory::default();
+    let start = webserve::unix_millis();
+    history.record(
+        &root,
+        &[
+            create(&root.join("new.js")),
+            modify(&root.join("new.js")),
+            modify(&root.join("index.html")),
+            remove(&root.join("gone.css")),
+            create(&root.join("tmp/scratch.txt")),
+            remove(&root.join("tmp/scratch.txt")),
+            modify(Path::new("/elsewhere/file.txt")),
+        ],
+    );
+    let set = history.since(start);
+    assert!(set.complete);
+    assert_eq!(set.added, vec!["/new.js"]);
+    assert_eq!(set.modified, vec!["/index.html"]);
+    assert_eq!(set.removed, vec!["/gone.css"]);
+    assert!(set.now >= start);
+
+    let later = history.since(set.now + 1);
+    assert!(later.added.is_empty() && later.modified.is_empty() && later.removed.is_empty());
+}
+
+#[test]
+fn history_is_incomplete_before_start_or_after_overflow() {
+    let root = PathBuf::from("/site");
+    let history = ChangeHistory::default();
+    assert!(!history.since(0).complete);
+
+    let start = webserve::unix_millis();
+    let events: Vec<Event> = (0..=HISTORY_LIMIT)
+        .map(|i| modify(&root.join(format!("{}.txt", i))))
+        .collect();
+    history.record(&root, &events);
+    let set = history.since(start);
+    assert!(!set.complete);
+    assert_eq!(set.modified.len(), HISTORY_LIMIT);
+}
+
+async fn get_changes(data: &web::Data<AppState>, query: &str) -> (u16, serde_json::Value) {
+    use actix_web::test;
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data.clone())
+            .route(&format!("{}/changes", ADMIN_PREFIX), web::get().to(changes)),
+    )
+    .await;
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/changes{}", ADMIN_PREFIX, query))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status().as_u16();
+    (status, test::read_body_json(res).await)
+}
+
+#[actix_web::test]
+async fn changes_endpoint_needs_watch() {
+    let data = web::Data::new(AppState::default());
+    let (status, body) = get_changes(&data, "?since=0").await;
+    assert_eq!(status, 404);
+    assert!(body["error"].as_str().unwrap().contains("--watch"));
+}
+
+#[actix_web::test]
+async fn changes_endpoint_returns_json() {
+    let temp = TempDir::new().unwrap();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        watch: true,
+        ..Default::default()
+    });
+    let start = webserve::unix_millis();
+    data.history
+        .record(temp.path(), &[create(&temp.path().join("a.html"))]);
+    let (status, body) = get_changes(&data, &format!("?since={}", start)).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["complete"], true);
+    assert_eq!(body["added"], serde_json::json!(["/a.html"]));
+
+    let (_, body) = get_changes(
+        &data,
+        &format!("?since={}", body["now"].as_u64().unwrap() + 1),
+    )
+    .await;
+    assert_eq!(body["added"], serde_json::json!([]));
+}
+
+#[test]
+fn reload_log_numbers_batches_and_reports_missed_ones() {
+    let root = PathBuf::from("/site");
+    let log = ReloadLog::default();
+    assert_eq!(log.current(), 0);
+    assert!(!log.since(0).stale(0));
+
+    assert_eq!(log.record(&root, &[modify(&root.join("a.html"))]), 1);
+    let seq = log.record(
+        &root,
+        &[modify(&root.join("b.css")), modify(&root.join("b.css"))],
+    );
+    assert_eq!(seq, 2);
+
+    let catchup = log.since(0);
+    assert!(catchup.stale(0) && catchup.complete);
+    assert_eq!(catchup.current, 2);
+    assert_eq!(catchup.missed.len(), 2);
+    assert_eq!(catchup.missed[1].paths, vec!["/b.css"]);
+    assert!(!log.since(2).stale(2));
+    // A sequence from before a restart is stale too.
+    assert!(log.since(9).stale(9));
+}
+
+#[test]
+fn reload_batches_carry_the_net_change_per_path() {
+    let root = PathBuf::from("/site");
+    let log = ReloadLog::default();
+    let seq = log.record(
+        &root,
+        &[
+            create(&root.join("new.css")),
+            modify(&root.join("new.css")),
+            modify(&root.join("index.html")),
+            remove(&root.join("index.html")),
+        ],
+    );
+    let batch = log.event(seq).unwrap();
+    assert_eq!(batch.paths, vec!["/index.html", "/new.css"]);
+    let change = |path: &str, kind| ReloadChange {
+        path: path.to_string(),
+        kind,
+    };
+    assert_eq!(
+        batch.changes,
+        vec![
+            change("/index.html", ChangeKind::Removed),
+            change("/new.css", ChangeKind::Added),
+        ]
+    );
+    assert_eq!(log.event(seq + 1), None);
+}
+
+#[test]
+fn reload_log_is_bounded() {
+    let root = PathBuf::from("/site");
+    let log = ReloadLog::default();
+    for _ in 0..RELOAD_HISTORY_LIMIT + 5 {
+        log.record(&root, &[modify(&root.join("a.html"))]);
+    }
+    let catchup = log.since(0);
+    assert!(catchup.stale(0));
+    assert!(!catchup.complete);
+    assert_eq!(catchup.missed.len(), RELOAD_HISTORY_LIMIT);
+    assert!(log.since(10).complete);
+}
+
+#[test]
+fn relative_root_matches_absolute_watcher_paths() {
+    let temp = TempDir::new_in("target").unwrap();
+    // `--dir target/.tmpXXXX`, while the watcher reports absolute paths
+    let root = Path::new("target").join(temp.path().file_name().unwrap());
+    let absolute = temp.path().join("app.js");
+    std::fs::write(&absolute, "run()").unwrap();
+
+    let history = ChangeHistory::default();
+    history.record(&root, &[create(&absolute)]);
+    assert_eq!(history.since(0).added, vec!["/app.js"]);
+}
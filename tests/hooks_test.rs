@@ -0,0 +1,80 @@
+//! Embedder hooks: `on_request`, `on_response` and `resolve_path`.
+
+mod common;
+
+use actix_web::http::header::HeaderValue;
+use actix_web::{test, App, HttpResponse};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+use webserve::Webserve;
+
+fn site() -> TempDir {
+    common::site(&[("index.html", "<h1>Home</h1>"), ("v2/app.js", "v2()")])
+}
+
+#[actix_web::test]
+async fn request_hooks_answer_and_response_hooks_see_everything() {
+    let temp = site();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .on_request(|req| match req.headers().contains_key("x-api-key") {
+            true => None,
+            false => Some(HttpResponse::Unauthorized().body("key required")),
+        })
+        .on_response(|req, head| {
+            let path = HeaderValue::from_str(req.path()).unwrap();
+            head.headers_mut().insert("x-hooked".parse().unwrap(), path);
+        })
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert_eq!(res.status(), 401);
+    assert_eq!(res.headers().get("x-hooked").unwrap(), "/");
+    assert_eq!(test::read_body(res).await, "key required");
+
+    let req = test::TestRequest::get()
+        .uri("/v2/app.js")
+        .insert_header(("X-Api-Key", "k"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("x-hooked").unwrap(), "/v2/app.js");
+    assert_eq!(test::read_body(res).await, "v2()");
+}
+
+#[actix_web::test]
+async fn resolve_hooks_pick_the_file() {
+    let temp = site();
+    let outside = TempDir::new().unwrap();
+    fs::write(outside.path().join("shared.css"), "body{}").unwrap();
+    let shared = outside.path().join("shared.css");
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .resolve_path(|_, path| {
+            path.strip_prefix("/latest")
+                .map(|rest| PathBuf::from(format!("v2{}", rest)))
+        })
+        .resolve_path(move |_, path| (path == "/shared.css").then(|| shared.clone()))
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+    assert_eq!(
+        test::call_and_read_body(&app, get("/latest/app.js")).await,
+        "v2()"
+    );
+    assert_eq!(
+        test::call_and_read_body(&app, get("/shared.css")).await,
+        "body{}"
+    );
+    assert_eq!(
+        test::call_and_read_body(&app, get("/")).await,
+        "<h1>Home</h1>"
+    );
+    let res = test::call_service(&app, get("/latest/missing.js")).await;
+    assert_eq!(res.status(), 404);
+}
@@ -0,0 +1,109 @@
+//! Shared disk reads for concurrent requests of the same file.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App as ActixApp};
+use futures::StreamExt;
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{serve_file, AppState, InflightReads, COALESCE_MIN_BYTES};
+
+fn large_file(dir: &TempDir) -> Vec<u8> {
+    let body: Vec<u8> = (0..COALESCE_MIN_BYTES as usize * 2)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    fs::write(dir.path().join("video.bin"), &body).unwrap();
+    body
+}
+
+async fn collect(stream: impl futures::Stream<Item = std::io::Result<bytes::Bytes>>) -> Vec<u8> {
+    let chunks: Vec<_> = stream.collect().await;
+    chunks.into_iter().flat_map(|c| c.unwrap()).collect()
+}
+
+#[actix_web::test]
+async fn concurrent_streams_share_one_read() {
+    let temp = TempDir::new().unwrap();
+    let body = large_file(&temp);
+    let path = temp.path().join("video.bin");
+    let metadata = fs::metadata(&path).unwrap();
+
+    let reads = InflightReads::default();
+    let (first, joined_first) = reads.stream(&path, &metadata);
+    let (second, joined_second) = reads.stream(&path, &metadata);
+    assert!(!joined_first);
+    assert!(joined_second);
+    assert_eq!(reads.in_flight(), 1);
+
+    let (a, b) = futures::join!(collect(first), collect(second));
+    assert_eq!(a, body);
+    assert_eq!(b, body);
+    assert_eq!(reads.in_flight(), 0);
+
+    // A changed file is a different read.
+    fs::write(&path, b"short").unwrap();
+    let metadata = fs::metadata(&path).unwrap();
+    let (stream, joined) = reads.stream(&path, &metadata);
+    assert!(!joined);
+    assert_eq!(collect(stream).await, b"short");
+}
+
+#[actix_web::test]
+async fn served_files_keep_length_and_ranges() {
+    let temp = TempDir::new().unwrap();
+    let body = large_file(&temp);
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data.clone())
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/video.bin").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().contains_key(header::ETAG));
+    assert_eq!(test::read_body(res).await.as_ref(), body.as_slice());
+
+    let req = test::TestRequest::get()
+        .uri("/video.bin")
+        .insert_header((header::RANGE, "bytes=0-9"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(test::read_body(res).await.as_ref(), &body[..10]);
+}
+
+#[actix_web::test]
+async fn files_changed_under_a_read_never_send_another_length() {
+    use actix_web::body::{BodySize, MessageBody};
+    use actix_web::HttpResponse;
+
+    let temp = TempDir::new().unwrap();
+    let body = large_file(&temp);
+    let path = temp.path().join("video.bin");
+    let stale = fs::metadata(&path).unwrap();
+    let reads = InflightReads::default();
+
+    // Shrunk or grown after the stat: the stream fails instead of ending early or late
+    for contents in [
+        &body[..body.len() / 2],
+        &[body.as_slice(), b"more"].concat(),
+    ] {
+        fs::write(&path, contents).unwrap();
+        let (stream, _) = reads.stream(&path, &stale);
+        let chunks: Vec<_> = stream.collect().await;
+        assert!(chunks.last().unwrap().is_err());
+    }
+
+    // Already changed when the response is built: the opened file's body is kept
+    let req = test::TestRequest::get().uri("/video.bin").to_http_request();
+    let res = HttpResponse::Ok().body(body.clone());
+    let res = reads.share_body(&req, res, &path, &stale);
+    assert_eq!(res.body().size(), BodySize::Sized(body.len() as u64));
+    assert_eq!(reads.in_flight(), 0);
+}
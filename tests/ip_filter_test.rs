@@ -0,0 +1,131 @@
+//! `--allow` / `--deny` client IP filtering.
+
+use actix_web::middleware::from_fn;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{ip_filter, serve_file, AppState, Cidr, IpFilter, ServeOptions, Settings};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+fn filter(allow: &[&str], deny: &[&str]) -> IpFilter {
+    let parse = |specs: &[&str]| specs.iter().map(|s| Cidr::parse(s).unwrap()).collect();
+    IpFilter::new(parse(allow), parse(deny))
+}
+
+#[test]
+fn ip_filter_options_build_rules_and_reject_bad_blocks() {
+    let options = ServeOptions::from_iter(
+        [
+            "webserve",
+            "--allow",
+            "10.0.0.0/8",
+            "--allow",
+            "127.0.0.1",
+            "--deny",
+            "0.0.0.0/0",
+        ]
+        .iter(),
+    );
+    assert_eq!(Settings::from_options(&options).unwrap().ip_filter.len(), 3);
+
+    for bad in ["10.0.0.0/33", "not-an-ip", "::1/129", "10.0.0.0/"] {
+        let options = ServeOptions::from_iter(["webserve", "--deny", bad].iter());
+        assert!(Settings::from_options(&options).is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn most_specific_rule_wins() {
+    let cidr = Cidr::parse("10.0.0.0/8").unwrap();
+    assert!(cidr.contains(ip("10.200.1.1")));
+    assert!(!cidr.contains(ip("11.0.0.1")));
+    assert!(cidr.contains(ip("::ffff:10.0.0.1")));
+    assert!(!cidr.contains(ip("fd00::1")));
+    assert!(Cidr::parse("0.0.0.0/0")
+        .unwrap()
+        .contains(ip("203.0.113.9")));
+    assert!(Cidr::parse("2001:db8::/32")
+        .unwrap()
+        .contains(ip("2001:db8:ffff::1")));
+    assert_eq!(
+        Cidr::parse("192.168.1.7").unwrap().to_string(),
+        "192.168.1.7/32"
+    );
+
+    let office = filter(&["10.0.0.0/8"], &["0.0.0.0/0"]);
+    assert!(office.permits(ip("10.4.5.6")));
+    assert!(office.permits(ip("::ffff:10.4.5.6")));
+    assert!(!office.permits(ip("192.168.1.2")));
+    // A /0 deny covers native IPv6 clients too (`--host ::`)
+    assert!(!office.permits(ip("2001:db8::1")));
+    assert!(!office.permits(ip("::1")));
+    assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(ip("fd00::1")));
+
+    let carve_out = filter(&["10.0.0.0/8"], &["10.9.0.0/16"]);
+    assert!(carve_out.permits(ip("10.1.0.1")));
+    assert!(!carve_out.permits(ip("10.9.0.1")));
+
+    // With an allowlist, clients no rule matches are refused
+    let allow_only = filter(&["10.0.0.0/8", "fd00::/8"], &[]);
+    assert!(allow_only.permits(ip("10.4.5.6")));
+    assert!(allow_only.permits(ip("fd00::7")));
+    assert!(!allow_only.permits(ip("192.168.1.2")));
+    assert!(!allow_only.permits(ip("2001:db8::1")));
+    let deny_only = filter(&[], &["192.168.0.0/16"]);
+    assert!(deny_only.permits(ip("10.4.5.6")));
+    assert!(deny_only.permits(ip("2001:db8::1")));
+    assert!(!deny_only.permits(ip("192.168.1.2")));
+
+    let tie = filter(&["10.0.0.0/8"], &["10.0.0.0/8"]);
+    assert!(!tie.permits(ip("10.0.0.1")));
+    assert!(IpFilter::default().permits(ip("198.51.100.1")));
+}
+
+#[actix_web::test]
+async fn blocked_clients_get_403_before_files() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("index.html"), "<p>office only</p>").unwrap();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                ip_filter: filter(&["10.0.0.0/8"], &["0.0.0.0/0"]),
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data)
+            .wrap(from_fn(ip_filter))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let from = |peer: &str| {
+        test::TestRequest::get()
+            .uri("/")
+            .peer_addr(peer.parse::<SocketAddr>().unwrap())
+            .to_request()
+    };
+
+    let res = test::call_service(&app, from("10.0.0.7:50000")).await;
+    assert_eq!(res.status(), 200);
+    let res = test::call_service(&app, from("203.0.113.5:50000")).await;
+    assert_eq!(res.status(), 403);
+    assert_eq!(test::read_body(res).await, "403 Forbidden");
+    let res = test::call_service(&app, from("[2001:db8::1]:50000")).await;
+    assert_eq!(res.status(), 403);
+    // No peer address (Unix socket): not filtered.
+    let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert_eq!(res.status(), 200);
+}
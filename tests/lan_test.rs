@@ -0,0 +1,70 @@
+//! LAN URL discovery and the `--qr` code.
+
+use std::net::IpAddr;
+use structopt::StructOpt;
+use webserve::{
+    is_wildcard_host, lan_addresses, network_urls, qr_code, resolve_options_with_env, FileConfig,
+    ServeOptions,
+};
+
+fn ips(list: &[&str]) -> Vec<IpAddr> {
+    list.iter().map(|s| s.parse().unwrap()).collect()
+}
+
+#[test]
+fn wildcard_hosts_are_recognized() {
+    assert!(is_wildcard_host("0.0.0.0"));
+    assert!(is_wildcard_host("::"));
+    assert!(!is_wildcard_host("127.0.0.1"));
+    assert!(!is_wildcard_host("192.168.1.5"));
+}
+
+#[test]
+fn lan_addresses_skip_loopback_and_link_local() {
+    let interfaces = ips(&[
+        "127.0.0.1",
+        "192.168.1.20",
+        "169.254.3.4",
+        "10.0.0.7",
+        "::1",
+        "fe80::1",
+        "2001:db8::5",
+    ]);
+    assert_eq!(
+        lan_addresses("0.0.0.0", &interfaces),
+        ips(&["10.0.0.7", "192.168.1.20"])
+    );
+    assert_eq!(
+        lan_addresses("::", &interfaces),
+        ips(&["10.0.0.7", "192.168.1.20", "2001:db8::5"])
+    );
+    assert!(lan_addresses("127.0.0.1", &interfaces).is_empty());
+}
+
+#[test]
+fn network_urls_use_the_bound_port() {
+    let interfaces = ips(&["192.168.1.20", "2001:db8::5"]);
+    assert_eq!(
+        network_urls("::", 3000, &interfaces),
+        vec!["http://192.168.1.20:3000/", "http://[2001:db8::5]:3000/"]
+    );
+}
+
+#[test]
+fn qr_code_renders_half_blocks() {
+    let code = qr_code("http://192.168.1.20:8080/").unwrap();
+    let lines: Vec<&str> = code.lines().collect();
+    assert!(lines.len() > 10);
+    assert!(code.contains('▀') || code.contains('▄'));
+    let width = lines[0].chars().count();
+    assert!(lines.iter().all(|l| l.chars().count() == width));
+}
+
+#[test]
+fn qr_flag_parses() {
+    assert!(!ServeOptions::from_iter(&["webserve"]).qr);
+    assert!(ServeOptions::from_iter(&["webserve", "--qr"]).qr);
+    assert!(FileConfig::parse("qr = true").unwrap().qr.unwrap());
+    let env = vec![("WEBSERVE_QR".to_string(), "yes".to_string())];
+    assert!(resolve_options_with_env(["webserve"], env).unwrap().qr);
+}
@@ -0,0 +1,190 @@
+//! `--max-request-size`, `--read-timeout` and the `--allow-method` allowlist.
+
+mod common;
+
+use actix_web::dev::Payload;
+use actix_web::http::{header, Method};
+use actix_web::App;
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt};
+use std::env::temp_dir;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{allowed_methods, Fixtures, Hooks, ServeOptions, Settings, Webserve};
+
+#[test]
+fn limit_options_describe_and_reject_bad_values() {
+    let settings = Settings::from_options(&ServeOptions::from_iter(["webserve"])).unwrap();
+    assert_eq!(settings.request_limits.max_body_bytes, None);
+    assert_eq!(settings.request_limits.read_timeout, None);
+    assert_eq!(settings.request_limits.describe(), "off");
+
+    let options = ServeOptions::from_iter([
+        "webserve",
+        "--max-request-size",
+        "2",
+        "--read-timeout",
+        "30s",
+        "--allow-method",
+        "patch",
+        "--allow-method",
+        "PATCH",
+    ]);
+    let limits = Settings::from_options(&options).unwrap().request_limits;
+    assert_eq!(limits.max_body_bytes, Some(2 * 1024 * 1024));
+    assert_eq!(limits.read_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(limits.extra_methods, vec![Method::PATCH]);
+    assert_eq!(limits.describe(), "bodies up to 2 MiB, 30s to read");
+
+    let bad = |args: &[&str]| {
+        let options = ServeOptions::from_iter(["webserve"].iter().chain(args));
+        Settings::from_options(&options).unwrap_err()
+    };
+    assert!(bad(&["--max-request-size", "0"]).contains("at least 1 MiB"));
+    assert!(bad(&["--allow-method", "GE T"]).contains("invalid method"));
+}
+
+#[test]
+fn allowlist_grows_with_the_enabled_modes() {
+    let hooks = Hooks::default();
+    let methods = |settings: &Settings| {
+        allowed_methods(settings, &hooks).map(|m| {
+            m.iter()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    };
+    let read_only = Settings::default();
+    assert_eq!(methods(&read_only).as_deref(), Some("GET, HEAD, OPTIONS"));
+    let upload = Settings {
+        upload: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        methods(&upload).as_deref(),
+        Some("GET, HEAD, OPTIONS, PUT, POST, DELETE, MKCOL")
+    );
+
+    let with_fixtures = |yaml: &str| Settings {
+        fixtures: Some(Arc::new(Fixtures::parse(yaml, &temp_dir()).unwrap())),
+        ..Default::default()
+    };
+    let fixtures =
+        with_fixtures("routes:\n  - path: /a\n    method: patch\n  - path: /b\n    method: GET");
+    assert_eq!(
+        methods(&fixtures).as_deref(),
+        Some("GET, HEAD, OPTIONS, PATCH, PUT, POST")
+    );
+    // A route without a method answers any
+    let any_fixture = with_fixtures("routes:\n  - path: /a");
+    assert_eq!(methods(&any_fixture), None);
+
+    let mut hooks = Hooks::default();
+    hooks.on_request(|_| None);
+    assert_eq!(allowed_methods(&read_only, &hooks), None);
+}
+
+fn site() -> TempDir {
+    common::site(&[("index.html", "<p>hi</p>")])
+}
+
+#[actix_web::test]
+async fn unexpected_methods_get_405() {
+    use actix_web::test;
+
+    let temp = site();
+    let server = Webserve::builder().dir(temp.path()).build().unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+    let req = test::TestRequest::get().uri("/index.html").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    for method in ["PUT", "POST", "DELETE", "PROPFIND"] {
+        let req = test::TestRequest::default()
+            .method(Method::from_bytes(method.as_bytes()).unwrap())
+            .uri("/index.html")
+            .set_payload("x")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 405, "{}", method);
+        assert_eq!(
+            res.headers().get(header::ALLOW).unwrap(),
+            "GET, HEAD, OPTIONS"
+        );
+    }
+
+    // `--allow-method` adds to the list
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .configure(|o| o.allow_methods = vec!["DELETE".to_string()])
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let req = test::TestRequest::patch().uri("/index.html").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 405);
+    assert_eq!(
+        res.headers().get(header::ALLOW).unwrap(),
+        "GET, HEAD, OPTIONS, DELETE"
+    );
+}
+
+#[actix_web::test]
+async fn oversized_and_slow_bodies_are_cut_off() {
+    use actix_web::test;
+
+    let temp = site();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .configure(|o| {
+            o.upload = true;
+            o.max_request_size = Some(1);
+            o.read_timeout = Some(Duration::from_millis(200));
+        })
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let big = vec![b'x'; 1024 * 1024 + 1];
+
+    // Announced by Content-Length
+    let req = test::TestRequest::put()
+        .uri("/big.bin")
+        .set_payload(big.clone())
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 413);
+    assert!(!res.response().head().keep_alive());
+
+    // Streamed without a length
+    let mut req = test::TestRequest::put()
+        .uri("/big.bin")
+        .set_payload(big)
+        .to_request();
+    req.headers_mut().remove(header::CONTENT_LENGTH);
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 413);
+    assert!(!temp.path().join("big.bin").exists());
+
+    // A body that stops arriving
+    let stalled = stream::once(async { Ok(Bytes::from_static(b"partial")) })
+        .chain(stream::pending())
+        .boxed_local();
+    let req = test::TestRequest::put().uri("/slow.txt").to_request();
+    let (req, _) = req.replace_payload(Payload::from(stalled));
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 408);
+
+    // Within both limits
+    let req = test::TestRequest::put()
+        .uri("/ok.txt")
+        .set_payload("fine")
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    assert_eq!(
+        fs::read_to_string(temp.path().join("ok.txt")).unwrap(),
+        "fine"
+    );
+}
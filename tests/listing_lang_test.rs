@@ -0,0 +1,137 @@
+//! `--listing-lang`: translated directory listings with localized sizes and dates.
+
+use actix_web::http::header;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::sync::Arc;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{serve_file, AppState, ListingLang, ListingView, Locale, ServeOptions, Settings};
+
+#[test]
+fn listing_lang_takes_codes_with_regions() {
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    assert_eq!(options.listing_lang, ListingLang::Fixed(Locale::En));
+    let options = ServeOptions::from_iter(["webserve", "--listing-lang", "pt-BR"].iter());
+    assert_eq!(options.listing_lang, ListingLang::Fixed(Locale::Pt));
+    assert_eq!(
+        Settings::from_options(&options)
+            .unwrap()
+            .listing_lang
+            .to_string(),
+        "pt"
+    );
+    assert!(ServeOptions::from_iter_safe(["webserve", "--listing-lang", "xx"].iter()).is_err());
+}
+
+#[test]
+fn negotiates_accept_language() {
+    assert_eq!(Locale::negotiate("de-DE,de;q=0.9,en;q=0.8"), Locale::De);
+    assert_eq!(Locale::negotiate("sv, fr;q=0.4, ja;q=0.6"), Locale::Ja);
+    assert_eq!(Locale::negotiate("zh-Hans-CN"), Locale::Zh);
+    assert_eq!(Locale::negotiate("sv, nb;q=0.5"), Locale::En);
+    assert_eq!(Locale::negotiate("fr;q=0, es"), Locale::Es);
+    assert_eq!(ListingLang::Auto.resolve(None), Locale::En);
+    assert_eq!(
+        ListingLang::Fixed(Locale::Nl).resolve(Some("ja")),
+        Locale::Nl
+    );
+}
+
+#[test]
+fn formats_sizes_and_dates_per_locale() {
+    assert_eq!(Locale::En.format_size(1536), "1.5 KB");
+    assert_eq!(Locale::De.format_size(1536), "1,5 KB");
+    assert_eq!(Locale::Fr.format_size(3 * 1024 * 1024), "3,0 Mo");
+    assert_eq!(Locale::Fr.format_size(12), "12 o");
+    let (date, time) = ((2026, 3, 9), (14, 5, 0));
+    assert_eq!(
+        Locale::En.format_datetime(date, time),
+        "2026-03-09 14:05:00"
+    );
+    assert_eq!(
+        Locale::De.format_datetime(date, time),
+        "09.03.2026 14:05:00"
+    );
+    assert_eq!(
+        Locale::Es.format_datetime(date, time),
+        "09/03/2026 14:05:00"
+    );
+    assert_eq!(
+        Locale::Ja.format_datetime(date, time),
+        "2026/03/09 14:05:00"
+    );
+}
+
+#[tokio::test]
+async fn listing_uses_the_locale_labels() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("data.bin"), vec![0u8; 2048]).unwrap();
+    fs::create_dir(temp.path().join("docs")).unwrap();
+    let view = |locale, max_entries| ListingView {
+        locale,
+        max_entries,
+        ..Default::default()
+    };
+    let listing = view(Locale::De, None).render(temp.path(), "/docs/").await;
+    assert!(listing.contains(r#"<html lang="de""#));
+    assert!(listing.contains("<title>Inhalt von /docs</title>"));
+    assert!(listing.contains("<th class=\"size\">Größe</th>"));
+    assert!(listing.contains("2,0 KB"));
+
+    let listing = view(Locale::Fr, Some(1)).render(temp.path(), "/").await;
+    assert!(listing.contains("Liste interrompue après 1 entrées"));
+
+    let listing = view(Locale::En, None).render(temp.path(), "/").await;
+    assert!(listing.contains("<title>Index of /</title>"));
+    assert!(listing.contains("<th>Name</th>"));
+}
+
+#[actix_web::test]
+async fn auto_follows_accept_language_and_varies() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("notes.txt"), "hi").unwrap();
+    let app_with = |lang: ListingLang| {
+        web::Data::new(AppState {
+            static_dir: Arc::new(temp.path().to_path_buf()),
+            settings: Arc::new(
+                Settings {
+                    listing_lang: lang,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            ..Default::default()
+        })
+    };
+    let get = || {
+        test::TestRequest::get()
+            .uri("/")
+            .insert_header((header::ACCEPT_LANGUAGE, "es-MX,es;q=0.9"))
+            .to_request()
+    };
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_with(ListingLang::Auto))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, get()).await;
+    assert_eq!(res.headers().get(header::VARY).unwrap(), "Accept-Language");
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("<th>Nombre</th>"), "{}", body);
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_with(ListingLang::Fixed(Locale::It)))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let res = test::call_service(&app, get()).await;
+    assert!(!res.headers().contains_key(header::VARY));
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("<th>Nome</th>"));
+}
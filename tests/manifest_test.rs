@@ -0,0 +1,98 @@
+//! `/__webserve/manifest` and the manifest builder.
+
+mod common;
+
+use actix_web::{web, App as ActixApp};
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{build_manifest, manifest, AppState, FileHashCache, WalkLimits, ADMIN_PREFIX};
+
+// SHA-256 of "hello"
+const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+fn site() -> TempDir {
+    common::site(&[
+        ("index.html", "hello"),
+        ("assets/js/app.js", "console.log(1)"),
+    ])
+}
+
+#[test]
+fn manifest_lists_files_with_sizes_and_hashes() {
+    let temp = site();
+    let m = build_manifest(
+        temp.path(),
+        WalkLimits::default(),
+        &FileHashCache::default(),
+    );
+    let paths: Vec<&str> = m.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["/assets/js/app.js", "/index.html"]);
+    assert_eq!(m.file_count, 2);
+    assert_eq!(m.total_size, 5 + 14);
+    assert!(!m.truncated);
+    let index = &m.files[1];
+    assert_eq!(index.size, 5);
+    assert_eq!(index.sha256, HELLO_SHA256);
+}
+
+#[test]
+fn manifest_respects_walk_limits() {
+    let temp = site();
+    let limits = WalkLimits {
+        max_depth: Some(1),
+        max_entries: None,
+    };
+    let m = build_manifest(temp.path(), limits, &FileHashCache::default());
+    let paths: Vec<&str> = m.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["/index.html"]);
+    assert!(m.truncated);
+}
+
+async fn fetch(data: &web::Data<AppState>) -> serde_json::Value {
+    use actix_web::test;
+    let app = test::init_service(ActixApp::new().app_data(data.clone()).route(
+        &format!("{}/manifest", ADMIN_PREFIX),
+        web::get().to(manifest),
+    ))
+    .await;
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/manifest", ADMIN_PREFIX))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert!(res.status().is_success());
+    test::read_body_json(res).await
+}
+
+#[actix_web::test]
+async fn manifest_endpoint_returns_json() {
+    let temp = site();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        ..Default::default()
+    });
+    let body = fetch(&data).await;
+    assert_eq!(body["file_count"], 2);
+    assert_eq!(body["files"][1]["path"], "/index.html");
+    assert_eq!(body["files"][1]["sha256"], HELLO_SHA256);
+
+    // Without --watch every request sees the current tree
+    std::fs::write(temp.path().join("new.txt"), b"x").unwrap();
+    assert_eq!(fetch(&data).await["file_count"], 3);
+}
+
+#[actix_web::test]
+async fn watch_mode_caches_until_invalidated() {
+    let temp = site();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        watch: true,
+        ..Default::default()
+    });
+    assert_eq!(fetch(&data).await["file_count"], 2);
+    std::fs::write(temp.path().join("new.txt"), b"x").unwrap();
+    assert_eq!(fetch(&data).await["file_count"], 2);
+
+    // What the watcher does on a change
+    *data.manifest.write().unwrap() = None;
+    assert_eq!(fetch(&data).await["file_count"], 3);
+}
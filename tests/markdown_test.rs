@@ -0,0 +1,106 @@
+//! `--render-markdown`: `.md` files and directory READMEs as HTML.
+
+#![cfg(feature = "markdown")]
+
+use actix_web::http::header;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{is_markdown, render_markdown, serve_file, wants_raw, AppState, Branding, Settings};
+
+#[test]
+fn renders_a_styled_page() {
+    assert!(is_markdown(Path::new("docs/Guide.MD")));
+    assert!(is_markdown(Path::new("notes.markdown")));
+    assert!(!is_markdown(Path::new("README")));
+    assert!(wants_raw("raw=1"));
+    assert!(wants_raw("v=2&raw"));
+    assert!(!wants_raw("raw=0"));
+    assert!(!wants_raw("draw=1"));
+
+    let source = "# Install `webserve`\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n";
+    let page = render_markdown(source, "guide.md", &Branding::default());
+    assert!(page.contains("<title>Install webserve</title>"), "{}", page);
+    assert!(page.contains("<h1>Install <code>webserve</code></h1>"));
+    assert!(page.contains("<table>"));
+    assert!(page.contains(r#"type="checkbox""#));
+
+    let page = render_markdown("plain <text>", "notes & todo.md", &Branding::default());
+    assert!(page.contains("<title>notes &amp; todo.md</title>"));
+    assert!(
+        page.contains("<p>plain <text></p>"),
+        "raw HTML is passed through"
+    );
+}
+
+#[actix_web::test]
+async fn serves_rendered_markdown_and_raw_source() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("docs")).unwrap();
+    fs::write(temp.path().join("docs/README.md"), "# Docs home\n").unwrap();
+    fs::write(temp.path().join("docs/setup.md"), "# Setup\n\nRun it.\n").unwrap();
+    fs::create_dir(temp.path().join("site")).unwrap();
+    fs::write(temp.path().join("site/index.html"), "<p>site</p>").unwrap();
+    fs::write(temp.path().join("site/README.md"), "# Site\n").unwrap();
+    let data_with = |render_markdown: bool| {
+        web::Data::new(AppState {
+            static_dir: Arc::new(temp.path().to_path_buf()),
+            settings: Arc::new(
+                Settings {
+                    render_markdown,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            ..Default::default()
+        })
+    };
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data_with(true))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/docs/setup.md")).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("<h1>Setup</h1>"));
+
+    let res = test::call_service(&app, get("/docs/setup.md?raw=1")).await;
+    assert_eq!(res.status(), 200);
+    assert_ne!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/html"
+    );
+    assert_eq!(test::read_body(res).await, "# Setup\n\nRun it.\n");
+
+    let body = test::call_and_read_body(&app, get("/docs/")).await;
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("<title>Docs home</title>"), "{}", body);
+    // index.html still wins over README.md.
+    assert_eq!(
+        test::call_and_read_body(&app, get("/site/")).await,
+        "<p>site</p>"
+    );
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data_with(false))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    assert_eq!(
+        test::call_and_read_body(&app, get("/docs/setup.md")).await,
+        "# Setup\n\nRun it.\n"
+    );
+}
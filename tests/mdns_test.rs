@@ -0,0 +1,48 @@
+//! `--mdns` name validation and announced addresses.
+
+use std::net::IpAddr;
+use webserve::{mdns_addresses, mdns_label, mdns_url};
+
+fn ips(list: &[&str]) -> Vec<IpAddr> {
+    list.iter().map(|s| s.parse().unwrap()).collect()
+}
+
+#[test]
+fn mdns_names_become_single_labels() {
+    assert_eq!(mdns_label("my-site").unwrap(), "my-site");
+    assert_eq!(mdns_label("My-Site.local").unwrap(), "my-site");
+    assert_eq!(mdns_label("docs.local.").unwrap(), "docs");
+    assert!(mdns_label("").is_err());
+    assert!(mdns_label("-site").is_err());
+    assert!(mdns_label("my.site").is_err());
+    assert!(mdns_label("my_site").is_err());
+    assert!(mdns_label(&"a".repeat(64)).is_err());
+    assert_eq!(mdns_url("my-site", 3000), "http://my-site.local:3000/");
+}
+
+#[test]
+fn mdns_addresses_follow_the_bind_host() {
+    let interfaces = ips(&["127.0.0.1", "192.168.1.20", "::1", "2001:db8::5"]);
+    assert_eq!(
+        mdns_addresses(&["0.0.0.0"], &interfaces),
+        Some(ips(&["192.168.1.20"]))
+    );
+    assert_eq!(
+        mdns_addresses(&["192.168.1.20"], &interfaces),
+        Some(ips(&["192.168.1.20"]))
+    );
+    assert_eq!(
+        mdns_addresses(&["[2001:db8::5]"], &interfaces),
+        Some(ips(&["2001:db8::5"]))
+    );
+    assert_eq!(mdns_addresses(&["127.0.0.1"], &interfaces), None);
+    assert_eq!(mdns_addresses(&["localhost"], &interfaces), None);
+    assert_eq!(
+        mdns_addresses(&["::1", "192.168.1.20", "127.0.0.1"], &interfaces),
+        Some(ips(&["192.168.1.20"]))
+    );
+    assert_eq!(
+        mdns_addresses(&["192.168.1.20", "preview.lan"], &interfaces),
+        Some(Vec::new())
+    );
+}
@@ -0,0 +1,92 @@
+//! `HEAD` and `OPTIONS` on served paths.
+
+mod common;
+
+use actix_web::{web, App};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use tempfile::TempDir;
+use webserve::{Webserve, READ_METHODS, UPLOAD_METHODS};
+
+fn site() -> TempDir {
+    common::site(&[("index.html", "<h1>Home</h1>"), ("a.txt", "hello")])
+}
+
+/// Sends `method path` and returns the raw response, read until the server closes.
+fn request(port: u16, method: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        method, path
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+fn content_length(response: &str) -> Option<usize> {
+    response.lines().find_map(|l| {
+        let (name, value) = l.split_once(':')?;
+        name.eq_ignore_ascii_case("content-length")
+            .then(|| value.trim().parse().ok())?
+    })
+}
+
+#[actix_web::test]
+async fn head_sends_the_get_headers_without_a_body() {
+    let temp = site();
+    let handle = Webserve::builder()
+        .dir(temp.path())
+        .watch(true)
+        .bind(([127, 0, 0, 1], 0).into())
+        .configure(|o| o.workers = Some(1))
+        .start()
+        .unwrap();
+    let port = handle.addr().port();
+    handle.await_ready().await.unwrap();
+
+    for path in ["/a.txt", "/index.html", "/"] {
+        let (get, head) =
+            web::block(move || (request(port, "GET", path), request(port, "HEAD", path)))
+                .await
+                .unwrap();
+        assert!(head.starts_with("HTTP/1.1 200"), "{}: {}", path, head);
+        let (body_start, _) = head.split_once("\r\n\r\n").unwrap();
+        assert_eq!(head.len(), body_start.len() + 4, "{}: {}", path, head);
+        // Injected HTML reports the length of the page GET sends
+        let get_body = get.split_once("\r\n\r\n").unwrap().1;
+        assert_eq!(content_length(&head), Some(get_body.len()), "{}", path);
+    }
+
+    let missing = web::block(move || request(port, "HEAD", "/missing"))
+        .await
+        .unwrap();
+    assert!(missing.starts_with("HTTP/1.1 404"), "{}", missing);
+    handle.stop().await.unwrap();
+}
+
+#[actix_web::test]
+async fn options_lists_the_allowed_methods() {
+    use actix_web::test;
+
+    let temp = site();
+    for (upload, allow) in [(false, READ_METHODS), (true, UPLOAD_METHODS)] {
+        let server = Webserve::builder()
+            .dir(temp.path())
+            .configure(|o| o.upload = upload)
+            .build()
+            .unwrap();
+        let app = test::init_service(App::new().service(server.into_actix_service())).await;
+        for uri in ["/", "/a.txt"] {
+            let req = test::TestRequest::default()
+                .method(actix_web::http::Method::OPTIONS)
+                .uri(uri)
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), 204, "{}", uri);
+            assert_eq!(res.headers().get("allow").unwrap(), allow, "{}", uri);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! `--mime EXT=TYPE` Content-Type overrides.
+
+use actix_web::App;
+use std::fs;
+use std::path::Path;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{MimeOverrides, ServeOptions, Webserve};
+
+#[test]
+fn parses_specs_and_matches_extensions() {
+    let (ext, mime) = MimeOverrides::parse(".GLB = model/gltf-binary").unwrap();
+    assert_eq!((ext.as_str(), mime.as_ref()), ("glb", "model/gltf-binary"));
+    for bad in [
+        "glb",
+        "=model/gltf-binary",
+        "glb=not a type",
+        "a/b=text/plain",
+    ] {
+        assert!(MimeOverrides::parse(bad).is_err(), "{}", bad);
+    }
+
+    let options = ServeOptions::from_iter(["webserve", "--mime", "glb=model/gltf-binary"]);
+    let overrides = MimeOverrides::from_options(&options).unwrap();
+    assert_eq!(
+        overrides
+            .for_path(Path::new("scene.GLB"))
+            .map(|m| m.as_ref()),
+        Some("model/gltf-binary")
+    );
+    assert_eq!(overrides.for_path(Path::new("scene.gltf")), None);
+}
+
+#[actix_web::test]
+async fn overrides_and_adds_content_types() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("scene.glb"), b"glTF").unwrap();
+    fs::write(temp.path().join("notes.log"), "plain").unwrap();
+    fs::write(temp.path().join("app.js"), "export {}").unwrap();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .configure(|o| {
+            o.mime = vec![
+                "glb=model/gltf-binary".to_string(),
+                "log=text/plain; charset=utf-8".to_string(),
+            ]
+        })
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/scene.glb")).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "model/gltf-binary"
+    );
+    assert!(res
+        .headers()
+        .get("content-disposition")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+
+    // A text type is displayed rather than downloaded
+    let res = test::call_service(&app, get("/notes.log")).await;
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    assert!(res
+        .headers()
+        .get("content-disposition")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("inline"));
+
+    let res = test::call_service(&app, get("/app.js")).await;
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "text/javascript; charset=utf-8"
+    );
+}
@@ -73,27 +73,26 @@ async fn serve_file_rejects_parent_dir() {
     use std::sync::Arc;
     use tempfile::TempDir;
     use tokio::sync::broadcast;
-    use webserve::{serve_file, AppState};
+    use webserve::{serve_file, AppState, ReloadEvent};
 
     let temp_dir = TempDir::new().unwrap();
     let static_dir = Arc::new(temp_dir.path().to_path_buf());
-    let (tx, _) = broadcast::channel::<()>(16);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
     let app_state = web::Data::new(AppState {
         static_dir,
         watch: false,
-        spa: false,
         addr: "127.0.0.1:8080".to_string(),
         tx,
-        redirect_dir_slash: true,
         reload_pending: Arc::new(AtomicBool::new(false)),
         html_cache: None,
+        ..Default::default()
     });
     let app = ActixApp::new()
         .app_data(app_state)
         .route("/{_:.*}", web::get().to(serve_file));
-    let mut app = test::init_service(app).await;
+    let app = test::init_service(app).await;
     let req = test::TestRequest::get().uri("/../Cargo.toml").to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
 }
 
@@ -124,7 +123,7 @@ async fn serve_file_redirects_directory_without_slash() {
     use std::sync::Arc;
     use tempfile::TempDir;
     use tokio::sync::broadcast;
-    use webserve::{serve_file, AppState};
+    use webserve::{serve_file, AppState, ReloadEvent};
 
     let temp_dir = TempDir::new().unwrap();
     fs::create_dir(temp_dir.path().join("docs")).unwrap();
@@ -134,26 +133,25 @@ async fn serve_file_redirects_directory_without_slash() {
     )
     .unwrap();
     let static_dir = Arc::new(temp_dir.path().to_path_buf());
-    let (tx, _) = broadcast::channel::<()>(16);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
     let app_state = web::Data::new(AppState {
         static_dir,
         watch: false,
-        spa: false,
         addr: "127.0.0.1:8080".to_string(),
         tx,
-        redirect_dir_slash: true,
         reload_pending: Arc::new(std::sync::atomic::AtomicBool::new(false)),
         html_cache: None,
+        ..Default::default()
     });
     let app = ActixApp::new()
         .app_data(app_state)
         .route("/{_:.*}", web::get().to(serve_file));
-    let mut app = test::init_service(app).await;
+    let app = test::init_service(app).await;
     let req = test::TestRequest::get().uri("/docs").to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(
         resp.status(),
-        actix_web::http::StatusCode::TEMPORARY_REDIRECT
+        actix_web::http::StatusCode::MOVED_PERMANENTLY
     );
     assert_eq!(
         resp.headers().get("location").unwrap().to_str().unwrap(),
@@ -169,7 +167,7 @@ async fn serve_file_no_redirect_dir_slash_serves_index() {
     use std::sync::Arc;
     use tempfile::TempDir;
     use tokio::sync::broadcast;
-    use webserve::{serve_file, AppState};
+    use webserve::{serve_file, AppState, ReloadEvent, Settings};
 
     let temp_dir = TempDir::new().unwrap();
     fs::create_dir(temp_dir.path().join("sub")).unwrap();
@@ -179,23 +177,29 @@ async fn serve_file_no_redirect_dir_slash_serves_index() {
     )
     .unwrap();
     let static_dir = Arc::new(temp_dir.path().to_path_buf());
-    let (tx, _) = broadcast::channel::<()>(16);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
     let app_state = web::Data::new(AppState {
         static_dir,
         watch: false,
-        spa: false,
         addr: "127.0.0.1:8080".to_string(),
         tx,
-        redirect_dir_slash: false,
+        settings: Arc::new(
+            Settings {
+                redirect_dir_slash: false,
+                ..Default::default()
+            }
+            .into(),
+        ),
         reload_pending: Arc::new(AtomicBool::new(false)),
         html_cache: None,
+        ..Default::default()
     });
     let app = ActixApp::new()
         .app_data(app_state)
         .route("/{_:.*}", web::get().to(serve_file));
-    let mut app = test::init_service(app).await;
+    let app = test::init_service(app).await;
     let req = test::TestRequest::get().uri("/sub").to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
     let body = test::read_body(resp).await;
     let body_str = String::from_utf8(body.to_vec()).unwrap();
@@ -224,3 +228,67 @@ fn validate_static_root_unit() {
         Err(StaticDirError::NotADirectory)
     );
 }
+
+#[test]
+fn percent_decode_and_encode_round_trip() {
+    use webserve::{encode_url_path, normalize_url_path, percent_decode_path};
+    assert_eq!(
+        percent_decode_path("/my%20docs/r%C3%A9sum%C3%A9.txt").as_deref(),
+        Some("/my docs/résumé.txt")
+    );
+    assert!(percent_decode_path("/bad%2").is_none());
+    assert!(percent_decode_path("/bad%zz").is_none());
+    // Encoded traversal is decoded first, then rejected by normalization.
+    let decoded = percent_decode_path("/a/%2E%2E/secret").unwrap();
+    assert!(normalize_url_path(&decoded).is_none());
+    assert_eq!(encode_url_path("/my docs/a#b.txt"), "/my%20docs/a%23b.txt");
+    assert_eq!(encode_url_path(""), "/");
+}
+
+#[actix_web::test]
+async fn serve_file_resolves_encoded_paths_and_encodes_listing_links() {
+    use actix_web::{test, web, App as ActixApp};
+    use std::fs;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use webserve::{serve_file, AppState};
+
+    let temp_dir = TempDir::new().unwrap();
+    let sub = temp_dir.path().join("my docs");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("a b.txt"), b"spaced").unwrap();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp_dir.path().to_path_buf()),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state)
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/my%20docs").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.status(),
+        actix_web::http::StatusCode::MOVED_PERMANENTLY
+    );
+    assert_eq!(
+        resp.headers().get("location").unwrap().to_str().unwrap(),
+        "/my%20docs/"
+    );
+
+    let req = test::TestRequest::get().uri("/my%20docs/").to_request();
+    let resp = test::call_service(&app, req).await;
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains(r#"href="/my%20docs/a%20b.txt""#));
+    assert!(body.contains(r#"href="/my%20docs/""#));
+
+    let req = test::TestRequest::get()
+        .uri("/my%20docs/a%20b.txt")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(test::read_body(resp).await, "spaced");
+}
@@ -0,0 +1,147 @@
+//! `--mock`: json-server style CRUD routes backed by a JSON file.
+
+mod common;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::App;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+use webserve::{ServeOptions, Webserve};
+
+const DB: &str = r#"{
+    "posts": [
+        { "id": 1, "title": "Hello", "author": "ada" },
+        { "id": 2, "title": "Again", "author": "bob" }
+    ],
+    "profile": { "name": "webserve" }
+}"#;
+
+fn site() -> TempDir {
+    common::site(&[("index.html", "<h1>Home</h1>"), ("db.json", DB)])
+}
+
+fn server(root: &Path, configure: impl FnOnce(&mut ServeOptions)) -> Webserve {
+    let mock = root.join("db.json");
+    Webserve::builder()
+        .dir(root)
+        .configure(|o| {
+            o.mock = Some(mock);
+            configure(o);
+        })
+        .build()
+        .unwrap()
+}
+
+async fn status_and_json(res: ServiceResponse) -> (u16, Value) {
+    let status = res.status().as_u16();
+    let body = actix_web::test::read_body(res).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn collections_answer_crud_requests() {
+    use actix_web::test::{self, TestRequest};
+
+    let temp = site();
+    let server = server(temp.path(), |_| {});
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let call = |req: TestRequest| test::call_service(&app, req.to_request());
+
+    let (status, posts) = status_and_json(call(TestRequest::get().uri("/posts")).await).await;
+    assert_eq!(status, 200);
+    assert_eq!(posts.as_array().unwrap().len(), 2);
+    let (_, found) = status_and_json(call(TestRequest::get().uri("/posts?author=bob")).await).await;
+    assert_eq!(
+        found,
+        json!([{ "id": 2, "title": "Again", "author": "bob" }])
+    );
+    let res = call(TestRequest::get().uri("/posts?_sort=id&_order=desc&_limit=1")).await;
+    assert_eq!(res.headers().get("x-total-count").unwrap(), "2");
+    assert_eq!(status_and_json(res).await.1[0]["id"], 2);
+
+    let res = call(
+        TestRequest::post()
+            .uri("/posts")
+            .set_json(json!({ "title": "New" })),
+    )
+    .await;
+    assert_eq!(res.status(), 201);
+    assert_eq!(res.headers().get("location").unwrap(), "/posts/3");
+    let res = call(
+        TestRequest::post()
+            .uri("/posts")
+            .set_json(json!({ "id": 1 })),
+    )
+    .await;
+    assert_eq!(res.status(), 409);
+
+    let patch = TestRequest::patch()
+        .uri("/posts/3")
+        .set_json(json!({ "author": "eve" }));
+    assert_eq!(
+        status_and_json(call(patch).await).await,
+        (200, json!({ "id": 3, "title": "New", "author": "eve" }))
+    );
+    let put = TestRequest::put()
+        .uri("/posts/3")
+        .set_json(json!({ "title": "Replaced" }));
+    assert_eq!(
+        status_and_json(call(put).await).await,
+        (200, json!({ "id": 3, "title": "Replaced" }))
+    );
+    assert_eq!(
+        call(TestRequest::delete().uri("/posts/3")).await.status(),
+        200
+    );
+    assert_eq!(call(TestRequest::get().uri("/posts/3")).await.status(), 404);
+    assert_eq!(
+        call(TestRequest::delete().uri("/posts")).await.status(),
+        405
+    );
+
+    // Files still answer, and without --mock-persist the file is untouched
+    let res = call(TestRequest::get().uri("/")).await;
+    assert_eq!(test::read_body(res).await, "<h1>Home</h1>");
+    assert_eq!(fs::read_to_string(temp.path().join("db.json")).unwrap(), DB);
+}
+
+#[actix_web::test]
+async fn persists_under_a_prefix_and_rereads_edits() {
+    use actix_web::test::{self, TestRequest};
+
+    let temp = site();
+    let server = server(temp.path(), |o| {
+        o.mock_prefix = "/api".to_string();
+        o.mock_persist = true;
+    });
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let call = |req: TestRequest| test::call_service(&app, req.to_request());
+
+    assert_eq!(call(TestRequest::get().uri("/posts")).await.status(), 404);
+    let patch = TestRequest::patch()
+        .uri("/api/profile")
+        .set_json(json!({ "theme": "dark" }));
+    assert_eq!(
+        status_and_json(call(patch).await).await,
+        (200, json!({ "name": "webserve", "theme": "dark" }))
+    );
+    let db = temp.path().join("db.json");
+    let saved: Value = serde_json::from_str(&fs::read_to_string(&db).unwrap()).unwrap();
+    assert_eq!(saved["profile"]["theme"], "dark");
+
+    fs::write(&db, r#"{ "todos": [] }"#).unwrap();
+    let file = fs::File::options().write(true).open(&db).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(
+        status_and_json(call(TestRequest::get().uri("/api/todos")).await).await,
+        (200, json!([]))
+    );
+    assert_eq!(
+        call(TestRequest::get().uri("/api/posts")).await.status(),
+        404
+    );
+}
@@ -0,0 +1,122 @@
+//! `--mount PREFIX=DIR` and `--vhost HOST=DIR`.
+
+mod common;
+
+use actix_web::http::header;
+use actix_web::App;
+use notify::event::{DataChange, ModifyKind};
+use notify::{Event, EventKind};
+use std::fs;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{sites, FileConfig, Mount, ServeOptions, Webserve};
+
+fn edit(dir: &Path) -> Event {
+    Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+        .add_path(dir.join("index.html"))
+}
+
+#[test]
+fn sites_parse_and_refuse_clashes() {
+    let options = ServeOptions::from_iter([
+        "webserve",
+        "--mount",
+        "/docs/=./docs",
+        "--vhost",
+        "Blog.localhost=./blog",
+    ]);
+    assert_eq!(
+        sites(&options).unwrap(),
+        vec![
+            (Mount::Path("/docs".to_string()), PathBuf::from("./docs")),
+            (
+                Mount::Host("blog.localhost".to_string()),
+                PathBuf::from("./blog")
+            ),
+        ]
+    );
+    for bad in [
+        "docs=./docs",
+        "/=./docs",
+        "/docs",
+        "/a b=./docs",
+        "/../x=./docs",
+        "/reload=./docs",
+        "/__webserve/x=./docs",
+    ] {
+        assert!(Mount::parse_path(bad).is_err(), "{}", bad);
+    }
+    assert!(Mount::parse_host("a/b=./x").is_err());
+    let twice = ServeOptions::from_iter(["webserve", "--mount", "/a=x", "--mount", "/a/=y"]);
+    assert_eq!(sites(&twice).unwrap_err(), "/a is configured twice");
+}
+
+#[test]
+fn configured_site_directories_are_relative_to_the_config() {
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("webserve.toml");
+    fs::write(&path, "[mounts]\n\"/docs\" = \"docs\"").unwrap();
+    let config = FileConfig::load(&path).unwrap();
+    assert_eq!(config.mounts["/docs"], temp.path().join("docs"));
+}
+
+#[actix_web::test]
+async fn mounts_and_vhosts_serve_and_reload_on_their_own() {
+    use actix_web::test;
+
+    let site = |body| common::site(&[("index.html", body)]);
+    let (root, docs, blog) = (site("root"), site("docs"), site("blog"));
+    fs::create_dir(docs.path().join("guide")).unwrap();
+    fs::write(docs.path().join("guide/a.txt"), "a").unwrap();
+    let server = Webserve::builder()
+        .dir(root.path())
+        .watch(true)
+        .configure(|o| {
+            o.mounts = vec![format!("/docs={}", docs.path().display())];
+            o.vhosts = vec![format!("blog.localhost={}", blog.path().display())];
+        })
+        .build()
+        .unwrap();
+    let root_state = server.state().clone();
+    let docs_state = server.sites()[0].state().clone();
+    assert_eq!(docs_state.mount, Mount::Path("/docs".to_string()));
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let body = test::call_and_read_body(&app, get("/docs/")).await;
+    let page = String::from_utf8_lossy(&body);
+    assert!(page.starts_with("docs"), "{}", page);
+    assert!(page.contains(r#"new URL("/docs/reload", location.href)"#));
+    let body = test::call_and_read_body(&app, get("/")).await;
+    assert!(String::from_utf8_lossy(&body).contains(r#"new URL("/reload", location.href)"#));
+    let req = test::TestRequest::get()
+        .uri("/")
+        .insert_header(("Host", "blog.localhost:8080"))
+        .to_request();
+    assert!(test::call_and_read_body(&app, req)
+        .await
+        .starts_with(b"blog"));
+
+    // Redirects and listings stay under the prefix
+    let res = test::call_service(&app, get("/docs?x=1")).await;
+    assert_eq!(res.status(), 301);
+    assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/docs/?x=1");
+    let res = test::call_service(&app, get("/docs/guide")).await;
+    assert_eq!(res.status(), 301);
+    assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/docs/guide/");
+    let body = test::call_and_read_body(&app, get("/docs/guide/")).await;
+    assert!(String::from_utf8_lossy(&body).contains(r#"href="/docs/guide/a.txt""#));
+    let res = test::call_service(&app, get("/docsx")).await;
+    assert_eq!(res.status(), 404);
+
+    // Editing the mounted site reloads only its readers
+    docs_state.publish_changes(&[edit(docs.path())]);
+    let res = test::call_service(&app, get("/reload?since=0")).await;
+    assert_eq!(res.status(), 204);
+    let res = test::call_service(&app, get("/docs/reload?since=0")).await;
+    assert_eq!(res.status(), 200);
+    root_state.publish_changes(&[edit(root.path())]);
+    let res = test::call_service(&app, get("/reload?since=0")).await;
+    assert_eq!(res.status(), 200);
+}
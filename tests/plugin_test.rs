@@ -0,0 +1,202 @@
+//! `--plugin` option and WebAssembly handlers (with `--features wasm-plugins`).
+
+#[cfg(not(feature = "wasm-plugins"))]
+#[test]
+fn plugin_flag_needs_the_feature() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--plugin", "handler.wasm", "-p", "0"])
+        .output()
+        .expect("run webserve binary");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--features wasm-plugins"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[cfg(feature = "wasm-plugins")]
+mod wasm {
+    use actix_web::body::to_bytes;
+    use actix_web::{test, App};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+    use webserve::{Plugin, Webserve};
+
+    const PRELUDE: &str = r#"
+        (memory (export "memory") 1)
+        (func (export "webserve_alloc") (param i32) (result i32) i32.const 1024)"#;
+
+    /// A module whose `export` always returns `json`.
+    fn answering(export: &str, json: &str) -> String {
+        let escaped = json.replace('"', "\\\"");
+        format!(
+            r#"(module {}
+                (data (i32.const 16) "{}")
+                (func (export "{}") (param i32 i32) (result i64)
+                    i64.const {}))"#,
+            PRELUDE,
+            escaped,
+            export,
+            (16u64 << 32) | json.len() as u64
+        )
+    }
+
+    fn write(dir: &Path, name: &str, wat: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+        path
+    }
+
+    fn site() -> TempDir {
+        let temp = TempDir::new().unwrap();
+        fs::write(temp.path().join("index.html"), "<h1>Home</h1>").unwrap();
+        temp
+    }
+
+    #[actix_web::test]
+    async fn request_handlers_see_the_request_and_answer() {
+        let temp = site();
+        // Hands its input back: the request's headers become the response's
+        let echo = format!(
+            r#"(module {}
+                (func (export "webserve_on_request") (param i32 i32) (result i64)
+                    (i64.or
+                        (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32))
+                        (i64.extend_i32_u (local.get 1)))))"#,
+            PRELUDE
+        );
+        let plugin = write(temp.path(), "echo.wasm", &echo);
+        let server = Webserve::builder()
+            .dir(temp.path())
+            .configure(|o| o.plugins = vec![plugin])
+            .build()
+            .unwrap();
+        let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/index.html")
+            .insert_header(("X-Echo", "hello"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers().get("x-echo").unwrap(), "hello");
+        assert_eq!(test::read_body(res).await, "");
+    }
+
+    #[actix_web::test]
+    async fn response_handlers_edit_headers_and_pass_requests() {
+        let temp = site();
+        let wat = format!(
+            r#"(module {}
+                (data (i32.const 16) "{{\"headers\":{{\"x-plugin\":\"yes\"}}}}")
+                (func (export "webserve_on_request") (param i32 i32) (result i64)
+                    i64.const 0)
+                (func (export "webserve_on_response") (param i32 i32) (result i64)
+                    i64.const {}))"#,
+            PRELUDE,
+            (16u64 << 32) | r#"{"headers":{"x-plugin":"yes"}}"#.len() as u64
+        );
+        let plugin = write(temp.path(), "headers.wasm", &wat);
+        let server = Webserve::builder()
+            .dir(temp.path())
+            .configure(|o| o.plugins = vec![plugin])
+            .build()
+            .unwrap();
+        let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers().get("x-plugin").unwrap(), "yes");
+        assert_eq!(test::read_body(res).await, "<h1>Home</h1>");
+    }
+
+    #[actix_web::test]
+    async fn runaway_plugins_run_out_of_fuel() {
+        let temp = site();
+        let wat = format!(
+            r#"(module {}
+                (func (export "webserve_on_request") (param i32 i32) (result i64)
+                    (loop $spin (br $spin))
+                    i64.const 0))"#,
+            PRELUDE
+        );
+        let plugin = write(temp.path(), "spin.wasm", &wat);
+        let server = Webserve::builder()
+            .dir(temp.path())
+            .configure(|o| o.plugins = vec![plugin])
+            .build()
+            .unwrap();
+        let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), 500);
+    }
+
+    #[actix_web::test]
+    async fn refuses_modules_outside_the_abi() {
+        let temp = TempDir::new().unwrap();
+        let wat = format!(
+            r#"(module (import "env" "open" (func (param i32)))
+                {}
+                (func (export "webserve_on_request") (param i32 i32) (result i64)
+                    i64.const 0))"#,
+            PRELUDE
+        );
+        let err = Plugin::load(&write(temp.path(), "env.wasm", &wat), false).unwrap_err();
+        assert!(err.contains("imports env::open"), "{}", err);
+
+        let wat = format!("(module {})", PRELUDE);
+        let err = Plugin::load(&write(temp.path(), "idle.wasm", &wat), false).unwrap_err();
+        assert!(err.contains("exports neither"), "{}", err);
+
+        let wasi = format!(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func (param i32 i32 i32 i32) (result i32)))
+                {}
+                (func (export "webserve_on_request") (param i32 i32) (result i64)
+                    i64.const 0))"#,
+            PRELUDE
+        );
+        assert!(Plugin::load(&write(temp.path(), "wasi.wasm", &wasi), false).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn hot_reload_picks_up_new_builds() {
+        let temp = TempDir::new().unwrap();
+        let path = write(
+            temp.path(),
+            "api.wasm",
+            &answering("webserve_on_request", r#"{"body":"v1"}"#),
+        );
+        let plugin = Plugin::load(&path, true).unwrap();
+        let req = test::TestRequest::get().uri("/api").to_http_request();
+        let res = plugin.on_request(&req).unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "v1");
+
+        let bump = |path: &Path| {
+            let file = fs::File::options().write(true).open(path).unwrap();
+            file.set_modified(SystemTime::now() + Duration::from_secs(5))
+                .unwrap();
+        };
+        write(
+            temp.path(),
+            "api.wasm",
+            &answering("webserve_on_request", r#"{"status":418,"body":"v2"}"#),
+        );
+        bump(&path);
+        let res = plugin.on_request(&req).unwrap();
+        assert_eq!(res.status(), 418);
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "v2");
+
+        // A broken build keeps the last good one
+        fs::write(&path, b"not wasm").unwrap();
+        bump(&path);
+        assert_eq!(plugin.on_request(&req).unwrap().status(), 418);
+    }
+}
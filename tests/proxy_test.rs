@@ -0,0 +1,244 @@
+//! `--proxy`, `--proxy-allow` and `--proxy-deny-private`.
+
+#![cfg(feature = "proxy")]
+
+mod common;
+
+use actix_web::http::header;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use std::net::SocketAddr;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{is_private, Proxy, ProxyRule, ServeOptions, Settings, Webserve};
+
+#[test]
+fn proxy_options_describe_the_rules() {
+    let settings = Settings::from_options(&ServeOptions::from_iter(["webserve"])).unwrap();
+    assert!(settings.proxy.is_none());
+
+    let options = ServeOptions::from_iter([
+        "webserve",
+        "--proxy",
+        "/api=http://localhost:3000",
+        "--proxy-allow",
+        "localhost:3000",
+        "--proxy-deny-private",
+    ]);
+    let proxy = Settings::from_options(&options).unwrap().proxy.unwrap();
+    assert_eq!(
+        proxy.describe(),
+        "/api/ -> http://localhost:3000/, private targets refused"
+    );
+}
+
+#[test]
+fn rules_match_whole_segments_and_the_longest_prefix() {
+    for bad in [
+        "api=http://x",
+        "/api",
+        "/api=ftp://x",
+        "/api=http://x/?q=1",
+        "/api=/local",
+    ] {
+        assert!(ProxyRule::parse(bad).is_err(), "{}", bad);
+    }
+    let options = ServeOptions::from_iter([
+        "webserve",
+        "--proxy",
+        "/api=http://localhost:3000",
+        "--proxy",
+        "/api/v2/=https://[::1]/v2",
+    ]);
+    let proxy = Proxy::from_options(&options).unwrap().unwrap();
+    let route = |path| {
+        proxy
+            .route(path)
+            .map(|(rule, rest)| (rule.to_string(), rest))
+    };
+    assert_eq!(
+        route("/api/users"),
+        Some(("/api/ -> http://localhost:3000/".to_string(), "/users"))
+    );
+    assert_eq!(
+        route("/api/v2/items"),
+        Some(("/api/v2/ -> https://[::1]:443/v2/".to_string(), "/items"))
+    );
+    assert_eq!(route("/api").map(|(_, rest)| rest), Some(""));
+    assert_eq!(route("/apix"), None);
+    assert_eq!(route("/index.html"), None);
+
+    let refused = ServeOptions::from_iter([
+        "webserve",
+        "--proxy",
+        "/api=http://localhost:3000",
+        "--proxy-allow",
+        "localhost:4000",
+    ]);
+    let err = Proxy::from_options(&refused).unwrap_err();
+    assert!(
+        err.contains("localhost:3000 is not in --proxy-allow"),
+        "{}",
+        err
+    );
+    let bad_allow = ServeOptions::from_iter(["webserve", "--proxy-allow", "host:port"]);
+    assert!(Proxy::from_options(&bad_allow).is_err());
+
+    for private in [
+        "127.0.0.1",
+        "10.1.2.3",
+        "192.168.1.1",
+        "169.254.169.254",
+        "::1",
+    ] {
+        assert!(is_private(private.parse().unwrap()), "{}", private);
+    }
+    for public in ["93.184.216.34", "2606:4700::1111"] {
+        assert!(!is_private(public.parse().unwrap()), "{}", public);
+    }
+}
+
+/// Starts an upstream on loopback echoing what it received.
+fn upstream() -> SocketAddr {
+    async fn echo(req: HttpRequest, body: web::Bytes) -> HttpResponse {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("-")
+                .to_string()
+        };
+        if req.path().ends_with("/moved") {
+            return HttpResponse::Found()
+                .insert_header((header::LOCATION, "/elsewhere"))
+                .finish();
+        }
+        HttpResponse::Ok()
+            .insert_header(("x-upstream", "yes"))
+            .body(format!(
+                "{} {} for={} host={} secret={} body={}",
+                req.method(),
+                req.uri(),
+                header("x-forwarded-for"),
+                header("x-forwarded-host"),
+                header("x-secret"),
+                String::from_utf8_lossy(&body)
+            ))
+    }
+    let server = HttpServer::new(|| App::new().default_service(web::to(echo)))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    addr
+}
+
+fn site() -> TempDir {
+    common::site(&[("index.html", "<p>hi</p>")])
+}
+
+#[actix_web::test]
+async fn requests_under_the_prefix_go_upstream() {
+    use actix_web::test;
+
+    let addr = upstream();
+    let temp = site();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .configure(|o| o.proxies = vec![format!("/api=http://{}/v1", addr)])
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+    let req = test::TestRequest::patch()
+        .uri("/api/users?page=2")
+        .insert_header(("Host", "site.test"))
+        .insert_header(("X-Secret", "kept"))
+        .insert_header((header::CONNECTION, "x-secret"))
+        .peer_addr("192.0.2.7:5000".parse().unwrap())
+        .set_payload("data")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("x-upstream").unwrap(), "yes");
+    let body = test::read_body(res).await;
+    assert_eq!(
+        body,
+        "PATCH /v1/users?page=2 for=192.0.2.7 host=site.test secret=- body=data"
+    );
+
+    // Redirects are passed back, not followed
+    let req = test::TestRequest::get().uri("/api/moved").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 302);
+    assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/elsewhere");
+
+    // `..` cannot climb out of the target's path
+    for uri in [
+        "/api/../admin",
+        "/api/%2e%2e/admin",
+        "/api/a/..%2f..%2fadmin",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400, "{}", uri);
+    }
+    let req = test::TestRequest::get().uri("/api/a..b").to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    assert!(body.starts_with(b"GET /v1/a..b "), "{:?}", body);
+
+    // Everything else is still served from the directory
+    let req = test::TestRequest::get().uri("/index.html").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let req = test::TestRequest::delete().uri("/index.html").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 405);
+}
+
+#[actix_web::test]
+async fn private_targets_need_the_allowlist() {
+    use actix_web::test;
+
+    let addr = upstream();
+    let temp = site();
+    let app = |allow: Vec<String>| {
+        let server = Webserve::builder()
+            .dir(temp.path())
+            .configure(|o| {
+                o.proxies = vec![format!("/api=http://localhost:{}", addr.port())];
+                o.proxy_allow = allow;
+                o.proxy_deny_private = true;
+            })
+            .build()
+            .unwrap();
+        test::init_service(App::new().service(server.into_actix_service()))
+    };
+
+    let refused = app(Vec::new()).await;
+    let req = test::TestRequest::get().uri("/api/").to_request();
+    assert_eq!(test::call_service(&refused, req).await.status(), 403);
+
+    let allowed = app(vec![format!("localhost:{}", addr.port())]).await;
+    let req = test::TestRequest::get().uri("/api/").to_request();
+    let res = test::call_service(&allowed, req).await;
+    assert_eq!(res.status(), 200);
+    assert!(test::read_body(res).await.starts_with(b"GET /"));
+}
+
+#[actix_web::test]
+async fn unreachable_targets_get_502() {
+    use actix_web::test;
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let temp = site();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .configure(|o| o.proxies = vec![format!("/=http://127.0.0.1:{}", port)])
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let req = test::TestRequest::get().uri("/index.html").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 502);
+}
@@ -0,0 +1,74 @@
+//! `--replace` / `--replace-regex` response body rules.
+
+use actix_web::App;
+use std::fs;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{Replacement, Replacements, ServeOptions, Webserve};
+
+#[test]
+fn parses_and_applies_rules() {
+    let rule = Replacement::parse("*.html=https://example.com/=>/", false).unwrap();
+    assert!(rule.applies_to("/docs/index.html"));
+    assert!(!rule.applies_to("/app.js"));
+    assert_eq!(
+        rule.apply("<a href=\"https://example.com/about\">"),
+        "<a href=\"/about\">"
+    );
+
+    let rule = Replacement::parse(r"*=v(\d+)\.min\.js=>v$1.js", true).unwrap();
+    assert_eq!(
+        rule.apply("lib-v3.min.js and v12.min.js"),
+        "lib-v3.js and v12.js"
+    );
+
+    for bad in ["no-rule", "*.html=missing-arrow", "=x=>y", "*==>y"] {
+        assert!(Replacement::parse(bad, false).is_err(), "{}", bad);
+    }
+    let err = Replacement::parse("*=(=>x", true).unwrap_err();
+    assert!(err.starts_with("--replace-regex *=(=>x:"), "{}", err);
+}
+
+#[test]
+fn rules_run_in_order_and_report_no_change() {
+    let mut options = ServeOptions::from_iter(["webserve"]);
+    options.replace = vec!["*=a=>b".to_string()];
+    options.replace_regex = vec!["*.txt=b+=>c".to_string()];
+    let rules = Replacements::from_options(&options).unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules.apply("/x.txt", "aab").as_deref(), Some("c"));
+    assert_eq!(rules.apply("/x.css", "aab").as_deref(), Some("bbb"));
+    assert_eq!(rules.apply("/x.css", "zzz"), None);
+}
+
+#[actix_web::test]
+async fn rewrites_text_responses_only() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    let html = "<a href=\"https://prod.example.com/docs/\">docs</a>";
+    fs::write(temp.path().join("index.html"), html).unwrap();
+    fs::write(temp.path().join("logo.png"), "https://prod.example.com/").unwrap();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .configure(|o| o.replace = vec!["*=https://prod.example.com/=>/".to_string()])
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+
+    let res = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/index.html").to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), 200);
+    assert!(!res.headers().contains_key("last-modified"));
+    let etag = res.headers().get("etag").unwrap().to_str().unwrap();
+    assert!(etag.starts_with("W/"), "{}", etag);
+    assert_eq!(test::read_body(res).await, "<a href=\"/docs/\">docs</a>");
+
+    let res =
+        test::call_service(&app, test::TestRequest::get().uri("/logo.png").to_request()).await;
+    assert!(res.headers().contains_key("last-modified"));
+    assert_eq!(test::read_body(res).await, "https://prod.example.com/");
+}
@@ -0,0 +1,162 @@
+//! Redirect/rewrite rule parsing and matching, plus handler integration.
+
+use webserve::{Rule, RuleMatch, Rules};
+
+#[test]
+fn redirect_flag_defaults_to_301_and_accepts_status() {
+    let rules = Rules::new(vec![
+        Rule::parse_redirect("/old=/new").unwrap(),
+        Rule::parse_redirect("/tmp=/elsewhere:302").unwrap(),
+    ]);
+    assert_eq!(
+        rules.evaluate("/old"),
+        Some(RuleMatch::Redirect {
+            location: "/new".into(),
+            status: 301
+        })
+    );
+    assert_eq!(
+        rules.evaluate("/tmp"),
+        Some(RuleMatch::Redirect {
+            location: "/elsewhere".into(),
+            status: 302
+        })
+    );
+    assert_eq!(rules.evaluate("/other"), None);
+}
+
+#[test]
+fn redirect_flag_rejects_bad_specs() {
+    assert!(Rule::parse_redirect("/old").is_err());
+    assert!(Rule::parse_rewrite("^/(unclosed=/x").is_err());
+}
+
+#[test]
+fn redirect_status_is_a_3xx_suffix_outside_the_authority() {
+    let target =
+        |spec: &str| match Rules::new(vec![Rule::parse_redirect(spec).unwrap()]).evaluate("/x") {
+            Some(RuleMatch::Redirect { location, status }) => (location, status),
+            other => panic!("{:?}", other),
+        };
+    assert_eq!(
+        target("/x=http://localhost:3000"),
+        ("http://localhost:3000".to_string(), 301)
+    );
+    assert_eq!(
+        target("/x=http://localhost:301"),
+        ("http://localhost:301".to_string(), 301)
+    );
+    assert_eq!(
+        target("/x=//cdn.test:308"),
+        ("//cdn.test:308".to_string(), 301)
+    );
+    assert_eq!(
+        target("/x=http://localhost:3000/a:302"),
+        ("http://localhost:3000/a".to_string(), 302)
+    );
+    assert_eq!(target("/x=/new:200"), ("/new:200".to_string(), 301));
+}
+
+#[test]
+fn rewrite_regex_captures_and_splat_placeholders() {
+    let rules = Rules::new(vec![
+        Rule::parse_rewrite("^/v1/(.*)=/api/$1").unwrap(),
+        Rule::parse_rewrite("/blog/:year/*=/posts/:year/:splat").unwrap(),
+    ]);
+    assert_eq!(
+        rules.evaluate("/v1/users/7"),
+        Some(RuleMatch::Rewrite("/api/users/7".into()))
+    );
+    assert_eq!(
+        rules.evaluate("/blog/2024/hello/world"),
+        Some(RuleMatch::Rewrite("/posts/2024/hello/world".into()))
+    );
+    assert_eq!(rules.evaluate("/blog/2024"), None);
+}
+
+#[test]
+fn rules_file_parses_netlify_style_lines() {
+    let rules = Rules::parse_file(
+        "# comment\n\n/home / 301\n/app/* /index.html 200\n/gone /new # trailing\n",
+    )
+    .unwrap();
+    assert_eq!(rules.len(), 3);
+    assert_eq!(
+        rules.evaluate("/app/deep/link"),
+        Some(RuleMatch::Rewrite("/index.html".into()))
+    );
+    assert_eq!(
+        rules.evaluate("/gone"),
+        Some(RuleMatch::Redirect {
+            location: "/new".into(),
+            status: 301
+        })
+    );
+    let rules = Rules::parse_file("#top\n/docs /guide#intro 302 #moved\n/a /b#c #d\n").unwrap();
+    assert_eq!(
+        rules.evaluate("/docs"),
+        Some(RuleMatch::Redirect {
+            location: "/guide#intro".into(),
+            status: 302
+        })
+    );
+    assert_eq!(
+        rules.evaluate("/a"),
+        Some(RuleMatch::Redirect {
+            location: "/b#c".into(),
+            status: 301
+        })
+    );
+    assert!(Rules::parse_file("/only-one-field\n").is_err());
+    assert!(Rules::parse_file("/a /b abc\n").is_err());
+}
+
+#[actix_web::test]
+async fn serve_file_applies_redirect_and_rewrite_rules() {
+    use actix_web::{test, web, App as ActixApp};
+    use std::fs;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use webserve::{serve_file, AppState, Settings};
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir(temp_dir.path().join("api")).unwrap();
+    fs::write(temp_dir.path().join("api").join("data.json"), b"{\"ok\":1}").unwrap();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp_dir.path().to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                rules: Rules::new(vec![
+                    Rule::parse_redirect("/old/*=/new/:splat:308").unwrap(),
+                    Rule::parse_rewrite("^/v1/(.*)=/api/$1").unwrap(),
+                ]),
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state)
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/old/page?x=1").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.status(),
+        actix_web::http::StatusCode::PERMANENT_REDIRECT
+    );
+    assert_eq!(
+        resp.headers().get("location").unwrap().to_str().unwrap(),
+        "/new/page?x=1"
+    );
+
+    let req = test::TestRequest::get().uri("/v1/data.json").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body = test::read_body(resp).await;
+    assert_eq!(body, "{\"ok\":1}");
+}
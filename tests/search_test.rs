@@ -0,0 +1,124 @@
+//! `/__webserve/search` and the listing's search box.
+
+mod common;
+
+use actix_web::{web, App as ActixApp};
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{search, search_tree, AppState, ListingView, Locale, SearchResults, WalkLimits};
+
+fn site() -> TempDir {
+    let temp = common::site(&[
+        ("index.html", "<h1>Welcome</h1>\n"),
+        ("docs/Setup.md", "# Setup\n\nRun the installer.\n"),
+        ("docs/guides/deploy.md", "# Deploy\n\nAfter setup, push.\n"),
+    ]);
+    std::fs::write(temp.path().join("docs/setup.bin"), b"setup\0\x01").unwrap();
+    temp
+}
+
+#[test]
+fn matches_paths_and_optionally_contents() {
+    let temp = site();
+    let root = temp.path();
+    let paths = |found: SearchResults| -> Vec<String> {
+        found.results.into_iter().map(|hit| hit.path).collect()
+    };
+
+    let found = search_tree(root, root, "SETUP", false, 50, WalkLimits::default());
+    assert_eq!(paths(found), vec!["/docs/Setup.md", "/docs/setup.bin"]);
+
+    let found = search_tree(root, root, "setup", true, 50, WalkLimits::default());
+    assert!(!found.truncated);
+    let deploy = found
+        .results
+        .iter()
+        .find(|hit| hit.path == "/docs/guides/deploy.md")
+        .expect("content match");
+    assert_eq!(deploy.matches[0].line, 3);
+    assert_eq!(deploy.matches[0].text, "After setup, push.");
+    let binary = found
+        .results
+        .iter()
+        .find(|hit| hit.path == "/docs/setup.bin");
+    assert!(
+        binary.unwrap().matches.is_empty(),
+        "binary files match by path only"
+    );
+
+    // Paths are matched below the searched directory
+    let found = search_tree(
+        root,
+        &root.join("docs"),
+        "docs",
+        false,
+        50,
+        WalkLimits::default(),
+    );
+    assert!(found.results.is_empty());
+
+    let found = search_tree(root, root, "s", false, 1, WalkLimits::default());
+    assert_eq!(found.results.len(), 1);
+    assert!(found.truncated);
+
+    let limits = WalkLimits {
+        max_depth: Some(2),
+        max_entries: None,
+    };
+    let found = search_tree(root, root, "deploy", false, 50, limits);
+    assert!(found.results.is_empty());
+    assert!(found.truncated);
+}
+
+#[actix_web::test]
+async fn endpoint_returns_json() {
+    use actix_web::test;
+
+    let temp = site();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data)
+            .route("/__webserve/search", web::get().to(search)),
+    )
+    .await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/__webserve/search?q=welcome&content=1")).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("cache-control").unwrap(), "no-store");
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(body["query"], "welcome");
+    assert_eq!(body["results"][0]["path"], "/index.html");
+    assert_eq!(body["results"][0]["matches"][0]["line"], 1);
+
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, get("/__webserve/search?q=md&dir=/docs/guides")).await;
+    assert_eq!(body["results"].as_array().unwrap().len(), 1);
+    assert_eq!(body["results"][0]["path"], "/docs/guides/deploy.md");
+    assert!(body["results"][0].get("matches").is_none());
+
+    let res = test::call_service(&app, get("/__webserve/search?q=+")).await;
+    assert_eq!(res.status(), 400);
+    let res = test::call_service(&app, get("/__webserve/search?q=a&dir=/missing")).await;
+    assert_eq!(res.status(), 404);
+    let res = test::call_service(&app, get("/__webserve/search?q=a&dir=/../etc")).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[actix_web::test]
+async fn listing_has_a_search_box() {
+    let temp = site();
+    let view = ListingView {
+        locale: Locale::Fr,
+        ..Default::default()
+    };
+    let listing = view.render(&temp.path().join("docs"), "/docs/").await;
+    assert!(listing.contains(r#"data-dir="/docs/""#));
+    assert!(listing.contains(r#"placeholder="Rechercher des fichiers""#));
+    assert!(listing.contains("'/__webserve/search?q='"));
+    assert!(listing.contains(r#"<table id="search-results" hidden>"#));
+}
@@ -0,0 +1,211 @@
+//! Embedding API: `Webserve::builder()`, mounting in an existing `App` and running on a
+//! listener.
+
+mod common;
+
+use actix_web::{web, App, HttpResponse};
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use webserve::{ServerEvent, Webserve, ADMIN_PREFIX};
+
+fn site() -> TempDir {
+    common::site(&[("index.html", "<h1>Home</h1>")])
+}
+
+#[test]
+fn builder_sets_options() {
+    let temp = site();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .spa(true)
+        .watch(true)
+        .watch_path("src")
+        .exec("make")
+        .bind(([0, 0, 0, 0], 3000).into())
+        .configure(|o| o.compress = true)
+        .build()
+        .unwrap();
+    let options = server.options();
+    assert_eq!(options.directory.as_deref(), Some(temp.path()));
+    assert!(options.spa && options.watch && options.compress);
+    assert_eq!(options.watch_paths, vec![std::path::PathBuf::from("src")]);
+    assert_eq!(options.exec.as_deref(), Some("make"));
+    assert_eq!(options.hosts, vec!["0.0.0.0"]);
+    assert_eq!(options.port, 3000);
+    assert!(server.state().watch);
+    assert!(server.state().settings.load().spa);
+
+    let err = Webserve::builder()
+        .dir(temp.path().join("missing"))
+        .build()
+        .err()
+        .unwrap();
+    assert!(err.ends_with("not found"), "{}", err);
+}
+
+#[actix_web::test]
+async fn mounts_inside_an_existing_app() {
+    use actix_web::test;
+
+    let temp = site();
+    let server = Webserve::builder()
+        .dir(temp.path())
+        .spa(true)
+        .configure(|o| o.headers = vec!["X-Served-By: webserve".to_string()])
+        .build()
+        .unwrap();
+    let app = test::init_service(
+        App::new()
+            .route(
+                "/api/hello",
+                web::get().to(|| async { HttpResponse::Ok().body("hi") }),
+            )
+            .service(server.into_actix_service()),
+    )
+    .await;
+
+    let res = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/api/hello").to_request(),
+    )
+    .await;
+    assert!(!res.headers().contains_key("x-served-by"));
+    assert_eq!(test::read_body(res).await, "hi");
+
+    // The middleware finds the state although the App itself has none
+    let res = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/app/route").to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("x-served-by").unwrap(), "webserve");
+    assert_eq!(test::read_body(res).await, "<h1>Home</h1>");
+
+    let uri = format!("{}/stats", ADMIN_PREFIX);
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .peer_addr("127.0.0.1:5000".parse().unwrap())
+        .to_request();
+    let stats: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(stats["total_requests"], 1);
+}
+
+#[actix_web::test]
+async fn host_services_reload_independently() {
+    use actix_web::test;
+    use notify::event::{DataChange, ModifyKind};
+    use notify::{Event, EventKind};
+
+    let (a, b) = (site(), site());
+    fs::write(b.path().join("index.html"), "<h1>B</h1>").unwrap();
+    let site_a = Webserve::builder().dir(a.path()).build().unwrap();
+    let site_b = Webserve::builder().dir(b.path()).build().unwrap();
+    let state_a = site_a.state().clone();
+    let app = test::init_service(
+        App::new()
+            .service(site_a.into_host_service("a.localhost"))
+            .service(site_b.into_host_service("b.localhost")),
+    )
+    .await;
+    let get = |host: &str, uri: &str| {
+        test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Host", host.to_string()))
+            .to_request()
+    };
+
+    let body = test::call_and_read_body(&app, get("b.localhost:8080", "/")).await;
+    assert_eq!(body, "<h1>B</h1>");
+    let res = test::call_service(&app, get("c.localhost", "/")).await;
+    assert_eq!(res.status(), 404);
+
+    let edit = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+        .add_path(a.path().join("index.html"));
+    state_a.publish_changes(&[edit]);
+    let res = test::call_service(&app, get("b.localhost", "/reload?since=0")).await;
+    assert_eq!(res.status(), 204);
+    let res = test::call_service(&app, get("a.localhost", "/reload?since=0")).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(test::read_body(res).await, "reload");
+}
+
+fn get(port: u16, path: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[actix_web::test]
+async fn handle_reports_port_events_and_stops() {
+    let temp = site();
+    let handle = Webserve::builder()
+        .dir(temp.path())
+        .bind(([127, 0, 0, 1], 0).into())
+        .configure(|o| o.workers = Some(1))
+        .start()
+        .unwrap();
+    let port = handle.addr().port();
+    assert_ne!(port, 0);
+    assert_eq!(handle.url(), format!("http://127.0.0.1:{}", port));
+    let mut events = handle.events();
+    handle.await_ready().await.unwrap();
+
+    let response = web::block(move || get(port, "/")).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    match event {
+        ServerEvent::Request { path, status, .. } => {
+            assert_eq!((path.as_str(), status), ("/", 200))
+        }
+        other => panic!("unexpected event {:?}", other),
+    }
+
+    handle.stop().await.unwrap();
+    assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+}
+
+#[actix_web::test]
+async fn runs_on_a_listener() {
+    let temp = site();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = Webserve::builder().dir(temp.path()).build().unwrap();
+    actix_web::rt::spawn(server.run_listener(listener));
+
+    let response = web::block(move || {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut stream = loop {
+            match TcpStream::connect(("127.0.0.1", port)) {
+                Ok(stream) => break stream,
+                Err(_) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(50))
+                }
+                Err(e) => panic!("server never came up: {}", e),
+            }
+        };
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    })
+    .await
+    .unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with("<h1>Home</h1>"));
+}
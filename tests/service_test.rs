@@ -0,0 +1,74 @@
+//! `webserve service`: Windows service install arguments.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{service_config, service_launch_arguments, Cli, Command, ServiceAction};
+
+#[test]
+fn parses_service_actions() {
+    let cli = Cli::from_iter(["webserve", "service", "install", "--name", "docs"]);
+    match cli.command {
+        Some(Command::Service(action)) => assert_eq!(
+            action,
+            ServiceAction::Install {
+                name: "docs".to_string(),
+                display_name: None,
+                manual: false,
+            }
+        ),
+        other => panic!("{:?}", other),
+    }
+    let cli = Cli::from_iter(["webserve", "service", "uninstall"]);
+    match cli.command {
+        Some(Command::Service(action)) => assert_eq!(
+            action,
+            ServiceAction::Uninstall {
+                name: "webserve".to_string()
+            }
+        ),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn install_records_an_absolute_config_path() {
+    let options = Cli::from_iter(["webserve", "service", "install"]).options;
+    assert!(service_config(&options).unwrap_err().contains("--config"));
+
+    let temp = TempDir::new().unwrap();
+    let config = temp.path().join("webserve.toml");
+    let options = Cli::from_iter([
+        OsString::from("webserve"),
+        "--config".into(),
+        config.clone().into(),
+    ])
+    .options;
+    assert!(service_config(&options).unwrap_err().contains("not found"));
+    fs::write(&config, "dir = \"share\"").unwrap();
+    assert_eq!(service_config(&options).unwrap(), config);
+
+    let args = service_launch_arguments(Path::new("C:/srv/webserve.toml"), "docs");
+    let cli = Cli::from_iter(std::iter::once("webserve".into()).chain(args));
+    assert_eq!(
+        cli.options.config.as_deref(),
+        Some(Path::new("C:/srv/webserve.toml"))
+    );
+    assert!(matches!(
+        cli.command,
+        Some(Command::Service(ServiceAction::Run { ref name })) if name == "docs"
+    ));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn service_is_windows_only() {
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["service", "run"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("only supported on Windows"));
+}
@@ -0,0 +1,97 @@
+//! `--file`: one file served at `/`, nothing else.
+
+use actix_web::App;
+use std::fs;
+use std::time::Duration;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{resolve_options_with_env, ServeOptions, Webserve};
+
+fn report() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("report.html"), "<h1>Report</h1>").unwrap();
+    fs::write(temp.path().join("secret.txt"), "private").unwrap();
+    temp
+}
+
+#[test]
+fn file_option_conflicts_and_errors() {
+    assert!(ServeOptions::from_iter_safe(["webserve", "--file", "a.html", "--dir", "x"]).is_err());
+    // `--dir` on the command line wins over a configured file
+    let env = vec![("WEBSERVE_FILE".to_string(), "report.html".to_string())];
+    let options = resolve_options_with_env(["webserve", "--dir", "site"], env).unwrap();
+    assert_eq!(options.file, None);
+
+    let temp = report();
+    let err = Webserve::builder().file(temp.path()).build().err().unwrap();
+    assert!(err.ends_with("is not a file"), "{}", err);
+    let err = Webserve::builder()
+        .file(temp.path().join("missing.html"))
+        .build()
+        .err()
+        .unwrap();
+    assert!(err.ends_with("not found"), "{}", err);
+    let err = Webserve::builder()
+        .file(temp.path().join("report.html"))
+        .configure(|o| o.upload = true)
+        .build()
+        .err()
+        .unwrap();
+    assert!(err.contains("--upload"), "{}", err);
+}
+
+#[actix_web::test]
+async fn serves_the_file_at_root_only() {
+    use actix_web::test;
+
+    let temp = report();
+    let server = Webserve::builder()
+        .file(temp.path().join("report.html"))
+        .build()
+        .unwrap();
+    let app = test::init_service(App::new().service(server.into_actix_service())).await;
+    let get = |uri: &str| test::TestRequest::get().uri(uri).to_request();
+
+    let res = test::call_service(&app, get("/")).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    assert_eq!(test::read_body(res).await, "<h1>Report</h1>");
+    for uri in ["/secret.txt", "/report.html", "/__webserve/manifest"] {
+        let res = test::call_service(&app, get(uri)).await;
+        let body = test::read_body(res).await;
+        assert!(
+            !String::from_utf8_lossy(&body).contains("secret"),
+            "{} leaked a sibling",
+            uri
+        );
+    }
+    let res = test::call_service(&app, get("/secret.txt")).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[actix_web::test]
+async fn watch_reloads_for_the_file_only() {
+    let temp = report();
+    let server = Webserve::builder()
+        .file(temp.path().join("report.html"))
+        .watch(true)
+        .build()
+        .unwrap();
+    let mut reloads = server.state().tx.subscribe();
+    // Let the watcher settle before touching files
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    fs::write(temp.path().join("secret.txt"), "changed").unwrap();
+    let sibling = tokio::time::timeout(Duration::from_millis(800), reloads.recv()).await;
+    assert!(sibling.is_err(), "a sibling change reloaded: {:?}", sibling);
+
+    fs::write(temp.path().join("report.html"), "<h1>Report v2</h1>").unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(5), reloads.recv())
+        .await
+        .expect("file change reloads")
+        .unwrap();
+    assert_eq!(event.paths, ["/"]);
+}
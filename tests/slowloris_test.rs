@@ -0,0 +1,172 @@
+//! `--slowloris-guard`: request head deadlines and minimum body rate.
+
+use actix_web::error::PayloadError;
+use bytes::Bytes;
+use futures_util::{stream, StreamExt};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    MinRate, ServeOptions, Settings, SlowlorisGuard, DEFAULT_HEADER_TIMEOUT, DEFAULT_MIN_RATE,
+};
+
+#[test]
+fn slowloris_options_build_the_guard() {
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    assert_eq!(Settings::from_options(&options).unwrap().slowloris, None);
+    assert_eq!(options.header_timeout, DEFAULT_HEADER_TIMEOUT);
+    assert_eq!(options.min_rate, DEFAULT_MIN_RATE);
+
+    let options = ServeOptions::from_iter(
+        [
+            "webserve",
+            "--slowloris-guard",
+            "--header-timeout",
+            "3s",
+            "--min-rate",
+            "1024",
+            "--keep-alive",
+            "2s",
+        ]
+        .iter(),
+    );
+    let guard = Settings::from_options(&options).unwrap().slowloris.unwrap();
+    assert_eq!(guard.header_timeout, Duration::from_secs(3));
+    assert_eq!(guard.min_rate, 1024);
+    assert_eq!(guard.keep_alive, Duration::from_secs(2));
+    assert_eq!(guard.describe(), "3s headers, 1024 B/s bodies");
+
+    for bad in [["--min-rate", "0"], ["--header-timeout", "0"]] {
+        let options = ServeOptions::from_iter(["webserve", "--slowloris-guard", bad[0], bad[1]]);
+        assert!(SlowlorisGuard::from_options(&options).is_err(), "{:?}", bad);
+    }
+}
+
+#[test]
+fn keep_alive_gap_bounds_the_head() {
+    let guard = SlowlorisGuard {
+        header_timeout: Duration::from_secs(10),
+        min_rate: 512,
+        keep_alive: Duration::from_secs(5),
+    };
+    assert!(!guard.head_too_slow(Duration::from_secs(14)));
+    assert!(guard.head_too_slow(Duration::from_secs(16)));
+    let no_keep_alive = SlowlorisGuard {
+        keep_alive: Duration::ZERO,
+        ..guard
+    };
+    assert!(!no_keep_alive.head_too_slow(Duration::from_secs(60)));
+}
+
+#[actix_web::test]
+async fn slow_bodies_fail_after_the_grace_period() {
+    let first = stream::iter([Ok::<_, PayloadError>(Bytes::from_static(b"0123456789"))]);
+    let stalled = first.chain(stream::pending());
+    // 10 bytes cover 10ms at 1000 B/s, after 100ms of grace.
+    let mut body = MinRate::new(stalled, 1000, Duration::from_millis(100));
+    let started = Instant::now();
+    assert_eq!(body.next().await.unwrap().unwrap().len(), 10);
+    assert!(body.next().await.unwrap().is_err());
+    assert!(started.elapsed() >= Duration::from_millis(100));
+
+    let steady = stream::iter([Ok::<_, PayloadError>(Bytes::from_static(b"ab"))]);
+    let mut body = MinRate::new(steady, 1, Duration::from_millis(10));
+    assert!(body.next().await.unwrap().is_ok());
+    assert!(body.next().await.is_none());
+}
+
+fn spawn_guarded(dir: &TempDir, extra: &[&str]) -> (Child, u16) {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--slowloris-guard", "--strict-port"])
+        .args(extra)
+        .args(["-p", &port.to_string(), "-d"])
+        .arg(dir.path())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run webserve binary");
+    let stdout = child.stdout.take().unwrap();
+    BufReader::new(stdout)
+        .lines()
+        .map_while(Result::ok)
+        .find(|line| line.contains("Serving on"))
+        .expect("server reports its URL");
+    (child, port)
+}
+
+/// Reads one response head and its `Content-Length` body.
+fn read_response(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    let head = String::from_utf8_lossy(&head).into_owned();
+    let length = head
+        .lines()
+        .find_map(|l| {
+            let (name, value) = l.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).unwrap();
+    head
+}
+
+#[test]
+fn dripped_heads_get_408() {
+    let temp = TempDir::new().unwrap();
+    std::fs::write(temp.path().join("index.html"), "hi").unwrap();
+    let (mut child, port) = spawn_guarded(
+        &temp,
+        // actix-web checks head deadlines against a clock cached for 500ms, so stay above it.
+        &["--header-timeout", "1s", "--keep-alive", "1s"],
+    );
+
+    // First request on a connection: actix-web's head timer.
+    let mut first = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    first
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    first.write_all(b"GET / HT").unwrap();
+    let timed_out = read_response(&mut first);
+
+    // Keep-alive request dripped over longer than keep-alive + header timeout.
+    let mut reused = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    reused
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    reused
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let ok = read_response(&mut reused);
+    for part in [
+        "GET / HTTP/1.1\r\n",
+        "Host: localhost\r\n",
+        "Accept: */*\r\n",
+        "X-Slow: 1\r\n",
+        "\r\n",
+    ] {
+        reused.write_all(part.as_bytes()).unwrap();
+        std::thread::sleep(Duration::from_millis(700));
+    }
+    let dripped = read_response(&mut reused);
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert!(timed_out.starts_with("HTTP/1.1 408"), "{}", timed_out);
+    assert!(ok.starts_with("HTTP/1.1 200"), "{}", ok);
+    assert!(dripped.starts_with("HTTP/1.1 408"), "{}", dripped);
+}
@@ -0,0 +1,214 @@
+//! `--store` key-value backends and the download counts kept in them.
+
+use actix_web::middleware::from_fn;
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{
+    collect_stats, counter, open_store, serve_file, stats_json, top_downloads, AppState,
+    MemoryStore, Settings, Store, ADMIN_PREFIX, DOWNLOADS_NS, DOWNLOAD_KEY_LIMIT,
+};
+
+#[test]
+fn rejects_unknown_or_incomplete_store_specs() {
+    assert_eq!(open_store("memory").unwrap().describe(), "memory");
+    assert!(open_store("redis://localhost").is_err());
+    assert!(open_store("sled").is_err());
+    assert!(open_store("sqlite:").is_err());
+    #[cfg(not(feature = "sled"))]
+    assert!(open_store("sled:data")
+        .err()
+        .is_some_and(|e| e.contains("--features sled")));
+}
+
+/// Behavior every backend must share.
+fn exercise(store: &dyn Store) {
+    assert_eq!(store.get("a", "k").unwrap(), None);
+    store.put("a", "k", b"one").unwrap();
+    store.put("b", "k", b"other namespace").unwrap();
+    assert_eq!(store.get("a", "k").unwrap().as_deref(), Some(&b"one"[..]));
+    store.put("a", "k", b"two").unwrap();
+    store.put("a", "j", b"first").unwrap();
+    assert_eq!(
+        store.scan("a").unwrap(),
+        vec![
+            ("j".to_string(), b"first".to_vec()),
+            ("k".to_string(), b"two".to_vec())
+        ]
+    );
+    store.remove("a", "k").unwrap();
+    store.remove("a", "missing").unwrap();
+    assert_eq!(store.get("a", "k").unwrap(), None);
+    assert_eq!(store.scan("b").unwrap().len(), 1);
+
+    assert_eq!(store.increment("c", "/x", 1).unwrap(), 1);
+    assert_eq!(store.increment("c", "/x", 2).unwrap(), 3);
+    assert_eq!(counter(store.get("c", "/x").unwrap().as_deref()), 3);
+}
+
+#[test]
+fn memory_store_behaves_like_a_store() {
+    exercise(&MemoryStore::default());
+
+    let store = Arc::new(MemoryStore::default());
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    store.increment(DOWNLOADS_NS, "/app.js", 1).unwrap();
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    store.increment(DOWNLOADS_NS, "/index.html", 5).unwrap();
+    assert_eq!(
+        top_downloads(store.as_ref(), 1).unwrap(),
+        vec![("/app.js".to_string(), 800)]
+    );
+}
+
+#[cfg(feature = "sled")]
+#[test]
+fn sled_store_persists() {
+    let temp = TempDir::new().unwrap();
+    let spec = format!("sled:{}", temp.path().join("db").display());
+    {
+        let store = open_store(&spec).unwrap();
+        exercise(store.as_ref());
+    }
+    // sled's flusher thread lets go of the directory lock shortly after the last handle.
+    let store = (0..50)
+        .find_map(|_| {
+            open_store(&spec)
+                .map_err(|_| std::thread::sleep(std::time::Duration::from_millis(20)))
+                .ok()
+        })
+        .expect("reopen sled store");
+    assert_eq!(store.get("a", "j").unwrap().as_deref(), Some(&b"first"[..]));
+    assert_eq!(store.increment("c", "/x", 1).unwrap(), 4);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_store_persists() {
+    let temp = TempDir::new().unwrap();
+    let spec = format!("sqlite:{}", temp.path().join("webserve.db").display());
+    {
+        let store = open_store(&spec).unwrap();
+        exercise(store.as_ref());
+    }
+    let store = open_store(&spec).unwrap();
+    assert_eq!(store.get("a", "j").unwrap().as_deref(), Some(&b"first"[..]));
+    assert_eq!(store.increment("c", "/x", 1).unwrap(), 4);
+}
+
+#[actix_web::test]
+async fn successful_gets_are_counted_as_downloads() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("report.pdf"), b"%PDF-1.7").unwrap();
+    fs::write(temp.path().join("index.html"), b"<p>app</p>").unwrap();
+    let store = Arc::new(MemoryStore::default());
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        store: store.clone(),
+        settings: Arc::new(
+            Settings {
+                spa: true,
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data)
+            .wrap(from_fn(collect_stats))
+            .route(
+                &format!("{}/stats", ADMIN_PREFIX),
+                web::get().to(stats_json),
+            )
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    // SPA fallbacks answer 200 but send no file of that name
+    for uri in ["/report.pdf", "/report.pdf", "/app/route-1", "/app/route-2"] {
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), 200, "{}", uri);
+    }
+    // Counting runs on the blocking pool after the response.
+    for _ in 0..50 {
+        if store
+            .get(DOWNLOADS_NS, "/report.pdf")
+            .unwrap()
+            .is_some_and(|v| counter(Some(&v)) == 2)
+        {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let stats: serde_json::Value = test::call_and_read_body_json(
+        &app,
+        test::TestRequest::get()
+            .uri(&format!("{}/stats", ADMIN_PREFIX))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(stats["downloads"], serde_json::json!({ "/report.pdf": 2 }));
+    assert_eq!(stats["options"]["Store"], "memory");
+}
+
+#[actix_web::test]
+async fn download_counts_stop_taking_new_paths_at_the_limit() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("old.txt"), b"old").unwrap();
+    fs::write(temp.path().join("new.txt"), b"new").unwrap();
+    let store = Arc::new(MemoryStore::default());
+    store.increment(DOWNLOADS_NS, "/old.txt", 1).unwrap();
+    for i in 1..DOWNLOAD_KEY_LIMIT {
+        store
+            .increment(DOWNLOADS_NS, &format!("/filler-{}", i), 1)
+            .unwrap();
+    }
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        store: store.clone(),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data)
+            .wrap(from_fn(collect_stats))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    for uri in ["/new.txt", "/old.txt"] {
+        test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+    }
+    for _ in 0..50 {
+        if store
+            .get(DOWNLOADS_NS, "/old.txt")
+            .unwrap()
+            .is_some_and(|v| counter(Some(&v)) == 2)
+        {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    // Paths already counted keep counting; new ones are left out
+    let old = store.get(DOWNLOADS_NS, "/old.txt").unwrap();
+    assert_eq!(counter(old.as_deref()), 2);
+    assert_eq!(store.get(DOWNLOADS_NS, "/new.txt").unwrap(), None);
+    assert_eq!(store.scan(DOWNLOADS_NS).unwrap().len(), DOWNLOAD_KEY_LIMIT);
+}
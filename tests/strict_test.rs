@@ -0,0 +1,83 @@
+//! `--strict-http` response audit rules.
+
+use actix_web::body::BodySize;
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use webserve::audit_response;
+
+fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+    }
+    map
+}
+
+#[test]
+fn well_formed_responses_pass() {
+    let html = headers(&[(header::CONTENT_TYPE, "text/html")]);
+    assert!(audit_response(&Method::GET, StatusCode::OK, &html, BodySize::Sized(12)).is_empty());
+    assert!(audit_response(
+        &Method::GET,
+        StatusCode::NOT_MODIFIED,
+        &HeaderMap::new(),
+        BodySize::None
+    )
+    .is_empty());
+    let redirect = headers(&[(header::LOCATION, "/docs/")]);
+    assert!(audit_response(
+        &Method::GET,
+        StatusCode::MOVED_PERMANENTLY,
+        &redirect,
+        BodySize::Sized(0)
+    )
+    .is_empty());
+}
+
+#[test]
+fn violations_are_reported() {
+    let mismatched = headers(&[
+        (header::CONTENT_TYPE, "text/plain"),
+        (header::CONTENT_LENGTH, "10"),
+    ]);
+    let problems = audit_response(
+        &Method::GET,
+        StatusCode::OK,
+        &mismatched,
+        BodySize::Sized(4),
+    );
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].contains("Content-Length 10"));
+
+    let problems = audit_response(
+        &Method::GET,
+        StatusCode::NO_CONTENT,
+        &headers(&[(header::CONTENT_TYPE, "text/plain")]),
+        BodySize::Sized(6),
+    );
+    assert!(problems.iter().any(|p| p.contains("204")));
+
+    let problems = audit_response(
+        &Method::HEAD,
+        StatusCode::OK,
+        &headers(&[(header::CONTENT_TYPE, "text/plain")]),
+        BodySize::Sized(3),
+    );
+    assert!(problems.iter().any(|p| p.contains("HEAD")));
+
+    let problems = audit_response(
+        &Method::GET,
+        StatusCode::OK,
+        &HeaderMap::new(),
+        BodySize::Stream,
+    );
+    assert!(problems.iter().any(|p| p.contains("Content-Type")));
+
+    let problems = audit_response(
+        &Method::GET,
+        StatusCode::TEMPORARY_REDIRECT,
+        &HeaderMap::new(),
+        BodySize::None,
+    );
+    assert!(problems.iter().any(|p| p.contains("Location")));
+}
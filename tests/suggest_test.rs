@@ -0,0 +1,91 @@
+//! `--suggest`: close matches on `404` pages.
+
+mod common;
+
+use actix_web::App;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    levenshtein, resolve_options_with_env, suggestions, FileConfig, ServeOptions, Webserve,
+    SUGGEST_HEADER,
+};
+
+fn site() -> TempDir {
+    common::site(&[
+        ("assets/img/logo.png", "png"),
+        ("assets/app.js", "js"),
+        ("index.html", "home"),
+        (".env", "SECRET=1"),
+        ("index.html~", "backup"),
+    ])
+}
+
+#[test]
+fn finds_case_and_typo_matches() {
+    assert_eq!(levenshtein("kitten", "sitting"), 3);
+    assert_eq!(levenshtein("", "abc"), 3);
+    assert_eq!(levenshtein("same", "same"), 0);
+
+    let temp = site();
+    let root = temp.path();
+    assert_eq!(
+        suggestions(root, "/Assets/IMG/Logo.PNG", None),
+        ["/assets/img/logo.png"]
+    );
+    assert_eq!(suggestions(root, "/assets/ap.js", None), ["/assets/app.js"]);
+    assert_eq!(suggestions(root, "/indx.html", None), ["/index.html"]);
+    assert!(suggestions(root, "/completely-different", None).is_empty());
+    // Dotfiles only match dotfile requests; editor backups never do
+    assert!(suggestions(root, "/env", None).is_empty());
+    assert_eq!(suggestions(root, "/.Env", None), ["/.env"]);
+    assert!(!suggestions(root, "/index.htm", None).contains(&"/index.html~".to_string()));
+
+    let options = ServeOptions::from_iter(["webserve", "--suggest"]);
+    assert!(options.suggest);
+    assert_eq!(
+        FileConfig::parse("suggest = true").unwrap().suggest,
+        Some(true)
+    );
+    let env = vec![("WEBSERVE_SUGGEST".to_string(), "1".to_string())];
+    assert!(resolve_options_with_env(["webserve"], env).unwrap().suggest);
+}
+
+#[actix_web::test]
+async fn not_found_pages_list_suggestions() {
+    use actix_web::test;
+
+    let temp = site();
+    let app_with = |suggest: bool| {
+        Webserve::builder()
+            .dir(temp.path())
+            .configure(|o| o.suggest = suggest)
+            .build()
+            .unwrap()
+            .into_actix_service()
+    };
+    let missing = |accept: &str| {
+        test::TestRequest::get()
+            .uri("/Assets/App.js")
+            .insert_header(("Accept", accept))
+            .to_request()
+    };
+
+    let app = test::init_service(App::new().service(app_with(true))).await;
+    let res = test::call_service(&app, missing("text/html")).await;
+    assert_eq!(res.status(), 404);
+    assert_eq!(res.headers().get(SUGGEST_HEADER).unwrap(), "/assets/app.js");
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("Did you mean"), "{}", body);
+    assert!(body.contains(r#"<a href="/assets/app.js">"#), "{}", body);
+
+    // Non-HTML clients get the header only
+    let res = test::call_service(&app, missing("*/*")).await;
+    assert_eq!(res.status(), 404);
+    assert!(res.headers().contains_key(SUGGEST_HEADER));
+    assert!(test::read_body(res).await.is_empty());
+
+    let app = test::init_service(App::new().service(app_with(false))).await;
+    let res = test::call_service(&app, missing("text/html")).await;
+    assert_eq!(res.status(), 404);
+    assert!(!res.headers().contains_key(SUGGEST_HEADER));
+}
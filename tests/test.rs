@@ -7,7 +7,9 @@ use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use tempfile::TempDir;
 use tokio::sync::broadcast;
-use webserve::{directory_listing, reload_poll, serve_file, AppState};
+use webserve::{
+    directory_listing, reload_poll, serve_file, AppState, ReloadEvent, Settings, RELOAD_SEQ_HEADER,
+};
 
 #[tokio::test]
 async fn test_directory_listing_empty() {
@@ -38,25 +40,24 @@ async fn test_serve_file_existing_file() {
     drop(file);
 
     let static_dir = Arc::new(temp_dir.path().to_path_buf());
-    let (tx, _) = broadcast::channel::<()>(16);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
     let app_state = web::Data::new(AppState {
         static_dir,
         watch: false,
-        spa: false,
         addr: "127.0.0.1:8080".to_string(),
         tx,
-        redirect_dir_slash: true,
         reload_pending: Arc::new(AtomicBool::new(false)),
         html_cache: None,
+        ..Default::default()
     });
 
     let app = ActixApp::new()
         .app_data(app_state.clone())
         .route("/{_:.*}", web::get().to(serve_file));
 
-    let mut app = test::init_service(app).await;
+    let app = test::init_service(app).await;
     let req = test::TestRequest::get().uri("/test.txt").to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
 }
 
@@ -64,27 +65,26 @@ async fn test_serve_file_existing_file() {
 async fn test_serve_file_not_found() {
     let temp_dir = TempDir::new().unwrap();
     let static_dir = Arc::new(temp_dir.path().to_path_buf());
-    let (tx, _) = broadcast::channel::<()>(16);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
     let app_state = web::Data::new(AppState {
         static_dir,
         watch: false,
-        spa: false,
         addr: "127.0.0.1:8080".to_string(),
         tx,
-        redirect_dir_slash: true,
         reload_pending: Arc::new(AtomicBool::new(false)),
         html_cache: None,
+        ..Default::default()
     });
 
     let app = ActixApp::new()
         .app_data(app_state.clone())
         .route("/{_:.*}", web::get().to(serve_file));
 
-    let mut app = test::init_service(app).await;
+    let app = test::init_service(app).await;
     let req = test::TestRequest::get()
         .uri("/nonexistent.txt")
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
 }
 
@@ -97,27 +97,33 @@ async fn test_serve_file_spa_fallback() {
     drop(file);
 
     let static_dir = Arc::new(temp_dir.path().to_path_buf());
-    let (tx, _) = broadcast::channel::<()>(16);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
     let app_state = web::Data::new(AppState {
         static_dir,
         watch: false,
-        spa: true,
+        settings: Arc::new(
+            Settings {
+                spa: true,
+                ..Default::default()
+            }
+            .into(),
+        ),
         addr: "127.0.0.1:8080".to_string(),
         tx,
-        redirect_dir_slash: true,
         reload_pending: Arc::new(AtomicBool::new(false)),
         html_cache: None,
+        ..Default::default()
     });
 
     let app = ActixApp::new()
         .app_data(app_state.clone())
         .route("/{_:.*}", web::get().to(serve_file));
 
-    let mut app = test::init_service(app).await;
+    let app = test::init_service(app).await;
     let req = test::TestRequest::get()
         .uri("/nonexistent-route")
         .to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
 }
 
@@ -128,25 +134,24 @@ async fn test_serve_file_directory_listing() {
     fs::File::create(&file_path).unwrap();
 
     let static_dir = Arc::new(temp_dir.path().to_path_buf());
-    let (tx, _) = broadcast::channel::<()>(16);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
     let app_state = web::Data::new(AppState {
         static_dir,
         watch: false,
-        spa: false,
         addr: "127.0.0.1:8080".to_string(),
         tx,
-        redirect_dir_slash: true,
         reload_pending: Arc::new(AtomicBool::new(false)),
         html_cache: None,
+        ..Default::default()
     });
 
     let app = ActixApp::new()
         .app_data(app_state.clone())
         .route("/{_:.*}", web::get().to(serve_file));
 
-    let mut app = test::init_service(app).await;
+    let app = test::init_service(app).await;
     let req = test::TestRequest::get().uri("/").to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
     let body = test::read_body(resp).await;
     let body_str = String::from_utf8(body.to_vec()).unwrap();
@@ -159,33 +164,406 @@ async fn test_reload_poll() {
     use std::sync::atomic::Ordering;
     let temp_dir = TempDir::new().unwrap();
     let static_dir = Arc::new(temp_dir.path().to_path_buf());
-    let (tx, _) = broadcast::channel::<()>(16);
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
     let reload_pending = Arc::new(AtomicBool::new(false));
     let app_state = web::Data::new(AppState {
         static_dir,
         watch: false,
-        spa: false,
         addr: "127.0.0.1:8080".to_string(),
         tx: tx.clone(),
-        redirect_dir_slash: true,
         reload_pending: reload_pending.clone(),
         html_cache: None,
+        ..Default::default()
     });
 
     let app = ActixApp::new()
         .app_data(app_state.clone())
         .route("/reload", web::get().to(reload_poll));
 
-    let mut app = test::init_service(app).await;
+    let app = test::init_service(app).await;
 
     let req = test::TestRequest::get().uri("/reload").to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), actix_web::http::StatusCode::NO_CONTENT);
 
     reload_pending.store(true, Ordering::SeqCst);
     let req = test::TestRequest::get().uri("/reload").to_request();
-    let resp = test::call_service(&mut app, req).await;
+    let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
     let body = test::read_body(resp).await;
     assert_eq!(body, "reload");
 }
+
+#[actix_web::test]
+async fn test_reload_poll_catches_up_from_sequence() {
+    let temp_dir = TempDir::new().unwrap();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp_dir.path().to_path_buf()),
+        watch: true,
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state.clone())
+            .route("/reload", web::get().to(reload_poll)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/reload?since=0").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::NO_CONTENT);
+    assert_eq!(resp.headers().get(RELOAD_SEQ_HEADER).unwrap(), "0");
+
+    // Two batches land while the client is away; it reloads once on its next poll.
+    let root = temp_dir.path();
+    for name in ["a.html", "b.html"] {
+        let event = notify::Event::new(notify::EventKind::Create(notify::event::CreateKind::File))
+            .add_path(root.join(name));
+        app_state.reload_log.record(root, &[event]);
+    }
+    let req = test::TestRequest::get().uri("/reload?since=0").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+    assert_eq!(resp.headers().get(RELOAD_SEQ_HEADER).unwrap(), "2");
+    assert_eq!(test::read_body(resp).await, "reload");
+
+    let req = test::TestRequest::get().uri("/reload?since=2").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::NO_CONTENT);
+}
+
+#[actix_web::test]
+async fn test_reload_poll_answers_json_with_changed_paths() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("site.css"), b"body{}").unwrap();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(root.to_path_buf()),
+        watch: true,
+        ..Default::default()
+    });
+    let mut rx = app_state.tx.subscribe();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state.clone())
+            .route("/reload", web::get().to(reload_poll)),
+    )
+    .await;
+
+    let event = notify::Event::new(notify::EventKind::Modify(notify::event::ModifyKind::Data(
+        notify::event::DataChange::Content,
+    )))
+    .add_path(root.join("site.css"));
+    app_state.publish_changes(&[event]);
+    let broadcast = rx.try_recv().unwrap();
+    assert_eq!(broadcast.seq, 1);
+    assert_eq!(broadcast.paths, vec!["/site.css"]);
+    assert_eq!(broadcast.changes[0].path, "/site.css");
+
+    let req = test::TestRequest::get()
+        .uri("/reload?since=0")
+        .insert_header(("Accept", "application/json"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["current"], 1);
+    assert_eq!(body["complete"], true);
+    assert_eq!(
+        body["missed"][0]["changes"],
+        serde_json::json!([{ "path": "/site.css", "kind": "modified" }])
+    );
+}
+
+#[actix_web::test]
+async fn test_watch_injects_location_relative_reload_script() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("index.html"), b"<p>hi</p>").unwrap();
+
+    let static_dir = Arc::new(temp_dir.path().to_path_buf());
+    let (tx, _) = broadcast::channel::<ReloadEvent>(16);
+    let app_state = web::Data::new(AppState {
+        static_dir,
+        watch: true,
+        addr: "0.0.0.0:9999".to_string(),
+        tx,
+        reload_pending: Arc::new(AtomicBool::new(false)),
+        html_cache: Some(Arc::new(std::sync::RwLock::new(
+            std::collections::HashMap::new(),
+        ))),
+        ..Default::default()
+    });
+
+    let app = ActixApp::new()
+        .app_data(app_state.clone())
+        .route("/{_:.*}", web::get().to(serve_file));
+
+    let app = test::init_service(app).await;
+    let req = test::TestRequest::get().uri("/index.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body = test::read_body(resp).await;
+    let body_str = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body_str.contains("new URL(\"/reload\", location.href)"));
+    assert!(body_str.contains("?since=0"));
+    assert!(body_str.contains("var page = \"/index.html\";"));
+    assert!(!body_str.contains("0.0.0.0:9999"));
+}
+
+#[actix_web::test]
+async fn test_generated_responses_answer_if_none_match_with_304() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("index.html"), b"<p>hi</p>").unwrap();
+    fs::create_dir(temp_dir.path().join("files")).unwrap();
+    fs::write(temp_dir.path().join("files").join("a.txt"), b"a").unwrap();
+
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp_dir.path().to_path_buf()),
+        watch: true,
+        html_cache: Some(Arc::new(std::sync::RwLock::new(
+            std::collections::HashMap::new(),
+        ))),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state)
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    for uri in ["/index.html", "/files/"] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        let etag = resp
+            .headers()
+            .get("etag")
+            .expect("generated response has an ETag")
+            .clone();
+
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("If-None-Match", etag))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_MODIFIED);
+        assert!(test::read_body(resp).await.is_empty());
+    }
+}
+
+#[actix_web::test]
+async fn test_injected_html_answers_if_modified_since() {
+    use std::time::{Duration, UNIX_EPOCH};
+    use webserve::{html_response_modified, EtagMode};
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("index.html"), b"<p>hi</p>").unwrap();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp_dir.path().to_path_buf()),
+        watch: true,
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state)
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/index.html").to_request();
+    let resp = test::call_service(&app, req).await;
+    let modified = resp
+        .headers()
+        .get("last-modified")
+        .expect("injected HTML has Last-Modified")
+        .clone();
+    let req = test::TestRequest::get()
+        .uri("/index.html")
+        .insert_header(("If-Modified-Since", modified))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_MODIFIED);
+
+    // If-None-Match decides when both are sent; a newer page is sent in full
+    let changed = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let since = |secs: u64| {
+        actix_web::http::header::HttpDate::from(UNIX_EPOCH + Duration::from_secs(secs)).to_string()
+    };
+    let body = actix_web::web::Bytes::from_static(b"<p>hi</p>");
+    let status = |req: actix_web::HttpRequest| {
+        html_response_modified(&req, body.clone(), EtagMode::Mtime, changed).status()
+    };
+    let req = test::TestRequest::get()
+        .insert_header(("If-Modified-Since", since(1_000_000)))
+        .to_http_request();
+    assert_eq!(status(req), actix_web::http::StatusCode::NOT_MODIFIED);
+    let req = test::TestRequest::get()
+        .insert_header(("If-Modified-Since", since(999_999)))
+        .to_http_request();
+    assert_eq!(status(req), actix_web::http::StatusCode::OK);
+    let req = test::TestRequest::get()
+        .insert_header(("If-Modified-Since", since(1_000_000)))
+        .insert_header(("If-None-Match", "\"stale\""))
+        .to_http_request();
+    assert_eq!(status(req), actix_web::http::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_bust_asset_urls_rewrites_local_references_only() {
+    use webserve::bust_asset_urls;
+    let html = r#"<script src="/app.js"></script><link rel="stylesheet" href="css/site.css?x=1#top"><a href="/page">p</a><img src="https://cdn.example/x.png"><script src="data:text/javascript,1"></script>"#;
+    let out = bust_asset_urls(html, "abc");
+    assert!(out.contains(r#"<script src="/app.js?__wsv=abc">"#));
+    assert!(out.contains(r#"href="css/site.css?x=1&__wsv=abc#top""#));
+    assert!(out.contains(r#"<a href="/page">"#));
+    assert!(out.contains(r#"src="https://cdn.example/x.png""#));
+    assert!(out.contains(r#"src="data:text/javascript,1""#));
+}
+
+#[actix_web::test]
+async fn test_no_cache_sends_no_store_and_ignores_validators() {
+    use actix_web::middleware::from_fn;
+    use webserve::no_store;
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), b"a").unwrap();
+    fs::write(
+        temp_dir.path().join("index.html"),
+        br#"<script src="/app.js"></script>"#,
+    )
+    .unwrap();
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp_dir.path().to_path_buf()),
+        watch: true,
+        html_cache: Some(Arc::new(std::sync::RwLock::new(
+            std::collections::HashMap::new(),
+        ))),
+        settings: Arc::new(
+            Settings {
+                no_cache: true,
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state)
+            .wrap(from_fn(no_store))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    for uri in ["/a.txt", "/index.html"] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("If-None-Match", "*"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::OK);
+        assert_eq!(resp.headers().get("cache-control").unwrap(), "no-store");
+        assert!(resp.headers().get("etag").is_none());
+        assert!(resp.headers().get("last-modified").is_none());
+        if uri == "/index.html" {
+            let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+            assert!(body.contains("/app.js?__wsv="));
+        }
+    }
+}
+
+#[actix_web::test]
+async fn test_etag_modes_apply_to_files_and_generated_bodies() {
+    use webserve::{sha256_etag, EtagMode};
+
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), b"hello").unwrap();
+    fs::create_dir(temp_dir.path().join("files")).unwrap();
+
+    let app_for = |mode: EtagMode| {
+        web::Data::new(AppState {
+            static_dir: Arc::new(temp_dir.path().to_path_buf()),
+            settings: Arc::new(
+                Settings {
+                    etag: mode,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            ..Default::default()
+        })
+    };
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_for(EtagMode::Sha256))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    let req = test::TestRequest::get().uri("/a.txt").to_request();
+    let resp = test::call_service(&app, req).await;
+    let etag = resp.headers().get("etag").unwrap().clone();
+    assert_eq!(etag.to_str().unwrap(), sha256_etag(b"hello").to_string());
+    let req = test::TestRequest::get()
+        .uri("/a.txt")
+        .insert_header(("If-None-Match", etag))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_MODIFIED);
+
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_for(EtagMode::Off))
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+    for uri in ["/a.txt", "/files/"] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("If-None-Match", "\"stale\""))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.headers().get("etag").is_none(), "{}", uri);
+        assert_ne!(resp.status(), actix_web::http::StatusCode::NOT_MODIFIED);
+    }
+}
+
+#[actix_web::test]
+async fn test_section_index_serves_nearest_ancestor_index() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("index.html"), b"root").unwrap();
+    fs::create_dir_all(temp_dir.path().join("docs").join("guide")).unwrap();
+    fs::write(temp_dir.path().join("docs").join("index.html"), b"docs").unwrap();
+    fs::create_dir(temp_dir.path().join("blog")).unwrap();
+
+    let app_state = web::Data::new(AppState {
+        static_dir: Arc::new(temp_dir.path().to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                section_index: true,
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(app_state)
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    for (uri, want) in [
+        ("/docs/guide/intro", "docs"),
+        ("/docs/missing.html", "docs"),
+        ("/blog/post", "root"),
+        ("/nowhere/deep/path", "root"),
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success(), "{}", uri);
+        assert_eq!(test::read_body(resp).await, want, "{}", uri);
+    }
+}
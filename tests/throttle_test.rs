@@ -0,0 +1,107 @@
+//! `--throttle` / `--latency` slow-network simulation.
+
+use actix_web::body::{self, BodySize, MessageBody};
+use actix_web::{web, App as ActixApp};
+use bytes::Bytes;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    resolve_options_with_env, serve_file, AppState, NetworkShape, ServeOptions, Settings, Throttled,
+};
+
+#[test]
+fn network_options_describe_and_reject_zero() {
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    assert!(Settings::from_options(&options).unwrap().network.is_off());
+
+    let options =
+        ServeOptions::from_iter(["webserve", "--throttle", "400", "--latency", "2s"].iter());
+    let network = Settings::from_options(&options).unwrap().network;
+    assert_eq!(network.kbps, Some(400));
+    assert_eq!(network.bytes_per_sec(), Some(50_000));
+    assert_eq!(network.latency, Some(Duration::from_secs(2)));
+    assert_eq!(network.describe(), "400 kbps, 2s latency");
+
+    let options = ServeOptions::from_iter(["webserve", "--throttle", "0"].iter());
+    assert!(Settings::from_options(&options).is_err());
+
+    // A bare number is milliseconds
+    let env = vec![("WEBSERVE_LATENCY".to_string(), "300".to_string())];
+    let options = resolve_options_with_env(["webserve"], env).unwrap();
+    assert_eq!(options.latency, Some(Duration::from_millis(300)));
+}
+
+#[actix_web::test]
+async fn throttled_body_is_paced_in_slices() {
+    let data = Bytes::from(vec![7u8; 3000]);
+    // 10 000 B/s: 1000-byte slices, 100ms apart.
+    let mut throttled = Throttled::new(data.clone(), 10_000);
+    assert_eq!(throttled.size(), BodySize::Sized(3000));
+
+    let started = Instant::now();
+    let mut slices = Vec::new();
+    while let Some(chunk) =
+        std::future::poll_fn(|cx| std::pin::Pin::new(&mut throttled).poll_next(cx)).await
+    {
+        slices.push(chunk.unwrap());
+    }
+    assert_eq!(
+        slices.iter().map(Bytes::len).collect::<Vec<_>>(),
+        vec![1000, 1000, 1000]
+    );
+    assert_eq!(slices.concat(), data);
+    assert!(
+        started.elapsed() >= Duration::from_millis(250),
+        "{:?}",
+        started.elapsed()
+    );
+}
+
+#[actix_web::test]
+async fn serve_file_applies_latency_and_throttle() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("app.js"), vec![b'x'; 2000]).unwrap();
+    let data = web::Data::new(AppState {
+        static_dir: Arc::new(temp.path().to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                network: NetworkShape {
+                    kbps: Some(80),
+                    latency: Some(Duration::from_millis(150)),
+                },
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    });
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data)
+            .route("/{_:.*}", web::get().to(serve_file)),
+    )
+    .await;
+
+    let started = Instant::now();
+    let res = test::call_service(&app, test::TestRequest::get().uri("/app.js").to_request()).await;
+    assert!(started.elapsed() >= Duration::from_millis(150));
+    assert_eq!(res.status(), 200);
+    let body = body::to_bytes(res.into_body()).await.unwrap();
+    assert_eq!(body.len(), 2000);
+    // 80 kbps = 10 000 B/s: 150ms latency plus two 100ms slices.
+    assert!(
+        started.elapsed() >= Duration::from_millis(300),
+        "{:?}",
+        started.elapsed()
+    );
+
+    let started = Instant::now();
+    let res = test::call_service(&app, test::TestRequest::get().uri("/missing").to_request()).await;
+    assert_eq!(res.status(), 404);
+    assert!(started.elapsed() >= Duration::from_millis(150));
+}
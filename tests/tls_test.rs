@@ -0,0 +1,249 @@
+//! `--tls-cert` / `--tls-key` HTTPS with HTTP/2 via ALPN, and `--http1-only`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{
+    https_location, redirect_https, server_config, HttpsPort, ServeOptions, Stats, Tls, TlsCounts,
+    TlsInfo,
+};
+
+fn tls_file(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/tls")
+        .join(name)
+}
+
+#[test]
+fn tls_needs_a_readable_cert_and_key_pair() {
+    let cert = tls_file("cert.pem");
+    let key = tls_file("key.pem");
+    assert!(server_config(&cert, &key).is_ok());
+    assert!(server_config(&cert, &cert).is_err());
+    assert!(server_config(&tls_file("missing.pem"), &key).is_err());
+
+    let options = |args: &[&str]| {
+        let mut argv = vec!["webserve"];
+        argv.extend_from_slice(args);
+        ServeOptions::from_iter(argv)
+    };
+    let (c, k) = (cert.to_str().unwrap(), key.to_str().unwrap());
+    assert!(Tls::from_options(&options(&[])).unwrap().is_none());
+    assert!(
+        Tls::from_options(&options(&["--tls-cert", c, "--tls-key", k]))
+            .unwrap()
+            .is_some()
+    );
+    assert!(Tls::from_options(&options(&["--tls-cert", c])).is_err());
+    assert!(Tls::from_options(&options(&["--http1-only"])).is_err());
+    assert!(Tls::from_options(&options(&["--redirect-http", "8081"])).is_err());
+    assert!(Tls::from_options(&options(&[
+        "--tls-cert",
+        c,
+        "--tls-key",
+        k,
+        "--redirect-http",
+        "8080"
+    ]))
+    .is_err());
+    assert!(Tls::from_options(&options(&[
+        "--tls-cert",
+        c,
+        "--tls-key",
+        k,
+        "--uds",
+        "w.sock"
+    ]))
+    .is_err());
+}
+
+#[actix_web::test]
+async fn redirect_listener_points_at_the_https_origin() {
+    use actix_web::http::header;
+    use actix_web::{test, web, App};
+
+    assert_eq!(
+        https_location("example.test:8080", 8443, "/a?b=1"),
+        "https://example.test:8443/a?b=1"
+    );
+    assert_eq!(https_location("[::1]:8080", 443, "/"), "https://[::1]/");
+    assert_eq!(
+        https_location("localhost", 8443, "/x"),
+        "https://localhost:8443/x"
+    );
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(HttpsPort(8443)))
+            .default_service(web::to(redirect_https)),
+    )
+    .await;
+    for method in [test::TestRequest::get(), test::TestRequest::post()] {
+        let req = method
+            .uri("/docs/page.html?lang=en")
+            .insert_header((header::HOST, "dev.local:8080"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 301);
+        assert_eq!(
+            res.headers().get(header::LOCATION).unwrap(),
+            "https://dev.local:8443/docs/page.html?lang=en"
+        );
+    }
+}
+
+/// Starts webserve over TLS, offers the `offered` ALPN protocols and returns the one
+/// negotiated and, over HTTP/1.1, the response of `GET path`.
+fn negotiate(extra: &[&str], offered: &[&[u8]], path: &str) -> (Option<Vec<u8>>, String) {
+    negotiate_on("127.0.0.1", extra, offered, path)
+}
+
+/// [`negotiate`] with the server bound to and reached at `host`.
+fn negotiate_on(
+    host: &str,
+    extra: &[&str],
+    offered: &[&[u8]],
+    path: &str,
+) -> (Option<Vec<u8>>, String) {
+    let temp = TempDir::new().unwrap();
+    std::fs::write(temp.path().join("index.html"), "<p>secure</p>").unwrap();
+    let addr = match host.contains(':') {
+        true => format!("[{}]", host),
+        false => host.to_string(),
+    };
+    let port = std::net::TcpListener::bind(format!("{}:0", addr))
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+        .to_string();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--strict-port", "--host", host, "-p", &port, "--tls-cert"])
+        .arg(tls_file("cert.pem"))
+        .arg("--tls-key")
+        .arg(tls_file("key.pem"))
+        .args(extra)
+        .arg("-d")
+        .arg(temp.path())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run webserve binary");
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+    let serving = stdout
+        .by_ref()
+        .map_while(Result::ok)
+        .find(|line| line.contains("Serving on"));
+    assert!(
+        serving.is_some_and(|l| l.ends_with(&format!("https://{}:{}", addr, port))),
+        "no https listener"
+    );
+
+    let mut roots = rustls::RootCertStore::empty();
+    let ca = std::fs::read(tls_file("ca.pem")).unwrap();
+    for cert in rustls_pemfile::certs(&mut ca.as_slice()) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let mut config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    config.alpn_protocols = offered.iter().map(|p| p.to_vec()).collect();
+    let conn =
+        rustls::ClientConnection::new(Arc::new(config), "localhost".try_into().unwrap()).unwrap();
+    let sock = TcpStream::connect(format!("{}:{}", addr, port)).unwrap();
+    let mut tls = rustls::StreamOwned::new(conn, sock);
+    while tls.conn.is_handshaking() {
+        tls.conn.complete_io(&mut tls.sock).unwrap();
+    }
+    let alpn = tls.conn.alpn_protocol().map(<[u8]>::to_vec);
+    let mut response = String::new();
+    if alpn.as_deref() == Some(b"http/1.1") {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        );
+        tls.write_all(request.as_bytes()).unwrap();
+        let _ = tls.read_to_string(&mut response);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    (alpn, response)
+}
+
+#[test]
+fn negotiates_h2_unless_http1_only() {
+    let both: &[&[u8]] = &[b"h2", b"http/1.1"];
+    let (alpn, _) = negotiate(&[], both, "/");
+    assert_eq!(alpn.as_deref(), Some(&b"h2"[..]));
+
+    let (alpn, response) = negotiate(&["--http1-only"], both, "/");
+    assert_eq!(alpn.as_deref(), Some(&b"http/1.1"[..]));
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("<p>secure</p>"));
+}
+
+#[test]
+fn http1_only_keeps_the_client_address() {
+    // Over IPv6 loopback, so a relay through `127.0.0.1` would show
+    let (_, response) = negotiate_on(
+        "::1",
+        &["--http1-only", "--deny", "::1"],
+        &[b"http/1.1"],
+        "/",
+    );
+    assert!(response.starts_with("HTTP/1.1 403"), "{}", response);
+    let (_, response) = negotiate_on(
+        "::1",
+        &["--http1-only", "--deny", "127.0.0.1"],
+        &[b"http/1.1"],
+        "/",
+    );
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+}
+
+#[test]
+fn handshakes_are_counted_in_stats() {
+    for extra in [&[][..], &["--http1-only"][..]] {
+        let (alpn, response) = negotiate(extra, &[b"http/1.1"], "/__webserve/stats");
+        assert_eq!(alpn.as_deref(), Some(&b"http/1.1"[..]));
+        let body = &response[response.find("\r\n\r\n").expect("response body") + 4..];
+        let stats: serde_json::Value = serde_json::from_str(body).unwrap();
+        let tls = &stats["tls"];
+        assert_eq!(tls["connections"], 1, "{:?}: {}", extra, tls);
+        assert_eq!(tls["versions"]["TLS 1.3"], 1);
+        assert_eq!(tls["alpn"]["http/1.1"], 1);
+        let ciphers = tls["ciphers"].as_object().unwrap();
+        assert!(
+            ciphers.keys().all(|c| c.starts_with("TLS13_")),
+            "{:?}",
+            ciphers
+        );
+    }
+}
+
+#[test]
+fn stats_tally_tls_connections() {
+    let stats = Stats::default();
+    assert_eq!(stats.tls_counts(), TlsCounts::default());
+    let info = |version: &str, alpn: Option<&str>| TlsInfo {
+        version: version.to_string(),
+        cipher: "TLS13_AES_128_GCM_SHA256".to_string(),
+        alpn: alpn.map(str::to_string),
+    };
+    stats.record_tls(&info("TLS 1.3", Some("h2")));
+    stats.record_tls(&info("TLS 1.3", None));
+    stats.record_tls(&info("TLS 1.2", Some("h2")));
+    let counts = stats.tls_counts();
+    assert_eq!(counts.connections, 3);
+    assert_eq!(counts.versions["TLS 1.3"], 2);
+    assert_eq!(counts.ciphers["TLS13_AES_128_GCM_SHA256"], 3);
+    assert_eq!(counts.alpn["h2"], 2);
+    assert_eq!(counts.alpn["none"], 1);
+}
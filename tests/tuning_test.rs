@@ -0,0 +1,117 @@
+//! `--workers`, `--max-connections`, `--keep-alive` and `--client-timeout`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use structopt::StructOpt;
+use tempfile::TempDir;
+use webserve::{resolve_options_with_env, ServeOptions, ServerTuning};
+
+#[test]
+fn tuning_options_describe_and_reject_bad_durations() {
+    let options = ServeOptions::from_iter(["webserve"].iter());
+    assert!(ServerTuning::from_options(&options).unwrap().is_default());
+
+    let options = ServeOptions::from_iter(
+        [
+            "webserve",
+            "--workers",
+            "2",
+            "--max-connections",
+            "500",
+            "--keep-alive",
+            "0",
+            "--client-timeout",
+            "1.5s",
+        ]
+        .iter(),
+    );
+    let tuning = ServerTuning::from_options(&options).unwrap();
+    assert_eq!(tuning.workers, Some(2));
+    assert_eq!(tuning.max_connections, Some(500));
+    assert_eq!(tuning.keep_alive, Some(Duration::ZERO));
+    assert_eq!(tuning.client_timeout, Some(Duration::from_millis(1500)));
+    assert_eq!(
+        tuning.describe(),
+        "workers 2, max connections 500, keep-alive off, client timeout 1.5s"
+    );
+    assert!(ServeOptions::from_iter_safe(["webserve", "--keep-alive", "soon"].iter()).is_err());
+
+    let env = vec![("WEBSERVE_KEEP_ALIVE".to_string(), "-1s".to_string())];
+    assert!(resolve_options_with_env(["webserve"], env).is_err());
+}
+
+#[test]
+fn zero_workers_or_connections_are_rejected() {
+    for flag in ["--workers", "--max-connections"] {
+        let options = ServeOptions::from_iter(["webserve", flag, "0"].iter());
+        let err = ServerTuning::from_options(&options).unwrap_err();
+        assert!(err.contains(&flag[2..]), "{}", err);
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--workers", "0", "-p", "0", "-d"])
+        .arg(temp_dir.path())
+        .output()
+        .expect("run webserve binary");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--workers must be at least 1"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn keep_alive_zero_closes_connections() {
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("index.html"), "hi").unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--workers", "1", "--keep-alive", "0", "--strict-port"])
+        .args(["-p", &port.to_string(), "-d"])
+        .arg(temp_dir.path())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("run webserve binary");
+    let stdout = child.stdout.take().unwrap();
+    let lines: Vec<String> = BufReader::new(stdout)
+        .lines()
+        .map_while(Result::ok)
+        .take_while(|line| !line.contains("Serving on"))
+        .collect();
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    let read = stream.read_to_string(&mut response);
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert!(
+        lines
+            .iter()
+            .any(|l| l.contains("Server tuning: workers 1, keep-alive off")),
+        "{:?}",
+        lines
+    );
+    read.expect("server closes the connection after the response");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(
+        response.to_ascii_lowercase().contains("connection: close"),
+        "{}",
+        response
+    );
+}
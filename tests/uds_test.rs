@@ -0,0 +1,74 @@
+//! `--uds` Unix domain socket listener.
+#![cfg(unix)]
+
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use std::{fs, thread};
+use tempfile::TempDir;
+use webserve::{prepare_socket_path, uds_addr};
+
+#[test]
+fn socket_path_is_checked_before_binding() {
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("webserve.sock");
+    prepare_socket_path(&path).unwrap();
+
+    let live = UnixListener::bind(&path).unwrap();
+    let err = prepare_socket_path(&path).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AddrInUse);
+    drop(live);
+    // Left behind by a listener that is gone: removed.
+    prepare_socket_path(&path).unwrap();
+    assert!(!path.exists());
+
+    let file = temp.path().join("notes.txt");
+    fs::write(&file, "keep me").unwrap();
+    let err = prepare_socket_path(&file).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+    assert!(file.exists());
+
+    assert_eq!(uds_addr(&path), format!("unix:{}", path.display()));
+}
+
+#[test]
+fn serves_over_a_unix_socket() {
+    let temp = TempDir::new().unwrap();
+    let site = temp.path().join("site");
+    fs::create_dir(&site).unwrap();
+    fs::write(site.join("index.html"), "<p>over uds</p>").unwrap();
+    let socket = temp.path().join("webserve.sock");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .arg("--watch")
+        .arg("--uds")
+        .arg(&socket)
+        .arg("-d")
+        .arg(&site)
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("run webserve binary");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut stream = loop {
+        match UnixStream::connect(&socket) {
+            Ok(s) => break s,
+            Err(_) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+            Err(e) => {
+                let _ = child.kill();
+                panic!("socket never came up: {}", e);
+            }
+        }
+    };
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("<p>over uds</p>"));
+    assert!(response.contains("new URL(\"/reload\", location.href)"));
+}
@@ -0,0 +1,372 @@
+//! `--upload`: `PUT` and multipart `POST` uploads.
+
+use actix_web::http::{header, Method};
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{
+    delete_path, make_dir, serve_file, upload_form, upload_put, AppState, ListingView, Locale,
+    Settings,
+};
+
+const BOUNDARY: &str = "webserve-test-boundary";
+
+fn multipart(files: &[(&str, &str)]) -> String {
+    let mut body = String::new();
+    body.push_str(&format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nignored\r\n"
+    ));
+    for (name, contents) in files {
+        body.push_str(&format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\nContent-Type: application/octet-stream\r\n\r\n{contents}\r\n"
+        ));
+    }
+    body.push_str(&format!("--{BOUNDARY}--\r\n"));
+    body
+}
+
+fn data(root: &TempDir, upload: bool) -> web::Data<AppState> {
+    web::Data::new(AppState {
+        static_dir: Arc::new(root.path().to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                upload,
+                max_upload_bytes: 16,
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    })
+}
+
+#[actix_web::test]
+async fn put_writes_beneath_the_root() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("inbox")).unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, true))
+            .route("/{_:.*}", web::get().to(serve_file))
+            .route("/{_:.*}", web::put().to(upload_put)),
+    )
+    .await;
+    let put = |uri: &str, body: &'static str| {
+        test::TestRequest::put()
+            .uri(uri)
+            .set_payload(body)
+            .to_request()
+    };
+
+    let res = test::call_service(&app, put("/inbox/new%20file.txt", "hello")).await;
+    assert_eq!(res.status(), 201);
+    assert_eq!(
+        res.headers().get(header::LOCATION).unwrap(),
+        "/inbox/new%20file.txt"
+    );
+    assert_eq!(
+        fs::read_to_string(temp.path().join("inbox/new file.txt")).unwrap(),
+        "hello"
+    );
+    let res = test::call_service(&app, put("/inbox/new%20file.txt", "again")).await;
+    assert_eq!(res.status(), 204);
+    let get = test::TestRequest::get()
+        .uri("/inbox/new%20file.txt")
+        .to_request();
+    assert_eq!(test::call_and_read_body(&app, get).await, "again");
+
+    let res = test::call_service(&app, put("/a/b/c.txt", "deep")).await;
+    assert_eq!(res.status(), 201, "missing directories are created");
+    assert!(temp.path().join("a/b/c.txt").is_file());
+
+    let res = test::call_service(&app, put("/inbox/big.bin", "0123456789abcdefXYZ")).await;
+    assert_eq!(res.status(), 413);
+    assert!(!temp.path().join("inbox/big.bin").exists());
+
+    assert_eq!(
+        test::call_service(&app, put("/%2e%2e/escape.txt", "x"))
+            .await
+            .status(),
+        403
+    );
+    assert_eq!(
+        test::call_service(&app, put("/inbox", "x")).await.status(),
+        409
+    );
+    assert_eq!(test::call_service(&app, put("/", "x")).await.status(), 409);
+
+    let leftovers: Vec<_> = fs::read_dir(temp.path().join("inbox"))
+        .unwrap()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(leftovers, vec!["new file.txt"], "no temporary files remain");
+}
+
+#[cfg(unix)]
+#[actix_web::test]
+async fn put_refuses_symlinks_out_of_the_root() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    std::os::unix::fs::symlink(outside.path(), temp.path().join("link")).unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, true))
+            .route("/{_:.*}", web::put().to(upload_put)),
+    )
+    .await;
+    let req = test::TestRequest::put()
+        .uri("/link/sub/x.txt")
+        .set_payload("x")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    assert!(!outside.path().join("sub").exists());
+}
+
+#[actix_web::test]
+async fn form_posts_store_each_file() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::create_dir(temp.path().join("drop")).unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, true))
+            .route("/{_:.*}", web::post().to(upload_form)),
+    )
+    .await;
+    let post = |uri: &str, body: String, accept: &str| {
+        test::TestRequest::post()
+            .uri(uri)
+            .insert_header((
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            ))
+            .insert_header((header::ACCEPT, accept.to_string()))
+            .set_payload(body)
+            .to_request()
+    };
+
+    let body = multipart(&[("a.txt", "first"), ("photos/b.txt", "second")]);
+    let res = test::call_service(&app, post("/drop/", body, "application/json")).await;
+    assert_eq!(res.status(), 201);
+    let json: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(
+        json["uploaded"],
+        serde_json::json!(["/drop/a.txt", "/drop/b.txt"])
+    );
+    assert_eq!(
+        fs::read_to_string(temp.path().join("drop/b.txt")).unwrap(),
+        "second"
+    );
+
+    let body = multipart(&[("c.txt", "third")]);
+    let res = test::call_service(&app, post("/drop/", body, "text/html,*/*")).await;
+    assert_eq!(res.status(), 303);
+    assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/drop/");
+
+    let body = multipart(&[("../up.txt", "x")]);
+    let res = test::call_service(&app, post("/", body, "*/*")).await;
+    assert_eq!(res.status(), 201);
+    assert!(
+        temp.path().join("up.txt").is_file(),
+        "only the base name is kept"
+    );
+
+    let body = multipart(&[("huge.txt", "0123456789abcdefXYZ")]);
+    let res = test::call_service(&app, post("/drop/", body, "*/*")).await;
+    assert_eq!(res.status(), 413);
+    assert!(!temp.path().join("drop/huge.txt").exists());
+    // The limit covers the whole form, not each file
+    let body = multipart(&[("one.txt", "0123456789"), ("two.txt", "0123456789")]);
+    let res = test::call_service(&app, post("/drop/", body, "*/*")).await;
+    assert_eq!(res.status(), 413);
+    assert!(!temp.path().join("drop/two.txt").exists());
+
+    let res = test::call_service(&app, post("/drop/", multipart(&[]), "*/*")).await;
+    assert_eq!(res.status(), 400);
+    let body = multipart(&[("a.txt", "x")]);
+    let res = test::call_service(&app, post("/missing/", body, "*/*")).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[actix_web::test]
+async fn cross_site_writes_are_refused() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("keep.txt"), "kept").unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, true))
+            .route("/{_:.*}", web::post().to(upload_form))
+            .route("/{_:.*}", web::put().to(upload_put))
+            .route("/{_:.*}", web::delete().to(delete_path)),
+    )
+    .await;
+    let post = |headers: &[(&str, &str)]| {
+        let mut req = test::TestRequest::post()
+            .uri("/")
+            .insert_header(("Host", "localhost:8080"))
+            .insert_header((
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            ))
+            .set_payload(multipart(&[("a.txt", "x")]));
+        for &header in headers {
+            req = req.insert_header(header);
+        }
+        req.to_request()
+    };
+    let refused = [
+        post(&[("Sec-Fetch-Site", "cross-site")]),
+        post(&[("Sec-Fetch-Site", "same-site")]),
+        post(&[("Origin", "https://evil.example")]),
+        post(&[("Origin", "null")]),
+        test::TestRequest::put()
+            .uri("/b.txt")
+            .insert_header(("Sec-Fetch-Site", "cross-site"))
+            .set_payload("x")
+            .to_request(),
+        test::TestRequest::delete()
+            .uri("/keep.txt")
+            .insert_header(("Host", "localhost:8080"))
+            .insert_header(("Origin", "http://evil.example"))
+            .to_request(),
+    ];
+    for req in refused {
+        assert_eq!(test::call_service(&app, req).await.status(), 403);
+    }
+    assert!(!temp.path().join("a.txt").exists());
+    assert!(!temp.path().join("b.txt").exists());
+    assert!(temp.path().join("keep.txt").exists());
+
+    // The listing's own form, and clients that send neither header
+    for headers in [
+        &[
+            ("Sec-Fetch-Site", "same-origin"),
+            ("Origin", "http://localhost:8080"),
+        ][..],
+        &[],
+    ] {
+        assert_eq!(test::call_service(&app, post(headers)).await.status(), 201);
+    }
+}
+
+#[actix_web::test]
+async fn deletes_and_creates_directories() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("old/nested")).unwrap();
+    fs::write(temp.path().join("old/nested/a.txt"), "a").unwrap();
+    fs::write(temp.path().join("b.txt"), "b").unwrap();
+    let data = data(&temp, true);
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data.clone())
+            .route("/{_:.*}", web::put().to(upload_put))
+            .route("/{_:.*}", web::delete().to(delete_path))
+            .route(
+                "/{_:.*}",
+                web::method(Method::from_bytes(b"MKCOL").unwrap()).to(make_dir),
+            ),
+    )
+    .await;
+    let request = |method: &str, uri: &str| {
+        test::TestRequest::default()
+            .method(Method::from_bytes(method.as_bytes()).unwrap())
+            .uri(uri)
+            .to_request()
+    };
+    let status = |req| {
+        let app = &app;
+        async move { test::call_service(app, req).await.status().as_u16() }
+    };
+
+    assert_eq!(status(request("DELETE", "/b.txt")).await, 204);
+    assert!(!temp.path().join("b.txt").exists());
+    assert_eq!(status(request("DELETE", "/old")).await, 204, "trees go too");
+    assert!(!temp.path().join("old").exists());
+    assert_eq!(status(request("DELETE", "/old")).await, 404);
+    assert_eq!(status(request("DELETE", "/")).await, 403);
+    assert_eq!(status(request("DELETE", "/%2e%2e/x")).await, 403);
+
+    assert_eq!(status(request("MKCOL", "/new%20dir")).await, 201);
+    assert!(temp.path().join("new dir").is_dir());
+    assert_eq!(status(request("MKCOL", "/new%20dir")).await, 405);
+    assert_eq!(status(request("MKCOL", "/a/b")).await, 409);
+    let put = test::TestRequest::put()
+        .uri("/new%20dir/c.txt")
+        .set_payload("c")
+        .to_request();
+    assert_eq!(status(put).await, 201);
+
+    // Without --watch the handlers publish what the watcher would have seen
+    let changes = data.history.since(0);
+    assert_eq!(changes.added, vec!["/new dir", "/new dir/c.txt"]);
+    assert_eq!(changes.removed, vec!["/b.txt", "/old"]);
+    assert_eq!(data.reload_log.current(), 4);
+}
+
+#[actix_web::test]
+async fn disabled_uploads_are_not_allowed() {
+    use actix_web::test;
+
+    let temp = TempDir::new().unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, false))
+            .route("/{_:.*}", web::get().to(serve_file))
+            .route("/{_:.*}", web::put().to(upload_put))
+            .route("/{_:.*}", web::post().to(upload_form))
+            .route("/{_:.*}", web::delete().to(delete_path)),
+    )
+    .await;
+    let req = test::TestRequest::put()
+        .uri("/x.txt")
+        .set_payload("x")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 405);
+    let req = test::TestRequest::post()
+        .uri("/")
+        .set_payload("x")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 405);
+    assert!(!temp.path().join("x.txt").exists());
+
+    let req = test::TestRequest::delete().uri("/x.txt").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 405);
+
+    fs::write(temp.path().join("kept.txt"), "k").unwrap();
+    let listing = test::call_and_read_body(&app, test::TestRequest::get().to_request()).await;
+    let listing = String::from_utf8(listing.to_vec()).unwrap();
+    assert!(!listing.contains("<form class=\"upload-form\""));
+    assert!(!listing.contains("delete-entry\""));
+}
+
+#[actix_web::test]
+async fn listing_shows_a_localized_upload_form() {
+    let temp = TempDir::new().unwrap();
+    fs::write(temp.path().join("notes.txt"), "n").unwrap();
+    let view = ListingView {
+        locale: Locale::De,
+        upload_form: true,
+        ..Default::default()
+    };
+    let listing = view.render(temp.path(), "/my docs/").await;
+    assert!(listing.contains(
+        r#"<form class="upload-form" method="post" enctype="multipart/form-data" action="/my%20docs">"#
+    ));
+    assert!(listing.contains(r#"<button type="submit">Hochladen</button>"#));
+    assert!(listing.contains(r#"<form class="upload-form mkdir-form" data-dir="/my%20docs">"#));
+    assert!(listing.contains(r#"<button type="submit">Neuer Ordner</button>"#));
+    assert!(listing.contains(
+        r#"class="delete-entry" data-path="/my%20docs/notes.txt" data-confirm="notes.txt löschen?""#
+    ));
+}
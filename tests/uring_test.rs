@@ -0,0 +1,69 @@
+//! `--io-uring` option and the io_uring read path (with `--features io-uring`).
+
+#[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+#[test]
+fn io_uring_flag_needs_the_feature() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_webserve"))
+        .args(["--io-uring", "-p", "0"])
+        .output()
+        .expect("run webserve binary");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--features io-uring"), "stderr: {}", stderr);
+}
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, web, App as ActixApp};
+    use std::fs;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use webserve::{serve_file, AppState, InflightReads, UringReader};
+
+    #[actix_web::test]
+    async fn reads_files_in_chunks() {
+        let Ok(reader) = UringReader::start() else {
+            return; // io_uring refused by this kernel or sandbox
+        };
+        let temp = TempDir::new().unwrap();
+        let body: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        fs::write(temp.path().join("a.bin"), &body).unwrap();
+        assert_eq!(reader.read(&temp.path().join("a.bin")).await.unwrap(), body);
+        assert!(reader.read(&temp.path().join("missing")).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn serves_whole_files_and_ranges() {
+        let Ok(reader) = UringReader::start() else {
+            return;
+        };
+        let temp = TempDir::new().unwrap();
+        let body: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        fs::write(temp.path().join("small.bin"), &body).unwrap();
+        let data = web::Data::new(AppState {
+            static_dir: Arc::new(temp.path().to_path_buf()),
+            inflight: InflightReads::with_uring(reader),
+            ..Default::default()
+        });
+        let app = test::init_service(
+            ActixApp::new()
+                .app_data(data.clone())
+                .route("/{_:.*}", web::get().to(serve_file)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/small.bin").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await.as_ref(), body.as_slice());
+
+        let req = test::TestRequest::get()
+            .uri("/small.bin")
+            .insert_header((header::RANGE, "bytes=10-19"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(test::read_body(res).await.as_ref(), &body[10..20]);
+    }
+}
@@ -0,0 +1,73 @@
+//! `--max-depth` / `--max-entries`: bounded tree walks and listings.
+
+use std::fs;
+use tempfile::TempDir;
+use webserve::{walk, ListingView, WalkLimits};
+
+fn tree() -> TempDir {
+    let temp = TempDir::new().unwrap();
+    let deep = temp.path().join("a").join("b").join("c");
+    fs::create_dir_all(&deep).unwrap();
+    fs::write(temp.path().join("top.txt"), b"").unwrap();
+    fs::write(temp.path().join("a").join("one.txt"), b"").unwrap();
+    fs::write(deep.join("deep.txt"), b"").unwrap();
+    temp
+}
+
+#[test]
+fn unlimited_walk_sees_everything() {
+    let temp = tree();
+    let found = walk(temp.path(), WalkLimits::default());
+    assert_eq!(found.files.len(), 3);
+    assert_eq!(found.dirs.len(), 4);
+    assert!(!found.truncated);
+}
+
+#[test]
+fn max_depth_stops_descending() {
+    let temp = tree();
+    let found = walk(
+        temp.path(),
+        WalkLimits {
+            max_depth: Some(2),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        found.dirs,
+        vec![temp.path().to_path_buf(), temp.path().join("a")]
+    );
+    assert_eq!(found.files.len(), 2);
+    assert!(found.truncated);
+}
+
+#[test]
+fn max_entries_stops_reading() {
+    let temp = tree();
+    let found = walk(
+        temp.path(),
+        WalkLimits {
+            max_entries: Some(2),
+            ..Default::default()
+        },
+    );
+    assert!(found.files.len() + found.dirs.len() <= 3);
+    assert!(found.truncated);
+}
+
+#[tokio::test]
+async fn listing_respects_max_entries() {
+    let temp = TempDir::new().unwrap();
+    for i in 0..5 {
+        fs::write(temp.path().join(format!("f{}.txt", i)), b"").unwrap();
+    }
+    let view = |max_entries| ListingView {
+        max_entries,
+        ..Default::default()
+    };
+    let listing = view(Some(3)).render(temp.path(), "/").await;
+    assert_eq!(listing.matches("class=\"entry\"").count(), 3);
+    assert!(listing.contains("Listing stopped after 3 entries"));
+    let listing = view(None).render(temp.path(), "/").await;
+    assert_eq!(listing.matches("class=\"entry\"").count(), 5);
+}
@@ -0,0 +1,172 @@
+//! Static-dir watching and the OS watch-limit fallback.
+
+use std::sync::mpsc;
+use std::time::Duration;
+use tempfile::TempDir;
+use webserve::{is_watch_limit, watch_tree, WalkLimits};
+
+#[test]
+fn watch_limit_errors_are_recognized() {
+    assert!(is_watch_limit(&notify::Error::new(
+        notify::ErrorKind::MaxFilesWatch
+    )));
+    assert!(is_watch_limit(&notify::Error::io(
+        std::io::Error::from_raw_os_error(28)
+    )));
+    assert!(!is_watch_limit(&notify::Error::path_not_found()));
+    assert!(!is_watch_limit(&notify::Error::io(
+        std::io::Error::from_raw_os_error(2)
+    )));
+}
+
+#[test]
+fn watch_tree_reports_changes() {
+    let temp = TempDir::new().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let _watcher = watch_tree(temp.path(), WalkLimits::default(), || {
+        let tx = tx.clone();
+        move |res: notify::Result<notify::Event>| {
+            if res.is_ok() {
+                let _ = tx.send(());
+            }
+        }
+    })
+    .unwrap();
+    std::fs::write(temp.path().join("new.txt"), b"x").unwrap();
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+}
+
+#[test]
+fn watch_tree_missing_root_is_an_error() {
+    let temp = TempDir::new().unwrap();
+    let missing = temp.path().join("gone");
+    let result = watch_tree(&missing, WalkLimits::default(), || {
+        |_: notify::Result<notify::Event>| {}
+    });
+    assert!(result.is_err());
+}
+
+fn create(path: &str) -> notify::Event {
+    notify::Event::new(notify::EventKind::Create(notify::event::CreateKind::File))
+        .add_path(std::path::PathBuf::from(path))
+}
+
+#[test]
+fn editor_temp_files_are_recognized() {
+    use std::path::Path;
+    use webserve::is_editor_temp;
+    for temp in [
+        "site/.index.html.swp",
+        "site/index.html~",
+        "site/4913",
+        "site/.#index.html",
+        "site/#index.html#",
+        "site/index.html___jb_tmp___",
+        "site/app.js.tmp",
+        "site/.goutputstream-ABC123",
+    ] {
+        assert!(is_editor_temp(Path::new(temp)), "{}", temp);
+    }
+    for real in ["site/index.html", "site/app.js", "site/tmp/page.html"] {
+        assert!(!is_editor_temp(Path::new(real)), "{}", real);
+    }
+}
+
+#[test]
+fn reads_and_temp_only_events_are_irrelevant() {
+    use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+    use webserve::is_relevant;
+    assert!(is_relevant(&create("site/index.html")));
+    assert!(!is_relevant(&create("site/.index.html.swp")));
+    let read = notify::Event::new(notify::EventKind::Access(AccessKind::Open(AccessMode::Any)))
+        .add_path("site/index.html".into());
+    assert!(!is_relevant(&read));
+    let rename = notify::Event::new(notify::EventKind::Modify(ModifyKind::Name(
+        RenameMode::Both,
+    )))
+    .add_path("site/index.html.tmp".into())
+    .add_path("site/index.html".into());
+    assert!(is_relevant(&rename));
+}
+
+#[test]
+fn atomic_save_burst_coalesces_into_one_batch() {
+    use webserve::coalesce;
+    let (tx, rx) = mpsc::channel();
+    let mut handler = coalesce(Duration::from_millis(50), move |batch| {
+        let _ = tx.send(batch);
+    });
+    handler(Ok(create("site/.index.html.swp")));
+    handler(Ok(create("site/index.html.tmp")));
+    handler(Ok(create("site/index.html")));
+    handler(Ok(create("site/index.html~")));
+
+    let batch: Vec<notify::Event> = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+    assert_eq!(batch.len(), 1);
+    assert_eq!(
+        batch[0].paths,
+        vec![std::path::PathBuf::from("site/index.html")]
+    );
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[test]
+fn temp_only_changes_do_not_produce_a_batch() {
+    use webserve::coalesce;
+    let (tx, rx) = mpsc::channel::<Vec<notify::Event>>();
+    let mut handler = coalesce(Duration::from_millis(20), move |batch| {
+        let _ = tx.send(batch);
+    });
+    handler(Ok(create("site/.index.html.swp")));
+    handler(Ok(create("site/4913")));
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[test]
+fn extra_roots_share_one_debounced_handler() {
+    use webserve::{coalesce, watch_trees};
+    let served = TempDir::new().unwrap();
+    let src = TempDir::new().unwrap();
+    let (tx, rx) = mpsc::channel::<Vec<notify::Event>>();
+    let _watcher = watch_trees(&[served.path(), src.path()], WalkLimits::default(), || {
+        let tx = tx.clone();
+        coalesce(Duration::from_millis(300), move |batch| {
+            let _ = tx.send(batch);
+        })
+    })
+    .unwrap();
+    std::fs::write(src.path().join("app.ts"), b"x").unwrap();
+    std::fs::write(served.path().join("app.js"), b"x").unwrap();
+
+    let batch = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    let paths: Vec<_> = batch.iter().flat_map(|e| e.paths.clone()).collect();
+    assert!(paths.iter().any(|p| p.ends_with("app.ts")), "{:?}", paths);
+    assert!(paths.iter().any(|p| p.ends_with("app.js")), "{:?}", paths);
+
+    let missing = src.path().join("gone");
+    let err = watch_trees(&[served.path(), &missing], WalkLimits::default(), || {
+        |_: notify::Result<notify::Event>| {}
+    })
+    .err()
+    .unwrap();
+    assert!(err.contains("gone"), "{}", err);
+    assert!(watch_trees(&[], WalkLimits::default(), || {
+        |_: notify::Result<notify::Event>| {}
+    })
+    .is_err());
+}
+
+#[test]
+fn configured_watch_paths_are_relative_to_the_config() {
+    use webserve::resolve_options_with_env;
+
+    let temp = TempDir::new().unwrap();
+    let config_path = temp.path().join("webserve.toml");
+    std::fs::write(&config_path, "watch-paths = [\"../src\"]\n").unwrap();
+    let options = resolve_options_with_env(
+        ["webserve", "--config", config_path.to_str().unwrap()],
+        Vec::new(),
+    )
+    .unwrap();
+    assert_eq!(options.watch_paths, vec![temp.path().join("../src")]);
+}
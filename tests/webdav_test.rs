@@ -0,0 +1,307 @@
+//! `--webdav`: PROPFIND, MKCOL, PUT, DELETE, COPY, MOVE and advisory locks.
+
+#![cfg(feature = "webdav")]
+
+mod common;
+
+use actix_web::http::{header, Method};
+use actix_web::{web, App as ActixApp};
+use std::fs;
+use std::sync::Arc;
+use tempfile::TempDir;
+use webserve::{
+    delete_path, make_dir, serve_file, upload_put, webdav, AppState, Settings, DAV_METHODS,
+};
+
+fn site() -> TempDir {
+    common::site(&[
+        ("docs/read me.txt", "hello"),
+        ("index.html", "<h1>Home</h1>"),
+    ])
+}
+
+fn data(root: &TempDir, webdav: bool) -> web::Data<AppState> {
+    web::Data::new(AppState {
+        static_dir: Arc::new(root.path().to_path_buf()),
+        settings: Arc::new(
+            Settings {
+                webdav,
+                ..Default::default()
+            }
+            .into(),
+        ),
+        ..Default::default()
+    })
+}
+
+fn mkcol() -> actix_web::Route {
+    web::method(Method::from_bytes(b"MKCOL").unwrap())
+}
+
+fn dav(method: &str, uri: &str) -> actix_web::test::TestRequest {
+    actix_web::test::TestRequest::default()
+        .method(Method::from_bytes(method.as_bytes()).unwrap())
+        .uri(uri)
+}
+
+#[actix_web::test]
+async fn propfind_describes_files_and_directories() {
+    use actix_web::test;
+
+    let temp = site();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, true))
+            .route("/{_:.*}", web::get().to(serve_file))
+            .route("/{_:.*}", web::route().to(webdav)),
+    )
+    .await;
+
+    let res = test::call_service(&app, dav("OPTIONS", "/").to_request()).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers().get("dav").unwrap(), "1, 2");
+    assert_eq!(res.headers().get(header::ALLOW).unwrap(), DAV_METHODS);
+
+    let req = dav("PROPFIND", "/docs")
+        .insert_header(("Depth", "1"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 207);
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("<D:href>/docs/</D:href>"));
+    assert!(body.contains("<D:resourcetype><D:collection/></D:resourcetype>"));
+    assert!(body.contains("<D:href>/docs/read%20me.txt</D:href>"));
+    assert!(body.contains("<D:displayname>read me.txt</D:displayname>"));
+    assert!(body.contains("<D:getcontentlength>5</D:getcontentlength>"));
+    assert!(body.contains("<D:getcontenttype>text/plain</D:getcontenttype>"));
+
+    let req = dav("PROPFIND", "/")
+        .insert_header(("Depth", "0"))
+        .to_request();
+    let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+    assert_eq!(body.matches("<D:response>").count(), 1);
+
+    // `Depth: infinity`, the default, would walk the whole tree
+    let res = test::call_service(&app, dav("PROPFIND", "/").to_request()).await;
+    assert_eq!(res.status(), 403);
+    let req = dav("PROPFIND", "/missing")
+        .insert_header(("Depth", "0"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 404);
+    let req = dav("PROPFIND", "/%2e%2e/")
+        .insert_header(("Depth", "0"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+
+    let res = test::call_service(&app, dav("HEAD", "/docs/read%20me.txt").to_request()).await;
+    assert_eq!(res.status(), 200);
+}
+
+#[actix_web::test]
+async fn writes_move_copy_and_delete() {
+    use actix_web::test;
+
+    let temp = site();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, true))
+            .route("/{_:.*}", web::put().to(upload_put))
+            .route("/{_:.*}", web::delete().to(delete_path))
+            .route("/{_:.*}", mkcol().to(make_dir))
+            .route("/{_:.*}", web::route().to(webdav)),
+    )
+    .await;
+    let status = |req| {
+        let app = &app;
+        async move { test::call_service(app, req).await.status().as_u16() }
+    };
+
+    assert_eq!(status(dav("MKCOL", "/new").to_request()).await, 201);
+    assert!(temp.path().join("new").is_dir());
+    assert_eq!(status(dav("MKCOL", "/new").to_request()).await, 405);
+    assert_eq!(status(dav("MKCOL", "/a/b").to_request()).await, 409);
+
+    let put = test::TestRequest::put()
+        .uri("/new/note.txt")
+        .set_payload("note")
+        .to_request();
+    assert_eq!(status(put).await, 201, "PUT works without --upload");
+
+    let copy = dav("COPY", "/new")
+        .insert_header(("Destination", "http://localhost:8080/copy"))
+        .to_request();
+    assert_eq!(status(copy).await, 201);
+    assert_eq!(
+        fs::read_to_string(temp.path().join("copy/note.txt")).unwrap(),
+        "note"
+    );
+    assert!(temp.path().join("new/note.txt").is_file());
+
+    let keep = dav("MOVE", "/index.html")
+        .insert_header(("Destination", "/docs/read%20me.txt"))
+        .insert_header(("Overwrite", "F"))
+        .to_request();
+    assert_eq!(status(keep).await, 412);
+    let replace = dav("MOVE", "/index.html")
+        .insert_header(("Destination", "/docs/read%20me.txt"))
+        .to_request();
+    assert_eq!(status(replace).await, 204);
+    assert!(!temp.path().join("index.html").exists());
+    assert_eq!(
+        fs::read_to_string(temp.path().join("docs/read me.txt")).unwrap(),
+        "<h1>Home</h1>"
+    );
+
+    let into_itself = dav("COPY", "/new")
+        .insert_header(("Destination", "/new/inner"))
+        .to_request();
+    assert_eq!(status(into_itself).await, 403);
+    let orphan = dav("MOVE", "/new")
+        .insert_header(("Destination", "/no/such/dir"))
+        .to_request();
+    assert_eq!(status(orphan).await, 409);
+    let escape = dav("MOVE", "/new")
+        .insert_header(("Destination", "/../outside"))
+        .to_request();
+    assert_eq!(status(escape).await, 403);
+    assert_eq!(status(dav("MOVE", "/new").to_request()).await, 400);
+
+    assert_eq!(status(dav("DELETE", "/copy").to_request()).await, 204);
+    assert!(!temp.path().join("copy").exists());
+    assert_eq!(status(dav("DELETE", "/copy").to_request()).await, 404);
+    assert_eq!(status(dav("DELETE", "/").to_request()).await, 403);
+}
+
+#[actix_web::test]
+async fn locks_are_advisory() {
+    use actix_web::test;
+
+    let temp = site();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, true))
+            .route("/{_:.*}", web::route().to(webdav)),
+    )
+    .await;
+
+    let res = test::call_service(&app, dav("LOCK", "/docs/new.txt").to_request()).await;
+    assert_eq!(res.status(), 201, "locking a missing file creates it");
+    let token = res
+        .headers()
+        .get("lock-token")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(token.starts_with("<opaquelocktoken:"));
+    assert!(temp.path().join("docs/new.txt").is_file());
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains(&token[1..token.len() - 1]));
+
+    let res = test::call_service(&app, dav("LOCK", "/index.html").to_request()).await;
+    assert_eq!(res.status(), 200);
+    let res = test::call_service(&app, dav("UNLOCK", "/index.html").to_request()).await;
+    assert_eq!(res.status(), 204);
+
+    let res = test::call_service(&app, dav("PROPPATCH", "/index.html").to_request()).await;
+    assert_eq!(res.status(), 207);
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("HTTP/1.1 403 Forbidden"));
+}
+
+#[cfg(unix)]
+#[actix_web::test]
+async fn refuses_symlinks_out_of_the_root() {
+    use actix_web::test;
+
+    let temp = site();
+    let outside = TempDir::new().unwrap();
+    fs::write(outside.path().join("secret.txt"), "s").unwrap();
+    std::os::unix::fs::symlink(outside.path(), temp.path().join("link")).unwrap();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, true))
+            .route("/{_:.*}", web::delete().to(delete_path))
+            .route("/{_:.*}", mkcol().to(make_dir))
+            .route("/{_:.*}", web::route().to(webdav)),
+    )
+    .await;
+
+    let req = dav("DELETE", "/link/secret.txt").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    let req = dav("MKCOL", "/link/sub").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    let req = dav("MOVE", "/index.html")
+        .insert_header(("Destination", "/link/index.html"))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 403);
+    assert!(outside.path().join("secret.txt").is_file());
+}
+
+#[actix_web::test]
+async fn disabled_webdav_changes_nothing() {
+    use actix_web::test;
+
+    let temp = site();
+    let app = test::init_service(
+        ActixApp::new()
+            .app_data(data(&temp, false))
+            .route("/{_:.*}", web::put().to(upload_put))
+            .route("/{_:.*}", web::delete().to(delete_path))
+            .route("/{_:.*}", mkcol().to(make_dir))
+            .route("/{_:.*}", web::route().to(webdav)),
+    )
+    .await;
+    for method in ["OPTIONS", "PROPFIND", "MOVE", "LOCK"] {
+        let res = test::call_service(&app, dav(method, "/index.html").to_request()).await;
+        assert_eq!(res.status(), 404, "{}", method);
+    }
+    for method in ["PUT", "MKCOL", "DELETE"] {
+        let res = test::call_service(&app, dav(method, "/index.html").to_request()).await;
+        assert_eq!(res.status(), 405, "{}", method);
+    }
+    assert!(temp.path().join("index.html").is_file());
+}
+
+#[actix_web::test]
+async fn destinations_under_a_mount_stay_in_it() {
+    use actix_web::test;
+    use webserve::Webserve;
+
+    let root = site();
+    let docs = common::site(&[("a.txt", "a"), ("c.txt", "c")]);
+    let server = Webserve::builder()
+        .dir(root.path())
+        .configure(|o| {
+            o.webdav = true;
+            o.mounts = vec![format!("/docs={}", docs.path().display())];
+        })
+        .build()
+        .unwrap();
+    let app = test::init_service(ActixApp::new().service(server.into_actix_service())).await;
+
+    // Both absolute URLs and paths lose the prefix
+    for (from, to) in [
+        ("/docs/a.txt", "/docs/b.txt"),
+        ("/docs/b.txt", "http://localhost:8080/docs/sub-b.txt"),
+    ] {
+        let req = dav("MOVE", from)
+            .insert_header(("Destination", to))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 201, "{}", to);
+    }
+    assert_eq!(
+        fs::read_to_string(docs.path().join("sub-b.txt")).unwrap(),
+        "a"
+    );
+    assert!(!docs.path().join("docs").exists());
+
+    // A destination outside the mount is another namespace
+    for to in ["/c.txt", "/docsx/c.txt", "http://localhost:8080/index.html"] {
+        let req = dav("COPY", "/docs/c.txt")
+            .insert_header(("Destination", to))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 502, "{}", to);
+    }
+    assert!(!root.path().join("c.txt").exists());
+}
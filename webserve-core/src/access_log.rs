@@ -0,0 +1,407 @@
+//! `--log`: one access-log line per request in common, combined or JSON format.
+//!
+//! `--log-exclude <pattern>` drops matching paths from the log and `--log-sample
+//! <pattern>=<rate>` keeps only that share of them (`0.1` logs every tenth request); error
+//! responses (`4xx`/`5xx`) of sampled paths are always logged. Patterns match the path
+//! without query, `*` standing for any run of characters, e.g. `/__webserve/*` or `*.map`.
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use serde_json::json;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::serve::days_to_ymd;
+use crate::{AppState, ServeOptions};
+
+/// Access-log line format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// NCSA common log format, plus latency in ms.
+    Common,
+    /// Common plus `"referer" "user-agent"`, plus latency in ms.
+    Combined,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "common" => Ok(LogFormat::Common),
+            "combined" => Ok(LogFormat::Combined),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "unknown log format {} (expected common, combined or json)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Common => "common",
+            LogFormat::Combined => "combined",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+/// Everything one log line needs, captured around the request.
+#[derive(Debug, Clone)]
+pub struct AccessRecord {
+    pub remote: String,
+    pub time: SystemTime,
+    pub method: String,
+    /// Path plus `?query` when present.
+    pub target: String,
+    pub version: String,
+    pub status: u16,
+    /// Body size when known up front.
+    pub bytes: Option<u64>,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub latency: Duration,
+}
+
+impl AccessRecord {
+    /// Renders the record in `format` (no trailing newline).
+    pub fn format(&self, format: LogFormat) -> String {
+        let latency_ms = self.latency.as_secs_f64() * 1000.0;
+        let bytes = self
+            .bytes
+            .map(|b| b.to_string())
+            .unwrap_or_else(|| "-".into());
+        match format {
+            LogFormat::Common => format!(
+                "{} - - [{}] \"{} {} {}\" {} {} {:.3}ms",
+                self.remote,
+                clf_time(self.time),
+                self.method,
+                self.target,
+                self.version,
+                self.status,
+                bytes,
+                latency_ms
+            ),
+            LogFormat::Combined => format!(
+                "{} - - [{}] \"{} {} {}\" {} {} \"{}\" \"{}\" {:.3}ms",
+                self.remote,
+                clf_time(self.time),
+                self.method,
+                self.target,
+                self.version,
+                self.status,
+                bytes,
+                self.referer.as_deref().unwrap_or("-"),
+                self.user_agent.as_deref().unwrap_or("-"),
+                latency_ms
+            ),
+            LogFormat::Json => json!({
+                "time": iso_time(self.time),
+                "remote": self.remote,
+                "method": self.method,
+                "path": self.target,
+                "version": self.version,
+                "status": self.status,
+                "bytes": self.bytes,
+                "referer": self.referer,
+                "user_agent": self.user_agent,
+                "latency_ms": (latency_ms * 1000.0).round() / 1000.0,
+            })
+            .to_string(),
+        }
+    }
+}
+
+/// Path pattern of `--log-exclude` / `--log-sample`; `*` matches any characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPattern(String);
+
+impl PathPattern {
+    pub fn new(pattern: &str) -> PathPattern {
+        PathPattern(pattern.to_string())
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        let mut parts = self.0.split('*');
+        let first = parts.next().unwrap_or("");
+        let Some(mut rest) = path.strip_prefix(first) else {
+            return false;
+        };
+        let mut parts: Vec<&str> = parts.collect();
+        let Some(last) = parts.pop() else {
+            return rest.is_empty();
+        };
+        for part in parts {
+            match rest.find(part) {
+                Some(i) => rest = &rest[i + part.len()..],
+                None => return false,
+            }
+        }
+        rest.ends_with(last)
+    }
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// One `--log-sample` rule; `seen` counts matching requests.
+#[derive(Debug)]
+struct SampleRule {
+    pattern: PathPattern,
+    rate: f64,
+    seen: AtomicU64,
+}
+
+impl SampleRule {
+    /// Every `1/rate`-th match, spread evenly: the first request is logged.
+    fn take(&self) -> bool {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed) as f64;
+        (n * self.rate).ceil() < ((n + 1.0) * self.rate).ceil()
+    }
+}
+
+/// Which requests reach the access log.
+#[derive(Debug, Default)]
+pub struct LogFilter {
+    exclude: Vec<PathPattern>,
+    sample: Vec<SampleRule>,
+}
+
+impl LogFilter {
+    /// From `--log-exclude` and `--log-sample`.
+    pub fn from_options(options: &ServeOptions) -> Result<LogFilter, String> {
+        let mut filter = LogFilter {
+            exclude: options
+                .log_exclude
+                .iter()
+                .map(|p| PathPattern::new(p))
+                .collect(),
+            sample: Vec::new(),
+        };
+        for spec in &options.log_sample {
+            let (pattern, rate) = parse_sample(spec)?;
+            filter = filter.sample(pattern, rate);
+        }
+        Ok(filter)
+    }
+
+    /// Adds a `--log-sample` rule; `rate` is the share of matching requests kept.
+    pub fn sample(mut self, pattern: PathPattern, rate: f64) -> LogFilter {
+        self.sample.push(SampleRule {
+            pattern,
+            rate,
+            seen: AtomicU64::new(0),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exclude.is_empty() && self.sample.is_empty()
+    }
+
+    /// Dashboard text, e.g. `2 excluded, 1 sampled`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{} excluded, {} sampled",
+            self.exclude.len(),
+            self.sample.len()
+        )
+    }
+
+    /// True when a request for `path` answered with `status` is logged; the first matching
+    /// sample rule decides.
+    pub fn keeps(&self, path: &str, status: u16) -> bool {
+        if self.exclude.iter().any(|p| p.matches(path)) {
+            return false;
+        }
+        match self.sample.iter().find(|rule| rule.pattern.matches(path)) {
+            Some(_) if status >= 400 => true,
+            Some(rule) => rule.take(),
+            None => true,
+        }
+    }
+}
+
+/// `PATTERN=RATE` with a rate in `(0, 1]`, e.g. `/api/*=0.1`.
+pub fn parse_sample(spec: &str) -> Result<(PathPattern, f64), String> {
+    let (pattern, rate) = spec
+        .rsplit_once('=')
+        .ok_or_else(|| format!("--log-sample {}: expected PATTERN=RATE", spec))?;
+    match rate.trim().parse::<f64>() {
+        Ok(r) if r > 0.0 && r <= 1.0 && !pattern.is_empty() => Ok((PathPattern::new(pattern), r)),
+        _ => Err(format!(
+            "--log-sample {}: rate must be above 0 and at most 1",
+            spec
+        )),
+    }
+}
+
+/// Writes formatted records to stdout or a file.
+pub struct AccessLogger {
+    format: LogFormat,
+    filter: LogFilter,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl fmt::Debug for AccessLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLogger")
+            .field("format", &self.format)
+            .field("filter", &self.filter)
+            .finish()
+    }
+}
+
+impl AccessLogger {
+    pub fn new(format: LogFormat, out: Box<dyn Write + Send>) -> AccessLogger {
+        AccessLogger {
+            format,
+            filter: LogFilter::default(),
+            out: Mutex::new(out),
+        }
+    }
+
+    /// Only logs the requests `filter` keeps.
+    pub fn with_filter(mut self, filter: LogFilter) -> AccessLogger {
+        self.filter = filter;
+        self
+    }
+
+    pub fn filter(&self) -> &LogFilter {
+        &self.filter
+    }
+
+    pub fn stdout(format: LogFormat) -> AccessLogger {
+        AccessLogger::new(format, Box::new(std::io::stdout()))
+    }
+
+    /// Appends to `path`, creating it if needed.
+    pub fn file(format: LogFormat, path: &Path) -> Result<AccessLogger, String> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("cannot open log file {}: {}", path.display(), e))?;
+        Ok(AccessLogger::new(format, Box::new(file)))
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    /// Writes one line; write errors are ignored so logging never fails a request.
+    pub fn log(&self, record: &AccessRecord) {
+        let line = record.format(self.format);
+        if let Ok(mut out) = self.out.lock() {
+            let _ = writeln!(out, "{}", line);
+            let _ = out.flush();
+        }
+    }
+}
+
+/// Middleware emitting one line per request when `AppState::access_log` is set and its
+/// [`LogFilter`] keeps the request.
+pub async fn access_log(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let logger = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|data| data.access_log.clone());
+    let Some(logger) = logger else {
+        return next.call(req).await;
+    };
+    let started = Instant::now();
+    let time = SystemTime::now();
+    let header_str = |name| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let referer = header_str(header::REFERER);
+    let user_agent = header_str(header::USER_AGENT);
+    let remote = req
+        .peer_addr()
+        .map(|a| a.ip().to_string())
+        .unwrap_or_else(|| "-".into());
+    let method = req.method().to_string();
+    let target = match req.query_string() {
+        "" => req.path().to_string(),
+        q => format!("{}?{}", req.path(), q),
+    };
+    let version = format!("{:?}", req.version());
+    let path = req.path().to_string();
+    let res = next.call(req).await?;
+    if !logger.filter().keeps(&path, res.status().as_u16()) {
+        return Ok(res);
+    }
+    let bytes = match res.response().body().size() {
+        BodySize::None => Some(0),
+        BodySize::Sized(n) => Some(n),
+        BodySize::Stream => None,
+    };
+    logger.log(&AccessRecord {
+        remote,
+        time,
+        method,
+        target,
+        version,
+        status: res.status().as_u16(),
+        bytes,
+        referer,
+        user_agent,
+        latency: started.elapsed(),
+    });
+    Ok(res)
+}
+
+/// `10/Oct/2000:13:55:36 +0000`
+fn clf_time(t: SystemTime) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (y, m, d, hh, mm, ss) = civil(t);
+    format!(
+        "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+        d,
+        MONTHS[(m - 1) as usize],
+        y,
+        hh,
+        mm,
+        ss
+    )
+}
+
+/// `2000-10-10T13:55:36Z`
+fn iso_time(t: SystemTime) -> String {
+    let (y, m, d, hh, mm, ss) = civil(t);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, m, d, hh, mm, ss)
+}
+
+fn civil(t: SystemTime) -> (u32, u32, u32, u64, u64, u64) {
+    let secs = t
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (y, m, d) = days_to_ymd((secs / 86400) as u32);
+    let time = secs % 86400;
+    (y, m, d, time / 3600, (time % 3600) / 60, time % 60)
+}
@@ -0,0 +1,389 @@
+//! Internal endpoints under `/__webserve/`.
+//!
+//! They answer loopback clients only, unless `--admin-remote` is given: the dashboard,
+//! stats, manifest and search describe the served tree and `scenario` changes what is
+//! served. A request without a peer address (`--uds`) or carrying `Forwarded` /
+//! `X-Forwarded-For` (relayed by a proxy on the same machine) is refused too. Writes
+//! (`PUT`/`POST /__webserve/scenario`) also need a `Host` naming this machine, so a page
+//! reaching the server through DNS rebinding cannot switch scenarios.
+//! `/__webserve/events` is left to its own token.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse, Responder};
+use serde_json::json;
+use std::path::Path;
+use tracing::debug;
+
+use crate::branding::BRAND_STYLE;
+use crate::limits::describe_methods;
+use crate::path::host_is_local;
+use crate::serve::html_escape;
+use crate::stats::{top_downloads, TOP_DOWNLOADS};
+use crate::AppState;
+
+/// Prefix reserved for webserve's own endpoints.
+pub const ADMIN_PREFIX: &str = "/__webserve";
+
+/// True when `req` comes straight from this machine: a loopback peer, not relayed by a proxy.
+fn is_loopback(req: &ServiceRequest) -> bool {
+    let relayed = req.headers().contains_key(header::FORWARDED)
+        || req.headers().contains_key("x-forwarded-for");
+    let loopback = req
+        .peer_addr()
+        .is_some_and(|addr| addr.ip().to_canonical().is_loopback());
+    loopback && !relayed
+}
+
+/// Middleware keeping `/__webserve/` to localhost unless `--admin-remote` (see the module docs).
+pub async fn admin_access<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let admin = req
+        .path()
+        .strip_prefix(ADMIN_PREFIX)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    let events = req.path() == format!("{}/events", ADMIN_PREFIX);
+    let data = req.app_data::<web::Data<AppState>>();
+    let remote = data.is_some_and(|data| data.settings.load().admin_remote);
+    if admin && !events && !remote && !is_loopback(&req) {
+        debug!(peer = ?req.peer_addr(), path = req.path(), "admin page refused to a remote client");
+        let res = HttpResponse::Forbidden()
+            .content_type("text/plain; charset=utf-8")
+            .body("403 Forbidden: /__webserve/ answers localhost only (see --admin-remote)");
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+    let write = !matches!(*req.method(), Method::GET | Method::HEAD);
+    let local_host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|host| host_is_local(host, data.map_or("", |data| data.addr.as_str())));
+    if admin && !events && !remote && write && !local_host {
+        debug!(
+            path = req.path(),
+            "admin write refused: Host does not name this machine"
+        );
+        let res = HttpResponse::Forbidden()
+            .content_type("text/plain; charset=utf-8")
+            .body("403 Forbidden: /__webserve/ writes need a localhost Host header");
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+/// Last component of `path`, so pages never show where the files sit on disk.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| ".".to_string())
+}
+
+fn scenario_body(data: &AppState) -> serde_json::Value {
+    let settings = data.settings.load();
+    let available = settings
+        .fixtures
+        .as_ref()
+        .map(|f| f.scenarios())
+        .unwrap_or_default();
+    json!({ "active": data.active_scenario(), "available": available })
+}
+
+/// `GET /__webserve/scenario`: active fixture scenario and the ones declared.
+pub async fn scenario_status(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(scenario_body(&data))
+}
+
+/// `PUT|POST /__webserve/scenario` with the scenario name as body; empty or `default` clears it.
+pub async fn set_scenario(data: web::Data<AppState>, body: String) -> impl Responder {
+    let name = body.trim();
+    let next = if name.is_empty() || name == "default" {
+        None
+    } else {
+        let known = data
+            .settings
+            .load()
+            .fixtures
+            .as_ref()
+            .is_some_and(|f| f.scenarios().iter().any(|s| s == name));
+        if !known {
+            return HttpResponse::NotFound()
+                .json(json!({ "error": format!("unknown scenario {}", name) }));
+        }
+        Some(name.to_string())
+    };
+    if let Ok(mut guard) = data.scenario.write() {
+        *guard = next;
+    }
+    HttpResponse::Ok().json(scenario_body(&data))
+}
+
+/// Active options as label/value pairs, read from the live settings.
+fn active_options(data: &AppState) -> Vec<(&'static str, String)> {
+    let settings = data.settings.load();
+    let on_off = |b: bool| if b { "on" } else { "off" }.to_string();
+    let mut options = vec![
+        ("Directory", file_name(&data.static_dir)),
+        ("Address", data.addr.clone()),
+        ("Mount", data.mount.to_string()),
+        ("Watch", on_off(data.watch)),
+        ("SPA", on_off(settings.spa)),
+        ("Section index", on_off(settings.section_index)),
+        ("404 suggestions", on_off(settings.suggest)),
+        (
+            "Directory slash redirect",
+            on_off(settings.redirect_dir_slash),
+        ),
+        ("ETag", settings.etag.to_string()),
+        ("Listing language", settings.listing_lang.to_string()),
+        ("Render Markdown", on_off(settings.render_markdown)),
+        ("Highlight", on_off(settings.highlight)),
+        (
+            "Uploads",
+            if settings.upload {
+                format!(
+                    "on, up to {} MiB",
+                    settings.max_upload_bytes / (1024 * 1024)
+                )
+            } else {
+                "off".to_string()
+            },
+        ),
+        ("WebDAV", on_off(settings.webdav)),
+        ("Open in editor", on_off(settings.open_in_editor)),
+        ("No-cache", on_off(settings.no_cache)),
+        ("Fault injection", on_off(settings.fault_injection)),
+        ("Hardening", on_off(settings.harden)),
+        (
+            "Slowloris guard",
+            settings
+                .slowloris
+                .map(|g| g.describe())
+                .unwrap_or_else(|| "off".to_string()),
+        ),
+        ("Request limits", settings.request_limits.describe()),
+        ("Methods", describe_methods(&settings, &data.hooks)),
+        ("Network shaping", settings.network.describe()),
+        ("Rules", settings.rules.len().to_string()),
+        ("IP filter rules", settings.ip_filter.len().to_string()),
+        ("Extra headers", settings.headers.len().to_string()),
+        ("MIME overrides", settings.mime.len().to_string()),
+        ("Charset", settings.charset.describe()),
+        ("Store", data.store.describe()),
+    ];
+    if let Some(ref fixtures) = settings.fixtures {
+        options.push(("Fixture routes", fixtures.len().to_string()));
+    }
+    if let Some(ref dir) = settings.graphql_mocks {
+        options.push(("GraphQL mocks", file_name(dir)));
+    }
+    if let Some(ref proxy) = settings.proxy {
+        options.push(("Proxy", proxy.describe()));
+    }
+    if let Some(ref logger) = data.access_log {
+        let mut access_log = logger.format().to_string();
+        if !logger.filter().is_empty() {
+            access_log = format!("{} ({})", access_log, logger.filter().describe());
+        }
+        options.push(("Access log", access_log));
+    }
+    if let Some(scenario) = data.active_scenario() {
+        options.push(("Scenario", scenario));
+    }
+    options
+}
+
+/// `GET /__webserve/stats`: the dashboard data as JSON.
+pub async fn stats_json(data: web::Data<AppState>) -> impl Responder {
+    let options: serde_json::Map<String, serde_json::Value> = active_options(&data)
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.into()))
+        .collect();
+    let store = data.store.clone();
+    let downloads = web::block(move || top_downloads(store.as_ref(), TOP_DOWNLOADS))
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .unwrap_or_default();
+    let downloads: serde_json::Map<String, serde_json::Value> = downloads
+        .into_iter()
+        .map(|(path, count)| (path, count.into()))
+        .collect();
+    HttpResponse::Ok().json(json!({
+        "uptime_secs": data.stats.uptime().as_secs(),
+        "total_requests": data.stats.total_requests(),
+        "options": options,
+        "reload_clients": data.stats.reload_clients(),
+        "recent_requests": data.stats.recent_requests(),
+        "recent_changes": data.stats.recent_changes(),
+        "downloads": downloads,
+        "tls": data.stats.tls_counts(),
+        "broken_links": data.stats.broken_links(),
+    }))
+}
+
+/// `GET /__webserve/broken-links`: paths that answered `404` with their referers, as JSON.
+pub async fn broken_links(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(data.stats.broken_links())
+}
+
+/// `GET /__webserve/`: HTML status dashboard, refreshed every few seconds.
+pub async fn dashboard(data: web::Data<AppState>) -> impl Responder {
+    let stats = &data.stats;
+    let settings = data.settings.load();
+    let options: String = active_options(&data)
+        .iter()
+        .map(|(k, v)| format!("<tr><th>{}</th><td>{}</td></tr>", k, html_escape(v)))
+        .collect();
+    let clients = stats.reload_clients();
+    let clients_html = if clients.is_empty() {
+        "<p class=\"muted\">None</p>".to_string()
+    } else {
+        let items: String = clients
+            .iter()
+            .map(|c| format!("<li>{}</li>", html_escape(c)))
+            .collect();
+        format!("<ul>{}</ul>", items)
+    };
+    let requests: String = stats
+        .recent_requests()
+        .iter()
+        .map(|r| {
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1} ms</td></tr>",
+                clock(r.time),
+                html_escape(&r.method),
+                html_escape(&r.path),
+                r.status,
+                r.latency_ms
+            )
+        })
+        .collect();
+    let changes: String = stats
+        .recent_changes()
+        .iter()
+        .map(|c| {
+            let paths: Vec<String> = c
+                .paths
+                .iter()
+                .map(|p| html_escape(&p.display().to_string()))
+                .collect();
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                clock(c.time),
+                html_escape(&c.kind),
+                paths.join("<br>")
+            )
+        })
+        .collect();
+    let broken = stats.broken_links();
+    let broken_html = if broken.is_empty() {
+        "<p class=\"muted\">None</p>".to_string()
+    } else {
+        let rows: String = broken
+            .iter()
+            .map(|link| {
+                let referers: Vec<String> = link
+                    .referers
+                    .iter()
+                    .map(|(r, n)| format!("{} ({})", html_escape(r), n))
+                    .collect();
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    html_escape(&link.path),
+                    link.hits,
+                    clock(link.last_seen),
+                    referers.join("<br>")
+                )
+            })
+            .collect();
+        format!(
+            "<table><tr><th>Path</th><th>Hits</th><th>Last (UTC)</th><th>Referers</th></tr>{}</table>",
+            rows
+        )
+    };
+    let tls = stats.tls_counts();
+    let tls_html = if tls.connections == 0 {
+        String::new()
+    } else {
+        let tally = |counts: &std::collections::BTreeMap<String, u64>| {
+            counts
+                .iter()
+                .map(|(k, n)| format!("{} ({})", html_escape(k), n))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        format!(
+            "<h2>TLS connections ({})</h2>\n  <table><tr><th>Version</th><td>{}</td></tr><tr><th>Cipher</th><td>{}</td></tr><tr><th>ALPN</th><td>{}</td></tr></table>",
+            tls.connections,
+            tally(&tls.versions),
+            tally(&tls.ciphers),
+            tally(&tls.alpn)
+        )
+    };
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta http-equiv="refresh" content="3">
+  <title>{page_title}</title>
+  <style>
+    body {{ font-family: system-ui, sans-serif; margin: 2rem; color: #1f2937; }}
+    h1 {{ font-size: 1.4rem; }}
+    h2 {{ font-size: 1.05rem; margin-top: 2rem; }}
+    table {{ border-collapse: collapse; width: 100%; font-size: 0.9rem; }}
+    th, td {{ text-align: left; padding: 0.3rem 0.6rem; border-bottom: 1px solid #e5e7eb; vertical-align: top; }}
+    .muted {{ color: #6b7280; }}{brand_style}
+  </style>
+</head>
+<body>
+  {brand}
+  <h1>webserve status</h1>
+  <p class="muted">Up {uptime}s &middot; {total} request(s) &middot; <a href="{prefix}/stats">JSON</a> &middot; <a href="{prefix}/manifest">Manifest</a> &middot; <a href="{prefix}/broken-links">Broken links</a></p>
+  <h2>Options</h2>
+  <table>{options}</table>
+  <h2>Live-reload clients ({client_count})</h2>
+  {clients}
+  {tls}
+  <h2>Recent requests</h2>
+  <table><tr><th>Time (UTC)</th><th>Method</th><th>Path</th><th>Status</th><th>Latency</th></tr>{requests}</table>
+  <h2>Broken links ({broken_count})</h2>
+  {broken}
+  <h2>Recent file changes</h2>
+  <table><tr><th>Time (UTC)</th><th>Kind</th><th>Paths</th></tr>{changes}</table>
+  {footer}
+</body>
+</html>"#,
+        page_title = html_escape(&settings.branding.page_title("webserve status")),
+        brand_style = BRAND_STYLE,
+        brand = settings.branding.banner_html(),
+        footer = settings.branding.footer_html(),
+        uptime = stats.uptime().as_secs(),
+        total = stats.total_requests(),
+        prefix = format!("{}{}", data.mount.base(), ADMIN_PREFIX),
+        options = options,
+        client_count = clients.len(),
+        clients = clients_html,
+        tls = tls_html,
+        requests = requests,
+        broken_count = broken.len(),
+        broken = broken_html,
+        changes = changes,
+    );
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header(("Cache-Control", "no-store"))
+        .body(body)
+}
+
+/// `HH:MM:SS` (UTC) of a Unix timestamp.
+fn clock(secs: u64) -> String {
+    let t = secs % 86400;
+    format!("{:02}:{:02}:{:02}", t / 3600, (t % 3600) / 60, t % 60)
+}
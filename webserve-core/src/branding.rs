@@ -0,0 +1,156 @@
+//! Custom branding of generated pages (`--brand-title`, `--brand-logo`, `--brand-footer`).
+//!
+//! The logo and title head directory listings, the status dashboard and HTML error pages,
+//! the title is appended to their `<title>`, and the footer text closes them. Error pages
+//! are only generated when branding is configured and the client accepts HTML; otherwise
+//! errors keep their empty or plain-text bodies.
+
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpRequest, HttpResponse};
+
+use crate::serve::html_escape;
+use crate::ServeOptions;
+
+/// Styles of the branding fragments; colors fall back when a page defines no theme.
+pub const BRAND_STYLE: &str = r#"
+    .brand { display: flex; align-items: center; gap: 0.6rem; margin-bottom: 0.75rem; }
+    .brand-logo { max-height: 2.25rem; max-width: 12rem; }
+    .brand-title { font-size: 1.15rem; font-weight: 600; color: var(--text, #1f2937); }
+    .brand-footer { margin-top: 1.5rem; font-size: 0.8rem; color: var(--text-muted, #6b7280); }
+"#;
+
+/// Logo, title and footer shown on generated pages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Branding {
+    /// Company or product name.
+    pub title: Option<String>,
+    /// Logo URL, e.g. `/assets/logo.svg` from the served directory or an absolute URL.
+    pub logo: Option<String>,
+    /// Footer text, e.g. a copyright or support contact.
+    pub footer: Option<String>,
+}
+
+impl Branding {
+    pub fn from_options(options: &ServeOptions) -> Branding {
+        let non_empty = |s: &Option<String>| s.clone().filter(|s| !s.trim().is_empty());
+        Branding {
+            title: non_empty(&options.brand_title),
+            logo: non_empty(&options.brand_logo),
+            footer: non_empty(&options.brand_footer),
+        }
+    }
+
+    /// True when nothing is configured.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.logo.is_none() && self.footer.is_none()
+    }
+
+    /// `<title>` text: `page` followed by the brand title, if any.
+    pub fn page_title(&self, page: &str) -> String {
+        match self.title {
+            Some(ref brand) => format!("{} · {}", page, brand),
+            None => page.to_string(),
+        }
+    }
+
+    /// Logo and title block for the top of a page; empty without logo and title.
+    pub fn banner_html(&self) -> String {
+        if self.title.is_none() && self.logo.is_none() {
+            return String::new();
+        }
+        let logo = self
+            .logo
+            .as_ref()
+            .map(|src| {
+                format!(
+                    r#"<img class="brand-logo" src="{}" alt="{}">"#,
+                    html_escape(src),
+                    html_escape(self.title.as_deref().unwrap_or(""))
+                )
+            })
+            .unwrap_or_default();
+        let title = self
+            .title
+            .as_ref()
+            .map(|t| format!(r#"<span class="brand-title">{}</span>"#, html_escape(t)))
+            .unwrap_or_default();
+        format!(r#"<div class="brand">{}{}</div>"#, logo, title)
+    }
+
+    /// Footer element; empty without footer text.
+    pub fn footer_html(&self) -> String {
+        self.footer
+            .as_ref()
+            .map(|f| {
+                format!(
+                    r#"<footer class="brand-footer">{}</footer>"#,
+                    html_escape(f)
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Branded HTML page for an error `status`, or `None` when unbranded or the client
+    /// does not accept HTML.
+    pub fn error_page(&self, req: &HttpRequest, status: StatusCode) -> Option<HttpResponse> {
+        if self.is_empty() {
+            return None;
+        }
+        self.error_page_with(req, status, "")
+    }
+
+    /// Error page for `status` with `details` HTML below the heading, branded or not;
+    /// `None` when the client does not accept HTML.
+    pub fn error_page_with(
+        &self,
+        req: &HttpRequest,
+        status: StatusCode,
+        details: &str,
+    ) -> Option<HttpResponse> {
+        let accepts_html = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|accept| accept.contains("text/html"));
+        if !accepts_html {
+            return None;
+        }
+        let heading = format!(
+            "{} {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("Error")
+        );
+        let body = format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{title}</title>
+  <style>
+    body {{ font-family: system-ui, sans-serif; margin: 2rem; color: #1f2937; }}
+    h1 {{ font-size: 1.4rem; }}{style}
+  </style>
+</head>
+<body>
+  {banner}
+  <h1>{heading}</h1>
+  {details}
+  {footer}
+</body>
+</html>"#,
+            title = html_escape(&self.page_title(&heading)),
+            style = BRAND_STYLE,
+            banner = self.banner_html(),
+            heading = html_escape(&heading),
+            details = details,
+            footer = self.footer_html(),
+        );
+        Some(
+            HttpResponse::build(status)
+                .content_type("text/html; charset=utf-8")
+                .insert_header((header::CACHE_CONTROL, "no-store"))
+                .body(body),
+        )
+    }
+}
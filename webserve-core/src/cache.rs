@@ -0,0 +1,134 @@
+//! `.webserve-cache`: the one directory webserve writes generated data to, so it is easy to
+//! find, cap and delete.
+//!
+//! `--cache-dir` moves the root (default `.webserve-cache` in the working directory). Each
+//! cache takes a subdirectory, e.g. `compress/` for `--compress-cache` given without a path.
+//! `--cache-size` caps the whole root in MiB: at startup the least recently written files
+//! are deleted until it fits, on top of each cache's own limit. `webserve clean` empties the
+//! root, or trims it with `--max-size`.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::info;
+
+use crate::listing_lang::Locale;
+use crate::walk::{walk, WalkLimits};
+use crate::ServeOptions;
+
+/// Default `--cache-dir`, relative to the working directory.
+pub const CACHE_DIR: &str = ".webserve-cache";
+
+/// Default `--cache-size`, in MiB.
+pub const DEFAULT_CACHE_MIB: u64 = 1024;
+
+/// Subdirectory of the root used by `--compress-cache` without a path.
+pub const COMPRESS_SUBDIR: &str = "compress";
+
+/// Files and bytes, of a whole cache or of what was deleted from it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl CacheUsage {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} file(s), {}",
+            self.files,
+            Locale::En.format_size(self.bytes)
+        )
+    }
+}
+
+/// Root of the generated caches: `--cache-dir`, else `.webserve-cache`.
+pub fn cache_root(options: &ServeOptions) -> PathBuf {
+    options
+        .cache_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(CACHE_DIR))
+}
+
+/// Directory of `--compress-cache`: the given path, else `compress/` under the root.
+pub fn compress_cache_dir(options: &ServeOptions) -> Option<PathBuf> {
+    match options.compress_cache {
+        Some(Some(ref dir)) => Some(dir.clone()),
+        Some(None) => Some(cache_root(options).join(COMPRESS_SUBDIR)),
+        None => None,
+    }
+}
+
+/// Files under `root`, least recently written first, with their sizes.
+fn files(root: &Path) -> Vec<(Option<SystemTime>, PathBuf, u64)> {
+    let mut found: Vec<_> = walk(root, WalkLimits::default())
+        .files
+        .into_iter()
+        .filter_map(|path| {
+            let meta = std::fs::symlink_metadata(&path).ok()?;
+            Some((meta.modified().ok(), path, meta.len()))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// What `root` holds; empty when it does not exist.
+pub fn usage(root: &Path) -> CacheUsage {
+    files(root)
+        .iter()
+        .fold(CacheUsage::default(), |usage, (_, _, size)| CacheUsage {
+            files: usage.files + 1,
+            bytes: usage.bytes + size,
+        })
+}
+
+/// Deletes the least recently written files under `root` until at most `max_bytes` remain
+/// (everything with `None`), then prunes emptied directories. With `dry_run` nothing is
+/// deleted. Returns what was (or would be) deleted.
+pub fn trim(root: &Path, max_bytes: Option<u64>, dry_run: bool) -> io::Result<CacheUsage> {
+    let found = files(root);
+    let mut left: u64 = found.iter().map(|(_, _, size)| size).sum();
+    let mut removed = CacheUsage::default();
+    for (_, path, size) in found {
+        if max_bytes.is_some_and(|max| left <= max) {
+            break;
+        }
+        if !dry_run {
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        left -= size;
+        removed.files += 1;
+        removed.bytes += size;
+    }
+    if !dry_run {
+        let mut dirs = walk(root, WalkLimits::default()).dirs;
+        // Deepest first; the root itself stays
+        for dir in dirs.drain(1..).rev() {
+            let _ = std::fs::remove_dir(dir);
+        }
+    }
+    Ok(removed)
+}
+
+/// Startup check: trims the root to `--cache-size` when it grew past it.
+pub fn enforce_cache_size(options: &ServeOptions) -> Result<(), String> {
+    let root = cache_root(options);
+    let max = options.cache_size.saturating_mul(1024 * 1024);
+    if usage(&root).bytes <= max {
+        return Ok(());
+    }
+    let removed = trim(&root, Some(max), false)
+        .map_err(|e| format!("--cache-size: trimming {}: {}", root.display(), e))?;
+    info!(
+        "Cache {}: over --cache-size {} MiB, deleted {}",
+        root.display(),
+        options.cache_size,
+        removed.describe()
+    );
+    Ok(())
+}
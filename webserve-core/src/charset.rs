@@ -0,0 +1,135 @@
+//! `--charset` / `--charset-ext`: the `charset` parameter of text responses.
+//!
+//! Files are sent as they are on disk, so a page saved as Shift_JIS or windows-1252 shows as
+//! mojibake when the `Content-Type` claims UTF-8 (or says nothing and the browser guesses).
+//! `--charset` names the encoding of text files (`text/*` and JavaScript), `utf-8` by
+//! default and `off` to send no parameter; `--charset-ext EXT=NAME` sets it per extension:
+//!
+//! ```text
+//! --charset utf-8 --charset-ext txt=shift_jis --charset-ext csv=windows-1252
+//! ```
+//!
+//! Pages webserve generates itself (listings, Markdown, errors) are always UTF-8.
+
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
+use actix_web::mime::{self, Mime};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::ServeOptions;
+
+/// Default `--charset`.
+pub const DEFAULT_CHARSET: &str = "utf-8";
+
+/// Charset announced for text files; `None` sends no `charset` parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Charsets {
+    default: Option<String>,
+    by_ext: BTreeMap<String, Option<String>>,
+}
+
+impl Default for Charsets {
+    fn default() -> Self {
+        Charsets {
+            default: Some(DEFAULT_CHARSET.to_string()),
+            by_ext: BTreeMap::new(),
+        }
+    }
+}
+
+impl Charsets {
+    /// Parses a charset name; `off` (or `none`) means no parameter.
+    pub fn parse_name(name: &str) -> Result<Option<String>, String> {
+        let name = name.trim().to_ascii_lowercase();
+        if name == "off" || name == "none" {
+            return Ok(None);
+        }
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.:+".contains(c));
+        if !valid {
+            return Err(format!("--charset {}: not a charset name", name));
+        }
+        Ok(Some(name))
+    }
+
+    /// Parses `EXT=NAME`, e.g. `txt=shift_jis`.
+    pub fn parse_ext(spec: &str) -> Result<(String, Option<String>), String> {
+        let (ext, name) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("--charset-ext {}: expected EXT=NAME", spec))?;
+        let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+        if ext.is_empty() || ext.contains('/') {
+            return Err(format!(
+                "--charset-ext {}: expected a file extension before =",
+                spec
+            ));
+        }
+        Ok((ext, Charsets::parse_name(name)?))
+    }
+
+    pub fn from_options(options: &ServeOptions) -> Result<Charsets, String> {
+        Ok(Charsets {
+            default: Charsets::parse_name(&options.charset)?,
+            by_ext: options
+                .charset_ext
+                .iter()
+                .map(|spec| Charsets::parse_ext(spec))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Charset of the file at `path`, if any.
+    pub fn for_path(&self, path: &Path) -> Option<&str> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.and_then(|ext| self.by_ext.get(&ext)) {
+            Some(name) => name.as_deref(),
+            None => self.default.as_deref(),
+        }
+    }
+
+    /// Rewrites the `charset` parameter of a text `Content-Type` in `headers` for the file
+    /// at `path`; other types are left alone.
+    pub fn apply(&self, path: &Path, headers: &mut HeaderMap) {
+        let Some(mime) = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<Mime>().ok())
+        else {
+            return;
+        };
+        if !is_text(&mime) {
+            return;
+        }
+        let mut value = mime.essence_str().to_string();
+        for (name, param) in mime.params().filter(|(name, _)| *name != mime::CHARSET) {
+            value.push_str(&format!("; {}={}", name, param));
+        }
+        if let Some(charset) = self.for_path(path) {
+            value.push_str(&format!("; charset={}", charset));
+        }
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(header::CONTENT_TYPE, value);
+        }
+    }
+
+    /// `--charset` and the per-extension entries, for logs and the dashboard.
+    pub fn describe(&self) -> String {
+        let name = |n: &Option<String>| n.clone().unwrap_or_else(|| "off".to_string());
+        let mut out = name(&self.default);
+        for (ext, charset) in &self.by_ext {
+            out.push_str(&format!(", {}={}", ext, name(charset)));
+        }
+        out
+    }
+}
+
+/// Whether `mime` is decoded with a charset: `text/*` and JavaScript.
+fn is_text(mime: &Mime) -> bool {
+    mime.type_() == mime::TEXT
+        || (mime.type_() == mime::APPLICATION && mime.subtype() == mime::JAVASCRIPT)
+}
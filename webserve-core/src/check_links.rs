@@ -0,0 +1,371 @@
+//! `webserve check-links [dir|url]`: finds broken internal links before users do.
+//!
+//! Given a directory, every `.html` page under it (within `--max-depth` / `--max-entries`)
+//! is read and each internal `href` / `src` is resolved the way the server would resolve
+//! it: a file, or a directory (which gets its `index.html` or a listing). Given an
+//! `http(s)://` URL, pages are crawled from there and every internal link is requested,
+//! following redirects. External links, `mailto:` and friends are skipped.
+//!
+//! Exit status: `0` when every link resolves, `1` when some are broken, `2` when the
+//! check could not run (missing directory, unreachable start page).
+
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+#[cfg(feature = "crawl")]
+use std::time::Duration;
+
+use crate::path::{join_serve_path, normalize_url_path, percent_decode_path, url_path_under};
+use crate::walk::{walk, WalkLimits};
+
+/// Every link resolved.
+pub const EXIT_OK: i32 = 0;
+/// At least one internal link is broken.
+pub const EXIT_BROKEN: i32 = 1;
+/// The check could not run.
+pub const EXIT_ERROR: i32 = 2;
+
+/// Pages larger than this are checked for existence but not parsed for links.
+const MAX_PAGE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Redirects followed per link before it counts as broken.
+#[cfg(feature = "crawl")]
+const MAX_REDIRECTS: usize = 10;
+
+/// An internal link that does not resolve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLink {
+    /// URL path of the page containing the link.
+    pub page: String,
+    /// The link as written in the page.
+    pub href: String,
+    /// URL path it resolves to.
+    pub target: String,
+    /// `not found`, an HTTP status such as `404 Not Found`, or a request error.
+    pub problem: String,
+}
+
+/// Outcome of a check.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkReport {
+    /// Pages parsed for links.
+    pub pages: usize,
+    /// Internal links checked, counting repeats.
+    pub links: usize,
+    pub broken: Vec<DeadLink>,
+    /// True when `--max-pages` left pages unparsed.
+    pub truncated: bool,
+}
+
+impl LinkReport {
+    pub fn exit_code(&self) -> i32 {
+        if self.broken.is_empty() {
+            EXIT_OK
+        } else {
+            EXIT_BROKEN
+        }
+    }
+}
+
+/// `href` and `src` attribute values in `html`, in order.
+pub fn extract_links(html: &str) -> Vec<String> {
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let attr = ATTR.get_or_init(|| {
+        Regex::new(r#"(?i)\s(?:href|src)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+            .expect("valid link regex")
+    });
+    attr.captures_iter(html)
+        .filter_map(|c| c.get(1).or(c.get(2)).or(c.get(3)))
+        .map(|m| m.as_str().replace("&amp;", "&"))
+        .collect()
+}
+
+/// URL path `href` points to from the page at `page`; `None` for links this check skips:
+/// other origins, `mailto:` and other schemes, and bare `#fragment`s.
+///
+/// `origin` (`http://host:port`) makes absolute links to the same site internal.
+pub fn resolve_link(page: &str, href: &str, origin: Option<&str>) -> Option<String> {
+    let href = href.trim();
+    let href = href.split('#').next().unwrap_or("");
+    let href = href.split('?').next().unwrap_or("");
+    if href.is_empty() {
+        return None;
+    }
+    let href = match origin.and_then(|o| href.strip_prefix(o)) {
+        Some("") => "/",
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => href,
+    };
+    if href.starts_with("//") || has_scheme(href) {
+        return None;
+    }
+    let joined = if href.starts_with('/') {
+        href.to_string()
+    } else {
+        let base = &page[..page.rfind('/').map_or(0, |i| i + 1)];
+        format!("{}{}", base, href)
+    };
+    let trailing = joined.ends_with('/');
+    let mut segments: Vec<&str> = Vec::new();
+    for seg in joined.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            seg => segments.push(seg),
+        }
+    }
+    let mut path = format!("/{}", segments.join("/"));
+    if trailing && path != "/" {
+        path.push('/');
+    }
+    Some(path)
+}
+
+/// True for `mailto:`, `https:`, `javascript:` and other `scheme:` prefixes.
+fn has_scheme(href: &str) -> bool {
+    let Some((scheme, _)) = href.split_once(':') else {
+        return false;
+    };
+    !scheme.is_empty()
+        && !scheme.contains('/')
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// What looking up one URL path found.
+struct Fetched {
+    problem: Option<String>,
+    /// Body when it is an HTML page.
+    html: Option<String>,
+    /// Where the body came from: the file behind a directory (`/docs/` -> `/docs/index.html`)
+    /// or the end of a redirect chain. Links in it resolve against this path.
+    page: Option<String>,
+}
+
+/// Where pages come from.
+enum Site {
+    Dir(PathBuf),
+    #[cfg(feature = "crawl")]
+    Http {
+        origin: String,
+        client: awc::Client,
+    },
+}
+
+fn is_html_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"))
+}
+
+impl Site {
+    fn origin(&self) -> Option<&str> {
+        match self {
+            Site::Dir(_) => None,
+            #[cfg(feature = "crawl")]
+            Site::Http { origin, .. } => Some(origin),
+        }
+    }
+
+    async fn fetch(&self, url_path: &str) -> Fetched {
+        match self {
+            Site::Dir(root) => fetch_file(root, url_path),
+            #[cfg(feature = "crawl")]
+            Site::Http { origin, client } => fetch_url(client, origin, url_path).await,
+        }
+    }
+}
+
+/// Resolves `url_path` under `root` like the file handler: a file, or a directory.
+fn fetch_file(root: &Path, url_path: &str) -> Fetched {
+    let file = percent_decode_path(url_path)
+        .and_then(|p| normalize_url_path(&p))
+        .and_then(|p| join_serve_path(root, &p));
+    let Some(mut file) = file.filter(|f| f.exists()) else {
+        return Fetched {
+            problem: Some("not found".to_string()),
+            html: None,
+            page: None,
+        };
+    };
+    if file.is_dir() {
+        file = file.join("index.html");
+    }
+    let html = is_html_file(&file)
+        .then(|| std::fs::read(&file).ok())
+        .flatten()
+        .filter(|bytes| bytes.len() <= MAX_PAGE_BYTES)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+    Fetched {
+        problem: None,
+        html,
+        page: url_path_under(root, &file),
+    }
+}
+
+/// Requests `url_path`, following same-site redirects so relative links resolve against
+/// the page that was finally served.
+#[cfg(feature = "crawl")]
+async fn fetch_url(client: &awc::Client, origin: &str, url_path: &str) -> Fetched {
+    let failed = |problem: String| Fetched {
+        problem: Some(problem),
+        html: None,
+        page: None,
+    };
+    let mut path = url_path.to_string();
+    let mut redirects = 0;
+    let mut res = loop {
+        let res = match client.get(format!("{}{}", origin, path)).send().await {
+            Ok(res) => res,
+            Err(e) => return failed(e.to_string()),
+        };
+        if !res.status().is_redirection() {
+            break res;
+        }
+        let location = res
+            .headers()
+            .get(awc::http::header::LOCATION)
+            .and_then(|v| v.to_str().ok());
+        let Some(location) = location else {
+            return failed(format!("{} without Location", res.status()));
+        };
+        let Some(next) = resolve_link(&path, location, Some(origin)) else {
+            // Redirected off-site: the link works as far as this site is concerned
+            return Fetched {
+                problem: None,
+                html: None,
+                page: None,
+            };
+        };
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            return failed("too many redirects".to_string());
+        }
+        path = next;
+    };
+    let status = res.status();
+    if status.is_client_error() || status.is_server_error() {
+        return failed(status.to_string());
+    }
+    let is_html = res
+        .headers()
+        .get(awc::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/html"));
+    let html = if is_html {
+        res.body()
+            .limit(MAX_PAGE_BYTES)
+            .await
+            .ok()
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    } else {
+        None
+    };
+    Fetched {
+        problem: None,
+        html,
+        page: Some(path),
+    }
+}
+
+/// Checks `site` from the already fetched `start` pages, parsing at most `max_pages`.
+async fn crawl(site: &Site, start: Vec<(String, Fetched)>, max_pages: usize) -> LinkReport {
+    let mut report = LinkReport::default();
+    let mut seen: HashMap<String, Option<String>> = HashMap::new();
+    let mut parsed: HashSet<String> = HashSet::new();
+    let mut queue: VecDeque<(String, String)> = VecDeque::new();
+    for (page, fetched) in start {
+        let key = fetched.page.unwrap_or_else(|| page.clone());
+        if let Some(html) = fetched.html.filter(|_| parsed.insert(key.clone())) {
+            queue.push_back((key, html));
+        }
+        seen.insert(page, fetched.problem);
+    }
+    while let Some((page, html)) = queue.pop_front() {
+        if report.pages == max_pages {
+            report.truncated = true;
+            break;
+        }
+        report.pages += 1;
+        for href in extract_links(&html) {
+            let Some(target) = resolve_link(&page, &href, site.origin()) else {
+                continue;
+            };
+            report.links += 1;
+            if !seen.contains_key(&target) {
+                let fetched = site.fetch(&target).await;
+                let key = fetched.page.unwrap_or_else(|| target.clone());
+                if let Some(html) = fetched.html.filter(|_| parsed.insert(key.clone())) {
+                    queue.push_back((key, html));
+                }
+                seen.insert(target.clone(), fetched.problem);
+            }
+            if let Some(Some(problem)) = seen.get(&target) {
+                report.broken.push(DeadLink {
+                    page: page.clone(),
+                    href,
+                    target,
+                    problem: problem.clone(),
+                });
+            }
+        }
+    }
+    report
+}
+
+/// Checks every HTML page under `root` within `limits`.
+pub async fn check_dir(root: &Path, limits: WalkLimits, max_pages: usize) -> LinkReport {
+    let mut pages: Vec<String> = walk(root, limits)
+        .files
+        .iter()
+        .filter(|f| is_html_file(f))
+        .filter_map(|f| url_path_under(root, f))
+        .collect();
+    pages.sort();
+    let start = pages
+        .into_iter()
+        .map(|page| {
+            let fetched = fetch_file(root, &page);
+            (page, fetched)
+        })
+        .collect();
+    crawl(&Site::Dir(root.to_path_buf()), start, max_pages).await
+}
+
+/// Crawls the site at `url` and checks the internal links of every page reached.
+#[cfg(feature = "crawl")]
+pub async fn check_url(url: &str, max_pages: usize) -> Result<LinkReport, String> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| format!("{}: expected an http(s):// URL", url))?;
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    if host.is_empty() {
+        return Err(format!("{}: missing host", url));
+    }
+    let origin = format!("{}://{}", scheme, host);
+    let start = resolve_link("/", if path.is_empty() { "/" } else { path }, None)
+        .unwrap_or_else(|| "/".to_string());
+    let client = awc::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .disable_redirects()
+        .finish();
+    let site = Site::Http { origin, client };
+    let first = site.fetch(&start).await;
+    if let Some(ref problem) = first.problem {
+        return Err(format!("{}: {}", url, problem));
+    }
+    Ok(crawl(&site, vec![(start, first)], max_pages).await)
+}
+
+/// Crawling a site needs a build with the `crawl` feature.
+#[cfg(not(feature = "crawl"))]
+pub async fn check_url(url: &str, _max_pages: usize) -> Result<LinkReport, String> {
+    Err(format!(
+        "{}: checking a URL needs a build with `--features crawl`",
+        url
+    ))
+}
@@ -0,0 +1,387 @@
+//! `--compress`: gzip / Brotli response compression that backs off under CPU load.
+//!
+//! Text-like bodies (HTML, CSS, JS, JSON, SVG, ...) between [`COMPRESS_MIN_BYTES`] and
+//! [`COMPRESS_MAX_BYTES`] are compressed for clients that accept it. The level follows the
+//! machine's CPU usage, sampled once a second from `/proc/stat` (Linux): the normal level
+//! below half of `--compress-cpu-limit`, the fastest level up to the limit, and no
+//! compression above it, so a busy small VPS spends its cycles on serving rather than
+//! squeezing bytes. Where the load cannot be sampled the normal level is used.
+
+use actix_web::body::{self, EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{error, web, Error, HttpRequest, HttpResponse};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Once};
+use tracing::{info, warn};
+
+use crate::compress_cache::CompressCache;
+use crate::AppState;
+
+/// Smaller bodies are sent as-is; the headers would eat most of the saving.
+pub const COMPRESS_MIN_BYTES: u64 = 1024;
+
+/// Larger bodies are sent as-is rather than buffered whole for compression.
+pub const COMPRESS_MAX_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Default `--compress-cpu-limit`, in percent.
+pub const DEFAULT_COMPRESS_CPU_LIMIT: u8 = 80;
+
+/// How often the CPU sampler reads `/proc/stat`.
+const CPU_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Stored in place of a percentage until the first sample is in.
+const UNKNOWN_LOAD: u8 = u8::MAX;
+
+/// Compression effort picked for the current CPU load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressLevel {
+    /// gzip 6 / Brotli 5.
+    Normal,
+    /// gzip 1 / Brotli 1.
+    Fast,
+    /// Send bodies uncompressed.
+    Off,
+}
+
+impl CompressLevel {
+    /// Level for a CPU `load` (percent, `None` when unknown) under `--compress-cpu-limit`.
+    pub fn for_load(load: Option<u8>, cpu_limit: u8) -> CompressLevel {
+        match load {
+            Some(load) if load >= cpu_limit => CompressLevel::Off,
+            Some(load) if load >= cpu_limit / 2 => CompressLevel::Fast,
+            _ => CompressLevel::Normal,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CompressLevel::Normal => "normal",
+            CompressLevel::Fast => "fast",
+            CompressLevel::Off => "off",
+        }
+    }
+}
+
+/// Content codings webserve produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    /// Best coding allowed by an `Accept-Encoding` value (Brotli first), if any.
+    pub fn negotiate(accept: &str) -> Option<Encoding> {
+        let mut gzip = false;
+        let mut brotli = false;
+        for item in accept.split(',') {
+            let mut parts = item.split(';');
+            let name = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+            let refused = parts.any(|p| {
+                p.trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            if refused {
+                continue;
+            }
+            match name.as_str() {
+                "br" => brotli = true,
+                "gzip" | "x-gzip" => gzip = true,
+                "*" => {
+                    brotli = true;
+                    gzip = true;
+                }
+                _ => {}
+            }
+        }
+        match (brotli, gzip) {
+            (true, _) => Some(Encoding::Brotli),
+            (false, true) => Some(Encoding::Gzip),
+            _ => None,
+        }
+    }
+
+    /// `Content-Encoding` token.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// Compresses `data`; [`CompressLevel::Off`] returns it unchanged.
+    pub fn encode(self, data: &[u8], level: CompressLevel) -> io::Result<Vec<u8>> {
+        let out = Vec::with_capacity(data.len() / 3);
+        Ok(match (self, level) {
+            (_, CompressLevel::Off) => data.to_vec(),
+            (Encoding::Gzip, level) => {
+                let level = match level {
+                    CompressLevel::Fast => flate2::Compression::fast(),
+                    _ => flate2::Compression::new(6),
+                };
+                let mut encoder = flate2::write::GzEncoder::new(out, level);
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            (Encoding::Brotli, level) => {
+                let quality = if level == CompressLevel::Fast { 1 } else { 5 };
+                let mut encoder = brotli::CompressorWriter::new(out, 4096, quality, 22);
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                encoder.into_inner()
+            }
+        })
+    }
+}
+
+/// CPU load tracking for `--compress`; the sampler thread starts on first use.
+pub struct AdaptiveCompression {
+    load: Arc<AtomicU8>,
+    sampler: Once,
+    /// Last level handed out, so changes are logged once.
+    level: AtomicU8,
+}
+
+impl Default for AdaptiveCompression {
+    fn default() -> Self {
+        AdaptiveCompression {
+            load: Arc::new(AtomicU8::new(UNKNOWN_LOAD)),
+            sampler: Once::new(),
+            level: AtomicU8::new(CompressLevel::Normal as u8),
+        }
+    }
+}
+
+impl AdaptiveCompression {
+    /// Uses a fixed CPU load instead of sampling the machine (for tests).
+    pub fn fixed(load: u8) -> AdaptiveCompression {
+        let compression = AdaptiveCompression::default();
+        compression.sampler.call_once(|| {});
+        compression.load.store(load.min(100), Ordering::Relaxed);
+        compression
+    }
+
+    /// Latest CPU usage in percent; `None` before the first sample or off Linux.
+    pub fn cpu_load(&self) -> Option<u8> {
+        self.sampler.call_once(|| start_sampler(self.load.clone()));
+        match self.load.load(Ordering::Relaxed) {
+            UNKNOWN_LOAD => None,
+            load => Some(load),
+        }
+    }
+
+    /// Level to compress with right now.
+    pub fn level(&self, cpu_limit: u8) -> CompressLevel {
+        let load = self.cpu_load();
+        let level = CompressLevel::for_load(load, cpu_limit);
+        if self.level.swap(level as u8, Ordering::Relaxed) != level as u8 {
+            info!(
+                "compression: CPU at {}%, level now {}",
+                load.unwrap_or(0),
+                level.name()
+            );
+        }
+        level
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn start_sampler(load: Arc<AtomicU8>) {
+    /// Busy and total jiffies from the aggregate `cpu` line of `/proc/stat`.
+    fn read_times() -> Option<(u64, u64)> {
+        let stat = std::fs::read_to_string("/proc/stat").ok()?;
+        let line = stat.lines().find(|l| l.starts_with("cpu "))?;
+        let times: Vec<u64> = line
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        let total: u64 = times.iter().take(8).sum();
+        let idle = times.get(3)? + times.get(4).copied().unwrap_or(0);
+        Some((total - idle, total))
+    }
+
+    let spawned = std::thread::Builder::new()
+        .name("webserve-cpu".into())
+        .spawn(move || {
+            let Some(mut last) = read_times() else { return };
+            loop {
+                std::thread::sleep(CPU_SAMPLE_INTERVAL);
+                let Some(now) = read_times() else { return };
+                let busy = now.0.saturating_sub(last.0);
+                let total = now.1.saturating_sub(last.1);
+                if let Some(percent) = (busy * 100).checked_div(total) {
+                    load.store(percent.min(100) as u8, Ordering::Relaxed);
+                }
+                last = now;
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("--compress: cannot sample CPU load: {}", e);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn start_sampler(_load: Arc<AtomicU8>) {}
+
+/// Whether a `Content-Type` is worth compressing (text, JSON, XML, SVG, JS, wasm).
+pub fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/javascript"
+                | "application/json"
+                | "application/xml"
+                | "application/wasm"
+                | "image/svg+xml"
+        )
+}
+
+fn worth_compressing<B: MessageBody>(res: &ServiceResponse<B>) -> bool {
+    let headers = res.headers();
+    let size_ok = match res.response().body().size() {
+        body::BodySize::Sized(n) => (COMPRESS_MIN_BYTES..=COMPRESS_MAX_BYTES).contains(&n),
+        _ => false,
+    };
+    let no_transform = headers
+        .get(header::CACHE_CONTROL)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.to_ascii_lowercase().contains("no-transform"));
+    res.status() == StatusCode::OK
+        && size_ok
+        && !no_transform
+        && !headers.contains_key(header::CONTENT_ENCODING)
+        && headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(is_compressible)
+}
+
+/// `--compress-cache` validator of a file response: only `NamedFile` responses carry
+/// `Last-Modified`, so generated bodies (listings, injected HTML) are never cached.
+fn cache_validator<B: MessageBody>(res: &ServiceResponse<B>) -> Option<String> {
+    let header = |name| {
+        res.headers()
+            .get(name)
+            .and_then(|v: &HeaderValue| v.to_str().ok())
+    };
+    let modified = header(header::LAST_MODIFIED)?;
+    let size = match res.response().body().size() {
+        body::BodySize::Sized(n) => n,
+        _ => return None,
+    };
+    Some(format!(
+        "{}|{}|{}",
+        modified,
+        header(header::ETAG).unwrap_or(""),
+        size
+    ))
+}
+
+/// Marks `res` as encoded with `encoding` and sets `body` on it.
+fn encoded_response<B>(
+    req: HttpRequest,
+    mut res: HttpResponse<()>,
+    encoding: Encoding,
+    body: Vec<u8>,
+) -> ServiceResponse<EitherBody<B>> {
+    let headers = res.headers_mut();
+    headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+    );
+    // Same resource, different bytes: the validator can only be weak now.
+    let weak = headers
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|tag| !tag.starts_with("W/"))
+        .and_then(|tag| HeaderValue::from_str(&format!("W/{}", tag)).ok());
+    if let Some(weak) = weak {
+        headers.insert(header::ETAG, weak);
+    }
+    let res = res.set_body(body).map_into_boxed_body();
+    ServiceResponse::new(req, res).map_into_right_body()
+}
+
+/// Middleware for `--compress`: encodes text responses at a level chosen by CPU load.
+pub async fn compress<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let data = req.app_data::<web::Data<AppState>>().cloned();
+    let cpu_limit = data
+        .as_ref()
+        .map(|data| data.settings.load())
+        .filter(|settings| settings.compress)
+        .map(|settings| settings.compress_cpu_limit);
+    let (Some(data), Some(cpu_limit), true) = (data, cpu_limit, req.method() == Method::GET) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let encoding = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .and_then(Encoding::negotiate);
+    let path = req.path().to_string();
+
+    let mut res = next.call(req).await?;
+    if !worth_compressing(&res) {
+        return Ok(res.map_into_left_body());
+    }
+    res.headers_mut()
+        .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    let Some(encoding) = encoding else {
+        return Ok(res.map_into_left_body());
+    };
+    let cached = data
+        .compress_cache
+        .clone()
+        .zip(cache_validator(&res))
+        .map(|(cache, validator)| (cache, CompressCache::key(&path, &validator, encoding)));
+    if let Some((cache, key)) = cached.clone() {
+        // A hit costs no CPU, so it is served even when the load turns compression off.
+        if let Some(body) = web::block(move || cache.get(&key)).await? {
+            let (req, res) = res.into_parts();
+            let (res, _) = res.into_parts();
+            return Ok(encoded_response(req, res, encoding, body));
+        }
+    }
+    let level = data.compression.level(cpu_limit);
+    if level == CompressLevel::Off {
+        return Ok(res.map_into_left_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(|e| error::ErrorInternalServerError(e.into()))?;
+    let encoded = web::block(move || {
+        let encoded = encoding.encode(&bytes, level)?;
+        // Only full-effort output is kept, so a busy spell doesn't fill the cache with
+        // fast-level bodies.
+        if let (Some((cache, key)), CompressLevel::Normal) = (cached, level) {
+            if let Err(e) = cache.put(&key, &encoded) {
+                warn!("compression cache: cannot store {}: {}", key, e);
+            }
+        }
+        Ok::<_, io::Error>(encoded)
+    })
+    .await?
+    .map_err(error::ErrorInternalServerError)?;
+    Ok(encoded_response(req, res, encoding, encoded))
+}
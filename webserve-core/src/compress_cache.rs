@@ -0,0 +1,172 @@
+//! `--compress-cache <dir>`: compressed file bodies kept on disk between requests.
+//!
+//! Entries are keyed by request path, the file's validators (`Last-Modified`, `ETag`, size)
+//! and the content coding, so an edited file simply misses and its old entry ages out. The
+//! directory is capped at `--compress-cache-size` MiB; least recently used entries are
+//! deleted first. Existing entries are picked up again after a restart.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::debug;
+
+use crate::compress::Encoding;
+use crate::conditional::hex;
+
+/// Default `--compress-cache-size`, in MiB.
+pub const DEFAULT_COMPRESS_CACHE_MIB: u64 = 256;
+
+struct Entry {
+    size: u64,
+    /// Tick of the last hit or write; the smallest is evicted first.
+    used: u64,
+}
+
+#[derive(Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    total: u64,
+    clock: u64,
+}
+
+impl Index {
+    fn touch(&mut self, key: &str) -> bool {
+        self.clock += 1;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.used = self.clock;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.total -= entry.size;
+        }
+    }
+}
+
+/// Size-capped LRU store of compressed bodies in one directory.
+pub struct CompressCache {
+    dir: PathBuf,
+    capacity: u64,
+    index: Mutex<Index>,
+}
+
+impl CompressCache {
+    /// Opens (creating if needed) `dir`, indexing entries left by a previous run.
+    pub fn open(dir: &Path, capacity: u64) -> io::Result<CompressCache> {
+        fs::create_dir_all(dir)?;
+        let mut found = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !(name.ends_with(".gz") || name.ends_with(".br")) {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            found.push((meta.modified().ok(), name, meta.len()));
+        }
+        found.sort();
+        let cache = CompressCache {
+            dir: dir.to_path_buf(),
+            capacity,
+            index: Mutex::default(),
+        };
+        if let Ok(mut index) = cache.index.lock() {
+            for (_, key, size) in found {
+                index.clock += 1;
+                index.total += size;
+                let used = index.clock;
+                index.entries.insert(key, Entry { size, used });
+            }
+            cache.evict(&mut index);
+        }
+        Ok(cache)
+    }
+
+    /// Cache key (also the file name) for `path` served with `validator` in `encoding`.
+    pub fn key(path: &str, validator: &str, encoding: Encoding) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(path.as_bytes());
+        hasher.update([0]);
+        hasher.update(validator.as_bytes());
+        let ext = match encoding {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+        };
+        format!("{}.{}", hex(&hasher.finalize()), ext)
+    }
+
+    /// Stored body for `key`, if any.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut index = self.index.lock().ok()?;
+        if !index.touch(key) {
+            return None;
+        }
+        match fs::read(self.dir.join(key)) {
+            Ok(body) => Some(body),
+            Err(e) => {
+                debug!("compression cache: dropping {}: {}", key, e);
+                index.remove(key);
+                None
+            }
+        }
+    }
+
+    /// Stores `body` under `key`, evicting least recently used entries to stay in budget.
+    pub fn put(&self, key: &str, body: &[u8]) -> io::Result<()> {
+        let size = body.len() as u64;
+        if size > self.capacity {
+            return Ok(());
+        }
+        let tmp = self.dir.join(format!("{}.tmp", key));
+        fs::write(&tmp, body)?;
+        fs::rename(&tmp, self.dir.join(key))?;
+        let Ok(mut index) = self.index.lock() else {
+            return Ok(());
+        };
+        index.remove(key);
+        index.clock += 1;
+        let used = index.clock;
+        index.entries.insert(key.to_string(), Entry { size, used });
+        index.total += size;
+        self.evict(&mut index);
+        Ok(())
+    }
+
+    fn evict(&self, index: &mut Index) {
+        while index.total > self.capacity {
+            let Some(oldest) = index
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            let _ = fs::remove_file(self.dir.join(&oldest));
+            index.remove(&oldest);
+        }
+    }
+
+    /// Number of stored entries.
+    pub fn len(&self) -> usize {
+        self.index.lock().map(|i| i.entries.len()).unwrap_or(0)
+    }
+
+    /// True when nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes stored.
+    pub fn size(&self) -> u64 {
+        self.index.lock().map(|i| i.total).unwrap_or(0)
+    }
+}
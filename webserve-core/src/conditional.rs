@@ -0,0 +1,274 @@
+//! Validators and conditional GET handling for generated responses.
+//!
+//! `NamedFile` already answers `If-None-Match` / `If-Modified-Since` for plain files;
+//! this covers the bodies webserve builds itself (directory listings, HTML with the
+//! reload script injected) so reload cycles can be answered with `304 Not Modified`.
+//! Injected pages also send `Last-Modified`: the later of the file's mtime and the last
+//! change to the reload script's sequence, so `If-Modified-Since` works for them too.
+//!
+//! `--etag` picks the validator: `mtime` (default) keeps `NamedFile`'s inode/size/mtime tag
+//! and a fast body hash for generated responses, `sha256` hashes content everywhere so tags
+//! survive rsync or checkout mtime churn, and `off` sends no ETag at all.
+//!
+//! `--no-cache` goes the other way: validators are dropped, every response is
+//! `no-store`, and asset URLs in injected HTML get a cache-busting query.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, EntityTag, HeaderValue, IfNoneMatch};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+use bytes::Bytes;
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::AppState;
+
+/// Query parameter appended to asset URLs by [`bust_asset_urls`].
+pub const CACHE_BUST_PARAM: &str = "__wsv";
+
+/// How ETags are derived (`--etag`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EtagMode {
+    /// File metadata for files, a fast body hash for generated responses.
+    #[default]
+    Mtime,
+    /// SHA-256 of the content for files and generated responses alike.
+    Sha256,
+    /// No ETags; files still carry `Last-Modified`.
+    Off,
+}
+
+impl FromStr for EtagMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mtime" => Ok(EtagMode::Mtime),
+            "sha256" => Ok(EtagMode::Sha256),
+            "off" | "none" => Ok(EtagMode::Off),
+            _ => Err(format!(
+                "unknown etag mode {} (expected mtime, sha256 or off)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EtagMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EtagMode::Mtime => "mtime",
+            EtagMode::Sha256 => "sha256",
+            EtagMode::Off => "off",
+        })
+    }
+}
+
+/// Path → (mtime, size, content ETag), so `--etag sha256` rehashes only changed files.
+pub type FileHashCache = Arc<RwLock<HashMap<PathBuf, (SystemTime, u64, EntityTag)>>>;
+
+/// Strong ETag derived from the exact response body.
+pub fn body_etag(body: &[u8]) -> EntityTag {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    EntityTag::new_strong(format!("{:016x}", hasher.finish()))
+}
+
+/// Strong ETag holding the hex SHA-256 of `body`.
+pub fn sha256_etag(body: &[u8]) -> EntityTag {
+    EntityTag::new_strong(hex(&Sha256::digest(body)))
+}
+
+/// SHA-256 ETag of the file at `path`, reusing `cache` while its mtime and size are unchanged.
+pub async fn file_sha256_etag(path: &Path, cache: &FileHashCache) -> std::io::Result<EntityTag> {
+    let owned = path.to_path_buf();
+    let cache = cache.clone();
+    web::block(move || cached_sha256(&owned, &cache))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// Blocking form of [`file_sha256_etag`], for callers already off the async runtime.
+pub fn cached_sha256(path: &Path, cache: &FileHashCache) -> std::io::Result<EntityTag> {
+    let meta = std::fs::metadata(path)?;
+    let modified = meta.modified()?;
+    let len = meta.len();
+    if let Ok(guard) = cache.read() {
+        if let Some((m, l, tag)) = guard.get(path) {
+            if *m == modified && *l == len {
+                return Ok(tag.clone());
+            }
+        }
+    }
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    let tag = EntityTag::new_strong(hex(&hasher.finalize()));
+    if let Ok(mut guard) = cache.write() {
+        guard.insert(path.to_path_buf(), (modified, len, tag.clone()));
+    }
+    Ok(tag)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// ETag for a body webserve generated itself; `None` with [`EtagMode::Off`].
+pub fn generated_etag(body: &[u8], mode: EtagMode) -> Option<EntityTag> {
+    match mode {
+        EtagMode::Mtime => Some(body_etag(body)),
+        EtagMode::Sha256 => Some(sha256_etag(body)),
+        EtagMode::Off => None,
+    }
+}
+
+/// True when the request's `If-None-Match` already covers `etag`.
+pub fn etag_matches(req: &HttpRequest, etag: &EntityTag) -> bool {
+    match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|t| t.weak_eq(etag)),
+        None => false,
+    }
+}
+
+/// Responds with `body` as HTML, or `304` when the client's copy is current.
+///
+/// `no-cache` keeps browsers revalidating on every load so edits still show up.
+pub fn html_with_validator(req: &HttpRequest, body: Bytes) -> HttpResponse {
+    html_response(req, body, EtagMode::Mtime)
+}
+
+/// [`html_with_validator`] with the ETag chosen by `mode`; no `304` when `mode` is off.
+pub fn html_response(req: &HttpRequest, body: Bytes, mode: EtagMode) -> HttpResponse {
+    html_page(req, body, mode, None)
+}
+
+/// [`html_response`] for a page last changed at `modified`, e.g. a file with the reload
+/// script injected: also sends `Last-Modified`, and answers `If-Modified-Since` with `304`
+/// when the request has no `If-None-Match`.
+pub fn html_response_modified(
+    req: &HttpRequest,
+    body: Bytes,
+    mode: EtagMode,
+    modified: SystemTime,
+) -> HttpResponse {
+    html_page(req, body, mode, Some(modified))
+}
+
+fn html_page(
+    req: &HttpRequest,
+    body: Bytes,
+    mode: EtagMode,
+    modified: Option<SystemTime>,
+) -> HttpResponse {
+    let cache_control = header::CacheControl(vec![header::CacheDirective::NoCache]);
+    let etag = generated_etag(&body, mode);
+    // HTTP dates have whole seconds; compare at that precision
+    let modified = modified.map(|m| {
+        let secs = m.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        header::HttpDate::from(UNIX_EPOCH + Duration::from_secs(secs))
+    });
+    let current = match (&etag, req.get_header::<IfNoneMatch>()) {
+        (Some(etag), Some(_)) => etag_matches(req, etag),
+        (_, Some(_)) => false,
+        (_, None) => match (modified, req.get_header::<header::IfModifiedSince>()) {
+            (Some(modified), Some(header::IfModifiedSince(since))) => {
+                SystemTime::from(modified) <= SystemTime::from(since)
+            }
+            _ => false,
+        },
+    };
+    let mut res = if current {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    res.insert_header(cache_control);
+    if let Some(etag) = etag {
+        res.insert_header(header::ETag(etag));
+    }
+    if let Some(modified) = modified {
+        res.insert_header(header::LastModified(modified));
+    }
+    if current {
+        return res.finish();
+    }
+    res.content_type("text/html; charset=utf-8").body(body)
+}
+
+/// Appends `?__wsv=<token>` to local `src`/`href` URLs of scripts, stylesheets and media.
+///
+/// Absolute (`http:`, `//`), `data:` and fragment-only URLs are left alone.
+pub fn bust_asset_urls(html: &str, token: &str) -> String {
+    static ASSET_REF: OnceLock<Regex> = OnceLock::new();
+    let re = ASSET_REF.get_or_init(|| {
+        Regex::new(
+            r#"(?i)(<(?:script|link|img|source|video|audio)\b[^>]*?\b(?:src|href)\s*=\s*")([^"]*)(")"#,
+        )
+        .expect("asset pattern compiles")
+    });
+    re.replace_all(html, |caps: &Captures| {
+        let url = &caps[2];
+        let lower = url.to_ascii_lowercase();
+        let external = url.is_empty()
+            || url.starts_with('#')
+            || url.starts_with("//")
+            || [
+                "http:",
+                "https:",
+                "data:",
+                "blob:",
+                "mailto:",
+                "javascript:",
+            ]
+            .iter()
+            .any(|scheme| lower.starts_with(scheme));
+        if external {
+            return caps[0].to_string();
+        }
+        let (base, fragment) = match url.find('#') {
+            Some(i) => url.split_at(i),
+            None => (url, ""),
+        };
+        let sep = if base.contains('?') { '&' } else { '?' };
+        format!(
+            "{}{}{}{}={}{}{}",
+            &caps[1], base, sep, CACHE_BUST_PARAM, token, fragment, &caps[3]
+        )
+    })
+    .into_owned()
+}
+
+/// Middleware for `--no-cache`: ignores client validators and sends `no-store` without
+/// `ETag` / `Last-Modified`, so nothing is ever answered from a stale copy.
+pub async fn no_store(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let enabled = req
+        .app_data::<web::Data<AppState>>()
+        .is_some_and(|data| data.settings.load().no_cache);
+    if enabled {
+        let headers = req.headers_mut();
+        headers.remove(header::IF_NONE_MATCH);
+        headers.remove(header::IF_MODIFIED_SINCE);
+    }
+    let mut res = next.call(req).await?;
+    if enabled {
+        let headers = res.headers_mut();
+        headers.remove(header::ETAG);
+        headers.remove(header::LAST_MODIFIED);
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+    Ok(res)
+}
@@ -0,0 +1,1257 @@
+//! Layered configuration: `webserve.toml`, then `WEBSERVE_*` environment variables, then
+//! command-line flags, each layer overriding the one before.
+//!
+//! The file is read from `--config <path>` (or `WEBSERVE_CONFIG`), else `webserve.toml` in
+//! the working directory when present. Relative paths in the file resolve against the
+//! file's own directory; relative paths in variables resolve against the working directory.
+//!
+//! `--export-config <path>` goes the other way: the merged options that differ from the
+//! defaults are written out as a config file, for moving long command lines into one.
+
+use actix_web::http::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::level_filters::LevelFilter;
+use tracing::warn;
+
+use crate::fault::{format_duration, parse_duration};
+use crate::path::bind_host;
+use crate::ServeOptions;
+
+/// Config file looked up in the working directory when `--config` is not given.
+pub const DEFAULT_CONFIG_FILE: &str = "webserve.toml";
+
+/// Prefix of the environment variables read as a config layer (`WEBSERVE_PORT`, ...).
+pub const ENV_PREFIX: &str = "WEBSERVE_";
+
+/// `open` key: a switch or the path to open.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum OpenSetting {
+    Flag(bool),
+    Path(String),
+}
+
+/// `compress-cache` key: a switch (`true` = under `cache-dir`) or a directory.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CacheSetting {
+    Flag(bool),
+    Path(PathBuf),
+}
+
+/// `host` key: one address or a list (`host = ["::1", "127.0.0.1"]`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum HostSetting {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HostSetting {
+    /// The addresses in order.
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            HostSetting::One(host) => vec![host],
+            HostSetting::Many(hosts) => hosts,
+        }
+    }
+}
+
+/// Contents of a `webserve.toml`. Every key is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FileConfig {
+    pub port: Option<u16>,
+    /// One address or a list of them.
+    pub host: Option<HostSetting>,
+    pub strict_port: Option<bool>,
+    pub uds: Option<PathBuf>,
+    pub systemd: Option<bool>,
+    pub daemon: Option<bool>,
+    pub pid_file: Option<PathBuf>,
+    pub daemon_log: Option<PathBuf>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub http1_only: Option<bool>,
+    pub redirect_http: Option<u16>,
+    pub dir: Option<PathBuf>,
+    pub file: Option<PathBuf>,
+    /// `--mount` sites by prefix, e.g. `[mounts]` / `"/docs" = "./docs"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mounts: BTreeMap<String, PathBuf>,
+    /// `--vhost` sites by host, e.g. `[vhosts]` / `"docs.localhost" = "./docs"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vhosts: BTreeMap<String, PathBuf>,
+    pub spa: Option<bool>,
+    pub section_index: Option<bool>,
+    pub suggest: Option<bool>,
+    pub watch: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_paths: Vec<PathBuf>,
+    pub exec: Option<String>,
+    /// `true`/`false`, or a path to open (`open = "/docs/"`).
+    pub open: Option<OpenSetting>,
+    pub qr: Option<bool>,
+    pub mdns: Option<String>,
+    pub redirect_dir_slash: Option<bool>,
+    pub strict_http: Option<bool>,
+    pub harden: Option<bool>,
+    pub throttle: Option<u32>,
+    pub latency: Option<String>,
+    pub max_header_bytes: Option<usize>,
+    pub fault_injection: Option<bool>,
+    pub no_cache: Option<bool>,
+    pub compress: Option<bool>,
+    pub compress_cpu_limit: Option<u8>,
+    /// `true`/`false`, or the directory to use.
+    pub compress_cache: Option<CacheSetting>,
+    pub store: Option<String>,
+    pub compress_cache_size: Option<u64>,
+    pub cache_dir: Option<PathBuf>,
+    pub cache_size: Option<u64>,
+    pub etag: Option<String>,
+    pub charset: Option<String>,
+    pub listing_lang: Option<String>,
+    pub render_markdown: Option<bool>,
+    pub highlight: Option<bool>,
+    pub upload: Option<bool>,
+    pub max_upload_size: Option<u64>,
+    pub webdav: Option<bool>,
+    pub open_in_editor: Option<bool>,
+    pub admin_remote: Option<bool>,
+    pub brand_title: Option<String>,
+    pub brand_logo: Option<String>,
+    pub brand_footer: Option<String>,
+    pub io_uring: Option<bool>,
+    pub max_depth: Option<usize>,
+    pub max_entries: Option<usize>,
+    pub workers: Option<usize>,
+    pub max_connections: Option<usize>,
+    pub keep_alive: Option<String>,
+    pub client_timeout: Option<String>,
+    pub slowloris_guard: Option<bool>,
+    pub header_timeout: Option<String>,
+    pub min_rate: Option<u64>,
+    pub max_request_size: Option<u64>,
+    pub read_timeout: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_methods: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rewrites: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replace: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replace_regex: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+    pub rules: Option<PathBuf>,
+    pub fixtures: Option<PathBuf>,
+    pub graphql_mocks: Option<PathBuf>,
+    pub mock: Option<PathBuf>,
+    pub mock_prefix: Option<String>,
+    pub mock_persist: Option<bool>,
+    /// `--proxy` rules by prefix, e.g. `[proxies]` / `"/api" = "http://localhost:3000"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub proxies: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proxy_allow: Vec<String>,
+    pub proxy_deny_private: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<PathBuf>,
+    pub log: Option<String>,
+    pub log_file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log_exclude: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log_sample: Vec<String>,
+    pub log_level: Option<String>,
+    pub events_token: Option<String>,
+    /// Extra response headers, e.g. `[headers]` / `X-Frame-Options = "DENY"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Content types by extension, e.g. `[mime]` / `glb = "model/gltf-binary"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mime: BTreeMap<String, String>,
+    /// Charsets by extension, e.g. `[charset-ext]` / `txt = "shift_jis"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub charset_ext: BTreeMap<String, String>,
+}
+
+impl FileConfig {
+    /// Parses TOML text.
+    pub fn parse(contents: &str) -> Result<FileConfig, String> {
+        toml::from_str(contents).map_err(|e| e.message().to_string())
+    }
+
+    /// Reads and parses a config file, resolving relative paths against its directory.
+    pub fn load(path: &Path) -> Result<FileConfig, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read config {}: {}", path.display(), e))?;
+        let mut config = FileConfig::parse(&contents)
+            .map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+        if let Some(base) = path.parent() {
+            config.dir = config.dir.map(|d| base.join(d));
+            config.file = config.file.map(|f| base.join(f));
+            for dir in config.mounts.values_mut().chain(config.vhosts.values_mut()) {
+                *dir = base.join(&dir);
+            }
+            config.uds = config.uds.map(|u| base.join(u));
+            config.tls_cert = config.tls_cert.map(|c| base.join(c));
+            config.tls_key = config.tls_key.map(|k| base.join(k));
+            config.compress_cache = config.compress_cache.map(|c| match c {
+                CacheSetting::Path(dir) => CacheSetting::Path(base.join(dir)),
+                flag => flag,
+            });
+            config.cache_dir = config.cache_dir.map(|c| base.join(c));
+            config.rules = config.rules.map(|r| base.join(r));
+            config.fixtures = config.fixtures.map(|f| base.join(f));
+            config.graphql_mocks = config.graphql_mocks.map(|g| base.join(g));
+            config.mock = config.mock.map(|m| base.join(m));
+            config.plugins = config.plugins.iter().map(|p| base.join(p)).collect();
+            config.log_file = config.log_file.map(|f| base.join(f));
+            config.pid_file = config.pid_file.map(|f| base.join(f));
+            config.daemon_log = config.daemon_log.map(|f| base.join(f));
+            config.watch_paths = config.watch_paths.iter().map(|p| base.join(p)).collect();
+        }
+        Ok(config)
+    }
+
+    /// Reads `WEBSERVE_*` variables into a config layer. A `WEBSERVE_` name no option reads
+    /// is logged as a warning and otherwise ignored (see [`unknown_env_vars`]).
+    ///
+    /// Booleans accept `1/true/yes/on` and `0/false/no/off`. Lists are comma-separated, except
+    /// `REDIRECT`, `REWRITE`, `REPLACE`, `REPLACE_REGEX` and `HEADER`, one entry per line.
+    pub fn from_env<I>(vars: I) -> Result<FileConfig, String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let (config, unknown) = FileConfig::read_env(vars)?;
+        for key in unknown {
+            warn!("{}: unknown variable, ignored", key);
+        }
+        Ok(config)
+    }
+
+    /// [`FileConfig::from_env`], with the `WEBSERVE_` names it did not know.
+    fn read_env<I>(vars: I) -> Result<(FileConfig, Vec<String>), String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut config = FileConfig::default();
+        let mut unknown = Vec::new();
+        for (key, value) in vars {
+            let Some(name) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let flag = |v: &str| parse_env_bool(&key, v);
+            match name {
+                "PORT" => {
+                    let port = value
+                        .parse()
+                        .map_err(|_| format!("{}: invalid port {}", key, value))?;
+                    config.port = Some(port);
+                }
+                "HOST" => {
+                    let hosts = value.split(',').map(|h| h.trim().to_string());
+                    config.host = Some(HostSetting::Many(hosts.collect()));
+                }
+                "STRICT_PORT" => config.strict_port = Some(flag(&value)?),
+                "UDS" => config.uds = Some(PathBuf::from(value)),
+                "SYSTEMD" => config.systemd = Some(flag(&value)?),
+                "DAEMON" => config.daemon = Some(flag(&value)?),
+                "PID_FILE" => config.pid_file = Some(PathBuf::from(value)),
+                "DAEMON_LOG" => config.daemon_log = Some(PathBuf::from(value)),
+                "TLS_CERT" => config.tls_cert = Some(PathBuf::from(value)),
+                "TLS_KEY" => config.tls_key = Some(PathBuf::from(value)),
+                "HTTP1_ONLY" => config.http1_only = Some(flag(&value)?),
+                "REDIRECT_HTTP" => {
+                    let port = value
+                        .parse()
+                        .map_err(|_| format!("{}: invalid port {}", key, value))?;
+                    config.redirect_http = Some(port);
+                }
+                "DIR" => config.dir = Some(PathBuf::from(value)),
+                "FILE" => config.file = Some(PathBuf::from(value)),
+                "MOUNT" => {
+                    for spec in value.split(',').filter(|s| !s.trim().is_empty()) {
+                        let (prefix, dir) = spec
+                            .split_once('=')
+                            .ok_or_else(|| format!("{}: expected PREFIX=DIR, got {}", key, spec))?;
+                        config
+                            .mounts
+                            .insert(prefix.trim().to_string(), PathBuf::from(dir.trim()));
+                    }
+                }
+                "VHOST" => {
+                    for spec in value.split(',').filter(|s| !s.trim().is_empty()) {
+                        let (host, dir) = spec
+                            .split_once('=')
+                            .ok_or_else(|| format!("{}: expected HOST=DIR, got {}", key, spec))?;
+                        config
+                            .vhosts
+                            .insert(host.trim().to_string(), PathBuf::from(dir.trim()));
+                    }
+                }
+                "SPA" => config.spa = Some(flag(&value)?),
+                "SECTION_INDEX" => config.section_index = Some(flag(&value)?),
+                "SUGGEST" => config.suggest = Some(flag(&value)?),
+                "WATCH" => config.watch = Some(flag(&value)?),
+                "WATCH_PATH" => {
+                    config.watch_paths = value.split(',').map(|p| PathBuf::from(p.trim())).collect()
+                }
+                "EXEC" => config.exec = Some(value),
+                "OPEN" if value.starts_with('/') => config.open = Some(OpenSetting::Path(value)),
+                "OPEN" => config.open = Some(OpenSetting::Flag(flag(&value)?)),
+                "QR" => config.qr = Some(flag(&value)?),
+                "MDNS" => config.mdns = Some(value),
+                "REDIRECT_DIR_SLASH" => config.redirect_dir_slash = Some(flag(&value)?),
+                "STRICT_HTTP" => config.strict_http = Some(flag(&value)?),
+                "HARDEN" => config.harden = Some(flag(&value)?),
+                "THROTTLE" => config.throttle = Some(parse_env_int(&key, &value)?),
+                "LATENCY" => config.latency = Some(value),
+                "MAX_HEADER_BYTES" => {
+                    config.max_header_bytes = Some(parse_env_count(&key, &value)?)
+                }
+                "FAULT_INJECTION" => config.fault_injection = Some(flag(&value)?),
+                "NO_CACHE" => config.no_cache = Some(flag(&value)?),
+                "ETAG" => config.etag = Some(value),
+                "CHARSET" => config.charset = Some(value),
+                "LISTING_LANG" => config.listing_lang = Some(value),
+                "RENDER_MARKDOWN" => config.render_markdown = Some(flag(&value)?),
+                "HIGHLIGHT" => config.highlight = Some(flag(&value)?),
+                "UPLOAD" => config.upload = Some(flag(&value)?),
+                "MAX_UPLOAD_SIZE" => config.max_upload_size = Some(parse_env_int(&key, &value)?),
+                "WEBDAV" => config.webdav = Some(flag(&value)?),
+                "OPEN_IN_EDITOR" => config.open_in_editor = Some(flag(&value)?),
+                "ADMIN_REMOTE" => config.admin_remote = Some(flag(&value)?),
+                "BRAND_TITLE" => config.brand_title = Some(value),
+                "BRAND_LOGO" => config.brand_logo = Some(value),
+                "BRAND_FOOTER" => config.brand_footer = Some(value),
+                "COMPRESS" => config.compress = Some(flag(&value)?),
+                "COMPRESS_CPU_LIMIT" => {
+                    let limit = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("{}: expected a percentage, got {}", key, value))?;
+                    config.compress_cpu_limit = Some(limit);
+                }
+                "COMPRESS_CACHE" => {
+                    config.compress_cache = Some(match flag(&value) {
+                        Ok(on) => CacheSetting::Flag(on),
+                        Err(_) => CacheSetting::Path(PathBuf::from(value)),
+                    })
+                }
+                "STORE" => config.store = Some(value),
+                "COMPRESS_CACHE_SIZE" => {
+                    config.compress_cache_size = Some(parse_env_int(&key, &value)?)
+                }
+                "CACHE_DIR" => config.cache_dir = Some(PathBuf::from(value)),
+                "CACHE_SIZE" => config.cache_size = Some(parse_env_int(&key, &value)?),
+                "IO_URING" => config.io_uring = Some(flag(&value)?),
+                "MAX_DEPTH" => config.max_depth = Some(parse_env_count(&key, &value)?),
+                "MAX_ENTRIES" => config.max_entries = Some(parse_env_count(&key, &value)?),
+                "WORKERS" => config.workers = Some(parse_env_count(&key, &value)?),
+                "MAX_CONNECTIONS" => config.max_connections = Some(parse_env_count(&key, &value)?),
+                "KEEP_ALIVE" => config.keep_alive = Some(value),
+                "CLIENT_TIMEOUT" => config.client_timeout = Some(value),
+                "SLOWLORIS_GUARD" => config.slowloris_guard = Some(flag(&value)?),
+                "HEADER_TIMEOUT" => config.header_timeout = Some(value),
+                "MIN_RATE" => config.min_rate = Some(parse_env_int(&key, &value)?),
+                "MAX_REQUEST_SIZE" => config.max_request_size = Some(parse_env_int(&key, &value)?),
+                "READ_TIMEOUT" => config.read_timeout = Some(value),
+                "ALLOW_METHODS" => {
+                    config.allow_methods = value.split(',').map(|m| m.trim().to_string()).collect()
+                }
+                "REDIRECT" => config.redirects = env_lines(&value),
+                "REWRITE" => config.rewrites = env_lines(&value),
+                "REPLACE" => config.replace = env_lines(&value),
+                "REPLACE_REGEX" => config.replace_regex = env_lines(&value),
+                "RULES" => config.rules = Some(PathBuf::from(value)),
+                "FIXTURES" => config.fixtures = Some(PathBuf::from(value)),
+                "ALLOW" => config.allow = value.split(',').map(|c| c.trim().to_string()).collect(),
+                "DENY" => config.deny = value.split(',').map(|c| c.trim().to_string()).collect(),
+                "HEADER" => {
+                    for spec in env_lines(&value) {
+                        let (name, value) = spec.split_once(':').ok_or_else(|| {
+                            format!("{}: expected Name: value, got {}", key, spec)
+                        })?;
+                        config
+                            .headers
+                            .insert(name.trim().to_string(), value.trim().to_string());
+                    }
+                }
+                "MIME" => {
+                    for spec in value.split(',').filter(|s| !s.trim().is_empty()) {
+                        let (ext, mime) = spec
+                            .split_once('=')
+                            .ok_or_else(|| format!("{}: expected EXT=TYPE, got {}", key, spec))?;
+                        config
+                            .mime
+                            .insert(ext.trim().to_string(), mime.trim().to_string());
+                    }
+                }
+                "CHARSET_EXT" => {
+                    for spec in value.split(',').filter(|s| !s.trim().is_empty()) {
+                        let (ext, charset) = spec
+                            .split_once('=')
+                            .ok_or_else(|| format!("{}: expected EXT=NAME, got {}", key, spec))?;
+                        config
+                            .charset_ext
+                            .insert(ext.trim().to_string(), charset.trim().to_string());
+                    }
+                }
+                "GRAPHQL_MOCKS" => config.graphql_mocks = Some(PathBuf::from(value)),
+                "MOCK" => config.mock = Some(PathBuf::from(value)),
+                "MOCK_PREFIX" => config.mock_prefix = Some(value),
+                "MOCK_PERSIST" => config.mock_persist = Some(flag(&value)?),
+                "PROXY" => {
+                    for spec in value.split(',').filter(|s| !s.trim().is_empty()) {
+                        let (prefix, url) = spec
+                            .split_once('=')
+                            .ok_or_else(|| format!("{}: expected PREFIX=URL, got {}", key, spec))?;
+                        config
+                            .proxies
+                            .insert(prefix.trim().to_string(), url.trim().to_string());
+                    }
+                }
+                "PROXY_ALLOW" => {
+                    config.proxy_allow = value.split(',').map(|h| h.trim().to_string()).collect()
+                }
+                "PROXY_DENY_PRIVATE" => config.proxy_deny_private = Some(flag(&value)?),
+                "PLUGIN" => {
+                    config.plugins = value.split(',').map(|p| PathBuf::from(p.trim())).collect()
+                }
+                "LOG" => config.log = Some(value),
+                "LOG_FILE" => config.log_file = Some(PathBuf::from(value)),
+                "LOG_EXCLUDE" => {
+                    config.log_exclude = value.split(',').map(|p| p.trim().to_string()).collect()
+                }
+                "LOG_SAMPLE" => {
+                    config.log_sample = value.split(',').map(|s| s.trim().to_string()).collect()
+                }
+                "LOG_LEVEL" => config.log_level = Some(value),
+                "EVENTS_TOKEN" => config.events_token = Some(value),
+                // Read by `resolve_layers` to find the config file
+                "CONFIG" => {}
+                _ => unknown.push(key.clone()),
+            }
+        }
+        Ok((config, unknown))
+    }
+
+    /// Returns this layer with every key set in `top` replaced by `top`'s value.
+    pub fn overlay(self, top: FileConfig) -> FileConfig {
+        let mut headers = self.headers;
+        headers.extend(top.headers);
+        let mut mime = self.mime;
+        mime.extend(top.mime);
+        let mut charset_ext = self.charset_ext;
+        charset_ext.extend(top.charset_ext);
+        let mut proxies = self.proxies;
+        proxies.extend(top.proxies);
+        let mut mounts = self.mounts;
+        mounts.extend(top.mounts);
+        let mut vhosts = self.vhosts;
+        vhosts.extend(top.vhosts);
+        FileConfig {
+            port: top.port.or(self.port),
+            host: top.host.or(self.host),
+            strict_port: top.strict_port.or(self.strict_port),
+            uds: top.uds.or(self.uds),
+            systemd: top.systemd.or(self.systemd),
+            daemon: top.daemon.or(self.daemon),
+            pid_file: top.pid_file.or(self.pid_file),
+            daemon_log: top.daemon_log.or(self.daemon_log),
+            tls_cert: top.tls_cert.or(self.tls_cert),
+            tls_key: top.tls_key.or(self.tls_key),
+            http1_only: top.http1_only.or(self.http1_only),
+            redirect_http: top.redirect_http.or(self.redirect_http),
+            dir: top.dir.or(self.dir),
+            file: top.file.or(self.file),
+            mounts,
+            vhosts,
+            spa: top.spa.or(self.spa),
+            section_index: top.section_index.or(self.section_index),
+            suggest: top.suggest.or(self.suggest),
+            watch: top.watch.or(self.watch),
+            watch_paths: if top.watch_paths.is_empty() {
+                self.watch_paths
+            } else {
+                top.watch_paths
+            },
+            exec: top.exec.or(self.exec),
+            open: top.open.or(self.open),
+            qr: top.qr.or(self.qr),
+            mdns: top.mdns.or(self.mdns),
+            redirect_dir_slash: top.redirect_dir_slash.or(self.redirect_dir_slash),
+            strict_http: top.strict_http.or(self.strict_http),
+            harden: top.harden.or(self.harden),
+            throttle: top.throttle.or(self.throttle),
+            latency: top.latency.or(self.latency),
+            max_header_bytes: top.max_header_bytes.or(self.max_header_bytes),
+            fault_injection: top.fault_injection.or(self.fault_injection),
+            no_cache: top.no_cache.or(self.no_cache),
+            etag: top.etag.or(self.etag),
+            charset: top.charset.or(self.charset),
+            listing_lang: top.listing_lang.or(self.listing_lang),
+            render_markdown: top.render_markdown.or(self.render_markdown),
+            highlight: top.highlight.or(self.highlight),
+            upload: top.upload.or(self.upload),
+            max_upload_size: top.max_upload_size.or(self.max_upload_size),
+            webdav: top.webdav.or(self.webdav),
+            open_in_editor: top.open_in_editor.or(self.open_in_editor),
+            admin_remote: top.admin_remote.or(self.admin_remote),
+            brand_title: top.brand_title.or(self.brand_title),
+            brand_logo: top.brand_logo.or(self.brand_logo),
+            brand_footer: top.brand_footer.or(self.brand_footer),
+            compress: top.compress.or(self.compress),
+            compress_cpu_limit: top.compress_cpu_limit.or(self.compress_cpu_limit),
+            compress_cache: top.compress_cache.or(self.compress_cache),
+            store: top.store.or(self.store),
+            compress_cache_size: top.compress_cache_size.or(self.compress_cache_size),
+            cache_dir: top.cache_dir.or(self.cache_dir),
+            cache_size: top.cache_size.or(self.cache_size),
+            io_uring: top.io_uring.or(self.io_uring),
+            max_depth: top.max_depth.or(self.max_depth),
+            max_entries: top.max_entries.or(self.max_entries),
+            workers: top.workers.or(self.workers),
+            max_connections: top.max_connections.or(self.max_connections),
+            keep_alive: top.keep_alive.or(self.keep_alive),
+            client_timeout: top.client_timeout.or(self.client_timeout),
+            slowloris_guard: top.slowloris_guard.or(self.slowloris_guard),
+            header_timeout: top.header_timeout.or(self.header_timeout),
+            min_rate: top.min_rate.or(self.min_rate),
+            max_request_size: top.max_request_size.or(self.max_request_size),
+            read_timeout: top.read_timeout.or(self.read_timeout),
+            allow_methods: if top.allow_methods.is_empty() {
+                self.allow_methods
+            } else {
+                top.allow_methods
+            },
+            redirects: if top.redirects.is_empty() {
+                self.redirects
+            } else {
+                top.redirects
+            },
+            rewrites: if top.rewrites.is_empty() {
+                self.rewrites
+            } else {
+                top.rewrites
+            },
+            replace: if top.replace.is_empty() {
+                self.replace
+            } else {
+                top.replace
+            },
+            replace_regex: if top.replace_regex.is_empty() {
+                self.replace_regex
+            } else {
+                top.replace_regex
+            },
+            allow: if top.allow.is_empty() {
+                self.allow
+            } else {
+                top.allow
+            },
+            deny: if top.deny.is_empty() {
+                self.deny
+            } else {
+                top.deny
+            },
+            rules: top.rules.or(self.rules),
+            fixtures: top.fixtures.or(self.fixtures),
+            graphql_mocks: top.graphql_mocks.or(self.graphql_mocks),
+            mock: top.mock.or(self.mock),
+            mock_prefix: top.mock_prefix.or(self.mock_prefix),
+            mock_persist: top.mock_persist.or(self.mock_persist),
+            proxies,
+            proxy_allow: if top.proxy_allow.is_empty() {
+                self.proxy_allow
+            } else {
+                top.proxy_allow
+            },
+            proxy_deny_private: top.proxy_deny_private.or(self.proxy_deny_private),
+            plugins: if top.plugins.is_empty() {
+                self.plugins
+            } else {
+                top.plugins
+            },
+            log: top.log.or(self.log),
+            log_file: top.log_file.or(self.log_file),
+            log_exclude: if top.log_exclude.is_empty() {
+                self.log_exclude
+            } else {
+                top.log_exclude
+            },
+            log_sample: if top.log_sample.is_empty() {
+                self.log_sample
+            } else {
+                top.log_sample
+            },
+            log_level: top.log_level.or(self.log_level),
+            events_token: top.events_token.or(self.events_token),
+            headers,
+            mime,
+            charset_ext,
+        }
+    }
+
+    /// Fills every option not given on the command line from this layer; `from_cli` tells
+    /// whether the field of that name was.
+    pub fn apply(
+        self,
+        options: &mut ServeOptions,
+        from_cli: impl Fn(&str) -> bool,
+    ) -> Result<(), String> {
+        if let (Some(port), false) = (self.port, from_cli("port")) {
+            options.port = port;
+        }
+        if let (Some(host), false) = (self.host, from_cli("hosts")) {
+            options.hosts = host.into_vec();
+        }
+        if !from_cli("strict_port") {
+            options.strict_port = self.strict_port.unwrap_or(options.strict_port);
+        }
+        if !from_cli("uds") && self.uds.is_some() {
+            options.uds = self.uds;
+        }
+        if !from_cli("tls_cert") && self.tls_cert.is_some() {
+            options.tls_cert = self.tls_cert;
+        }
+        if !from_cli("tls_key") && self.tls_key.is_some() {
+            options.tls_key = self.tls_key;
+        }
+        if !from_cli("http1_only") {
+            options.http1_only = self.http1_only.unwrap_or(options.http1_only);
+        }
+        if !from_cli("redirect_http") && self.redirect_http.is_some() {
+            options.redirect_http = self.redirect_http;
+        }
+        if !from_cli("systemd") {
+            options.systemd = self.systemd.unwrap_or(options.systemd);
+        }
+        if !from_cli("daemon") {
+            options.daemon = self.daemon.unwrap_or(options.daemon);
+        }
+        if !from_cli("pid_file") && self.pid_file.is_some() {
+            options.pid_file = self.pid_file;
+        }
+        if !from_cli("daemon_log") && self.daemon_log.is_some() {
+            options.daemon_log = self.daemon_log;
+        }
+        if !from_cli("directory") && self.dir.is_some() {
+            options.directory = self.dir;
+        }
+        // `--dir` on the command line beats a configured file
+        if !from_cli("file") && !from_cli("directory") && self.file.is_some() {
+            options.file = self.file;
+        }
+        if !from_cli("mounts") && !self.mounts.is_empty() {
+            options.mounts = site_specs(self.mounts);
+        }
+        if !from_cli("vhosts") && !self.vhosts.is_empty() {
+            options.vhosts = site_specs(self.vhosts);
+        }
+        if !from_cli("spa") {
+            options.spa = self.spa.unwrap_or(options.spa);
+        }
+        if !from_cli("section_index") {
+            options.section_index = self.section_index.unwrap_or(options.section_index);
+        }
+        if !from_cli("suggest") {
+            options.suggest = self.suggest.unwrap_or(options.suggest);
+        }
+        if !from_cli("watch") {
+            options.watch = self.watch.unwrap_or(options.watch);
+        }
+        if !from_cli("watch_paths") && !self.watch_paths.is_empty() {
+            options.watch_paths = self.watch_paths;
+        }
+        if !from_cli("exec") && self.exec.is_some() {
+            options.exec = self.exec;
+        }
+        if !from_cli("open") {
+            match self.open {
+                Some(OpenSetting::Flag(true)) => options.open = Some(None),
+                Some(OpenSetting::Flag(false)) => options.open = None,
+                Some(OpenSetting::Path(path)) => options.open = Some(Some(path)),
+                None => {}
+            }
+        }
+        if !from_cli("qr") {
+            options.qr = self.qr.unwrap_or(options.qr);
+        }
+        if !from_cli("mdns") && self.mdns.is_some() {
+            options.mdns = self.mdns;
+        }
+        if !from_cli("strict_http") {
+            options.strict_http = self.strict_http.unwrap_or(options.strict_http);
+        }
+        if !from_cli("slowloris_guard") {
+            options.slowloris_guard = self.slowloris_guard.unwrap_or(options.slowloris_guard);
+        }
+        if !from_cli("header_timeout") {
+            if let Some(ref d) = self.header_timeout {
+                options.header_timeout =
+                    parse_duration(d).map_err(|e| format!("header-timeout: {}", e))?;
+            }
+        }
+        if !from_cli("min_rate") {
+            options.min_rate = self.min_rate.unwrap_or(options.min_rate);
+        }
+        if !from_cli("max_request_size") && self.max_request_size.is_some() {
+            options.max_request_size = self.max_request_size;
+        }
+        if !from_cli("read_timeout") {
+            if let Some(ref d) = self.read_timeout {
+                options.read_timeout =
+                    Some(parse_duration(d).map_err(|e| format!("read-timeout: {}", e))?);
+            }
+        }
+        if !from_cli("allow_methods") && !self.allow_methods.is_empty() {
+            options.allow_methods = self.allow_methods;
+        }
+        if !from_cli("throttle") && self.throttle.is_some() {
+            options.throttle = self.throttle;
+        }
+        if !from_cli("latency") {
+            if let Some(ref d) = self.latency {
+                options.latency = Some(parse_duration(d).map_err(|e| format!("latency: {}", e))?);
+            }
+        }
+        if !from_cli("harden") {
+            options.harden = self.harden.unwrap_or(options.harden);
+        }
+        if !from_cli("max_header_bytes") {
+            options.max_header_bytes = self.max_header_bytes.unwrap_or(options.max_header_bytes);
+        }
+        if !from_cli("fault_injection") {
+            options.fault_injection = self.fault_injection.unwrap_or(options.fault_injection);
+        }
+        if !from_cli("no_cache") {
+            options.no_cache = self.no_cache.unwrap_or(options.no_cache);
+        }
+        if !from_cli("compress") {
+            options.compress = self.compress.unwrap_or(options.compress);
+        }
+        if !from_cli("compress_cpu_limit") {
+            options.compress_cpu_limit = self
+                .compress_cpu_limit
+                .unwrap_or(options.compress_cpu_limit);
+        }
+        if !from_cli("store") {
+            if let Some(store) = self.store {
+                options.store = store;
+            }
+        }
+        if !from_cli("compress_cache") {
+            match self.compress_cache {
+                Some(CacheSetting::Flag(true)) => options.compress_cache = Some(None),
+                Some(CacheSetting::Flag(false)) => options.compress_cache = None,
+                Some(CacheSetting::Path(dir)) => options.compress_cache = Some(Some(dir)),
+                None => {}
+            }
+        }
+        if !from_cli("compress_cache_size") {
+            options.compress_cache_size = self
+                .compress_cache_size
+                .unwrap_or(options.compress_cache_size);
+        }
+        if !from_cli("cache_dir") && self.cache_dir.is_some() {
+            options.cache_dir = self.cache_dir;
+        }
+        if !from_cli("cache_size") {
+            options.cache_size = self.cache_size.unwrap_or(options.cache_size);
+        }
+        if !from_cli("io_uring") {
+            options.io_uring = self.io_uring.unwrap_or(options.io_uring);
+        }
+        if !from_cli("max_depth") && self.max_depth.is_some() {
+            options.max_depth = self.max_depth;
+        }
+        if !from_cli("max_entries") && self.max_entries.is_some() {
+            options.max_entries = self.max_entries;
+        }
+        if !from_cli("workers") && self.workers.is_some() {
+            options.workers = self.workers;
+        }
+        if !from_cli("max_connections") && self.max_connections.is_some() {
+            options.max_connections = self.max_connections;
+        }
+        if !from_cli("keep_alive") {
+            if let Some(ref d) = self.keep_alive {
+                options.keep_alive =
+                    Some(parse_duration(d).map_err(|e| format!("keep-alive: {}", e))?);
+            }
+        }
+        if !from_cli("client_timeout") {
+            if let Some(ref d) = self.client_timeout {
+                options.client_timeout =
+                    Some(parse_duration(d).map_err(|e| format!("client-timeout: {}", e))?);
+            }
+        }
+        if !from_cli("render_markdown") {
+            options.render_markdown = self.render_markdown.unwrap_or(options.render_markdown);
+        }
+        if !from_cli("highlight") {
+            options.highlight = self.highlight.unwrap_or(options.highlight);
+        }
+        if !from_cli("upload") {
+            options.upload = self.upload.unwrap_or(options.upload);
+        }
+        if !from_cli("max_upload_size") {
+            options.max_upload_size = self.max_upload_size.unwrap_or(options.max_upload_size);
+        }
+        if !from_cli("webdav") {
+            options.webdav = self.webdav.unwrap_or(options.webdav);
+        }
+        if !from_cli("open_in_editor") {
+            options.open_in_editor = self.open_in_editor.unwrap_or(options.open_in_editor);
+        }
+        if !from_cli("admin_remote") {
+            options.admin_remote = self.admin_remote.unwrap_or(options.admin_remote);
+        }
+        if !from_cli("brand_title") && self.brand_title.is_some() {
+            options.brand_title = self.brand_title;
+        }
+        if !from_cli("brand_logo") && self.brand_logo.is_some() {
+            options.brand_logo = self.brand_logo;
+        }
+        if !from_cli("brand_footer") && self.brand_footer.is_some() {
+            options.brand_footer = self.brand_footer;
+        }
+        if !from_cli("listing_lang") {
+            if let Some(ref lang) = self.listing_lang {
+                options.listing_lang = lang.parse()?;
+            }
+        }
+        if !from_cli("etag") {
+            if let Some(ref mode) = self.etag {
+                options.etag = mode.parse()?;
+            }
+        }
+        if !from_cli("charset") {
+            if let Some(ref charset) = self.charset {
+                options.charset = charset.clone();
+            }
+        }
+        if !from_cli("no_redirect_dir_slash") {
+            if let Some(redirect) = self.redirect_dir_slash {
+                options.no_redirect_dir_slash = !redirect;
+            }
+        }
+        if !from_cli("redirects") && !self.redirects.is_empty() {
+            options.redirects = self.redirects;
+        }
+        if !from_cli("rewrites") && !self.rewrites.is_empty() {
+            options.rewrites = self.rewrites;
+        }
+        if !from_cli("replace") && !self.replace.is_empty() {
+            options.replace = self.replace;
+        }
+        if !from_cli("replace_regex") && !self.replace_regex.is_empty() {
+            options.replace_regex = self.replace_regex;
+        }
+        if !from_cli("allow") && !self.allow.is_empty() {
+            options.allow = self.allow;
+        }
+        if !from_cli("deny") && !self.deny.is_empty() {
+            options.deny = self.deny;
+        }
+        if !from_cli("rules_file") && self.rules.is_some() {
+            options.rules_file = self.rules;
+        }
+        if !from_cli("fixtures") && self.fixtures.is_some() {
+            options.fixtures = self.fixtures;
+        }
+        if !from_cli("graphql_mocks") && self.graphql_mocks.is_some() {
+            options.graphql_mocks = self.graphql_mocks;
+        }
+        if !from_cli("mock") && self.mock.is_some() {
+            options.mock = self.mock;
+        }
+        if !from_cli("mock_prefix") {
+            if let Some(prefix) = self.mock_prefix {
+                options.mock_prefix = prefix;
+            }
+        }
+        if !from_cli("mock_persist") {
+            options.mock_persist = self.mock_persist.unwrap_or(options.mock_persist);
+        }
+        if !from_cli("proxies") && !self.proxies.is_empty() {
+            options.proxies = self
+                .proxies
+                .into_iter()
+                .map(|(prefix, url)| format!("{}={}", prefix, url))
+                .collect();
+        }
+        if !from_cli("proxy_allow") && !self.proxy_allow.is_empty() {
+            options.proxy_allow = self.proxy_allow;
+        }
+        if !from_cli("proxy_deny_private") {
+            options.proxy_deny_private = self
+                .proxy_deny_private
+                .unwrap_or(options.proxy_deny_private);
+        }
+        if !from_cli("plugins") && !self.plugins.is_empty() {
+            options.plugins = self.plugins;
+        }
+        if !from_cli("log") {
+            if let Some(ref format) = self.log {
+                options.log = Some(format.parse()?);
+            }
+        }
+        if !from_cli("log_file") && self.log_file.is_some() {
+            options.log_file = self.log_file;
+        }
+        if !from_cli("log_exclude") && !self.log_exclude.is_empty() {
+            options.log_exclude = self.log_exclude;
+        }
+        if !from_cli("log_sample") && !self.log_sample.is_empty() {
+            options.log_sample = self.log_sample;
+        }
+        if !from_cli("log_level") && !from_cli("verbose") {
+            if let Some(ref level) = self.log_level {
+                options.log_level = Some(
+                    level
+                        .parse()
+                        .map_err(|_| format!("invalid log-level {}", level))?,
+                );
+            }
+        }
+        if !from_cli("events_token") && self.events_token.is_some() {
+            options.events_token = self.events_token;
+        }
+        if !from_cli("headers") && !self.headers.is_empty() {
+            options.headers = self
+                .headers
+                .into_iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect();
+        }
+        if !from_cli("mime") && !self.mime.is_empty() {
+            options.mime = self
+                .mime
+                .into_iter()
+                .map(|(ext, mime)| format!("{}={}", ext, mime))
+                .collect();
+        }
+        if !from_cli("charset_ext") && !self.charset_ext.is_empty() {
+            options.charset_ext = self
+                .charset_ext
+                .into_iter()
+                .map(|(ext, charset)| format!("{}={}", ext, charset))
+                .collect();
+        }
+        Ok(())
+    }
+
+    /// The options that differ from the defaults, as config keys; relative paths are made
+    /// relative to `base`, the directory the file will be written to.
+    pub fn from_options(options: &ServeOptions, base: &Path) -> FileConfig {
+        let defaults = ServeOptions::default();
+        let on = |value: bool| value.then_some(true);
+        let changed = |value: String, default: String| (value != default).then_some(value);
+        let path = |p: &Option<PathBuf>| p.as_deref().map(|p| rebase(p, base));
+        FileConfig {
+            port: (options.port != defaults.port).then_some(options.port),
+            host: match options.hosts.as_slice() {
+                hosts if hosts == defaults.hosts.as_slice() => None,
+                [host] => Some(HostSetting::One(host.clone())),
+                hosts => Some(HostSetting::Many(hosts.to_vec())),
+            },
+            strict_port: on(options.strict_port),
+            uds: path(&options.uds),
+            systemd: on(options.systemd),
+            daemon: on(options.daemon),
+            pid_file: path(&options.pid_file),
+            daemon_log: path(&options.daemon_log),
+            tls_cert: path(&options.tls_cert),
+            tls_key: path(&options.tls_key),
+            http1_only: on(options.http1_only),
+            redirect_http: options.redirect_http,
+            dir: path(&options.directory),
+            file: path(&options.file),
+            mounts: sites_of(&options.mounts, base),
+            vhosts: sites_of(&options.vhosts, base),
+            spa: on(options.spa),
+            section_index: on(options.section_index),
+            suggest: on(options.suggest),
+            watch: on(options.watch),
+            watch_paths: options
+                .watch_paths
+                .iter()
+                .map(|p| rebase(p, base))
+                .collect(),
+            exec: options.exec.clone(),
+            open: match options.open {
+                Some(Some(ref path)) => Some(OpenSetting::Path(path.clone())),
+                Some(None) => Some(OpenSetting::Flag(true)),
+                None => None,
+            },
+            qr: on(options.qr),
+            mdns: options.mdns.clone(),
+            redirect_dir_slash: options.no_redirect_dir_slash.then_some(false),
+            strict_http: on(options.strict_http),
+            harden: on(options.harden),
+            throttle: options.throttle,
+            latency: options.latency.map(format_duration),
+            max_header_bytes: (options.max_header_bytes != defaults.max_header_bytes)
+                .then_some(options.max_header_bytes),
+            fault_injection: on(options.fault_injection),
+            no_cache: on(options.no_cache),
+            compress: on(options.compress),
+            compress_cpu_limit: (options.compress_cpu_limit != defaults.compress_cpu_limit)
+                .then_some(options.compress_cpu_limit),
+            compress_cache: match options.compress_cache {
+                Some(Some(ref dir)) => Some(CacheSetting::Path(rebase(dir, base))),
+                Some(None) => Some(CacheSetting::Flag(true)),
+                None => None,
+            },
+            store: changed(options.store.clone(), defaults.store.clone()),
+            compress_cache_size: (options.compress_cache_size != defaults.compress_cache_size)
+                .then_some(options.compress_cache_size),
+            cache_dir: path(&options.cache_dir),
+            cache_size: (options.cache_size != defaults.cache_size).then_some(options.cache_size),
+            etag: changed(options.etag.to_string(), defaults.etag.to_string()),
+            charset: changed(options.charset.clone(), defaults.charset.clone()),
+            listing_lang: changed(
+                options.listing_lang.to_string(),
+                defaults.listing_lang.to_string(),
+            ),
+            render_markdown: on(options.render_markdown),
+            highlight: on(options.highlight),
+            upload: on(options.upload),
+            max_upload_size: (options.max_upload_size != defaults.max_upload_size)
+                .then_some(options.max_upload_size),
+            webdav: on(options.webdav),
+            open_in_editor: on(options.open_in_editor),
+            admin_remote: on(options.admin_remote),
+            brand_title: options.brand_title.clone(),
+            brand_logo: options.brand_logo.clone(),
+            brand_footer: options.brand_footer.clone(),
+            io_uring: on(options.io_uring),
+            max_depth: options.max_depth,
+            max_entries: options.max_entries,
+            workers: options.workers,
+            max_connections: options.max_connections,
+            keep_alive: options.keep_alive.map(format_duration),
+            client_timeout: options.client_timeout.map(format_duration),
+            slowloris_guard: on(options.slowloris_guard),
+            header_timeout: changed(
+                format_duration(options.header_timeout),
+                format_duration(defaults.header_timeout),
+            ),
+            min_rate: (options.min_rate != defaults.min_rate).then_some(options.min_rate),
+            max_request_size: options.max_request_size,
+            read_timeout: options.read_timeout.map(format_duration),
+            allow_methods: options.allow_methods.clone(),
+            redirects: options.redirects.clone(),
+            rewrites: options.rewrites.clone(),
+            replace: options.replace.clone(),
+            replace_regex: options.replace_regex.clone(),
+            allow: options.allow.clone(),
+            deny: options.deny.clone(),
+            rules: path(&options.rules_file),
+            fixtures: path(&options.fixtures),
+            graphql_mocks: path(&options.graphql_mocks),
+            mock: path(&options.mock),
+            mock_prefix: changed(options.mock_prefix.clone(), defaults.mock_prefix.clone()),
+            mock_persist: on(options.mock_persist),
+            proxies: options
+                .proxies
+                .iter()
+                .filter_map(|spec| spec.split_once('='))
+                .map(|(prefix, url)| (prefix.trim().to_string(), url.trim().to_string()))
+                .collect(),
+            proxy_allow: options.proxy_allow.clone(),
+            proxy_deny_private: on(options.proxy_deny_private),
+            plugins: options.plugins.iter().map(|p| rebase(p, base)).collect(),
+            log: options.log.map(|format| format.to_string()),
+            log_file: path(&options.log_file),
+            log_exclude: options.log_exclude.clone(),
+            log_sample: options.log_sample.clone(),
+            log_level: log_level(options).map(|level| level.to_string().to_ascii_lowercase()),
+            events_token: options.events_token.clone(),
+            headers: options
+                .headers
+                .iter()
+                .filter_map(|spec| spec.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect(),
+            mime: options
+                .mime
+                .iter()
+                .filter_map(|spec| spec.split_once('='))
+                .map(|(ext, mime)| (ext.trim().to_string(), mime.trim().to_string()))
+                .collect(),
+            charset_ext: options
+                .charset_ext
+                .iter()
+                .filter_map(|spec| spec.split_once('='))
+                .map(|(ext, charset)| (ext.trim().to_string(), charset.trim().to_string()))
+                .collect(),
+        }
+    }
+
+    /// TOML text of this layer; unset keys are left out.
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+}
+
+/// `NAME=DIR` specs of `--mount` or `--vhost` for configured sites.
+fn site_specs(sites: BTreeMap<String, PathBuf>) -> Vec<String> {
+    sites
+        .into_iter()
+        .map(|(site, dir)| format!("{}={}", site, dir.display()))
+        .collect()
+}
+
+/// `--mount` or `--vhost` specs as a config table in `base`.
+fn sites_of(specs: &[String], base: &Path) -> BTreeMap<String, PathBuf> {
+    specs
+        .iter()
+        .filter_map(|spec| spec.split_once('='))
+        .map(|(site, dir)| (site.trim().to_string(), rebase(Path::new(dir.trim()), base)))
+        .collect()
+}
+
+/// `path` as written in a config file in `base`: absolute paths stay, relative ones are
+/// re-anchored so they still name the same file.
+fn rebase(path: &Path, base: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let base = cwd.join(base);
+    if base == cwd {
+        return path.to_path_buf();
+    }
+    let target = cwd.join(path);
+    match target.strip_prefix(&base) {
+        Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => target,
+    }
+}
+
+/// Level picked by `--log-level` or the `-v` count; `None` when neither was given.
+pub fn log_level(options: &ServeOptions) -> Option<LevelFilter> {
+    options.log_level.or(match options.verbose {
+        0 => None,
+        1 => Some(LevelFilter::DEBUG),
+        _ => Some(LevelFilter::TRACE),
+    })
+}
+
+/// `WEBSERVE_*` names in `vars` that no option reads, such as a misspelled `WEBSERVE_PROT`.
+///
+/// The binary reads the environment before logging is set up, so it calls this afterwards
+/// to report them.
+pub fn unknown_env_vars<I>(vars: I) -> Vec<String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    FileConfig::read_env(vars)
+        .map(|(_, unknown)| unknown)
+        .unwrap_or_default()
+}
+
+/// `--export-config <path>`: writes the merged options to `path` as a config file.
+pub fn export_config(options: &ServeOptions, path: &Path) -> Result<(), String> {
+    let base = path.parent().unwrap_or(Path::new(""));
+    let toml = FileConfig::from_options(options, base).to_toml()?;
+    std::fs::write(path, toml).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// Layers the config file and `env` underneath `options`, the parsed command line;
+/// `from_cli` tells whether the field of that name was given there. The CLI's
+/// `resolve_options` calls this after parsing the arguments.
+pub fn resolve_layers<E>(
+    options: &mut ServeOptions,
+    from_cli: impl Fn(&str) -> bool,
+    env: E,
+) -> Result<(), String>
+where
+    E: IntoIterator<Item = (String, String)>,
+{
+    let env: Vec<(String, String)> = env.into_iter().collect();
+    let env_config = env
+        .iter()
+        .find(|(k, _)| k == "WEBSERVE_CONFIG")
+        .map(|(_, v)| PathBuf::from(v));
+    let path = match options.config.clone().or(env_config) {
+        Some(p) => Some(p),
+        None => {
+            let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+            default.is_file().then_some(default)
+        }
+    };
+    let file = match path {
+        Some(ref path) => FileConfig::load(path)?,
+        None => FileConfig::default(),
+    };
+    file.overlay(FileConfig::from_env(env)?)
+        .apply(options, from_cli)?;
+    if options.config.is_none() {
+        options.config = path;
+    }
+    let mut seen = HashSet::new();
+    options
+        .hosts
+        .retain(|h| !h.trim().is_empty() && seen.insert(bind_host(h).to_string()));
+    if options.hosts.is_empty() {
+        return Err("--host needs at least one address".to_string());
+    }
+    Ok(())
+}
+
+fn parse_env_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        _ => Err(format!("{}: expected a boolean, got {}", key, value)),
+    }
+}
+
+fn parse_env_count(key: &str, value: &str) -> Result<usize, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{}: expected a number, got {}", key, value))
+}
+
+/// [`parse_env_count`] as the option's integer type, failing when it does not fit.
+fn parse_env_int<T: TryFrom<usize>>(key: &str, value: &str) -> Result<T, String> {
+    T::try_from(parse_env_count(key, value)?)
+        .map_err(|_| format!("{}: {} is out of range", key, value.trim()))
+}
+
+/// Non-empty lines of a variable holding rules or headers, which may contain commas.
+fn env_lines(value: &str) -> Vec<String> {
+    value
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses a `Name: value` response header spec.
+pub fn parse_header(spec: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("header expects `Name: value`, got {}", spec))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("invalid header name {}", name.trim()))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|_| format!("invalid value for header {}", name))?;
+    Ok((name, value))
+}
@@ -0,0 +1,202 @@
+//! Nonces for webserve's own inline scripts under a Content-Security-Policy.
+//!
+//! A policy that restricts scripts, sent as a header (`--header`, `[headers]`) or in a
+//! `<meta http-equiv="Content-Security-Policy">` tag, blocks the injected reload script. So
+//! every HTML response carrying scripts marked [`SCRIPT_MARKER`] gets a fresh nonce: on
+//! those `<script>` tags, and as `'nonce-…'` in the directive that governs script elements
+//! (`script-src-elem`, else `script-src`, else `default-src`) of the header and meta policies.
+//! The page's own scripts get no nonce, so the policy still applies to them. A directive
+//! that allows `'unsafe-inline'` without any nonce or hash is left alone: adding a nonce
+//! would switch `'unsafe-inline'` off.
+//!
+//! A nonced body differs on every response, so it is sent without `ETag` and
+//! `Last-Modified`, and browsers refetch it instead of revalidating a stale nonce.
+
+use actix_web::body::{self, EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{error, web, Error};
+use regex::Regex;
+use std::sync::OnceLock;
+use tracing::trace;
+
+use crate::events::generate_token;
+use crate::AppState;
+
+/// Opening tag of the scripts webserve adds to pages; only these get the nonce.
+pub const SCRIPT_MARKER: &str = "<script data-webserve>";
+
+/// Larger bodies are sent unchanged rather than buffered whole.
+pub const CSP_MAX_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Headers whose policies get the nonce.
+const POLICY_HEADERS: [HeaderName; 2] = [
+    header::CONTENT_SECURITY_POLICY,
+    header::CONTENT_SECURITY_POLICY_REPORT_ONLY,
+];
+
+/// Directives consulted for `<script>` elements, most specific first.
+const SCRIPT_DIRECTIVES: [&str; 3] = ["script-src-elem", "script-src", "default-src"];
+
+/// `policy` (one header value, possibly several comma-separated policies) with
+/// `'nonce-<nonce>'` added where scripts would otherwise be blocked; `None` when no
+/// policy restricts inline scripts.
+pub fn add_nonce(policy: &str, nonce: &str) -> Option<String> {
+    let mut changed = false;
+    let policies: Vec<String> = policy
+        .split(',')
+        .map(|policy| match nonce_policy(policy, nonce) {
+            Some(p) => {
+                changed = true;
+                p
+            }
+            None => policy.to_string(),
+        })
+        .collect();
+    changed.then(|| policies.join(","))
+}
+
+fn nonce_policy(policy: &str, nonce: &str) -> Option<String> {
+    let mut directives: Vec<String> = policy.split(';').map(str::to_string).collect();
+    let name = |d: &String| {
+        d.split_whitespace()
+            .next()
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default()
+    };
+    let governing = SCRIPT_DIRECTIVES
+        .iter()
+        .find_map(|wanted| directives.iter().position(|d| name(d) == *wanted))?;
+    let sources: Vec<String> = directives[governing]
+        .split_whitespace()
+        .skip(1)
+        .map(str::to_ascii_lowercase)
+        .collect();
+    let keyed = sources
+        .iter()
+        .any(|s| s.starts_with("'nonce-") || s.starts_with("'sha"));
+    if sources.iter().any(|s| s == "'unsafe-inline'") && !keyed {
+        return None;
+    }
+    let directive = &mut directives[governing];
+    let trailing = directive.len() - directive.trim_end().len();
+    directive.insert_str(directive.len() - trailing, &format!(" 'nonce-{}'", nonce));
+    Some(directives.join(";"))
+}
+
+/// `<meta http-equiv="Content-Security-Policy" content="...">` tags in `html` with the nonce
+/// added to their policy; `None` when there are none to change.
+fn nonce_meta_policies(html: &str, nonce: &str) -> Option<String> {
+    static META: OnceLock<Regex> = OnceLock::new();
+    static CONTENT: OnceLock<Regex> = OnceLock::new();
+    let meta = META.get_or_init(|| {
+        Regex::new(r#"(?i)<meta\b[^>]*\bhttp-equiv\s*=\s*["']?content-security-policy["']?[^>]*>"#)
+            .expect("valid regex")
+    });
+    // Policies quote keywords with `'`, so the attribute is double-quoted
+    let content = CONTENT
+        .get_or_init(|| Regex::new(r#"(?i)\bcontent\s*=\s*"([^"]*)""#).expect("valid regex"));
+    let mut changed = false;
+    let out = meta.replace_all(html, |tag: &regex::Captures| {
+        let tag = &tag[0];
+        let Some(caps) = content.captures(tag) else {
+            return tag.to_string();
+        };
+        let Some(policy) = add_nonce(&caps[1], nonce) else {
+            return tag.to_string();
+        };
+        changed = true;
+        let whole = caps.get(0).expect("whole match");
+        format!(
+            "{}content=\"{}\"{}",
+            &tag[..whole.start()],
+            policy,
+            &tag[whole.end()..]
+        )
+    });
+    changed.then(|| out.into_owned())
+}
+
+/// `html` and its policy headers with a fresh nonce; `None` when no policy needs one.
+fn nonce_page(
+    html: &str,
+    headers: &header::HeaderMap,
+) -> Option<(String, Vec<(HeaderName, HeaderValue)>)> {
+    let nonce = generate_token();
+    let policies: Vec<(HeaderName, HeaderValue)> = POLICY_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let policy = headers.get(name)?.to_str().ok()?;
+            let value = HeaderValue::from_str(&add_nonce(policy, &nonce)?).ok()?;
+            Some((name.clone(), value))
+        })
+        .collect();
+    let meta = nonce_meta_policies(html, &nonce);
+    if policies.is_empty() && meta.is_none() {
+        return None;
+    }
+    let html = meta.as_deref().unwrap_or(html).replace(
+        SCRIPT_MARKER,
+        &format!("<script data-webserve nonce=\"{}\">", nonce),
+    );
+    Some((html, policies))
+}
+
+fn html_ok<B: MessageBody>(res: &ServiceResponse<B>) -> bool {
+    let headers = res.headers();
+    let html = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/html"));
+    let size_ok = match res.response().body().size() {
+        body::BodySize::Sized(n) => n <= CSP_MAX_BYTES,
+        _ => false,
+    };
+    res.status() == StatusCode::OK
+        && html
+        && size_ok
+        && !headers.contains_key(header::CONTENT_ENCODING)
+}
+
+/// Middleware adding a per-response nonce to webserve's scripts and the page's policy.
+pub async fn csp_nonce<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let watch = req
+        .app_data::<web::Data<AppState>>()
+        .is_some_and(|data| data.watch);
+    if req.method() != Method::GET {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+    let res = next.call(req).await?;
+    // Without a policy header, only injected pages can carry a meta policy worth changing
+    let has_header = POLICY_HEADERS.iter().any(|h| res.headers().contains_key(h));
+    if !(watch || has_header) || !html_ok(&res) {
+        return Ok(res.map_into_left_body());
+    }
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(|e| error::ErrorInternalServerError(e.into()))?;
+    let nonced = std::str::from_utf8(&bytes)
+        .ok()
+        .filter(|html| html.contains(SCRIPT_MARKER))
+        .and_then(|html| nonce_page(html, res.headers()));
+    let Some((html, policies)) = nonced else {
+        let res = res.set_body(bytes).map_into_boxed_body();
+        return Ok(ServiceResponse::new(req, res).map_into_right_body());
+    };
+    trace!(path = %req.path(), "nonce added to webserve's scripts");
+    let headers = res.headers_mut();
+    for (name, value) in policies {
+        headers.insert(name, value);
+    }
+    headers.remove(header::ETAG);
+    headers.remove(header::LAST_MODIFIED);
+    let res = res.set_body(html).map_into_boxed_body();
+    Ok(ServiceResponse::new(req, res).map_into_right_body())
+}
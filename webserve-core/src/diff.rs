@@ -0,0 +1,278 @@
+//! `webserve diff <before> <after>`: what replacing one build output with another changes.
+//!
+//! Both directories are hashed like `/__webserve/manifest` (within `--max-depth` /
+//! `--max-entries`) and compared by URL path: files only in `after` are added, files only in
+//! `before` removed, and files whose SHA-256 differs changed, with their size delta. The
+//! report renders as text lines ([`text_line`]) or an HTML page ([`diff_report_html`]); the
+//! CLI prints the one and, with `--serve`, serves the other.
+//!
+//! Exit status follows `diff(1)`: `0` when the trees match, `1` when they differ, `2` when a
+//! directory cannot be read.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::conditional::FileHashCache;
+use crate::listing_lang::Locale;
+use crate::manifest::{build_manifest, Manifest, ManifestEntry};
+use crate::serve::html_escape;
+use crate::walk::WalkLimits;
+
+/// The trees hold the same files.
+pub const EXIT_IDENTICAL: i32 = 0;
+/// At least one file was added, removed or changed.
+pub const EXIT_DIFFERENT: i32 = 1;
+
+/// Hex digits of a hash shown in the text report.
+const SHORT_HASH: usize = 12;
+
+/// How a path differs between the trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Size and hash of a file on one side.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileVersion {
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// One added, removed or changed file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileDiff {
+    /// URL path, e.g. `/assets/app.js`.
+    pub path: String,
+    pub kind: DiffKind,
+    pub before: Option<FileVersion>,
+    pub after: Option<FileVersion>,
+}
+
+impl FileDiff {
+    /// Bytes gained (positive) or lost (negative).
+    pub fn size_delta(&self) -> i64 {
+        let size = |v: &Option<FileVersion>| v.as_ref().map_or(0, |v| v.size as i64);
+        size(&self.after) - size(&self.before)
+    }
+}
+
+/// Outcome of comparing two trees.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TreeDiff {
+    /// Differing files, sorted by path.
+    pub files: Vec<FileDiff>,
+    pub unchanged: usize,
+    /// True when `--max-depth` / `--max-entries` cut either walk short.
+    pub truncated: bool,
+}
+
+impl TreeDiff {
+    pub fn count(&self, kind: DiffKind) -> usize {
+        self.files.iter().filter(|f| f.kind == kind).count()
+    }
+
+    /// Total bytes gained (positive) or lost (negative).
+    pub fn size_delta(&self) -> i64 {
+        self.files.iter().map(FileDiff::size_delta).sum()
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        if self.files.is_empty() {
+            EXIT_IDENTICAL
+        } else {
+            EXIT_DIFFERENT
+        }
+    }
+
+    /// `2 added, 1 removed, 3 changed, 40 unchanged; size +1.2 KB`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} added, {} removed, {} changed, {} unchanged; size {}{}",
+            self.count(DiffKind::Added),
+            self.count(DiffKind::Removed),
+            self.count(DiffKind::Changed),
+            self.unchanged,
+            signed_size(self.size_delta()),
+            if self.truncated {
+                " (walk stopped at --max-depth/--max-entries)"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+fn version(entry: &ManifestEntry) -> FileVersion {
+    FileVersion {
+        size: entry.size,
+        sha256: entry.sha256.clone(),
+    }
+}
+
+/// Compares two manifests by path and content hash.
+pub fn diff_manifests(before: &Manifest, after: &Manifest) -> TreeDiff {
+    let mut paths: BTreeMap<&str, (Option<&ManifestEntry>, Option<&ManifestEntry>)> =
+        BTreeMap::new();
+    for entry in &before.files {
+        paths.entry(&entry.path).or_default().0 = Some(entry);
+    }
+    for entry in &after.files {
+        paths.entry(&entry.path).or_default().1 = Some(entry);
+    }
+    let mut diff = TreeDiff {
+        truncated: before.truncated || after.truncated,
+        ..Default::default()
+    };
+    for (path, sides) in paths {
+        let kind = match sides {
+            (Some(_), None) => DiffKind::Removed,
+            (None, Some(_)) => DiffKind::Added,
+            (Some(a), Some(b)) if a.sha256 != b.sha256 => DiffKind::Changed,
+            _ => {
+                diff.unchanged += 1;
+                continue;
+            }
+        };
+        diff.files.push(FileDiff {
+            path: path.to_string(),
+            kind,
+            before: sides.0.map(version),
+            after: sides.1.map(version),
+        });
+    }
+    diff
+}
+
+/// Hashes both directories within `limits` and compares them.
+pub fn diff_dirs(before: &Path, after: &Path, limits: WalkLimits) -> TreeDiff {
+    let hashes = FileHashCache::default();
+    diff_manifests(
+        &build_manifest(before, limits, &hashes),
+        &build_manifest(after, limits, &hashes),
+    )
+}
+
+/// `+1.2 KB`, `-300 B`, `0 B`.
+fn signed_size(delta: i64) -> String {
+    let sign = match delta {
+        d if d > 0 => "+",
+        d if d < 0 => "-",
+        _ => "",
+    };
+    format!("{}{}", sign, Locale::En.format_size(delta.unsigned_abs()))
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..hash.len().min(SHORT_HASH)]
+}
+
+/// One line of the text report.
+pub fn text_line(file: &FileDiff) -> String {
+    match (&file.before, &file.after) {
+        (Some(a), Some(b)) => format!(
+            "~ {}  {} -> {} ({}), sha256 {} -> {}",
+            file.path,
+            Locale::En.format_size(a.size),
+            Locale::En.format_size(b.size),
+            signed_size(file.size_delta()),
+            short(&a.sha256),
+            short(&b.sha256)
+        ),
+        (None, Some(v)) | (Some(v), None) => format!(
+            "{} {}  {}, sha256 {}",
+            if file.kind == DiffKind::Added {
+                '+'
+            } else {
+                '-'
+            },
+            file.path,
+            Locale::En.format_size(v.size),
+            short(&v.sha256)
+        ),
+        (None, None) => String::new(),
+    }
+}
+
+/// Standalone HTML page listing the differing files of `diff` between `before` and `after`.
+pub fn diff_report_html(before: &str, after: &str, diff: &TreeDiff) -> String {
+    let hash_cell = |v: &Option<FileVersion>| match v {
+        Some(v) => format!(
+            r#"<td class="hash" title="{}">{}</td>"#,
+            html_escape(&v.sha256),
+            html_escape(short(&v.sha256))
+        ),
+        None => r#"<td class="hash">—</td>"#.to_string(),
+    };
+    let size_cell = |v: &Option<FileVersion>| match v {
+        Some(v) => format!(
+            r#"<td class="size">{}</td>"#,
+            Locale::En.format_size(v.size)
+        ),
+        None => r#"<td class="size">—</td>"#.to_string(),
+    };
+    let rows: String = diff
+        .files
+        .iter()
+        .map(|file| {
+            let kind = match file.kind {
+                DiffKind::Added => "added",
+                DiffKind::Removed => "removed",
+                DiffKind::Changed => "changed",
+            };
+            format!(
+                r#"<tr class="{0}"><td>{0}</td><td class="path">{1}</td>{2}{3}{4}{5}<td class="size">{6}</td></tr>"#,
+                kind,
+                html_escape(&file.path),
+                size_cell(&file.before),
+                size_cell(&file.after),
+                hash_cell(&file.before),
+                hash_cell(&file.after),
+                signed_size(file.size_delta())
+            )
+        })
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>webserve diff</title>
+  <style>
+    body {{ margin: 2rem; font-family: system-ui, sans-serif; font-size: 14px; color: #18181b; background: #f4f4f5; }}
+    h1 {{ font-size: 1.1rem; margin: 0 0 0.25rem 0; }}
+    p {{ color: #71717a; margin: 0 0 1rem 0; }}
+    code, td.path, td.hash {{ font-family: ui-monospace, monospace; }}
+    table {{ width: 100%; border-collapse: collapse; background: #fff; border: 1px solid #e4e4e7; }}
+    th, td {{ text-align: left; padding: 0.45rem 0.75rem; border-bottom: 1px solid #e4e4e7; }}
+    th {{ font-weight: 500; font-size: 0.75rem; text-transform: uppercase; color: #71717a; }}
+    td.size {{ white-space: nowrap; }}
+    tr.added td:first-child {{ color: #15803d; }}
+    tr.removed td:first-child {{ color: #b91c1c; }}
+    tr.changed td:first-child {{ color: #b45309; }}
+  </style>
+</head>
+<body>
+  <h1><code>{before}</code> → <code>{after}</code></h1>
+  <p>{summary}</p>
+  <table>
+    <thead>
+      <tr><th>Change</th><th>Path</th><th>Size before</th><th>Size after</th><th>SHA-256 before</th><th>SHA-256 after</th><th>Delta</th></tr>
+    </thead>
+    <tbody>
+      {rows}
+    </tbody>
+  </table>
+</body>
+</html>"#,
+        before = html_escape(before),
+        after = html_escape(after),
+        summary = html_escape(&diff.summary()),
+        rows = rows,
+    )
+}
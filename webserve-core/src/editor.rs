@@ -0,0 +1,271 @@
+//! `--open-in-editor`: `/__webserve/open-in-editor?file=...&line=...` opens a file in the
+//! developer's editor.
+//!
+//! The editor is `$VISUAL`, then `$EDITOR`, then VS Code (`code`); known editors get their
+//! own jump-to-line syntax (`code --goto f:12:3`, `subl f:12:3`, `idea --line 12 f`,
+//! `vim +12 f`). `file` is a URL path under the served root (what a stack trace shows) or a
+//! path relative to the working directory; anything resolving outside both is refused. Only
+//! loopback clients are served, and the request must say it comes from the page itself
+//! (`Sec-Fetch-Site: same-origin`, or a matching `Origin`), so another site cannot open
+//! files on the developer's machine. `Host` must name this machine too (`localhost`, a
+//! loopback address or the bound one), which refuses pages reaching the server through DNS
+//! rebinding. Requests over a Unix socket or without either header, such as `curl`'s, are
+//! refused.
+//!
+//! The error overlay injected with the reload script links stack frames here.
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use tracing::{info, warn};
+
+use crate::path::{host_is_local, join_serve_path, normalize_url_path};
+use crate::upload::origin_matches_host;
+use crate::AppState;
+
+/// Editor used when neither `$VISUAL` nor `$EDITOR` is set.
+pub const DEFAULT_EDITOR: &str = "code";
+
+/// Error overlay appended to the reload script under `--open-in-editor`.
+///
+/// Uncaught errors and unhandled rejections show their stack; frames from this origin link
+/// to the editor endpoint with the frame's URL path, line and column. `__WEBSERVE_BASE__`
+/// becomes the site's [`Mount::base`](crate::Mount::base).
+pub const ERROR_OVERLAY_SCRIPT: &str = r#"<script data-webserve>
+(function(){
+  var endpoint = new URL("__WEBSERVE_BASE__/__webserve/open-in-editor", location.href);
+  var box;
+  function el(tag, css, text){
+    var e = document.createElement(tag);
+    if (css) e.style.cssText = css;
+    if (text) e.textContent = text;
+    return e;
+  }
+  function close(){ if (box) { box.remove(); box = null; } }
+  function frame(line){
+    var m = /(https?:\/\/[^\s()]+):(\d+):(\d+)/.exec(line);
+    if (!m) return el("div", "", line);
+    var src = new URL(m[1]);
+    if (src.origin !== location.origin) return el("div", "", line);
+    var a = el("a", "color:#7cc4ff;cursor:pointer;text-decoration:underline", line.trim());
+    a.title = "Open in editor";
+    a.addEventListener("click", function(){
+      var u = new URL(endpoint);
+      u.searchParams.set("file", decodeURIComponent(src.pathname));
+      u.searchParams.set("line", m[2]);
+      u.searchParams.set("column", m[3]);
+      fetch(u, { cache: "no-store" }).then(function(r){
+        if (!r.ok) r.text().then(function(t){ console.warn("open-in-editor:", r.status, t); });
+      });
+    });
+    var d = el("div");
+    d.appendChild(a);
+    return d;
+  }
+  function show(message, stack){
+    close();
+    box = el("div", "position:fixed;inset:0;z-index:2147483647;overflow:auto;padding:2rem;" +
+      "background:rgba(20,20,24,.92);color:#eee;font:13px/1.5 ui-monospace,monospace");
+    box.id = "webserve-error-overlay";
+    var x = el("button", "float:right;background:none;border:1px solid #888;color:#eee;" +
+      "cursor:pointer;padding:.2rem .6rem", "Close");
+    x.addEventListener("click", close);
+    box.appendChild(x);
+    box.appendChild(el("div", "color:#ff6b6b;font-size:15px;white-space:pre-wrap;margin-bottom:1rem", message));
+    (stack || "").split("\n").forEach(function(l){ if (l.trim()) box.appendChild(frame(l)); });
+    document.body.appendChild(box);
+  }
+  addEventListener("keydown", function(e){ if (e.key === "Escape") close(); });
+  addEventListener("error", function(e){
+    var err = e.error;
+    var stack = err && err.stack ? err.stack : (e.filename ? "at " + e.filename + ":" + e.lineno + ":" + e.colno : "");
+    show(String(err || e.message), stack);
+  });
+  addEventListener("unhandledrejection", function(e){
+    var err = e.reason;
+    show("Unhandled rejection: " + String(err), err && err.stack ? err.stack : "");
+  });
+})();
+</script>"#;
+
+#[derive(Debug, Deserialize)]
+pub struct OpenQuery {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+/// `$VISUAL`, `$EDITOR` or [`DEFAULT_EDITOR`].
+pub fn configured_editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string())
+}
+
+/// Command opening `file` at `line`/`column` in `editor` (a program plus optional arguments,
+/// e.g. `"code -r"`); `None` for an empty editor.
+pub fn editor_command(
+    editor: &str,
+    file: &Path,
+    line: Option<u32>,
+    column: Option<u32>,
+) -> Option<Command> {
+    let mut words = editor.split_whitespace();
+    let program = words.next()?;
+    let mut command = Command::new(program);
+    command.args(words);
+    let name = Path::new(program)
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or(program)
+        .to_ascii_lowercase();
+    let position = |line: u32| {
+        let mut target = file.as_os_str().to_os_string();
+        target.push(format!(":{}", line));
+        if let Some(column) = column {
+            target.push(format!(":{}", column));
+        }
+        target
+    };
+    match (name.as_str(), line) {
+        (_, None) => {
+            command.arg(file);
+        }
+        ("code" | "code-insiders" | "codium" | "vscodium" | "cursor" | "windsurf", Some(line)) => {
+            command.arg("--goto").arg(position(line));
+        }
+        ("subl" | "sublime_text" | "zed" | "hx" | "helix", Some(line)) => {
+            command.arg(position(line));
+        }
+        (
+            "idea" | "webstorm" | "phpstorm" | "pycharm" | "goland" | "rubymine" | "clion"
+            | "rustrover" | "rider",
+            Some(line),
+        ) => {
+            command.arg("--line").arg(line.to_string()).arg(file);
+        }
+        (_, Some(line)) => {
+            command.arg(format!("+{}", line)).arg(file);
+        }
+    }
+    Some(command)
+}
+
+/// Existing file named by `file`: a URL path under `root`, else a path relative to `cwd`
+/// (or absolute). `Err(403)` when it resolves outside both, `Err(404)` when there is none.
+pub fn resolve_editor_file(root: &Path, cwd: &Path, file: &str) -> Result<PathBuf, u16> {
+    let mut candidates = Vec::new();
+    if let Some(candidate) =
+        normalize_url_path(file).and_then(|normalized| join_serve_path(root, &normalized))
+    {
+        candidates.push(candidate);
+    }
+    candidates.push(cwd.join(file));
+    let allowed: Vec<PathBuf> = [root, cwd]
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+    let mut outside = false;
+    for candidate in candidates {
+        let Ok(canonical) = candidate.canonicalize() else {
+            continue;
+        };
+        if !canonical.is_file() {
+            continue;
+        }
+        if allowed.iter().any(|dir| canonical.starts_with(dir)) {
+            return Ok(canonical);
+        }
+        outside = true;
+    }
+    Err(if outside { 403 } else { 404 })
+}
+
+/// True when the request may open files: a loopback peer, a `Host` naming this machine
+/// (see [`host_is_local`]; `bound` is [`AppState::addr`]), and a browser saying the request
+/// comes from the page itself (`Sec-Fetch-Site: same-origin` or `none`, or without that
+/// header an `Origin` naming the `Host`). Unknown peers and requests saying neither are
+/// refused.
+fn is_local(req: &HttpRequest, bound: &str) -> bool {
+    let loopback = req
+        .peer_addr()
+        .is_some_and(|addr| addr.ip().to_canonical().is_loopback());
+    let same_site = match req
+        .headers()
+        .get("sec-fetch-site")
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(site) => site == "same-origin" || site == "none",
+        None => origin_matches_host(req) == Some(true),
+    };
+    let host = req
+        .headers()
+        .get("host")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|host| host_is_local(host, bound));
+    loopback && host && same_site && origin_matches_host(req) != Some(false)
+}
+
+fn refuse(status: u16, message: &str) -> HttpResponse {
+    HttpResponse::build(actix_web::http::StatusCode::from_u16(status).expect("valid status code"))
+        .json(serde_json::json!({ "error": message }))
+}
+
+/// `GET /__webserve/open-in-editor`: opens `file` at `line`/`column` under `--open-in-editor`;
+/// `204` once the editor is started.
+pub async fn open_in_editor(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<OpenQuery>,
+) -> HttpResponse {
+    if !data.settings.load().open_in_editor {
+        return HttpResponse::NotFound().finish();
+    }
+    if !is_local(&req, &data.addr) {
+        warn!(peer = ?req.peer_addr(), "open-in-editor: refused non-local request");
+        return refuse(
+            403,
+            "open-in-editor only answers same-origin requests from localhost",
+        );
+    }
+    let Some(file) = query.file.as_deref().filter(|f| !f.is_empty()) else {
+        return refuse(400, "missing ?file=");
+    };
+    // Frames of a `--mount` site carry its prefix
+    let file = file
+        .strip_prefix(data.mount.base())
+        .filter(|rest| rest.starts_with('/'))
+        .unwrap_or(file);
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let path = match resolve_editor_file(&data.static_dir, &cwd, file) {
+        Ok(path) => path,
+        Err(403) => return refuse(403, "file is outside the served root and working directory"),
+        Err(_) => return refuse(404, "no such file"),
+    };
+    let editor = configured_editor();
+    let Some(mut command) = editor_command(&editor, &path, query.line, query.column) else {
+        return refuse(500, "no editor configured");
+    };
+    match command.stdin(Stdio::null()).spawn() {
+        Ok(mut child) => {
+            info!(
+                "open-in-editor: {} {}{}",
+                editor,
+                path.display(),
+                query.line.map(|l| format!(":{}", l)).unwrap_or_default()
+            );
+            // Reap the editor's launcher without blocking the request
+            thread::spawn(move || child.wait());
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            warn!("open-in-editor: cannot run `{}`: {}", editor, e);
+            refuse(500, &format!("cannot run `{}`: {}", editor, e))
+        }
+    }
+}
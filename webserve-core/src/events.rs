@@ -0,0 +1,205 @@
+//! `/__webserve/events`: a Server-Sent Events stream of what the server is doing.
+//!
+//! Every request, file change, reload broadcast and server-side error is published as a
+//! JSON [`ServerEvent`], so dashboards and editor plugins can follow a running instance:
+//!
+//! ```text
+//! id: 7
+//! event: change
+//! data: {"type":"change","path":"/index.html","kind":"modified","time":1718000000000}
+//! ```
+//!
+//! The stream needs the token from `--events-token` (or the one generated and logged at
+//! startup) as `Authorization: Bearer <token>` or, for `EventSource`, `?token=<token>`.
+//! Slow clients skip the events they missed; an idle stream gets a comment line every
+//! [`KEEPALIVE`].
+
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::history::{unix_millis, ChangeKind, ReloadChange};
+use crate::AppState;
+
+/// Events buffered per subscriber before a slow one starts skipping.
+pub const EVENT_BUFFER: usize = 256;
+
+/// Comment line sent on an idle stream so proxies keep it open.
+pub const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Something that happened in the server.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ServerEvent {
+    /// A request was answered (`/reload` polls and `/__webserve/` are left out).
+    Request {
+        method: String,
+        path: String,
+        status: u16,
+        duration_ms: f64,
+    },
+    /// The watcher (or an upload) changed a file.
+    Change { path: String, kind: ChangeKind },
+    /// Live-reload clients were told to refresh.
+    Reload {
+        seq: u64,
+        paths: Vec<String>,
+        changes: Vec<ReloadChange>,
+    },
+    /// A `5xx` response or a failed `--exec` build.
+    Error { message: String },
+}
+
+impl ServerEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            ServerEvent::Request { .. } => "request",
+            ServerEvent::Change { .. } => "change",
+            ServerEvent::Reload { .. } => "reload",
+            ServerEvent::Error { .. } => "error",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Stamped<'a> {
+    #[serde(flatten)]
+    event: &'a ServerEvent,
+    /// Unix milliseconds.
+    time: u64,
+}
+
+/// Fan-out of [`ServerEvent`]s to `/__webserve/events` subscribers and in-process listeners
+/// (see [`crate::ServerHandle::events`]).
+#[derive(Debug)]
+pub struct EventBus {
+    tx: broadcast::Sender<Bytes>,
+    typed: broadcast::Sender<ServerEvent>,
+    next_id: AtomicU64,
+    token: String,
+}
+
+impl Default for EventBus {
+    /// A bus with a freshly generated token.
+    fn default() -> Self {
+        EventBus::new(generate_token())
+    }
+}
+
+impl EventBus {
+    pub fn new(token: impl Into<String>) -> Self {
+        let (tx, _rx) = broadcast::channel(EVENT_BUFFER);
+        let (typed, _rx) = broadcast::channel(EVENT_BUFFER);
+        EventBus {
+            tx,
+            typed,
+            next_id: AtomicU64::new(1),
+            token: token.into(),
+        }
+    }
+
+    /// Token subscribers must present.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Sends `event` to every subscriber; a no-op (not even serialized) without any.
+    pub fn emit(&self, event: ServerEvent) {
+        if self.typed.receiver_count() > 0 {
+            let _ = self.typed.send(event.clone());
+        }
+        if self.tx.receiver_count() == 0 {
+            return;
+        }
+        let data = serde_json::to_string(&Stamped {
+            event: &event,
+            time: unix_millis(),
+        })
+        .unwrap_or_default();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let frame = format!("id: {}\nevent: {}\ndata: {}\n\n", id, event.name(), data);
+        let _ = self.tx.send(Bytes::from(frame));
+    }
+
+    /// SSE frames, as sent on `/__webserve/events`.
+    pub fn subscribe(&self) -> broadcast::Receiver<Bytes> {
+        self.tx.subscribe()
+    }
+
+    /// The events themselves, for listeners in the same process.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ServerEvent> {
+        self.typed.subscribe()
+    }
+
+    /// True when `candidate` is the token; compares every byte regardless of where they differ.
+    pub fn authorized(&self, candidate: &str) -> bool {
+        candidate.len() == self.token.len()
+            && candidate
+                .bytes()
+                .zip(self.token.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// 32 hex digits from the process's randomly seeded hasher keys.
+pub fn generate_token() -> String {
+    let half = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(unix_millis() as u128 ^ std::process::id() as u128);
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", half(), half())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    pub token: Option<String>,
+}
+
+fn bearer(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// `GET /__webserve/events`: the event stream, for holders of the events token.
+pub async fn event_stream(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<EventsQuery>,
+) -> HttpResponse {
+    let token = bearer(&req).or(query.token.as_deref()).unwrap_or_default();
+    if !data.events.authorized(token) {
+        return HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer realm=\"webserve events\""))
+            .json(serde_json::json!({ "error": "events need the token from --events-token" }));
+    }
+    let rx = data.events.subscribe();
+    let hello = futures::stream::once(async {
+        Ok::<_, actix_web::Error>(Bytes::from_static(b"retry: 3000\n: connected\n\n"))
+    });
+    let frames = futures::stream::unfold(rx, |mut rx| async move {
+        let frame = match tokio::time::timeout(KEEPALIVE, rx.recv()).await {
+            Ok(Ok(frame)) => frame,
+            Ok(Err(RecvError::Lagged(n))) => Bytes::from(format!(": skipped {} events\n\n", n)),
+            Ok(Err(RecvError::Closed)) => return None,
+            Err(_) => Bytes::from_static(b": keep-alive\n\n"),
+        };
+        Some((Ok::<_, actix_web::Error>(frame), rx))
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(futures::StreamExt::chain(hello, frames))
+}
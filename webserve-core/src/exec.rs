@@ -0,0 +1,225 @@
+//! `--exec "npm run build"`: a build step between a watcher batch and the reload broadcast.
+//!
+//! Each batch of source changes runs the command through the shell (`sh -c`, `cmd /C` on
+//! Windows) with its output going straight to the console. Browsers reload only when it
+//! exits successfully. A failed build keeps the last good output on disk and hands back a
+//! [`BuildError`] with the tail of its output, which live-reload clients show as an overlay.
+//!
+//! The build usually writes into the watched tree. Events seen while it runs (and for
+//! [`WATCH_DEBOUNCE`] after) are its own output: they are held back and published with the
+//! reload instead of starting another build.
+
+use notify::Event;
+use serde::Serialize;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::watch::{is_relevant, WATCH_DEBOUNCE};
+
+/// Bytes of build output kept for the error overlay (the end of it).
+pub const MAX_BUILD_OUTPUT: usize = 64 * 1024;
+
+/// A failed `--exec` run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildError {
+    pub command: String,
+    /// Exit status, or why the command could not start.
+    pub status: String,
+    /// Last [`MAX_BUILD_OUTPUT`] bytes of stdout and stderr, without terminal colors.
+    pub output: String,
+}
+
+impl BuildError {
+    /// One-line summary for logs and the event stream.
+    pub fn message(&self) -> String {
+        format!("exec: `{}` failed ({})", self.command, self.status)
+    }
+}
+
+/// `text` without ANSI escape sequences (colors, cursor movement).
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in `@`..=`~`
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ST
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Copies `source` to `sink` as it arrives, keeping the last [`MAX_BUILD_OUTPUT`] bytes.
+fn tee(
+    mut source: impl Read + Send + 'static,
+    mut sink: impl Write + Send + 'static,
+    captured: Arc<Mutex<Vec<u8>>>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        while let Ok(n) = source.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let _ = sink.write_all(&buf[..n]);
+            let _ = sink.flush();
+            let mut captured = captured.lock().unwrap();
+            captured.extend_from_slice(&buf[..n]);
+            if captured.len() > 2 * MAX_BUILD_OUTPUT {
+                let excess = captured.len() - MAX_BUILD_OUTPUT;
+                captured.drain(..excess);
+            }
+        }
+    })
+}
+
+/// Shell command run for each watcher batch before browsers reload.
+#[derive(Debug, Default)]
+pub struct BuildCommand {
+    command: String,
+    running: AtomicBool,
+    /// Events produced by the running build.
+    held: Mutex<Vec<Event>>,
+}
+
+impl BuildCommand {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    fn shell(&self) -> Command {
+        let mut shell = if cfg!(windows) {
+            let mut c = Command::new("cmd");
+            c.arg("/C");
+            c
+        } else {
+            let mut c = Command::new("sh");
+            c.arg("-c");
+            c
+        };
+        shell.arg(&self.command);
+        shell
+    }
+
+    fn failure(&self, status: String, output: &[u8]) -> BuildError {
+        let tail = &output[output.len().saturating_sub(MAX_BUILD_OUTPUT)..];
+        let mut text = strip_ansi(&String::from_utf8_lossy(tail));
+        if tail.len() < output.len() {
+            text.insert_str(0, "...\n");
+        }
+        BuildError {
+            command: self.command.clone(),
+            status,
+            output: text,
+        }
+    }
+
+    /// Runs the command to completion, echoing its output to the console as it comes.
+    pub fn run(&self) -> Result<(), BuildError> {
+        let started = Instant::now();
+        info!("exec: {}", self.command);
+        let mut child = match self
+            .shell()
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("exec: cannot run `{}`: {}; not reloading", self.command, e);
+                return Err(self.failure(format!("cannot run: {}", e), &[]));
+            }
+        };
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let readers = [
+            child
+                .stdout
+                .take()
+                .map(|out| tee(out, io::stdout(), captured.clone())),
+            child
+                .stderr
+                .take()
+                .map(|err| tee(err, io::stderr(), captured.clone())),
+        ];
+        let status = child.wait();
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
+        }
+        let output = std::mem::take(&mut *captured.lock().unwrap());
+        match status {
+            Ok(status) if status.success() => {
+                info!("exec: finished in {:.1}s", started.elapsed().as_secs_f32());
+                Ok(())
+            }
+            Ok(status) => {
+                warn!(
+                    "exec: `{}` failed ({}); not reloading",
+                    self.command, status
+                );
+                Err(self.failure(status.to_string(), &output))
+            }
+            Err(e) => {
+                warn!("exec: lost `{}`: {}; not reloading", self.command, e);
+                Err(self.failure(e.to_string(), &output))
+            }
+        }
+    }
+
+    /// Watcher side: true when `event` was produced by the running build and is held back.
+    pub fn absorb(&self, event: &notify::Result<Event>) -> bool {
+        if !self.running.load(Ordering::SeqCst) {
+            return false;
+        }
+        if let Ok(event) = event {
+            if is_relevant(event) {
+                self.held.lock().unwrap().push(event.clone());
+            }
+        }
+        true
+    }
+
+    /// Builds for `batch`; on success returns it together with the build's own events.
+    pub fn build(&self, mut batch: Vec<Event>) -> Result<Vec<Event>, BuildError> {
+        self.running.store(true, Ordering::SeqCst);
+        let result = self.run();
+        // Late events from the build's last writes still belong to it
+        thread::sleep(WATCH_DEBOUNCE);
+        self.running.store(false, Ordering::SeqCst);
+        let held = std::mem::take(&mut *self.held.lock().unwrap());
+        result.map(|()| {
+            batch.extend(held);
+            batch
+        })
+    }
+}
@@ -0,0 +1,122 @@
+//! `--fault-injection`: per-request misbehavior requested by the client.
+//!
+//! With the flag on, a request carrying `X-Webserve-Fault: delay=2s` is held before being
+//! handled, and `status=503` answers with that status instead of running the handler.
+//! Directives combine with commas (`delay=500ms, status=502`). Without the flag the header
+//! is ignored, so a stray header never changes a normal server's behavior.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use std::time::Duration;
+
+use crate::AppState;
+
+/// Request header carrying fault directives.
+pub const FAULT_HEADER: &str = "x-webserve-fault";
+
+/// Parsed `X-Webserve-Fault` directives.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fault {
+    /// Wait this long before answering.
+    pub delay: Option<Duration>,
+    /// Answer with this status instead of the real response.
+    pub status: Option<StatusCode>,
+}
+
+impl Fault {
+    /// Parses `delay=2s, status=503`; unknown directives are an error.
+    pub fn parse(spec: &str) -> Result<Fault, String> {
+        let mut fault = Fault::default();
+        for part in spec
+            .split([',', ';'])
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("fault directive expects key=value, got {}", part))?;
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "delay" => fault.delay = Some(parse_duration(value)?),
+                "status" => {
+                    let status = value
+                        .parse::<u16>()
+                        .ok()
+                        .and_then(|s| StatusCode::from_u16(s).ok())
+                        .ok_or_else(|| format!("invalid fault status {}", value))?;
+                    fault.status = Some(status);
+                }
+                other => return Err(format!("unknown fault directive {}", other)),
+            }
+        }
+        Ok(fault)
+    }
+}
+
+/// `250ms`, `2s`, `1.5s`; a bare number is milliseconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration {}", value);
+    let (number, scale) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(s) = value.strip_suffix('s') {
+        (s, 1.0)
+    } else {
+        (value, 0.001)
+    };
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs_f64(number * scale))
+}
+
+/// Inverse of [`parse_duration`]: `2s` for whole seconds, else `250ms`.
+pub fn format_duration(duration: Duration) -> String {
+    let ms = duration.as_millis();
+    if ms.is_multiple_of(1000) {
+        format!("{}s", ms / 1000)
+    } else {
+        format!("{}ms", ms)
+    }
+}
+
+/// Middleware applying `X-Webserve-Fault` when fault injection is enabled.
+pub async fn inject_fault(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let enabled = req
+        .app_data::<web::Data<AppState>>()
+        .is_some_and(|data| data.settings.load().fault_injection);
+    let spec = req
+        .headers()
+        .get(FAULT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| enabled)
+        .map(str::to_string);
+    let Some(spec) = spec else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let fault = match Fault::parse(&spec) {
+        Ok(fault) => fault,
+        Err(e) => {
+            let (req, _) = req.into_parts();
+            let res = HttpResponse::BadRequest().body(e);
+            return Ok(ServiceResponse::new(req, res).map_into_right_body());
+        }
+    };
+    if let Some(delay) = fault.delay {
+        tokio::time::sleep(delay).await;
+    }
+    match fault.status {
+        Some(status) => {
+            let (req, _) = req.into_parts();
+            let res = HttpResponse::build(status).body(format!("injected fault: {}", status));
+            Ok(ServiceResponse::new(req, res).map_into_right_body())
+        }
+        None => Ok(next.call(req).await?.map_into_left_body()),
+    }
+}
@@ -0,0 +1,274 @@
+//! `--fixtures <dir>`: canned responses declared in YAML, checked before the filesystem.
+//!
+//! Every `*.yaml` / `*.yml` file in the directory holds a `routes` list:
+//!
+//! ```yaml
+//! routes:
+//!   - path: /api/users        # exact, or a prefix ending in `*`
+//!     method: GET             # optional; any method when omitted
+//!     status: 200             # default 200
+//!     headers: { X-Total: "2" }
+//!     json: [{ id: 1 }, { id: 2 }]   # or `body: "text"` / `body_file: users.json`
+//!     delay_ms: 300           # optional latency
+//! ```
+//!
+//! Files load in name order and the first matching route wins.
+//!
+//! A file-level `scenario: error-state` (or a per-route `scenario`) puts routes in a named
+//! scenario. Scenario routes only answer while that scenario is active — per request via the
+//! `X-Webserve-Scenario` header, or globally via `PUT /__webserve/scenario` — and are tried
+//! before the routes that have no scenario.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use bytes::Bytes;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FixtureFile {
+    scenario: Option<String>,
+    #[serde(default)]
+    routes: Vec<RouteSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteSpec {
+    path: String,
+    method: Option<String>,
+    status: Option<u16>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+    body_file: Option<String>,
+    json: Option<serde_yaml::Value>,
+    delay_ms: Option<u64>,
+    scenario: Option<String>,
+}
+
+/// Request header selecting a fixture scenario for that request only.
+pub const SCENARIO_HEADER: &str = "x-webserve-scenario";
+
+/// One compiled fixture route.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub path: String,
+    pub method: Option<Method>,
+    pub status: StatusCode,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub body: Bytes,
+    pub delay: Option<Duration>,
+    /// Named scenario this route belongs to; `None` routes are always active.
+    pub scenario: Option<String>,
+}
+
+/// All fixture routes, in match order.
+#[derive(Debug, Clone, Default)]
+pub struct Fixtures {
+    routes: Vec<Fixture>,
+}
+
+impl Fixture {
+    /// True when this route answers `method` + `path`.
+    pub fn matches(&self, method: &Method, path: &str) -> bool {
+        if let Some(ref m) = self.method {
+            let head_as_get = *method == Method::HEAD && *m == Method::GET;
+            if m != method && !head_as_get {
+                return false;
+            }
+        }
+        match self.path.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => self.path == path,
+        }
+    }
+
+    /// Builds the canned response.
+    pub fn response(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status);
+        for (name, value) in &self.headers {
+            res.insert_header((name.clone(), value.clone()));
+        }
+        res.body(self.body.clone())
+    }
+}
+
+impl Fixtures {
+    pub fn new(routes: Vec<Fixture>) -> Fixtures {
+        Fixtures { routes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Methods the routes answer; `None` when a route answers any method.
+    pub fn methods(&self) -> Option<Vec<Method>> {
+        self.routes.iter().map(|f| f.method.clone()).collect()
+    }
+
+    /// Loads every YAML file in `dir` (sorted by file name).
+    pub fn load_dir(dir: &Path) -> Result<Fixtures, String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("cannot read fixtures {}: {}", dir.display(), e))?;
+        let mut files: Vec<_> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.is_file()
+                    && p.extension()
+                        .is_some_and(|ext| ext == "yaml" || ext == "yml")
+            })
+            .collect();
+        files.sort();
+        let mut routes = Vec::new();
+        for file in files {
+            let contents = std::fs::read_to_string(&file)
+                .map_err(|e| format!("cannot read {}: {}", file.display(), e))?;
+            let parsed = Fixtures::parse(&contents, dir)
+                .map_err(|e| format!("{}: {}", file.display(), e))?;
+            routes.extend(parsed.routes);
+        }
+        Ok(Fixtures { routes })
+    }
+
+    /// Parses one YAML document; `body_file` paths resolve against `base`.
+    pub fn parse(contents: &str, base: &Path) -> Result<Fixtures, String> {
+        let file: FixtureFile = serde_yaml::from_str(contents).map_err(|e| e.to_string())?;
+        let file_scenario = file.scenario;
+        let routes = file
+            .routes
+            .into_iter()
+            .map(|mut spec| {
+                spec.scenario = spec.scenario.or_else(|| file_scenario.clone());
+                compile(spec, base)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Fixtures { routes })
+    }
+
+    /// First route answering `method` + `path`: the active scenario's routes, then the rest.
+    pub fn find(&self, method: &Method, path: &str, scenario: Option<&str>) -> Option<&Fixture> {
+        let in_scenario = scenario.and_then(|name| {
+            self.routes
+                .iter()
+                .find(|f| f.scenario.as_deref() == Some(name) && f.matches(method, path))
+        });
+        in_scenario.or_else(|| {
+            self.routes
+                .iter()
+                .find(|f| f.scenario.is_none() && f.matches(method, path))
+        })
+    }
+
+    /// Scenario names declared across all files, sorted.
+    pub fn scenarios(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .routes
+            .iter()
+            .filter_map(|f| f.scenario.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+fn compile(spec: RouteSpec, base: &Path) -> Result<Fixture, String> {
+    let path = spec.path;
+    let method = spec
+        .method
+        .map(|m| Method::from_bytes(m.to_ascii_uppercase().as_bytes()))
+        .transpose()
+        .map_err(|_| format!("{}: invalid method", path))?;
+    let status = StatusCode::from_u16(spec.status.unwrap_or(200))
+        .map_err(|_| format!("{}: invalid status", path))?;
+    let mut headers = Vec::new();
+    for (name, value) in spec.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("{}: invalid header name {}", path, name))?;
+        let value = HeaderValue::from_str(&value)
+            .map_err(|_| format!("{}: invalid value for header {}", path, name))?;
+        headers.push((name, value));
+    }
+    let sources = [
+        spec.body.is_some(),
+        spec.body_file.is_some(),
+        spec.json.is_some(),
+    ];
+    if sources.iter().filter(|s| **s).count() > 1 {
+        return Err(format!("{}: use only one of body, body_file, json", path));
+    }
+    let (body, default_type) = if let Some(json) = spec.json {
+        let text = serde_json::to_vec(&json).map_err(|e| format!("{}: {}", path, e))?;
+        (Bytes::from(text), Some("application/json"))
+    } else if let Some(file) = spec.body_file {
+        let full = base.join(&file);
+        let data = std::fs::read(&full)
+            .map_err(|e| format!("{}: cannot read {}: {}", path, full.display(), e))?;
+        (Bytes::from(data), None)
+    } else {
+        (
+            Bytes::from(spec.body.unwrap_or_default()),
+            Some("text/plain; charset=utf-8"),
+        )
+    };
+    if let Some(ct) = default_type {
+        if !headers.iter().any(|(n, _)| n == CONTENT_TYPE) && !body.is_empty() {
+            headers.push((CONTENT_TYPE, HeaderValue::from_static(ct)));
+        }
+    }
+    Ok(Fixture {
+        path,
+        method,
+        status,
+        headers,
+        body,
+        delay: spec.delay_ms.map(Duration::from_millis),
+        scenario: spec.scenario,
+    })
+}
+
+/// Middleware answering requests from the current fixtures before any route runs.
+pub async fn serve_fixtures(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let fixture = req.app_data::<web::Data<AppState>>().and_then(|data| {
+        let settings = data.settings.load();
+        let fixtures = settings.fixtures.as_ref()?;
+        let scenario = req
+            .headers()
+            .get(SCENARIO_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .or_else(|| data.active_scenario());
+        fixtures
+            .find(req.method(), req.path(), scenario.as_deref())
+            .cloned()
+    });
+    match fixture {
+        Some(fixture) => {
+            if let Some(delay) = fixture.delay {
+                tokio::time::sleep(delay).await;
+            }
+            let (req, _) = req.into_parts();
+            Ok(ServiceResponse::new(req, fixture.response()).map_into_right_body())
+        }
+        None => Ok(next.call(req).await?.map_into_left_body()),
+    }
+}
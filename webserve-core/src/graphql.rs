@@ -0,0 +1,169 @@
+//! `--graphql-mocks <dir>`: a `/graphql` endpoint answering from canned JSON files.
+//!
+//! The operation name (from `operationName`, or the name in `query Foo { ... }`) selects
+//! `<dir>/<Foo>.json`. A file holding an object with `data` or `errors` is sent as is;
+//! anything else is wrapped as `{"data": ...}`. Files are read per request, so edits
+//! apply immediately.
+//!
+//! Introspection queries are answered from `<dir>/__introspection.json` when present,
+//! otherwise from a minimal empty schema so tooling can connect.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::ALLOW;
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use bytes::Bytes;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+
+use crate::AppState;
+
+/// URL path of the mock endpoint.
+pub const GRAPHQL_PATH: &str = "/graphql";
+
+/// Mock file answering introspection queries.
+pub const INTROSPECTION_FILE: &str = "__introspection.json";
+
+/// A GraphQL request as sent by clients (POST body or GET query string).
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphqlRequest {
+    #[serde(default)]
+    pub query: String,
+    pub operation_name: Option<String>,
+}
+
+impl GraphqlRequest {
+    /// `operationName` when given, else the name declared in the query document.
+    pub fn operation(&self) -> Option<String> {
+        self.operation_name
+            .clone()
+            .filter(|n| !n.is_empty())
+            .or_else(|| operation_name(&self.query))
+    }
+
+    /// True for schema introspection (`IntrospectionQuery` or a `__schema` selection).
+    pub fn is_introspection(&self) -> bool {
+        self.operation().as_deref() == Some("IntrospectionQuery") || self.query.contains("__schema")
+    }
+}
+
+/// Name of the first operation in `query`, e.g. `GetUser` for `query GetUser($id: ID) {...}`.
+pub fn operation_name(query: &str) -> Option<String> {
+    let rest = query.trim_start();
+    let rest = ["query", "mutation", "subscription"]
+        .iter()
+        .find_map(|kw| rest.strip_prefix(kw))?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let name: String = rest
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Builds the response for `request` from the mocks in `dir`.
+pub fn mock_response(dir: &Path, request: &GraphqlRequest) -> HttpResponse {
+    if request.is_introspection() {
+        return match read_mock(&dir.join(INTROSPECTION_FILE)) {
+            Some(Ok(body)) => HttpResponse::Ok().json(body),
+            Some(Err(e)) => graphql_error(&e),
+            None => HttpResponse::Ok().json(introspection_stub()),
+        };
+    }
+    let Some(name) = request.operation() else {
+        return graphql_error("anonymous operation: set operationName or name the query");
+    };
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return graphql_error(&format!("invalid operation name {}", name));
+    }
+    match read_mock(&dir.join(format!("{}.json", name))) {
+        Some(Ok(body)) => HttpResponse::Ok().json(body),
+        Some(Err(e)) => graphql_error(&e),
+        None => graphql_error(&format!("no mock for operation {}", name)),
+    }
+}
+
+/// `None` when the file does not exist; the mock as a GraphQL response body otherwise.
+fn read_mock(path: &Path) -> Option<Result<Value, String>> {
+    let text = std::fs::read(path).ok()?;
+    Some(
+        serde_json::from_slice::<Value>(&text)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+            .map(|value| match value {
+                Value::Object(ref map)
+                    if map.contains_key("data") || map.contains_key("errors") =>
+                {
+                    value
+                }
+                other => json!({ "data": other }),
+            }),
+    )
+}
+
+/// GraphQL-style error: status 200 with an `errors` list, as servers usually do.
+fn graphql_error(message: &str) -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "data": null, "errors": [{ "message": message }] }))
+}
+
+fn introspection_stub() -> Value {
+    json!({
+        "data": {
+            "__schema": {
+                "queryType": { "name": "Query" },
+                "mutationType": null,
+                "subscriptionType": null,
+                "types": [{
+                    "kind": "OBJECT",
+                    "name": "Query",
+                    "description": null,
+                    "fields": [],
+                    "inputFields": null,
+                    "interfaces": [],
+                    "enumValues": null,
+                    "possibleTypes": null
+                }],
+                "directives": []
+            }
+        }
+    })
+}
+
+/// Middleware answering `/graphql` from the mocks directory when `--graphql-mocks` is set.
+pub async fn graphql_mock(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let dir = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|data| data.settings.load().graphql_mocks.clone());
+    let Some(dir) = dir.filter(|_| req.path() == GRAPHQL_PATH) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let res = match *req.method() {
+        Method::GET => match web::Query::<GraphqlRequest>::from_query(req.query_string()) {
+            Ok(query) => mock_response(&dir, &query),
+            Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+        },
+        Method::POST => {
+            let body = req.extract::<Bytes>().await?;
+            match serde_json::from_slice::<GraphqlRequest>(&body) {
+                Ok(request) => mock_response(&dir, &request),
+                Err(e) => {
+                    HttpResponse::BadRequest().body(format!("invalid GraphQL request: {}", e))
+                }
+            }
+        }
+        _ => HttpResponse::MethodNotAllowed()
+            .insert_header((ALLOW, "GET, POST"))
+            .finish(),
+    };
+    let (req, _) = req.into_parts();
+    Ok(ServiceResponse::new(req, res).map_into_right_body())
+}
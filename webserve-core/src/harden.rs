@@ -0,0 +1,115 @@
+//! `--harden`: rejects ambiguous or oversized requests before they reach a handler.
+//!
+//! Meant for a webserve exposed directly to the internet, where a proxy in front of it (or a
+//! hostile client) could read the same bytes differently. A request is answered with
+//! `400 Bad Request` and `Connection: close`, and logged, when it has
+//!
+//! - both `Content-Length` and `Transfer-Encoding`, several differing or non-numeric
+//!   `Content-Length` values, or a `Transfer-Encoding` other than plain `chunked`;
+//! - a header section larger than `--max-header-bytes`;
+//! - an absolute-form (`http://host/path`) or authority-form target on HTTP/1.x, which
+//!   only proxies should receive.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::http::{Uri, Version};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use tracing::warn;
+
+use crate::AppState;
+
+/// Default `--max-header-bytes`.
+pub const DEFAULT_MAX_HEADER_BYTES: usize = 8 * 1024;
+
+/// Checks one request head; returns a description of each problem found.
+pub fn audit_request(
+    version: Version,
+    uri: &Uri,
+    headers: &HeaderMap,
+    max_header_bytes: usize,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let lengths: Vec<&[u8]> = headers
+        .get_all(header::CONTENT_LENGTH)
+        .flat_map(|v| v.as_bytes().split(|b| *b == b','))
+        .map(|v| v.trim_ascii())
+        .collect();
+    let encodings: Vec<&header::HeaderValue> = headers.get_all(header::TRANSFER_ENCODING).collect();
+    if !lengths.is_empty() && !encodings.is_empty() {
+        problems.push("both Content-Length and Transfer-Encoding".to_string());
+    }
+    if lengths
+        .iter()
+        .any(|v| v.is_empty() || !v.iter().all(u8::is_ascii_digit))
+    {
+        problems.push("Content-Length is not a number".to_string());
+    } else if lengths.windows(2).any(|pair| pair[0] != pair[1]) {
+        problems.push("conflicting Content-Length values".to_string());
+    }
+    if !encodings.is_empty()
+        && (encodings.len() > 1 || !encodings[0].as_bytes().eq_ignore_ascii_case(b"chunked"))
+    {
+        problems.push("Transfer-Encoding other than chunked".to_string());
+    }
+
+    // Name, `: ` and CRLF per field, as on the wire.
+    let header_bytes: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum();
+    if header_bytes > max_header_bytes {
+        problems.push(format!(
+            "header section of {} bytes exceeds {}",
+            header_bytes, max_header_bytes
+        ));
+    }
+
+    // HTTP/2 and HTTP/3 always carry scheme and authority in the URI.
+    if version < Version::HTTP_2 && uri.authority().is_some() {
+        problems.push(match uri.scheme() {
+            Some(_) => "absolute-form request target".to_string(),
+            None => "authority-form request target".to_string(),
+        });
+    }
+    problems
+}
+
+/// Middleware for `--harden`; answers `400` and logs when [`audit_request`] finds a problem.
+pub async fn harden<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let settings = req
+        .app_data::<web::Data<AppState>>()
+        .map(|data| data.settings.load());
+    let problems = match settings {
+        Some(ref settings) if settings.harden => audit_request(
+            req.version(),
+            req.uri(),
+            req.headers(),
+            settings.max_header_bytes,
+        ),
+        _ => Vec::new(),
+    };
+    if problems.is_empty() {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+    let peer = req.peer_addr().map(|a| a.ip());
+    for problem in &problems {
+        warn!(
+            peer = ?peer,
+            method = %req.method(),
+            target = %req.uri(),
+            "harden: rejected, {}",
+            problem
+        );
+    }
+    let res = HttpResponse::BadRequest()
+        .content_type("text/plain; charset=utf-8")
+        .force_close()
+        .body("400 Bad Request");
+    Ok(req.into_response(res).map_into_right_body())
+}
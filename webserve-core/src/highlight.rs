@@ -0,0 +1,159 @@
+//! Syntax-highlighted source viewer (`--highlight`).
+//!
+//! A code file requested with `?view=1` is served as an HTML page: highlighted by syntect
+//! (classes styled with a light theme, and a dark one when the system is dark) with a gutter
+//! of `#L<n>` line anchors. Without the query the file is served as usual. Directory
+//! listings get a toggle that adds the query to the links of code files.
+
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+use crate::branding::{Branding, BRAND_STYLE};
+use crate::path::query_flag;
+use crate::serve::html_escape;
+
+/// Files larger than this are shown with line numbers but without highlighting.
+pub const MAX_HIGHLIGHT_BYTES: usize = 1024 * 1024;
+
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+const LIGHT_THEME: &str = "InspiredGitHub";
+const DARK_THEME: &str = "base16-ocean.dark";
+
+/// Page styles around the theme CSS: a bordered code box with a line-number gutter.
+const SOURCE_STYLE: &str = r#"
+    :root { --text: #1f2937; --text-muted: #6b7280; --bg: #ffffff; --border: #e5e7eb; --link: #2563eb; }
+    @media (prefers-color-scheme: dark) {
+      :root { --text: #e5e7eb; --text-muted: #9ca3af; --bg: #111827; --border: #374151; --link: #60a5fa; }
+    }
+    body { margin: 0; background: var(--bg); color: var(--text); font: 15px/1.5 system-ui, -apple-system, "Segoe UI", sans-serif; }
+    .container { padding: 1.5rem 1.25rem 3rem; }
+    .file-header { display: flex; align-items: baseline; gap: 1rem; margin-bottom: 1rem; }
+    .file-header h1 { font-size: 1rem; margin: 0; word-break: break-all; }
+    .file-header a { color: var(--link); font-size: 0.875rem; }
+    .file-header .muted { color: var(--text-muted); font-size: 0.875rem; }
+    .source { display: flex; border: 1px solid var(--border); border-radius: 8px; overflow-x: auto; }
+    .source pre { margin: 0; padding: 0.75rem 1rem; font: 13px/1.5 ui-monospace, "JetBrains Mono", Menlo, Consolas, monospace; }
+    .source .gutter { text-align: right; user-select: none; color: var(--text-muted); border-right: 1px solid var(--border); }
+    .source .gutter a { color: inherit; text-decoration: none; }
+    .source .gutter a:target, .source .gutter a:hover { color: var(--link); }
+    .source .hl-code { flex: 1; }
+"#;
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+/// Light theme CSS, then the dark one inside `prefers-color-scheme: dark`.
+fn theme_css() -> &'static str {
+    static CSS: OnceLock<String> = OnceLock::new();
+    CSS.get_or_init(|| {
+        let themes = ThemeSet::load_defaults();
+        let css = |name: &str| {
+            themes
+                .themes
+                .get(name)
+                .and_then(|theme| css_for_theme_with_class_style(theme, CLASS_STYLE).ok())
+                .unwrap_or_default()
+        };
+        format!(
+            "{}\n@media (prefers-color-scheme: dark) {{\n{}\n}}",
+            css(LIGHT_THEME),
+            css(DARK_THEME)
+        )
+    })
+}
+
+/// Syntax for `path` by extension, else by file name (e.g. `Makefile`).
+fn syntax_for(path: &Path) -> Option<&'static SyntaxReference> {
+    let syntaxes = syntax_set();
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| syntaxes.find_syntax_by_extension(ext))
+        .or_else(|| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| syntaxes.find_syntax_by_extension(name))
+        })
+}
+
+/// True for files the viewer knows a syntax for.
+pub fn is_highlightable(path: &Path) -> bool {
+    syntax_for(path).is_some()
+}
+
+/// True when the query asks for the viewer: `view`, `view=1` or `view=true`.
+pub fn wants_view(query: &str) -> bool {
+    query_flag(query, "view")
+}
+
+/// `source` as highlighted HTML spans; plain text past [`MAX_HIGHLIGHT_BYTES`] or on error.
+fn highlight_lines(source: &str, syntax: &SyntaxReference) -> String {
+    let syntaxes = syntax_set();
+    let syntax = if source.len() > MAX_HIGHLIGHT_BYTES {
+        syntaxes.find_syntax_plain_text()
+    } else {
+        syntax
+    };
+    let mut html = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, CLASS_STYLE);
+    for line in LinesWithEndings::from(source) {
+        if html
+            .parse_html_for_line_which_includes_newline(line)
+            .is_err()
+        {
+            return html_escape(source);
+        }
+    }
+    html.finalize()
+}
+
+/// Renders `source` of the file `name` as a full HTML page linking to `raw_href`.
+pub fn render_source(source: &str, name: &str, raw_href: &str, branding: &Branding) -> String {
+    let syntax =
+        syntax_for(Path::new(name)).unwrap_or_else(|| syntax_set().find_syntax_plain_text());
+    let line_count = source.lines().count().max(1);
+    let gutter: String = (1..=line_count)
+        .map(|n| format!("<a id=\"L{n}\" href=\"#L{n}\">{n}</a>\n"))
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{page_title}</title>
+  <style>{style}{theme}{brand_style}
+  </style>
+</head>
+<body>
+  <div class="container">
+    {brand}
+    <div class="file-header">
+      <h1>{name}</h1>
+      <span class="muted">{language} &middot; {line_count} {lines}</span>
+      <a href="{raw_href}">Raw</a>
+    </div>
+    <div class="source"><pre class="gutter">{gutter}</pre><pre class="hl-code">{code}</pre></div>
+    {footer}
+  </div>
+</body>
+</html>"#,
+        page_title = html_escape(&branding.page_title(name)),
+        style = SOURCE_STYLE,
+        theme = theme_css(),
+        brand_style = BRAND_STYLE,
+        brand = branding.banner_html(),
+        name = html_escape(name),
+        language = html_escape(&syntax.name),
+        line_count = line_count,
+        lines = if line_count == 1 { "line" } else { "lines" },
+        raw_href = html_escape(raw_href),
+        gutter = gutter,
+        code = highlight_lines(source, syntax),
+        footer = branding.footer_html(),
+    )
+}
@@ -0,0 +1,388 @@
+//! Watcher change history behind `/__webserve/changes` and `/reload?since=<seq>`.
+//!
+//! The watcher records every relevant file event in a bounded buffer of [`HISTORY_LIMIT`]
+//! entries. `GET /__webserve/changes?since=<ms>` folds the events newer than `since` (Unix
+//! milliseconds) into the net set of files added, modified and removed, so an incremental
+//! sync client only fetches what changed. When the buffer no longer reaches back to
+//! `since` the answer says `"complete": false` and the client should resync fully.
+//!
+//! Each watcher batch also gets a sequence number in a [`ReloadLog`] of
+//! [`RELOAD_HISTORY_LIMIT`] batches. Injected pages carry the sequence they were built at and
+//! poll `/reload?since=<seq>`, so a client that was asleep or offline while files changed
+//! still reloads (once) when it reconnects. Each batch carries its [`ReloadChange`]s, so the
+//! client can swap a stylesheet or skip another page's HTML instead of reloading. A failed
+//! `--exec` build is a batch of its own carrying the [`BuildError`], shown as an overlay.
+
+use actix_web::{web, HttpResponse, Responder};
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::exec::BuildError;
+use crate::path::url_path_under;
+use crate::watch::is_editor_temp;
+use crate::AppState;
+
+/// File events kept for `/__webserve/changes`.
+pub const HISTORY_LIMIT: usize = 1000;
+
+/// Watcher batches kept for `/reload?since=<seq>` catch-up.
+pub const RELOAD_HISTORY_LIMIT: usize = 100;
+
+/// What happened to a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// One recorded file event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    /// Unix milliseconds.
+    pub time: u64,
+    /// URL path, e.g. `/assets/app.js`.
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// Net changes since a point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ChangeSet {
+    /// Pass back as `since` on the next request.
+    pub now: u64,
+    /// False when older events were already dropped (or predate the watcher).
+    pub complete: bool,
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Bounded history of file events.
+#[derive(Debug)]
+pub struct ChangeHistory {
+    started: u64,
+    state: Mutex<HistoryState>,
+}
+
+#[derive(Debug, Default)]
+struct HistoryState {
+    events: VecDeque<FileChange>,
+    /// Time of the newest event pushed out of the buffer.
+    dropped_until: Option<u64>,
+}
+
+impl Default for ChangeHistory {
+    fn default() -> Self {
+        ChangeHistory {
+            started: unix_millis(),
+            state: Mutex::default(),
+        }
+    }
+}
+
+/// Current time in Unix milliseconds.
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Splits a watcher event into per-path changes; editor temp files are skipped.
+pub fn classify(event: &Event) -> Vec<(&Path, ChangeKind)> {
+    let exists = |p: &Path| {
+        if p.exists() {
+            ChangeKind::Modified
+        } else {
+            ChangeKind::Removed
+        }
+    };
+    let changes: Vec<(&Path, ChangeKind)> = match event.kind {
+        EventKind::Create(_) => event
+            .paths
+            .iter()
+            .map(|p| (p.as_path(), ChangeKind::Added))
+            .collect(),
+        EventKind::Remove(_) => event
+            .paths
+            .iter()
+            .map(|p| (p.as_path(), ChangeKind::Removed))
+            .collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => event
+            .paths
+            .iter()
+            .map(|p| (p.as_path(), ChangeKind::Removed))
+            .collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => event
+            .paths
+            .iter()
+            .map(|p| (p.as_path(), ChangeKind::Added))
+            .collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => vec![
+            (event.paths[0].as_path(), ChangeKind::Removed),
+            (event.paths[1].as_path(), ChangeKind::Added),
+        ],
+        EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Metadata(_))
+        | EventKind::Access(AccessKind::Close(AccessMode::Write)) => event
+            .paths
+            .iter()
+            .map(|p| (p.as_path(), ChangeKind::Modified))
+            .collect(),
+        EventKind::Access(_) => Vec::new(),
+        _ => event
+            .paths
+            .iter()
+            .map(|p| (p.as_path(), exists(p)))
+            .collect(),
+    };
+    changes
+        .into_iter()
+        .filter(|(p, _)| !is_editor_temp(p))
+        .collect()
+}
+
+impl ChangeHistory {
+    /// Records `events` for paths under `root`.
+    pub fn record(&self, root: &Path, events: &[Event]) {
+        let time = unix_millis();
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        for event in events {
+            for (path, kind) in classify(event) {
+                let Some(path) = url_path_under(root, path) else {
+                    continue;
+                };
+                if state.events.len() == HISTORY_LIMIT {
+                    if let Some(old) = state.events.pop_front() {
+                        state.dropped_until = Some(old.time);
+                    }
+                }
+                state.events.push_back(FileChange { time, path, kind });
+            }
+        }
+    }
+
+    /// Net changes at or after `since` (Unix milliseconds).
+    pub fn since(&self, since: u64) -> ChangeSet {
+        let now = unix_millis();
+        let Ok(state) = self.state.lock() else {
+            return ChangeSet {
+                now,
+                ..Default::default()
+            };
+        };
+        let complete = since >= self.started && state.dropped_until.is_none_or(|t| since > t);
+        // Per path: did it exist before the first event, and does it exist after the last?
+        let mut net: BTreeMap<&str, (bool, bool)> = BTreeMap::new();
+        for change in state.events.iter().filter(|c| c.time >= since) {
+            let exists_after = change.kind != ChangeKind::Removed;
+            net.entry(&change.path)
+                .and_modify(|(_, after)| *after = exists_after)
+                .or_insert((change.kind != ChangeKind::Added, exists_after));
+        }
+        let mut set = ChangeSet {
+            now,
+            complete,
+            ..Default::default()
+        };
+        for (path, existence) in net {
+            let list = match existence {
+                (false, true) => &mut set.added,
+                (true, true) => &mut set.modified,
+                (true, false) => &mut set.removed,
+                (false, false) => continue,
+            };
+            list.push(path.to_string());
+        }
+        set
+    }
+}
+
+/// Net change to one URL path within a reload batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReloadChange {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// One watcher batch that triggered a reload; also the payload of the reload broadcast.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReloadEvent {
+    pub seq: u64,
+    /// Unix milliseconds.
+    pub time: u64,
+    /// URL paths touched by the batch.
+    pub paths: Vec<String>,
+    /// The same paths with what happened to each, sorted by path.
+    pub changes: Vec<ReloadChange>,
+    /// Set instead of paths when an `--exec` build failed.
+    pub error: Option<BuildError>,
+}
+
+/// What a client at some sequence has missed; the JSON answer of `/reload?since=<seq>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Catchup {
+    /// Sequence of the newest batch (0 before any change).
+    pub current: u64,
+    /// Batches after the client's sequence that are still buffered, oldest first.
+    pub missed: Vec<ReloadEvent>,
+    /// False when batches after the client's sequence were already dropped.
+    pub complete: bool,
+}
+
+impl Catchup {
+    /// True when the client should reload: something changed, or the server restarted
+    /// and the client's sequence is from a previous run.
+    pub fn stale(&self, seq: u64) -> bool {
+        seq != self.current
+    }
+}
+
+/// Sequence-numbered ring buffer of watcher batches.
+#[derive(Debug)]
+pub struct ReloadLog {
+    state: Mutex<ReloadState>,
+    created: SystemTime,
+}
+
+#[derive(Debug, Default)]
+struct ReloadState {
+    current: u64,
+    events: VecDeque<ReloadEvent>,
+    /// When `current` last moved.
+    changed: Option<SystemTime>,
+}
+
+impl Default for ReloadLog {
+    fn default() -> Self {
+        ReloadLog {
+            state: Mutex::default(),
+            created: SystemTime::now(),
+        }
+    }
+}
+
+impl ReloadLog {
+    /// Records a watcher batch under `root` and returns its sequence number.
+    pub fn record(&self, root: &Path, events: &[Event]) -> u64 {
+        // Last kind wins, except that a new file stays added while it is written
+        let mut net: BTreeMap<String, ChangeKind> = BTreeMap::new();
+        for (path, kind) in events.iter().flat_map(classify) {
+            let Some(path) = url_path_under(root, path) else {
+                continue;
+            };
+            net.entry(path)
+                .and_modify(|k| {
+                    if !(*k == ChangeKind::Added && kind == ChangeKind::Modified) {
+                        *k = kind;
+                    }
+                })
+                .or_insert(kind);
+        }
+        let paths = net.keys().cloned().collect();
+        let changes = net
+            .into_iter()
+            .map(|(path, kind)| ReloadChange { path, kind })
+            .collect();
+        self.push(ReloadEvent {
+            paths,
+            changes,
+            ..Default::default()
+        })
+    }
+
+    /// Records a failed `--exec` build, which clients show instead of reloading, and returns
+    /// its sequence number.
+    pub fn record_error(&self, error: BuildError) -> u64 {
+        self.push(ReloadEvent {
+            error: Some(error),
+            ..Default::default()
+        })
+    }
+
+    fn push(&self, mut event: ReloadEvent) -> u64 {
+        let Ok(mut state) = self.state.lock() else {
+            return 0;
+        };
+        state.current += 1;
+        state.changed = Some(SystemTime::now());
+        event.seq = state.current;
+        event.time = unix_millis();
+        if state.events.len() == RELOAD_HISTORY_LIMIT {
+            state.events.pop_front();
+        }
+        state.events.push_back(event);
+        state.current
+    }
+
+    /// The failed build behind the newest batch, while no later batch has replaced it.
+    pub fn build_error(&self) -> Option<BuildError> {
+        let state = self.state.lock().ok()?;
+        state.events.back()?.error.clone()
+    }
+
+    /// Batch `seq`, while it is still buffered.
+    pub fn event(&self, seq: u64) -> Option<ReloadEvent> {
+        let state = self.state.lock().ok()?;
+        state.events.iter().find(|e| e.seq == seq).cloned()
+    }
+
+    /// Sequence of the newest batch (0 before any change).
+    pub fn current(&self) -> u64 {
+        self.state.lock().map(|s| s.current).unwrap_or(0)
+    }
+
+    /// When the sequence last moved, or when the log was created: the last change to the
+    /// reload script injected into pages.
+    pub fn changed_at(&self) -> SystemTime {
+        self.state
+            .lock()
+            .ok()
+            .and_then(|s| s.changed)
+            .unwrap_or(self.created)
+    }
+
+    /// Batches recorded after `seq`.
+    pub fn since(&self, seq: u64) -> Catchup {
+        let Ok(state) = self.state.lock() else {
+            return Catchup::default();
+        };
+        let missed: Vec<ReloadEvent> = state
+            .events
+            .iter()
+            .filter(|e| e.seq > seq)
+            .cloned()
+            .collect();
+        let oldest = state.events.front().map_or(state.current + 1, |e| e.seq);
+        Catchup {
+            current: state.current,
+            complete: seq <= state.current && oldest <= seq + 1,
+            missed,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    /// Unix milliseconds; omitted means everything still in the buffer.
+    pub since: Option<u64>,
+}
+
+/// `GET /__webserve/changes?since=<ms>`: files added, modified and removed since `since`.
+pub async fn changes(data: web::Data<AppState>, query: web::Query<ChangesQuery>) -> impl Responder {
+    if !data.watch {
+        return HttpResponse::NotFound()
+            .json(serde_json::json!({ "error": "change history needs --watch" }));
+    }
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(data.history.since(query.since.unwrap_or(0)))
+}
@@ -0,0 +1,129 @@
+//! Extension points for embedders (see [`crate::WebserveBuilder`]): code run on every request
+//! and response, and a say in which file a URL path maps to, without forking
+//! [`serve_file`](crate::serve_file).
+//!
+//! - `on_request` hooks run after `--allow` / `--deny` and before fixtures, rules and file
+//!   lookup; the first to return a response answers the request (custom auth, feature gates).
+//! - `on_response` hooks see every response on its way out, after `--header` and
+//!   `--compress`, and may change its status or headers.
+//! - `resolve_path` hooks map the URL path left by the redirect/rewrite rules to a file; the
+//!   first `Some` wins and a relative path is taken from the served root. The file then goes
+//!   through the usual directory index, SPA and section fallbacks; when every hook returns
+//!   `None` the path is looked up under the root as usual.
+//!
+//! Hooks run in the order they were added, an embedder's before `--plugin` modules'.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ResponseHead, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::AppState;
+
+pub type RequestHook = Arc<dyn Fn(&HttpRequest) -> Option<HttpResponse> + Send + Sync>;
+pub type ResponseHook = Arc<dyn Fn(&HttpRequest, &mut ResponseHead) + Send + Sync>;
+pub type ResolveHook = Arc<dyn Fn(&HttpRequest, &str) -> Option<PathBuf> + Send + Sync>;
+
+/// Hooks registered by an embedder or loaded from `--plugin` modules.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    request: Vec<RequestHook>,
+    response: Vec<ResponseHook>,
+    resolve: Vec<ResolveHook>,
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("on_request", &self.request.len())
+            .field("on_response", &self.response.len())
+            .field("resolve_path", &self.resolve.len())
+            .finish()
+    }
+}
+
+impl Hooks {
+    pub fn on_request(
+        &mut self,
+        hook: impl Fn(&HttpRequest) -> Option<HttpResponse> + Send + Sync + 'static,
+    ) {
+        self.request.push(Arc::new(hook));
+    }
+
+    pub fn on_response(
+        &mut self,
+        hook: impl Fn(&HttpRequest, &mut ResponseHead) + Send + Sync + 'static,
+    ) {
+        self.response.push(Arc::new(hook));
+    }
+
+    pub fn resolve_path(
+        &mut self,
+        hook: impl Fn(&HttpRequest, &str) -> Option<PathBuf> + Send + Sync + 'static,
+    ) {
+        self.resolve.push(Arc::new(hook));
+    }
+
+    /// Appends `other`'s hooks after these.
+    pub fn extend(&mut self, other: &Hooks) {
+        self.request.extend(other.request.iter().cloned());
+        self.response.extend(other.response.iter().cloned());
+        self.resolve.extend(other.resolve.iter().cloned());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.request.is_empty() && self.response.is_empty() && self.resolve.is_empty()
+    }
+
+    /// True when an `on_request` hook may answer requests, whatever their method.
+    pub fn answers_requests(&self) -> bool {
+        !self.request.is_empty()
+    }
+
+    /// Response from the first `on_request` hook that answers `req`.
+    pub fn answer(&self, req: &HttpRequest) -> Option<HttpResponse> {
+        self.request.iter().find_map(|hook| hook(req))
+    }
+
+    /// Lets every `on_response` hook adjust `head`.
+    pub fn finish(&self, req: &HttpRequest, head: &mut ResponseHead) {
+        for hook in &self.response {
+            hook(req, head);
+        }
+    }
+
+    /// File the first `resolve_path` hook maps `url_path` to, under `root` when relative.
+    pub fn resolve(&self, req: &HttpRequest, url_path: &str, root: &Path) -> Option<PathBuf> {
+        let path = self.resolve.iter().find_map(|hook| hook(req, url_path))?;
+        debug!(url_path, file = %path.display(), "resolved by hook");
+        Some(root.join(path))
+    }
+}
+
+/// Middleware running the `on_request` and `on_response` hooks.
+pub async fn run_hooks<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let hooks = req
+        .app_data::<web::Data<AppState>>()
+        .map(|data| data.hooks.clone())
+        .filter(|hooks| !hooks.is_empty());
+    let Some(hooks) = hooks else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let mut res = match hooks.answer(req.request()) {
+        Some(answer) => {
+            debug!(path = req.path(), "answered by on_request hook");
+            req.into_response(answer).map_into_right_body()
+        }
+        None => next.call(req).await?.map_into_left_body(),
+    };
+    let http_req = res.request().clone();
+    hooks.finish(&http_req, res.response_mut().head_mut());
+    Ok(res)
+}
@@ -0,0 +1,270 @@
+//! One disk read for identical concurrent file requests.
+//!
+//! When several clients fetch the same file at once (a classroom loading the same demo
+//! video), the first request starts a read task that buffers the file in chunks and every
+//! request for the same path, size and mtime that arrives while it runs streams from that
+//! buffer instead of opening the file again. The entry is dropped when the read finishes,
+//! so later requests read fresh. Only whole-file `200` responses of
+//! [`COALESCE_MIN_BYTES`]..=[`COALESCE_MAX_BYTES`] are shared; ranges and small files go
+//! straight to disk.
+//!
+//! The read opens the path again, so the file may have changed since it was stat'ed. A
+//! file whose size or mtime already differs keeps the `NamedFile` body; one that changes
+//! while it is read ends the body with an error rather than sending another number of
+//! bytes than `Content-Length` announced.
+
+use actix_web::body::SizedStream;
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::{HttpRequest, HttpResponse};
+use bytes::{Bytes, BytesMut};
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt, TryStreamExt};
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::io::AsyncReadExt;
+use tokio::sync::watch;
+use tracing::{debug, trace};
+
+/// Files smaller than this are read per request.
+pub const COALESCE_MIN_BYTES: u64 = 256 * 1024;
+
+/// Files larger than this are streamed per request rather than buffered in memory.
+pub const COALESCE_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Read size of the shared task.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// In-flight shared reads, keyed by path, size and mtime.
+#[derive(Clone, Default)]
+pub struct InflightReads {
+    reads: Arc<Mutex<HashMap<ReadKey, Arc<SharedRead>>>>,
+    /// `--io-uring`: reads go through the io_uring thread instead of the blocking pool.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    uring: Option<crate::uring::UringReader>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ReadKey {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Progress {
+    /// Chunks available so far.
+    Reading(usize),
+    /// Every chunk is available.
+    Done(usize),
+    Failed,
+}
+
+struct SharedRead {
+    chunks: Mutex<Vec<Bytes>>,
+    progress: watch::Sender<Progress>,
+}
+
+impl InflightReads {
+    /// Shared reads whose disk I/O, and every whole-file body, goes through io_uring.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    pub fn with_uring(uring: crate::uring::UringReader) -> Self {
+        InflightReads {
+            reads: Arc::default(),
+            uring: Some(uring),
+        }
+    }
+
+    /// Chunks of `path` from io_uring when enabled, else from `tokio::fs`.
+    fn disk_stream(&self, path: &Path) -> BoxStream<'static, io::Result<Bytes>> {
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        if let Some(ref uring) = self.uring {
+            return uring.stream(path).boxed();
+        }
+        let path = path.to_path_buf();
+        futures::stream::once(async move { tokio::fs::File::open(path).await })
+            .map_ok(|file| {
+                futures::stream::try_unfold(file, |mut file| async move {
+                    let mut buf = BytesMut::with_capacity(CHUNK_SIZE);
+                    match file.read_buf(&mut buf).await? {
+                        0 => Ok(None),
+                        _ => Ok(Some((buf.freeze(), file))),
+                    }
+                })
+            })
+            .try_flatten()
+            .boxed()
+    }
+
+    /// Number of reads currently shared.
+    pub fn in_flight(&self) -> usize {
+        self.reads.lock().map(|r| r.len()).unwrap_or(0)
+    }
+
+    /// Body stream for `path`, joining a running read when there is one.
+    ///
+    /// Returns the stream and whether it joined an existing read.
+    pub fn stream(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+    ) -> (impl Stream<Item = Result<Bytes, io::Error>>, bool) {
+        let key = ReadKey {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        };
+        let (shared, joined) = {
+            let mut reads = self.reads.lock().unwrap_or_else(|e| e.into_inner());
+            match reads.get(&key) {
+                Some(shared) => (shared.clone(), true),
+                None => {
+                    let (progress, _) = watch::channel(Progress::Reading(0));
+                    let shared = Arc::new(SharedRead {
+                        chunks: Mutex::new(Vec::new()),
+                        progress,
+                    });
+                    reads.insert(key.clone(), shared.clone());
+                    let chunks = exact_len(key.len, self.disk_stream(path));
+                    tokio::spawn(read_file(self.reads.clone(), key, shared.clone(), chunks));
+                    (shared, false)
+                }
+            }
+        };
+        (follow(shared), joined)
+    }
+
+    /// Swaps the body of a whole-file `200` for a shared read of `path`; other responses
+    /// (ranges, `304`, small or huge files) pass through.
+    pub fn share_body(
+        &self,
+        req: &HttpRequest,
+        res: HttpResponse,
+        path: &Path,
+        metadata: &Metadata,
+    ) -> HttpResponse {
+        let len = metadata.len();
+        if req.method() != Method::GET
+            || res.status() != StatusCode::OK
+            || req.headers().contains_key(header::RANGE)
+        {
+            return res;
+        }
+        // Replaced since it was opened: the open `NamedFile` still has the old contents
+        let current = std::fs::metadata(path).ok();
+        if current.as_ref().map(|m| (m.len(), m.modified().ok()))
+            != Some((len, metadata.modified().ok()))
+        {
+            debug!(file = %path.display(), "file changed since it was opened, not shared");
+            return res;
+        }
+        if !(COALESCE_MIN_BYTES..=COALESCE_MAX_BYTES).contains(&len) {
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            if self.uring.is_some() {
+                let stream = exact_len(len, self.disk_stream(path));
+                return res
+                    .map_body(|_, _| SizedStream::new(len, stream))
+                    .map_into_boxed_body();
+            }
+            return res;
+        }
+        let (stream, joined) = self.stream(path, metadata);
+        if joined {
+            debug!(file = %path.display(), "joined in-flight read");
+        }
+        res.map_body(|_, _| SizedStream::new(len, Box::pin(stream)))
+            .map_into_boxed_body()
+    }
+}
+
+/// `chunks`, failing once they add up to more or fewer than the `len` bytes the response
+/// announced.
+fn exact_len(
+    len: u64,
+    chunks: BoxStream<'static, io::Result<Bytes>>,
+) -> BoxStream<'static, io::Result<Bytes>> {
+    let changed = || io::Error::other("file changed while it was read");
+    futures::stream::unfold(Some((chunks, 0u64)), move |state| async move {
+        let (mut chunks, read) = state?;
+        match chunks.next().await {
+            Some(Ok(chunk)) => {
+                let read = read + chunk.len() as u64;
+                if read > len {
+                    return Some((Err(changed()), None));
+                }
+                Some((Ok(chunk), Some((chunks, read))))
+            }
+            Some(Err(e)) => Some((Err(e), None)),
+            None if read != len => Some((Err(changed()), None)),
+            None => None,
+        }
+    })
+    .boxed()
+}
+
+/// Reads `disk` into `shared`, then retires the entry.
+async fn read_file(
+    reads: Arc<Mutex<HashMap<ReadKey, Arc<SharedRead>>>>,
+    key: ReadKey,
+    shared: Arc<SharedRead>,
+    mut disk: BoxStream<'static, io::Result<Bytes>>,
+) {
+    let result = async {
+        let mut count = 0;
+        while let Some(chunk) = disk.next().await {
+            if let Ok(mut chunks) = shared.chunks.lock() {
+                chunks.push(chunk?);
+                count = chunks.len();
+            }
+            shared.progress.send_replace(Progress::Reading(count));
+        }
+        Ok::<usize, io::Error>(count)
+    }
+    .await;
+    if let Ok(mut map) = reads.lock() {
+        if map.get(&key).is_some_and(|s| Arc::ptr_eq(s, &shared)) {
+            map.remove(&key);
+        }
+    }
+    match result {
+        Ok(count) => {
+            trace!(file = %key.path.display(), chunks = count, "shared read finished");
+            shared.progress.send_replace(Progress::Done(count));
+        }
+        Err(e) => {
+            debug!(file = %key.path.display(), error = %e, "shared read failed");
+            shared.progress.send_replace(Progress::Failed);
+        }
+    }
+}
+
+/// Streams the chunks of `shared` from the start, waiting for the reader as needed.
+fn follow(shared: Arc<SharedRead>) -> impl Stream<Item = Result<Bytes, io::Error>> {
+    let progress = shared.progress.subscribe();
+    futures::stream::unfold(Some((shared, progress, 0usize)), |state| async move {
+        let (shared, mut progress, next) = state?;
+        loop {
+            let current = *progress.borrow_and_update();
+            let available = match current {
+                Progress::Reading(n) | Progress::Done(n) => n,
+                Progress::Failed => {
+                    let err = io::Error::other("shared file read failed");
+                    return Some((Err(err), None));
+                }
+            };
+            if next < available {
+                let chunk = shared.chunks.lock().ok()?.get(next).cloned()?;
+                return Some((Ok(chunk), Some((shared, progress, next + 1))));
+            }
+            if matches!(current, Progress::Done(_)) {
+                return None;
+            }
+            if progress.changed().await.is_err() {
+                return None;
+            }
+        }
+    })
+}
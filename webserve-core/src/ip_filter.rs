@@ -0,0 +1,167 @@
+//! Client IP allowlist / denylist (`--allow`, `--deny`).
+//!
+//! Each rule is a CIDR block (or a single address). The most specific block containing
+//! the client address decides, with `--deny` winning ties. Addresses matching no rule are
+//! refused once any `--allow` is given and allowed otherwise, so `--allow 10.0.0.0/8`
+//! alone admits only the office network. A `/0` block (`0.0.0.0/0`, `::/0`) is every
+//! address of either family, so `--deny 0.0.0.0/0` does not leave IPv6 clients of a
+//! `--host ::` bind open. IPv4-mapped IPv6 peers (dual-stack listeners) are matched as
+//! IPv4; requests without a peer address (`--uds`, socket-activated Unix sockets) are
+//! not filtered.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use std::fmt;
+use std::net::IpAddr;
+use tracing::debug;
+
+use crate::{AppState, ServeOptions};
+
+/// An address block such as `10.0.0.0/8` or `2001:db8::/32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Parses `addr/prefix`; a bare address is a single-host block.
+    pub fn parse(spec: &str) -> Result<Cidr, String> {
+        let (addr, prefix) = match spec.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (spec.trim(), None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid address in {}", spec))?;
+        let addr = canonical(addr);
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("invalid prefix length in {}", spec))?,
+            None => max,
+        };
+        Ok(Cidr { addr, prefix })
+    }
+
+    /// True when `ip` lies inside the block; a `/0` block holds every address of both
+    /// families.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        if self.prefix == 0 {
+            return true;
+        }
+        match (self.addr, canonical(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// `::ffff:a.b.c.d` as `a.b.c.d`; other addresses unchanged.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    }
+}
+
+/// The `--allow` / `--deny` rules.
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl IpFilter {
+    pub fn new(allow: Vec<Cidr>, deny: Vec<Cidr>) -> IpFilter {
+        IpFilter { allow, deny }
+    }
+
+    pub fn from_options(options: &ServeOptions) -> Result<IpFilter, String> {
+        let parse = |flag: &str, specs: &[String]| {
+            specs
+                .iter()
+                .map(|s| Cidr::parse(s).map_err(|e| format!("--{}: {}", flag, e)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(IpFilter {
+            allow: parse("allow", &options.allow)?,
+            deny: parse("deny", &options.deny)?,
+        })
+    }
+
+    /// True when no rules are configured.
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Rule count, for the dashboard.
+    pub fn len(&self) -> usize {
+        self.allow.len() + self.deny.len()
+    }
+
+    /// Whether `ip` may be served: the most specific matching rule decides, and an
+    /// address no rule matches only gets in when there is no `--allow`.
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        let longest = |rules: &[Cidr]| {
+            rules
+                .iter()
+                .filter(|c| c.contains(ip))
+                .map(|c| c.prefix)
+                .max()
+        };
+        match (longest(&self.allow), longest(&self.deny)) {
+            (Some(allow), Some(deny)) => allow > deny,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => self.allow.is_empty(),
+        }
+    }
+}
+
+/// Middleware for `--allow` / `--deny`: `403 Forbidden` before any other handling.
+pub async fn ip_filter<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let settings = req
+        .app_data::<web::Data<AppState>>()
+        .map(|data| data.settings.load());
+    let peer = req.peer_addr().map(|a| a.ip());
+    let blocked = match (&settings, peer) {
+        (Some(settings), Some(ip)) => !settings.ip_filter.permits(ip),
+        _ => false,
+    };
+    if blocked {
+        debug!(peer = ?peer, path = req.path(), "blocked by --allow/--deny");
+        let branded = settings
+            .as_ref()
+            .and_then(|s| s.branding.error_page(req.request(), StatusCode::FORBIDDEN));
+        let res = branded.unwrap_or_else(|| {
+            HttpResponse::Forbidden()
+                .content_type("text/plain; charset=utf-8")
+                .body("403 Forbidden")
+        });
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+    Ok(next.call(req).await?.map_into_left_body())
+}
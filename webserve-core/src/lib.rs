@@ -0,0 +1,209 @@
+//! # webserve-core
+//!
+//! The library behind the `webserve` CLI: options and config layering, the file handler
+//! and directory listings, live reload, the middleware stack, the `/__webserve/` admin
+//! endpoints and the [`Webserve`] builder for embedding. The listener extras that pull in
+//! their own stacks (TLS, Unix sockets, systemd activation, mDNS, LAN QR codes) and the log
+//! subscriber stay in the `webserve` crate.
+//!
+//! The [`server`] module shows how to run or mount one.
+//!
+//! ## Stability
+//! The public API is what the crate root exports, modules included, and it follows semver:
+//! removing or changing an item is a major release, listed in `CHANGELOG.md`. Every new CLI
+//! option adds a field to [`ServeOptions`], [`FileConfig`] and often [`Settings`]; those
+//! additions ship in minor releases, so build the structs from `Default` (or
+//! `StructOpt::from_iter` with the `cli` feature) and update fields from there.
+//!
+//! Nothing here prints: the subcommands (`check-links`, `diff`, `clean`, ...) and their
+//! reports live in the CLI, on top of [`check_dir`], [`diff_dirs`] and [`trim`].
+//!
+//! ## Cargo features
+//! - `markdown` (default): `--render-markdown`, via pulldown-cmark
+//! - `highlight` (default): `--highlight` source viewer, via syntect
+//! - `webdav` (default): `--webdav`
+//! - `crawl` (default): `check-links <url>`, via awc
+//! - `proxy` (default): `--proxy` forwarding, via awc
+//! - `io-uring`: `--io-uring` file reads on Linux
+//! - `sled`, `sqlite`: persistent `--store` backends
+//! - `wasm-plugins`: `--plugin` WebAssembly handlers
+//! - `cli`: derives `StructOpt` for [`ServeOptions`], the flag parser of the `webserve` binary
+//!
+//! Options of a subsystem left out are still parsed, but using them fails at startup with a
+//! message naming the feature.
+
+pub mod access_log;
+pub mod admin;
+pub mod branding;
+pub mod cache;
+pub mod charset;
+pub mod check_links;
+pub mod compress;
+pub mod compress_cache;
+pub mod conditional;
+pub mod config;
+pub mod csp;
+pub mod diff;
+pub mod editor;
+pub mod events;
+pub mod exec;
+pub mod fault;
+pub mod fixtures;
+pub mod graphql;
+pub mod harden;
+#[cfg(feature = "highlight")]
+pub mod highlight;
+pub mod history;
+pub mod hooks;
+pub mod inflight;
+pub mod ip_filter;
+pub mod limits;
+pub mod listing_lang;
+pub mod manifest;
+#[cfg(feature = "markdown")]
+pub mod markdown;
+pub mod mime_types;
+pub mod mock;
+pub mod mount;
+pub mod path;
+#[cfg(feature = "wasm-plugins")]
+pub mod plugin;
+pub mod proxy;
+pub mod replace;
+pub mod rules;
+pub mod search;
+pub mod serve;
+pub mod server;
+pub mod settings;
+pub mod slowloris;
+pub mod stats;
+pub mod storage;
+pub mod strict;
+pub mod suggest;
+pub mod throttle;
+pub mod tuning;
+pub mod types;
+pub mod upload;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+pub mod walk;
+pub mod watch;
+#[cfg(feature = "webdav")]
+pub mod webdav;
+
+pub use access_log::{
+    access_log, parse_sample, AccessLogger, AccessRecord, LogFilter, LogFormat, PathPattern,
+};
+pub use admin::{broken_links, dashboard, scenario_status, set_scenario, stats_json, ADMIN_PREFIX};
+pub use branding::{Branding, BRAND_STYLE};
+pub use cache::{
+    cache_root, compress_cache_dir, enforce_cache_size, trim, usage, CacheUsage, CACHE_DIR,
+    COMPRESS_SUBDIR, DEFAULT_CACHE_MIB,
+};
+pub use charset::{Charsets, DEFAULT_CHARSET};
+pub use check_links::{
+    check_dir, check_url, extract_links, resolve_link, DeadLink, LinkReport, EXIT_BROKEN,
+    EXIT_ERROR, EXIT_OK,
+};
+pub use compress::{
+    compress, is_compressible, AdaptiveCompression, CompressLevel, Encoding, COMPRESS_MAX_BYTES,
+    COMPRESS_MIN_BYTES, DEFAULT_COMPRESS_CPU_LIMIT,
+};
+pub use compress_cache::{CompressCache, DEFAULT_COMPRESS_CACHE_MIB};
+pub use conditional::{
+    body_etag, bust_asset_urls, cached_sha256, etag_matches, file_sha256_etag, generated_etag,
+    html_response, html_response_modified, html_with_validator, no_store, sha256_etag, EtagMode,
+    FileHashCache, CACHE_BUST_PARAM,
+};
+pub use config::{
+    export_config, log_level, parse_header, resolve_layers, unknown_env_vars, CacheSetting,
+    FileConfig, HostSetting, OpenSetting, DEFAULT_CONFIG_FILE, ENV_PREFIX,
+};
+pub use csp::{add_nonce, csp_nonce, CSP_MAX_BYTES, SCRIPT_MARKER};
+pub use diff::{
+    diff_dirs, diff_manifests, diff_report_html, text_line, DiffKind, FileDiff, FileVersion,
+    TreeDiff, EXIT_DIFFERENT, EXIT_IDENTICAL,
+};
+pub use editor::{
+    configured_editor, editor_command, open_in_editor, resolve_editor_file, OpenQuery,
+    DEFAULT_EDITOR, ERROR_OVERLAY_SCRIPT,
+};
+pub use events::{
+    event_stream, generate_token, EventBus, EventsQuery, ServerEvent, EVENT_BUFFER, KEEPALIVE,
+};
+pub use exec::{strip_ansi, BuildCommand, BuildError, MAX_BUILD_OUTPUT};
+pub use fault::{format_duration, inject_fault, parse_duration, Fault, FAULT_HEADER};
+pub use fixtures::{serve_fixtures, Fixture, Fixtures, SCENARIO_HEADER};
+pub use graphql::{graphql_mock, operation_name, GraphqlRequest, GRAPHQL_PATH};
+pub use harden::{audit_request, harden, DEFAULT_MAX_HEADER_BYTES};
+#[cfg(feature = "highlight")]
+pub use highlight::{is_highlightable, render_source, wants_view, MAX_HIGHLIGHT_BYTES};
+pub use history::{
+    changes, classify, unix_millis, Catchup, ChangeHistory, ChangeKind, ChangeSet, FileChange,
+    ReloadChange, ReloadEvent, ReloadLog, HISTORY_LIMIT, RELOAD_HISTORY_LIMIT,
+};
+pub use hooks::{run_hooks, Hooks, RequestHook, ResolveHook, ResponseHook};
+pub use inflight::{InflightReads, COALESCE_MAX_BYTES, COALESCE_MIN_BYTES};
+pub use ip_filter::{ip_filter, Cidr, IpFilter};
+pub use limits::{allowed_methods, describe_methods, request_limits, RequestLimits, BASE_METHODS};
+pub use listing_lang::{Labels, ListingLang, Locale, LOCALES};
+pub use manifest::{build_manifest, manifest, Manifest, ManifestCache, ManifestEntry};
+#[cfg(feature = "markdown")]
+pub use markdown::{is_markdown, render_markdown, wants_raw, MARKDOWN_EXTENSIONS};
+pub use mime_types::{is_inline, MimeOverrides};
+pub use mock::{mock_api, MockApi};
+pub use mount::{site_options, sites, Mount};
+pub use path::{
+    bind_host, browser_url, encode_url_path, host_is_local, host_port, join_serve_path,
+    normalize_url_path, percent_decode_path, percent_encode_path_segment, url_path_under,
+    validate_static_root,
+};
+#[cfg(feature = "wasm-plugins")]
+pub use plugin::{Plugin, PLUGIN_FUEL, PLUGIN_MEMORY_BYTES};
+pub use proxy::{is_private, proxy_requests, Proxy, ProxyRule, PROXY_TIMEOUT};
+pub use replace::{replace_body, Replacement, Replacements, REPLACE_MAX_BYTES};
+pub use rules::{Rule, RuleAction, RuleMatch, Rules};
+pub use search::{
+    search, search_tree, LineMatch, SearchHit, SearchQuery, SearchResults, DEFAULT_SEARCH_RESULTS,
+    MAX_CONTENT_BYTES, MAX_SEARCH_ENTRIES, MAX_SEARCH_RESULTS,
+};
+pub use serve::{
+    directory_listing, options, reload_poll, serve_file, ListingView, READ_METHODS,
+    RELOAD_SEQ_HEADER, UPLOAD_METHODS,
+};
+pub use server::{
+    app_state, listen_error, routes, site_state, static_root, watch_files, ServerHandle, Webserve,
+    WebserveBuilder,
+};
+pub use settings::{extra_headers, load_rules, LiveSettings, Settings};
+pub use slowloris::{
+    slowloris_guard, track_connection, ConnectionClock, MinRate, SlowlorisGuard, Stamped,
+    DEFAULT_HEADER_TIMEOUT, DEFAULT_MIN_RATE,
+};
+pub use stats::{
+    collect_stats, top_downloads, BrokenLink, ChangeEvent, RequestEvent, Stats, TlsCounts, TlsInfo,
+    BROKEN_LINK_LIMIT, DOWNLOAD_KEY_LIMIT, REFERER_LIMIT, TOP_DOWNLOADS,
+};
+#[cfg(feature = "sled")]
+pub use storage::SledStore;
+#[cfg(feature = "sqlite")]
+pub use storage::SqliteStore;
+pub use storage::{counter, open_store, MemoryStore, Store, DOWNLOADS_NS};
+pub use strict::{audit_response, strict_http};
+pub use suggest::{levenshtein, suggest_header, suggestions, MAX_SUGGESTIONS, SUGGEST_HEADER};
+pub use throttle::{NetworkShape, Throttled};
+pub use tuning::ServerTuning;
+pub use types::{AppState, DirEntry, ServeOptions, StaticDirError};
+pub use upload::{
+    delete_path, make_dir, upload_form, upload_put, upload_target, UploadError,
+    DEFAULT_MAX_UPLOAD_MIB,
+};
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use uring::UringReader;
+pub use walk::{walk, Walk, WalkLimits};
+pub use watch::{
+    coalesce, is_editor_temp, is_relevant, is_watch_limit, watch_tree, watch_trees, MAX_BATCH_WAIT,
+    POLL_INTERVAL, WATCH_DEBOUNCE, WATCH_LIMIT_HINT,
+};
+#[cfg(feature = "webdav")]
+pub use webdav::{webdav, DAV_METHODS};
@@ -0,0 +1,311 @@
+//! Request limits (`--max-request-size`, `--read-timeout`) and the method allowlist
+//! (`--allow-method`).
+//!
+//! Meant for a webserve exposed on a LAN, where any client can send it anything. Before a
+//! request reaches a handler:
+//!
+//! - its method must be one the server was asked to answer: `GET`, `HEAD` and `OPTIONS`,
+//!   the methods of the enabled modes (`--upload`, `--webdav`, `--mock`, `--graphql-mocks`,
+//!   fixture routes) and `--allow-method`. Others get `405 Method Not Allowed` with an
+//!   `Allow` header. Fixture routes without a `method` and `on_request` hooks (an embedder's
+//!   or `--plugin` modules') may answer anything, so they allow every method, as does
+//!   `--allow-method '*'`. Paths under a `--proxy` prefix take any method, leaving the
+//!   choice to the upstream;
+//! - a body larger than `--max-request-size` gets `413 Payload Too Large`, up front when
+//!   `Content-Length` announces it, otherwise once that many bytes have arrived;
+//! - a body still arriving `--read-timeout` after the request head gets `408 Request
+//!   Timeout`.
+//!
+//! Both body limits close the connection, since the rest of the body is left unread.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::PayloadError;
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpResponse};
+use bytes::Bytes;
+use futures_util::Stream;
+use std::cell::Cell;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Sleep;
+use tracing::warn;
+
+use crate::hooks::Hooks;
+use crate::serve::UPLOAD_METHODS;
+use crate::settings::Settings;
+use crate::{AppState, ServeOptions};
+
+/// Methods accepted whatever the enabled modes.
+pub const BASE_METHODS: [Method; 3] = [Method::GET, Method::HEAD, Method::OPTIONS];
+
+/// Methods `--mock` answers besides the base ones.
+const MOCK_METHODS: &str = "POST, PUT, PATCH, DELETE";
+
+/// `--max-request-size`, `--read-timeout` and `--allow-method`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// Largest request body, in bytes.
+    pub max_body_bytes: Option<u64>,
+    /// Time a request body may take to arrive.
+    pub read_timeout: Option<Duration>,
+    /// Methods accepted on top of those the enabled modes need.
+    pub extra_methods: Vec<Method>,
+    /// `--allow-method '*'`: every method is accepted.
+    pub any_method: bool,
+}
+
+impl RequestLimits {
+    pub fn from_options(options: &ServeOptions) -> Result<RequestLimits, String> {
+        if options.max_request_size == Some(0) {
+            return Err("--max-request-size must be at least 1 MiB".to_string());
+        }
+        let mut limits = RequestLimits {
+            max_body_bytes: options
+                .max_request_size
+                .map(|mib| mib.saturating_mul(1024 * 1024)),
+            read_timeout: options.read_timeout.filter(|t| !t.is_zero()),
+            ..RequestLimits::default()
+        };
+        for name in options.allow_methods.iter().map(|m| m.trim()) {
+            if name == "*" {
+                limits.any_method = true;
+                continue;
+            }
+            let method = Method::from_bytes(name.to_ascii_uppercase().as_bytes())
+                .map_err(|_| format!("--allow-method: invalid method {:?}", name))?;
+            add_methods(&mut limits.extra_methods, [method]);
+        }
+        Ok(limits)
+    }
+
+    /// Dashboard text, e.g. `bodies up to 10 MiB, 30s to read`, or `off`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(max) = self.max_body_bytes {
+            parts.push(format!("bodies up to {} MiB", max / (1024 * 1024)));
+        }
+        if let Some(timeout) = self.read_timeout {
+            parts.push(format!("{:?} to read", timeout));
+        }
+        match parts.is_empty() {
+            true => "off".to_string(),
+            false => parts.join(", "),
+        }
+    }
+}
+
+fn add_methods(methods: &mut Vec<Method>, more: impl IntoIterator<Item = Method>) {
+    for method in more {
+        if !methods.contains(&method) {
+            methods.push(method);
+        }
+    }
+}
+
+/// Methods in an `Allow`-style list such as [`UPLOAD_METHODS`].
+fn listed(list: &str) -> impl Iterator<Item = Method> + '_ {
+    list.split(',')
+        .map(|m| Method::from_bytes(m.trim().as_bytes()).expect("valid method"))
+}
+
+/// Methods the server answers with `settings` and `hooks`; `None` when it accepts any.
+pub fn allowed_methods(settings: &Settings, hooks: &Hooks) -> Option<Vec<Method>> {
+    let limits = &settings.request_limits;
+    if limits.any_method || hooks.answers_requests() {
+        return None;
+    }
+    let mut methods = BASE_METHODS.to_vec();
+    if settings.upload {
+        add_methods(&mut methods, listed(UPLOAD_METHODS));
+    }
+    #[cfg(feature = "webdav")]
+    if settings.webdav {
+        add_methods(&mut methods, listed(crate::webdav::DAV_METHODS));
+    }
+    if settings.mock.is_some() {
+        add_methods(&mut methods, listed(MOCK_METHODS));
+    }
+    if settings.graphql_mocks.is_some() {
+        add_methods(&mut methods, [Method::POST]);
+    }
+    if let Some(ref fixtures) = settings.fixtures {
+        add_methods(&mut methods, fixtures.methods()?);
+        // `/__webserve/scenario` switches between the fixtures' scenarios
+        add_methods(&mut methods, [Method::PUT, Method::POST]);
+    }
+    add_methods(&mut methods, limits.extra_methods.iter().cloned());
+    Some(methods)
+}
+
+/// Dashboard text for [`allowed_methods`], e.g. `GET, HEAD, OPTIONS`.
+pub fn describe_methods(settings: &Settings, hooks: &Hooks) -> String {
+    match allowed_methods(settings, hooks) {
+        Some(methods) if settings.proxy.is_some() => {
+            format!("{} (any under --proxy prefixes)", join_methods(&methods))
+        }
+        Some(methods) => join_methods(&methods),
+        None => "any".to_string(),
+    }
+}
+
+fn join_methods(methods: &[Method]) -> String {
+    methods
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn rejection(status: StatusCode) -> actix_web::HttpResponseBuilder {
+    let mut res = HttpResponse::build(status);
+    res.content_type("text/plain; charset=utf-8");
+    res
+}
+
+/// Middleware enforcing the method allowlist, `--max-request-size` and `--read-timeout`.
+pub async fn request_limits<B: MessageBody>(
+    mut req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let Some(data) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let settings = data.settings.load();
+    let peer = req.peer_addr().map(|a| a.ip());
+    let proxied = settings
+        .proxy
+        .as_ref()
+        .is_some_and(|proxy| proxy.route(req.path()).is_some());
+    if !BASE_METHODS.contains(req.method()) && !proxied {
+        if let Some(allowed) = allowed_methods(&settings, &data.hooks) {
+            if !allowed.contains(req.method()) {
+                warn!(
+                    peer = ?peer,
+                    method = %req.method(),
+                    path = req.path(),
+                    "request-limits: method not allowed"
+                );
+                let res = rejection(StatusCode::METHOD_NOT_ALLOWED)
+                    .insert_header((header::ALLOW, join_methods(&allowed)))
+                    .body("405 Method Not Allowed");
+                return Ok(req.into_response(res).map_into_right_body());
+            }
+        }
+    }
+    let limits = &settings.request_limits;
+    if limits.max_body_bytes.is_none() && limits.read_timeout.is_none() {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+    if let (Some(max), Some(length)) = (limits.max_body_bytes, content_length(req.headers())) {
+        if length > max {
+            warn!(
+                peer = ?peer,
+                path = req.path(),
+                "request-limits: body of {} bytes exceeds {}",
+                length,
+                max
+            );
+            let res = rejection(StatusCode::PAYLOAD_TOO_LARGE)
+                .force_close()
+                .body("413 Payload Too Large");
+            return Ok(req.into_response(res).map_into_right_body());
+        }
+    }
+    let tripped = Rc::new(Cell::new(None));
+    let payload: Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>> = Box::pin(LimitedBody {
+        inner: req.take_payload(),
+        max_bytes: limits.max_body_bytes,
+        received: 0,
+        deadline: limits.read_timeout.map(|t| Box::pin(tokio::time::sleep(t))),
+        tripped: tripped.clone(),
+    });
+    req.set_payload(Payload::from(payload));
+    let res = next.call(req).await?;
+    let Some(status) = tripped.get() else {
+        return Ok(res.map_into_left_body());
+    };
+    warn!(
+        peer = ?peer,
+        path = res.request().path(),
+        "request-limits: body cut off, {}",
+        status
+    );
+    let answer = rejection(status).force_close().body(status.to_string());
+    Ok(res.into_response(answer).map_into_right_body())
+}
+
+/// Request body stream failing once it passes `max_bytes` or is still arriving at
+/// `deadline`; records the status answering that in `tripped`.
+struct LimitedBody<S> {
+    inner: S,
+    max_bytes: Option<u64>,
+    received: u64,
+    deadline: Option<Pin<Box<Sleep>>>,
+    tripped: Rc<Cell<Option<StatusCode>>>,
+}
+
+impl<S> LimitedBody<S> {
+    fn trip(
+        &self,
+        status: StatusCode,
+        error: PayloadError,
+    ) -> Poll<Option<Result<Bytes, PayloadError>>> {
+        self.tripped.set(Some(status));
+        Poll::Ready(Some(Err(error)))
+    }
+}
+
+impl<S> Stream for LimitedBody<S>
+where
+    S: Stream<Item = Result<Bytes, PayloadError>> + Unpin,
+{
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.tripped.get().is_some() {
+            return Poll::Ready(None);
+        }
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                this.received += chunk.len() as u64;
+                if this.max_bytes.is_some_and(|max| this.received > max) {
+                    return this.trip(StatusCode::PAYLOAD_TOO_LARGE, PayloadError::Overflow);
+                }
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Pending
+                if this
+                    .deadline
+                    .as_mut()
+                    .is_some_and(|d| d.as_mut().poll(cx).is_ready()) =>
+            {
+                this.trip(
+                    StatusCode::REQUEST_TIMEOUT,
+                    PayloadError::Io(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "request body slower than --read-timeout",
+                    )),
+                )
+            }
+            other => other,
+        }
+    }
+}
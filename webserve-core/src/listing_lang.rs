@@ -0,0 +1,331 @@
+//! Translated directory listing labels and locale-aware sizes and dates (`--listing-lang`).
+//!
+//! `--listing-lang de` fixes the language; `auto` picks the best match of the request's
+//! `Accept-Language` (falling back to English) and marks listings `Vary: Accept-Language`.
+//! Dates stay in UTC; only their order and separators follow the locale.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Languages the listing page is translated into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+    It,
+    Pt,
+    Nl,
+    Ja,
+    Zh,
+}
+
+/// Every [`Locale`], in the order shown in help texts.
+pub const LOCALES: [Locale; 9] = [
+    Locale::En,
+    Locale::De,
+    Locale::Fr,
+    Locale::Es,
+    Locale::It,
+    Locale::Pt,
+    Locale::Nl,
+    Locale::Ja,
+    Locale::Zh,
+];
+
+/// Strings of one listing page.
+pub struct Labels {
+    /// Page title; `{}` is the directory path.
+    pub index_of: &'static str,
+    pub name: &'static str,
+    pub size: &'static str,
+    pub modified: &'static str,
+    /// Footer row with `--max-entries`; `{}` is the limit.
+    pub truncated: &'static str,
+    pub theme_toggle: &'static str,
+    /// `--highlight` listing button opening code files in the source viewer.
+    pub source_toggle: &'static str,
+    /// Placeholder of the search box above the listing.
+    pub search: &'static str,
+    /// `--upload` form button.
+    pub upload: &'static str,
+    /// `--upload` delete button on each entry.
+    pub delete: &'static str,
+    /// Confirmation before deleting; `{}` is the entry name.
+    pub delete_confirm: &'static str,
+    /// `--upload` button and placeholder for creating a directory.
+    pub new_folder: &'static str,
+}
+
+impl Locale {
+    /// Locale for a language tag such as `de`, `pt-BR` or `zh-Hans-CN`.
+    pub fn from_tag(tag: &str) -> Option<Locale> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        LOCALES.into_iter().find(|l| l.code() == primary)
+    }
+
+    /// Best supported language of an `Accept-Language` header, English when none fits.
+    pub fn negotiate(accept_language: &str) -> Locale {
+        let mut best: Option<(f32, Locale)> = None;
+        for item in accept_language.split(',') {
+            let mut parts = item.split(';');
+            let tag = parts.next().unwrap_or("").trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let Some(locale) = Locale::from_tag(tag) else {
+                continue;
+            };
+            if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+                best = Some((q, locale));
+            }
+        }
+        best.map(|(_, l)| l).unwrap_or_default()
+    }
+
+    /// ISO 639-1 code, also used for `<html lang>`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+            Locale::Fr => "fr",
+            Locale::Es => "es",
+            Locale::It => "it",
+            Locale::Pt => "pt",
+            Locale::Nl => "nl",
+            Locale::Ja => "ja",
+            Locale::Zh => "zh",
+        }
+    }
+
+    pub fn labels(self) -> &'static Labels {
+        match self {
+            Locale::En => &Labels {
+                index_of: "Index of {}",
+                name: "Name",
+                size: "Size",
+                modified: "Modified",
+                truncated: "Listing stopped after {} entries (--max-entries)",
+                theme_toggle: "Toggle light/dark mode",
+                source_toggle: "Open code files as highlighted source",
+                search: "Search files",
+                upload: "Upload",
+                delete: "Delete",
+                delete_confirm: "Delete {}?",
+                new_folder: "New folder",
+            },
+            Locale::De => &Labels {
+                index_of: "Inhalt von {}",
+                name: "Name",
+                size: "Größe",
+                modified: "Geändert",
+                truncated: "Auflistung nach {} Einträgen beendet (--max-entries)",
+                theme_toggle: "Hell-/Dunkelmodus umschalten",
+                source_toggle: "Codedateien als hervorgehobenen Quelltext öffnen",
+                search: "Dateien durchsuchen",
+                upload: "Hochladen",
+                delete: "Löschen",
+                delete_confirm: "{} löschen?",
+                new_folder: "Neuer Ordner",
+            },
+            Locale::Fr => &Labels {
+                index_of: "Index de {}",
+                name: "Nom",
+                size: "Taille",
+                modified: "Modifié",
+                truncated: "Liste interrompue après {} entrées (--max-entries)",
+                theme_toggle: "Basculer entre mode clair et sombre",
+                source_toggle: "Ouvrir les fichiers de code avec coloration syntaxique",
+                search: "Rechercher des fichiers",
+                upload: "Téléverser",
+                delete: "Supprimer",
+                delete_confirm: "Supprimer {} ?",
+                new_folder: "Nouveau dossier",
+            },
+            Locale::Es => &Labels {
+                index_of: "Índice de {}",
+                name: "Nombre",
+                size: "Tamaño",
+                modified: "Modificado",
+                truncated: "Listado detenido tras {} entradas (--max-entries)",
+                theme_toggle: "Cambiar modo claro/oscuro",
+                source_toggle: "Abrir archivos de código con resaltado de sintaxis",
+                search: "Buscar archivos",
+                upload: "Subir",
+                delete: "Eliminar",
+                delete_confirm: "¿Eliminar {}?",
+                new_folder: "Nueva carpeta",
+            },
+            Locale::It => &Labels {
+                index_of: "Indice di {}",
+                name: "Nome",
+                size: "Dimensione",
+                modified: "Modificato",
+                truncated: "Elenco interrotto dopo {} voci (--max-entries)",
+                theme_toggle: "Cambia modalità chiara/scura",
+                source_toggle: "Apri i file di codice con evidenziazione della sintassi",
+                search: "Cerca file",
+                upload: "Carica",
+                delete: "Elimina",
+                delete_confirm: "Eliminare {}?",
+                new_folder: "Nuova cartella",
+            },
+            Locale::Pt => &Labels {
+                index_of: "Índice de {}",
+                name: "Nome",
+                size: "Tamanho",
+                modified: "Modificado",
+                truncated: "Listagem interrompida após {} entradas (--max-entries)",
+                theme_toggle: "Alternar modo claro/escuro",
+                source_toggle: "Abrir arquivos de código com realce de sintaxe",
+                search: "Pesquisar arquivos",
+                upload: "Enviar",
+                delete: "Excluir",
+                delete_confirm: "Excluir {}?",
+                new_folder: "Nova pasta",
+            },
+            Locale::Nl => &Labels {
+                index_of: "Inhoud van {}",
+                name: "Naam",
+                size: "Grootte",
+                modified: "Gewijzigd",
+                truncated: "Lijst gestopt na {} items (--max-entries)",
+                theme_toggle: "Lichte/donkere modus wisselen",
+                source_toggle: "Codebestanden openen met syntaxismarkering",
+                search: "Bestanden zoeken",
+                upload: "Uploaden",
+                delete: "Verwijderen",
+                delete_confirm: "{} verwijderen?",
+                new_folder: "Nieuwe map",
+            },
+            Locale::Ja => &Labels {
+                index_of: "{} の一覧",
+                name: "名前",
+                size: "サイズ",
+                modified: "更新日時",
+                truncated: "{} 件で一覧を打ち切りました (--max-entries)",
+                theme_toggle: "ライト/ダークモードを切り替え",
+                source_toggle: "コードファイルをシンタックスハイライト付きで開く",
+                search: "ファイルを検索",
+                upload: "アップロード",
+                delete: "削除",
+                delete_confirm: "{} を削除しますか?",
+                new_folder: "新しいフォルダー",
+            },
+            Locale::Zh => &Labels {
+                index_of: "{} 的索引",
+                name: "名称",
+                size: "大小",
+                modified: "修改时间",
+                truncated: "列表在 {} 项后停止 (--max-entries)",
+                theme_toggle: "切换浅色/深色模式",
+                source_toggle: "以语法高亮方式打开代码文件",
+                search: "搜索文件",
+                upload: "上传",
+                delete: "删除",
+                delete_confirm: "删除 {}?",
+                new_folder: "新建文件夹",
+            },
+        }
+    }
+
+    /// Human-readable size, e.g. `1.5 KB` or `1,5 Ko`.
+    pub fn format_size(self, n: u64) -> String {
+        const KB: u64 = 1024;
+        const MB: u64 = KB * 1024;
+        const GB: u64 = MB * 1024;
+        let units = match self {
+            Locale::Fr => ["o", "Ko", "Mo", "Go"],
+            _ => ["B", "KB", "MB", "GB"],
+        };
+        let (value, unit) = match n {
+            n if n < KB => return format!("{} {}", n, units[0]),
+            n if n < MB => (n as f64 / KB as f64, units[1]),
+            n if n < GB => (n as f64 / MB as f64, units[2]),
+            n => (n as f64 / GB as f64, units[3]),
+        };
+        let value = format!("{:.1}", value);
+        match self {
+            Locale::En | Locale::Ja | Locale::Zh => format!("{} {}", value, unit),
+            _ => format!("{} {}", value.replace('.', ","), unit),
+        }
+    }
+
+    /// Date and time in the locale's field order, e.g. `2026-10-16 14:03:00` or
+    /// `16.10.2026 14:03:00`.
+    pub fn format_datetime(
+        self,
+        (y, m, d): (u32, u32, u32),
+        (h, min, s): (u64, u64, u64),
+    ) -> String {
+        let date = match self {
+            Locale::En => format!("{:04}-{:02}-{:02}", y, m, d),
+            Locale::De => format!("{:02}.{:02}.{:04}", d, m, y),
+            Locale::Nl => format!("{:02}-{:02}-{:04}", d, m, y),
+            Locale::Fr | Locale::Es | Locale::It | Locale::Pt => {
+                format!("{:02}/{:02}/{:04}", d, m, y)
+            }
+            Locale::Ja | Locale::Zh => format!("{:04}/{:02}/{:02}", y, m, d),
+        };
+        format!("{} {:02}:{:02}:{:02}", date, h, min, s)
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// `--listing-lang`: a fixed language or `auto` (from `Accept-Language`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListingLang {
+    Auto,
+    Fixed(Locale),
+}
+
+impl Default for ListingLang {
+    fn default() -> Self {
+        ListingLang::Fixed(Locale::En)
+    }
+}
+
+impl ListingLang {
+    /// Language for a request carrying `accept_language`.
+    pub fn resolve(self, accept_language: Option<&str>) -> Locale {
+        match self {
+            ListingLang::Auto => accept_language.map(Locale::negotiate).unwrap_or_default(),
+            ListingLang::Fixed(locale) => locale,
+        }
+    }
+}
+
+impl FromStr for ListingLang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(ListingLang::Auto);
+        }
+        Locale::from_tag(s).map(ListingLang::Fixed).ok_or_else(|| {
+            let codes: Vec<&str> = LOCALES.iter().map(|l| l.code()).collect();
+            format!(
+                "unknown listing language {} (expected auto or one of {})",
+                s,
+                codes.join(", ")
+            )
+        })
+    }
+}
+
+impl fmt::Display for ListingLang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListingLang::Auto => f.write_str("auto"),
+            ListingLang::Fixed(locale) => locale.fmt(f),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! `/__webserve/manifest`: what a running instance is actually serving.
+//!
+//! A JSON list of every file under the static root with its size and SHA-256, so a
+//! deployment script can compare a preview against the build it expects. With `--watch`
+//! the manifest is cached and dropped whenever the watcher fires; otherwise it is rebuilt
+//! per request. Either way unchanged files are not re-hashed (see [`cached_sha256`]).
+
+use actix_web::{web, HttpResponse, Responder};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::conditional::{cached_sha256, FileHashCache};
+use crate::path::url_path_under;
+use crate::walk::{walk, WalkLimits};
+use crate::AppState;
+
+/// One served file.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// URL path, e.g. `/assets/app.js`.
+    pub path: String,
+    pub size: u64,
+    /// Hex SHA-256 of the contents.
+    pub sha256: String,
+}
+
+/// Files under the static root, sorted by path.
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    /// Seconds since the Unix epoch when the manifest was built.
+    pub generated: u64,
+    pub file_count: usize,
+    pub total_size: u64,
+    /// True when `--max-depth` / `--max-entries` cut the walk short.
+    pub truncated: bool,
+    pub files: Vec<ManifestEntry>,
+}
+
+/// Last built manifest; `None` until requested or after the watcher saw a change.
+pub type ManifestCache = Arc<RwLock<Option<Arc<Manifest>>>>;
+
+/// Walks `root` within `limits` and hashes every file; unreadable files are skipped.
+pub fn build_manifest(root: &Path, limits: WalkLimits, hashes: &FileHashCache) -> Manifest {
+    let tree = walk(root, limits);
+    let mut files: Vec<ManifestEntry> = tree
+        .files
+        .iter()
+        .filter_map(|file| {
+            let path = url_path_under(root, file)?;
+            let size = std::fs::metadata(file).ok()?.len();
+            let sha256 = cached_sha256(file, hashes).ok()?.tag().to_string();
+            Some(ManifestEntry { path, size, sha256 })
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Manifest {
+        generated: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        file_count: files.len(),
+        total_size: files.iter().map(|f| f.size).sum(),
+        truncated: tree.truncated,
+        files,
+    }
+}
+
+/// `GET /__webserve/manifest`: the served files with sizes and hashes as JSON.
+pub async fn manifest(data: web::Data<AppState>) -> impl Responder {
+    let cached = data
+        .watch
+        .then(|| data.manifest.read().ok().and_then(|m| m.clone()))
+        .flatten();
+    let manifest = match cached {
+        Some(manifest) => manifest,
+        None => {
+            let root = data.static_dir.clone();
+            let limits = data.settings.load().limits;
+            let hashes = data.file_hashes.clone();
+            let built = web::block(move || build_manifest(&root, limits, &hashes)).await;
+            let Ok(built) = built else {
+                return HttpResponse::InternalServerError().body("manifest build failed");
+            };
+            let built = Arc::new(built);
+            if data.watch {
+                if let Ok(mut guard) = data.manifest.write() {
+                    *guard = Some(built.clone());
+                }
+            }
+            built
+        }
+    };
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(&*manifest)
+}
@@ -0,0 +1,104 @@
+//! Markdown rendered as HTML pages (`--render-markdown`).
+//!
+//! `.md` / `.markdown` files, and the `README.md` of a directory without `index.html`, are
+//! served as styled HTML; `?raw=1` returns the file unchanged. Raw HTML inside the Markdown
+//! is passed through, as on most documentation sites, so only render trusted folders.
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+use std::path::Path;
+
+use crate::branding::{Branding, BRAND_STYLE};
+use crate::path::query_flag;
+use crate::serve::html_escape;
+
+/// Extensions rendered under `--render-markdown` (case-insensitive).
+pub const MARKDOWN_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+/// Page styles: a readable column, code blocks and tables, dark when the system is.
+const MARKDOWN_STYLE: &str = r#"
+    :root { --text: #1f2937; --text-muted: #6b7280; --bg: #ffffff; --border: #e5e7eb; --code-bg: #f3f4f6; --link: #2563eb; }
+    @media (prefers-color-scheme: dark) {
+      :root { --text: #e5e7eb; --text-muted: #9ca3af; --bg: #111827; --border: #374151; --code-bg: #1f2937; --link: #60a5fa; }
+    }
+    body { margin: 0; background: var(--bg); color: var(--text); font: 16px/1.6 system-ui, -apple-system, "Segoe UI", sans-serif; }
+    .container { max-width: 48rem; margin: 0 auto; padding: 2rem 1.25rem 3rem; }
+    a { color: var(--link); }
+    h1, h2 { border-bottom: 1px solid var(--border); padding-bottom: 0.3em; }
+    code { background: var(--code-bg); border-radius: 4px; padding: 0.15em 0.35em; font-size: 0.9em; }
+    pre { background: var(--code-bg); border-radius: 6px; padding: 1rem; overflow-x: auto; }
+    pre code { padding: 0; background: none; }
+    blockquote { margin: 0; padding-left: 1rem; border-left: 4px solid var(--border); color: var(--text-muted); }
+    table { border-collapse: collapse; }
+    th, td { border: 1px solid var(--border); padding: 0.4rem 0.75rem; }
+    img { max-width: 100%; }
+"#;
+
+/// True for files rendered under `--render-markdown`.
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            MARKDOWN_EXTENSIONS
+                .iter()
+                .any(|m| ext.eq_ignore_ascii_case(m))
+        })
+}
+
+/// True when the query asks for the source: `raw`, `raw=1` or `raw=true`.
+pub fn wants_raw(query: &str) -> bool {
+    query_flag(query, "raw")
+}
+
+/// Renders `source` as a full HTML page titled after its first heading, or `fallback_title`.
+pub fn render_markdown(source: &str, fallback_title: &str, branding: &Branding) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS;
+    let title = first_heading(Parser::new_ext(source, options))
+        .unwrap_or_else(|| fallback_title.to_string());
+    let mut content = String::with_capacity(source.len() * 3 / 2);
+    html::push_html(&mut content, Parser::new_ext(source, options));
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{page_title}</title>
+  <style>{style}{brand_style}
+  </style>
+</head>
+<body>
+  <div class="container">
+    {brand}
+    <article class="markdown">
+{content}    </article>
+    {footer}
+  </div>
+</body>
+</html>"#,
+        page_title = html_escape(&branding.page_title(&title)),
+        style = MARKDOWN_STYLE,
+        brand_style = BRAND_STYLE,
+        brand = branding.banner_html(),
+        content = content,
+        footer = branding.footer_html(),
+    )
+}
+
+/// Plain text of the first heading, if any.
+fn first_heading<'a>(events: impl Iterator<Item = Event<'a>>) -> Option<String> {
+    let mut text = String::new();
+    let mut inside = false;
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading { .. }) => inside = true,
+            Event::End(TagEnd::Heading(_)) => break,
+            Event::Text(t) | Event::Code(t) if inside => text.push_str(&t),
+            _ => {}
+        }
+    }
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
@@ -0,0 +1,89 @@
+//! `--mime EXT=TYPE`: Content-Type overrides and additions for served files.
+//!
+//! File types are guessed from the extension, and the guesses lag behind newer asset
+//! formats: unknown extensions such as `.glb` go out as `application/octet-stream`, which
+//! breaks `WebAssembly.instantiateStreaming` and strict `fetch()` consumers. Each `--mime`
+//! maps an extension (case-insensitive, leading dot optional) to the type to send instead:
+//!
+//! ```text
+//! --mime wasm=application/wasm --mime glb=model/gltf-binary
+//! ```
+
+use actix_web::mime::{self, Mime};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::ServeOptions;
+
+/// Types sent for files by extension, overriding the guessed ones.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MimeOverrides(BTreeMap<String, Mime>);
+
+impl MimeOverrides {
+    /// Parses `EXT=TYPE`, e.g. `glb=model/gltf-binary`.
+    pub fn parse(spec: &str) -> Result<(String, Mime), String> {
+        let (ext, mime) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("--mime {}: expected EXT=TYPE", spec))?;
+        let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+        if ext.is_empty() || ext.contains('/') {
+            return Err(format!(
+                "--mime {}: expected a file extension before =",
+                spec
+            ));
+        }
+        let mime = mime
+            .trim()
+            .parse::<Mime>()
+            .map_err(|e| format!("--mime {}: {}", spec, e))?;
+        Ok((ext, mime))
+    }
+
+    pub fn from_options(options: &ServeOptions) -> Result<MimeOverrides, String> {
+        options
+            .mime
+            .iter()
+            .map(|spec| MimeOverrides::parse(spec))
+            .collect::<Result<_, _>>()
+            .map(MimeOverrides)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Type configured for `path`'s extension, if any.
+    pub fn for_path(&self, path: &Path) -> Option<&Mime> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        self.0.get(&ext)
+    }
+
+    /// `ext=type` pairs, for logs and the dashboard.
+    pub fn describe(&self) -> String {
+        self.0
+            .iter()
+            .map(|(ext, mime)| format!("{}={}", ext, mime))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Whether browsers should display a file of type `mime` rather than download it; the
+/// same split the file handler makes for guessed types.
+pub fn is_inline(mime: &Mime) -> bool {
+    match mime.type_() {
+        mime::IMAGE | mime::TEXT | mime::AUDIO | mime::VIDEO => true,
+        mime::APPLICATION => {
+            let subtype = mime.subtype();
+            subtype == mime::JAVASCRIPT
+                || subtype == mime::JSON
+                || subtype == "wasm"
+                || subtype == "xhtml"
+        }
+        _ => false,
+    }
+}
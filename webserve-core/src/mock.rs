@@ -0,0 +1,401 @@
+//! `--mock <api.json>`: a fake REST API backed by a JSON file, in the style of json-server.
+//!
+//! Each top-level key of the file is a route under `--mock-prefix` (default `/`). An array
+//! is a collection of objects identified by their `id`:
+//!
+//! - `GET /posts` lists them, filtered by `?field=value`, ordered by `_sort` / `_order=desc`
+//!   and cut at `_limit` (`X-Total-Count` tells the count before the cut);
+//! - `POST /posts` adds one (`201`; a missing `id` becomes the next number);
+//! - `GET`, `PUT` (replace), `PATCH` (merge) and `DELETE /posts/{id}` work on one item.
+//!
+//! Any other value is a single resource answering `GET`, `PUT` and `PATCH` at `/profile`.
+//! Paths naming no key fall through to the files, so the API and the site share one server.
+//!
+//! Changes live in memory unless `--mock-persist` writes them back to the file, which under
+//! `--watch` also reloads the browsers. Edits to the file are picked up on the next request.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{ALLOW, LOCATION};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use bytes::Bytes;
+use notify::event::{DataChange, ModifyKind};
+use notify::{Event, EventKind};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+use tracing::{debug, info, warn};
+
+use crate::AppState;
+
+#[derive(Debug)]
+struct Db {
+    data: Map<String, Value>,
+    modified: Option<SystemTime>,
+}
+
+/// The `--mock` database and its routes.
+#[derive(Debug)]
+pub struct MockApi {
+    path: PathBuf,
+    prefix: String,
+    persist: bool,
+    db: RwLock<Db>,
+}
+
+/// Whether a request changed the data.
+type Outcome = (HttpResponse, bool);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read_db(path: &Path) -> Result<Map<String, Value>, String> {
+    let text = std::fs::read(path).map_err(|e| format!("--mock {}: {}", path.display(), e))?;
+    match serde_json::from_slice(&text) {
+        Ok(Value::Object(data)) => Ok(data),
+        Ok(_) => Err(format!(
+            "--mock {}: expected a JSON object of routes",
+            path.display()
+        )),
+        Err(e) => Err(format!("--mock {}: {}", path.display(), e)),
+    }
+}
+
+/// `id` as compared with URL segments: strings as is, numbers in decimal.
+fn id_of(item: &Value) -> Option<String> {
+    match item.get("id")? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Query values compare as text against strings, numbers and booleans.
+fn field_matches(item: &Value, field: &str, wanted: &str) -> bool {
+    match item.get(field) {
+        Some(Value::String(s)) => s == wanted,
+        Some(Value::Number(n)) => wanted.parse::<f64>().ok() == n.as_f64(),
+        Some(Value::Bool(b)) => wanted.parse::<bool>().ok() == Some(*b),
+        _ => false,
+    }
+}
+
+fn compare(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(Value::Object(Map::new()))
+}
+
+fn not_allowed(allow: &'static str) -> HttpResponse {
+    HttpResponse::MethodNotAllowed()
+        .insert_header((ALLOW, allow))
+        .finish()
+}
+
+fn object(body: &[u8]) -> Result<Map<String, Value>, HttpResponse> {
+    match serde_json::from_slice(body) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(HttpResponse::BadRequest().body("expected a JSON object")),
+        Err(e) => Err(HttpResponse::BadRequest().body(format!("invalid JSON: {}", e))),
+    }
+}
+
+impl MockApi {
+    /// Loads `path`; routes are served under `prefix` (`/` or e.g. `/api`).
+    pub fn load(path: &Path, prefix: &str, persist: bool) -> Result<MockApi, String> {
+        let data = read_db(path)?;
+        Ok(MockApi {
+            path: path.to_path_buf(),
+            prefix: prefix.trim_end_matches('/').to_string(),
+            persist,
+            db: RwLock::new(Db {
+                data,
+                modified: modified(path),
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn persist(&self) -> bool {
+        self.persist
+    }
+
+    /// Route names, e.g. `["posts", "profile"]`.
+    pub fn routes(&self) -> Vec<String> {
+        match self.db.read() {
+            Ok(db) => db.data.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// URL of route `name`, e.g. `/api/posts`.
+    pub fn url(&self, name: &str) -> String {
+        format!("{}/{}", self.prefix, name)
+    }
+
+    /// Route name and item id `path` addresses, when it names a key of the file.
+    fn route<'a>(&self, path: &'a str) -> Option<(&'a str, Option<&'a str>)> {
+        let rest = path.strip_prefix(self.prefix.as_str())?.strip_prefix('/')?;
+        let rest = rest.strip_suffix('/').unwrap_or(rest);
+        let (name, id) = match rest.split_once('/') {
+            Some((name, id)) if !id.is_empty() && !id.contains('/') => (name, Some(id)),
+            Some(_) => return None,
+            None => (rest, None),
+        };
+        self.refresh();
+        let known = self.db.read().is_ok_and(|db| db.data.contains_key(name));
+        known.then_some((name, id))
+    }
+
+    /// Re-reads the file after outside edits; a broken file keeps the current data.
+    fn refresh(&self) {
+        let current = modified(&self.path);
+        if self.db.read().is_ok_and(|db| db.modified == current) {
+            return;
+        }
+        let Ok(mut db) = self.db.write() else {
+            return;
+        };
+        db.modified = current;
+        match read_db(&self.path) {
+            Ok(data) => {
+                info!("Mock API reloaded: {}", self.path.display());
+                db.data = data;
+            }
+            Err(e) => warn!("{}; keeping the previous data", e),
+        }
+    }
+
+    /// Writes the data back through a temporary sibling file.
+    fn save(&self, db: &mut Db) -> Result<(), String> {
+        let text = serde_json::to_vec_pretty(&db.data).map_err(|e| e.to_string())?;
+        let name = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let temp = self.path.with_file_name(format!(".{}.mock.tmp", name));
+        std::fs::write(&temp, text)
+            .and_then(|()| std::fs::rename(&temp, &self.path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&temp);
+                format!("--mock-persist {}: {}", self.path.display(), e)
+            })?;
+        db.modified = modified(&self.path);
+        Ok(())
+    }
+
+    /// Answers `method` on `path`, or `None` when the path is not an API route.
+    pub fn respond(
+        &self,
+        method: &Method,
+        path: &str,
+        query: &str,
+        body: &[u8],
+    ) -> Option<Outcome> {
+        let (name, id) = self.route(path)?;
+        let Ok(mut db) = self.db.write() else {
+            return Some((HttpResponse::InternalServerError().finish(), false));
+        };
+        let (res, changed) = match db.data.get_mut(name)? {
+            Value::Array(items) => match id {
+                None => self.collection(items, name, method, query, body),
+                Some(id) => item(items, id, method, body),
+            },
+            _ if id.is_some() => (not_found(), false),
+            value => single(value, method, body),
+        };
+        if changed && self.persist {
+            if let Err(e) = self.save(&mut db) {
+                warn!("{}", e);
+                return Some((HttpResponse::InternalServerError().body(e), false));
+            }
+        }
+        Some((res, changed))
+    }
+
+    fn collection(
+        &self,
+        items: &mut Vec<Value>,
+        name: &str,
+        method: &Method,
+        query: &str,
+        body: &[u8],
+    ) -> Outcome {
+        match *method {
+            Method::GET | Method::HEAD => (list(items, query), false),
+            Method::POST => {
+                let mut new = match object(body) {
+                    Ok(new) => new,
+                    Err(res) => return (res, false),
+                };
+                let id = match new.get("id") {
+                    Some(id) => id.clone(),
+                    None => {
+                        let max = items
+                            .iter()
+                            .filter_map(|item| item.get("id")?.as_u64())
+                            .max()
+                            .unwrap_or(0);
+                        Value::from(max + 1)
+                    }
+                };
+                new.insert("id".to_string(), id);
+                let new = Value::Object(new);
+                let id = id_of(&new).unwrap_or_default();
+                if items
+                    .iter()
+                    .any(|item| id_of(item).as_deref() == Some(id.as_str()))
+                {
+                    return (
+                        HttpResponse::Conflict().body(format!("{} {} exists", name, id)),
+                        false,
+                    );
+                }
+                items.push(new.clone());
+                let res = HttpResponse::Created()
+                    .insert_header((LOCATION, format!("{}/{}", self.url(name), id)))
+                    .json(new);
+                (res, true)
+            }
+            _ => (not_allowed("GET, HEAD, POST"), false),
+        }
+    }
+}
+
+fn list(items: &[Value], query: &str) -> HttpResponse {
+    let params = web::Query::<Vec<(String, String)>>::from_query(query)
+        .map(|q| q.into_inner())
+        .unwrap_or_default();
+    let param = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v);
+    let mut found: Vec<&Value> = items
+        .iter()
+        .filter(|item| {
+            params
+                .iter()
+                .filter(|(k, _)| !k.starts_with('_'))
+                .all(|(k, v)| field_matches(item, k, v))
+        })
+        .collect();
+    if let Some(field) = param("_sort") {
+        found.sort_by(|a, b| compare(a.get(field), b.get(field)));
+        if param("_order").is_some_and(|o| o.eq_ignore_ascii_case("desc")) {
+            found.reverse();
+        }
+    }
+    let total = found.len();
+    if let Some(limit) = param("_limit").and_then(|l| l.parse().ok()) {
+        found.truncate(limit);
+    }
+    HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(found)
+}
+
+fn item(items: &mut Vec<Value>, id: &str, method: &Method, body: &[u8]) -> Outcome {
+    let Some(index) = items
+        .iter()
+        .position(|item| id_of(item).as_deref() == Some(id))
+    else {
+        return (not_found(), false);
+    };
+    match *method {
+        Method::GET | Method::HEAD => (HttpResponse::Ok().json(&items[index]), false),
+        Method::PUT | Method::PATCH => {
+            let update = match object(body) {
+                Ok(update) => update,
+                Err(res) => return (res, false),
+            };
+            let key = items[index].get("id").cloned();
+            let item = &mut items[index];
+            match (method == Method::PATCH, item.as_object_mut()) {
+                (true, Some(fields)) => fields.extend(update),
+                _ => *item = Value::Object(update),
+            }
+            if let (Some(key), Some(fields)) = (key, item.as_object_mut()) {
+                fields.insert("id".to_string(), key);
+            }
+            (HttpResponse::Ok().json(&*item), true)
+        }
+        Method::DELETE => {
+            items.remove(index);
+            (HttpResponse::Ok().json(Value::Object(Map::new())), true)
+        }
+        _ => (not_allowed("GET, HEAD, PUT, PATCH, DELETE"), false),
+    }
+}
+
+fn single(value: &mut Value, method: &Method, body: &[u8]) -> Outcome {
+    match *method {
+        Method::GET | Method::HEAD => (HttpResponse::Ok().json(&*value), false),
+        Method::PUT | Method::PATCH => {
+            let update = match serde_json::from_slice::<Value>(body) {
+                Ok(update) => update,
+                Err(e) => {
+                    return (
+                        HttpResponse::BadRequest().body(format!("invalid JSON: {}", e)),
+                        false,
+                    )
+                }
+            };
+            match (method == Method::PATCH, value.as_object_mut(), update) {
+                (true, Some(fields), Value::Object(update)) => fields.extend(update),
+                (_, _, update) => *value = update,
+            }
+            (HttpResponse::Ok().json(&*value), true)
+        }
+        _ => (not_allowed("GET, HEAD, PUT, PATCH"), false),
+    }
+}
+
+/// Middleware answering `--mock` routes ahead of the files.
+pub async fn mock_api(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let state = req.app_data::<web::Data<AppState>>().cloned();
+    let api = state
+        .as_ref()
+        .and_then(|data| data.settings.load().mock.clone())
+        .filter(|api| api.route(req.path()).is_some());
+    let (Some(state), Some(api)) = (state, api) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let body = match *req.method() {
+        Method::POST | Method::PUT | Method::PATCH => req.extract::<Bytes>().await?,
+        _ => Bytes::new(),
+    };
+    let Some((res, changed)) = api.respond(req.method(), req.path(), req.query_string(), &body)
+    else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    debug!(method = %req.method(), path = req.path(), changed, "answered by --mock");
+    // Inside the root the watcher reports the write itself
+    let watched = api.path().starts_with(state.static_dir.as_path());
+    if changed && api.persist() && state.watch && !watched {
+        let event = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path(api.path().to_path_buf());
+        state.publish_changes(&[event]);
+    }
+    let (req, _) = req.into_parts();
+    Ok(ServiceResponse::new(req, res).map_into_right_body())
+}
@@ -0,0 +1,213 @@
+//! `--mount PREFIX=DIR` and `--vhost HOST=DIR`: more sites served next to the main directory.
+//!
+//! Every site is a [`Webserve`](crate::Webserve) of its own, built from the same options
+//! with its directory swapped in, so it has its own watcher, reload channel, history and
+//! `/__webserve/` pages: editing one site only reloads the browsers viewing it. The
+//! [`Mount`] a site sits at is kept in its [`AppState`](crate::AppState).
+//!
+//! A path mount sees its requests with the prefix taken off; the pages it renders link
+//! under [`Mount::base`], absolute `Location` headers it answers with get the prefix put
+//! back, and `/docs` redirects to `/docs/`. A WebDAV `Destination` under the prefix loses it
+//! too; one outside the mount gets `502 Bad Gateway`, as RFC 4918 answers a destination in
+//! another namespace. A virtual host answers requests whose `Host` is
+//! its name, port ignored. Anything no site claims goes to the main directory.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{HttpServiceFactory, ServiceRequest, ServiceResponse, Url};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::Uri;
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, Error, HttpResponse, Scope};
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::{ServeOptions, ADMIN_PREFIX};
+
+/// Where a site is served from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Mount {
+    /// The main directory, at `/` on every host.
+    #[default]
+    Root,
+    /// `--mount`: under a URL prefix such as `/docs` (no trailing slash).
+    Path(String),
+    /// `--vhost`: at `/` for one host name.
+    Host(String),
+}
+
+impl Mount {
+    /// Parses `--mount PREFIX=DIR`. Prefixes are made of unreserved URL characters, so they
+    /// can go into pages and scripts as they are, and may not hide `/reload` or the admin
+    /// pages of the main directory.
+    pub fn parse_path(spec: &str) -> Result<(Mount, PathBuf), String> {
+        let (prefix, dir) = split_spec(spec, "--mount", "PREFIX=DIR")?;
+        let prefix = prefix.trim_end_matches('/');
+        let bad = |why: &str| Err(format!("--mount {}: {}", spec, why));
+        let Some(path) = prefix.strip_prefix('/') else {
+            return bad("the prefix must start with /");
+        };
+        if path.is_empty() {
+            return bad("/ is the main directory; mount under a prefix such as /docs");
+        }
+        for segment in path.split('/') {
+            if segment.is_empty() || segment == "." || segment == ".." {
+                return bad("empty, . and .. segments are not allowed");
+            }
+            let unreserved = |c: char| c.is_ascii_alphanumeric() || "-._~".contains(c);
+            if !segment.chars().all(unreserved) {
+                return bad("use letters, digits, -, ., _ and ~ in the prefix");
+            }
+        }
+        if prefix == "/reload" || under(prefix, ADMIN_PREFIX) {
+            return bad("the prefix is taken by webserve itself");
+        }
+        Ok((Mount::Path(prefix.to_string()), dir))
+    }
+
+    /// Parses `--vhost HOST=DIR`; the host is compared case-insensitively, without a port.
+    pub fn parse_host(spec: &str) -> Result<(Mount, PathBuf), String> {
+        let (host, dir) = split_spec(spec, "--vhost", "HOST=DIR")?;
+        let valid = |c: char| c.is_ascii_alphanumeric() || "-.".contains(c);
+        if host.is_empty() || !host.chars().all(valid) {
+            return Err(format!(
+                "--vhost {}: expected a host name such as docs.localhost",
+                spec
+            ));
+        }
+        Ok((Mount::Host(host.to_ascii_lowercase()), dir))
+    }
+
+    /// URL path the site's pages live under: a path mount's prefix, else empty.
+    pub fn base(&self) -> &str {
+        match self {
+            Mount::Path(prefix) => prefix,
+            Mount::Root | Mount::Host(_) => "",
+        }
+    }
+}
+
+impl fmt::Display for Mount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mount::Root => f.write_str("/"),
+            Mount::Path(prefix) => f.write_str(prefix),
+            Mount::Host(host) => write!(f, "host {}", host),
+        }
+    }
+}
+
+/// `NAME=DIR` of `flag`, trimmed.
+fn split_spec(spec: &str, flag: &str, form: &str) -> Result<(String, PathBuf), String> {
+    match spec.split_once('=') {
+        Some((name, dir)) if !dir.trim().is_empty() => {
+            Ok((name.trim().to_string(), PathBuf::from(dir.trim())))
+        }
+        _ => Err(format!("{}: expected {}, got {}", flag, form, spec)),
+    }
+}
+
+/// True when `path` is `prefix` or lies under it.
+fn under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Every `--mount` and `--vhost` of `options` with its directory, mounts first. Two sites
+/// may not claim the same prefix or host.
+pub fn sites(options: &ServeOptions) -> Result<Vec<(Mount, PathBuf)>, String> {
+    let mut sites = Vec::new();
+    let paths = options.mounts.iter().map(|s| Mount::parse_path(s));
+    let hosts = options.vhosts.iter().map(|s| Mount::parse_host(s));
+    for site in paths.chain(hosts) {
+        let (mount, dir) = site?;
+        if sites.iter().any(|(m, _)| *m == mount) {
+            return Err(format!("{} is configured twice", mount));
+        }
+        sites.push((mount, dir));
+    }
+    Ok(sites)
+}
+
+/// Options for the site at `dir`: `options` with the directory replaced and without what
+/// belongs to the whole server: the other sites, `--file`, `--exec` and `--watch-path`.
+pub fn site_options(options: &ServeOptions, dir: PathBuf) -> ServeOptions {
+    ServeOptions {
+        directory: Some(dir),
+        file: None,
+        mounts: Vec::new(),
+        vhosts: Vec::new(),
+        exec: None,
+        watch_paths: Vec::new(),
+        ..options.clone()
+    }
+}
+
+/// `site` answering under `prefix` (see the module docs).
+pub(crate) fn path_service(prefix: &str, site: Scope) -> impl HttpServiceFactory {
+    let base = prefix.to_string();
+    web::scope(prefix)
+        .wrap(from_fn(move |req, next| {
+            strip_prefix(base.clone(), req, next)
+        }))
+        .service(site)
+}
+
+/// Takes `prefix` off the request path and puts it back on a `Location` the site answers with.
+async fn strip_prefix(
+    prefix: String,
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let query = req
+        .uri()
+        .query()
+        .map(|q| format!("?{}", q))
+        .unwrap_or_default();
+    let rest = req.match_info().unprocessed().to_string();
+    if rest.is_empty() {
+        let res = HttpResponse::MovedPermanently()
+            .insert_header((header::LOCATION, format!("{}/{}", prefix, query)))
+            .finish();
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+    if let Some(dest) = req
+        .headers()
+        .get("destination")
+        .and_then(|v| v.to_str().ok())
+    {
+        let path = match dest.split_once("://") {
+            Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+            None => dest,
+        };
+        let inner = path
+            .strip_prefix(prefix.as_str())
+            .filter(|rest| rest.starts_with('/'))
+            .and_then(|rest| HeaderValue::from_str(rest).ok());
+        let Some(inner) = inner else {
+            let res = HttpResponse::BadGateway().body("Destination is outside this mount");
+            return Ok(req.into_response(res).map_into_right_body());
+        };
+        req.headers_mut()
+            .insert(HeaderName::from_static("destination"), inner);
+    }
+    let uri: Uri = match format!("{}{}", rest, query).parse() {
+        Ok(uri) => uri,
+        Err(_) => {
+            let res = HttpResponse::BadRequest().finish();
+            return Ok(req.into_response(res).map_into_right_body());
+        }
+    };
+    req.match_info_mut().set(Url::new(uri.clone()));
+    req.head_mut().uri = uri;
+    let mut res = next.call(req).await?;
+    let location = res
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .filter(|l| l.starts_with('/') && !l.starts_with("//"))
+        .and_then(|l| HeaderValue::from_str(&format!("{}{}", prefix, l)).ok());
+    if let Some(location) = location {
+        res.headers_mut().insert(header::LOCATION, location);
+    }
+    Ok(res.map_into_left_body())
+}
@@ -0,0 +1,201 @@
+//! Path normalization and static root validation.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::StaticDirError;
+
+/// Ensures the server root exists and is a directory before binding.
+pub fn validate_static_root(path: &Path) -> Result<(), StaticDirError> {
+    if !path.exists() {
+        Err(StaticDirError::NotFound)
+    } else if !path.is_dir() {
+        Err(StaticDirError::NotADirectory)
+    } else {
+        Ok(())
+    }
+}
+
+/// Collapses repeated `/` and `.` segments; rejects `..`. Root is `/`.
+pub fn normalize_url_path(path: &str) -> Option<String> {
+    let mut out: Vec<&str> = Vec::new();
+    for seg in path.split('/') {
+        if seg.is_empty() || seg == "." {
+            continue;
+        }
+        if seg == ".." {
+            return None;
+        }
+        out.push(seg);
+    }
+    if out.is_empty() {
+        Some("/".to_string())
+    } else {
+        Some(format!("/{}", out.join("/")))
+    }
+}
+
+/// Joins normalized URL path onto the serve root (no `..`).
+pub fn join_serve_path(base: &Path, normalized_path: &str) -> Option<PathBuf> {
+    let rel = normalized_path.trim_start_matches('/');
+    let mut out = base.to_path_buf();
+    for c in Path::new(rel).components() {
+        match c {
+            Component::Normal(part) => out.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+/// Decodes `%XX` escapes in a request path; `None` if an escape is malformed or not UTF-8.
+///
+/// Run before [`normalize_url_path`] so encoded `..` or `/` cannot slip past it.
+pub fn percent_decode_path(path: &str) -> Option<String> {
+    if !path.contains('%') {
+        return Some(path.to_string());
+    }
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Percent-encodes one path segment for use in an `href` or `Location`.
+pub fn percent_encode_path_segment(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ' ' => out.push_str("%20"),
+            '/' => out.push_str("%2F"),
+            '%' => out.push_str("%25"),
+            '#' => out.push_str("%23"),
+            '?' => out.push_str("%3F"),
+            '&' => out.push_str("%26"),
+            '=' => out.push_str("%3D"),
+            '+' => out.push_str("%2B"),
+            c if c.is_ascii() && !c.is_ascii_alphanumeric() && "-_.!~*'()".contains(c) => {
+                out.push(c)
+            }
+            c if c.is_ascii_alphanumeric() => out.push(c),
+            c => {
+                for b in c.to_string().as_bytes() {
+                    out.push_str(&format!("%{:02X}", b));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Percent-encodes each segment of a decoded URL path, keeping `/` separators. Root is `/`.
+pub fn encode_url_path(path: &str) -> String {
+    let encoded: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(percent_encode_path_segment)
+        .collect();
+    format!("/{}", encoded.join("/"))
+}
+
+/// URL path (`/a/b.js`) of a file under `root`; `None` when `path` is outside it.
+///
+/// A relative `root` (`--dir dist`) also matches the absolute paths the watcher reports.
+pub fn url_path_under(root: &Path, path: &Path) -> Option<String> {
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) if root.is_relative() => {
+            let cwd = std::env::current_dir().ok()?;
+            path.strip_prefix(cwd.join(root)).ok()?
+        }
+        Err(_) => return None,
+    };
+    let parts: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(format!("/{}", parts.join("/")))
+}
+
+/// Host as the socket API wants it: IPv6 brackets (`[::1]`) removed.
+pub fn bind_host(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// True when a `Host` header names this machine: `localhost` (or a `*.localhost` name), a
+/// loopback address, or the host of one of the `bound` addresses (`host:port`, comma
+/// separated, as in [`AppState::addr`](crate::AppState)).
+///
+/// A page reaching a loopback server through DNS rebinding still sends its own host name,
+/// so checking `Host` tells it apart from the developer's own tabs.
+pub fn host_is_local(host: &str, bound: &str) -> bool {
+    let name = |host: &str| -> String {
+        let host = host.trim();
+        let name = match host.strip_prefix('[') {
+            Some(rest) => rest.split(']').next().unwrap_or(rest),
+            None => host.split(':').next().unwrap_or(host),
+        };
+        name.trim_end_matches('.').to_ascii_lowercase()
+    };
+    let host = name(host);
+    if host.is_empty() {
+        return false;
+    }
+    host == "localhost"
+        || host.ends_with(".localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.to_canonical().is_loopback())
+        || bound
+            .split(',')
+            .map(name)
+            .any(|bound| !bound.is_empty() && bound == host)
+}
+
+/// `host:port` for logs and the dashboard, with IPv6 literals in brackets (`[::1]:8080`).
+pub fn host_port(host: &str, port: u16) -> String {
+    let host = bind_host(host);
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// URL for a browser on this machine: wildcard binds become loopback, IPv6 hosts get brackets.
+///
+/// `path` may carry a query or fragment; a missing leading `/` is added.
+pub fn browser_url(host: &str, port: u16, path: &str) -> String {
+    let host = match host {
+        "0.0.0.0" | "" => "127.0.0.1".to_string(),
+        "::" | "[::]" => "[::1]".to_string(),
+        h if h.contains(':') && !h.starts_with('[') => format!("[{}]", h),
+        h => h.to_string(),
+    };
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+    format!("http://{}:{}{}", host, port, path)
+}
+
+/// True when `query` sets `name`, `name=1` or `name=true`.
+pub(crate) fn query_flag(query: &str, name: &str) -> bool {
+    query.split('&').any(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, "1"));
+        key == name && matches!(value, "1" | "true" | "")
+    })
+}
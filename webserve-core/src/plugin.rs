@@ -0,0 +1,464 @@
+//! `--plugin handler.wasm`: WebAssembly request handlers (`wasm-plugins` cargo feature).
+//!
+//! A plugin is a WebAssembly module, e.g. a Rust `cdylib` built for `wasm32-wasip1` or
+//! `wasm32-unknown-unknown`, exporting:
+//!
+//! - `memory`, and `webserve_alloc(len: i32) -> i32` returning room for `len` input bytes;
+//! - `webserve_on_request(ptr: i32, len: i32) -> i64`, given the request as JSON
+//!   (`{"method", "path", "query", "headers": {name: value}}`), and/or
+//! - `webserve_on_response(ptr: i32, len: i32) -> i64`, given `{"method", "path", "status",
+//!   "headers"}` of each response.
+//!
+//! Both return `ptr << 32 | len` of a JSON answer in memory, or `0` to pass. A request answer
+//! is the response: `{"status": 200, "headers": {...}, "body": "..."}`; a response answer
+//! may set `status` and `headers` (a `null` value removes the header). Request bodies are
+//! not passed in.
+//!
+//! Plugins run through [`crate::Hooks`], after `--allow` / `--deny`, each call in a fresh
+//! instance capped at [`PLUGIN_FUEL`] instructions and [`PLUGIN_MEMORY_BYTES`] of memory,
+//! so they keep no state between requests and a runaway loop ends in a `500`. The only
+//! imports offered are a few `wasi_snapshot_preview1` functions: `fd_write` to stdout /
+//! stderr (logged at debug level), `proc_exit`, `random_get`, `clock_time_get`, and empty
+//! arguments and environment; a module importing anything else is refused at startup.
+//! Under `--watch` a plugin is recompiled when its file changes; a broken build keeps the
+//! previous one running.
+
+use actix_web::dev::ResponseHead;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+use wasmi::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+/// Instructions (roughly) one plugin call may run.
+pub const PLUGIN_FUEL: u64 = 50_000_000;
+
+/// Linear memory one plugin instance may grow to.
+pub const PLUGIN_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+const WASI: &str = "wasi_snapshot_preview1";
+const ON_REQUEST: &str = "webserve_on_request";
+const ON_RESPONSE: &str = "webserve_on_response";
+
+/// Everything [`wasi_linker`] defines.
+const WASI_IMPORTS: [&str; 8] = [
+    "args_get",
+    "args_sizes_get",
+    "environ_get",
+    "environ_sizes_get",
+    "proc_exit",
+    "fd_write",
+    "random_get",
+    "clock_time_get",
+];
+
+/// WASI errno values returned by the stubs.
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+const ERRNO_FAULT: i32 = 21;
+
+/// Per-call store data.
+struct Sandbox {
+    plugin: String,
+    limits: StoreLimits,
+}
+
+#[derive(Serialize)]
+struct RequestInfo<'a> {
+    method: &'a str,
+    path: &'a str,
+    query: &'a str,
+    headers: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct ResponseInfo<'a> {
+    method: &'a str,
+    path: &'a str,
+    status: u16,
+    headers: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    status: Option<u16>,
+    #[serde(default)]
+    headers: BTreeMap<String, Option<String>>,
+    #[serde(default)]
+    body: String,
+}
+
+struct Loaded {
+    module: Arc<Module>,
+    modified: Option<SystemTime>,
+}
+
+/// A loaded `--plugin` module.
+pub struct Plugin {
+    path: PathBuf,
+    name: String,
+    engine: Engine,
+    hot_reload: bool,
+    loaded: RwLock<Loaded>,
+}
+
+impl std::fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Plugin").field("path", &self.path).finish()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Plugin {
+    /// Compiles `path` and checks its imports and exports; with `hot_reload` the file is
+    /// recompiled when it changes.
+    pub fn load(path: &Path, hot_reload: bool) -> Result<Plugin, String> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let modified = modified(path);
+        let module = compile(&engine, path)?;
+        Ok(Plugin {
+            path: path.to_path_buf(),
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            engine,
+            hot_reload,
+            loaded: RwLock::new(Loaded {
+                module: Arc::new(module),
+                modified,
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn handles_requests(&self) -> bool {
+        self.module().get_export(ON_REQUEST).is_some()
+    }
+
+    pub fn handles_responses(&self) -> bool {
+        self.module().get_export(ON_RESPONSE).is_some()
+    }
+
+    /// The module to run, recompiled first if the file changed under hot reload.
+    fn module(&self) -> Arc<Module> {
+        let read = |loaded: &Loaded| (loaded.module.clone(), loaded.modified);
+        let (module, seen) = match self.loaded.read() {
+            Ok(guard) => read(&guard),
+            Err(poisoned) => read(&poisoned.into_inner()),
+        };
+        if !self.hot_reload {
+            return module;
+        }
+        let current = modified(&self.path);
+        if current == seen {
+            return module;
+        }
+        let Ok(mut guard) = self.loaded.write() else {
+            return module;
+        };
+        if guard.modified != seen {
+            // Another request reloaded it meanwhile
+            return guard.module.clone();
+        }
+        guard.modified = current;
+        match compile(&self.engine, &self.path) {
+            Ok(module) => {
+                info!("Plugin reloaded: {}", self.path.display());
+                guard.module = Arc::new(module);
+            }
+            Err(e) => warn!("{}; keeping the previous build", e),
+        }
+        guard.module.clone()
+    }
+
+    /// Runs `export` on `input` in a fresh instance; `None` when the plugin passes.
+    fn call(&self, export: &str, input: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let module = self.module();
+        let mut store = Store::new(
+            &self.engine,
+            Sandbox {
+                plugin: self.name.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(PLUGIN_MEMORY_BYTES)
+                    .build(),
+            },
+        );
+        store.limiter(|sandbox| &mut sandbox.limits);
+        store
+            .set_fuel(PLUGIN_FUEL)
+            .map_err(|e| format!("cannot fuel plugin: {}", e))?;
+        let instance = wasi_linker(&self.engine)?
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| format!("cannot instantiate: {}", e))?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or("no `memory` export")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "webserve_alloc")
+            .map_err(|e| format!("webserve_alloc: {}", e))?;
+        let handler = instance
+            .get_typed_func::<(i32, i32), i64>(&store, export)
+            .map_err(|e| format!("{}: {}", export, e))?;
+        let len = i32::try_from(input.len()).map_err(|_| "input too large".to_string())?;
+        let ptr = alloc
+            .call(&mut store, len)
+            .map_err(|e| format!("webserve_alloc: {}", e))?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| format!("webserve_alloc returned an unusable pointer: {}", e))?;
+        let packed = handler
+            .call(&mut store, (ptr, len))
+            .map_err(|e| format!("{}: {}", export, e))? as u64;
+        if packed == 0 {
+            return Ok(None);
+        }
+        let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let out = memory
+            .data(&store)
+            .get(out_ptr..out_ptr.saturating_add(out_len))
+            .ok_or_else(|| format!("{} returned bytes outside memory", export))?;
+        Ok(Some(out.to_vec()))
+    }
+
+    fn answer(&self, export: &str, input: &impl Serialize) -> Result<Option<Answer>, String> {
+        let input = serde_json::to_vec(input).map_err(|e| e.to_string())?;
+        match self.call(export, &input)? {
+            Some(out) => serde_json::from_slice(&out)
+                .map(Some)
+                .map_err(|e| format!("{} returned invalid JSON: {}", export, e)),
+            None => Ok(None),
+        }
+    }
+
+    /// `on_request` hook: the plugin's response, or `None` to let webserve answer. A failing
+    /// plugin answers `500`.
+    pub fn on_request(&self, req: &HttpRequest) -> Option<HttpResponse> {
+        let info = RequestInfo {
+            method: req.method().as_str(),
+            path: req.path(),
+            query: req.query_string(),
+            headers: header_map(req.headers()),
+        };
+        let answer = match self.answer(ON_REQUEST, &info) {
+            Ok(answer) => answer?,
+            Err(e) => {
+                warn!("plugin {} failed on {}: {}", self.name, req.path(), e);
+                return Some(
+                    HttpResponse::InternalServerError()
+                        .content_type("text/plain; charset=utf-8")
+                        .body(format!("plugin {} failed", self.name)),
+                );
+            }
+        };
+        debug!(plugin = %self.name, path = req.path(), "answered by plugin");
+        let status = answer.status.unwrap_or(200);
+        let mut res = HttpResponse::build(
+            StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        );
+        for (name, value) in answer.headers.iter() {
+            if let Some(value) = value {
+                res.insert_header((name.as_str(), value.as_str()));
+            }
+        }
+        Some(res.body(answer.body))
+    }
+
+    /// `on_response` hook: applies the plugin's status and header changes to `head`.
+    pub fn on_response(&self, req: &HttpRequest, head: &mut ResponseHead) {
+        let info = ResponseInfo {
+            method: req.method().as_str(),
+            path: req.path(),
+            status: head.status.as_u16(),
+            headers: header_map(&head.headers),
+        };
+        let answer = match self.answer(ON_RESPONSE, &info) {
+            Ok(Some(answer)) => answer,
+            Ok(None) => return,
+            Err(e) => return warn!("plugin {} failed on {}: {}", self.name, req.path(), e),
+        };
+        if let Some(status) = answer.status.and_then(|s| StatusCode::from_u16(s).ok()) {
+            head.status = status;
+        }
+        for (name, value) in answer.headers {
+            let Ok(name) = HeaderName::try_from(name.as_str()) else {
+                continue;
+            };
+            match value.map(|v| HeaderValue::try_from(v.as_str())) {
+                Some(Ok(value)) => head.headers.insert(name, value),
+                Some(Err(_)) => continue,
+                None => head.headers.remove(name),
+            };
+        }
+    }
+}
+
+fn header_map(headers: &actix_web::http::header::HeaderMap) -> BTreeMap<String, String> {
+    let mut map = BTreeMap::new();
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        map.entry(name.as_str().to_string())
+            .and_modify(|v: &mut String| {
+                v.push_str(", ");
+                v.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    map
+}
+
+/// Compiles `path`, refusing imports the sandbox does not provide and missing exports.
+fn compile(engine: &Engine, path: &Path) -> Result<Module, String> {
+    let what = |e: &dyn std::fmt::Display| format!("plugin {}: {}", path.display(), e);
+    let wasm = std::fs::read(path).map_err(|e| what(&e))?;
+    let module = Module::new(engine, &wasm[..]).map_err(|e| what(&e))?;
+    for import in module.imports() {
+        if import.module() != WASI || !WASI_IMPORTS.contains(&import.name()) {
+            return Err(what(&format!(
+                "imports {}::{}, which webserve does not provide",
+                import.module(),
+                import.name()
+            )));
+        }
+    }
+    let exports: Vec<&str> = module.exports().map(|e| e.name()).collect();
+    for required in ["memory", "webserve_alloc"] {
+        if !exports.contains(&required) {
+            return Err(what(&format!("missing `{}` export", required)));
+        }
+    }
+    if !exports.contains(&ON_REQUEST) && !exports.contains(&ON_RESPONSE) {
+        return Err(what(&format!(
+            "exports neither `{}` nor `{}`",
+            ON_REQUEST, ON_RESPONSE
+        )));
+    }
+    Ok(module)
+}
+
+fn memory(caller: &Caller<'_, Sandbox>) -> Option<Memory> {
+    caller.get_export("memory").and_then(Extern::into_memory)
+}
+
+fn write_u32(caller: &mut Caller<'_, Sandbox>, ptr: i32, value: u32) -> i32 {
+    match memory(caller).map(|m| m.write(&mut *caller, ptr as u32 as usize, &value.to_le_bytes())) {
+        Some(Ok(())) => ERRNO_SUCCESS,
+        _ => ERRNO_FAULT,
+    }
+}
+
+/// The `wasi_snapshot_preview1` subset plugins may import.
+fn wasi_linker(engine: &Engine) -> Result<Linker<Sandbox>, String> {
+    let mut linker = Linker::new(engine);
+    let empty = |mut caller: Caller<'_, Sandbox>, count: i32, size: i32| match write_u32(
+        &mut caller,
+        count,
+        0,
+    ) {
+        ERRNO_SUCCESS => write_u32(&mut caller, size, 0),
+        errno => errno,
+    };
+    linker
+        .func_wrap(WASI, "args_sizes_get", empty)
+        .and_then(|l| l.func_wrap(WASI, "environ_sizes_get", empty))
+        .and_then(|l| l.func_wrap(WASI, "args_get", |_: i32, _: i32| ERRNO_SUCCESS))
+        .and_then(|l| l.func_wrap(WASI, "environ_get", |_: i32, _: i32| ERRNO_SUCCESS))
+        .and_then(|l| {
+            l.func_wrap(WASI, "proc_exit", |code: i32| -> Result<(), wasmi::Error> {
+                Err(wasmi::Error::i32_exit(code))
+            })
+        })
+        .and_then(|l| {
+            l.func_wrap(
+                WASI,
+                "fd_write",
+                |mut caller: Caller<'_, Sandbox>, fd: i32, iovs: i32, count: i32, written: i32| {
+                    if fd != 1 && fd != 2 {
+                        return ERRNO_BADF;
+                    }
+                    let Some(memory) = memory(&caller) else {
+                        return ERRNO_FAULT;
+                    };
+                    let data = memory.data(&caller);
+                    let word = |at: usize| {
+                        data.get(at..at + 4)
+                            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                    };
+                    let mut text = Vec::new();
+                    for i in 0..count.max(0) as usize {
+                        let at = iovs as u32 as usize + i * 8;
+                        let (Some(ptr), Some(len)) = (word(at), word(at + 4)) else {
+                            return ERRNO_FAULT;
+                        };
+                        let Some(bytes) = data.get(ptr..ptr.saturating_add(len)) else {
+                            return ERRNO_FAULT;
+                        };
+                        text.extend_from_slice(bytes);
+                    }
+                    let plugin = &caller.data().plugin;
+                    for line in String::from_utf8_lossy(&text).lines() {
+                        debug!(plugin = %plugin, fd, "{}", line);
+                    }
+                    write_u32(&mut caller, written, text.len() as u32)
+                },
+            )
+        })
+        .and_then(|l| {
+            l.func_wrap(
+                WASI,
+                "random_get",
+                |mut caller: Caller<'_, Sandbox>, buf: i32, len: i32| {
+                    let mut bytes = Vec::with_capacity(len.max(0) as usize);
+                    while bytes.len() < len.max(0) as usize {
+                        let mut hasher = RandomState::new().build_hasher();
+                        hasher.write_usize(bytes.len());
+                        bytes.extend_from_slice(&hasher.finish().to_le_bytes());
+                    }
+                    bytes.truncate(len.max(0) as usize);
+                    match memory(&caller).map(|m| m.write(&mut caller, buf as u32 as usize, &bytes))
+                    {
+                        Some(Ok(())) => ERRNO_SUCCESS,
+                        _ => ERRNO_FAULT,
+                    }
+                },
+            )
+        })
+        .and_then(|l| {
+            l.func_wrap(
+                WASI,
+                "clock_time_get",
+                |mut caller: Caller<'_, Sandbox>, _clock: i32, _precision: i64, out: i32| {
+                    let nanos = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_nanos() as u64)
+                        .unwrap_or(0);
+                    match memory(&caller)
+                        .map(|m| m.write(&mut caller, out as u32 as usize, &nanos.to_le_bytes()))
+                    {
+                        Some(Ok(())) => ERRNO_SUCCESS,
+                        _ => ERRNO_FAULT,
+                    }
+                },
+            )
+        })
+        .map_err(|e| e.to_string())?;
+    Ok(linker)
+}
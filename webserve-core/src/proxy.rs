@@ -0,0 +1,454 @@
+//! `--proxy PREFIX=URL`: forwards requests under a path prefix to another server, such as the
+//! API a frontend talks to during development.
+//!
+//! `--proxy /api=http://localhost:3000` sends `/api/users?page=2` to
+//! `http://localhost:3000/users?page=2`: the prefix is replaced by the target's path, so
+//! `/api=http://localhost:3000/api` keeps it. Prefixes match whole segments and the longest
+//! one wins. The method, headers (hop-by-hop ones aside) and body are passed on with
+//! `X-Forwarded-For`, `X-Forwarded-Host` and `X-Forwarded-Proto` added, and the answer is
+//! streamed back as it arrives. A path with a `..` segment (encoded or not) after the prefix
+//! gets `400 Bad Request`, so it cannot climb out of the target's path. Upstream redirects are returned to the client, not followed.
+//! An unreachable target gets `502 Bad Gateway`, one without an answer after
+//! [`PROXY_TIMEOUT`] `504 Gateway Timeout`.
+//!
+//! Targets come from the rules, never from the request, but a typo or a name that later
+//! resolves elsewhere could still aim the server at internal services:
+//!
+//! - `--proxy-allow HOST[:PORT]` limits targets to a list; rules naming anything else fail at
+//!   startup;
+//! - `--proxy-deny-private` refuses (`403`) targets resolving to loopback, private,
+//!   link-local or unique-local addresses unless `--proxy-allow` lists them. Names are
+//!   resolved on every request and the request goes to the address that was checked.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+#[cfg(feature = "proxy")]
+use actix_web::http::header::{self, HeaderMap, HeaderName};
+use actix_web::http::Uri;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use std::cmp::Reverse;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::ip_filter::Cidr;
+use crate::path::percent_decode_path;
+use crate::{AppState, ServeOptions};
+
+/// Time an upstream has to answer with a response head.
+pub const PROXY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Blocks `--proxy-deny-private` refuses: loopback, RFC 1918, shared (CGNAT), link-local,
+/// "this network", unique-local and the unspecified addresses.
+const PRIVATE_RANGES: [&str; 11] = [
+    "127.0.0.0/8",
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "100.64.0.0/10",
+    "169.254.0.0/16",
+    "0.0.0.0/8",
+    "::1/128",
+    "::/128",
+    "fc00::/7",
+    "fe80::/10",
+];
+
+/// True when `ip` is in one of the [`PRIVATE_RANGES`].
+pub fn is_private(ip: IpAddr) -> bool {
+    static RANGES: OnceLock<Vec<Cidr>> = OnceLock::new();
+    RANGES
+        .get_or_init(|| {
+            PRIVATE_RANGES
+                .iter()
+                .map(|block| Cidr::parse(block).expect("valid private range"))
+                .collect()
+        })
+        .iter()
+        .any(|cidr| cidr.contains(ip))
+}
+
+/// One `--proxy PREFIX=URL` rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyRule {
+    /// Path prefix without a trailing `/`; empty for `/`.
+    prefix: String,
+    scheme: String,
+    host: String,
+    port: u16,
+    /// Target path without a trailing `/`.
+    path: String,
+}
+
+impl ProxyRule {
+    /// Parses `PREFIX=URL`, e.g. `/api=http://localhost:3000`.
+    pub fn parse(spec: &str) -> Result<ProxyRule, String> {
+        let (prefix, target) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("--proxy {}: expected PREFIX=URL", spec))?;
+        let prefix = prefix.trim();
+        if !prefix.starts_with('/') {
+            return Err(format!("--proxy {}: the prefix must start with /", spec));
+        }
+        let uri: Uri = target
+            .trim()
+            .parse()
+            .map_err(|_| format!("--proxy {}: invalid URL {}", spec, target.trim()))?;
+        let scheme = match uri.scheme_str() {
+            Some(scheme @ ("http" | "https")) => scheme.to_string(),
+            _ => return Err(format!("--proxy {}: the URL must be http or https", spec)),
+        };
+        let host = uri
+            .host()
+            .filter(|h| !h.is_empty())
+            .ok_or_else(|| format!("--proxy {}: the URL has no host", spec))?;
+        if uri.query().is_some() {
+            return Err(format!("--proxy {}: the URL cannot have a query", spec));
+        }
+        Ok(ProxyRule {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            port: uri
+                .port_u16()
+                .unwrap_or(if scheme == "https" { 443 } else { 80 }),
+            scheme,
+            host: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            path: uri.path().trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// The rest of `path` after the prefix, when the prefix matches whole segments.
+    fn strip<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let rest = path.strip_prefix(&self.prefix)?;
+        (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+    }
+
+    /// Target URL for a request to `path` with `query`.
+    fn url(&self, rest: &str, query: &str) -> String {
+        let host = match self.host.contains(':') {
+            true => format!("[{}]", self.host),
+            false => self.host.clone(),
+        };
+        let path = match format!("{}{}", self.path, rest) {
+            path if path.is_empty() => "/".to_string(),
+            path => path,
+        };
+        match query.is_empty() {
+            true => format!("{}://{}:{}{}", self.scheme, host, self.port, path),
+            false => format!("{}://{}:{}{}?{}", self.scheme, host, self.port, path, query),
+        }
+    }
+}
+
+impl fmt::Display for ProxyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let host = match self.host.contains(':') {
+            true => format!("[{}]", self.host),
+            false => self.host.clone(),
+        };
+        write!(
+            f,
+            "{}/ -> {}://{}:{}{}/",
+            self.prefix, self.scheme, host, self.port, self.path
+        )
+    }
+}
+
+/// A `--proxy-allow` entry: a host, optionally with a port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AllowedTarget {
+    host: String,
+    port: Option<u16>,
+}
+
+impl AllowedTarget {
+    fn parse(spec: &str) -> Result<AllowedTarget, String> {
+        let spec = spec.trim();
+        let invalid = || format!("--proxy-allow: invalid HOST[:PORT] {:?}", spec);
+        let (host, port) = match spec.strip_prefix('[') {
+            Some(rest) => {
+                let (host, port) = rest.split_once(']').ok_or_else(invalid)?;
+                (host, port.strip_prefix(':'))
+            }
+            None => match spec.split_once(':') {
+                Some((host, port)) if !port.contains(':') => (host, Some(port)),
+                _ => (spec, None),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        let port = match port {
+            Some(port) => Some(port.parse().map_err(|_| invalid())?),
+            None => None,
+        };
+        Ok(AllowedTarget {
+            host: host.to_ascii_lowercase(),
+            port,
+        })
+    }
+
+    fn admits(&self, rule: &ProxyRule) -> bool {
+        self.host.eq_ignore_ascii_case(&rule.host) && self.port.is_none_or(|p| p == rule.port)
+    }
+}
+
+/// `--proxy` rules with `--proxy-allow` and `--proxy-deny-private`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Proxy {
+    rules: Vec<ProxyRule>,
+    allow: Vec<AllowedTarget>,
+    deny_private: bool,
+}
+
+impl Proxy {
+    /// The configured proxy; `None` without `--proxy` rules.
+    pub fn from_options(options: &ServeOptions) -> Result<Option<Proxy>, String> {
+        let allow = options
+            .proxy_allow
+            .iter()
+            .map(|spec| AllowedTarget::parse(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+        if options.proxies.is_empty() {
+            return Ok(None);
+        }
+        let mut rules = options
+            .proxies
+            .iter()
+            .map(|spec| ProxyRule::parse(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !allow.is_empty() {
+            if let Some(rule) = rules.iter().find(|r| !allow.iter().any(|a| a.admits(r))) {
+                return Err(format!(
+                    "--proxy {}: {}:{} is not in --proxy-allow",
+                    rule.prefix, rule.host, rule.port
+                ));
+            }
+        }
+        // Longest prefix first
+        rules.sort_by_key(|rule| Reverse(rule.prefix.len()));
+        Ok(Some(Proxy {
+            rules,
+            allow,
+            deny_private: options.proxy_deny_private,
+        }))
+    }
+
+    /// The rule answering `path`, with the rest of the path after its prefix.
+    pub fn route<'a>(&self, path: &'a str) -> Option<(&ProxyRule, &'a str)> {
+        self.rules
+            .iter()
+            .find_map(|rule| rule.strip(path).map(|rest| (rule, rest)))
+    }
+
+    pub fn rules(&self) -> &[ProxyRule] {
+        &self.rules
+    }
+
+    /// Whether `rule`'s target may resolve to a private address.
+    fn private_allowed(&self, rule: &ProxyRule) -> bool {
+        !self.deny_private || self.allow.iter().any(|a| a.admits(rule))
+    }
+
+    /// Dashboard text, e.g. `/api/ -> http://localhost:3000/, private targets refused`.
+    pub fn describe(&self) -> String {
+        let rules = self
+            .rules
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        match self.deny_private {
+            true => format!("{}, private targets refused", rules),
+            false => rules,
+        }
+    }
+}
+
+/// Connection-scoped headers, which are not passed on in either direction.
+#[cfg(feature = "proxy")]
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// `headers` minus hop-by-hop ones (also those `Connection` names), `Host` and
+/// `Content-Length`, which the forwarding sets itself.
+#[cfg(feature = "proxy")]
+fn end_to_end(headers: &HeaderMap) -> impl Iterator<Item = (&HeaderName, &header::HeaderValue)> {
+    let named: Vec<String> = headers
+        .get_all(header::CONNECTION)
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .collect();
+    headers.iter().filter(move |(name, _)| {
+        let name = name.as_str();
+        !HOP_BY_HOP.contains(&name)
+            && !named.iter().any(|n| n == name)
+            && name != "host"
+            && name != "content-length"
+    })
+}
+
+#[cfg(feature = "proxy")]
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Middleware sending requests under a `--proxy` prefix upstream.
+pub async fn proxy_requests<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let Some(data) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let settings = data.settings.load();
+    let Some(ref proxy) = settings.proxy else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let Some((rule, rest)) = proxy.route(req.path()) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let climbs = percent_decode_path(rest).is_none_or(|rest| rest.split('/').any(|s| s == ".."));
+    if climbs {
+        let res = HttpResponse::BadRequest().body("400 Bad Request: `..` in a proxied path");
+        return Ok(req.into_response(res).map_into_right_body());
+    }
+    let url = rule.url(rest, req.query_string());
+    let private_allowed = proxy.private_allowed(rule);
+    let rule = rule.clone();
+    forward(req, rule, url, private_allowed).await
+}
+
+#[cfg(feature = "proxy")]
+async fn forward<B: MessageBody + 'static>(
+    mut req: ServiceRequest,
+    rule: ProxyRule,
+    url: String,
+    private_allowed: bool,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    use actix_web::body::SizedStream;
+    use actix_web::HttpMessage;
+    use awc::error::SendRequestError;
+    use tracing::{debug, warn};
+
+    thread_local! {
+        static CLIENT: awc::Client = awc::Client::builder()
+            .disable_redirects()
+            .no_default_headers()
+            .timeout(PROXY_TIMEOUT)
+            .finish();
+    }
+
+    let answer =
+        |req: ServiceRequest, res: HttpResponse| Ok(req.into_response(res).map_into_right_body());
+    let mut upstream = CLIENT
+        .with(|client| client.clone())
+        .request(req.method().clone(), url.as_str());
+    if !private_allowed {
+        let resolved = match tokio::net::lookup_host((rule.host.as_str(), rule.port)).await {
+            Ok(addrs) => addrs.collect::<Vec<_>>(),
+            Err(e) => {
+                warn!(target = %url, "proxy: cannot resolve {}: {}", rule.host, e);
+                return answer(req, HttpResponse::BadGateway().body("502 Bad Gateway"));
+            }
+        };
+        if resolved.is_empty() || resolved.iter().any(|addr| is_private(addr.ip())) {
+            warn!(
+                peer = ?req.peer_addr(),
+                target = %url,
+                "proxy: refused, {} resolves to a private address",
+                rule.host
+            );
+            return answer(
+                req,
+                HttpResponse::Forbidden().body("proxy target resolves to a private address"),
+            );
+        }
+        // Connect to the address just checked, not a second lookup's answer
+        upstream = upstream.address(resolved[0]);
+    }
+    for (name, value) in end_to_end(req.headers()) {
+        upstream = upstream.append_header((name.clone(), value.clone()));
+    }
+    let info = req.connection_info().clone();
+    let forwarded_for = match (
+        req.headers()
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok()),
+        req.peer_addr(),
+    ) {
+        (Some(chain), Some(peer)) => Some(format!("{}, {}", chain, peer.ip())),
+        (None, Some(peer)) => Some(peer.ip().to_string()),
+        (chain, None) => chain.map(str::to_string),
+    };
+    if let Some(chain) = forwarded_for {
+        upstream = upstream.insert_header(("x-forwarded-for", chain));
+    }
+    upstream = upstream
+        .insert_header(("x-forwarded-host", info.host()))
+        .insert_header(("x-forwarded-proto", info.scheme()));
+
+    let length = content_length(req.headers());
+    let chunked = req.headers().contains_key(header::TRANSFER_ENCODING);
+    let payload = req.take_payload();
+    let sent = match (length, chunked) {
+        (Some(len), false) => upstream.send_body(SizedStream::new(len, payload)).await,
+        (_, true) => upstream.send_stream(payload).await,
+        (None, false) => upstream.send().await,
+    };
+    let response = match sent {
+        Ok(response) => response,
+        Err(SendRequestError::Timeout) => {
+            warn!(target = %url, "proxy: no answer within {:?}", PROXY_TIMEOUT);
+            return answer(
+                req,
+                HttpResponse::GatewayTimeout().body("504 Gateway Timeout"),
+            );
+        }
+        Err(e) => {
+            warn!(target = %url, "proxy: {}", e);
+            return answer(req, HttpResponse::BadGateway().body("502 Bad Gateway"));
+        }
+    };
+    debug!(target = %url, status = response.status().as_u16(), "proxied");
+    let mut res = HttpResponse::build(response.status());
+    for (name, value) in end_to_end(response.headers()) {
+        res.append_header((name.clone(), value.clone()));
+    }
+    let res = match content_length(response.headers()) {
+        Some(len) => res.body(SizedStream::new(len, response)),
+        None => res.streaming(response),
+    };
+    answer(req, res)
+}
+
+/// Without the `proxy` feature `--proxy` fails at startup, so no request gets here.
+#[cfg(not(feature = "proxy"))]
+async fn forward<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    _rule: ProxyRule,
+    _url: String,
+    _private_allowed: bool,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let res = HttpResponse::NotImplemented().body("--proxy needs the proxy feature");
+    Ok(req.into_response(res).map_into_right_body())
+}
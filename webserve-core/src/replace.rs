@@ -0,0 +1,201 @@
+//! `--replace` / `--replace-regex`: find/replace rules for text response bodies.
+//!
+//! A rule is `PATTERN=FIND=>REPLACE`: in responses to paths matching PATTERN (`*` standing
+//! for any run of characters, as in `--log-exclude`), every FIND becomes REPLACE. With
+//! `--replace-regex` FIND is a regular expression and REPLACE may use `$1` / `${name}`.
+//! Rules run in order on successful `GET` responses with a text type (HTML, CSS, JS, JSON,
+//! XML, SVG) up to [`REPLACE_MAX_BYTES`], before `--compress`, so previewing a scraped or
+//! exported site can point its absolute production URLs at the local server:
+//!
+//! ```text
+//! --replace '*.html=https://www.example.com/=>/'
+//! --replace-regex '*=https?://cdn\.example\.com/(\w+)=>/static/$1'
+//! ```
+//!
+//! A rewritten body keeps a weak ETag only and loses `Last-Modified`, like other generated
+//! bodies.
+
+use actix_web::body::{self, EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{error, web, Error};
+use regex::Regex;
+use std::borrow::Cow;
+use tracing::trace;
+
+use crate::access_log::PathPattern;
+use crate::compress::is_compressible;
+use crate::{AppState, ServeOptions};
+
+/// Larger bodies are sent unchanged rather than buffered whole.
+pub const REPLACE_MAX_BYTES: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+enum Find {
+    Text(String),
+    Regex(Regex),
+}
+
+/// One `--replace` / `--replace-regex` rule.
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pattern: PathPattern,
+    find: Find,
+    replace: String,
+}
+
+impl Replacement {
+    /// Parses `PATTERN=FIND=>REPLACE`; with `regex`, FIND is a regular expression.
+    pub fn parse(spec: &str, regex: bool) -> Result<Replacement, String> {
+        let flag = if regex {
+            "--replace-regex"
+        } else {
+            "--replace"
+        };
+        let (pattern, rule) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("{} {}: expected PATTERN=FIND=>REPLACE", flag, spec))?;
+        let (find, replace) = rule
+            .split_once("=>")
+            .ok_or_else(|| format!("{} {}: expected PATTERN=FIND=>REPLACE", flag, spec))?;
+        if pattern.is_empty() || find.is_empty() {
+            return Err(format!(
+                "{} {}: PATTERN and FIND must not be empty",
+                flag, spec
+            ));
+        }
+        let find = match regex {
+            true => Regex::new(find)
+                .map(Find::Regex)
+                .map_err(|e| format!("{} {}: {}", flag, spec, e))?,
+            false => Find::Text(find.to_string()),
+        };
+        Ok(Replacement {
+            pattern: PathPattern::new(pattern),
+            find,
+            replace: replace.to_string(),
+        })
+    }
+
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.pattern.matches(path)
+    }
+
+    /// `text` with every match replaced; borrowed when nothing matched.
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self.find {
+            Find::Text(ref find) if text.contains(find.as_str()) => {
+                Cow::Owned(text.replace(find.as_str(), &self.replace))
+            }
+            Find::Text(_) => Cow::Borrowed(text),
+            Find::Regex(ref re) => re.replace_all(text, self.replace.as_str()),
+        }
+    }
+}
+
+/// Every replacement rule, in the order given (`--replace` before `--replace-regex`).
+#[derive(Debug, Clone, Default)]
+pub struct Replacements(Vec<Replacement>);
+
+impl Replacements {
+    pub fn from_options(options: &ServeOptions) -> Result<Replacements, String> {
+        let plain = options.replace.iter().map(|s| Replacement::parse(s, false));
+        let regex = options
+            .replace_regex
+            .iter()
+            .map(|s| Replacement::parse(s, true));
+        plain
+            .chain(regex)
+            .collect::<Result<_, _>>()
+            .map(Replacements)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.0.iter().any(|rule| rule.applies_to(path))
+    }
+
+    /// `text` after every rule for `path`; `None` when nothing changed.
+    pub fn apply(&self, path: &str, text: &str) -> Option<String> {
+        let mut out = Cow::Borrowed(text);
+        for rule in self.0.iter().filter(|rule| rule.applies_to(path)) {
+            if let Cow::Owned(changed) = rule.apply(&out) {
+                out = Cow::Owned(changed);
+            }
+        }
+        match out {
+            Cow::Owned(changed) => Some(changed),
+            Cow::Borrowed(_) => None,
+        }
+    }
+}
+
+fn rewritable<B: MessageBody>(res: &ServiceResponse<B>) -> bool {
+    let headers = res.headers();
+    let text = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| is_compressible(ct) && !ct.starts_with("application/wasm"));
+    let size_ok = match res.response().body().size() {
+        body::BodySize::Sized(n) => n <= REPLACE_MAX_BYTES,
+        _ => false,
+    };
+    res.status() == StatusCode::OK
+        && text
+        && size_ok
+        && !headers.contains_key(header::CONTENT_ENCODING)
+}
+
+/// Middleware applying `--replace` / `--replace-regex` rules (read per request so config
+/// reloads apply).
+pub async fn replace_body<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let settings = req
+        .app_data::<web::Data<AppState>>()
+        .map(|data| data.settings.load())
+        .filter(|settings| settings.replacements.applies_to(req.path()));
+    let (Some(settings), true) = (settings, req.method() == Method::GET) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let path = req.path().to_string();
+    let res = next.call(req).await?;
+    if !rewritable(&res) {
+        return Ok(res.map_into_left_body());
+    }
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(|e| error::ErrorInternalServerError(e.into()))?;
+    let changed = std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|text| settings.replacements.apply(&path, text));
+    let Some(changed) = changed else {
+        let res = res.set_body(bytes).map_into_boxed_body();
+        return Ok(ServiceResponse::new(req, res).map_into_right_body());
+    };
+    trace!(%path, "response body rewritten by --replace");
+    let headers = res.headers_mut();
+    headers.remove(header::LAST_MODIFIED);
+    let weak = headers
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|tag| !tag.starts_with("W/"))
+        .and_then(|tag| HeaderValue::from_str(&format!("W/{}", tag)).ok());
+    if let Some(weak) = weak {
+        headers.insert(header::ETAG, weak);
+    }
+    let res = res.set_body(changed).map_into_boxed_body();
+    Ok(ServiceResponse::new(req, res).map_into_right_body())
+}
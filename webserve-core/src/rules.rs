@@ -0,0 +1,243 @@
+//! Redirect and rewrite rules evaluated before file resolution.
+//!
+//! Rules come from repeated `--redirect` / `--rewrite` flags or a `--rules` file in
+//! Netlify `_redirects` style (`from to [status]`, one per line, `#` comments at the start of
+//! a line or after whitespace, so `/docs#intro` keeps its fragment).
+//!
+//! A `from` pattern starting with `^` is a regular expression and `to` may use `$1` /
+//! `${name}`. Otherwise it is a path pattern where `*` is a splat and `:name` matches
+//! one segment; `to` refers to them as `:splat` / `:name`.
+
+use regex::Regex;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// What happens when a rule matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleAction {
+    /// Respond with this 3xx status and a `Location` header.
+    Redirect(u16),
+    /// Serve the target path instead, keeping the browser URL.
+    Rewrite,
+}
+
+/// One compiled redirect or rewrite rule.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pattern: Regex,
+    to: String,
+    pub action: RuleAction,
+}
+
+/// Result of evaluating rules against a request path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleMatch {
+    Redirect { location: String, status: u16 },
+    Rewrite(String),
+}
+
+/// Ordered rule list; the first matching rule wins.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rule {
+    /// Compiles `from` → `to` with the given action.
+    pub fn new(from: &str, to: &str, action: RuleAction) -> Result<Rule, String> {
+        if let RuleAction::Redirect(status) = action {
+            if !(300..400).contains(&status) {
+                return Err(format!("invalid redirect status {}", status));
+            }
+        }
+        let (source, to) = if from.starts_with('^') {
+            (from.to_string(), to.to_string())
+        } else {
+            (path_pattern_to_regex(from), path_target_to_replacement(to))
+        };
+        let pattern =
+            Regex::new(&source).map_err(|e| format!("invalid rule pattern {}: {}", from, e))?;
+        Ok(Rule {
+            pattern,
+            to,
+            action,
+        })
+    }
+
+    /// Parses a `--redirect` value: `/old=/new` or `/old=/new:302` (default 301).
+    ///
+    /// Only a 3xx suffix outside the target's authority is a status, so
+    /// `/api=http://localhost:3000` keeps its port.
+    pub fn parse_redirect(spec: &str) -> Result<Rule, String> {
+        let (from, to) = split_spec(spec, "--redirect")?;
+        let (to, status) = match to.rsplit_once(':') {
+            Some((target, code)) if is_redirect_status(code) && !ends_in_authority(target) => {
+                let status = code
+                    .parse()
+                    .map_err(|_| format!("invalid redirect status {}", code))?;
+                (target, status)
+            }
+            _ => (to, 301),
+        };
+        Rule::new(from, to, RuleAction::Redirect(status))
+    }
+
+    /// Parses a `--rewrite` value: `^/v1/(.*)=/api/$1` or `/old/*=/new/:splat`.
+    pub fn parse_rewrite(spec: &str) -> Result<Rule, String> {
+        let (from, to) = split_spec(spec, "--rewrite")?;
+        Rule::new(from, to, RuleAction::Rewrite)
+    }
+
+    /// Returns the substituted target if `path` matches.
+    pub fn apply(&self, path: &str) -> Option<String> {
+        let caps = self.pattern.captures(path)?;
+        let mut out = String::new();
+        caps.expand(&self.to, &mut out);
+        Some(out)
+    }
+}
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>) -> Rules {
+        Rules { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Parses a `_redirects`-style file body. Status `200` means rewrite.
+    pub fn parse_file(contents: &str) -> Result<Rules, String> {
+        let mut rules = Vec::new();
+        for (i, raw) in contents.lines().enumerate() {
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (from, to, status) = match parts.as_slice() {
+                [from, to] => (*from, *to, 301),
+                [from, to, status] => {
+                    let code = status.trim_end_matches('!');
+                    let status = code
+                        .parse::<u16>()
+                        .map_err(|_| format!("line {}: invalid status {}", i + 1, status))?;
+                    (*from, *to, status)
+                }
+                _ => return Err(format!("line {}: expected `from to [status]`", i + 1)),
+            };
+            let action = if status == 200 {
+                RuleAction::Rewrite
+            } else {
+                RuleAction::Redirect(status)
+            };
+            rules.push(Rule::new(from, to, action).map_err(|e| format!("line {}: {}", i + 1, e))?);
+        }
+        Ok(Rules { rules })
+    }
+
+    /// Appends `other` after the current rules.
+    pub fn extend(&mut self, other: Rules) {
+        self.rules.extend(other.rules);
+    }
+
+    /// Finds the first matching rule for a normalized URL path.
+    pub fn evaluate(&self, path: &str) -> Option<RuleMatch> {
+        self.rules.iter().find_map(|rule| {
+            let target = rule.apply(path)?;
+            Some(match rule.action {
+                RuleAction::Redirect(status) => RuleMatch::Redirect {
+                    location: target,
+                    status,
+                },
+                RuleAction::Rewrite => RuleMatch::Rewrite(target),
+            })
+        })
+    }
+}
+
+/// `300`–`399`, written as three digits.
+fn is_redirect_status(code: &str) -> bool {
+    code.len() == 3 && code.starts_with('3') && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// True when `target` stops inside its authority (`http://host`, `//host`), where a `:`
+/// that follows starts a port.
+fn ends_in_authority(target: &str) -> bool {
+    let authority = match target.split_once("://") {
+        Some((_, rest)) => Some(rest),
+        None => target.strip_prefix("//"),
+    };
+    authority.is_some_and(|rest| !rest.contains('/'))
+}
+
+/// `line` without its comment: a `#` at the start or after whitespace; one inside a path
+/// such as `/docs#intro` is a URL fragment.
+fn strip_comment(line: &str) -> &str {
+    let mut after_space = true;
+    for (i, c) in line.char_indices() {
+        if c == '#' && after_space {
+            return &line[..i];
+        }
+        after_space = c.is_whitespace();
+    }
+    line
+}
+
+fn split_spec<'a>(spec: &'a str, flag: &str) -> Result<(&'a str, &'a str), String> {
+    match spec.split_once('=') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() => Ok((from, to)),
+        _ => Err(format!("{} expects FROM=TO, got {}", flag, spec)),
+    }
+}
+
+/// `/blog/:year/*` → `^/blog/(?P<year>[^/]+)/(?P<splat>.*)$`
+fn path_pattern_to_regex(pattern: &str) -> String {
+    let mut out = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => out.push_str("(?P<splat>.*)"),
+            ':' if chars.peek().is_some_and(|n| n.is_ascii_alphabetic()) => {
+                out.push_str(&format!("(?P<{}>[^/]+)", take_ident(&mut chars)));
+            }
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    out.push('$');
+    out
+}
+
+/// `/news/:year/:splat` → `/news/${year}/${splat}` (and escapes literal `$`).
+fn path_target_to_replacement(target: &str) -> String {
+    let mut out = String::new();
+    let mut chars = target.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' => out.push_str("$$"),
+            ':' if chars.peek().is_some_and(|n| n.is_ascii_alphabetic()) => {
+                out.push_str(&format!("${{{}}}", take_ident(&mut chars)));
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Consumes a `:name` placeholder's identifier (the `:` is already consumed).
+fn take_ident(chars: &mut Peekable<Chars<'_>>) -> String {
+    let mut name = String::new();
+    while let Some(&n) = chars.peek() {
+        if n.is_ascii_alphanumeric() || n == '_' {
+            name.push(n);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    name
+}
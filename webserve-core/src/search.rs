@@ -0,0 +1,173 @@
+//! `/__webserve/search`: find served files by path, and optionally by content.
+//!
+//! `?q=` matches paths case-insensitively; `content=1` also looks inside text files and
+//! returns the matching lines. `dir=` narrows the search to a subdirectory (the listing's
+//! search box passes its own). The walk honours `--max-depth` / `--max-entries` like the
+//! manifest and never visits more than [`MAX_SEARCH_ENTRIES`], so a broad query against a
+//! huge tree stops early and says so with `"truncated": true`.
+
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::path::{join_serve_path, normalize_url_path, query_flag, url_path_under};
+use crate::walk::{walk, WalkLimits};
+use crate::AppState;
+
+/// Results returned when the request has no `limit`.
+pub const DEFAULT_SEARCH_RESULTS: usize = 50;
+/// Upper bound for `limit`.
+pub const MAX_SEARCH_RESULTS: usize = 500;
+/// Files and directories one search visits at most, whatever `--max-entries` says.
+pub const MAX_SEARCH_ENTRIES: usize = 20_000;
+/// Larger files are matched by path only.
+pub const MAX_CONTENT_BYTES: u64 = 1024 * 1024;
+
+/// Matching lines reported per file.
+const MAX_LINE_MATCHES: usize = 5;
+/// Matching lines are cut to this many characters.
+const MAX_LINE_CHARS: usize = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+    /// URL path of the directory to search; the whole root when omitted.
+    pub dir: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// One line of a file that contains the query.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct LineMatch {
+    /// 1-based line number.
+    pub line: usize,
+    pub text: String,
+}
+
+/// One matching file.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SearchHit {
+    /// URL path, e.g. `/docs/setup.md`.
+    pub path: String,
+    /// Lines containing the query; only with `content=1`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<LineMatch>,
+}
+
+/// Matching files, sorted by path.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResults {
+    pub query: String,
+    pub results: Vec<SearchHit>,
+    /// True when the result limit or a walk limit stopped the search early.
+    pub truncated: bool,
+}
+
+/// Searches the files under `scope` (inside `root`) for `query`.
+///
+/// A file matches when its path below `scope` contains `query`, or with `content` when
+/// one of its lines does; both ignore case. Binary files and files over
+/// [`MAX_CONTENT_BYTES`] are matched by path only.
+pub fn search_tree(
+    root: &Path,
+    scope: &Path,
+    query: &str,
+    content: bool,
+    max_results: usize,
+    limits: WalkLimits,
+) -> SearchResults {
+    let needle = query.to_lowercase();
+    let max_entries = limits
+        .max_entries
+        .map_or(MAX_SEARCH_ENTRIES, |max| max.min(MAX_SEARCH_ENTRIES));
+    let tree = walk(
+        scope,
+        WalkLimits {
+            max_entries: Some(max_entries),
+            ..limits
+        },
+    );
+    let mut files: Vec<(String, &Path)> = tree
+        .files
+        .iter()
+        .filter_map(|file| Some((url_path_under(root, file)?, file.as_path())))
+        .collect();
+    files.sort();
+
+    let mut results = Vec::new();
+    let mut truncated = tree.truncated;
+    for (path, file) in files {
+        let relative = url_path_under(scope, file).unwrap_or_else(|| path.clone());
+        let matches = if content {
+            matching_lines(file, &needle)
+        } else {
+            Vec::new()
+        };
+        if !relative.to_lowercase().contains(&needle) && matches.is_empty() {
+            continue;
+        }
+        if results.len() == max_results {
+            truncated = true;
+            break;
+        }
+        results.push(SearchHit { path, matches });
+    }
+    SearchResults {
+        query: query.to_string(),
+        results,
+        truncated,
+    }
+}
+
+/// Lines of `file` containing the lowercase `needle`; empty for binary or large files.
+fn matching_lines(file: &Path, needle: &str) -> Vec<LineMatch> {
+    let small = std::fs::metadata(file).is_ok_and(|m| m.len() <= MAX_CONTENT_BYTES);
+    let Some(bytes) = small.then(|| std::fs::read(file).ok()).flatten() else {
+        return Vec::new();
+    };
+    if bytes.iter().take(8192).any(|&b| b == 0) {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.to_lowercase().contains(needle))
+        .take(MAX_LINE_MATCHES)
+        .map(|(i, line)| LineMatch {
+            line: i + 1,
+            text: line.trim().chars().take(MAX_LINE_CHARS).collect(),
+        })
+        .collect()
+}
+
+/// `GET /__webserve/search?q=<text>[&content=1][&dir=<path>][&limit=<n>]`: matching files as JSON.
+pub async fn search(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<SearchQuery>,
+) -> impl Responder {
+    let SearchQuery { q, dir, limit } = query.into_inner();
+    let q = q.trim().to_string();
+    if q.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "missing ?q=" }));
+    }
+    let root = data.static_dir.to_path_buf();
+    let scope = normalize_url_path(dir.as_deref().unwrap_or("/"))
+        .and_then(|dir| join_serve_path(&root, &dir));
+    let Some(scope) = scope.filter(|scope| scope.is_dir()) else {
+        return HttpResponse::NotFound().json(serde_json::json!({ "error": "no such directory" }));
+    };
+    let content = query_flag(req.query_string(), "content");
+    let limit = limit
+        .unwrap_or(DEFAULT_SEARCH_RESULTS)
+        .clamp(1, MAX_SEARCH_RESULTS);
+    let limits = data.settings.load().limits;
+    let found = web::block(move || search_tree(&root, &scope, &q, content, limit, limits)).await;
+    let Ok(found) = found else {
+        return HttpResponse::InternalServerError().body("search failed");
+    };
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(found)
+}
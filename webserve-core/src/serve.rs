@@ -0,0 +1,1320 @@
+//! HTTP serving: app state, directory listing, file handler, reload poll.
+
+use crate::branding::{Branding, BRAND_STYLE};
+use crate::conditional::{
+    bust_asset_urls, etag_matches, file_sha256_etag, html_response, html_response_modified,
+    EtagMode,
+};
+use crate::editor::ERROR_OVERLAY_SCRIPT;
+#[cfg(feature = "highlight")]
+use crate::highlight::{is_highlightable, render_source, wants_view};
+use crate::listing_lang::{Labels, ListingLang, Locale};
+#[cfg(feature = "markdown")]
+use crate::markdown::{is_markdown, render_markdown, wants_raw};
+use crate::mime_types::is_inline;
+use crate::path::{
+    encode_url_path, join_serve_path, normalize_url_path, percent_decode_path,
+    percent_encode_path_segment, url_path_under,
+};
+use crate::rules::RuleMatch;
+use crate::stats::mark_served;
+use crate::suggest::{suggest_header, suggestions, SUGGEST_HEADER};
+use crate::ADMIN_PREFIX;
+use actix_files::NamedFile;
+use actix_web::http::{header, StatusCode};
+use actix_web::{mime, web, HttpRequest, HttpResponse, Responder};
+use bytes::Bytes;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+use tracing::{debug, trace};
+
+use crate::{AppState, DirEntry, Settings};
+
+/// Live reload client appended to HTML in `--watch` mode.
+///
+/// The poll URL is built from `location` in the browser rather than the bind address,
+/// so it keeps working over HTTPS, behind a reverse proxy, or when opened via a LAN IP.
+/// `__WEBSERVE_SEQ__` becomes the reload sequence the page was built at and
+/// `__WEBSERVE_PAGE__` the URL path of the file behind it; `__WEBSERVE_BASE__` is the prefix
+/// of a `--mount` site, so its polls reach its own reload channel.
+///
+/// The poll answers with the changed paths: stylesheets linked from the page are swapped in
+/// place, HTML of other pages is ignored, and anything else (or an incomplete answer after a
+/// long absence or a restart) reloads the page. A failed `--exec` build covers the page with
+/// its output until the next successful build reloads it; `__WEBSERVE_BUILD_ERROR__` is the
+/// failure current when the page was built, or `null`.
+const RELOAD_SCRIPT: &str = r#"<script data-webserve>
+(function(){
+  var page = __WEBSERVE_PAGE__;
+  var poll = new URL("__WEBSERVE_BASE__/reload", location.href);
+  poll.search = "?since=__WEBSERVE_SEQ__";
+  var overlay;
+  function showBuildError(e){
+    if (overlay) overlay.remove();
+    overlay = document.createElement("div");
+    overlay.id = "webserve-build-error";
+    overlay.style.cssText = "position:fixed;inset:0;z-index:2147483647;overflow:auto;padding:2rem;" +
+      "background:rgba(20,20,24,.95);color:#eee;font:13px/1.5 ui-monospace,monospace";
+    var title = document.createElement("div");
+    title.style.cssText = "color:#ff6b6b;font-size:16px;margin-bottom:1rem";
+    title.textContent = "Build failed: " + e.command + " (" + e.status + ")";
+    var output = document.createElement("pre");
+    output.style.cssText = "margin:0;white-space:pre-wrap";
+    output.textContent = e.output || "(no output)";
+    overlay.appendChild(title);
+    overlay.appendChild(output);
+    document.documentElement.appendChild(overlay);
+  }
+  var failed = __WEBSERVE_BUILD_ERROR__;
+  if (failed) showBuildError(failed);
+  function action(c){
+    if (/\.css$/i.test(c.path) && c.kind !== "removed") return "css";
+    if (/\.html?$/i.test(c.path) && c.path !== page) return "none";
+    return "reload";
+  }
+  function swapCss(path){
+    document.querySelectorAll('link[rel~="stylesheet"][href]').forEach(function(link){
+      var href = new URL(link.href);
+      if (href.origin !== location.origin || decodeURIComponent(href.pathname) !== "__WEBSERVE_BASE__" + path) return;
+      href.searchParams.set("__webserve_css", Date.now());
+      link.href = href.href;
+    });
+  }
+  async function tick(){
+    try {
+      var r = await fetch(poll, { cache: "no-store", headers: { Accept: "application/json" } });
+      if (r.status === 200) {
+        var d = await r.json();
+        var last = d.missed[d.missed.length - 1];
+        if (d.complete && last && last.error) {
+          showBuildError(last.error);
+          poll.searchParams.set("since", d.current);
+          setTimeout(tick, 600); return;
+        }
+        var changes = [].concat.apply([], d.missed.map(function(m){ return m.changes; }));
+        if (overlay || !d.complete || !changes.length || changes.some(function(c){ return action(c) === "reload"; })) {
+          location.reload(); return;
+        }
+        changes.forEach(function(c){ if (action(c) === "css") swapCss(c.path); });
+        poll.searchParams.set("since", d.current);
+      }
+    } catch(e) { console.error(e); }
+    setTimeout(tick, 600);
+  }
+  tick();
+})();
+</script>"#;
+
+/// Response header carrying the server's current reload sequence.
+pub const RELOAD_SEQ_HEADER: &str = "X-Webserve-Reload-Seq";
+
+/// `value` as a JavaScript literal that is safe inside `<script>`.
+fn script_json(value: &impl serde::Serialize) -> String {
+    // JSON is valid JS; `<` is escaped so a string cannot close the script
+    serde_json::to_string(value)
+        .unwrap_or_else(|_| "null".to_string())
+        .replace('<', "\\u003c")
+}
+
+/// [`RELOAD_SCRIPT`] for the page at URL path `page` built at reload sequence `seq`, followed
+/// by the error overlay under `--open-in-editor`.
+fn reload_script(data: &AppState, seq: u64, page: &str, settings: &Settings) -> String {
+    let failed = data.reload_log.event(seq).and_then(|e| e.error);
+    let mut script = RELOAD_SCRIPT
+        .replace("__WEBSERVE_SEQ__", &seq.to_string())
+        .replace("__WEBSERVE_PAGE__", &script_json(&page))
+        .replace("__WEBSERVE_BUILD_ERROR__", &script_json(&failed));
+    if settings.open_in_editor {
+        script.push_str(ERROR_OVERLAY_SCRIPT);
+    }
+    script.replace("__WEBSERVE_BASE__", data.mount.base())
+}
+
+/// Generates a full HTML page with a styled directory listing.
+///
+/// `url_prefix` is the current directory's decoded URL path with trailing slash (e.g. `/` or
+/// `/foo bar/`); links are percent-encoded per segment from it.
+pub async fn directory_listing(path: &Path, url_prefix: &str) -> String {
+    ListingView::default().render(path, url_prefix).await
+}
+
+/// Branding of a [`ListingView`] that sets none.
+static NO_BRANDING: Branding = Branding {
+    title: None,
+    logo: None,
+    footer: None,
+};
+
+/// How a listing page is rendered besides its entries.
+///
+/// ```no_run
+/// # async fn page() -> String {
+/// use webserve_core::{ListingView, Locale};
+///
+/// let view = ListingView {
+///     max_entries: Some(1000),
+///     locale: Locale::Fr,
+///     ..Default::default()
+/// };
+/// view.render("public".as_ref(), "/").await
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ListingView<'a> {
+    /// [`Mount::base`](crate::Mount::base) of the site, put in front of every link.
+    pub base: &'a str,
+    /// Entries read at most (`--max-entries`); the page says when more were left out.
+    pub max_entries: Option<usize>,
+    /// Labels, sizes and dates (`--listing-lang`).
+    pub locale: Locale,
+    /// `--brand-*` logo, title and footer.
+    pub branding: &'a Branding,
+    /// `--highlight`: a header button making code file links open the source viewer
+    /// (`?view=1`).
+    pub source_toggle: bool,
+    /// `--upload`: a delete button per entry, and forms for posting files and creating a
+    /// directory after the table.
+    pub upload_form: bool,
+}
+
+impl Default for ListingView<'_> {
+    fn default() -> Self {
+        ListingView {
+            base: "",
+            max_entries: None,
+            locale: Locale::En,
+            branding: &NO_BRANDING,
+            source_toggle: false,
+            upload_form: false,
+        }
+    }
+}
+
+impl ListingView<'_> {
+    /// The listing page of the directory at `path`; `url_prefix` is its URL path within the
+    /// site, as for [`directory_listing`].
+    pub async fn render(&self, path: &Path, url_prefix: &str) -> String {
+        render_listing(path, url_prefix, self).await
+    }
+}
+
+async fn render_listing(path: &Path, url_prefix: &str, view: &ListingView<'_>) -> String {
+    let ListingView {
+        base: site_base,
+        max_entries,
+        locale,
+        branding,
+        source_toggle,
+        upload_form,
+    } = *view;
+    let labels = locale.labels();
+    let mut dirs: Vec<DirEntry> = Vec::new();
+    let mut files: Vec<DirEntry> = Vec::new();
+    let mut truncated = false;
+
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.flatten() {
+            if max_entries.is_some_and(|max| dirs.len() + files.len() >= max) {
+                truncated = true;
+                break;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let meta = entry.metadata().ok();
+            let is_dir = meta.as_ref().map(|m| m.is_dir()).unwrap_or(false);
+            let size = meta
+                .as_ref()
+                .and_then(|m| if m.is_file() { Some(m.len()) } else { None });
+            let modified = meta.and_then(|m| m.modified().ok());
+
+            let e = DirEntry {
+                name,
+                is_dir,
+                size,
+                modified,
+            };
+            if e.is_dir {
+                dirs.push(e);
+            } else {
+                files.push(e);
+            }
+        }
+    }
+
+    dirs.sort_by_key(|e| e.name.to_lowercase());
+    files.sort_by_key(|e| e.name.to_lowercase());
+
+    // What the browser sees: the directory's path under the site's prefix
+    let shown = format!("{}{}", site_base, url_prefix);
+    let breadcrumb = format_breadcrumb(&shown);
+    let path_for_title = shown.trim_end_matches('/');
+    let title = if path_for_title.is_empty() || path_for_title == "/" {
+        labels.index_of.replace("{}", "/")
+    } else {
+        labels.index_of.replace("{}", path_for_title)
+    };
+
+    let mut rows = String::new();
+    let encoded_base = encode_url_path(shown.trim_end_matches('/'));
+    let base = encoded_base.as_str();
+    let deletable = upload_form.then_some(labels);
+
+    for e in dirs {
+        let encoded = percent_encode_path_segment(&e.name);
+        let href = if base == "/" {
+            format!("/{}/", encoded)
+        } else {
+            format!("{}/{}/", base, encoded)
+        };
+        let size_str = String::from("—");
+        let date_str = format_time(e.modified, locale);
+        rows.push_str(&format_entry_row(
+            &e.name, &href, true, false, &size_str, &date_str, deletable,
+        ));
+    }
+    for e in files {
+        let encoded = percent_encode_path_segment(&e.name);
+        let href = if base == "/" {
+            format!("/{}", encoded)
+        } else {
+            format!("{}/{}", base, encoded)
+        };
+        let size_str = locale.format_size(e.size.unwrap_or(0));
+        let date_str = format_time(e.modified, locale);
+        #[cfg(feature = "highlight")]
+        let viewable = source_toggle && is_highlightable(Path::new(&e.name));
+        #[cfg(not(feature = "highlight"))]
+        let viewable = false;
+        rows.push_str(&format_entry_row(
+            &e.name, &href, false, viewable, &size_str, &date_str, deletable,
+        ));
+    }
+    if truncated {
+        rows.push_str(&format!(
+            r#"<tr><td colspan="3" class="size">{}</td></tr>"#,
+            html_escape(
+                &labels
+                    .truncated
+                    .replace("{}", &max_entries.unwrap_or(0).to_string())
+            )
+        ));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}" data-theme="light">
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>{page_title}</title>
+  <link rel="preconnect" href="https://fonts.googleapis.com">
+  <link rel="preconnect" href="https://fonts.gstatic.com" crossorigin>
+  <link href="https://fonts.googleapis.com/css2?family=JetBrains+Mono:wght@400;500&family=Outfit:wght@400;500;600&display=swap" rel="stylesheet">
+  <style>
+    :root, [data-theme="dark"] {{
+      --bg: #0f0f12;
+      --surface: #18181c;
+      --border: #2a2a30;
+      --text: #e4e4e7;
+      --text-muted: #71717a;
+      --accent: #a78bfa;
+      --accent-hover: #c4b5fd;
+      --dir-color: #fbbf24;
+      --hover-bg: rgba(255,255,255,0.03);
+    }}
+    [data-theme="light"] {{
+      --bg: #f4f4f5;
+      --surface: #ffffff;
+      --border: #e4e4e7;
+      --text: #18181b;
+      --text-muted: #71717a;
+      --accent: #7c3aed;
+      --accent-hover: #6d28d9;
+      --dir-color: #b45309;
+      --hover-bg: rgba(0,0,0,0.04);
+    }}
+    * {{ box-sizing: border-box; }}
+    body {{
+      margin: 0;
+      min-height: 100vh;
+      background: var(--bg);
+      color: var(--text);
+      font-family: 'Outfit', system-ui, sans-serif;
+      font-size: 15px;
+      line-height: 1.5;
+    }}
+    .container-fluid {{
+      width: 100%;
+      padding: 2rem 1.5rem;
+    }}
+    .header-row {{
+      display: flex;
+      flex-wrap: wrap;
+      align-items: center;
+      justify-content: space-between;
+      gap: 1rem;
+      margin-bottom: 1.5rem;
+    }}
+    .header-left {{ flex: 1; min-width: 0; }}
+    h1 {{
+      font-size: 1rem;
+      font-weight: 600;
+      color: var(--text-muted);
+      margin: 0 0 0.25rem 0;
+      letter-spacing: 0.02em;
+    }}
+    .breadcrumb {{
+      font-size: 0.875rem;
+      color: var(--text-muted);
+    }}
+    .breadcrumb a {{
+      color: var(--accent);
+      text-decoration: none;
+    }}
+    .breadcrumb a:hover {{ color: var(--accent-hover); }}
+    .breadcrumb span {{ color: var(--text-muted); margin: 0 0.35rem; }}
+    .theme-toggle {{
+      flex-shrink: 0;
+      display: inline-flex;
+      align-items: center;
+      justify-content: center;
+      width: 2.25rem;
+      height: 2.25rem;
+      padding: 0;
+      border: 1px solid var(--border);
+      border-radius: 8px;
+      background: var(--surface);
+      color: var(--text);
+      cursor: pointer;
+    }}
+    .theme-toggle:hover {{ background: var(--hover-bg); }}
+    .theme-toggle .theme-icon {{ width: 1.2rem; height: 1.2rem; }}
+    .header-actions {{ display: flex; gap: 0.5rem; }}
+    .theme-toggle[aria-pressed="true"] {{ color: var(--accent); border-color: var(--accent); }}
+    .theme-toggle .theme-icon-sun {{ display: none; }}
+    .theme-toggle .theme-icon-moon {{ display: block; }}
+    [data-theme="dark"] .theme-toggle .theme-icon-sun {{ display: block; }}
+    [data-theme="dark"] .theme-toggle .theme-icon-moon {{ display: none; }}
+    table {{
+      width: 100%;
+      border-collapse: collapse;
+      background: var(--surface);
+      border: 1px solid var(--border);
+      border-radius: 10px;
+      overflow: hidden;
+    }}
+    th {{
+      text-align: left;
+      font-weight: 500;
+      font-size: 0.75rem;
+      text-transform: uppercase;
+      letter-spacing: 0.06em;
+      color: var(--text-muted);
+      padding: 0.65rem 1rem;
+      border-bottom: 1px solid var(--border);
+    }}
+    th:first-child {{ padding-left: 1.25rem; }}
+    th.size {{ width: 6rem; }}
+    th.date {{ width: 10rem; }}
+    td {{
+      padding: 0.6rem 1rem;
+      border-bottom: 1px solid var(--border);
+      font-family: 'JetBrains Mono', monospace;
+      font-size: 0.9rem;
+    }}
+    tr:last-child td {{ border-bottom: none; }}
+    tr:hover td {{ background: var(--hover-bg); }}
+    td:first-child {{ padding-left: 1.25rem; }}
+    td.size, td.date {{
+      color: var(--text-muted);
+      font-size: 0.8rem;
+    }}
+    a.entry {{
+      color: var(--text);
+      text-decoration: none;
+      display: inline-flex;
+      align-items: center;
+      gap: 0.5rem;
+    }}
+    a.entry:hover {{ color: var(--accent); }}
+    a.entry.dir {{ color: var(--dir-color); }}
+    a.entry.dir:hover {{ opacity: 0.9; }}
+    .icon {{
+      width: 1.1em;
+      height: 1.1em;
+      flex-shrink: 0;
+    }}
+    .search-input {{
+      width: 14rem;
+      height: 2.25rem;
+      padding: 0 0.75rem;
+      border: 1px solid var(--border);
+      border-radius: 8px;
+      background: var(--surface);
+      color: var(--text);
+      font: inherit;
+      font-size: 0.875rem;
+    }}
+    .search-input:focus {{ outline: none; border-color: var(--accent); }}
+    #search-results td.size {{ text-align: right; }}
+    .upload-form {{
+      display: flex;
+      flex-wrap: wrap;
+      align-items: center;
+      gap: 0.75rem;
+      margin-top: 1rem;
+      font-size: 0.875rem;
+      color: var(--text-muted);
+    }}
+    .upload-form button {{
+      height: 2.25rem;
+      padding: 0 1rem;
+      border: 1px solid var(--border);
+      border-radius: 8px;
+      background: var(--surface);
+      color: var(--text);
+      font: inherit;
+      cursor: pointer;
+    }}
+    .upload-form button:hover {{ border-color: var(--accent); color: var(--accent); }}
+    .delete-entry {{
+      margin-left: 0.5rem;
+      padding: 0 0.4rem;
+      border: none;
+      background: none;
+      color: var(--text-muted);
+      font: inherit;
+      cursor: pointer;
+      visibility: hidden;
+    }}
+    tr:hover .delete-entry, .delete-entry:focus {{ visibility: visible; }}
+    .delete-entry:hover {{ color: #ef4444; }}{brand_style}
+  </style>
+</head>
+<body>
+  <div class="container-fluid">
+    <div class="header-row">
+      <div class="header-left">
+        {brand}
+        <h1>{title}</h1>
+        <nav class="breadcrumb" aria-label="Breadcrumb">{breadcrumb_html}</nav>
+      </div>
+      <div class="header-actions">
+        <input type="search" class="search-input" id="search" data-dir="{search_dir}" placeholder="{search}" aria-label="{search}" autocomplete="off">{source_button}
+        <button type="button" class="theme-toggle" id="theme-toggle" aria-label="{theme_toggle}">
+          <svg class="theme-icon theme-icon-sun" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round"><circle cx="12" cy="12" r="4"/><path d="M12 2v2M12 20v2M4.93 4.93l1.41 1.41M17.66 17.66l1.41 1.41M2 12h2M20 12h2M6.34 17.66l-1.41 1.41M19.07 4.93l-1.41 1.41"/></svg>
+          <svg class="theme-icon theme-icon-moon" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round"><path d="M21 12.79A9 9 0 1 1 11.21 3 7 7 0 0 0 21 12.79z"/></svg>
+        </button>
+      </div>
+    </div>
+    <table id="search-results" hidden>
+      <tbody></tbody>
+    </table>
+    <table>
+      <thead>
+        <tr>
+          <th>{name}</th>
+          <th class="size">{size}</th>
+          <th class="date">{modified}</th>
+        </tr>
+      </thead>
+      <tbody>
+        {rows}
+      </tbody>
+    </table>{upload}
+    {footer}
+  </div>
+  <script data-webserve>
+    (function() {{
+      var key = 'webserve-theme';
+      var dark = false;
+      function apply() {{
+        document.documentElement.setAttribute('data-theme', dark ? 'dark' : 'light');
+      }}
+      try {{
+        var s = localStorage.getItem(key);
+        if (s === 'dark') {{ dark = true; }}
+      }} catch (e) {{}}
+      apply();
+      document.getElementById('theme-toggle').addEventListener('click', function() {{
+        dark = !dark;
+        try {{ localStorage.setItem(key, dark ? 'dark' : 'light'); }} catch (e) {{}}
+        apply();
+      }});
+    }})();{search_script}{source_script}{manage_script}
+  </script>
+</body>
+</html>"#,
+        lang = locale.code(),
+        page_title = html_escape(&branding.page_title(&title)),
+        brand_style = BRAND_STYLE,
+        brand = branding.banner_html(),
+        footer = branding.footer_html(),
+        title = html_escape(&title),
+        theme_toggle = labels.theme_toggle,
+        name = labels.name,
+        size = labels.size,
+        modified = labels.modified,
+        breadcrumb_html = breadcrumb,
+        rows = rows,
+        search = html_escape(labels.search),
+        search_dir = html_escape(url_prefix),
+        search_script = SEARCH_SCRIPT
+            .replace(
+                "__ADMIN_PREFIX__",
+                &format!("{}{}", site_base, ADMIN_PREFIX)
+            )
+            .replace("__WEBSERVE_BASE__", site_base),
+        upload = if upload_form {
+            upload_form_html(&shown, labels)
+        } else {
+            String::new()
+        },
+        manage_script = if upload_form { MANAGE_SCRIPT } else { "" },
+        source_button = if source_toggle {
+            source_toggle_button(labels.source_toggle)
+        } else {
+            String::new()
+        },
+        source_script = if source_toggle {
+            SOURCE_TOGGLE_SCRIPT
+        } else {
+            ""
+        },
+    )
+}
+
+/// Queries `/__webserve/search` under the listed directory as the user types and shows the
+/// matching files in place of the listing; clearing the box brings the listing back.
+const SEARCH_SCRIPT: &str = r#"
+    (function() {
+      var input = document.getElementById('search');
+      var found = document.getElementById('search-results');
+      var listing = found.nextElementSibling;
+      var body = found.querySelector('tbody');
+      var timer = null;
+      var latest = 0;
+      function show(results, truncated) {
+        body.textContent = '';
+        results.forEach(function(hit) {
+          var row = body.insertRow();
+          var link = document.createElement('a');
+          link.className = 'entry';
+          link.href = '__WEBSERVE_BASE__' + hit.path.split('/').map(encodeURIComponent).join('/');
+          link.textContent = hit.path;
+          row.insertCell().appendChild(link);
+        });
+        if (!results.length || truncated) {
+          var cell = body.insertRow().insertCell();
+          cell.className = 'size';
+          cell.textContent = results.length ? '…' : '—';
+        }
+      }
+      function run() {
+        var q = input.value.trim();
+        var id = ++latest;
+        if (!q) {
+          found.hidden = true;
+          listing.hidden = false;
+          return;
+        }
+        var url = '__ADMIN_PREFIX__/search?q=' + encodeURIComponent(q) +
+          '&dir=' + encodeURIComponent(input.getAttribute('data-dir'));
+        fetch(url).then(function(res) { return res.json(); }).then(function(data) {
+          if (id !== latest) { return; }
+          show(data.results || [], data.truncated);
+          listing.hidden = true;
+          found.hidden = false;
+        }).catch(function() {});
+      }
+      input.addEventListener('input', function() {
+        clearTimeout(timer);
+        timer = setTimeout(run, 200);
+      });
+    })();"#;
+
+/// Multipart form posting the chosen files to the listed directory, and a form creating a
+/// directory in it through [`MANAGE_SCRIPT`].
+fn upload_form_html(url_prefix: &str, labels: &Labels) -> String {
+    let action = html_escape(&encode_url_path(url_prefix));
+    format!(
+        r#"
+    <form class="upload-form" method="post" enctype="multipart/form-data" action="{0}">
+      <input type="file" name="file" multiple required>
+      <button type="submit">{1}</button>
+    </form>
+    <form class="upload-form mkdir-form" data-dir="{0}">
+      <input type="text" class="search-input" name="name" placeholder="{2}" aria-label="{2}" required>
+      <button type="submit">{2}</button>
+    </form>"#,
+        action,
+        html_escape(labels.upload),
+        html_escape(labels.new_folder)
+    )
+}
+
+/// `DELETE`s an entry after confirmation and `MKCOL`s the new-folder name, then reloads.
+const MANAGE_SCRIPT: &str = r#"
+    (function() {
+      function done(res) {
+        if (res.ok) { location.reload(); } else { alert(res.status + ' ' + res.statusText); }
+      }
+      var buttons = document.querySelectorAll('button.delete-entry');
+      for (var i = 0; i < buttons.length; i++) {
+        buttons[i].addEventListener('click', function() {
+          if (!confirm(this.getAttribute('data-confirm'))) { return; }
+          fetch(this.getAttribute('data-path'), { method: 'DELETE' }).then(done);
+        });
+      }
+      var mkdir = document.querySelector('form.mkdir-form');
+      mkdir.addEventListener('submit', function(e) {
+        e.preventDefault();
+        var name = mkdir.elements.name.value.trim();
+        var dir = mkdir.getAttribute('data-dir').replace(/\/$/, '');
+        fetch(dir + '/' + encodeURIComponent(name), { method: 'MKCOL' }).then(done);
+      });
+    })();"#;
+
+/// Header button of the source toggle, off until the script restores it.
+fn source_toggle_button(label: &str) -> String {
+    format!(
+        r#"
+        <button type="button" class="theme-toggle" id="source-toggle" aria-pressed="false" aria-label="{0}" title="{0}">
+          <svg class="theme-icon" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round"><polyline points="16 18 22 12 16 6"/><polyline points="8 6 2 12 8 18"/></svg>
+        </button>"#,
+        html_escape(label)
+    )
+}
+
+/// Adds or drops `?view=1` on `data-source` links; the choice is kept in `localStorage`.
+const SOURCE_TOGGLE_SCRIPT: &str = r#"
+    (function() {
+      var key = 'webserve-source-view';
+      var on = false;
+      var button = document.getElementById('source-toggle');
+      var links = document.querySelectorAll('a[data-source]');
+      function apply() {
+        button.setAttribute('aria-pressed', on ? 'true' : 'false');
+        for (var i = 0; i < links.length; i++) {
+          var href = links[i].getAttribute('href').split('?')[0];
+          links[i].setAttribute('href', on ? href + '?view=1' : href);
+        }
+      }
+      try { on = localStorage.getItem(key) === 'on'; } catch (e) {}
+      apply();
+      button.addEventListener('click', function() {
+        on = !on;
+        try { localStorage.setItem(key, on ? 'on' : 'off'); } catch (e) {}
+        apply();
+      });
+    })();"#;
+
+pub(crate) fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn format_breadcrumb(url_prefix: &str) -> String {
+    let mut html = String::from(r#"<a href="/">/</a>"#);
+    let mut acc = String::from("/");
+    for seg in url_prefix.split('/').filter(|s| !s.is_empty()) {
+        acc.push_str(&percent_encode_path_segment(seg));
+        acc.push('/');
+        let href = html_escape(&acc);
+        let name = html_escape(seg);
+        html.push_str(r#"<span aria-hidden="true"> / </span><a href=""#);
+        html.push_str(&href);
+        html.push_str(r#"">"#);
+        html.push_str(&name);
+        html.push_str("</a>");
+    }
+    html
+}
+
+fn format_time(t: Option<std::time::SystemTime>, locale: Locale) -> String {
+    let Some(t) = t else { return "—".to_string() };
+    let Ok(d) = t.duration_since(std::time::UNIX_EPOCH) else {
+        return "—".to_string();
+    };
+    let secs = d.as_secs();
+    let days = secs / 86400;
+    let time = secs % 86400;
+    let h = time / 3600;
+    let m = (time % 3600) / 60;
+    let s = time % 60;
+    locale.format_datetime(days_to_ymd(days as u32), (h, m, s))
+}
+
+pub(crate) fn days_to_ymd(days: u32) -> (u32, u32, u32) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (y, m, d)
+}
+
+/// `viewable` marks code files for the source toggle of [`directory_listing_page`]; with
+/// `delete` labels the entry gets a delete button.
+fn format_entry_row(
+    name: &str,
+    href: &str,
+    is_dir: bool,
+    viewable: bool,
+    size: &str,
+    date: &str,
+    delete: Option<&Labels>,
+) -> String {
+    let name_esc = html_escape(name);
+    let href_esc = html_escape(href);
+    let icon = if is_dir {
+        r#"<svg class="icon" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><path d="M22 19a2 2 0 0 1-2 2H4a2 2 0 0 1-2-2V5a2 2 0 0 1 2-2h5l2 3h9a2 2 0 0 1 2 2z"/></svg>"#
+    } else {
+        r#"<svg class="icon" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><path d="M14 2H6a2 2 0 0 0-2 2v16a2 2 0 0 0 2 2h12a2 2 0 0 0 2-2V8z"/><polyline points="14 2 14 8 20 8"/><line x1="16" y1="13" x2="8" y2="13"/><line x1="16" y1="17" x2="8" y2="17"/></svg>"#
+    };
+    let class = if is_dir { "entry dir" } else { "entry" };
+    let source = if viewable { " data-source" } else { "" };
+    let delete = delete
+        .map(|labels| {
+            format!(
+                r#" <button type="button" class="delete-entry" data-path="{}" data-confirm="{}" title="{2}" aria-label="{2}">&times;</button>"#,
+                href_esc,
+                html_escape(&labels.delete_confirm.replace("{}", name)),
+                html_escape(labels.delete),
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        r#"<tr><td><a class="{}" href="{}"{}>{} {}</a>{}</td><td class="size">{}</td><td class="date">{}</td></tr>"#,
+        class,
+        href_esc,
+        source,
+        icon,
+        name_esc,
+        delete,
+        html_escape(size),
+        html_escape(date),
+    )
+}
+
+/// `404`, as a branded HTML page when `--brand-*` is set and the client accepts HTML.
+fn not_found(req: &HttpRequest, settings: &Settings) -> HttpResponse {
+    settings
+        .branding
+        .error_page(req, StatusCode::NOT_FOUND)
+        .unwrap_or_else(|| HttpResponse::NotFound().finish())
+}
+
+/// `404` for `url_path`, with "did you mean" links and [`SUGGEST_HEADER`] under `--suggest`.
+fn missing(
+    req: &HttpRequest,
+    data: &AppState,
+    settings: &Settings,
+    url_path: &str,
+) -> HttpResponse {
+    if !settings.suggest {
+        return not_found(req, settings);
+    }
+    let found = suggestions(&data.static_dir, url_path, settings.limits.max_entries);
+    if found.is_empty() {
+        return not_found(req, settings);
+    }
+    debug!(suggestions = ?found, "suggesting close matches");
+    let links: String = found
+        .iter()
+        .map(|path| {
+            format!(
+                r#"<li><a href="{}">{}</a></li>"#,
+                html_escape(&format!("{}{}", data.mount.base(), encode_url_path(path))),
+                html_escape(path)
+            )
+        })
+        .collect();
+    let details = format!("<p>Did you mean:</p>\n  <ul>{}</ul>", links);
+    let mut res = settings
+        .branding
+        .error_page_with(req, StatusCode::NOT_FOUND, &details)
+        .unwrap_or_else(|| HttpResponse::NotFound().finish());
+    if let Ok(value) = header::HeaderValue::from_str(&suggest_header(&found)) {
+        res.headers_mut()
+            .insert(header::HeaderName::from_static(SUGGEST_HEADER), value);
+    }
+    res
+}
+
+/// Handles file requests.
+///
+/// - Serves static files from the given directory.
+/// - Provides directory listings if no `index.html` exists, in the `--listing-lang` language.
+/// - Falls back to `index.html` if in SPA mode.
+/// - With `--section-index`, a missing path falls back to the nearest ancestor's `index.html`.
+/// - With `--suggest`, a `404` lists close matches among the missing path's siblings.
+/// - Optionally injects a live reload script when `--watch` is enabled.
+/// - When `--watch` is on, caches HTML bodies with that script per file path to avoid repeated read+inject work.
+/// - Generated bodies (listings, injected HTML) carry an `ETag` and answer `If-None-Match` with `304`.
+/// - `--etag` picks file metadata, SHA-256 or no ETag for files and generated bodies alike.
+/// - With `--no-cache`, local asset URLs in injected HTML get a cache-busting query.
+#[tracing::instrument(level = "debug", skip_all, fields(path = %req.path()))]
+pub async fn serve_file(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> actix_web::Result<impl Responder> {
+    let network = data.settings.load().network;
+    let res = resolve_response(req, data).await?;
+    if network.is_off() {
+        return Ok(res);
+    }
+    Ok(network.apply(res).await)
+}
+
+/// The decoded, normalized URL path after redirect/rewrite rules, and whether a rewrite
+/// produced it; `Err` answers the request (a rule redirect, or `404` for a bad path).
+fn request_path(req: &HttpRequest, settings: &Settings) -> Result<(String, bool), HttpResponse> {
+    let Some(canonical_path) = percent_decode_path(req.path()).and_then(|p| normalize_url_path(&p))
+    else {
+        debug!("rejected: undecodable or escapes the root");
+        return Err(not_found(req, settings));
+    };
+
+    // Redirect/rewrite rules run before the path touches the filesystem
+    match settings.rules.evaluate(&canonical_path) {
+        Some(RuleMatch::Redirect { location, status }) => {
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::MOVED_PERMANENTLY);
+            debug!(%location, %status, "rule redirect");
+            Err(redirect(req, status, location))
+        }
+        Some(RuleMatch::Rewrite(target)) => {
+            let target = target.split('?').next().unwrap_or("");
+            let Some(normalized) = normalize_url_path(target) else {
+                return Err(not_found(req, settings));
+            };
+            debug!(to = %normalized, "rule rewrite");
+            Ok((normalized, true))
+        }
+        None => Ok((canonical_path, false)),
+    }
+}
+
+/// File `url_path` maps to: from a `resolve_path` hook, else under the served root.
+fn locate(req: &HttpRequest, data: &AppState, url_path: &str) -> Option<PathBuf> {
+    data.hooks
+        .resolve(req, url_path, &data.static_dir)
+        .or_else(|| join_serve_path(&data.static_dir, url_path))
+}
+
+/// [`serve_file`] before `--throttle` / `--latency` shaping.
+async fn resolve_response(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    let base_dir = &data.static_dir;
+    let settings = data.settings.load();
+    let (canonical_path, rewritten) = match request_path(&req, &settings) {
+        Ok(path) => path,
+        Err(res) => return Ok(res),
+    };
+    let Some(mut file_path) = locate(&req, &data, &canonical_path) else {
+        return Ok(not_found(&req, &settings));
+    };
+
+    // Directory without trailing slash -> redirect to .../ (normalized URLs always lack trailing slash except root)
+    if settings.redirect_dir_slash
+        && !rewritten
+        && file_path.is_dir()
+        && canonical_path != "/"
+        && !req.path().ends_with('/')
+    {
+        let location = format!("{}/", encode_url_path(&canonical_path));
+        debug!(%location, "directory slash redirect");
+        return Ok(redirect(&req, StatusCode::MOVED_PERMANENTLY, location));
+    }
+
+    // If the request points to a directory, check for an index.html file
+    if file_path.is_dir() {
+        let index_file = file_path.join("index.html");
+        let readme = file_path.join("README.md");
+        if index_file.exists() {
+            file_path = index_file;
+        } else if settings.render_markdown && readme.is_file() {
+            file_path = readme;
+        } else {
+            let url_prefix = if canonical_path == "/" {
+                "/".to_string()
+            } else {
+                format!("{}/", canonical_path)
+            };
+            let accept_language = req
+                .headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|v| v.to_str().ok());
+            let locale = settings.listing_lang.resolve(accept_language);
+            debug!(dir = %file_path.display(), %locale, "directory listing");
+            let view = ListingView {
+                base: data.mount.base(),
+                max_entries: settings.limits.max_entries,
+                locale,
+                branding: &settings.branding,
+                source_toggle: settings.highlight,
+                upload_form: settings.upload,
+            };
+            let listing = view.render(&file_path, &url_prefix).await;
+            let mut res = html_response(&req, Bytes::from(listing), settings.etag);
+            if settings.listing_lang == ListingLang::Auto {
+                res.headers_mut().append(
+                    header::VARY,
+                    header::HeaderValue::from_static("Accept-Language"),
+                );
+            }
+            return Ok(res);
+        }
+    }
+
+    // Section fallback: nearest ancestor directory's index.html
+    let mut fallback = false;
+    if !file_path.exists() && settings.section_index {
+        if let Some(index) = nearest_section_index(base_dir, &file_path) {
+            debug!(index = %index.display(), "section index fallback");
+            file_path = index;
+            fallback = true;
+        }
+    }
+
+    // SPA fallback: return index.html if file not found
+    if !file_path.exists() && settings.spa {
+        let spa_index = base_dir.join("index.html");
+        if spa_index.exists() {
+            debug!(missing = %file_path.display(), "SPA fallback to index.html");
+            file_path = spa_index;
+            fallback = true;
+        } else {
+            debug!("not found; SPA mode but no index.html");
+            return Ok(missing(&req, &data, &settings, &canonical_path));
+        }
+    } else if !file_path.exists() {
+        debug!(file = %file_path.display(), "not found");
+        return Ok(missing(&req, &data, &settings, &canonical_path));
+    }
+    debug!(file = %file_path.display(), "resolved");
+
+    #[cfg(feature = "highlight")]
+    if settings.highlight && wants_view(req.query_string()) && is_highlightable(&file_path) {
+        return Ok(source_page(&req, &data, &settings, &file_path).await);
+    }
+    #[cfg(feature = "markdown")]
+    if settings.render_markdown && is_markdown(&file_path) && !wants_raw(req.query_string()) {
+        return Ok(markdown_page(&req, &data, &settings, &file_path).await);
+    }
+
+    // Serve file (race: gone after exists check → 404)
+    let named_file = match NamedFile::open_async(&file_path).await {
+        Ok(f) => f,
+        Err(_) => return Ok(not_found(&req, &settings)),
+    };
+    let named_file = match settings.mime.for_path(&file_path) {
+        Some(mime) => {
+            let mut disposition = named_file.content_disposition().clone();
+            disposition.disposition = if is_inline(mime) {
+                header::DispositionType::Inline
+            } else {
+                header::DispositionType::Attachment
+            };
+            named_file
+                .set_content_type(mime.clone())
+                .set_content_disposition(disposition)
+        }
+        None => named_file,
+    };
+
+    // Inject live reload script into HTML if watch mode is on; use cache to avoid per-request read+inject
+    if data.watch {
+        if let Some(ext) = named_file.path().extension() {
+            if ext == "html" {
+                // The page changes with the file and with the injected script's sequence
+                let modified = named_file
+                    .metadata()
+                    .modified()
+                    .map_or(SystemTime::now(), |m| m.max(data.reload_log.changed_at()));
+                if let Some(ref cache) = data.html_cache {
+                    if let Ok(guard) = cache.read() {
+                        if let Some(cached) = guard.get(&file_path) {
+                            trace!("injected HTML served from cache");
+                            let mut res = html_response_modified(
+                                &req,
+                                cached.clone(),
+                                settings.etag,
+                                modified,
+                            );
+                            settings.charset.apply(&file_path, res.headers_mut());
+                            if !fallback {
+                                mark_served(&mut res, &canonical_path);
+                            }
+                            return Ok(res);
+                        }
+                    }
+                }
+
+                let seq = data.reload_log.current();
+                let read_path = named_file.path().to_path_buf();
+                let mut body = match tokio::fs::read(&read_path).await {
+                    Ok(b) => b,
+                    Err(_) => return Ok(HttpResponse::InternalServerError().finish()),
+                };
+                if settings.no_cache {
+                    body = bust_asset_urls(&String::from_utf8_lossy(&body), &cache_token())
+                        .into_bytes();
+                }
+                body.extend_from_slice(
+                    reload_script(&data, seq, &page_path(&data, &req, &read_path), &settings)
+                        .as_bytes(),
+                );
+                let body_bytes = Bytes::from(body);
+
+                if let Some(ref cache) = data.html_cache {
+                    if let Ok(mut guard) = cache.write() {
+                        guard.insert(file_path.clone(), body_bytes.clone());
+                    }
+                }
+                let mut res = html_response_modified(&req, body_bytes, settings.etag, modified);
+                settings.charset.apply(&file_path, res.headers_mut());
+                if !fallback {
+                    mark_served(&mut res, &canonical_path);
+                }
+                return Ok(res);
+            }
+        }
+    }
+
+    // An explicit `--mime` charset wins over `--charset`
+    let explicit_charset = settings
+        .mime
+        .for_path(&file_path)
+        .is_some_and(|mime| mime.get_param(mime::CHARSET).is_some());
+    let metadata = named_file.metadata().clone();
+    let mut res = match settings.etag {
+        EtagMode::Mtime => named_file.into_response(&req),
+        EtagMode::Off => named_file.use_etag(false).into_response(&req),
+        EtagMode::Sha256 => match file_sha256_etag(&file_path, &data.file_hashes).await {
+            Err(_) => named_file.use_etag(false).into_response(&req),
+            Ok(etag) => {
+                if etag_matches(&req, &etag) {
+                    return Ok(HttpResponse::NotModified()
+                        .insert_header(header::ETag(etag))
+                        .finish());
+                }
+                let mut res = named_file.use_etag(false).into_response(&req);
+                if res.status().is_success() {
+                    res.headers_mut().insert(
+                        header::ETAG,
+                        header::HeaderValue::from_str(&etag.to_string())
+                            .expect("hex ETag is a valid header value"),
+                    );
+                }
+                res
+            }
+        },
+    };
+    if !explicit_charset {
+        settings.charset.apply(&file_path, res.headers_mut());
+    }
+    let mut res = data.inflight.share_body(&req, res, &file_path, &metadata);
+    if !fallback {
+        mark_served(&mut res, &canonical_path);
+    }
+    Ok(res)
+}
+
+/// URL path of the file behind a page, for the reload script; the request path when the
+/// file is not under the root.
+fn page_path(data: &AppState, req: &HttpRequest, file: &Path) -> String {
+    url_path_under(&data.static_dir, file)
+        .or_else(|| percent_decode_path(req.path()))
+        .unwrap_or_else(|| req.path().to_string())
+}
+
+/// `--render-markdown`: `path` as an HTML page, with the reload script under `--watch`.
+#[cfg(feature = "markdown")]
+async fn markdown_page(
+    req: &HttpRequest,
+    data: &AppState,
+    settings: &Settings,
+    path: &Path,
+) -> HttpResponse {
+    let seq = data.reload_log.current();
+    let Ok(source) = tokio::fs::read(path).await else {
+        return not_found(req, settings);
+    };
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    debug!(file = %path.display(), "rendering Markdown");
+    let mut page = render_markdown(&String::from_utf8_lossy(&source), &name, &settings.branding);
+    if data.watch {
+        page.push_str(&reload_script(
+            data,
+            seq,
+            &page_path(data, req, path),
+            settings,
+        ));
+    }
+    html_response(req, Bytes::from(page), settings.etag)
+}
+
+/// `--highlight`: `path` in the source viewer, with the reload script under `--watch`.
+#[cfg(feature = "highlight")]
+async fn source_page(
+    req: &HttpRequest,
+    data: &AppState,
+    settings: &Settings,
+    path: &Path,
+) -> HttpResponse {
+    let seq = data.reload_log.current();
+    let Ok(source) = tokio::fs::read(path).await else {
+        return not_found(req, settings);
+    };
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    debug!(file = %path.display(), "rendering source view");
+    let mut page = render_source(
+        &String::from_utf8_lossy(&source),
+        &name,
+        &format!("{}{}", data.mount.base(), req.path()),
+        &settings.branding,
+    );
+    if data.watch {
+        page.push_str(&reload_script(
+            data,
+            seq,
+            &page_path(data, req, path),
+            settings,
+        ));
+    }
+    html_response(req, Bytes::from(page), settings.etag)
+}
+
+/// `index.html` of the closest existing ancestor directory of `missing`, stopping at `base`.
+fn nearest_section_index(base: &Path, missing: &Path) -> Option<std::path::PathBuf> {
+    missing
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(base))
+        .map(|dir| dir.join("index.html"))
+        .find(|index| index.is_file())
+}
+
+/// Changes whenever injected HTML is rebuilt, i.e. after every watched file change.
+fn cache_token() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("{:x}", nanos)
+}
+
+/// Builds a redirect to `location`, carrying over the request query unless the target has its own.
+fn redirect(req: &HttpRequest, status: StatusCode, location: String) -> HttpResponse {
+    let location = match req.uri().query() {
+        Some(q) if !location.contains('?') => format!("{}?{}", location, q),
+        _ => location,
+    };
+    HttpResponse::build(status)
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReloadQuery {
+    /// Reload sequence the page was built at.
+    pub since: Option<u64>,
+}
+
+/// Short poll: 200 + body `reload` if a file changed; otherwise 204 immediately.
+///
+/// With `?since=<seq>` the answer comes from the reload log, so a client that missed
+/// polls (sleep, network drop) still reloads once; without it, from the shared pending flag.
+/// A client sending `Accept: application/json` gets the [`Catchup`](crate::Catchup) with
+/// the changed paths instead of `reload`.
+pub async fn reload_poll(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<ReloadQuery>,
+) -> impl Responder {
+    data.stats.record_reload_poll(&req);
+    if let Some(since) = query.since {
+        let catchup = data.reload_log.since(since);
+        let seq = (RELOAD_SEQ_HEADER, catchup.current.to_string());
+        if !catchup.stale(since) {
+            return HttpResponse::NoContent().insert_header(seq).finish();
+        }
+        debug!(
+            since,
+            current = catchup.current,
+            missed = catchup.missed.len(),
+            complete = catchup.complete,
+            "reload catch-up delivered to poller"
+        );
+        let wants_json = req
+            .headers()
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/json"));
+        if wants_json {
+            return HttpResponse::Ok().insert_header(seq).json(catchup);
+        }
+        return HttpResponse::Ok()
+            .insert_header(seq)
+            .content_type("text/plain")
+            .body("reload");
+    }
+    if data.reload_pending.swap(false, Ordering::SeqCst) {
+        debug!("reload delivered to poller");
+        HttpResponse::Ok().content_type("text/plain").body("reload")
+    } else {
+        HttpResponse::NoContent().finish()
+    }
+}
+
+/// Methods served for files without `--upload`, advertised in `Allow`.
+pub const READ_METHODS: &str = "GET, HEAD, OPTIONS";
+
+/// Methods served for files under `--upload`.
+pub const UPLOAD_METHODS: &str = "GET, HEAD, OPTIONS, PUT, POST, DELETE, MKCOL";
+
+/// `OPTIONS`: `204` with the methods the server answers in `Allow`, for preflight checks and
+/// scripts probing what a path supports. `--webdav` answers with its own method list and
+/// `DAV` header.
+pub async fn options(
+    #[cfg_attr(not(feature = "webdav"), allow(unused_variables))] req: HttpRequest,
+    data: web::Data<AppState>,
+) -> HttpResponse {
+    let settings = data.settings.load();
+    #[cfg(feature = "webdav")]
+    if settings.webdav {
+        return crate::webdav::webdav(req, data.clone()).await;
+    }
+    let allow = if settings.upload {
+        UPLOAD_METHODS
+    } else {
+        READ_METHODS
+    };
+    HttpResponse::NoContent()
+        .insert_header((header::ALLOW, allow))
+        .finish()
+}
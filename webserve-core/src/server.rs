@@ -0,0 +1,908 @@
+//! Embedding API: the server the `webserve` binary runs, for other Rust tools to start or
+//! mount instead of shelling out to the binary.
+//!
+//! ```no_run
+//! # async fn demo() -> Result<(), String> {
+//! webserve_core::Webserve::builder()
+//!     .dir("./dist")
+//!     .spa(true)
+//!     .watch(true)
+//!     .bind(([127, 0, 0, 1], 3000).into())
+//!     .run()
+//!     .await
+//! # }
+//! ```
+//!
+//! Every CLI option is reachable through [`WebserveBuilder::configure`]. [`Webserve::run`]
+//! binds plain HTTP on the configured hosts and port; TLS, port fallback, Unix sockets,
+//! systemd activation, mDNS and opening a browser stay with the binary.
+//! [`Webserve::start`] does the same in the background and returns a [`ServerHandle`] with
+//! the bound address, readiness, the event stream and `stop()`.
+//! [`Webserve::into_actix_service`] mounts the whole server (admin routes, live reload,
+//! middleware) in an existing actix `App`: register the app's own routes first, as webserve
+//! answers every path it is asked about.
+//!
+//! Each [`Webserve`] owns its watcher, reload channel and history, so several of them mounted
+//! with [`Webserve::into_host_service`] act as virtual hosts: editing one site only reloads
+//! the browsers viewing it. `--mount` and `--vhost` sites (see [`crate::mount`]) are built
+//! that way and come with [`Webserve::into_actix_service`].
+
+use actix_web::dev::ResponseHead;
+use actix_web::http::Method;
+use actix_web::middleware::{from_fn, Condition};
+use actix_web::{guard, web, App, HttpRequest, HttpResponse, HttpServer, Scope};
+use notify::Watcher;
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::access_log::{access_log, AccessLogger, LogFilter, LogFormat};
+use crate::admin::{
+    admin_access, broken_links, dashboard, scenario_status, set_scenario, stats_json,
+};
+use crate::cache::{compress_cache_dir, enforce_cache_size};
+use crate::charset::Charsets;
+use crate::compress::{compress, AdaptiveCompression};
+use crate::compress_cache::CompressCache;
+use crate::conditional::{no_store, EtagMode, FileHashCache};
+use crate::csp::csp_nonce;
+use crate::editor::{configured_editor, open_in_editor};
+use crate::events::{event_stream, EventBus, ServerEvent};
+use crate::exec::BuildCommand;
+use crate::fault::inject_fault;
+use crate::fixtures::serve_fixtures;
+use crate::graphql::graphql_mock;
+use crate::harden::harden;
+use crate::history::{changes, ChangeHistory, ReloadEvent, ReloadLog};
+use crate::hooks::{run_hooks, Hooks};
+use crate::inflight::InflightReads;
+use crate::ip_filter::ip_filter;
+use crate::limits::request_limits;
+use crate::manifest::{manifest, ManifestCache};
+use crate::mock::mock_api;
+use crate::mount::{path_service, site_options, sites, Mount};
+use crate::path::{bind_host, host_port, validate_static_root};
+use crate::proxy::proxy_requests;
+use crate::replace::replace_body;
+use crate::search::search;
+use crate::serve::{options, reload_poll, serve_file};
+use crate::settings::{extra_headers, LiveSettings, Settings};
+use crate::slowloris::slowloris_guard;
+use crate::stats::{collect_stats, Stats};
+use crate::storage::open_store;
+use crate::strict::strict_http;
+use crate::tuning::ServerTuning;
+use crate::upload::{delete_path, make_dir, upload_form, upload_put};
+use crate::walk::WalkLimits;
+use crate::watch::{coalesce, watch_trees, WATCH_DEBOUNCE};
+#[cfg(feature = "webdav")]
+use crate::webdav::webdav;
+use crate::{AppState, ServeOptions, StaticDirError, ADMIN_PREFIX};
+
+/// Human-readable reason binding `addr` failed.
+pub fn listen_error(addr: &str, e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::AddrInUse => format!("{} already in use", addr),
+        io::ErrorKind::PermissionDenied => format!("permission denied binding to {}", addr),
+        io::ErrorKind::AddrNotAvailable => format!("address not available: {}", addr),
+        io::ErrorKind::InvalidInput => format!("invalid listen address {}", addr),
+        io::ErrorKind::AlreadyExists => format!("{} exists and is not a socket", addr),
+        io::ErrorKind::Unsupported => format!("{}: Unix sockets are not supported here", addr),
+        _ => format!("cannot listen on {}: {}", addr, e),
+    }
+}
+
+/// Every webserve route behind its middleware stack, serving `state`.
+///
+/// The state sits on an outer scope so the middleware, which reads it from the request,
+/// finds it when the scope is mounted in an `App` without it.
+pub fn routes(state: web::Data<AppState>, strict_http_audit: bool) -> Scope {
+    let admin = |path: &str| format!("{}{}", ADMIN_PREFIX, path);
+    let inner = web::scope("")
+        .wrap(from_fn(mock_api))
+        .wrap(from_fn(graphql_mock))
+        .wrap(from_fn(serve_fixtures))
+        .wrap(from_fn(inject_fault))
+        .wrap(from_fn(no_store))
+        .wrap(from_fn(extra_headers))
+        .wrap(from_fn(csp_nonce))
+        .wrap(from_fn(replace_body))
+        .wrap(from_fn(compress))
+        .wrap(from_fn(proxy_requests))
+        .wrap(from_fn(run_hooks))
+        .wrap(Condition::new(strict_http_audit, from_fn(strict_http)))
+        .wrap(from_fn(request_limits))
+        .wrap(from_fn(admin_access))
+        .wrap(from_fn(ip_filter))
+        .wrap(from_fn(harden))
+        .wrap(from_fn(access_log))
+        .wrap(from_fn(collect_stats))
+        .wrap(from_fn(slowloris_guard))
+        .route("/reload", web::get().to(reload_poll))
+        .route(ADMIN_PREFIX, web::get().to(dashboard))
+        .route(&admin("/"), web::get().to(dashboard))
+        .route(&admin("/changes"), web::get().to(changes))
+        .route(&admin("/events"), web::get().to(event_stream))
+        .route(&admin("/open-in-editor"), web::get().to(open_in_editor))
+        .route(&admin("/manifest"), web::get().to(manifest))
+        .route(&admin("/search"), web::get().to(search))
+        .route(&admin("/broken-links"), web::get().to(broken_links))
+        .route(&admin("/stats"), web::get().to(stats_json))
+        .route(&admin("/scenario"), web::get().to(scenario_status))
+        .route(&admin("/scenario"), web::put().to(set_scenario))
+        .route(&admin("/scenario"), web::post().to(set_scenario))
+        .route("/{_:.*}", web::get().to(serve_file))
+        .route("/{_:.*}", web::head().to(serve_file))
+        .route("/{_:.*}", web::method(Method::OPTIONS).to(options))
+        .route("/{_:.*}", web::put().to(upload_put))
+        .route("/{_:.*}", web::post().to(upload_form))
+        .route("/{_:.*}", web::delete().to(delete_path))
+        .route(
+            "/{_:.*}",
+            web::method(Method::from_bytes(b"MKCOL").expect("valid method")).to(make_dir),
+        );
+    #[cfg(feature = "webdav")]
+    let inner = inner.route("/{_:.*}", web::route().to(webdav));
+    web::scope("").app_data(state).service(inner)
+}
+
+/// Shared-read state, backed by io_uring with `--io-uring`.
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+fn file_reads(options: &ServeOptions) -> Result<InflightReads, String> {
+    if !options.io_uring {
+        return Ok(InflightReads::default());
+    }
+    match crate::uring::UringReader::start() {
+        Ok(reader) => {
+            info!("io_uring file reads: enabled (experimental)");
+            Ok(InflightReads::with_uring(reader))
+        }
+        Err(e) => {
+            warn!("io_uring unavailable, using the default file path: {}", e);
+            Ok(InflightReads::default())
+        }
+    }
+}
+
+/// Shared-read state; `--io-uring` needs a Linux build with the `io-uring` feature.
+#[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+fn file_reads(options: &ServeOptions) -> Result<InflightReads, String> {
+    if options.io_uring {
+        return Err("--io-uring needs a Linux build with `--features io-uring`".to_string());
+    }
+    Ok(InflightReads::default())
+}
+
+/// `--plugin` modules as hooks, hot-reloaded under `--watch`.
+#[cfg(feature = "wasm-plugins")]
+fn plugin_hooks(options: &ServeOptions) -> Result<Hooks, String> {
+    let mut hooks = Hooks::default();
+    for path in &options.plugins {
+        let plugin = Arc::new(crate::plugin::Plugin::load(path, options.watch)?);
+        info!("Plugin: {}", path.display());
+        if plugin.handles_requests() {
+            let plugin = plugin.clone();
+            hooks.on_request(move |req| plugin.on_request(req));
+        }
+        if plugin.handles_responses() {
+            hooks.on_response(move |req, head| plugin.on_response(req, head));
+        }
+    }
+    Ok(hooks)
+}
+
+/// No hooks; `--plugin` needs a build with the `wasm-plugins` feature.
+#[cfg(not(feature = "wasm-plugins"))]
+fn plugin_hooks(options: &ServeOptions) -> Result<Hooks, String> {
+    if !options.plugins.is_empty() {
+        return Err("--plugin needs a build with `--features wasm-plugins`".to_string());
+    }
+    Ok(Hooks::default())
+}
+
+/// Root `options` serve: the `--file`, else `--dir`, else the working directory.
+pub fn static_root(options: &ServeOptions) -> Result<PathBuf, String> {
+    if let Some(ref file) = options.file {
+        // Absolute, so the file's directory can be watched
+        return std::fs::canonicalize(file).map_err(|_| format!("{} not found", file.display()));
+    }
+    match options.directory {
+        Some(ref p) => Ok(p.clone()),
+        None => {
+            std::env::current_dir().map_err(|e| format!("working directory unavailable: {}", e))
+        }
+    }
+}
+
+/// `--file`: the root must be a regular file, and nothing may write next to it.
+fn check_single_file(file: &Path, options: &ServeOptions) -> Result<(), String> {
+    if !file.is_file() {
+        return Err(format!("{} is not a file", file.display()));
+    }
+    if options.upload || options.webdav {
+        return Err("--file serves a single file; --upload and --webdav need --dir".to_string());
+    }
+    Ok(())
+}
+
+/// Application state for `options`: settings, caches, store, access log and event bus,
+/// each logged as it is set up. The root must exist.
+pub fn app_state(options: &ServeOptions) -> Result<AppState, String> {
+    let static_dir = Arc::new(static_root(options)?);
+    if options.file.is_some() {
+        check_single_file(&static_dir, options)?;
+    } else {
+        validate_static_root(&static_dir).map_err(|e| match e {
+            StaticDirError::NotFound => format!("{} not found", static_dir.display()),
+            StaticDirError::NotADirectory => {
+                format!("{} is not a directory", static_dir.display())
+            }
+        })?;
+    }
+    let initial_settings = Settings::from_options(options)?;
+
+    info!("Starting webserve");
+    if options.file.is_some() {
+        info!("File: {} (served at /)", static_dir.display());
+    } else {
+        info!("Directory: {}", static_dir.display());
+    }
+    info!("Host: {}", options.hosts.join(", "));
+    info!("Port: {}", options.port);
+    if options.spa {
+        info!("SPA mode: enabled");
+    }
+    if options.section_index {
+        info!("Section index fallback: enabled");
+    }
+    if options.suggest {
+        info!("404 suggestions: enabled");
+    }
+    if options.watch {
+        info!("Watch: enabled");
+    }
+    if !options.watch_paths.is_empty() && !options.watch {
+        warn!("--watch-path has no effect without --watch");
+    }
+    if let Some(ref command) = options.exec {
+        if options.watch {
+            info!("Exec on change: {}", command);
+        } else {
+            warn!("--exec has no effect without --watch");
+        }
+    }
+    if let Some(path) = options.open_path() {
+        info!("Open browser: {}", path);
+    }
+    if options.no_redirect_dir_slash {
+        info!("Directory slash redirect: disabled");
+    }
+    if options.strict_http {
+        info!("Strict HTTP audit: enabled");
+    }
+    if let Some(ref path) = options.config {
+        info!("Config: {}", path.display());
+    }
+    if !initial_settings.headers.is_empty() {
+        info!("Extra headers: {}", initial_settings.headers.len());
+    }
+    if let Some(ref f) = initial_settings.fixtures {
+        info!("Fixtures: {} route(s)", f.len());
+    }
+    if initial_settings.etag != EtagMode::Mtime {
+        info!("ETag: {}", initial_settings.etag);
+    }
+    if initial_settings.no_cache {
+        info!("No-cache mode: no-store responses, validators off");
+    }
+    if initial_settings.fault_injection {
+        info!("Fault injection: honoring X-Webserve-Fault request headers");
+    }
+    if let Some(ref dir) = initial_settings.graphql_mocks {
+        info!("GraphQL mocks: {} at /graphql", dir.display());
+    }
+    if let Some(ref api) = initial_settings.mock {
+        let routes: Vec<String> = api.routes().iter().map(|r| api.url(r)).collect();
+        info!(
+            "Mock API: {} ({}){}",
+            api.path().display(),
+            routes.join(", "),
+            if api.persist() { ", persisted" } else { "" }
+        );
+    }
+    if let Some(ref proxy) = initial_settings.proxy {
+        info!("Proxy: {}", proxy.describe());
+    }
+    if initial_settings.request_limits.max_body_bytes.is_some()
+        || initial_settings.request_limits.read_timeout.is_some()
+    {
+        info!(
+            "Request limits: {}",
+            initial_settings.request_limits.describe()
+        );
+    }
+    if !options.allow_methods.is_empty() {
+        info!("Extra methods: {}", options.allow_methods.join(", "));
+    }
+    if !initial_settings.ip_filter.is_empty() {
+        info!(
+            "IP filter: {} allow/deny rule(s)",
+            initial_settings.ip_filter.len()
+        );
+    }
+    if !initial_settings.rules.is_empty() {
+        info!(
+            "Rules: {} redirect/rewrite rule(s)",
+            initial_settings.rules.len()
+        );
+    }
+    if !initial_settings.replacements.is_empty() {
+        info!(
+            "Body replacements: {} rule(s)",
+            initial_settings.replacements.len()
+        );
+    }
+    if !initial_settings.mime.is_empty() {
+        info!("MIME types: {}", initial_settings.mime.describe());
+    }
+    if initial_settings.charset != Charsets::default() {
+        info!("Charset: {}", initial_settings.charset.describe());
+    }
+    let store = open_store(&options.store)?;
+    if options.store != "memory" {
+        info!("Store: {}", store.describe());
+    }
+    let inflight = file_reads(options)?;
+    let hooks = plugin_hooks(options)?;
+    enforce_cache_size(options)?;
+    let compress_cache = match compress_cache_dir(options) {
+        Some(ref dir) => {
+            if !options.compress {
+                warn!("--compress-cache has no effect without --compress");
+            }
+            let capacity = options.compress_cache_size.saturating_mul(1024 * 1024);
+            let cache = CompressCache::open(dir, capacity)
+                .map_err(|e| format!("--compress-cache {}: {}", dir.display(), e))?;
+            info!(
+                "Compression cache: {} ({} entries, {} of {} MiB used)",
+                dir.display(),
+                cache.len(),
+                cache.size() / (1024 * 1024),
+                options.compress_cache_size
+            );
+            Some(Arc::new(cache))
+        }
+        None => None,
+    };
+    if options.upload {
+        info!(
+            "Uploads: on, files up to {} MiB are written under {}",
+            options.max_upload_size,
+            static_dir.display()
+        );
+    }
+    if options.webdav {
+        info!(
+            "WebDAV: on, {} can be mounted read/write",
+            static_dir.display()
+        );
+    }
+    if options.open_in_editor {
+        info!(
+            "Open in editor: on, {}/open-in-editor launches {} for localhost requests",
+            ADMIN_PREFIX,
+            configured_editor()
+        );
+    }
+    let log_filter = LogFilter::from_options(options)?;
+    let access_logger = match (options.log, options.log_file.as_deref()) {
+        (None, None) => None,
+        (format, None) => format.map(AccessLogger::stdout),
+        (format, Some(path)) => Some(AccessLogger::file(
+            format.unwrap_or(LogFormat::Common),
+            path,
+        )?),
+    }
+    .map(|logger| Arc::new(logger.with_filter(log_filter)));
+    if let Some(ref logger) = access_logger {
+        match options.log_file {
+            Some(ref path) => info!("Access log: {} -> {}", logger.format(), path.display()),
+            None => info!("Access log: {}", logger.format()),
+        }
+    }
+    let events = Arc::new(match options.events_token {
+        Some(ref token) => EventBus::new(token.clone()),
+        None => EventBus::default(),
+    });
+
+    let (tx, _rx) = broadcast::channel::<ReloadEvent>(16);
+    Ok(AppState {
+        static_dir,
+        watch: options.watch,
+        addr: String::new(),
+        tx,
+        reload_pending: Arc::new(AtomicBool::new(false)),
+        html_cache: options.watch.then(|| Arc::new(RwLock::new(HashMap::new()))),
+        settings: Arc::new(LiveSettings::new(initial_settings)),
+        scenario: Arc::new(RwLock::new(None)),
+        access_log: access_logger,
+        file_hashes: FileHashCache::default(),
+        stats: Arc::new(Stats::default()),
+        manifest: ManifestCache::default(),
+        history: Arc::new(ChangeHistory::default()),
+        reload_log: Arc::new(ReloadLog::default()),
+        inflight,
+        compression: Arc::new(AdaptiveCompression::default()),
+        compress_cache,
+        store,
+        events,
+        hooks: Arc::new(hooks),
+        mount: Mount::Root,
+    })
+}
+
+/// State for the `--mount` or `--vhost` site `options` serve at `mount`: settings, caches,
+/// history and reload channel of its own; access log, store, hooks and compression shared
+/// with `root`. The directory must exist.
+pub fn site_state(
+    root: &AppState,
+    options: &ServeOptions,
+    mount: Mount,
+) -> Result<AppState, String> {
+    let static_dir = Arc::new(static_root(options)?);
+    validate_static_root(&static_dir).map_err(|e| match e {
+        StaticDirError::NotFound => format!("{}: {} not found", mount, static_dir.display()),
+        StaticDirError::NotADirectory => {
+            format!("{}: {} is not a directory", mount, static_dir.display())
+        }
+    })?;
+    match mount {
+        Mount::Host(ref host) => info!("Virtual host: {} -> {}", host, static_dir.display()),
+        _ => info!("Mount: {} -> {}", mount, static_dir.display()),
+    }
+    let (tx, _rx) = broadcast::channel::<ReloadEvent>(16);
+    Ok(AppState {
+        static_dir,
+        tx,
+        reload_pending: Arc::new(AtomicBool::new(false)),
+        html_cache: options.watch.then(|| Arc::new(RwLock::new(HashMap::new()))),
+        settings: Arc::new(LiveSettings::new(Settings::from_options(options)?)),
+        scenario: Arc::new(RwLock::new(None)),
+        file_hashes: FileHashCache::default(),
+        stats: Arc::new(Stats::default()),
+        manifest: ManifestCache::default(),
+        history: Arc::new(ChangeHistory::default()),
+        reload_log: Arc::new(ReloadLog::default()),
+        events: Arc::new(EventBus::new(root.events.token().to_string())),
+        mount,
+        ..root.clone()
+    })
+}
+
+/// With `--watch`: watches the root and `--watch-path`s, runs `--exec` and publishes each
+/// batch to `state`. Changes are seen while the returned watcher lives.
+pub fn watch_files(
+    options: &ServeOptions,
+    state: &AppState,
+) -> Result<Box<dyn Watcher + Send>, String> {
+    let watch_state = state.clone();
+    let build = options.exec.clone().map(|c| Arc::new(BuildCommand::new(c)));
+    let single_file = options
+        .file
+        .is_some()
+        .then(|| state.static_dir.to_path_buf());
+    let make_handler = move || {
+        let state = watch_state.clone();
+        let batch_build = build.clone();
+        let mut batches = coalesce(WATCH_DEBOUNCE, move |batch: Vec<notify::Event>| {
+            let _span = tracing::debug_span!("watch_batch", events = batch.len()).entered();
+            let batch = match batch_build {
+                Some(ref build) => match build.build(batch) {
+                    Ok(batch) => batch,
+                    Err(error) => return state.publish_build_error(error),
+                },
+                None => batch,
+            };
+            state.publish_changes(&batch);
+        });
+        let build = build.clone();
+        let single_file = single_file.clone();
+        move |res: notify::Result<notify::Event>| {
+            // `--file`: of the file's directory, only events for the file itself count
+            if let (Some(file), Ok(event)) = (&single_file, &res) {
+                let elsewhere = |p: &PathBuf| p != file && p.parent() == file.parent();
+                if event.paths.iter().all(elsewhere) {
+                    return;
+                }
+            }
+            if !build.as_ref().is_some_and(|b| b.absorb(&res)) {
+                batches(res);
+            }
+        }
+    };
+    let mut roots = vec![state.static_dir.as_path()];
+    roots.extend(options.watch_paths.iter().map(PathBuf::as_path));
+    let watcher = watch_trees(&roots, WalkLimits::from_options(options), make_handler)?;
+    if options.file.is_some() {
+        info!("Watching file: {}", state.static_dir.display());
+    } else {
+        info!("Watching directory: {}", state.static_dir.display());
+    }
+    for path in &options.watch_paths {
+        info!("Watching directory: {} (not served)", path.display());
+    }
+    Ok(watcher)
+}
+
+/// Keeps the file watcher of a [`Webserve`] (and its mounted services) alive.
+struct WatchGuard {
+    _watcher: Mutex<Option<Box<dyn Watcher + Send>>>,
+}
+
+/// A configured server: state built, file watcher running.
+#[derive(Clone)]
+pub struct Webserve {
+    options: ServeOptions,
+    state: AppState,
+    watcher: Arc<WatchGuard>,
+    /// `--mount` and `--vhost` sites, each a server of its own.
+    sites: Vec<Webserve>,
+}
+
+impl Webserve {
+    pub fn builder() -> WebserveBuilder {
+        WebserveBuilder::default()
+    }
+
+    /// Builds the server for fully resolved options (see [`crate::resolve_layers`]).
+    pub fn from_options(options: ServeOptions) -> Result<Webserve, String> {
+        let state = app_state(&options)?;
+        let sites = sites(&options)?
+            .into_iter()
+            .map(|(mount, dir)| {
+                let options = site_options(&options, dir);
+                let state = site_state(&state, &options, mount)?;
+                Webserve::watching(options, state, Vec::new())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Webserve::watching(options, state, sites)
+    }
+
+    /// Starts the watcher of `state` under `--watch`.
+    fn watching(
+        options: ServeOptions,
+        state: AppState,
+        sites: Vec<Webserve>,
+    ) -> Result<Webserve, String> {
+        let watcher = match options.watch {
+            true => Some(watch_files(&options, &state)?),
+            false => None,
+        };
+        Ok(Webserve {
+            options,
+            state,
+            watcher: Arc::new(WatchGuard {
+                _watcher: Mutex::new(watcher),
+            }),
+            sites,
+        })
+    }
+
+    pub fn options(&self) -> &ServeOptions {
+        &self.options
+    }
+
+    /// Shared state, e.g. to call [`AppState::publish_changes`] from a custom watcher.
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// `--mount` and `--vhost` sites, e.g. to swap in reloaded settings for each.
+    pub fn sites(&self) -> &[Webserve] {
+        &self.sites
+    }
+
+    /// Everything webserve serves, to mount with `App::new().service(...)`; clone the
+    /// `Webserve` for each worker. The file watcher runs while any of them is alive.
+    /// `--mount` and `--vhost` sites come before the main directory.
+    pub fn into_actix_service(self) -> Scope {
+        let strict = self.options.strict_http;
+        let root = routes(web::Data::new(self.state), strict).app_data(self.watcher);
+        self.sites
+            .into_iter()
+            .fold(web::scope(""), |scope, site| {
+                match site.state.mount.clone() {
+                    Mount::Path(prefix) => {
+                        scope.service(path_service(&prefix, site.into_actix_service()))
+                    }
+                    Mount::Host(host) => scope.service(site.into_host_service(&host)),
+                    Mount::Root => scope.service(site.into_actix_service()),
+                }
+            })
+            .service(root)
+    }
+
+    /// [`into_actix_service`](Self::into_actix_service) answering only requests whose `Host`
+    /// is `host` (port ignored), to serve several sites from one `App`. Reloads, `/reload`
+    /// polls and the `/__webserve/` pages stay with the site whose files changed.
+    pub fn into_host_service(self, host: &str) -> Scope {
+        self.into_actix_service().guard(guard::Host(host))
+    }
+
+    /// This server and its sites reporting `addr` as the address they listen on.
+    pub fn with_addr(self, addr: String) -> Webserve {
+        let sites = self
+            .sites
+            .into_iter()
+            .map(|site| site.with_addr(addr.clone()))
+            .collect();
+        Webserve {
+            state: AppState { addr, ..self.state },
+            sites,
+            ..self
+        }
+    }
+
+    /// Serves plain HTTP on the configured hosts and port until the server stops.
+    pub async fn run(self) -> Result<(), String> {
+        self.start()?.wait().await
+    }
+
+    /// Serves plain HTTP on an already bound listener (e.g. port 0 in tests).
+    pub async fn run_listener(self, listener: TcpListener) -> Result<(), String> {
+        self.start_listener(listener)?.wait().await
+    }
+
+    /// Binds the configured hosts and port and serves in the background on the current actix
+    /// runtime. With port `0` the OS picks a free one; [`ServerHandle::addr`] tells which.
+    pub fn start(self) -> Result<ServerHandle, String> {
+        let port = self.options.port;
+        let listeners = self
+            .options
+            .hosts
+            .iter()
+            .map(|host| {
+                let listener = TcpListener::bind((bind_host(host), port))
+                    .map_err(|e| listen_error(&host_port(host, port), &e))?;
+                Ok((host.clone(), listener))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        self.serve(listeners)
+    }
+
+    /// [`start`](Self::start) on an already bound listener.
+    pub fn start_listener(self, listener: TcpListener) -> Result<ServerHandle, String> {
+        let host = match listener.local_addr() {
+            Ok(addr) => addr.ip().to_string(),
+            Err(e) => return Err(format!("cannot read the listener address: {}", e)),
+        };
+        self.serve(vec![(host, listener)])
+    }
+
+    fn serve(self, listeners: Vec<(String, TcpListener)>) -> Result<ServerHandle, String> {
+        let mut addrs = Vec::new();
+        let mut names = Vec::new();
+        for (host, listener) in &listeners {
+            let addr = listener
+                .local_addr()
+                .map_err(|e| format!("cannot read the listener address: {}", e))?;
+            names.push(host_port(host, addr.port()));
+            addrs.push(addr);
+        }
+        let tuning = ServerTuning::from_options(&self.options)?;
+        let events = self.state.events.clone();
+        let server = self.with_addr(names.join(", "));
+        // Workers build their app once they start, just before accepting
+        let (ready_tx, ready) = watch::channel(false);
+        let ready_tx = Arc::new(ready_tx);
+        let mut server = HttpServer::new(move || {
+            ready_tx.send_replace(true);
+            App::new().service(server.clone().into_actix_service())
+        });
+        if let Some(n) = tuning.workers {
+            server = server.workers(n);
+        }
+        if let Some(n) = tuning.max_connections {
+            server = server.max_connections(n);
+        }
+        if let Some(d) = tuning.keep_alive {
+            server = server.keep_alive(d);
+        }
+        if let Some(d) = tuning.client_timeout {
+            server = server.client_request_timeout(d);
+        }
+        let server = listeners
+            .into_iter()
+            .zip(&names)
+            .try_fold(server, |s, ((_, listener), name)| {
+                s.listen(listener).map_err(|e| listen_error(name, &e))
+            })?
+            .run();
+        for name in &names {
+            info!("Serving on http://{}", name);
+        }
+        Ok(ServerHandle {
+            addrs,
+            server: server.handle(),
+            task: actix_web::rt::spawn(server),
+            ready,
+            events,
+        })
+    }
+}
+
+/// A [`Webserve`] serving in the background, from [`Webserve::start`]: e.g. the site under
+/// test in a frontend project's integration tests.
+pub struct ServerHandle {
+    addrs: Vec<SocketAddr>,
+    server: actix_web::dev::ServerHandle,
+    task: JoinHandle<io::Result<()>>,
+    ready: watch::Receiver<bool>,
+    events: Arc<EventBus>,
+}
+
+impl ServerHandle {
+    /// First bound address, with the port actually in use.
+    pub fn addr(&self) -> SocketAddr {
+        self.addrs[0]
+    }
+
+    /// Every bound address, one per configured host.
+    pub fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    /// `http://` URL of [`addr`](Self::addr).
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr())
+    }
+
+    /// Waits until a worker is up to answer requests.
+    pub async fn await_ready(&self) -> Result<(), String> {
+        let mut ready = self.ready.clone();
+        ready
+            .wait_for(|up| *up)
+            .await
+            .map(|_| ())
+            .map_err(|_| "server stopped before it was ready".to_string())
+    }
+
+    /// Requests, file changes, reloads and errors from now on, as on `/__webserve/events`.
+    pub fn events(&self) -> broadcast::Receiver<ServerEvent> {
+        self.events.subscribe_events()
+    }
+
+    /// Stops accepting, lets in-flight requests finish and waits for the server to exit.
+    pub async fn stop(self) -> Result<(), String> {
+        self.server.stop(true).await;
+        self.wait().await
+    }
+
+    /// Waits for the server to exit, e.g. on Ctrl-C.
+    pub async fn wait(self) -> Result<(), String> {
+        match self.task.await {
+            Ok(result) => result.map_err(|e| format!("server error: {}", e)),
+            Err(e) => Err(format!("server task failed: {}", e)),
+        }
+    }
+}
+
+/// Options for a [`Webserve`], starting from the CLI defaults (`127.0.0.1:8080`, the working
+/// directory, every feature off).
+#[derive(Debug, Clone, Default)]
+pub struct WebserveBuilder {
+    options: ServeOptions,
+    hooks: Hooks,
+}
+
+impl WebserveBuilder {
+    /// Directory to serve (`--dir`).
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.directory = Some(dir.into());
+        self
+    }
+
+    /// Single file to serve at `/` instead of a directory (`--file`).
+    pub fn file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.options.file = Some(file.into());
+        self
+    }
+
+    /// Fall back to `index.html` for unknown paths (`--spa`).
+    pub fn spa(mut self, on: bool) -> Self {
+        self.options.spa = on;
+        self
+    }
+
+    /// Watch files and live-reload browsers (`--watch`).
+    pub fn watch(mut self, on: bool) -> Self {
+        self.options.watch = on;
+        self
+    }
+
+    /// Also watch `dir`, which is not served (`--watch-path`, repeatable).
+    pub fn watch_path(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.options.watch_paths.push(dir.into());
+        self
+    }
+
+    /// Build command run before each reload (`--exec`).
+    pub fn exec(mut self, command: impl Into<String>) -> Self {
+        self.options.exec = Some(command.into());
+        self
+    }
+
+    /// Address [`Webserve::run`] listens on.
+    pub fn bind(mut self, addr: SocketAddr) -> Self {
+        self.options.hosts = vec![addr.ip().to_string()];
+        self.options.port = addr.port();
+        self
+    }
+
+    /// Adjusts any other option, e.g. `|o| o.compress = true`.
+    pub fn configure(mut self, f: impl FnOnce(&mut ServeOptions)) -> Self {
+        f(&mut self.options);
+        self
+    }
+
+    /// Replaces every option, e.g. with the CLI's resolved ones.
+    pub fn options(mut self, options: ServeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Runs `hook` on each request before fixtures, rules and file lookup; a response it
+    /// returns answers the request.
+    pub fn on_request(
+        mut self,
+        hook: impl Fn(&HttpRequest) -> Option<HttpResponse> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_request(hook);
+        self
+    }
+
+    /// Runs `hook` on each response on its way out, e.g. to set headers.
+    pub fn on_response(
+        mut self,
+        hook: impl Fn(&HttpRequest, &mut ResponseHead) + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.on_response(hook);
+        self
+    }
+
+    /// Lets `hook` pick the file served for a URL path (see [`crate::hooks`]).
+    pub fn resolve_path(
+        mut self,
+        hook: impl Fn(&HttpRequest, &str) -> Option<PathBuf> + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.resolve_path(hook);
+        self
+    }
+
+    pub fn build(self) -> Result<Webserve, String> {
+        let mut server = Webserve::from_options(self.options)?;
+        let mut hooks = self.hooks;
+        hooks.extend(&server.state.hooks);
+        server.state.hooks = Arc::new(hooks);
+        for site in &mut server.sites {
+            site.state.hooks = server.state.hooks.clone();
+        }
+        Ok(server)
+    }
+
+    /// [`build`](Self::build) and [`Webserve::run`].
+    pub async fn run(self) -> Result<(), String> {
+        self.build()?.run().await
+    }
+
+    /// [`build`](Self::build) and [`Webserve::start`].
+    pub fn start(self) -> Result<ServerHandle, String> {
+        self.build()?.start()
+    }
+}
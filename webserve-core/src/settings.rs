@@ -0,0 +1,333 @@
+//! Per-request settings that can be swapped while the server runs.
+//!
+//! Handlers take a snapshot with [`LiveSettings::load`]; `--config` together with
+//! `--watch` stores a fresh [`Settings`] whenever the config file changes, so SPA mode,
+//! headers, rules, body replacements, MIME types, charsets, fixtures, the mock API, proxy
+//! rules and IP filters update without rebinding.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate::branding::Branding;
+use crate::charset::Charsets;
+use crate::compress::DEFAULT_COMPRESS_CPU_LIMIT;
+use crate::conditional::EtagMode;
+use crate::config::parse_header;
+use crate::fixtures::Fixtures;
+use crate::harden::DEFAULT_MAX_HEADER_BYTES;
+use crate::ip_filter::IpFilter;
+use crate::limits::RequestLimits;
+use crate::listing_lang::ListingLang;
+use crate::mime_types::MimeOverrides;
+use crate::mock::MockApi;
+use crate::proxy::Proxy;
+use crate::replace::Replacements;
+use crate::rules::{Rule, Rules};
+use crate::slowloris::SlowlorisGuard;
+use crate::throttle::NetworkShape;
+use crate::upload::DEFAULT_MAX_UPLOAD_MIB;
+use crate::walk::WalkLimits;
+use crate::{AppState, ServeOptions};
+
+/// Options read on every request.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub spa: bool,
+    /// Missing paths fall back to the nearest ancestor `index.html`.
+    pub section_index: bool,
+    /// `--suggest`: close matches on `404` pages.
+    pub suggest: bool,
+    /// 301-redirect GET when URL names a directory but has no trailing `/`.
+    pub redirect_dir_slash: bool,
+    /// Redirect/rewrite rules checked before the URL is mapped onto the filesystem.
+    pub rules: Rules,
+    /// `--replace` / `--replace-regex` rules for text response bodies.
+    pub replacements: Replacements,
+    /// `--mime` Content-Type overrides by extension.
+    pub mime: MimeOverrides,
+    /// `--charset` / `--charset-ext` for text files.
+    pub charset: Charsets,
+    /// Canned responses from `--fixtures`; matched before rules and files.
+    pub fixtures: Option<Arc<Fixtures>>,
+    /// `--graphql-mocks` directory answered at `/graphql`.
+    pub graphql_mocks: Option<PathBuf>,
+    /// `--mock` REST API, answered ahead of the files.
+    pub mock: Option<Arc<MockApi>>,
+    /// `--proxy` rules, forwarded ahead of mocks, fixtures and files.
+    pub proxy: Option<Arc<Proxy>>,
+    /// `--max-depth` / `--max-entries` for tree walks and listings.
+    pub limits: WalkLimits,
+    /// `--etag`: validator used for files and generated bodies.
+    pub etag: EtagMode,
+    /// `--no-cache`: `no-store` everywhere, no validators, cache-busted asset URLs.
+    pub no_cache: bool,
+    /// `--compress`: encode text responses for clients that accept it.
+    pub compress: bool,
+    /// `--compress-cpu-limit`: CPU percent above which compression is skipped.
+    pub compress_cpu_limit: u8,
+    /// Honor `X-Webserve-Fault` request headers.
+    pub fault_injection: bool,
+    /// Extra headers added to every response that does not already set them.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// `--allow` / `--deny` client address rules.
+    pub ip_filter: IpFilter,
+    /// `--listing-lang`: language of directory listings.
+    pub listing_lang: ListingLang,
+    /// `--brand-*`: logo, title and footer of generated pages.
+    pub branding: Branding,
+    /// `--harden`: reject smuggling-prone and oversized request heads.
+    pub harden: bool,
+    /// `--max-header-bytes`: header section limit under `--harden`.
+    pub max_header_bytes: usize,
+    /// `--throttle` / `--latency`: simulated slow network for `serve_file` responses.
+    pub network: NetworkShape,
+    /// `--render-markdown`: serve `.md` files and directory READMEs as HTML.
+    pub render_markdown: bool,
+    /// `--highlight`: `?view=1` shows source files syntax-highlighted.
+    pub highlight: bool,
+    /// `--upload`: `PUT` and form uploads beneath the root.
+    pub upload: bool,
+    /// `--max-upload-size` in bytes.
+    pub max_upload_bytes: u64,
+    /// `--webdav`: WebDAV methods for mounting the root read/write.
+    pub webdav: bool,
+    /// `--open-in-editor`: the editor endpoint and the error overlay linking to it.
+    pub open_in_editor: bool,
+    /// `--admin-remote`: `/__webserve/` answers every client, not only loopback ones.
+    pub admin_remote: bool,
+    /// `--slowloris-guard`: head deadline for keep-alive requests and minimum body rate.
+    pub slowloris: Option<SlowlorisGuard>,
+    /// `--max-request-size`, `--read-timeout` and `--allow-method`.
+    pub request_limits: RequestLimits,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            spa: false,
+            section_index: false,
+            suggest: false,
+            redirect_dir_slash: true,
+            rules: Rules::default(),
+            replacements: Replacements::default(),
+            mime: MimeOverrides::default(),
+            charset: Charsets::default(),
+            fixtures: None,
+            graphql_mocks: None,
+            mock: None,
+            proxy: None,
+            fault_injection: false,
+            no_cache: false,
+            compress: false,
+            compress_cpu_limit: DEFAULT_COMPRESS_CPU_LIMIT,
+            etag: EtagMode::default(),
+            limits: WalkLimits::default(),
+            headers: Vec::new(),
+            ip_filter: IpFilter::default(),
+            listing_lang: ListingLang::default(),
+            branding: Branding::default(),
+            harden: false,
+            max_header_bytes: DEFAULT_MAX_HEADER_BYTES,
+            network: NetworkShape::default(),
+            render_markdown: false,
+            highlight: false,
+            upload: false,
+            max_upload_bytes: DEFAULT_MAX_UPLOAD_MIB * 1024 * 1024,
+            webdav: false,
+            open_in_editor: false,
+            admin_remote: false,
+            slowloris: None,
+            request_limits: RequestLimits::default(),
+        }
+    }
+}
+
+/// Refuses flags whose subsystem was left out of this build.
+fn check_features(options: &ServeOptions) -> Result<(), String> {
+    let flags = [
+        (
+            options.render_markdown,
+            cfg!(feature = "markdown"),
+            "--render-markdown",
+            "markdown",
+        ),
+        (
+            options.highlight,
+            cfg!(feature = "highlight"),
+            "--highlight",
+            "highlight",
+        ),
+        (
+            options.webdav,
+            cfg!(feature = "webdav"),
+            "--webdav",
+            "webdav",
+        ),
+        (
+            !options.proxies.is_empty(),
+            cfg!(feature = "proxy"),
+            "--proxy",
+            "proxy",
+        ),
+    ];
+    match flags.iter().find(|(set, built, _, _)| *set && !built) {
+        Some((_, _, flag, feature)) => Err(format!(
+            "{} needs a build with `--features {}`",
+            flag, feature
+        )),
+        None => Ok(()),
+    }
+}
+
+impl Settings {
+    /// Builds settings from resolved options, reading the rules file and fixtures from disk.
+    pub fn from_options(options: &ServeOptions) -> Result<Settings, String> {
+        check_features(options)?;
+        let fixtures = match options.fixtures {
+            Some(ref dir) => Some(Arc::new(Fixtures::load_dir(dir)?)),
+            None => None,
+        };
+        let mock = match options.mock {
+            Some(ref file) => Some(Arc::new(MockApi::load(
+                file,
+                &options.mock_prefix,
+                options.mock_persist,
+            )?)),
+            None => None,
+        };
+        let headers = options
+            .headers
+            .iter()
+            .map(|spec| parse_header(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !(1..=100).contains(&options.compress_cpu_limit) {
+            return Err(format!(
+                "--compress-cpu-limit expects a percentage from 1 to 100, got {}",
+                options.compress_cpu_limit
+            ));
+        }
+        Ok(Settings {
+            spa: options.spa,
+            section_index: options.section_index,
+            suggest: options.suggest,
+            redirect_dir_slash: !options.no_redirect_dir_slash,
+            rules: load_rules(options)?,
+            replacements: Replacements::from_options(options)?,
+            mime: MimeOverrides::from_options(options)?,
+            charset: Charsets::from_options(options)?,
+            fixtures,
+            graphql_mocks: options.graphql_mocks.clone(),
+            mock,
+            proxy: Proxy::from_options(options)?.map(Arc::new),
+            fault_injection: options.fault_injection,
+            no_cache: options.no_cache,
+            compress: options.compress,
+            compress_cpu_limit: options.compress_cpu_limit,
+            etag: options.etag,
+            limits: WalkLimits::from_options(options),
+            headers,
+            ip_filter: IpFilter::from_options(options)?,
+            listing_lang: options.listing_lang,
+            branding: Branding::from_options(options),
+            harden: options.harden,
+            max_header_bytes: options.max_header_bytes,
+            network: NetworkShape::from_options(options)?,
+            render_markdown: options.render_markdown,
+            highlight: options.highlight,
+            upload: options.upload,
+            max_upload_bytes: options.max_upload_size.saturating_mul(1024 * 1024),
+            webdav: options.webdav,
+            open_in_editor: options.open_in_editor,
+            admin_remote: options.admin_remote,
+            slowloris: SlowlorisGuard::from_options(options)?,
+            request_limits: RequestLimits::from_options(options)?,
+        })
+    }
+}
+
+/// Shared, swappable [`Settings`]; readers get a cheap `Arc` snapshot.
+#[derive(Debug, Default)]
+pub struct LiveSettings {
+    current: RwLock<Arc<Settings>>,
+}
+
+impl LiveSettings {
+    pub fn new(settings: Settings) -> LiveSettings {
+        LiveSettings {
+            current: RwLock::new(Arc::new(settings)),
+        }
+    }
+
+    /// Current settings; stays valid even if a reload happens mid-request.
+    pub fn load(&self) -> Arc<Settings> {
+        match self.current.read() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replaces the settings for subsequent requests.
+    pub fn store(&self, settings: Settings) {
+        let mut guard = match self.current.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *guard = Arc::new(settings);
+    }
+}
+
+impl From<Settings> for LiveSettings {
+    fn from(settings: Settings) -> Self {
+        LiveSettings::new(settings)
+    }
+}
+
+/// Collects `--rules` file entries first, then `--redirect` flags, then `--rewrite` flags.
+pub fn load_rules(options: &ServeOptions) -> Result<Rules, String> {
+    let mut rules = Rules::default();
+    if let Some(ref path) = options.rules_file {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read rules file {}: {}", path.display(), e))?;
+        let parsed =
+            Rules::parse_file(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        rules.extend(parsed);
+    }
+    let flags = options
+        .redirects
+        .iter()
+        .map(|spec| Rule::parse_redirect(spec))
+        .chain(
+            options
+                .rewrites
+                .iter()
+                .map(|spec| Rule::parse_rewrite(spec)),
+        )
+        .collect::<Result<Vec<_>, _>>()?;
+    rules.extend(Rules::new(flags));
+    Ok(rules)
+}
+
+/// Middleware adding the configured extra headers (read per request so reloads apply).
+pub async fn extra_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let settings = req
+        .app_data::<web::Data<AppState>>()
+        .map(|data| data.settings.load());
+    let mut res = next.call(req).await?;
+    if let Some(settings) = settings {
+        let headers = res.headers_mut();
+        for (name, value) in &settings.headers {
+            if !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+    }
+    Ok(res)
+}
@@ -0,0 +1,231 @@
+//! Slow-client protection (`--slowloris-guard`, `--header-timeout`, `--min-rate`).
+//!
+//! Slowloris-style clients open many connections and drip bytes so each one stays busy.
+//! With the guard on:
+//!
+//! - the request line and headers of a connection's first request must arrive within
+//!   `--header-timeout`, or actix-web answers `408` and closes (unless `--client-timeout`
+//!   sets that deadline explicitly);
+//! - later keep-alive requests on an HTTP/1.x connection get at most the keep-alive idle time
+//!   plus `--header-timeout` after the previous response; a request that took longer is
+//!   answered `408` and its connection closed;
+//! - request bodies must average `--min-rate` bytes per second once `--header-timeout` has
+//!   passed, otherwise reading them fails.
+
+use actix_web::body::{BodySize, EitherBody, MessageBody};
+use actix_web::dev::{Extensions, Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::PayloadError;
+use actix_web::http::Version;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpResponse};
+use bytes::Bytes;
+use futures_util::Stream;
+use std::any::Any;
+use std::cell::Cell;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::time::Sleep;
+use tracing::warn;
+
+use crate::{AppState, ServeOptions};
+
+/// Default `--header-timeout`.
+pub const DEFAULT_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default `--min-rate`, in bytes per second.
+pub const DEFAULT_MIN_RATE: u64 = 512;
+/// actix-web's keep-alive idle time when `--keep-alive` is not set.
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(5);
+
+/// Limits enforced by `--slowloris-guard`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlowlorisGuard {
+    /// Time allowed for a request line and its headers.
+    pub header_timeout: Duration,
+    /// Least average request body rate, in bytes per second.
+    pub min_rate: u64,
+    /// Keep-alive idle time of the server; zero when keep-alive is off.
+    pub keep_alive: Duration,
+}
+
+impl SlowlorisGuard {
+    /// `None` without `--slowloris-guard`.
+    pub fn from_options(options: &ServeOptions) -> Result<Option<SlowlorisGuard>, String> {
+        if !options.slowloris_guard {
+            return Ok(None);
+        }
+        if options.header_timeout.is_zero() {
+            return Err("--header-timeout must be greater than zero".to_string());
+        }
+        if options.min_rate == 0 {
+            return Err("--min-rate must be at least 1 byte per second".to_string());
+        }
+        Ok(Some(SlowlorisGuard {
+            header_timeout: options.header_timeout,
+            min_rate: options.min_rate,
+            keep_alive: options.keep_alive.unwrap_or(DEFAULT_KEEP_ALIVE),
+        }))
+    }
+
+    /// Dashboard text, e.g. `10s headers, 512 B/s bodies`.
+    pub fn describe(&self) -> String {
+        format!(
+            "{:?} headers, {} B/s bodies",
+            self.header_timeout, self.min_rate
+        )
+    }
+
+    /// True when a keep-alive request arriving `gap` after the previous response must have
+    /// dripped its head for longer than `--header-timeout`.
+    pub fn head_too_slow(&self, gap: Duration) -> bool {
+        !self.keep_alive.is_zero() && gap > self.keep_alive + self.header_timeout
+    }
+}
+
+/// When the previous response on a connection finished; stored per connection.
+#[derive(Clone, Default)]
+pub struct ConnectionClock(Rc<Cell<Option<Instant>>>);
+
+/// `HttpServer::on_connect` hook giving each connection a [`ConnectionClock`].
+pub fn track_connection(_: &dyn Any, extensions: &mut Extensions) {
+    extensions.insert(ConnectionClock::default());
+}
+
+/// Middleware for `--slowloris-guard`.
+pub async fn slowloris_guard<B: MessageBody + 'static>(
+    mut req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<Stamped<B>>>, Error> {
+    let guard = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|data| data.settings.load().slowloris);
+    let Some(guard) = guard else {
+        let res = next.call(req).await?;
+        return Ok(res.map_body(|_, body| EitherBody::left(Stamped::new(body, None))));
+    };
+    let clock = req.conn_data::<ConnectionClock>().cloned();
+    if let Some(ref clock) = clock {
+        let gap = clock.0.get().map(|done| done.elapsed());
+        if req.version() < Version::HTTP_2 && gap.is_some_and(|gap| guard.head_too_slow(gap)) {
+            warn!(
+                peer = ?req.peer_addr().map(|a| a.ip()),
+                path = req.path(),
+                "slowloris-guard: request head took longer than {:?}",
+                guard.header_timeout
+            );
+            let res = HttpResponse::RequestTimeout()
+                .content_type("text/plain; charset=utf-8")
+                .force_close()
+                .body("408 Request Timeout");
+            return Ok(req.into_response(res).map_into_right_body());
+        }
+    }
+    let payload: Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>> = Box::pin(MinRate::new(
+        req.take_payload(),
+        guard.min_rate,
+        guard.header_timeout,
+    ));
+    req.set_payload(Payload::from(payload));
+    let res = next.call(req).await?;
+    Ok(res.map_body(|_, body| EitherBody::left(Stamped::new(body, clock))))
+}
+
+/// Response body that stamps its connection's [`ConnectionClock`] once written and dropped.
+pub struct Stamped<B> {
+    inner: B,
+    clock: Option<ConnectionClock>,
+}
+
+impl<B> Stamped<B> {
+    fn new(inner: B, clock: Option<ConnectionClock>) -> Stamped<B> {
+        Stamped { inner, clock }
+    }
+}
+
+impl<B> Drop for Stamped<B> {
+    fn drop(&mut self) {
+        if let Some(ref clock) = self.clock {
+            clock.0.set(Some(Instant::now()));
+        }
+    }
+}
+
+impl<B: MessageBody + Unpin> MessageBody for Stamped<B> {
+    type Error = B::Error;
+
+    fn size(&self) -> BodySize {
+        self.inner.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        Pin::new(&mut self.get_mut().inner).poll_next(cx)
+    }
+}
+
+/// Request body stream failing once its average rate drops below `min_rate` bytes per
+/// second, measured after a `grace` period.
+pub struct MinRate<S> {
+    inner: S,
+    min_rate: u64,
+    grace: Duration,
+    started: Instant,
+    received: u64,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl<S> MinRate<S> {
+    pub fn new(inner: S, min_rate: u64, grace: Duration) -> MinRate<S> {
+        let started = Instant::now();
+        MinRate {
+            inner,
+            min_rate: min_rate.max(1),
+            grace,
+            started,
+            received: 0,
+            deadline: Box::pin(tokio::time::sleep_until((started + grace).into())),
+        }
+    }
+
+    /// Moment the bytes received so far stop covering the minimum rate.
+    fn owed_until(&self) -> Instant {
+        self.started
+            + self.grace
+            + Duration::from_secs_f64(self.received as f64 / self.min_rate as f64)
+    }
+}
+
+impl<S> Stream for MinRate<S>
+where
+    S: Stream<Item = Result<Bytes, PayloadError>> + Unpin,
+{
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                this.received += chunk.len() as u64;
+                let until = this.owed_until();
+                this.deadline.as_mut().reset(until.into());
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Pending if this.deadline.as_mut().poll(cx).is_ready() => {
+                warn!(
+                    received = this.received,
+                    "slowloris-guard: request body below {} B/s", this.min_rate
+                );
+                Poll::Ready(Some(Err(PayloadError::Io(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "request body slower than --min-rate",
+                )))))
+            }
+            other => other,
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! In-memory counters behind the `/__webserve/` dashboard.
+//!
+//! Keeps the last [`RECENT_LIMIT`] requests and file-change events, a request total, and
+//! the live-reload clients seen polling `/reload` within [`CLIENT_TIMEOUT`], and what TLS
+//! connections negotiated. `GET` requests answered `404` are tallied per path with the pages
+//! that linked to them (the broken-links report). Per-path
+//! download counts live in the `--store` instead, so a persistent backend keeps them: only
+//! files [`serve_file`](crate::serve_file) sent from disk count (not SPA or section
+//! fallbacks, proxied or mocked answers), and at most [`DOWNLOAD_KEY_LIMIT`] paths.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::admin::ADMIN_PREFIX;
+use crate::events::ServerEvent;
+use crate::storage::{counter, Store, DOWNLOADS_NS};
+use crate::AppState;
+
+/// Entries kept per recent-events list.
+pub const RECENT_LIMIT: usize = 50;
+
+/// Paths listed under `downloads` in `/__webserve/stats`.
+pub const TOP_DOWNLOADS: usize = 20;
+
+/// Distinct paths given a download count; files first served after that are not counted.
+pub const DOWNLOAD_KEY_LIMIT: usize = 10_000;
+
+/// Distinct missing paths tracked; the least recently requested one makes room for a new one.
+pub const BROKEN_LINK_LIMIT: usize = 500;
+
+/// Referers kept per missing path; later ones are only counted in `hits`.
+pub const REFERER_LIMIT: usize = 10;
+
+/// A reload client counts as connected while it polled within this window.
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a TLS connection negotiated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// e.g. `TLS 1.3`.
+    pub version: String,
+    /// Cipher suite name, e.g. `TLS13_AES_256_GCM_SHA384`.
+    pub cipher: String,
+    /// ALPN protocol, e.g. `h2`; `None` when the client offered none we support.
+    pub alpn: Option<String>,
+}
+
+/// One served request.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestEvent {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub latency_ms: f64,
+}
+
+/// One filesystem change reported by the watcher.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub kind: String,
+    /// URL paths of the changed files; the file name alone for files outside the directory.
+    pub paths: Vec<PathBuf>,
+}
+
+/// A path that answered `404`, and who linked to it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BrokenLink {
+    pub path: String,
+    pub hits: u64,
+    /// Seconds since the Unix epoch of the first and the latest request.
+    pub first_seen: u64,
+    pub last_seen: u64,
+    /// Referring pages (`-` for none) and how often each sent a request.
+    pub referers: BTreeMap<String, u64>,
+}
+
+/// TLS connections by negotiated version, cipher suite and ALPN protocol (`none` if absent).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TlsCounts {
+    pub connections: u64,
+    pub versions: BTreeMap<String, u64>,
+    pub ciphers: BTreeMap<String, u64>,
+    pub alpn: BTreeMap<String, u64>,
+}
+
+/// Shared stats collector; cheap to update from handlers and the watcher thread.
+#[derive(Debug)]
+pub struct Stats {
+    started: SystemTime,
+    total_requests: AtomicU64,
+    requests: Mutex<VecDeque<RequestEvent>>,
+    changes: Mutex<VecDeque<ChangeEvent>>,
+    reload_clients: Mutex<HashMap<String, Instant>>,
+    tls: Mutex<TlsCounts>,
+    not_found: Mutex<HashMap<String, BrokenLink>>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            started: SystemTime::now(),
+            total_requests: AtomicU64::new(0),
+            requests: Mutex::default(),
+            changes: Mutex::default(),
+            reload_clients: Mutex::default(),
+            tls: Mutex::default(),
+            not_found: Mutex::default(),
+        }
+    }
+}
+
+fn push_capped<T>(list: &Mutex<VecDeque<T>>, item: T) {
+    if let Ok(mut list) = list.lock() {
+        if list.len() == RECENT_LIMIT {
+            list.pop_front();
+        }
+        list.push_back(item);
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Stats {
+    pub fn record_request(&self, method: &str, path: &str, status: u16, latency: Duration) {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+        push_capped(
+            &self.requests,
+            RequestEvent {
+                time: unix_now(),
+                method: method.to_string(),
+                path: path.to_string(),
+                status,
+                latency_ms: (latency.as_secs_f64() * 1_000_000.0).round() / 1000.0,
+            },
+        );
+    }
+
+    pub fn record_change(&self, kind: &str, paths: Vec<PathBuf>) {
+        push_capped(
+            &self.changes,
+            ChangeEvent {
+                time: unix_now(),
+                kind: kind.to_string(),
+                paths,
+            },
+        );
+    }
+
+    /// Notes a `/reload` poll from the client identified by peer address and user agent.
+    pub fn record_reload_poll(&self, req: &HttpRequest) {
+        let ip = req
+            .peer_addr()
+            .map(|a| a.ip().to_string())
+            .unwrap_or_else(|| "-".into());
+        let agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("-");
+        if let Ok(mut clients) = self.reload_clients.lock() {
+            let now = Instant::now();
+            clients.retain(|_, seen| now.duration_since(*seen) < CLIENT_TIMEOUT);
+            clients.insert(format!("{} {}", ip, agent), now);
+        }
+    }
+
+    /// Clients that polled `/reload` within [`CLIENT_TIMEOUT`], sorted.
+    pub fn reload_clients(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut clients: Vec<String> = self
+            .reload_clients
+            .lock()
+            .map(|c| {
+                c.iter()
+                    .filter(|(_, seen)| now.duration_since(**seen) < CLIENT_TIMEOUT)
+                    .map(|(k, _)| k.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        clients.sort();
+        clients
+    }
+
+    /// Counts one finished TLS handshake.
+    pub fn record_tls(&self, info: &TlsInfo) {
+        if let Ok(mut tls) = self.tls.lock() {
+            tls.connections += 1;
+            *tls.versions.entry(info.version.clone()).or_default() += 1;
+            *tls.ciphers.entry(info.cipher.clone()).or_default() += 1;
+            let alpn = info.alpn.as_deref().unwrap_or("none");
+            *tls.alpn.entry(alpn.to_string()).or_default() += 1;
+        }
+    }
+
+    /// Counts a `404` for `path`, linked from `referer` when the request named one.
+    pub fn record_not_found(&self, path: &str, referer: Option<&str>) {
+        let Ok(mut links) = self.not_found.lock() else {
+            return;
+        };
+        let now = unix_now();
+        if !links.contains_key(path) && links.len() >= BROKEN_LINK_LIMIT {
+            let stalest = links
+                .values()
+                .min_by_key(|l| l.last_seen)
+                .map(|l| l.path.clone());
+            if let Some(stalest) = stalest {
+                links.remove(&stalest);
+            }
+        }
+        let link = links.entry(path.to_string()).or_insert_with(|| BrokenLink {
+            path: path.to_string(),
+            hits: 0,
+            first_seen: now,
+            last_seen: now,
+            referers: BTreeMap::new(),
+        });
+        link.hits += 1;
+        link.last_seen = now;
+        let referer = referer.unwrap_or("-");
+        if link.referers.len() < REFERER_LIMIT || link.referers.contains_key(referer) {
+            *link.referers.entry(referer.to_string()).or_default() += 1;
+        }
+    }
+
+    /// Missing paths, most requested first.
+    pub fn broken_links(&self) -> Vec<BrokenLink> {
+        let mut links: Vec<BrokenLink> = self
+            .not_found
+            .lock()
+            .map(|l| l.values().cloned().collect())
+            .unwrap_or_default();
+        links.sort_by(|a, b| b.hits.cmp(&a.hits).then_with(|| a.path.cmp(&b.path)));
+        links
+    }
+
+    pub fn tls_counts(&self) -> TlsCounts {
+        self.tls.lock().map(|t| t.clone()).unwrap_or_default()
+    }
+
+    pub fn total_requests(&self) -> u64 {
+        self.total_requests.load(Ordering::Relaxed)
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed().unwrap_or_default()
+    }
+
+    /// Recent requests, newest first.
+    pub fn recent_requests(&self) -> Vec<RequestEvent> {
+        self.requests
+            .lock()
+            .map(|l| l.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Recent file changes, newest first.
+    pub fn recent_changes(&self) -> Vec<ChangeEvent> {
+        self.changes
+            .lock()
+            .map(|l| l.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The `limit` most downloaded paths in `store`, most first.
+pub fn top_downloads(store: &dyn Store, limit: usize) -> std::io::Result<Vec<(String, u64)>> {
+    let mut counts: Vec<(String, u64)> = store
+        .scan(DOWNLOADS_NS)?
+        .into_iter()
+        .map(|(path, value)| (path, counter(Some(&value))))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(limit);
+    Ok(counts)
+}
+
+/// Response extension naming the URL path of a file sent from disk.
+#[derive(Debug, Clone)]
+struct ServedFile(String);
+
+/// Marks `res` as the file at `url_path`, sent from disk, for the download counts.
+pub(crate) fn mark_served(res: &mut HttpResponse, url_path: &str) {
+    res.extensions_mut()
+        .insert(ServedFile(url_path.to_string()));
+}
+
+/// Adds a download of `path`, unless it is new and [`DOWNLOAD_KEY_LIMIT`] paths are counted.
+fn count_download(store: &dyn Store, path: &str) -> std::io::Result<()> {
+    if store.get(DOWNLOADS_NS, path)?.is_none()
+        && store.scan(DOWNLOADS_NS)?.len() >= DOWNLOAD_KEY_LIMIT
+    {
+        return Ok(());
+    }
+    store.increment(DOWNLOADS_NS, path, 1).map(drop)
+}
+
+/// `Referer` of `req`, reduced to its path when it points at this same host.
+fn referer_of(req: &ServiceRequest) -> Option<String> {
+    let referer = req.headers().get(header::REFERER)?.to_str().ok()?;
+    let host = req.connection_info().host().to_string();
+    let same_host = referer
+        .split_once("://")
+        .and_then(|(_, rest)| rest.strip_prefix(host.as_str()))
+        .filter(|path| path.is_empty() || path.starts_with('/'));
+    Some(match same_host {
+        Some("") => "/".to_string(),
+        Some(path) => path.to_string(),
+        None => referer.to_string(),
+    })
+}
+
+/// Middleware feeding [`Stats`] and the download counts of the `--store`; `/reload` polls
+/// and `/__webserve/` itself are not recorded.
+pub async fn collect_stats(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let path = req.path().to_string();
+    let skip = path == "/reload" || path.starts_with(ADMIN_PREFIX);
+    let data = req
+        .app_data::<web::Data<AppState>>()
+        .cloned()
+        .filter(|_| !skip);
+    let method = req.method().to_string();
+    let referer = referer_of(&req);
+    let started = Instant::now();
+    let res = next.call(req).await?;
+    if let Some(data) = data {
+        let status = res.status().as_u16();
+        let elapsed = started.elapsed();
+        data.stats.record_request(&method, &path, status, elapsed);
+        if status >= 500 {
+            data.events.emit(ServerEvent::Error {
+                message: format!("{} {} answered {}", method, path, status),
+            });
+        }
+        data.events.emit(ServerEvent::Request {
+            method: method.clone(),
+            path: path.clone(),
+            status,
+            duration_ms: elapsed.as_secs_f64() * 1000.0,
+        });
+        if status == 404 && (method == "GET" || method == "HEAD") {
+            data.stats.record_not_found(&path, referer.as_deref());
+        }
+        let served = res
+            .response()
+            .extensions()
+            .get::<ServedFile>()
+            .map(|file| file.0.clone());
+        if let Some(file) = served.filter(|_| method == "GET" && status == 200) {
+            // Persistent stores may touch disk; the response doesn't wait for the count.
+            let store = data.store.clone();
+            actix_web::rt::task::spawn_blocking(move || {
+                if let Err(e) = count_download(store.as_ref(), &file) {
+                    tracing::debug!("download count for {}: {}", file, e);
+                }
+            });
+        }
+    }
+    Ok(res)
+}
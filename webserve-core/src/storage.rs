@@ -0,0 +1,328 @@
+//! Pluggable key-value storage (`--store`) for state that may outlive a restart.
+//!
+//! Values are opaque bytes grouped into namespaces (one per consumer, e.g. [`DOWNLOADS_NS`]
+//! for download counts). `memory` keeps everything in the process and is the default;
+//! `sled:<dir>` and `sqlite:<file>` persist it and need the `sled` / `sqlite` build features.
+//! Embedders can put their own [`Store`] into [`crate::AppState::store`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// Namespace of per-path download counts (successful `GET`s).
+pub const DOWNLOADS_NS: &str = "downloads";
+
+/// Key-value store shared by every consumer of persisted state.
+pub trait Store: Send + Sync {
+    /// Value of `key` in `namespace`, if set.
+    fn get(&self, namespace: &str, key: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Sets `key` in `namespace` to `value`.
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> io::Result<()>;
+
+    /// Deletes `key` from `namespace`; missing keys are not an error.
+    fn remove(&self, namespace: &str, key: &str) -> io::Result<()>;
+
+    /// Every entry of `namespace`, sorted by key.
+    fn scan(&self, namespace: &str) -> io::Result<Vec<(String, Vec<u8>)>>;
+
+    /// Adds `by` to the counter at `key` and returns the new value.
+    ///
+    /// Counters are stored as 8 big-endian bytes. The default is a plain read-modify-write;
+    /// the bundled backends override it to be atomic.
+    fn increment(&self, namespace: &str, key: &str, by: u64) -> io::Result<u64> {
+        let value = counter(self.get(namespace, key)?.as_deref()).saturating_add(by);
+        self.put(namespace, key, &value.to_be_bytes())?;
+        Ok(value)
+    }
+
+    /// Backend name and location, as shown in logs and on the dashboard.
+    fn describe(&self) -> String;
+}
+
+/// Decodes a counter written by [`Store::increment`]; anything else reads as 0.
+pub fn counter(value: Option<&[u8]>) -> u64 {
+    value
+        .and_then(|v| <[u8; 8]>::try_from(v).ok())
+        .map(u64::from_be_bytes)
+        .unwrap_or(0)
+}
+
+/// Opens the store named by `--store`: `memory`, `sled:<dir>` or `sqlite:<file>`.
+pub fn open_store(spec: &str) -> Result<Arc<dyn Store>, String> {
+    let (kind, location) = spec.split_once(':').unwrap_or((spec, ""));
+    match (kind, location) {
+        ("memory", "") => Ok(Arc::new(MemoryStore::default())),
+        ("sled" | "sqlite", "") => Err(format!("--store {}: missing path", spec)),
+        #[cfg(feature = "sled")]
+        ("sled", dir) => sled_store::SledStore::open(std::path::Path::new(dir))
+            .map(|s| Arc::new(s) as Arc<dyn Store>)
+            .map_err(|e| format!("--store {}: {}", spec, e)),
+        #[cfg(feature = "sqlite")]
+        ("sqlite", file) => sqlite_store::SqliteStore::open(std::path::Path::new(file))
+            .map(|s| Arc::new(s) as Arc<dyn Store>)
+            .map_err(|e| format!("--store {}: {}", spec, e)),
+        #[cfg(not(feature = "sled"))]
+        ("sled", _) => Err(format!(
+            "--store {}: needs a build with `--features sled`",
+            spec
+        )),
+        #[cfg(not(feature = "sqlite"))]
+        ("sqlite", _) => Err(format!(
+            "--store {}: needs a build with `--features sqlite`",
+            spec
+        )),
+        _ => Err(format!(
+            "--store {}: expected memory, sled:<dir> or sqlite:<file>",
+            spec
+        )),
+    }
+}
+
+/// In-process store; the default, and lost on exit.
+#[derive(Default)]
+pub struct MemoryStore {
+    namespaces: Mutex<HashMap<String, BTreeMap<String, Vec<u8>>>>,
+}
+
+impl MemoryStore {
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&mut HashMap<String, BTreeMap<String, Vec<u8>>>) -> T,
+    ) -> io::Result<T> {
+        let mut namespaces = self
+            .namespaces
+            .lock()
+            .map_err(|_| io::Error::other("memory store lock poisoned"))?;
+        Ok(f(&mut namespaces))
+    }
+}
+
+impl Store for MemoryStore {
+    fn get(&self, namespace: &str, key: &str) -> io::Result<Option<Vec<u8>>> {
+        self.with(|n| n.get(namespace).and_then(|ns| ns.get(key)).cloned())
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> io::Result<()> {
+        self.with(|n| {
+            n.entry(namespace.to_string())
+                .or_default()
+                .insert(key.to_string(), value.to_vec());
+        })
+    }
+
+    fn remove(&self, namespace: &str, key: &str) -> io::Result<()> {
+        self.with(|n| {
+            if let Some(ns) = n.get_mut(namespace) {
+                ns.remove(key);
+            }
+        })
+    }
+
+    fn scan(&self, namespace: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+        self.with(|n| {
+            n.get(namespace)
+                .map(|ns| ns.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+                .unwrap_or_default()
+        })
+    }
+
+    fn increment(&self, namespace: &str, key: &str, by: u64) -> io::Result<u64> {
+        self.with(|n| {
+            let slot = n
+                .entry(namespace.to_string())
+                .or_default()
+                .entry(key.to_string())
+                .or_default();
+            let value = counter(Some(slot)).saturating_add(by);
+            *slot = value.to_be_bytes().to_vec();
+            value
+        })
+    }
+
+    fn describe(&self) -> String {
+        "memory".to_string()
+    }
+}
+
+#[cfg(feature = "sled")]
+pub use sled_store::SledStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite_store::SqliteStore;
+
+#[cfg(feature = "sled")]
+mod sled_store {
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    use super::{counter, Store};
+
+    /// Embedded [sled](https://docs.rs/sled) database; one tree per namespace.
+    pub struct SledStore {
+        db: sled::Db,
+        path: PathBuf,
+    }
+
+    impl SledStore {
+        pub fn open(path: &Path) -> io::Result<SledStore> {
+            let db = sled::open(path).map_err(io::Error::other)?;
+            Ok(SledStore {
+                db,
+                path: path.to_path_buf(),
+            })
+        }
+
+        fn tree(&self, namespace: &str) -> io::Result<sled::Tree> {
+            self.db.open_tree(namespace).map_err(io::Error::other)
+        }
+    }
+
+    impl Store for SledStore {
+        fn get(&self, namespace: &str, key: &str) -> io::Result<Option<Vec<u8>>> {
+            let value = self.tree(namespace)?.get(key).map_err(io::Error::other)?;
+            Ok(value.map(|v| v.to_vec()))
+        }
+
+        fn put(&self, namespace: &str, key: &str, value: &[u8]) -> io::Result<()> {
+            self.tree(namespace)?
+                .insert(key, value)
+                .map_err(io::Error::other)?;
+            Ok(())
+        }
+
+        fn remove(&self, namespace: &str, key: &str) -> io::Result<()> {
+            self.tree(namespace)?
+                .remove(key)
+                .map_err(io::Error::other)?;
+            Ok(())
+        }
+
+        fn scan(&self, namespace: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+            self.tree(namespace)?
+                .iter()
+                .map(|entry| {
+                    let (k, v) = entry.map_err(io::Error::other)?;
+                    Ok((String::from_utf8_lossy(&k).into_owned(), v.to_vec()))
+                })
+                .collect()
+        }
+
+        fn increment(&self, namespace: &str, key: &str, by: u64) -> io::Result<u64> {
+            let value = self
+                .tree(namespace)?
+                .update_and_fetch(key, |old| {
+                    Some(counter(old).saturating_add(by).to_be_bytes().to_vec())
+                })
+                .map_err(io::Error::other)?;
+            Ok(counter(value.as_deref()))
+        }
+
+        fn describe(&self) -> String {
+            format!("sled:{}", self.path.display())
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite_store {
+    use rusqlite::{params, Connection, OptionalExtension};
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+
+    use super::{counter, Store};
+
+    /// SQLite file with a single `kv (ns, key, value)` table.
+    pub struct SqliteStore {
+        conn: Mutex<Connection>,
+        path: PathBuf,
+    }
+
+    impl SqliteStore {
+        pub fn open(path: &Path) -> io::Result<SqliteStore> {
+            let conn = Connection::open(path).map_err(io::Error::other)?;
+            conn.execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 CREATE TABLE IF NOT EXISTS kv (
+                     ns TEXT NOT NULL,
+                     key TEXT NOT NULL,
+                     value BLOB NOT NULL,
+                     PRIMARY KEY (ns, key)
+                 );",
+            )
+            .map_err(io::Error::other)?;
+            Ok(SqliteStore {
+                conn: Mutex::new(conn),
+                path: path.to_path_buf(),
+            })
+        }
+
+        fn with<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> io::Result<T> {
+            let conn = self
+                .conn
+                .lock()
+                .map_err(|_| io::Error::other("sqlite store lock poisoned"))?;
+            f(&conn).map_err(io::Error::other)
+        }
+    }
+
+    const UPSERT: &str = "INSERT INTO kv (ns, key, value) VALUES (?1, ?2, ?3)
+                          ON CONFLICT (ns, key) DO UPDATE SET value = excluded.value";
+
+    impl Store for SqliteStore {
+        fn get(&self, namespace: &str, key: &str) -> io::Result<Option<Vec<u8>>> {
+            self.with(|c| {
+                c.query_row(
+                    "SELECT value FROM kv WHERE ns = ?1 AND key = ?2",
+                    params![namespace, key],
+                    |row| row.get(0),
+                )
+                .optional()
+            })
+        }
+
+        fn put(&self, namespace: &str, key: &str, value: &[u8]) -> io::Result<()> {
+            self.with(|c| c.execute(UPSERT, params![namespace, key, value]))
+                .map(|_| ())
+        }
+
+        fn remove(&self, namespace: &str, key: &str) -> io::Result<()> {
+            self.with(|c| {
+                c.execute(
+                    "DELETE FROM kv WHERE ns = ?1 AND key = ?2",
+                    params![namespace, key],
+                )
+            })
+            .map(|_| ())
+        }
+
+        fn scan(&self, namespace: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+            self.with(|c| {
+                let mut stmt = c.prepare("SELECT key, value FROM kv WHERE ns = ?1 ORDER BY key")?;
+                let rows =
+                    stmt.query_map(params![namespace], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                rows.collect()
+            })
+        }
+
+        // The connection mutex already serializes the read and the write.
+        fn increment(&self, namespace: &str, key: &str, by: u64) -> io::Result<u64> {
+            self.with(|c| {
+                let old: Option<Vec<u8>> = c
+                    .query_row(
+                        "SELECT value FROM kv WHERE ns = ?1 AND key = ?2",
+                        params![namespace, key],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let value = counter(old.as_deref()).saturating_add(by);
+                c.execute(UPSERT, params![namespace, key, &value.to_be_bytes()[..]])?;
+                Ok(value)
+            })
+        }
+
+        fn describe(&self) -> String {
+            format!("sqlite:{}", self.path.display())
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! `--strict-http`: audits outgoing responses and logs protocol violations.
+//!
+//! Debug aid for the hand-built `HttpResponse`s in this crate; it never changes a response.
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::Error;
+
+/// Checks one response; returns a description of each violation found.
+pub fn audit_response(
+    method: &Method,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: BodySize,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let body_len = match body {
+        BodySize::None => Some(0),
+        BodySize::Sized(n) => Some(n),
+        BodySize::Stream => None,
+    };
+    let has_body = body_len != Some(0);
+
+    if let Some(declared) = headers.get(header::CONTENT_LENGTH) {
+        match (
+            declared.to_str().ok().and_then(|v| v.parse::<u64>().ok()),
+            body_len,
+        ) {
+            (None, _) => problems.push("Content-Length is not a number".to_string()),
+            (Some(declared), Some(actual)) if declared != actual && *method != Method::HEAD => {
+                problems.push(format!(
+                    "Content-Length {} does not match body size {}",
+                    declared, actual
+                ))
+            }
+            _ => {}
+        }
+    }
+    if has_body
+        && (status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED)
+    {
+        problems.push(format!("{} response must not have a body", status.as_u16()));
+    }
+    if has_body && *method == Method::HEAD {
+        problems.push("HEAD response must not have a body".to_string());
+    }
+    if has_body && !headers.contains_key(header::CONTENT_TYPE) {
+        problems.push("response with a body has no Content-Type".to_string());
+    }
+    if matches!(status.as_u16(), 301 | 302 | 303 | 307 | 308)
+        && !headers.contains_key(header::LOCATION)
+    {
+        problems.push(format!("{} redirect has no Location", status.as_u16()));
+    }
+    problems
+}
+
+/// Middleware for `--strict-http`; logs each violation with the request line.
+pub async fn strict_http(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let res = next.call(req).await?;
+    let problems = audit_response(
+        &method,
+        res.status(),
+        res.headers(),
+        res.response().body().size(),
+    );
+    for problem in problems {
+        tracing::warn!(%method, %path, "strict-http: {}", problem);
+    }
+    Ok(res)
+}
@@ -0,0 +1,124 @@
+//! `--suggest`: "did you mean" links on `404` pages.
+//!
+//! A request for `/Assets/Logo.PNG` when the file is `/assets/logo.png` is the most common
+//! typo in development, and a bare `404` hides it. With `--suggest`, each path segment that
+//! does not exist is matched against its siblings: first ignoring case, then by edit distance
+//! (at most a third of the name). Up to [`MAX_SUGGESTIONS`] existing paths are listed on the
+//! error page and in the [`SUGGEST_HEADER`] response header.
+//!
+//! Dotfiles are only suggested for a request that itself names a dotfile, and editor
+//! swap/backup files never are. Each directory read honours `--max-entries`.
+
+use std::path::Path;
+
+use crate::path::encode_url_path;
+use crate::watch::is_editor_temp;
+
+/// Response header listing suggested URL paths, comma-separated and percent-encoded.
+pub const SUGGEST_HEADER: &str = "x-webserve-suggest";
+
+/// Paths suggested at most.
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Existing URL paths close to the missing `url_path` under `root`, best match first.
+pub fn suggestions(root: &Path, url_path: &str, max_entries: Option<usize>) -> Vec<String> {
+    let segments: Vec<&str> = url_path.split('/').filter(|s| !s.is_empty()).collect();
+    let mut found = Vec::new();
+    collect(root, "", &segments, max_entries, &mut found);
+    found.truncate(MAX_SUGGESTIONS);
+    found
+}
+
+/// Resolves `segments` below `dir` (URL path `prefix`), trying close siblings for each
+/// segment that does not exist.
+fn collect(
+    dir: &Path,
+    prefix: &str,
+    segments: &[&str],
+    max_entries: Option<usize>,
+    found: &mut Vec<String>,
+) {
+    let Some((segment, rest)) = segments.split_first() else {
+        found.push(if prefix.is_empty() {
+            "/".to_string()
+        } else {
+            prefix.to_string()
+        });
+        return;
+    };
+    if found.len() >= MAX_SUGGESTIONS {
+        return;
+    }
+    let exact = dir.join(segment);
+    let candidates = if exact.exists() {
+        vec![segment.to_string()]
+    } else {
+        close_siblings(dir, segment, max_entries)
+    };
+    for name in candidates {
+        let path = dir.join(&name);
+        if !rest.is_empty() && !path.is_dir() {
+            continue;
+        }
+        collect(
+            &path,
+            &format!("{}/{}", prefix, name),
+            rest,
+            max_entries,
+            found,
+        );
+    }
+}
+
+/// Entries of `dir` close to `wanted`, closest first.
+fn close_siblings(dir: &Path, wanted: &str, max_entries: Option<usize>) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let wanted_lower = wanted.to_lowercase();
+    let max_distance = (wanted.chars().count() / 3).max(1);
+    let mut scored: Vec<(usize, String)> = entries
+        .take(max_entries.unwrap_or(usize::MAX))
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            if (name.starts_with('.') && !wanted.starts_with('.')) || is_editor_temp(&entry.path())
+            {
+                return None;
+            }
+            let distance = levenshtein(&name.to_lowercase(), &wanted_lower);
+            (distance <= max_distance).then_some((distance, name))
+        })
+        .collect();
+    scored.sort();
+    scored.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Edit distance between `a` and `b`, counted in characters.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// [`SUGGEST_HEADER`] value for `paths`.
+pub fn suggest_header(paths: &[String]) -> String {
+    paths
+        .iter()
+        .map(|p| encode_url_path(p))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,140 @@
+//! Slow-network simulation (`--throttle <kbps>`, `--latency <ms>`).
+//!
+//! Unlike browser devtools throttling, this shapes the server side, so service workers,
+//! `<video>` range requests and non-browser clients see the slow link too. `--latency`
+//! holds each `serve_file` response back before its head is sent; `--throttle` paces the
+//! body in slices of a tenth of a second's worth of bytes. Pacing applies to the bytes
+//! `serve_file` produces, so `--compress` sends its (smaller) output only once the whole
+//! body has trickled through.
+
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::HttpResponse;
+use bytes::Bytes;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Sleep;
+
+use crate::ServeOptions;
+
+/// Pacing granularity: one slice of the body per tick.
+const TICK: Duration = Duration::from_millis(100);
+
+/// `--throttle` / `--latency` settings; both off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkShape {
+    /// Bandwidth in kilobits per second.
+    pub kbps: Option<u32>,
+    /// Delay before each response.
+    pub latency: Option<Duration>,
+}
+
+impl NetworkShape {
+    pub fn from_options(options: &ServeOptions) -> Result<NetworkShape, String> {
+        if options.throttle == Some(0) {
+            return Err("--throttle must be at least 1 kbps".to_string());
+        }
+        Ok(NetworkShape {
+            kbps: options.throttle,
+            latency: options.latency.filter(|d| !d.is_zero()),
+        })
+    }
+
+    /// True when responses go out unshaped.
+    pub fn is_off(&self) -> bool {
+        self.kbps.is_none() && self.latency.is_none()
+    }
+
+    /// Dashboard text, e.g. `400 kbps, 2s latency`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(kbps) = self.kbps {
+            parts.push(format!("{} kbps", kbps));
+        }
+        if let Some(latency) = self.latency {
+            parts.push(format!("{:?} latency", latency));
+        }
+        match parts.is_empty() {
+            true => "off".to_string(),
+            false => parts.join(", "),
+        }
+    }
+
+    /// Bandwidth in bytes per second.
+    pub fn bytes_per_sec(&self) -> Option<u64> {
+        self.kbps.map(|kbps| (kbps as u64 * 1000 / 8).max(1))
+    }
+
+    /// Waits out `--latency`, then wraps the body of `res` in a [`Throttled`] pacer.
+    pub async fn apply(&self, res: HttpResponse) -> HttpResponse {
+        if let Some(latency) = self.latency {
+            tokio::time::sleep(latency).await;
+        }
+        match self.bytes_per_sec() {
+            Some(rate) => res.map_body(|_, body| BoxBody::new(Throttled::new(body, rate))),
+            None => res,
+        }
+    }
+}
+
+/// Body wrapper releasing at most `rate` bytes per second.
+pub struct Throttled<B> {
+    inner: B,
+    rate: u64,
+    /// Rest of a chunk larger than one slice.
+    pending: Bytes,
+    /// Pause owed for the slice sent last.
+    pause: Option<Pin<Box<Sleep>>>,
+}
+
+impl<B> Throttled<B> {
+    pub fn new(inner: B, rate: u64) -> Throttled<B> {
+        Throttled {
+            inner,
+            rate: rate.max(1),
+            pending: Bytes::new(),
+            pause: None,
+        }
+    }
+
+    /// Bytes released per tick.
+    fn slice_len(&self) -> usize {
+        (self.rate * TICK.as_millis() as u64 / 1000).max(1) as usize
+    }
+}
+
+impl<B: MessageBody + Unpin> MessageBody for Throttled<B> {
+    type Error = B::Error;
+
+    fn size(&self) -> BodySize {
+        self.inner.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(ref mut pause) = this.pause {
+            if pause.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.pause = None;
+        }
+        if this.pending.is_empty() {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => this.pending = chunk,
+                other => return other,
+            }
+            if this.pending.is_empty() {
+                return Poll::Ready(Some(Ok(Bytes::new())));
+            }
+        }
+        let len = this.slice_len().min(this.pending.len());
+        let slice = this.pending.split_to(len);
+        let wait = Duration::from_secs_f64(len as f64 / this.rate as f64);
+        this.pause = Some(Box::pin(tokio::time::sleep(wait)));
+        Poll::Ready(Some(Ok(slice)))
+    }
+}
@@ -0,0 +1,67 @@
+//! Worker and connection tuning (`--workers`, `--max-connections`, `--keep-alive`,
+//! `--client-timeout`).
+//!
+//! Each option maps onto the `HttpServer` builder method of the same name; unset options
+//! keep actix-web's defaults (one worker per physical core, 25k connections per worker,
+//! 5s keep-alive, 5s to receive the request head). Durations take the `parse_duration`
+//! forms (`250ms`, `2s`, a bare number of milliseconds), and `0` disables the timeout.
+
+use std::time::Duration;
+
+use crate::ServeOptions;
+
+/// Server builder settings; `None` leaves the actix-web default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerTuning {
+    /// Worker threads, each with its own event loop.
+    pub workers: Option<usize>,
+    /// Concurrent connections accepted per worker.
+    pub max_connections: Option<usize>,
+    /// Idle time before a kept-alive connection is closed.
+    pub keep_alive: Option<Duration>,
+    /// Time allowed for a client to send the request head.
+    pub client_timeout: Option<Duration>,
+}
+
+impl ServerTuning {
+    /// Errors on zero workers or connections, which would leave the server unable to answer.
+    pub fn from_options(options: &ServeOptions) -> Result<ServerTuning, String> {
+        let positive = |flag: &str, n: Option<usize>| match n {
+            Some(0) => Err(format!("--{} must be at least 1", flag)),
+            n => Ok(n),
+        };
+        Ok(ServerTuning {
+            workers: positive("workers", options.workers)?,
+            max_connections: positive("max-connections", options.max_connections)?,
+            keep_alive: options.keep_alive,
+            client_timeout: options.client_timeout,
+        })
+    }
+
+    /// True when every setting keeps its default.
+    pub fn is_default(&self) -> bool {
+        *self == ServerTuning::default()
+    }
+
+    /// Startup log line, e.g. `workers 4, max connections 1000, keep-alive off`.
+    pub fn describe(&self) -> String {
+        let duration = |d: Duration| match d.is_zero() {
+            true => "off".to_string(),
+            false => format!("{:?}", d),
+        };
+        let mut parts = Vec::new();
+        if let Some(n) = self.workers {
+            parts.push(format!("workers {}", n));
+        }
+        if let Some(n) = self.max_connections {
+            parts.push(format!("max connections {}", n));
+        }
+        if let Some(d) = self.keep_alive {
+            parts.push(format!("keep-alive {}", duration(d)));
+        }
+        if let Some(d) = self.client_timeout {
+            parts.push(format!("client timeout {}", duration(d)));
+        }
+        parts.join(", ")
+    }
+}
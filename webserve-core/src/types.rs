@@ -0,0 +1,793 @@
+use bytes::Bytes;
+use notify::Event;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, trace};
+
+#[cfg(feature = "cli")]
+use structopt::StructOpt;
+
+use crate::access_log::{AccessLogger, LogFormat};
+use crate::cache::DEFAULT_CACHE_MIB;
+use crate::compress::AdaptiveCompression;
+use crate::compress_cache::CompressCache;
+use crate::conditional::{EtagMode, FileHashCache};
+use crate::events::{EventBus, ServerEvent};
+use crate::exec::BuildError;
+#[cfg(feature = "cli")]
+use crate::fault::parse_duration;
+use crate::history::{classify, ChangeHistory, ReloadEvent, ReloadLog};
+use crate::hooks::Hooks;
+use crate::inflight::InflightReads;
+use crate::listing_lang::ListingLang;
+use crate::manifest::ManifestCache;
+use crate::mount::Mount;
+use crate::path::url_path_under;
+use crate::settings::{LiveSettings, Settings};
+use crate::stats::Stats;
+use crate::storage::{MemoryStore, Store};
+
+pub type HtmlCache = Arc<RwLock<HashMap<PathBuf, Bytes>>>;
+/// Shared application state accessible by Actix handlers.
+#[derive(Clone)]
+pub struct AppState {
+    pub static_dir: Arc<PathBuf>,
+    pub watch: bool,
+    pub addr: String,
+    /// Every reload batch, with the paths it changed.
+    pub tx: broadcast::Sender<ReloadEvent>,
+    /// Set by filesystem watcher; `/reload` clears and tells clients to refresh.
+    pub reload_pending: Arc<AtomicBool>,
+    /// When `--watch`: cache of path → injected HTML body; cleared when watcher fires.
+    pub html_cache: Option<HtmlCache>,
+    /// SPA mode, rules, fixtures, headers: swapped on config reload without rebinding.
+    pub settings: Arc<LiveSettings>,
+    /// Fixture scenario chosen via `/__webserve/scenario`; kept across config reloads.
+    pub scenario: Arc<RwLock<Option<String>>>,
+    /// `--log`: access-log sink; `None` disables request logging.
+    pub access_log: Option<Arc<AccessLogger>>,
+    /// Content hashes for `--etag sha256`, keyed by path and checked against mtime/size.
+    pub file_hashes: FileHashCache,
+    /// Request, reload-client and file-change history for `/__webserve/`.
+    pub stats: Arc<Stats>,
+    /// `/__webserve/manifest` result, kept while `--watch` sees no change.
+    pub manifest: ManifestCache,
+    /// File events seen by the watcher, for `/__webserve/changes`.
+    pub history: Arc<ChangeHistory>,
+    /// Sequence-numbered watcher batches for `/reload?since=<seq>` catch-up.
+    pub reload_log: Arc<ReloadLog>,
+    /// Disk reads shared by concurrent requests for the same file.
+    pub inflight: InflightReads,
+    /// CPU load that picks the `--compress` level.
+    pub compression: Arc<AdaptiveCompression>,
+    /// `--compress-cache`: compressed file bodies kept on disk.
+    pub compress_cache: Option<Arc<CompressCache>>,
+    /// `--store`: key-value storage for state that may outlive a restart.
+    pub store: Arc<dyn Store>,
+    /// Requests, changes, reloads and errors for `/__webserve/events` subscribers.
+    pub events: Arc<EventBus>,
+    /// Embedder hooks (see [`crate::WebserveBuilder::on_request`]); none for the binary.
+    pub hooks: Arc<Hooks>,
+    /// Where this site is served: the root, a `--mount` prefix or a `--vhost` host. Each site
+    /// has its own reload channel, watcher and history.
+    pub mount: Mount,
+}
+
+impl Default for AppState {
+    /// Serves the current directory with every optional feature off.
+    fn default() -> Self {
+        let (tx, _rx) = broadcast::channel(16);
+        AppState {
+            static_dir: Arc::new(PathBuf::from(".")),
+            watch: false,
+            addr: "127.0.0.1:8080".to_string(),
+            tx,
+            reload_pending: Arc::new(AtomicBool::new(false)),
+            html_cache: None,
+            settings: Arc::default(),
+            scenario: Arc::default(),
+            access_log: None,
+            file_hashes: FileHashCache::default(),
+            stats: Arc::default(),
+            manifest: ManifestCache::default(),
+            history: Arc::default(),
+            reload_log: Arc::default(),
+            inflight: InflightReads::default(),
+            compression: Arc::default(),
+            compress_cache: None,
+            store: Arc::new(MemoryStore::default()),
+            events: Arc::default(),
+            hooks: Arc::default(),
+            mount: Mount::Root,
+        }
+    }
+}
+
+impl ServeOptions {
+    /// Path `--open` should load: `None` when the flag is off, `/` when given without a path.
+    pub fn open_path(&self) -> Option<&str> {
+        self.open.as_ref().map(|p| p.as_deref().unwrap_or("/"))
+    }
+}
+
+impl AppState {
+    /// Records a batch of file events for `/__webserve/changes`, the dashboard and `/reload`
+    /// catch-up, drops caches built from the old files and tells live-reload clients to
+    /// refresh.
+    pub fn publish_changes(&self, batch: &[Event]) {
+        self.history.record(&self.static_dir, batch);
+        let seq = self.reload_log.record(&self.static_dir, batch);
+        for event in batch {
+            debug!(kind = ?event.kind, paths = ?event.paths, "file change");
+            // The dashboard shows URL paths, not where the files sit on disk
+            let paths = event
+                .paths
+                .iter()
+                .map(|p| match url_path_under(&self.static_dir, p) {
+                    Some(url) => PathBuf::from(url),
+                    None => PathBuf::from(p.file_name().unwrap_or_default()),
+                })
+                .collect();
+            self.stats
+                .record_change(&format!("{:?}", event.kind), paths);
+            for (path, kind) in classify(event) {
+                if let Some(path) = url_path_under(&self.static_dir, path) {
+                    self.events.emit(ServerEvent::Change { path, kind });
+                }
+            }
+        }
+        let reload = self.reload_log.event(seq).unwrap_or(ReloadEvent {
+            seq,
+            ..Default::default()
+        });
+        self.events.emit(ServerEvent::Reload {
+            seq,
+            paths: reload.paths.clone(),
+            changes: reload.changes.clone(),
+        });
+        self.reload_pending.store(true, Ordering::SeqCst);
+        let receivers = self.tx.send(reload).unwrap_or(0);
+        debug!(
+            receivers,
+            seq,
+            mount = %self.mount,
+            "reload broadcast; next /reload poll reloads the page"
+        );
+        self.clear_html_cache();
+        if let Ok(mut guard) = self.manifest.write() {
+            *guard = None;
+        }
+    }
+
+    /// Tells live-reload clients that the `--exec` build failed, so they show its output over
+    /// the page instead of silently keeping the previous build.
+    pub fn publish_build_error(&self, error: BuildError) {
+        self.events.emit(ServerEvent::Error {
+            message: error.message(),
+        });
+        let seq = self.reload_log.record_error(error);
+        let event = self.reload_log.event(seq).unwrap_or_default();
+        let receivers = self.tx.send(event).unwrap_or(0);
+        debug!(receivers, seq, "build error broadcast");
+        // Pages served from now on carry the error
+        self.clear_html_cache();
+    }
+
+    /// Swaps in reloaded settings. Injected pages cached under the old ones are dropped:
+    /// `--open-in-editor` and `--no-cache` change what gets injected.
+    pub fn reload_settings(&self, settings: Settings) {
+        self.settings.store(settings);
+        self.clear_html_cache();
+    }
+
+    fn clear_html_cache(&self) {
+        if let Some(Ok(mut guard)) = self.html_cache.as_ref().map(|c| c.write()) {
+            trace!(entries = guard.len(), "clearing injected HTML cache");
+            guard.clear();
+        }
+    }
+
+    /// Publishes a change made by a request (upload, delete, new folder). Under `--watch`
+    /// the watcher sees it on disk and publishes it itself.
+    pub fn publish_mutation(&self, event: Event) {
+        if !self.watch {
+            self.publish_changes(&[event]);
+        }
+    }
+
+    /// Globally active fixture scenario, if any.
+    pub fn active_scenario(&self) -> Option<String> {
+        self.scenario.read().ok().and_then(|s| s.clone())
+    }
+}
+
+/// Entry for one file or directory in a listing.
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: Option<u64>,
+    pub modified: Option<std::time::SystemTime>,
+}
+
+/// Why the chosen static root cannot be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaticDirError {
+    /// Path does not exist on disk.
+    NotFound,
+    /// Path exists but is not a directory (e.g. a file).
+    NotADirectory,
+}
+
+/// Every option of the server, whether set by a flag, the config file or the environment.
+/// With the `cli` feature it is also the StructOpt parser of those flags.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(StructOpt))]
+#[cfg_attr(
+    feature = "cli",
+    structopt(
+        name = "webserve",
+        about = "A simple static file server with live reload."
+    )
+)]
+pub struct ServeOptions {
+    /// The port to listen on (default: 8080)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(short = "p", long = "port", default_value = "8080")
+    )]
+    pub port: u16,
+
+    /// Listen on this Unix domain socket instead of TCP (`--host` / `--port` are ignored)
+    #[cfg_attr(feature = "cli", structopt(long = "uds", parse(from_os_str)))]
+    pub uds: Option<PathBuf>,
+
+    /// PEM certificate chain; serves HTTPS (HTTP/2 via ALPN) together with `--tls-key`
+    #[cfg_attr(feature = "cli", structopt(long = "tls-cert", parse(from_os_str)))]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for `--tls-cert`
+    #[cfg_attr(feature = "cli", structopt(long = "tls-key", parse(from_os_str)))]
+    pub tls_key: Option<PathBuf>,
+
+    /// Negotiate HTTP/1.1 only on TLS listeners (no HTTP/2), for debugging
+    #[cfg_attr(feature = "cli", structopt(long = "http1-only"))]
+    pub http1_only: bool,
+
+    /// With TLS, also listen for plain HTTP on this port and 301 every request to HTTPS
+    #[cfg_attr(feature = "cli", structopt(long = "redirect-http"))]
+    pub redirect_http: Option<u16>,
+
+    /// Accept sockets passed by systemd (`LISTEN_FDS`) and send `READY=1` once listening
+    #[cfg_attr(feature = "cli", structopt(long = "systemd"))]
+    pub systemd: bool,
+
+    /// Unix: fork into the background, write --pid-file and send output to --daemon-log; stop with `webserve stop`
+    #[cfg_attr(feature = "cli", structopt(long = "daemon"))]
+    pub daemon: bool,
+
+    /// PID file written by --daemon and read by `webserve stop` (default: webserve.pid)
+    #[cfg_attr(feature = "cli", structopt(long = "pid-file", parse(from_os_str)))]
+    pub pid_file: Option<PathBuf>,
+
+    /// File --daemon appends the server's own output (startup, warnings, errors) to; discarded without one
+    #[cfg_attr(feature = "cli", structopt(long = "daemon-log", parse(from_os_str)))]
+    pub daemon_log: Option<PathBuf>,
+
+    /// Fail instead of trying the next port when the requested one is in use
+    #[cfg_attr(feature = "cli", structopt(long = "strict-port"))]
+    pub strict_port: bool,
+
+    /// Address to bind to, IPv4 or IPv6 (repeatable: `--host ::1 --host 127.0.0.1`)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(
+            short = "h",
+            long = "host",
+            default_value = "127.0.0.1",
+            number_of_values = 1
+        )
+    )]
+    pub hosts: Vec<String>,
+
+    /// The directory to serve files from (defaults to current directory)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(short = "d", long = "dir", parse(from_os_str))
+    )]
+    pub directory: Option<PathBuf>,
+
+    /// Serve only this file, at `/` (everything else is 404); `--watch` watches just the file
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "file", parse(from_os_str), conflicts_with = "directory")
+    )]
+    pub file: Option<PathBuf>,
+
+    /// Serve another directory under a URL prefix, e.g. `/docs=./docs` (repeatable); it gets its own watcher and reloads
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "mount", value_name = "prefix=dir", number_of_values = 1)
+    )]
+    pub mounts: Vec<String>,
+
+    /// Serve another directory to requests for a host name, e.g. `docs.localhost=./docs` (repeatable)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "vhost", value_name = "host=dir", number_of_values = 1)
+    )]
+    pub vhosts: Vec<String>,
+
+    /// Enable Single Page Application (SPA) mode — fall back to index.html
+    #[cfg_attr(feature = "cli", structopt(long = "spa"))]
+    pub spa: bool,
+
+    /// Enable live reload by watching for file changes
+    #[cfg_attr(feature = "cli", structopt(short = "w", long = "watch"))]
+    pub watch: bool,
+
+    /// With --watch, also watch this directory outside the served root, e.g. `src` (repeatable)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "watch-path", number_of_values = 1, parse(from_os_str))
+    )]
+    pub watch_paths: Vec<PathBuf>,
+
+    /// With --watch, run this shell command on changes and reload only if it succeeds
+    #[cfg_attr(feature = "cli", structopt(long = "exec", value_name = "command"))]
+    pub exec: Option<String>,
+
+    /// Open the default browser after startup, optionally at a path (`--open /docs/`)
+    #[cfg_attr(feature = "cli", structopt(long = "open", value_name = "path"))]
+    pub open: Option<Option<String>>,
+
+    /// Print a QR code of the LAN URL (or local URL) at startup
+    #[cfg_attr(feature = "cli", structopt(long = "qr"))]
+    pub qr: bool,
+
+    /// Advertise the server on the LAN as `<name>.local` via mDNS / Bonjour
+    #[cfg_attr(feature = "cli", structopt(long = "mdns", value_name = "name"))]
+    pub mdns: Option<String>,
+
+    /// Do not 301-redirect `/dir` to `/dir/` when the URL names a directory (default: redirect)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "no-redirect-dir-slash", visible_alias = "no-slash-redirect")
+    )]
+    pub no_redirect_dir_slash: bool,
+
+    /// Redirect rule FROM=TO[:STATUS], e.g. `/old=/new:301` or `/blog/*=/news/:splat` (repeatable)
+    #[cfg_attr(feature = "cli", structopt(long = "redirect", number_of_values = 1))]
+    pub redirects: Vec<String>,
+
+    /// Rewrite rule FROM=TO served without redirecting, e.g. `^/v1/(.*)=/api/$1` (repeatable)
+    #[cfg_attr(feature = "cli", structopt(long = "rewrite", number_of_values = 1))]
+    pub rewrites: Vec<String>,
+
+    /// Replace text in response bodies as PATTERN=FIND=>REPLACE, e.g. `*.html=https://example.com/=>/` (repeatable)
+    #[cfg_attr(feature = "cli", structopt(long = "replace", number_of_values = 1))]
+    pub replace: Vec<String>,
+
+    /// Like --replace with a regular expression for FIND and `$1` references in REPLACE (repeatable)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "replace-regex", number_of_values = 1)
+    )]
+    pub replace_regex: Vec<String>,
+
+    /// Only serve clients in this CIDR block, e.g. `10.0.0.0/8` (repeatable; most specific rule wins)
+    #[cfg_attr(feature = "cli", structopt(long = "allow", number_of_values = 1))]
+    pub allow: Vec<String>,
+
+    /// Answer clients in this CIDR block with 403, e.g. `0.0.0.0/0` (repeatable)
+    #[cfg_attr(feature = "cli", structopt(long = "deny", number_of_values = 1))]
+    pub deny: Vec<String>,
+
+    /// Netlify `_redirects`-style rules file (`from to [status]` per line; 200 = rewrite)
+    #[cfg_attr(feature = "cli", structopt(long = "rules", parse(from_os_str)))]
+    pub rules_file: Option<PathBuf>,
+
+    /// Directory of YAML fixture files with canned routes served ahead of the filesystem
+    #[cfg_attr(feature = "cli", structopt(long = "fixtures", parse(from_os_str)))]
+    pub fixtures: Option<PathBuf>,
+
+    /// Directory of `<OperationName>.json` mocks answered at `/graphql`
+    #[cfg_attr(feature = "cli", structopt(long = "graphql-mocks", parse(from_os_str)))]
+    pub graphql_mocks: Option<PathBuf>,
+
+    /// JSON file served as a fake REST API: each top-level key becomes CRUD routes
+    #[cfg_attr(feature = "cli", structopt(long = "mock", parse(from_os_str)))]
+    pub mock: Option<PathBuf>,
+
+    /// URL prefix of the --mock routes, e.g. `/api`
+    #[cfg_attr(feature = "cli", structopt(long = "mock-prefix", default_value = "/"))]
+    pub mock_prefix: String,
+
+    /// Write --mock changes back to the file (and reload browsers under --watch)
+    #[cfg_attr(feature = "cli", structopt(long = "mock-persist"))]
+    pub mock_persist: bool,
+
+    /// Forward requests under a path prefix to another server, e.g. `/api=http://localhost:3000` (repeatable)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "proxy", value_name = "prefix=url", number_of_values = 1)
+    )]
+    pub proxies: Vec<String>,
+
+    /// Only proxy to this HOST[:PORT] (repeatable; also lets --proxy-deny-private reach it)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "proxy-allow", value_name = "host[:port]", number_of_values = 1)
+    )]
+    pub proxy_allow: Vec<String>,
+
+    /// Refuse proxy targets resolving to loopback or private addresses unless --proxy-allow lists them
+    #[cfg_attr(feature = "cli", structopt(long = "proxy-deny-private"))]
+    pub proxy_deny_private: bool,
+
+    /// WebAssembly module handling requests and responses (repeatable; needs the `wasm-plugins` build feature)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "plugin", number_of_values = 1, parse(from_os_str))
+    )]
+    pub plugins: Vec<PathBuf>,
+
+    /// Extra response header `Name: value` added to every response (repeatable)
+    #[cfg_attr(feature = "cli", structopt(long = "header", number_of_values = 1))]
+    pub headers: Vec<String>,
+
+    /// Content-Type for files with an extension, e.g. `glb=model/gltf-binary` (repeatable; overrides the guessed type)
+    #[cfg_attr(feature = "cli", structopt(long = "mime", number_of_values = 1))]
+    pub mime: Vec<String>,
+
+    /// Charset announced for text files (text/*, JavaScript), or `off` for none
+    #[cfg_attr(feature = "cli", structopt(long = "charset", default_value = "utf-8"))]
+    pub charset: String,
+
+    /// Charset for files with an extension, e.g. `txt=shift_jis` (repeatable; overrides --charset)
+    #[cfg_attr(feature = "cli", structopt(long = "charset-ext", number_of_values = 1))]
+    pub charset_ext: Vec<String>,
+
+    /// Serve the nearest ancestor directory's index.html for missing paths (sectioned sites)
+    #[cfg_attr(feature = "cli", structopt(long = "section-index"))]
+    pub section_index: bool,
+
+    /// On 404, suggest close matches among the missing path's siblings (case, typos)
+    #[cfg_attr(feature = "cli", structopt(long = "suggest"))]
+    pub suggest: bool,
+
+    /// Experimental: read files through io_uring (Linux, needs the `io-uring` build feature)
+    #[cfg_attr(feature = "cli", structopt(long = "io-uring"))]
+    pub io_uring: bool,
+
+    /// Deepest directory level tree-walking features descend into (default: unlimited)
+    #[cfg_attr(feature = "cli", structopt(long = "max-depth"))]
+    pub max_depth: Option<usize>,
+
+    /// Most files and directories a tree walk or listing reads (default: unlimited)
+    #[cfg_attr(feature = "cli", structopt(long = "max-entries"))]
+    pub max_entries: Option<usize>,
+
+    /// Worker threads (default: one per physical CPU core)
+    #[cfg_attr(feature = "cli", structopt(long = "workers"))]
+    pub workers: Option<usize>,
+
+    /// Concurrent connections accepted per worker (default: 25000)
+    #[cfg_attr(feature = "cli", structopt(long = "max-connections"))]
+    pub max_connections: Option<usize>,
+
+    /// Idle keep-alive timeout, e.g. `30s` or `0` to close after each response (default: 5s)
+    #[cfg_attr(feature = "cli", structopt(long = "keep-alive", parse(try_from_str = parse_duration)))]
+    pub keep_alive: Option<Duration>,
+
+    /// Time a client gets to send the request head, e.g. `10s` or `0` for no limit (default: 5s)
+    #[cfg_attr(feature = "cli", structopt(long = "client-timeout", parse(try_from_str = parse_duration)))]
+    pub client_timeout: Option<Duration>,
+
+    /// Cut off clients that drip request heads or bodies (see `--header-timeout`, `--min-rate`)
+    #[cfg_attr(feature = "cli", structopt(long = "slowloris-guard"))]
+    pub slowloris_guard: bool,
+
+    /// With `--slowloris-guard`, time a client gets to send a request line and headers
+    #[cfg_attr(feature = "cli", structopt(long = "header-timeout", default_value = "10s", parse(try_from_str = parse_duration)))]
+    pub header_timeout: Duration,
+
+    /// With `--slowloris-guard`, least average request body rate in bytes per second
+    #[cfg_attr(feature = "cli", structopt(long = "min-rate", default_value = "512"))]
+    pub min_rate: u64,
+
+    /// Largest request body accepted, in MiB; bigger ones get `413` (default: unlimited)
+    #[cfg_attr(feature = "cli", structopt(long = "max-request-size"))]
+    pub max_request_size: Option<u64>,
+
+    /// Time a client gets to send a request body once its head arrived, e.g. `30s` (default: no limit)
+    #[cfg_attr(feature = "cli", structopt(long = "read-timeout", parse(try_from_str = parse_duration)))]
+    pub read_timeout: Option<Duration>,
+
+    /// Also accept this HTTP method, or `*` for any (default: GET, HEAD, OPTIONS plus those the enabled modes need)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "allow-method", number_of_values = 1)
+    )]
+    pub allow_methods: Vec<String>,
+
+    /// ETag strategy: mtime (file metadata), sha256 (content hash) or off
+    #[cfg_attr(feature = "cli", structopt(long = "etag", default_value = "mtime"))]
+    pub etag: EtagMode,
+
+    /// Serve `.md` files (and a directory's README.md when it has no index.html) as HTML; `?raw=1` for the source
+    #[cfg_attr(feature = "cli", structopt(long = "render-markdown"))]
+    pub render_markdown: bool,
+
+    /// Serve source files requested with `?view=1` as syntax-highlighted HTML with line numbers
+    #[cfg_attr(feature = "cli", structopt(long = "highlight"))]
+    pub highlight: bool,
+
+    /// Accept `PUT` uploads, `DELETE`, `MKCOL` and a multipart upload form on directory listings
+    #[cfg_attr(feature = "cli", structopt(long = "upload"))]
+    pub upload: bool,
+
+    /// Largest file `--upload` accepts, in MiB
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "max-upload-size", default_value = "100")
+    )]
+    pub max_upload_size: u64,
+
+    /// Serve the root over WebDAV (PROPFIND, MKCOL, PUT, DELETE, COPY, MOVE) for read/write mounts
+    #[cfg_attr(feature = "cli", structopt(long = "webdav"))]
+    pub webdav: bool,
+
+    /// Serve /__webserve/open-in-editor (localhost only) and link error overlay stack frames to it
+    #[cfg_attr(feature = "cli", structopt(long = "open-in-editor"))]
+    pub open_in_editor: bool,
+
+    /// Answer /__webserve/ (dashboard, stats, manifest, search, scenarios) for any client, not only localhost
+    #[cfg_attr(feature = "cli", structopt(long = "admin-remote"))]
+    pub admin_remote: bool,
+
+    /// Name shown with the logo on listings, the dashboard and error pages
+    #[cfg_attr(feature = "cli", structopt(long = "brand-title"))]
+    pub brand_title: Option<String>,
+
+    /// Logo URL for generated pages, e.g. `/assets/logo.svg` or `https://…`
+    #[cfg_attr(feature = "cli", structopt(long = "brand-logo"))]
+    pub brand_logo: Option<String>,
+
+    /// Footer text for generated pages
+    #[cfg_attr(feature = "cli", structopt(long = "brand-footer"))]
+    pub brand_footer: Option<String>,
+
+    /// Directory listing language: en, de, fr, es, it, pt, nl, ja, zh, or auto (from Accept-Language)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "listing-lang", default_value = "en")
+    )]
+    pub listing_lang: ListingLang,
+
+    /// gzip/Brotli-compress text responses, backing off as CPU usage rises
+    #[cfg_attr(feature = "cli", structopt(long = "compress"))]
+    pub compress: bool,
+
+    /// CPU usage (percent) at which `--compress` stops compressing; fastest level above half
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "compress-cpu-limit", default_value = "80")
+    )]
+    pub compress_cpu_limit: u8,
+
+    /// Storage for the download counts: memory, sled:<dir> or sqlite:<file>
+    #[cfg_attr(feature = "cli", structopt(long = "store", default_value = "memory"))]
+    pub store: String,
+
+    /// Keep compressed file bodies on disk instead of recompressing per client, in this directory (default: `compress/` under --cache-dir)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "compress-cache", value_name = "dir")
+    )]
+    pub compress_cache: Option<Option<PathBuf>>,
+
+    /// Size cap of `--compress-cache` in MiB; least recently used entries go first
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "compress-cache-size", default_value = "256")
+    )]
+    pub compress_cache_size: u64,
+
+    /// Root of generated on-disk caches (default: `.webserve-cache` in the working directory)
+    #[cfg_attr(feature = "cli", structopt(long = "cache-dir", parse(from_os_str)))]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Size cap of --cache-dir in MiB, enforced at startup; least recently written files go first
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "cache-size", default_value = "1024")
+    )]
+    pub cache_size: u64,
+
+    /// Development caching off: `Cache-Control: no-store`, no ETags, cache-busted asset URLs
+    #[cfg_attr(feature = "cli", structopt(long = "no-cache"))]
+    pub no_cache: bool,
+
+    /// Honor the `X-Webserve-Fault: delay=2s, status=503` request header (for tests)
+    #[cfg_attr(feature = "cli", structopt(long = "fault-injection"))]
+    pub fault_injection: bool,
+
+    /// Reject requests with conflicting Content-Length/Transfer-Encoding, oversized headers or absolute-form targets
+    #[cfg_attr(feature = "cli", structopt(long = "harden"))]
+    pub harden: bool,
+
+    /// Largest header section accepted under `--harden`, in bytes
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "max-header-bytes", default_value = "8192")
+    )]
+    pub max_header_bytes: usize,
+
+    /// Limit file responses to this bandwidth in kilobits per second, e.g. 400 for slow 3G
+    #[cfg_attr(feature = "cli", structopt(long = "throttle"))]
+    pub throttle: Option<u32>,
+
+    /// Delay every file response, e.g. `400` (milliseconds) or `2s`
+    #[cfg_attr(feature = "cli", structopt(long = "latency", parse(try_from_str = parse_duration)))]
+    pub latency: Option<Duration>,
+
+    /// Log responses that break HTTP rules (Content-Length mismatch, body on 204/304/HEAD, missing headers)
+    #[cfg_attr(feature = "cli", structopt(long = "strict-http"))]
+    pub strict_http: bool,
+
+    /// Access log format: common, combined or json (one line per request)
+    #[cfg_attr(feature = "cli", structopt(long = "log"))]
+    pub log: Option<LogFormat>,
+
+    /// Write the access log to this file instead of stdout (implies --log common)
+    #[cfg_attr(feature = "cli", structopt(long = "log-file", parse(from_os_str)))]
+    pub log_file: Option<PathBuf>,
+
+    /// Leave requests for paths matching this pattern out of the access log, e.g. `/__webserve/*` (repeatable)
+    #[cfg_attr(feature = "cli", structopt(long = "log-exclude", number_of_values = 1))]
+    pub log_exclude: Vec<String>,
+
+    /// Log only this share of requests matching PATTERN, as PATTERN=RATE, e.g. `/api/*=0.1`; errors are always logged (repeatable)
+    #[cfg_attr(feature = "cli", structopt(long = "log-sample", number_of_values = 1))]
+    pub log_sample: Vec<String>,
+
+    /// Diagnostic log level: error, warn, info, debug or trace (default: info)
+    #[cfg_attr(feature = "cli", structopt(long = "log-level"))]
+    pub log_level: Option<LevelFilter>,
+
+    /// More diagnostic output: -v for debug, -vv for trace
+    #[cfg_attr(
+        feature = "cli",
+        structopt(short = "v", long = "verbose", parse(from_occurrences))
+    )]
+    pub verbose: u8,
+
+    /// Token for the `/__webserve/events` stream (default: generated and logged at startup)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "events-token", value_name = "token")
+    )]
+    pub events_token: Option<String>,
+
+    /// Config file (TOML); flags given on the command line override it (default: ./webserve.toml if present)
+    #[cfg_attr(
+        feature = "cli",
+        structopt(short = "c", long = "config", parse(from_os_str))
+    )]
+    pub config: Option<PathBuf>,
+
+    /// Write the merged options (config file, environment and flags) to this TOML file and exit
+    #[cfg_attr(
+        feature = "cli",
+        structopt(long = "export-config", value_name = "file", parse(from_os_str))
+    )]
+    pub export_config: Option<PathBuf>,
+}
+
+impl Default for ServeOptions {
+    /// What `webserve` runs with when given no flags; keep in step with the `default_value`s.
+    fn default() -> Self {
+        ServeOptions {
+            port: 8080,
+            uds: None,
+            tls_cert: None,
+            tls_key: None,
+            http1_only: false,
+            redirect_http: None,
+            systemd: false,
+            daemon: false,
+            pid_file: None,
+            daemon_log: None,
+            strict_port: false,
+            hosts: vec!["127.0.0.1".to_string()],
+            directory: None,
+            file: None,
+            mounts: Vec::new(),
+            vhosts: Vec::new(),
+            spa: false,
+            watch: false,
+            watch_paths: Vec::new(),
+            exec: None,
+            open: None,
+            qr: false,
+            mdns: None,
+            no_redirect_dir_slash: false,
+            redirects: Vec::new(),
+            rewrites: Vec::new(),
+            replace: Vec::new(),
+            replace_regex: Vec::new(),
+            allow: Vec::new(),
+            deny: Vec::new(),
+            rules_file: None,
+            fixtures: None,
+            graphql_mocks: None,
+            mock: None,
+            mock_prefix: "/".to_string(),
+            mock_persist: false,
+            proxies: Vec::new(),
+            proxy_allow: Vec::new(),
+            proxy_deny_private: false,
+            plugins: Vec::new(),
+            headers: Vec::new(),
+            mime: Vec::new(),
+            charset: "utf-8".to_string(),
+            charset_ext: Vec::new(),
+            section_index: false,
+            suggest: false,
+            io_uring: false,
+            max_depth: None,
+            max_entries: None,
+            workers: None,
+            max_connections: None,
+            keep_alive: None,
+            client_timeout: None,
+            slowloris_guard: false,
+            header_timeout: Duration::from_secs(10),
+            min_rate: 512,
+            max_request_size: None,
+            read_timeout: None,
+            allow_methods: Vec::new(),
+            etag: EtagMode::Mtime,
+            render_markdown: false,
+            highlight: false,
+            upload: false,
+            max_upload_size: 100,
+            webdav: false,
+            open_in_editor: false,
+            admin_remote: false,
+            brand_title: None,
+            brand_logo: None,
+            brand_footer: None,
+            listing_lang: ListingLang::default(),
+            compress: false,
+            compress_cpu_limit: 80,
+            store: "memory".to_string(),
+            compress_cache: None,
+            compress_cache_size: 256,
+            cache_dir: None,
+            cache_size: DEFAULT_CACHE_MIB,
+            no_cache: false,
+            fault_injection: false,
+            harden: false,
+            max_header_bytes: 8192,
+            throttle: None,
+            latency: None,
+            strict_http: false,
+            log: None,
+            log_file: None,
+            log_exclude: Vec::new(),
+            log_sample: Vec::new(),
+            log_level: None,
+            verbose: 0,
+            events_token: None,
+            config: None,
+            export_config: None,
+        }
+    }
+}
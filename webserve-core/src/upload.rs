@@ -0,0 +1,430 @@
+//! `--upload`: `PUT`, multipart `POST`, `DELETE` and `MKCOL` writes beneath the served root.
+//!
+//! `PUT /dir/file.txt` stores the request body, creating missing directories; `POST /dir/`
+//! with `multipart/form-data` stores every file part in `dir`, which is what the form on
+//! directory listings sends. `DELETE /path` removes a file or directory tree and
+//! `MKCOL /dir/new` creates a directory, backing the listing's delete buttons and new-folder
+//! form. Every change is published like a watcher event, so the dashboard and live-reload
+//! clients see it. A file is written to a hidden `.tmp` sibling and renamed into
+//! place, so neither readers nor the watcher see half an upload. Bodies over
+//! `--max-upload-size` MiB, counting every file of a form together, are cut off with `413`;
+//! targets that would leave the root, also through a symlink, get `403`.
+//!
+//! A multipart `POST` needs no CORS preflight, so any page the user visits could submit one.
+//! Writes a browser marks as coming from another site (`Sec-Fetch-Site`, or an `Origin`
+//! other than the `Host`) get `403`; clients such as `curl` send neither and are served.
+
+use actix_multipart::Multipart;
+use actix_web::http::header;
+use actix_web::web::{self, Bytes};
+use actix_web::{HttpRequest, HttpResponse};
+use futures_util::{Stream, StreamExt};
+use notify::event::{CreateKind, DataChange, ModifyKind, RemoveKind};
+use notify::{Event, EventKind};
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
+
+use crate::path::{
+    encode_url_path, join_serve_path, normalize_url_path, percent_decode_path, url_path_under,
+};
+use crate::AppState;
+
+/// Default `--max-upload-size`, in MiB.
+pub const DEFAULT_MAX_UPLOAD_MIB: u64 = 100;
+
+/// Why an upload was refused.
+#[derive(Debug)]
+pub enum UploadError {
+    /// The target is outside the served root.
+    Forbidden,
+    /// The directory of a form upload does not exist.
+    NotFound,
+    /// The target is a directory.
+    Conflict,
+    TooLarge,
+    BadRequest(String),
+    Io(io::Error),
+}
+
+impl UploadError {
+    pub(crate) fn response(&self) -> HttpResponse {
+        match self {
+            UploadError::Forbidden => HttpResponse::Forbidden().finish(),
+            UploadError::NotFound => HttpResponse::NotFound().finish(),
+            UploadError::Conflict => HttpResponse::Conflict().body("target is a directory"),
+            UploadError::TooLarge => HttpResponse::PayloadTooLarge().body("upload too large"),
+            UploadError::BadRequest(msg) => HttpResponse::BadRequest().body(msg.clone()),
+            UploadError::Io(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for UploadError {
+    fn from(err: io::Error) -> Self {
+        UploadError::Io(err)
+    }
+}
+
+/// True when `path`, or its closest existing ancestor, resolves to somewhere inside `root`.
+pub(crate) fn within_root(root: &Path, path: &Path) -> bool {
+    let Ok(root) = root.canonicalize() else {
+        return false;
+    };
+    let mut existing = path;
+    while existing.symlink_metadata().is_err() {
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => return false,
+        }
+    }
+    existing
+        .canonicalize()
+        .is_ok_and(|resolved| resolved.starts_with(&root))
+}
+
+/// File path for a `PUT` to `url_path`; `..`, the root itself and symlink escapes are refused.
+pub fn upload_target(root: &Path, url_path: &str) -> Result<PathBuf, UploadError> {
+    let normalized = percent_decode_path(url_path)
+        .and_then(|p| normalize_url_path(&p))
+        .ok_or(UploadError::Forbidden)?;
+    if normalized == "/" {
+        return Err(UploadError::Conflict);
+    }
+    let target = join_serve_path(root, &normalized).ok_or(UploadError::Forbidden)?;
+    if !within_root(root, &target) {
+        return Err(UploadError::Forbidden);
+    }
+    if target.is_dir() {
+        return Err(UploadError::Conflict);
+    }
+    Ok(target)
+}
+
+/// Normalized URL path and file path for a `DELETE`, `MKCOL` or WebDAV write to `url_path`.
+pub(crate) fn writable_path(root: &Path, url_path: &str) -> Result<(String, PathBuf), UploadError> {
+    let normalized = percent_decode_path(url_path)
+        .and_then(|p| normalize_url_path(&p))
+        .ok_or(UploadError::Forbidden)?;
+    let path = join_serve_path(root, &normalized).ok_or(UploadError::Forbidden)?;
+    if !within_root(root, &path) {
+        return Err(UploadError::Forbidden);
+    }
+    Ok((normalized, path))
+}
+
+/// Removes a file, symlink or whole directory tree.
+pub(crate) fn remove(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Directory a multipart `POST` to `url_path` writes into; it must already exist.
+fn upload_dir(root: &Path, url_path: &str) -> Result<PathBuf, UploadError> {
+    let dir = percent_decode_path(url_path)
+        .and_then(|p| normalize_url_path(&p))
+        .and_then(|p| join_serve_path(root, &p))
+        .ok_or(UploadError::Forbidden)?;
+    if !dir.is_dir() {
+        return Err(UploadError::NotFound);
+    }
+    if !within_root(root, &dir) {
+        return Err(UploadError::Forbidden);
+    }
+    Ok(dir)
+}
+
+/// Last segment of a form file name; `None` for empty, `.` and `..`.
+fn file_name(name: &str) -> Option<&str> {
+    let name = name.rsplit(['/', '\\']).next()?.trim();
+    (!name.is_empty() && name != "." && name != "..").then_some(name)
+}
+
+/// True when a browser says the request comes from another site: `Sec-Fetch-Site` other
+/// than `same-origin` or `none`, or an `Origin` whose authority is not the `Host`.
+pub(crate) fn cross_site(req: &HttpRequest) -> bool {
+    let other_site = req
+        .headers()
+        .get("sec-fetch-site")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|site| site != "same-origin" && site != "none");
+    other_site || origin_matches_host(req) == Some(false)
+}
+
+/// Whether the request's `Origin` names its `Host`; `None` when either is missing.
+pub(crate) fn origin_matches_host(req: &HttpRequest) -> Option<bool> {
+    let origin = req.headers().get(header::ORIGIN)?.to_str().ok()?;
+    let host = req.headers().get(header::HOST)?.to_str().ok()?;
+    Some(
+        origin
+            .split_once("://")
+            .is_some_and(|(_, authority)| authority.eq_ignore_ascii_case(host)),
+    )
+}
+
+/// `403` for a write from another site, see [`cross_site`].
+fn refuse_cross_site(req: &HttpRequest) -> Option<HttpResponse> {
+    if !cross_site(req) {
+        return None;
+    }
+    warn!(
+        peer = ?req.peer_addr(),
+        method = %req.method(),
+        path = req.path(),
+        "upload: refused cross-site request"
+    );
+    Some(HttpResponse::Forbidden().body("cross-site write refused"))
+}
+
+/// Streams `body` into `target` through a temporary sibling; true when `target` is new, and
+/// the number of bytes written.
+async fn store<S, E>(target: &Path, mut body: S, max_bytes: u64) -> Result<(bool, u64), UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp = target.with_file_name(format!(
+        ".{}.{}.upload.tmp",
+        name,
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = async {
+        let mut file = tokio::fs::File::create(&temp).await?;
+        let mut written = 0u64;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| UploadError::BadRequest(e.to_string()))?;
+            written += chunk.len() as u64;
+            if written > max_bytes {
+                return Err(UploadError::TooLarge);
+            }
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        let created = !target.exists();
+        tokio::fs::rename(&temp, target).await?;
+        Ok((created, written))
+    }
+    .await;
+    match result {
+        Ok((created, written)) => {
+            info!(path = %target.display(), bytes = written, "upload stored");
+            Ok((created, written))
+        }
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&temp).await;
+            Err(err)
+        }
+    }
+}
+
+/// `Content-Length` of the request, when sent.
+fn content_length(req: &HttpRequest) -> Option<u64> {
+    req.headers()
+        .get(header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// `PUT /path/file` under `--upload` or `--webdav`: `201` for a new file, `204` for a replaced
+/// one.
+pub async fn upload_put(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Payload,
+) -> HttpResponse {
+    let settings = data.settings.load();
+    if !settings.upload && !settings.webdav {
+        return HttpResponse::MethodNotAllowed().finish();
+    }
+    if let Some(refused) = refuse_cross_site(&req) {
+        return refused;
+    }
+    let max_bytes = settings.max_upload_bytes;
+    if content_length(&req).is_some_and(|len| len > max_bytes) {
+        return UploadError::TooLarge.response();
+    }
+    let root = data.static_dir.as_path();
+    let result = async {
+        let target = upload_target(root, req.path())?;
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let (created, _) = store(&target, body, max_bytes).await?;
+        Ok::<_, UploadError>((target, created))
+    }
+    .await;
+    match result {
+        Ok((target, true)) => {
+            let location = encode_url_path(&url_path_under(root, &target).unwrap_or_default());
+            data.publish_mutation(Event::new(EventKind::Create(CreateKind::File)).add_path(target));
+            HttpResponse::Created()
+                .insert_header((header::LOCATION, location))
+                .finish()
+        }
+        Ok((target, false)) => {
+            data.publish_mutation(
+                Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+                    .add_path(target),
+            );
+            HttpResponse::NoContent().finish()
+        }
+        Err(err) => {
+            debug!(path = %req.path(), ?err, "upload refused");
+            err.response()
+        }
+    }
+}
+
+/// `POST /dir/` with `multipart/form-data` under `--upload`: stores each file part in `dir`,
+/// all of them together within `--max-upload-size`.
+///
+/// Browsers (`Accept: text/html`) are sent back to the listing with `303`; other clients get
+/// `201` and the uploaded URL paths as JSON.
+pub async fn upload_form(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Payload,
+) -> HttpResponse {
+    let settings = data.settings.load();
+    if !settings.upload {
+        return HttpResponse::MethodNotAllowed().finish();
+    }
+    if let Some(refused) = refuse_cross_site(&req) {
+        return refused;
+    }
+    let max_bytes = settings.max_upload_bytes;
+    let root = data.static_dir.as_path();
+    let mut listing = String::from("/");
+    let result = async {
+        let dir = upload_dir(root, req.path())?;
+        if let Some(path) = url_path_under(root, &dir).filter(|p| p != "/") {
+            listing = format!("{}/", encode_url_path(&path));
+        }
+        let mut form = Multipart::new(req.headers(), body);
+        let mut uploaded = Vec::new();
+        let mut total = 0u64;
+        while let Some(field) = form.next().await {
+            let field = field.map_err(|e| UploadError::BadRequest(e.to_string()))?;
+            let name = field
+                .content_disposition()
+                .and_then(|cd| cd.get_filename())
+                .and_then(file_name)
+                .map(str::to_string);
+            // Plain form fields and empty file inputs carry no file name
+            let Some(name) = name else {
+                continue;
+            };
+            let target = dir.join(&name);
+            if target.is_dir() {
+                return Err(UploadError::Conflict);
+            }
+            let (created, written) = store(&target, field, max_bytes - total).await?;
+            total += written;
+            uploaded.extend(url_path_under(root, &target));
+            let kind = if created {
+                EventKind::Create(CreateKind::File)
+            } else {
+                EventKind::Modify(ModifyKind::Data(DataChange::Content))
+            };
+            data.publish_mutation(Event::new(kind).add_path(target));
+        }
+        if uploaded.is_empty() {
+            return Err(UploadError::BadRequest("no file in the form".to_string()));
+        }
+        Ok(uploaded)
+    }
+    .await;
+    let uploaded = match result {
+        Ok(uploaded) => uploaded,
+        Err(err) => {
+            debug!(path = %req.path(), ?err, "upload refused");
+            return err.response();
+        }
+    };
+    let wants_html = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    if wants_html {
+        return HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, listing))
+            .finish();
+    }
+    HttpResponse::Created().json(serde_json::json!({ "uploaded": uploaded }))
+}
+
+/// `DELETE /path` under `--upload` or `--webdav`: removes a file or a whole directory tree;
+/// the root itself is kept.
+pub async fn delete_path(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    let settings = data.settings.load();
+    if !settings.upload && !settings.webdav {
+        return HttpResponse::MethodNotAllowed().finish();
+    }
+    if let Some(refused) = refuse_cross_site(&req) {
+        return refused;
+    }
+    let result = writable_path(&data.static_dir, req.path()).and_then(|(url_path, path)| {
+        if url_path == "/" {
+            return Err(UploadError::Forbidden);
+        }
+        if path.symlink_metadata().is_err() {
+            return Err(UploadError::NotFound);
+        }
+        remove(&path)?;
+        Ok(path)
+    });
+    match result {
+        Ok(path) => {
+            info!(path = %path.display(), "deleted");
+            data.publish_mutation(Event::new(EventKind::Remove(RemoveKind::Any)).add_path(path));
+            HttpResponse::NoContent().finish()
+        }
+        Err(err) => {
+            debug!(path = %req.path(), ?err, "delete refused");
+            err.response()
+        }
+    }
+}
+
+/// `MKCOL /dir/new` under `--upload` or `--webdav`: creates one directory, whose parent must
+/// exist (`409`); an existing path gets `405`, as WebDAV asks.
+pub async fn make_dir(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    let settings = data.settings.load();
+    if !settings.upload && !settings.webdav {
+        return HttpResponse::MethodNotAllowed().finish();
+    }
+    if let Some(refused) = refuse_cross_site(&req) {
+        return refused;
+    }
+    let (url_path, path) = match writable_path(&data.static_dir, req.path()) {
+        Ok(found) => found,
+        Err(err) => return err.response(),
+    };
+    if url_path == "/" || path.symlink_metadata().is_ok() {
+        return HttpResponse::MethodNotAllowed().finish();
+    }
+    if !path.parent().is_some_and(Path::is_dir) {
+        return HttpResponse::Conflict().body("parent directory does not exist");
+    }
+    if let Err(err) = fs::create_dir(&path) {
+        return UploadError::Io(err).response();
+    }
+    info!(path = %path.display(), "directory created");
+    data.publish_mutation(Event::new(EventKind::Create(CreateKind::Folder)).add_path(path));
+    HttpResponse::Created().finish()
+}
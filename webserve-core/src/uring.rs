@@ -0,0 +1,112 @@
+//! Experimental `--io-uring` file reads (Linux, `io-uring` cargo feature).
+//!
+//! io_uring needs its own runtime, so a dedicated thread runs `tokio-uring` and serves read
+//! jobs sent from the Actix workers. Each job opens the file once and streams it back in
+//! chunks read with `read_at`, saving the per-chunk blocking-pool hop of the default path.
+//! Whole-file `200` responses and the shared reads of [`crate::inflight`] use it when
+//! enabled; ranges still go through `NamedFile`.
+
+use bytes::Bytes;
+use futures::Stream;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Chunk size of uring reads.
+const URING_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks buffered per read before the uring task waits for the client.
+const URING_STREAM_DEPTH: usize = 4;
+
+struct ReadJob {
+    path: PathBuf,
+    chunks: mpsc::Sender<io::Result<Bytes>>,
+}
+
+/// Handle to the io_uring reader thread; cheap to clone.
+#[derive(Clone)]
+pub struct UringReader {
+    jobs: mpsc::UnboundedSender<ReadJob>,
+}
+
+impl UringReader {
+    /// Starts the reader thread; fails when the kernel or sandbox refuses io_uring.
+    pub fn start() -> io::Result<UringReader> {
+        let (jobs, mut queue) = mpsc::unbounded_channel::<ReadJob>();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel::<io::Result<()>>();
+        std::thread::Builder::new()
+            .name("webserve-uring".into())
+            .spawn(move || {
+                let started = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    tokio_uring::start(async {
+                        let _ = ready_tx.send(Ok(()));
+                        while let Some(job) = queue.recv().await {
+                            tokio_uring::spawn(read_job(job));
+                        }
+                    })
+                }));
+                if started.is_err() {
+                    let _ = ready_tx.send(Err(io::Error::other("io_uring runtime failed")));
+                }
+            })?;
+        ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("io_uring thread exited")))?;
+        Ok(UringReader { jobs })
+    }
+
+    /// Streams the contents of `path` in chunks read through io_uring.
+    pub fn stream(&self, path: &Path) -> impl Stream<Item = io::Result<Bytes>> {
+        let (chunks, rx) = mpsc::channel(URING_STREAM_DEPTH);
+        let job = ReadJob {
+            path: path.to_path_buf(),
+            chunks,
+        };
+        if let Err(mpsc::error::SendError(job)) = self.jobs.send(job) {
+            let _ = job
+                .chunks
+                .try_send(Err(io::Error::other("io_uring reader stopped")));
+        }
+        ReceiverStream::new(rx)
+    }
+
+    /// Reads all of `path` through io_uring.
+    pub async fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        use futures::StreamExt;
+        let mut body = Vec::new();
+        let mut stream = std::pin::pin!(self.stream(path));
+        while let Some(chunk) = stream.next().await {
+            body.extend_from_slice(&chunk?);
+        }
+        Ok(body)
+    }
+}
+
+async fn read_job(job: ReadJob) {
+    let file = match tokio_uring::fs::File::open(&job.path).await {
+        Ok(f) => f,
+        Err(e) => {
+            let _ = job.chunks.send(Err(e)).await;
+            return;
+        }
+    };
+    let mut pos = 0u64;
+    loop {
+        let (res, mut buf) = file.read_at(vec![0u8; URING_CHUNK_SIZE], pos).await;
+        let n = match res {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                let _ = job.chunks.send(Err(e)).await;
+                break;
+            }
+        };
+        buf.truncate(n);
+        pos += n as u64;
+        if job.chunks.send(Ok(Bytes::from(buf))).await.is_err() {
+            break; // client went away
+        }
+    }
+    let _ = file.close().await;
+}
@@ -0,0 +1,75 @@
+//! Bounded directory traversal shared by features that look at the whole tree.
+//!
+//! `--max-depth` and `--max-entries` cap how far and how much is read, so pointing
+//! webserve at a huge monorepo cannot keep the disk busy for minutes. Both default to
+//! unlimited. Symlinked directories are never followed.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use crate::ServeOptions;
+
+/// Traversal limits; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalkLimits {
+    /// Directory levels below the root to descend into (`1` = the root's entries only).
+    pub max_depth: Option<usize>,
+    /// Files and directories visited before stopping.
+    pub max_entries: Option<usize>,
+}
+
+impl WalkLimits {
+    pub fn from_options(options: &ServeOptions) -> WalkLimits {
+        WalkLimits {
+            max_depth: options.max_depth,
+            max_entries: options.max_entries,
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_depth.is_none() && self.max_entries.is_none()
+    }
+}
+
+/// What a bounded walk found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Walk {
+    /// Directories whose entries were read, root first, breadth-first.
+    pub dirs: Vec<PathBuf>,
+    pub files: Vec<PathBuf>,
+    /// True when a limit cut the walk short.
+    pub truncated: bool,
+}
+
+/// Breadth-first walk of `root` within `limits`; unreadable directories are skipped.
+pub fn walk(root: &Path, limits: WalkLimits) -> Walk {
+    let mut out = Walk::default();
+    let mut entries = 0usize;
+    let mut queue = VecDeque::from([(root.to_path_buf(), 0usize)]);
+    while let Some((dir, depth)) = queue.pop_front() {
+        if limits.max_depth.is_some_and(|max| depth >= max) {
+            out.truncated = true;
+            continue;
+        }
+        let Ok(read) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        out.dirs.push(dir);
+        for entry in read.flatten() {
+            if limits.max_entries.is_some_and(|max| entries >= max) {
+                out.truncated = true;
+                return out;
+            }
+            entries += 1;
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                queue.push_back((entry.path(), depth + 1));
+            } else {
+                out.files.push(entry.path());
+            }
+        }
+    }
+    out
+}
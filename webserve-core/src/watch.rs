@@ -0,0 +1,189 @@
+//! Static-directory watching for `--watch`, with a polling fallback.
+//!
+//! `--watch-path src` adds directories outside the served root (sources of a build that
+//! writes into it); they share one watcher, so one handler debounces events from all roots.
+//!
+//! Native watchers (inotify on Linux) need one watch per directory and fail on big trees
+//! once `fs.inotify.max_user_watches` is exhausted. When that happens webserve logs how to
+//! raise the limit and keeps going with a [`PollWatcher`] instead of exiting.
+//!
+//! Raw events go through [`coalesce`]: editor temp/backup files are dropped and a burst of
+//! events (an atomic save is a write to a temp file plus a rename) becomes one batch,
+//! delivered once the tree has been quiet for [`WATCH_DEBOUNCE`].
+
+use notify::event::{AccessKind, AccessMode};
+use notify::{Config, Event, EventHandler, EventKind, PollWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, trace, warn};
+
+use crate::walk::{walk, WalkLimits};
+
+/// Scan interval of the polling fallback.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Quiet period after the last event before a batch is delivered.
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Longest a batch is held while events keep arriving (e.g. a long build).
+pub const MAX_BATCH_WAIT: Duration = Duration::from_secs(2);
+
+/// Printed when the OS refuses more watches.
+pub const WATCH_LIMIT_HINT: &str =
+    "raise it with `sudo sysctl fs.inotify.max_user_watches=524288` \
+(persist in /etc/sysctl.d/), or narrow the tree with --dir / --max-depth";
+
+/// True when `err` means the OS watch limit (or inotify instance limit) was hit.
+pub fn is_watch_limit(err: &notify::Error) -> bool {
+    match err.kind {
+        notify::ErrorKind::MaxFilesWatch => true,
+        // ENOSPC: out of watches; EMFILE: out of inotify instances
+        notify::ErrorKind::Io(ref e) => matches!(e.raw_os_error(), Some(28) | Some(24)),
+        _ => false,
+    }
+}
+
+/// Registers `root` on `watcher`: recursively, or directory by directory within `limits`.
+fn register(watcher: &mut dyn Watcher, root: &Path, limits: WalkLimits) -> notify::Result<()> {
+    // A single file: its directory, so a save that replaces the file is still seen
+    if root.is_file() {
+        let dir = root.parent().unwrap_or(root);
+        return watcher.watch(dir, RecursiveMode::NonRecursive);
+    }
+    if limits.is_unlimited() {
+        return watcher.watch(root, RecursiveMode::Recursive);
+    }
+    // Bounded: watch each directory the walk reached; new subdirectories need a restart
+    let tree = walk(root, limits);
+    for dir in &tree.dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    }
+    info!("Watching {} director(ies) within limits", tree.dirs.len());
+    if tree.truncated {
+        warn!("--max-depth/--max-entries reached; deeper changes are not watched");
+    }
+    Ok(())
+}
+
+/// Watches `root` with the native watcher, falling back to polling when OS limits are hit.
+///
+/// `make_handler` is called once per watcher attempt, so it must hand out a fresh handler.
+pub fn watch_tree<F, H>(
+    root: &Path,
+    limits: WalkLimits,
+    make_handler: F,
+) -> Result<Box<dyn Watcher + Send>, String>
+where
+    F: Fn() -> H,
+    H: EventHandler,
+{
+    watch_trees(&[root], limits, make_handler)
+}
+
+/// [`watch_tree`] over several roots, all reporting to the same handler.
+pub fn watch_trees<F, H>(
+    roots: &[&Path],
+    limits: WalkLimits,
+    make_handler: F,
+) -> Result<Box<dyn Watcher + Send>, String>
+where
+    F: Fn() -> H,
+    H: EventHandler,
+{
+    if roots.is_empty() {
+        return Err("nothing to watch".to_string());
+    }
+    let register_all = |watcher: &mut dyn Watcher| -> Result<(), (&Path, notify::Error)> {
+        for root in roots {
+            register(watcher, root, limits).map_err(|e| (*root, e))?;
+        }
+        Ok(())
+    };
+    let native = notify::recommended_watcher(make_handler())
+        .map_err(|e| (roots[0], e))
+        .and_then(|mut watcher| {
+            register_all(&mut watcher)?;
+            Ok(watcher)
+        });
+    match native {
+        Ok(watcher) => Ok(Box::new(watcher)),
+        Err((_, e)) if is_watch_limit(&e) => {
+            warn!("OS file watch limit reached ({}); {}", e, WATCH_LIMIT_HINT);
+            warn!(
+                "falling back to polling every {}s",
+                POLL_INTERVAL.as_secs_f32()
+            );
+            let config = Config::default().with_poll_interval(POLL_INTERVAL);
+            let mut watcher = PollWatcher::new(make_handler(), config)
+                .map_err(|e| format!("polling watcher unavailable: {}", e))?;
+            register_all(&mut watcher)
+                .map_err(|(root, e)| format!("cannot watch {}: {}", root.display(), e))?;
+            Ok(Box::new(watcher))
+        }
+        Err((root, e)) => Err(format!("cannot watch {}: {}", root.display(), e)),
+    }
+}
+
+/// True for editor swap, backup and atomic-save temp files (vim, emacs, JetBrains, gedit...).
+pub fn is_editor_temp(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    name.ends_with('~')
+        || name == "4913"
+        || name.starts_with(".#")
+        || (name.starts_with('#') && name.ends_with('#'))
+        || name.starts_with(".goutputstream-")
+        || name.ends_with("___jb_tmp___")
+        || name.ends_with("___jb_old___")
+        || [".swp", ".swx", ".swo", ".tmp", ".crswap"]
+            .iter()
+            .any(|ext| name.ends_with(ext))
+}
+
+/// True when `event` can change what is served: reads and temp-file-only events are not.
+pub fn is_relevant(event: &Event) -> bool {
+    let kind_matters = match event.kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        EventKind::Access(_) => false,
+        _ => true,
+    };
+    kind_matters && event.paths.iter().any(|p| !is_editor_temp(p))
+}
+
+/// Wraps `on_batch` in a watcher handler that drops irrelevant events and groups the rest.
+///
+/// A batch is delivered once no relevant event arrived for `quiet`, or after
+/// [`MAX_BATCH_WAIT`] at most, so a save done as write-temp-then-rename yields one call.
+pub fn coalesce<F>(quiet: Duration, mut on_batch: F) -> impl FnMut(notify::Result<Event>) + Send
+where
+    F: FnMut(Vec<Event>) + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel::<Event>();
+    thread::spawn(move || {
+        while let Ok(first) = rx.recv() {
+            let started = Instant::now();
+            let mut batch = vec![first];
+            loop {
+                let remaining = MAX_BATCH_WAIT.saturating_sub(started.elapsed());
+                if remaining.is_zero() {
+                    break;
+                }
+                match rx.recv_timeout(quiet.min(remaining)) {
+                    Ok(event) => batch.push(event),
+                    Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            on_batch(batch);
+        }
+    });
+    move |res: notify::Result<Event>| match res {
+        Ok(event) if is_relevant(&event) => {
+            let _ = tx.send(event);
+        }
+        Ok(event) => trace!(kind = ?event.kind, paths = ?event.paths, "ignored watch event"),
+        Err(e) => warn!("watch error: {}", e),
+    }
+}
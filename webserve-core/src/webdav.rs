@@ -0,0 +1,326 @@
+//! `--webdav`: WebDAV (RFC 4918) so the served root mounts read/write in Finder, Explorer,
+//! davfs2 or rclone.
+//!
+//! `GET`, `PUT`, `DELETE` and `MKCOL` stay with [`serve_file`] and the [`upload`](crate::upload)
+//! handlers; this module answers the remaining methods. `PROPFIND` supports `Depth: 0` and `1` and refuses
+//! `infinity` with `403`, as the RFC allows. Locks are advisory: `LOCK` hands out a token so
+//! clients that insist on locking before writing (Finder, Office) mount read/write, but nothing
+//! is enforced, and `PROPPATCH` reports every property as read-only. Paths that would leave
+//! the root, also through a symlink, get `403` as with uploads.
+
+use actix_web::http::header::{self, HttpDate};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use notify::event::{CreateKind, ModifyKind, RenameMode};
+use notify::{Event, EventKind};
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+use crate::path::encode_url_path;
+use crate::serve::{html_escape, serve_file};
+use crate::upload::{remove, writable_path, UploadError};
+use crate::AppState;
+
+/// Methods advertised in `Allow` under `--webdav`.
+pub const DAV_METHODS: &str =
+    "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+
+/// Lifetime reported for `LOCK` tokens, in seconds.
+const LOCK_TIMEOUT_SECS: u64 = 3600;
+
+const XML_HEADER: &str = r#"<?xml version="1.0" encoding="utf-8"?>"#;
+
+/// Path of the `Destination` header, which clients send as an absolute URL or path.
+fn destination(req: &HttpRequest) -> Option<String> {
+    let dest = req.headers().get("destination")?.to_str().ok()?;
+    let path = match dest.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/')?..],
+        None => dest,
+    };
+    Some(path.split(['?', '#']).next().unwrap_or("").to_string())
+}
+
+/// `Overwrite: F` forbids replacing an existing destination; anything else allows it.
+fn overwrite(req: &HttpRequest) -> bool {
+    req.headers()
+        .get("overwrite")
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|v| !v.trim().eq_ignore_ascii_case("f"))
+}
+
+fn multistatus(body: String) -> HttpResponse {
+    HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(body)
+}
+
+/// `href` of a resource; collections end in `/`.
+fn href(url_path: &str, is_dir: bool) -> String {
+    let mut href = encode_url_path(url_path);
+    if is_dir && !href.ends_with('/') {
+        href.push('/');
+    }
+    href
+}
+
+/// One `<D:response>` with the live properties of a file or directory.
+fn push_response(out: &mut String, url_path: &str, meta: &Metadata) {
+    let name = url_path.rsplit('/').next().unwrap_or("");
+    out.push_str("<D:response><D:href>");
+    out.push_str(&html_escape(&href(url_path, meta.is_dir())));
+    out.push_str("</D:href><D:propstat><D:prop>");
+    out.push_str(&format!(
+        "<D:displayname>{}</D:displayname>",
+        html_escape(name)
+    ));
+    if meta.is_dir() {
+        out.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        let mime = mime_guess::from_path(name).first_or_octet_stream();
+        out.push_str(&format!(
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>\
+             <D:getcontenttype>{}</D:getcontenttype>",
+            meta.len(),
+            html_escape(mime.essence_str())
+        ));
+    }
+    if let Ok(modified) = meta.modified() {
+        out.push_str(&format!(
+            "<D:getlastmodified>{}</D:getlastmodified>",
+            HttpDate::from(modified)
+        ));
+    }
+    out.push_str(
+        "<D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope>\
+         <D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>",
+    );
+    out.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>");
+}
+
+/// `PROPFIND`: properties of the target and, with `Depth: 1`, of its children.
+fn propfind(
+    req: &HttpRequest,
+    root: &Path,
+    max_entries: Option<usize>,
+) -> Result<HttpResponse, UploadError> {
+    let (url_path, path) = writable_path(root, req.path())?;
+    let meta = fs::metadata(&path).map_err(|_| UploadError::NotFound)?;
+    let depth = req
+        .headers()
+        .get("depth")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("infinity");
+    let children = match depth.trim() {
+        "0" => false,
+        "1" => true,
+        _ => {
+            let body = format!(
+                r#"{}<D:error xmlns:D="DAV:"><D:propfind-finite-depth/></D:error>"#,
+                XML_HEADER
+            );
+            return Ok(HttpResponse::Forbidden()
+                .content_type("application/xml; charset=utf-8")
+                .body(body));
+        }
+    };
+    let mut body = format!(r#"{}<D:multistatus xmlns:D="DAV:">"#, XML_HEADER);
+    push_response(&mut body, &url_path, &meta);
+    if children && meta.is_dir() {
+        let mut entries: Vec<_> = fs::read_dir(&path)?.flatten().collect();
+        entries.sort_by_key(|e| e.file_name());
+        let base = url_path.trim_end_matches('/');
+        for entry in entries.into_iter().take(max_entries.unwrap_or(usize::MAX)) {
+            // Broken symlinks have nothing to describe
+            let Ok(meta) = entry.path().metadata() else {
+                continue;
+            };
+            let name = entry.file_name();
+            push_response(
+                &mut body,
+                &format!("{}/{}", base, name.to_string_lossy()),
+                &meta,
+            );
+        }
+    }
+    body.push_str("</D:multistatus>");
+    Ok(multistatus(body))
+}
+
+/// `PROPPATCH`: dead properties are not stored, so every change is refused in a `207`.
+fn proppatch(req: &HttpRequest, root: &Path) -> Result<HttpResponse, UploadError> {
+    let (url_path, path) = writable_path(root, req.path())?;
+    let meta = fs::metadata(&path).map_err(|_| UploadError::NotFound)?;
+    Ok(multistatus(format!(
+        r#"{}<D:multistatus xmlns:D="DAV:"><D:response><D:href>{}</D:href><D:propstat><D:prop/><D:status>HTTP/1.1 403 Forbidden</D:status></D:propstat></D:response></D:multistatus>"#,
+        XML_HEADER,
+        html_escape(&href(&url_path, meta.is_dir()))
+    )))
+}
+
+/// Copies a file or directory tree; symlinked directories and dangling links are skipped.
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    if from.symlink_metadata()?.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let (from, to) = (entry.path(), to.join(entry.file_name()));
+            if entry.file_type()?.is_dir() || from.is_file() {
+                copy_tree(&from, &to)?;
+            }
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// `COPY` and `MOVE` to the `Destination` header: `201` when created, `204` when replaced.
+async fn transfer(
+    req: &HttpRequest,
+    data: &AppState,
+    moving: bool,
+) -> Result<HttpResponse, UploadError> {
+    let root = data.static_dir.as_path();
+    let (from_url, from) = writable_path(root, req.path())?;
+    let dest = destination(req)
+        .ok_or_else(|| UploadError::BadRequest("missing Destination header".to_string()))?;
+    let (to_url, to) = writable_path(root, &dest)?;
+    if from_url == "/" || to_url == "/" {
+        return Err(UploadError::Forbidden);
+    }
+    if from.symlink_metadata().is_err() {
+        return Err(UploadError::NotFound);
+    }
+    if to_url == from_url || to_url.starts_with(&format!("{}/", from_url)) {
+        return Err(UploadError::Forbidden);
+    }
+    if !to.parent().is_some_and(Path::is_dir) {
+        return Ok(HttpResponse::Conflict().body("destination directory does not exist"));
+    }
+    let replaced = to.symlink_metadata().is_ok();
+    if replaced {
+        if !overwrite(req) {
+            return Ok(HttpResponse::PreconditionFailed().finish());
+        }
+        remove(&to)?;
+    }
+    let event = if moving {
+        fs::rename(&from, &to)?;
+        Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(from.clone())
+            .add_path(to.clone())
+    } else {
+        let (source, copy) = (from.clone(), to.clone());
+        web::block(move || copy_tree(&source, &copy))
+            .await
+            .map_err(|e| UploadError::Io(io::Error::other(e.to_string())))??;
+        Event::new(EventKind::Create(CreateKind::Any)).add_path(to.clone())
+    };
+    info!(
+        from = %from.display(),
+        to = %to.display(),
+        "webdav {}",
+        if moving { "move" } else { "copy" }
+    );
+    data.publish_mutation(event);
+    Ok(if replaced {
+        HttpResponse::NoContent().finish()
+    } else {
+        HttpResponse::Created().finish()
+    })
+}
+
+/// Fresh `opaquelocktoken:` URI for `LOCK`.
+fn lock_token() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!(
+        "opaquelocktoken:{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        nanos >> 32,
+        (nanos >> 16) & 0xffff,
+        nanos & 0xffff,
+        std::process::id() & 0xffff,
+        n
+    )
+}
+
+/// `LOCK`: grants an advisory exclusive lock; a missing file is created empty, as RFC 4918
+/// asks, so it shows up while the client is still writing it.
+fn lock(req: &HttpRequest, data: &AppState) -> Result<HttpResponse, UploadError> {
+    let (url_path, path) = writable_path(&data.static_dir, req.path())?;
+    let mut created = false;
+    if path.symlink_metadata().is_err() {
+        if !path.parent().is_some_and(Path::is_dir) {
+            return Ok(HttpResponse::Conflict().body("parent directory does not exist"));
+        }
+        fs::File::create(&path)?;
+        data.publish_mutation(
+            Event::new(EventKind::Create(CreateKind::File)).add_path(path.clone()),
+        );
+        created = true;
+    }
+    let token = lock_token();
+    let body = format!(
+        r#"{}<D:prop xmlns:D="DAV:"><D:lockdiscovery><D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>infinity</D:depth><D:timeout>Second-{}</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock></D:lockdiscovery></D:prop>"#,
+        XML_HEADER,
+        LOCK_TIMEOUT_SECS,
+        token,
+        html_escape(&href(&url_path, path.is_dir()))
+    );
+    let mut res = if created {
+        HttpResponse::Created()
+    } else {
+        HttpResponse::Ok()
+    };
+    Ok(res
+        .insert_header(("Lock-Token", format!("<{}>", token)))
+        .content_type("application/xml; charset=utf-8")
+        .body(body))
+}
+
+/// Handles the methods without a route of their own under `--webdav`; without it they stay
+/// `404` as before.
+pub async fn webdav(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    let settings = data.settings.load();
+    if !settings.webdav {
+        return HttpResponse::NotFound().finish();
+    }
+    let root = data.static_dir.as_path();
+    let result = match req.method().as_str() {
+        "HEAD" => {
+            return match serve_file(req.clone(), data.clone()).await {
+                Ok(res) => res.respond_to(&req).map_into_boxed_body(),
+                Err(err) => err.error_response(),
+            };
+        }
+        "OPTIONS" => Ok(HttpResponse::Ok()
+            .insert_header(("DAV", "1, 2"))
+            .insert_header((header::ALLOW, DAV_METHODS))
+            .insert_header(("MS-Author-Via", "DAV"))
+            .finish()),
+        "PROPFIND" => propfind(&req, root, settings.limits.max_entries),
+        "PROPPATCH" => proppatch(&req, root),
+        "COPY" => transfer(&req, &data, false).await,
+        "MOVE" => transfer(&req, &data, true).await,
+        "LOCK" => lock(&req, &data),
+        "UNLOCK" => Ok(HttpResponse::NoContent().finish()),
+        _ => Ok(HttpResponse::MethodNotAllowed()
+            .insert_header((header::ALLOW, DAV_METHODS))
+            .finish()),
+    };
+    match result {
+        Ok(res) => res,
+        Err(err) => {
+            debug!(method = %req.method(), path = %req.path(), ?err, "webdav refused");
+            err.response()
+        }
+    }
+}